@@ -0,0 +1,90 @@
+/*
+ * Cairus - a reimplementation of the cairo graphics library in Rust
+ *
+ * Copyright © 2017 CairusOrg
+ *
+ * This library is free software; you can redistribute it and/or
+ * modify it either under the terms of the GNU Lesser General Public
+ * License version 2.1 as published by the Free Software Foundation
+ * (the "LGPL") or, at your option, under the terms of the Mozilla
+ * Public License Version 2.0 (the "MPL"). If you do not alter this
+ * notice, a recipient may use your version of this file under either
+ * the MPL or the LGPL.
+ *
+ * You should have received a copy of the LGPL along with this library
+ * in the file LICENSE-LGPL-2_1; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Suite 500, Boston, MA 02110-1335, USA
+ * You should have received a copy of the MPL along with this library
+ * in the file LICENSE-MPL-2_0
+ *
+ * The contents of this file are subject to the Mozilla Public License
+ * Version 2.0 (the "License"); you may not use this file except in
+ * compliance with the License. You may obtain a copy of the License at
+ * http://www.mozilla.org/MPL/
+ *
+ * This software is distributed on an "AS IS" basis, WITHOUT WARRANTY
+ * OF ANY KIND, either express or implied. See the LGPL or the MPL for
+ * the specific language governing rights and limitations.
+ *
+ * The Original Code is the cairus graphics library.
+ *
+ * Contributor(s):
+ *	CairusOrg
+ *
+ */
+
+//! Times the compositing loops for each operator: a row of pixels composited with
+//! `composite_span`, as solid fills are, and the same row with partial coverage through
+//! `composite_span_with_coverage`, as antialiased edges are.  The row is a mix of opaque,
+//! translucent and clear pixels, since several operators take shortcuts on some of them.
+//!
+//! Each run composites onto a fresh copy of the row, which criterion makes outside the timing.
+//!
+//! Run with `cargo bench --bench operators`.
+
+extern crate cairus;
+#[macro_use]
+extern crate criterion;
+
+use cairus::operators::{Operator, composite_span, composite_span_with_coverage};
+use cairus::Rgba;
+use criterion::{BatchSize, Criterion, Throughput};
+
+const WIDTH: usize = 4096;
+
+// Returns the destination row: opaque, translucent and clear pixels in turn
+fn row() -> Vec<Rgba> {
+    let pixels = [Rgba::new(0.9, 0.5, 0.1, 1.), Rgba::new(0.2, 0.3, 0.1, 0.4),
+                  Rgba::new(0., 0., 0., 0.)];
+    (0..WIDTH).map(|x| pixels[x % pixels.len()]).collect()
+}
+
+fn operators(criterion: &mut Criterion) {
+    let source = Rgba::new(0.1, 0.2, 0.6, 0.6);
+    let coverage: Vec<f32> = (0..WIDTH).map(|x| (x % 17) as f32 / 16.).collect();
+
+    let mut group = criterion.benchmark_group("span");
+    group.throughput(Throughput::Elements(WIDTH as u64));
+    for operator in Operator::all() {
+        group.bench_function(operator.name(), |bencher| {
+            bencher.iter_batched_ref(row, |destination| {
+                composite_span(operator, &source, destination)
+            }, BatchSize::SmallInput)
+        });
+    }
+    group.finish();
+
+    let mut group = criterion.benchmark_group("coverage");
+    group.throughput(Throughput::Elements(WIDTH as u64));
+    for operator in Operator::all() {
+        group.bench_function(operator.name(), |bencher| {
+            bencher.iter_batched_ref(row, |destination| {
+                composite_span_with_coverage(operator, &source, destination, &coverage)
+            }, BatchSize::SmallInput)
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, operators);
+criterion_main!(benches);
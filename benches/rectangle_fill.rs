@@ -0,0 +1,98 @@
+/*
+ * Cairus - a reimplementation of the cairo graphics library in Rust
+ *
+ * Copyright © 2017 CairusOrg
+ *
+ * This library is free software; you can redistribute it and/or
+ * modify it either under the terms of the GNU Lesser General Public
+ * License version 2.1 as published by the Free Software Foundation
+ * (the "LGPL") or, at your option, under the terms of the Mozilla
+ * Public License Version 2.0 (the "MPL"). If you do not alter this
+ * notice, a recipient may use your version of this file under either
+ * the MPL or the LGPL.
+ *
+ * You should have received a copy of the LGPL along with this library
+ * in the file LICENSE-LGPL-2_1; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Suite 500, Boston, MA 02110-1335, USA
+ * You should have received a copy of the MPL along with this library
+ * in the file LICENSE-MPL-2_0
+ *
+ * The contents of this file are subject to the Mozilla Public License
+ * Version 2.0 (the "License"); you may not use this file except in
+ * compliance with the License. You may obtain a copy of the License at
+ * http://www.mozilla.org/MPL/
+ *
+ * This software is distributed on an "AS IS" basis, WITHOUT WARRANTY
+ * OF ANY KIND, either express or implied. See the LGPL or the MPL for
+ * the specific language governing rights and limitations.
+ *
+ * The Original Code is the cairus graphics library.
+ *
+ * Contributor(s):
+ *	CairusOrg
+ *
+ */
+
+//! Times filling pixel-aligned rectangles with the fast path for them, against filling the same
+//! rectangles through the sweep.  The sweep is forced by giving each rectangle an extra corner in
+//! the middle of its top side, which covers exactly the same pixels.
+//!
+//! Each group tiles the surface with squares of one size and color, filled both ways.  The
+//! surface is made once, outside the timing.
+//!
+//! Run with `cargo bench --bench rectangle_fill`.
+
+extern crate cairus;
+#[macro_use]
+extern crate criterion;
+
+use cairus::context::Context;
+use cairus::surfaces::{Format, ImageSurface};
+use criterion::{Criterion, Throughput};
+
+const WIDTH: usize = 256;
+const HEIGHT: usize = 256;
+
+// Fills a `size` by `size` rectangle at (x, y), through the sweep if `swept`
+fn rectangle(context: &mut Context, x: f32, y: f32, size: f32, swept: bool) {
+    context.move_to(x, y);
+    if swept {
+        context.line_to(x + size / 2., y);
+    }
+    context.line_to(x + size, y);
+    context.line_to(x + size, y + size);
+    context.line_to(x, y + size);
+    context.close_path();
+    context.fill();
+}
+
+// Tiles `surface` with as many `size` by `size` squares as fit, through the sweep if `swept`
+fn fill_squares(surface: &mut ImageSurface, size: f32, alpha: f32, swept: bool) {
+    let mut context = Context::create(surface);
+    context.set_source_rgba(0.2, 0.4, 0.8, alpha);
+    let count = (WIDTH as f32 / size).floor() as usize;
+    for index in 0..count * count {
+        let (column, row) = ((index % count) as f32, (index / count) as f32);
+        rectangle(&mut context, column * size, row * size, size, swept);
+    }
+}
+
+fn rectangle_fill(criterion: &mut Criterion) {
+    for &(size, alpha) in [(200., 1.), (200., 0.5), (8., 1.)].iter() {
+        let mut surface = ImageSurface::create_for_format(Format::ARGB32, WIDTH, HEIGHT);
+        let count = (WIDTH as f32 / size).floor() as u64;
+
+        let mut group = criterion.benchmark_group(format!("{}px squares, alpha {}", size, alpha));
+        group.throughput(Throughput::Elements(count * count));
+        group.bench_function("sweep", |bencher| {
+            bencher.iter(|| fill_squares(&mut surface, size, alpha, true))
+        });
+        group.bench_function("fast path", |bencher| {
+            bencher.iter(|| fill_squares(&mut surface, size, alpha, false))
+        });
+        group.finish();
+    }
+}
+
+criterion_group!(benches, rectangle_fill);
+criterion_main!(benches);
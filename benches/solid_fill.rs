@@ -0,0 +1,102 @@
+/*
+ * Cairus - a reimplementation of the cairo graphics library in Rust
+ *
+ * Copyright © 2017 CairusOrg
+ *
+ * This library is free software; you can redistribute it and/or
+ * modify it either under the terms of the GNU Lesser General Public
+ * License version 2.1 as published by the Free Software Foundation
+ * (the "LGPL") or, at your option, under the terms of the Mozilla
+ * Public License Version 2.0 (the "MPL"). If you do not alter this
+ * notice, a recipient may use your version of this file under either
+ * the MPL or the LGPL.
+ *
+ * You should have received a copy of the LGPL along with this library
+ * in the file LICENSE-LGPL-2_1; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Suite 500, Boston, MA 02110-1335, USA
+ * You should have received a copy of the MPL along with this library
+ * in the file LICENSE-MPL-2_0
+ *
+ * The contents of this file are subject to the Mozilla Public License
+ * Version 2.0 (the "License"); you may not use this file except in
+ * compliance with the License. You may obtain a copy of the License at
+ * http://www.mozilla.org/MPL/
+ *
+ * This software is distributed on an "AS IS" basis, WITHOUT WARRANTY
+ * OF ANY KIND, either express or implied. See the LGPL or the MPL for
+ * the specific language governing rights and limitations.
+ *
+ * The Original Code is the cairus graphics library.
+ *
+ * Contributor(s):
+ *	CairusOrg
+ *
+ */
+
+//! Times compositing a solid color onto a surface with the fast paths for solid fills, against
+//! blending it onto each pixel in turn as the rasterizers used to.  The surface is kept small
+//! enough to stay in cache, so that the compositing is timed rather than the memory.  An opaque
+//! color is filled outright, and a translucent one goes through `over_span`.
+//!
+//! Each group composites one color three ways: blended per pixel, with `composite_span`, and
+//! painted through a context.  The surface is made once, outside the timing.
+//!
+//! Run with `cargo bench --bench solid_fill`.
+
+extern crate cairus;
+#[macro_use]
+extern crate criterion;
+
+use cairus::context::Context;
+use cairus::operators::{Operator, composite_span, composite_with_coverage, operator_over};
+use cairus::surfaces::{Format, ImageSurface};
+use cairus::Rgba;
+use criterion::{Criterion, Throughput};
+
+const WIDTH: usize = 256;
+const HEIGHT: usize = 256;
+
+// Returns a pixel of the color to composite, as the context would hand it to the compositor
+fn swatch(alpha: f32) -> Rgba {
+    let mut swatch = ImageSurface::create_for_format(Format::ARGB32, 1, 1);
+    {
+        let mut context = Context::create(&mut swatch);
+        context.set_operator(Operator::Source);
+        context.set_source_rgba(0.2, 0.4, 0.8, alpha);
+        context.paint();
+    }
+    swatch.get_pixel(0, 0).unwrap()
+}
+
+fn solid_fill(criterion: &mut Criterion) {
+    for &alpha in [1., 0.5].iter() {
+        let color = swatch(alpha);
+        let mut surface = ImageSurface::create_for_format(Format::ARGB32, WIDTH, HEIGHT);
+
+        let mut group = criterion.benchmark_group(format!("alpha {}", alpha));
+        group.throughput(Throughput::Elements((WIDTH * HEIGHT) as u64));
+        group.bench_function("per pixel", |bencher| {
+            bencher.iter(|| {
+                for pixel in surface.iter_mut() {
+                    composite_with_coverage(operator_over, &color, pixel, 1.);
+                }
+            })
+        });
+        group.bench_function("composite_span", |bencher| {
+            bencher.iter(|| {
+                composite_span(&Operator::Over, &color, surface.iter_mut().into_slice())
+            })
+        });
+        group.bench_function("paint", |bencher| {
+            bencher.iter(|| {
+                let mut context = Context::create(&mut surface);
+                context.set_source_rgba(0.2, 0.4, 0.8, alpha);
+                context.paint();
+            })
+        });
+        group.finish();
+    }
+}
+
+criterion_group!(benches, solid_fill);
+criterion_main!(benches);
@@ -0,0 +1,131 @@
+/*
+ * Cairus - a reimplementation of the cairo graphics library in Rust
+ *
+ * Copyright © 2017 CairusOrg
+ *
+ * This library is free software; you can redistribute it and/or
+ * modify it either under the terms of the GNU Lesser General Public
+ * License version 2.1 as published by the Free Software Foundation
+ * (the "LGPL") or, at your option, under the terms of the Mozilla
+ * Public License Version 2.0 (the "MPL"). If you do not alter this
+ * notice, a recipient may use your version of this file under either
+ * the MPL or the LGPL.
+ *
+ * You should have received a copy of the LGPL along with this library
+ * in the file LICENSE-LGPL-2_1; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Suite 500, Boston, MA 02110-1335, USA
+ * You should have received a copy of the MPL along with this library
+ * in the file LICENSE-MPL-2_0
+ *
+ * The contents of this file are subject to the Mozilla Public License
+ * Version 2.0 (the "License"); you may not use this file except in
+ * compliance with the License. You may obtain a copy of the License at
+ * http://www.mozilla.org/MPL/
+ *
+ * This software is distributed on an "AS IS" basis, WITHOUT WARRANTY
+ * OF ANY KIND, either express or implied. See the LGPL or the MPL for
+ * the specific language governing rights and limitations.
+ *
+ * The Original Code is the cairus graphics library.
+ *
+ * Contributor(s):
+ *	CairusOrg
+ *
+ */
+
+//! Times the stages a fill goes through on the way to pixels, on polygons of growing numbers of
+//! edges: building the event queue, sweeping the edges into trapezoids, and rasterizing the
+//! trapezoids onto a surface.  Each is timed on a convex polygon, whose edges never cross, and on
+//! a star polygon, whose every edge crosses many others, so that work done per intersection shows
+//! up apart from work done per edge.
+//!
+//! The queue and the sweep take their edges by value, so each run is handed a clone, which
+//! criterion makes outside the timing.
+//!
+//! Run with `cargo bench --bench tessellation`.
+
+extern crate cairus;
+#[macro_use]
+extern crate criterion;
+
+use cairus::common_geometry::{Edge, Point};
+use cairus::internals::{EventQueue, rasterize_trapezoids, sweep};
+use cairus::operators::Operator;
+use cairus::surfaces::{Format, ImageSurface};
+use cairus::Rgba;
+use criterion::{BatchSize, BenchmarkId, Criterion, Throughput};
+use std::f32::consts::PI;
+
+const SIZE: usize = 256;
+const EDGE_COUNTS: [usize; 4] = [16, 64, 256, 1024];
+// Past this many edges a star has over a million trapezoids, which take seconds a run
+const STAR_EDGE_LIMIT: usize = 256;
+
+// Returns the edges of a polygon through `count` points evenly spaced around a circle filling
+// the surface, visiting every `step`th point, so a step of 1 is convex and larger steps cross.
+fn polygon(count: usize, step: usize) -> Vec<Edge> {
+    let (center, radius) = (SIZE as f32 / 2., SIZE as f32 / 2. - 1.);
+    let point = |index: usize| {
+        let angle = 2. * PI * (index * step % count) as f32 / count as f32;
+        Point::new(center + radius * angle.cos(), center + radius * angle.sin())
+    };
+    (0..count).map(|index| Edge::from_points(point(index), point(index + 1))).collect()
+}
+
+// Returns the polygons to time, each with its name and edge count
+fn polygons() -> Vec<(&'static str, usize, Vec<Edge>)> {
+    let mut polygons = Vec::new();
+    for &count in EDGE_COUNTS.iter() {
+        polygons.push(("convex", count, polygon(count, 1)));
+        if count <= STAR_EDGE_LIMIT {
+            // A step coprime with the count, about a third of the way round, crosses the most
+            // edges
+            let step = (count / 3..count).find(|step| gcd(*step, count) == 1).unwrap();
+            polygons.push(("star", count, polygon(count, step)));
+        }
+    }
+    polygons
+}
+
+fn tessellation(criterion: &mut Criterion) {
+    let polygons = polygons();
+
+    let mut group = criterion.benchmark_group("queue");
+    for (name, count, edges) in &polygons {
+        group.throughput(Throughput::Elements(*count as u64));
+        group.bench_with_input(BenchmarkId::new(*name, count), edges, |bencher, edges| {
+            bencher.iter_batched(|| edges.clone(), EventQueue::from_edges, BatchSize::SmallInput)
+        });
+    }
+    group.finish();
+
+    let mut group = criterion.benchmark_group("sweep");
+    group.sample_size(20);
+    for (name, count, edges) in &polygons {
+        group.throughput(Throughput::Elements(*count as u64));
+        group.bench_with_input(BenchmarkId::new(*name, count), edges, |bencher, edges| {
+            bencher.iter_batched(|| edges.clone(), sweep, BatchSize::SmallInput)
+        });
+    }
+    group.finish();
+
+    let mut group = criterion.benchmark_group("rasterize");
+    group.sample_size(20);
+    let color = Rgba::new(0.2, 0.4, 0.8, 0.5);
+    for (name, count, edges) in &polygons {
+        let trapezoids = sweep(edges.clone());
+        let mut surface = ImageSurface::create_for_format(Format::ARGB32, SIZE, SIZE);
+        group.throughput(Throughput::Elements(trapezoids.len() as u64));
+        group.bench_with_input(BenchmarkId::new(*name, count), &trapezoids, |bencher, traps| {
+            bencher.iter(|| rasterize_trapezoids(traps, &color, &Operator::Over, &mut surface))
+        });
+    }
+    group.finish();
+}
+
+fn gcd(a: usize, b: usize) -> usize {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+criterion_group!(benches, tessellation);
+criterion_main!(benches);
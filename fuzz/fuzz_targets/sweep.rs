@@ -0,0 +1,90 @@
+/*
+ * Cairus - a reimplementation of the cairo graphics library in Rust
+ *
+ * Copyright © 2017 CairusOrg
+ *
+ * This library is free software; you can redistribute it and/or
+ * modify it either under the terms of the GNU Lesser General Public
+ * License version 2.1 as published by the Free Software Foundation
+ * (the "LGPL") or, at your option, under the terms of the Mozilla
+ * Public License Version 2.0 (the "MPL"). If you do not alter this
+ * notice, a recipient may use your version of this file under either
+ * the MPL or the LGPL.
+ *
+ * You should have received a copy of the LGPL along with this library
+ * in the file LICENSE-LGPL-2_1; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Suite 500, Boston, MA 02110-1335, USA
+ * You should have received a copy of the MPL along with this library
+ * in the file LICENSE-MPL-2_0
+ *
+ * The contents of this file are subject to the Mozilla Public License
+ * Version 2.0 (the "License"); you may not use this file except in
+ * compliance with the License. You may obtain a copy of the License at
+ * http://www.mozilla.org/MPL/
+ *
+ * This software is distributed on an "AS IS" basis, WITHOUT WARRANTY
+ * OF ANY KIND, either express or implied. See the LGPL or the MPL for
+ * the specific language governing rights and limitations.
+ *
+ * The Original Code is the cairus graphics library.
+ *
+ * Contributor(s):
+ *	CairusOrg
+ *
+ */
+
+//! Feeds arbitrary edges to the sweep-line tessellator, checking that it doesn't panic, that it
+//! finishes, and that no trapezoid it returns has a negative area.
+//!
+//! The sweep can only finish if it takes a bounded number of events: a Start and an End for each
+//! edge, and one for each pair of edges that cross.  It is run with that as its limit, so a sweep
+//! that would go on forever fails at once instead of hanging.
+//!
+//! Run from this directory with `cargo +nightly fuzz run sweep`.
+
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate cairus;
+
+use cairus::common_geometry::{Edge, Point};
+use cairus::internals::sweep_with_event_limit;
+use cairus::FillRule;
+
+// More edges than this only make each run slower
+const MAX_EDGES: usize = 64;
+
+// How far a trapezoid's base may be reversed by rounding before it counts as negative
+const TOLERANCE: f32 = 1e-2;
+
+// Returns the edges in `data`, eight bytes an edge: the x and y of its ends, each a little-endian
+// 16-bit integer counting eighths of a pixel.  Whole coordinates and ones sharing their ends
+// come up often this way, as they do in real paths.
+fn edges(data: &[u8]) -> Vec<Edge> {
+    let coordinate = |bytes: &[u8]| i16::from_le_bytes([bytes[0], bytes[1]]) as f32 / 8.;
+    data.chunks_exact(8).take(MAX_EDGES).map(|bytes| {
+        Edge::from_points(Point::new(coordinate(&bytes[0..2]), coordinate(&bytes[2..4])),
+                          Point::new(coordinate(&bytes[4..6]), coordinate(&bytes[6..8])))
+    }).collect()
+}
+
+fuzz_target!(|data: &[u8]| {
+    let (fill_rule, edges) = match data.split_first() {
+        Some((&first, rest)) if first & 1 == 0 => (FillRule::Winding, edges(rest)),
+        Some((_, rest)) => (FillRule::EvenOdd, edges(rest)),
+        None => return,
+    };
+
+    let count = edges.len();
+    let limit = 2 * count + count * count.saturating_sub(1) / 2;
+    let trapezoids = sweep_with_event_limit(edges.clone(), fill_rule, limit)
+        .unwrap_or_else(|| panic!("sweep took over {} events for {:?}", limit, edges));
+
+    for trapezoid in &trapezoids {
+        let (top, bottom) = (trapezoid.top(), trapezoid.bottom());
+        let (left, right) = (trapezoid.left(), trapezoid.right());
+        let widths = (right.point1.x - left.point1.x, right.point2.x - left.point2.x);
+        assert!(bottom >= top && widths.0 >= -TOLERANCE && widths.1 >= -TOLERANCE,
+                "negative area: {:?} from {:?}", trapezoid, edges);
+    }
+});
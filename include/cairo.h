@@ -0,0 +1,254 @@
+/*
+ * Cairus - a reimplementation of the cairo graphics library in Rust
+ *
+ * Copyright © 2017 CairusOrg
+ *
+ * This library is free software; you can redistribute it and/or
+ * modify it either under the terms of the GNU Lesser General Public
+ * License version 2.1 as published by the Free Software Foundation
+ * (the "LGPL") or, at your option, under the terms of the Mozilla
+ * Public License Version 2.0 (the "MPL"). If you do not alter this
+ * notice, a recipient may use your version of this file under either
+ * the MPL or the LGPL.
+ *
+ * You should have received a copy of the LGPL along with this library
+ * in the file LICENSE-LGPL-2_1; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Suite 500, Boston, MA 02110-1335, USA
+ * You should have received a copy of the MPL along with this library
+ * in the file LICENSE-MPL-2_0
+ *
+ * The contents of this file are subject to the Mozilla Public License
+ * Version 2.0 (the "License"); you may not use this file except in
+ * compliance with the License. You may obtain a copy of the License at
+ * http://www.mozilla.org/MPL/
+ *
+ * This software is distributed on an "AS IS" basis, WITHOUT WARRANTY
+ * OF ANY KIND, either express or implied. See the LGPL or the MPL for
+ * the specific language governing rights and limitations.
+ *
+ * The Original Code is the cairus graphics library.
+ *
+ * Contributor(s):
+ *	CairusOrg
+ *
+ */
+
+/*
+ * The subset of cairo's C API that cairus implements, for building C programs written against
+ * cairo with cairus instead.  See src/ffi.rs for what the subset covers and where it differs.
+ */
+
+#ifndef CAIRO_H
+#define CAIRO_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct _cairo cairo_t;
+typedef struct _cairo_surface cairo_surface_t;
+
+typedef enum _cairo_status {
+    CAIRO_STATUS_SUCCESS = 0,
+    CAIRO_STATUS_NO_MEMORY,
+    CAIRO_STATUS_INVALID_RESTORE,
+    CAIRO_STATUS_INVALID_POP_GROUP,
+    CAIRO_STATUS_NO_CURRENT_POINT,
+    CAIRO_STATUS_INVALID_MATRIX,
+    CAIRO_STATUS_INVALID_STATUS,
+    CAIRO_STATUS_NULL_POINTER,
+    CAIRO_STATUS_INVALID_STRING,
+    CAIRO_STATUS_INVALID_PATH_DATA,
+    CAIRO_STATUS_READ_ERROR,
+    CAIRO_STATUS_WRITE_ERROR,
+    CAIRO_STATUS_SURFACE_FINISHED,
+    CAIRO_STATUS_SURFACE_TYPE_MISMATCH,
+    CAIRO_STATUS_PATTERN_TYPE_MISMATCH,
+    CAIRO_STATUS_INVALID_CONTENT,
+    CAIRO_STATUS_INVALID_FORMAT,
+    CAIRO_STATUS_INVALID_VISUAL,
+    CAIRO_STATUS_FILE_NOT_FOUND,
+    CAIRO_STATUS_INVALID_DASH,
+    CAIRO_STATUS_INVALID_DSC_COMMENT,
+    CAIRO_STATUS_INVALID_INDEX,
+    CAIRO_STATUS_CLIP_NOT_REPRESENTABLE,
+    CAIRO_STATUS_TEMP_FILE_ERROR,
+    CAIRO_STATUS_INVALID_STRIDE,
+    CAIRO_STATUS_FONT_TYPE_MISMATCH,
+    CAIRO_STATUS_USER_FONT_IMMUTABLE,
+    CAIRO_STATUS_USER_FONT_ERROR,
+    CAIRO_STATUS_NEGATIVE_COUNT,
+    CAIRO_STATUS_INVALID_CLUSTERS,
+    CAIRO_STATUS_INVALID_SLANT,
+    CAIRO_STATUS_INVALID_WEIGHT,
+    CAIRO_STATUS_INVALID_SIZE
+} cairo_status_t;
+
+typedef enum _cairo_format {
+    CAIRO_FORMAT_INVALID   = -1,
+    CAIRO_FORMAT_ARGB32    = 0,
+    CAIRO_FORMAT_RGB24     = 1,
+    CAIRO_FORMAT_A8        = 2,
+    CAIRO_FORMAT_A1        = 3,
+    CAIRO_FORMAT_RGB16_565 = 4,
+    CAIRO_FORMAT_RGB30     = 5
+} cairo_format_t;
+
+typedef enum _cairo_operator {
+    CAIRO_OPERATOR_CLEAR,
+    CAIRO_OPERATOR_SOURCE,
+    CAIRO_OPERATOR_OVER,
+    CAIRO_OPERATOR_IN,
+    CAIRO_OPERATOR_OUT,
+    CAIRO_OPERATOR_ATOP,
+    CAIRO_OPERATOR_DEST,
+    CAIRO_OPERATOR_DEST_OVER,
+    CAIRO_OPERATOR_DEST_IN,
+    CAIRO_OPERATOR_DEST_OUT,
+    CAIRO_OPERATOR_DEST_ATOP,
+    CAIRO_OPERATOR_XOR,
+    CAIRO_OPERATOR_ADD,
+    CAIRO_OPERATOR_SATURATE,
+    CAIRO_OPERATOR_MULTIPLY,
+    CAIRO_OPERATOR_SCREEN,
+    CAIRO_OPERATOR_OVERLAY,
+    CAIRO_OPERATOR_DARKEN,
+    CAIRO_OPERATOR_LIGHTEN,
+    CAIRO_OPERATOR_COLOR_DODGE,
+    CAIRO_OPERATOR_COLOR_BURN,
+    CAIRO_OPERATOR_HARD_LIGHT,
+    CAIRO_OPERATOR_SOFT_LIGHT,
+    CAIRO_OPERATOR_DIFFERENCE,
+    CAIRO_OPERATOR_EXCLUSION,
+    CAIRO_OPERATOR_HSL_HUE,
+    CAIRO_OPERATOR_HSL_SATURATION,
+    CAIRO_OPERATOR_HSL_COLOR,
+    CAIRO_OPERATOR_HSL_LUMINOSITY
+} cairo_operator_t;
+
+typedef enum _cairo_line_cap {
+    CAIRO_LINE_CAP_BUTT,
+    CAIRO_LINE_CAP_ROUND,
+    CAIRO_LINE_CAP_SQUARE
+} cairo_line_cap_t;
+
+typedef enum _cairo_line_join {
+    CAIRO_LINE_JOIN_MITER,
+    CAIRO_LINE_JOIN_ROUND,
+    CAIRO_LINE_JOIN_BEVEL
+} cairo_line_join_t;
+
+typedef enum _cairo_fill_rule {
+    CAIRO_FILL_RULE_WINDING,
+    CAIRO_FILL_RULE_EVEN_ODD
+} cairo_fill_rule_t;
+
+typedef enum _cairo_antialias {
+    CAIRO_ANTIALIAS_DEFAULT,
+    CAIRO_ANTIALIAS_NONE,
+    CAIRO_ANTIALIAS_GRAY,
+    CAIRO_ANTIALIAS_SUBPIXEL,
+    CAIRO_ANTIALIAS_FAST,
+    CAIRO_ANTIALIAS_GOOD,
+    CAIRO_ANTIALIAS_BEST
+} cairo_antialias_t;
+
+typedef enum _cairo_font_slant {
+    CAIRO_FONT_SLANT_NORMAL,
+    CAIRO_FONT_SLANT_ITALIC,
+    CAIRO_FONT_SLANT_OBLIQUE
+} cairo_font_slant_t;
+
+typedef enum _cairo_font_weight {
+    CAIRO_FONT_WEIGHT_NORMAL,
+    CAIRO_FONT_WEIGHT_BOLD
+} cairo_font_weight_t;
+
+const char *cairo_status_to_string(cairo_status_t status);
+int cairo_format_stride_for_width(cairo_format_t format, int width);
+
+/* Image surfaces */
+cairo_surface_t *cairo_image_surface_create(cairo_format_t format, int width, int height);
+cairo_surface_t *cairo_surface_reference(cairo_surface_t *surface);
+void cairo_surface_destroy(cairo_surface_t *surface);
+unsigned int cairo_surface_get_reference_count(cairo_surface_t *surface);
+cairo_status_t cairo_surface_status(cairo_surface_t *surface);
+void cairo_surface_flush(cairo_surface_t *surface);
+void cairo_surface_mark_dirty(cairo_surface_t *surface);
+cairo_status_t cairo_surface_write_to_png(cairo_surface_t *surface, const char *filename);
+unsigned char *cairo_image_surface_get_data(cairo_surface_t *surface);
+cairo_format_t cairo_image_surface_get_format(cairo_surface_t *surface);
+int cairo_image_surface_get_width(cairo_surface_t *surface);
+int cairo_image_surface_get_height(cairo_surface_t *surface);
+int cairo_image_surface_get_stride(cairo_surface_t *surface);
+
+/* Contexts */
+cairo_t *cairo_create(cairo_surface_t *target);
+cairo_t *cairo_reference(cairo_t *cr);
+void cairo_destroy(cairo_t *cr);
+unsigned int cairo_get_reference_count(cairo_t *cr);
+cairo_status_t cairo_status(cairo_t *cr);
+cairo_surface_t *cairo_get_target(cairo_t *cr);
+
+/* Drawing state */
+void cairo_set_source_rgb(cairo_t *cr, double red, double green, double blue);
+void cairo_set_source_rgba(cairo_t *cr, double red, double green, double blue, double alpha);
+void cairo_set_source_surface(cairo_t *cr, cairo_surface_t *surface, double x, double y);
+void cairo_set_operator(cairo_t *cr, cairo_operator_t op);
+void cairo_set_line_width(cairo_t *cr, double width);
+double cairo_get_line_width(cairo_t *cr);
+void cairo_set_line_cap(cairo_t *cr, cairo_line_cap_t line_cap);
+void cairo_set_line_join(cairo_t *cr, cairo_line_join_t line_join);
+void cairo_set_miter_limit(cairo_t *cr, double limit);
+void cairo_set_dash(cairo_t *cr, const double *dashes, int num_dashes, double offset);
+void cairo_set_fill_rule(cairo_t *cr, cairo_fill_rule_t fill_rule);
+void cairo_set_tolerance(cairo_t *cr, double tolerance);
+void cairo_set_antialias(cairo_t *cr, cairo_antialias_t antialias);
+
+/* Transformations */
+void cairo_translate(cairo_t *cr, double tx, double ty);
+void cairo_scale(cairo_t *cr, double sx, double sy);
+void cairo_rotate(cairo_t *cr, double angle);
+void cairo_identity_matrix(cairo_t *cr);
+
+/* Paths */
+void cairo_new_path(cairo_t *cr);
+void cairo_move_to(cairo_t *cr, double x, double y);
+void cairo_line_to(cairo_t *cr, double x, double y);
+void cairo_curve_to(cairo_t *cr, double x1, double y1, double x2, double y2, double x3,
+                    double y3);
+void cairo_rel_move_to(cairo_t *cr, double dx, double dy);
+void cairo_rel_line_to(cairo_t *cr, double dx, double dy);
+void cairo_rel_curve_to(cairo_t *cr, double dx1, double dy1, double dx2, double dy2,
+                        double dx3, double dy3);
+void cairo_arc(cairo_t *cr, double xc, double yc, double radius, double angle1, double angle2);
+void cairo_arc_negative(cairo_t *cr, double xc, double yc, double radius, double angle1,
+                        double angle2);
+void cairo_rectangle(cairo_t *cr, double x, double y, double width, double height);
+void cairo_close_path(cairo_t *cr);
+void cairo_get_current_point(cairo_t *cr, double *x, double *y);
+
+/* Drawing */
+void cairo_paint(cairo_t *cr);
+void cairo_paint_with_alpha(cairo_t *cr, double alpha);
+void cairo_fill(cairo_t *cr);
+void cairo_fill_preserve(cairo_t *cr);
+void cairo_stroke(cairo_t *cr);
+void cairo_stroke_preserve(cairo_t *cr);
+void cairo_clip(cairo_t *cr);
+void cairo_clip_preserve(cairo_t *cr);
+void cairo_reset_clip(cairo_t *cr);
+void cairo_push_group(cairo_t *cr);
+void cairo_pop_group_to_source(cairo_t *cr);
+
+/* Text */
+void cairo_select_font_face(cairo_t *cr, const char *family, cairo_font_slant_t slant,
+                            cairo_font_weight_t weight);
+void cairo_set_font_size(cairo_t *cr, double size);
+void cairo_show_text(cairo_t *cr, const char *utf8);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* CAIRO_H */
@@ -55,14 +55,14 @@ edge is a line + top, bot, dir
         and a left side with a -1 dir.
 SL_edge has edge + *prev, *next, *colinear, deferred_trap (top, *right)
 
-1. build event queue (EQ) (BST?)
+1. build event queue (EQ) (binary heap)
     add event for each endpoint of lines in edge list.
         min(y of points) is START, max is END
     event is a point and associated edge or two and an enum event type
     sort events by point.y first, then by edge (top bottom, left right)
 
 2. initialize sweep line list (SLL)
-    SLL starts empty. Contains SL_edges. Is doubly linked list
+    SLL starts empty. Contains SL_edges. Is a Vec sorted by x at the sweep line
     SL has *head, y, *current_SL_edge
     ? what about multiple lines intersecting at the same point?
 
@@ -130,7 +130,12 @@ In case of multiple lines crossing at same intersection point we have a couple p
     1. if order of event insertion is wrong, we may end up with non-adjacent edges in SLL being
         swapped
     2. we end up in an infinite loop adding the same intersections to the event queue over and over
-does slope of lines help with this? investigate cairo code...
+Both are handled by treating an intersection as a vertex: the first event for a point gathers
+every adjacent edge through it and sorts them into their order below it in one go, and the
+events for the other pairs are then stale, since those pairs are no longer out of order.  The
+same pair is never queued twice.  An edge that only nearly meets the point is left out, and
+gets its own intersection event once it is next to one of the sorted edges, so it still ends up
+in order.
 
 */
 /*
@@ -153,17 +158,19 @@ add_to_traps(SL_edge edge, float bot, int mask, traps *traps)
         traps_push(left, right, edge.deferred_trap.top, bot)
 */
 use common_geometry::{Edge, Point, LineSegment};
-use std::cmp::Ordering;
+use fixed::snap_point;
+use std::cmp::{Ordering, Reverse};
 use std::clone::Clone;
+use std::collections::{BTreeSet, BinaryHeap};
 use trapezoid_rasterizer::Trapezoid;
-extern crate linked_list;
-use self::linked_list::{LinkedList, Cursor};
+use predicates::{self, Precision};
+use types::FillRule;
 
 
 /// ## EventType
 ///
 /// Defines a type of event.
-#[derive(Eq, PartialEq, Debug)]
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
 pub enum EventType {
     Start,
     End,
@@ -172,7 +179,7 @@ pub enum EventType {
 
 impl PartialOrd for EventType {
     fn partial_cmp(&self, other: &EventType) -> Option<Ordering> {
-        Some(self.cmp(&other))
+        Some(self.cmp(other))
     }
 }
 
@@ -209,7 +216,7 @@ impl Ord for EventType {
 /// edge_right will only contain edges if the event is an Intersection
 /// point is where the event will take place
 /// event_type is the type of event {Start, End, Intersection}
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Event {
     edge_left: Edge,
     edge_right: Vec<Edge>,
@@ -219,38 +226,59 @@ pub struct Event {
 
 impl PartialOrd for Event {
     fn partial_cmp(&self, other: &Event) -> Option<Ordering> {
-        Some(self.cmp(&other))
+        Some(self.cmp(other))
     }
 }
 
 /// Event ordering: compare y values (smaller is less) if y's are equal compare x's (smaller x comes
 ///     first. IF point is equal compare event type ( End < Intersection < Start)
+///     If those are equal too, the event whose edge heads further left below the point comes
+///     first, and the edges' end points and directions break any remaining tie, so that only
+///     events for the same edges compare equal.
 impl Ord for Event {
     fn cmp(&self, other: &Event) -> Ordering {
-        let y_compare = self.point.y.partial_cmp(&other.point.y).unwrap_or(Ordering::Equal);
-        if y_compare != Ordering::Equal   {
-                return y_compare
-        }
-
-        let x_compare = self.point.x.partial_cmp(&other.point.x).unwrap_or(Ordering::Equal);
-        if x_compare != Ordering::Equal   {
-                return x_compare
-        }
-
-        let type_compare = self.event_type.cmp(&other.event_type);
-        if type_compare == Ordering::Equal {
-            return Ordering::Greater
-        }
-        type_compare
+        compare_f32(self.point.y, other.point.y)
+            .then_with(|| compare_f32(self.point.x, other.point.x))
+            .then_with(|| self.event_type.cmp(&other.event_type))
+            .then_with(|| compare_edges(&self.edge_left, &other.edge_left))
+            .then_with(|| self.edge_right.len().cmp(&other.edge_right.len()))
+            .then_with(|| {
+                self.edge_right.iter().zip(&other.edge_right)
+                    .map(|(edge, other_edge)| compare_edges(edge, other_edge))
+                    .find(|&ordering| ordering != Ordering::Equal)
+                    .unwrap_or(Ordering::Equal)
+            })
     }
 }
 
+/// Events are equal when they happen at the same point, are of the same type and are for the
+/// same edges.
 impl PartialEq for Event {
-    fn eq(&self, other:&Event) -> bool {
-        true
+    fn eq(&self, other: &Event) -> bool {
+        self.cmp(other) == Ordering::Equal
     }
 }
 
+/// Compares two coordinates, treating NaN as greater than any number so the order stays total.
+fn compare_f32(a: f32, b: f32) -> Ordering {
+    a.partial_cmp(&b).unwrap_or_else(|| a.is_nan().cmp(&b.is_nan()))
+}
+
+/// Orders edges by the way they head below their top point, leftmost first, then by their end
+/// points and directions.
+fn compare_edges(edge: &Edge, other: &Edge) -> Ordering {
+    let (top, bottom) = (edge.line.min_y_point(), edge.line.max_y_point());
+    let (other_top, other_bottom) = (other.line.min_y_point(), other.line.max_y_point());
+    // The angle from straight down, which grows as the edge heads further right
+    let heading = |top: Point, bottom: Point| (bottom.x - top.x).atan2(bottom.y - top.y);
+    compare_f32(heading(top, bottom), heading(other_top, other_bottom))
+        .then_with(|| compare_f32(top.y, other_top.y))
+        .then_with(|| compare_f32(top.x, other_top.x))
+        .then_with(|| compare_f32(bottom.y, other_bottom.y))
+        .then_with(|| compare_f32(bottom.x, other_bottom.x))
+        .then_with(|| edge.direction.cmp(&other.direction))
+}
+
 impl Eq for Event {}
 
 impl Event {
@@ -258,26 +286,95 @@ impl Event {
     fn new(edge_left: Edge, point: &Point, event_type: EventType) -> Event {
         Event {
             point: *point,
-            edge_left: edge_left,
+            edge_left,
             edge_right: Vec::new(),
-            event_type: event_type,
+            event_type,
         }
     }
     /// Creates a new Event for an Intersection type
     fn new_intersection(edge_left: Edge, edge_right: Edge, point: &Point) -> Event {
         Event {
             point: *point,
-            edge_left: edge_left,
+            edge_left,
             edge_right: vec![edge_right],
             event_type: EventType::Intersection,
         }
     }
 }
 
+/// ## EventQueue
+///
+/// The events sweep() has still to process, soonest first.
+///
+/// A binary heap, so taking the next event and pushing an intersection found partway through the
+/// sweep both take O(log n) time.  The intersections waiting are kept in a set as well, so one
+/// found again is turned away in O(log n) time too.
+#[derive(Debug, Default)]
+pub struct EventQueue {
+    // Reversed, since BinaryHeap pops its greatest item first
+    heap: BinaryHeap<Reverse<Event>>,
+    // A copy of every intersection in the heap, which its point and edges identify
+    intersections: BTreeSet<Event>,
+}
+
+impl EventQueue {
+    /// Returns a queue holding `events`, in any order.
+    fn new(events: Vec<Event>) -> EventQueue {
+        EventQueue {
+            intersections: events.iter()
+                                 .filter(|event| event.event_type == EventType::Intersection)
+                                 .cloned()
+                                 .collect(),
+            heap: events.into_iter().map(Reverse).collect(),
+        }
+    }
+
+    /// Returns a queue holding the Start and End events of `edges`, as the sweep starts with.
+    pub fn from_edges(edges: Vec<Edge>) -> EventQueue {
+        EventQueue::new(event_list_from_edges(edges))
+    }
+
+    /// Adds an event to the queue, returning false if it is an intersection that is already
+    /// waiting.
+    ///
+    /// Starts and ends are always added, since a path can have the same edge more than once.  An
+    /// intersection that was queued twice would swap its edges and then swap them back.
+    fn push(&mut self, event: Event) -> bool {
+        if event.event_type == EventType::Intersection &&
+           !self.intersections.insert(event.clone()) {
+            return false;
+        }
+        self.heap.push(Reverse(event));
+        true
+    }
+
+    /// Removes and returns the soonest event, or None if the queue is empty.
+    fn pop(&mut self) -> Option<Event> {
+        let Reverse(event) = self.heap.pop()?;
+        if event.event_type == EventType::Intersection {
+            self.intersections.remove(&event);
+        }
+        Some(event)
+    }
+
+    /// Returns the number of events waiting.
+    pub fn len(&self) -> usize {
+        self.heap.len()
+    }
+
+    /// Returns true if no events are waiting.
+    pub fn is_empty(&self) -> bool {
+        self.heap.is_empty()
+    }
+}
+
 /// Takes a list of edges, converts them into a list of events, then returns a sorted event list.
+///
+/// The edges are normalized first, as normalize_edges() describes, so each one left gets exactly
+/// one Start and one End event.
 fn event_list_from_edges(edges: Vec<Edge>) -> Vec<Event> {
     let mut events = Vec::new();
-    for edge in edges {
+    for edge in normalize_edges(edges) {
         // Case for horizontal line
         if edge.top == edge.bottom {
             let start_point = edge.line.min_x_point();
@@ -295,6 +392,44 @@ fn event_list_from_edges(edges: Vec<Edge>) -> Vec<Event> {
     events
 }
 
+/// Drops the edges that can't bound anything and merges exact duplicates.
+///
+/// Zero-length edges are dropped.  Edges between the same two points, whichever way round they
+/// were drawn, are merged into one whose direction is the sum of theirs, which changes the
+/// winding number the same way the separate edges did; if the directions cancel out, the edges
+/// are dropped.  Horizontal edges have no direction, so duplicates of one are merged into one.
+fn normalize_edges(edges: Vec<Edge>) -> Vec<Edge> {
+    // An edge's end points, in event order, so that duplicates have the same ones
+    let ends = |edge: &Edge| {
+        let (point1, point2) = (edge.line.point1, edge.line.point2);
+        let first = compare_f32(point1.y, point2.y).then_with(|| compare_f32(point1.x, point2.x));
+        if first == Ordering::Greater { (point2, point1) } else { (point1, point2) }
+    };
+    let compare_ends = |edge: &Edge, other: &Edge| {
+        let ((first, second), (other_first, other_second)) = (ends(edge), ends(other));
+        compare_f32(first.y, other_first.y)
+            .then_with(|| compare_f32(first.x, other_first.x))
+            .then_with(|| compare_f32(second.y, other_second.y))
+            .then_with(|| compare_f32(second.x, other_second.x))
+    };
+
+    let mut edges: Vec<Edge> = edges.into_iter()
+        .filter(|edge| edge.line.point1 != edge.line.point2)
+        .collect();
+    edges.sort_by(|edge, other| compare_ends(edge, other));
+    let mut merged: Vec<Edge> = Vec::with_capacity(edges.len());
+    for edge in edges {
+        match merged.last_mut() {
+            Some(last) if compare_ends(last, &edge) == Ordering::Equal => {
+                last.direction += edge.direction;
+            },
+            _ => merged.push(edge),
+        }
+    }
+    merged.retain(|edge| edge.direction != 0 || edge.top == edge.bottom);
+    merged
+}
+
 /// Defines a SweepLineEdge for our SweepLineList
 ///
 /// The SweepLineEdges will be used to create trapezoids.
@@ -316,260 +451,351 @@ pub struct SweepLineEdge {
 impl SweepLineEdge {
     fn new(trap_top: f32, left: f32, edge: Edge) -> SweepLineEdge {
         SweepLineEdge {
-            trap_top: trap_top,
-            left: left,
-            edge: edge,
+            trap_top,
+            left,
+            edge,
         }
     }
 }
 
-/// Creates trapezoids out of the passed in edges.
-pub fn sweep(edges: Vec<Edge>) -> Vec<Trapezoid> {
-    // Create the empty sweep Line Linked List
-    let mut sl_list: LinkedList<SweepLineEdge> = LinkedList::new();
-    // Create a cursor to move over the list
-    let mut cursor = sl_list.cursor();
-    // Create the list of events
-    let mut events = event_list_from_edges(edges);
-    // Create empty traps list for eventual return
-    let mut traps: Vec<Trapezoid> = Vec::new();
-    // Keep looping until the Event List is empty
-    while !events.is_empty() {
-        // Get the current event
-        let event = events.remove(0);
-
-        // Set the sweep line to the events y value
-        let sweep_line = event.point.y;
-
-        // Process Event
-        // START CASE
-        if event.event_type == EventType::Start{
-            println!("Starting START case for point: ({},{})", event.point.x, event.point.y);
-            println!("Sweep Line is: {}", sweep_line );
-            // find the left most point of the edge_left line
-            let left = event.edge_left.line.min_x_point().x;
-            // create a new node and add it to the list
-            let sl_edge = SweepLineEdge::new(sweep_line, left, event.edge_left);
-            // Set the cursor back to the beginning
-            cursor.reset();
-            if cursor.peek_next().is_none() {
-                cursor.insert(sl_edge);
-            } else {
-                while find_line_place(event.point, event.edge_left, *cursor.peek_next().unwrap()) == Comparator::Less {
-                    cursor.next();
-                    if cursor.peek_next().is_none() {
-                        break;
-                    }
-                }
-                // **** ADD TRAPEZOID *****
-                // If before we add our new sl_edge there is a previous and next we need to make a
-                // new Trapezoid and set the prev top
-                if cursor.peek_prev().is_some() && cursor.peek_next().is_some() {
-                    // passing -1 for mask as winding rule default 0xFFFFFFFF
-                    add_to_traps(&mut cursor, sweep_line, -1 , &mut traps);
-                    cursor.peek_prev().unwrap().trap_top = sweep_line;
-                }
+/// ## SweepLineList
+///
+/// The edges crossing the sweep line, sorted by where they cross it from left to right.
+///
+/// Positions between edges are given as gaps: gap `i` is between the edges at indices `i - 1`
+/// and `i`, so gap 0 is before the first edge and gap `len()` is after the last.
+#[derive(Debug, Default)]
+pub struct SweepLineList {
+    edges: Vec<SweepLineEdge>,
+}
 
-                // Add the new edge to our sweep line list
-                cursor.insert(sl_edge);
+impl SweepLineList {
+    /// Returns the number of edges crossing the sweep line.
+    pub fn len(&self) -> usize {
+        self.edges.len()
+    }
 
-                // **** CHECK FOR INTERSECTIONS ****
-                // Check to see if the new edge intersects with the previous or next
-                // if it does after the current sweep line then we add it to our event list.
-                // Check if there is an intersection with the left line, if yes check for interaction
+    /// Returns true if no edges cross the sweep line.
+    pub fn is_empty(&self) -> bool {
+        self.edges.is_empty()
+    }
 
-                // Move the cursor to just before our newly inserted sweep line edge
-                cursor.prev();
-                // If it has a previous before our new event there way be an intersection
-                if cursor.peek_prev().is_some() {
-                    check_for_intersection(sweep_line, &mut cursor, &mut events);
-                }
-                // Move the cursor to after the newly added sweep line edge
-                cursor.next();
-                // If there is a sweep line edge after the new one, check for intersections
-                if cursor.peek_next().is_some() {
-                    check_for_intersection(sweep_line, &mut cursor, &mut events);
-                }
+    /// Returns the gap an edge starting at `point` belongs in, found by binary search.
+    fn insert_position(&self, point: Point, edge: Edge, precision: Precision) -> usize {
+        self.edges.partition_point(|next| {
+            find_line_place(point, edge, *next, precision) == Comparator::Less
+        })
+    }
 
+    /// Returns the index of the edge along `line`, which crosses the sweep line at `point`.
+    ///
+    /// The search starts where `point` falls in the list and works outwards, so it only has to
+    /// look past the edges that cross the sweep line at about the same place.
+    fn position(&self, line: &LineSegment, point: Point) -> Option<usize> {
+        let middle = self.edges.partition_point(|next| {
+            next.edge.line.current_x_for_y(point.y) < point.x
+        });
+        for distance in 0..self.edges.len() {
+            let after = middle + distance;
+            if after < self.edges.len() && self.edges[after].edge.line == *line {
+                return Some(after);
+            }
+            if distance < middle && self.edges[middle - 1 - distance].edge.line == *line {
+                return Some(middle - 1 - distance);
             }
-            println!("Finished START Case");
-         //   println!("current x, y value: {} {}",cursor.next().unwrap().edge.line.current_x_for_y(sweep_line), sweep_line );
         }
+        None
+    }
 
-        // END CASE
-        else if event.event_type == EventType::End {
-            println!("Starting END case for point: ({},{})", event.point.x, event.point.y);
-            println!("Sweep Line is: {}", sweep_line );
-
-            // REMOVE FROM SL_LIST
-            // if our event line is equal to our cursor_left line then see if our lines are equal, if yes remove
-            // if no then we need to see which direction to move...
-            // if our event line is greater then our cursor left line then we need to move right and repeat
-            // if our event line is less then our cursor left line then we need to move left
-
-            // Move the cursor to before the sweep line edge we wish to delete
-            move_cursor_to_line(event.point, event.edge_left, &mut cursor);
-
-            let line = cursor.peek_next().unwrap().edge.line.clone();
-            println!("Cursor Next point is: ({},{})", line.current_x_for_y(sweep_line), sweep_line);
-
-            // **** CREATE TRAPEZOIDS *****
-            // we will be at the point of removal here, so we need to see about building trapezoids
-            // before and after this point before we remove it. We will want to update the TOP of the
-            // node before if we create a trapezoid
-            if cursor.peek_prev().is_some() {
-                // passing -1 for mask as winding rule default 0xFFFFFFFF
-                println!("Calling add_to_traps for trap before current cursor");
-                add_to_traps(&mut cursor, sweep_line, -1 , &mut traps);
-                cursor.peek_prev().unwrap().trap_top = sweep_line;
-            }
-            if cursor.peek_next().is_some() {
-                cursor.next();
-                if cursor.peek_next().is_some() {
-                    println!("Calling add_to_traps for trap after current cursor");
-                    let line_before = cursor.peek_prev().unwrap().edge.line.clone();
-                    let line_after = cursor.peek_next().unwrap().edge.line.clone();
-                    println!("   Line Previous point is: ({},{})", line_before.current_x_for_y(sweep_line), sweep_line);
-                    println!("   Line Next point is: ({},{})", line_after.current_x_for_y(sweep_line), sweep_line);
-                    // passing -1 for mask as winding rule default 0xFFFFFFFF
-                    add_to_traps(&mut cursor, sweep_line, -1, &mut traps);
-                    cursor.peek_prev().unwrap().trap_top = sweep_line;
-                }
-                cursor.prev();
-            }
-            cursor.remove();
-
-            // ****** CHECK FOR INTERSECTIONS ****
-            // After we remove it we will want to see if there is any intersections with the lines
-            // before and after the cursor. If yes, and it happens after our current y we add it to
-            // our event list.
-            if cursor.peek_prev().is_some() && cursor.peek_next().is_some() {
-                check_for_intersection(sweep_line, &mut cursor, &mut events);
-            }
+    /// Returns the indices of the first and last of the adjacent edges that pass through `point`
+    /// and carry on below it, starting from the edges at `left` and `right`.
+    fn through_point(&self, left: usize, right: usize, point: Point) -> (usize, usize) {
+        let mut first = left;
+        while first > 0 && passes_through(&self.edges[first - 1].edge.line, point) {
+            first -= 1;
+        }
+        let mut last = right;
+        while last + 1 < self.edges.len() &&
+              passes_through(&self.edges[last + 1].edge.line, point) {
+            last += 1;
+        }
+        (first, last)
+    }
 
-            println!("Finished END Case");
+    /// Returns the edges either side of `gap`, if there are edges on both sides.
+    fn neighbours(&self, gap: usize) -> Option<(Edge, Edge)> {
+        if gap == 0 || gap >= self.edges.len() {
+            return None;
         }
+        Some((self.edges[gap - 1].edge, self.edges[gap].edge))
+    }
+}
 
+/// Creates trapezoids out of the passed in edges, filling with the winding rule.
+pub fn sweep(edges: Vec<Edge>) -> Vec<Trapezoid> {
+    sweep_with_fill_rule(edges, FillRule::Winding)
+}
 
-        // INTERSECT CASE
-            else if event.event_type == EventType::Intersection {
-                println!("Starting INTERSECT case for point: ({},{})", event.point.x, event.point.y);
-                println!("Sweep Line is: {}", sweep_line);
-
-                // move the cursor between the two edges
-                // *** Issue: How do i access the element inside of a BOX? ***
-                move_cursor_to_line(event.point, *event.edge_right.get(0).unwrap(), &mut cursor );
-                let line_before = cursor.peek_prev().unwrap().edge.line.clone();
-                let line_after = cursor.peek_next().unwrap().edge.line.clone();
-                println!("   Line Previous point is: ({},{})", line_before.current_x_for_y(sweep_line), sweep_line);
-                println!("   Line Next point is: ({},{})", line_after.current_x_for_y(sweep_line), sweep_line);
-                // check for traps before
-                println!("Starting trap checks");
-                cursor.prev();
-                if cursor.peek_prev().is_some() {
-                    add_to_traps(&mut cursor, sweep_line, -1 , &mut traps);
-                    cursor.peek_prev().unwrap().trap_top = sweep_line;
-                }
-                // check for traps between
-                cursor.next();
-                add_to_traps(&mut cursor, sweep_line, -1 , &mut traps);
-                cursor.peek_prev().unwrap().trap_top = sweep_line;
-
-                // check for traps after
-                cursor.next();
-                if cursor.next().is_some() {
-                    add_to_traps(&mut cursor, sweep_line, -1 , &mut traps);
-                    cursor.peek_prev().unwrap().trap_top = sweep_line;
-                }
-                println!("Ending trap checks");
+/// Creates trapezoids out of the passed in edges, covering the area inside them by `fill_rule`.
+pub fn sweep_with_fill_rule(edges: Vec<Edge>, fill_rule: FillRule) -> Vec<Trapezoid> {
+    sweep_with_precision(edges, fill_rule, Precision::Fast)
+}
 
-                // move the cursor back between our edges
-                cursor.prev();
+/// Creates trapezoids out of the passed in edges, covering the area inside them by `fill_rule`
+/// and evaluating the sweep's geometric predicates with `precision`.
+///
+/// `Precision::Exact` decides which side of an edge each event falls on, the order of edges
+/// leaving a shared point and where edges cross without rounding error.  It is slower, but
+/// nearly degenerate input can't make those decisions contradict each other.
+pub fn sweep_with_precision(edges: Vec<Edge>, fill_rule: FillRule, precision: Precision)
+                            -> Vec<Trapezoid> {
+    sweep_slabs(edges, fill_rule, precision).iter()
+        .filter_map(|slab| bo_trap_from_lines(&slab.left, &slab.right, slab.top, slab.bottom))
+        .collect()
+}
 
-                if  cursor.peek_prev().is_none() || cursor.peek_next().is_none() {
-                    println! ("**** ERROR WHAT HAPPENED TO THE CURSOR ****");
-                    move_cursor_to_line(event.point, *event.edge_right.get(0).unwrap(), &mut cursor );
-                }
-                let line_before = cursor.peek_prev().unwrap().edge.line.clone();
-                let line_after = cursor.peek_next().unwrap().edge.line.clone();
-                println!("   Line Previous point is: ({},{})", line_before.current_x_for_y(sweep_line), sweep_line);
-                println!("   Line Next point is: ({},{})", line_after.current_x_for_y(sweep_line), sweep_line);
-
-                // swap
-                let swap_sl_edge = cursor.remove().unwrap();
-                cursor.prev();
-                cursor.insert(swap_sl_edge);
-
-                // check for intersections before set
-                cursor.prev();
-                if cursor.peek_prev().is_some() {
-                    check_for_intersection(sweep_line, &mut cursor, &mut events);
+/// Sweeps the passed in edges as `sweep_with_fill_rule` does, but gives up and returns None once
+/// more than `limit` events have been taken from the queue, so that a sweep that would never
+/// finish can be caught.
+///
+/// Every edge makes a Start and an End event, and each pair of edges can cross at most once,
+/// which bounds the events a sweep that finishes takes.
+pub fn sweep_with_event_limit(edges: Vec<Edge>, fill_rule: FillRule, limit: usize)
+                              -> Option<Vec<Trapezoid>> {
+    let mask = winding_mask(fill_rule);
+    let slabs = sweep_slabs_limited(edges, Precision::Fast, &|winding| winding & mask != 0,
+                                    limit)?;
+    Some(slabs.iter()
+        .filter_map(|slab| bo_trap_from_lines(&slab.left, &slab.right, slab.top, slab.bottom))
+        .collect())
+}
+
+/// ## Slab
+///
+/// A part of the filled area as the sweep finds it: between the `left` and `right` edges, which
+/// are whole edges rather than the parts of them the slab touches, from `top` to `bottom`.
+///
+/// The slabs the sweep returns don't overlap, and their corners all come from
+/// `LineSegment::current_x_for_y` on the edges, so slabs that meet share their corners exactly.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Slab {
+    pub left: LineSegment,
+    pub right: LineSegment,
+    pub top: f32,
+    pub bottom: f32,
+}
+
+/// Sweeps the passed in edges as `sweep_with_precision` does, but returns the slabs the
+/// trapezoids are cut from, which still know the edges either side of them.
+pub fn sweep_slabs(edges: Vec<Edge>, fill_rule: FillRule, precision: Precision) -> Vec<Slab> {
+    let mask = winding_mask(fill_rule);
+    sweep_slabs_inside(edges, precision, &|winding| winding & mask != 0)
+}
+
+/// Sweeps the passed in edges as `sweep_slabs` does, but fills wherever `inside` is true for the
+/// winding number, the sum of the directions of the edges to the right, instead of by a fill
+/// rule.
+pub fn sweep_slabs_inside(edges: Vec<Edge>, precision: Precision, inside: &dyn Fn(i32) -> bool)
+                          -> Vec<Slab> {
+    sweep_slabs_limited(edges, precision, inside, usize::MAX).unwrap_or_default()
+}
+
+// Sweeps as `sweep_slabs_inside` does, or returns None once more than `limit` events have been
+// taken from the queue.
+fn sweep_slabs_limited(edges: Vec<Edge>, precision: Precision, inside: &dyn Fn(i32) -> bool,
+                       limit: usize) -> Option<Vec<Slab>> {
+    // Create the empty sweep line list
+    let mut sl_list = SweepLineList::default();
+    // Snap the edges to fixed point, so that ends meant to meet compare equal.  Horizontal edges
+    // never cross the horizontal ray used for the winding count, and the trapezoids' tops and
+    // bottoms come from the events, so they can be dropped up front.
+    let edges = edges.into_iter()
+        .map(snap_edge)
+        .filter(|edge| edge.line.point1.y != edge.line.point2.y)
+        .collect();
+    // Create the queue of events
+    let mut events = EventQueue::from_edges(edges);
+    // Create empty traps list for eventual return
+    let mut traps: Vec<Slab> = Vec::new();
+    let mut taken = 0;
+    // Keep looping until the Event Queue is empty
+    while let Some(event) = events.pop() {
+        taken += 1;
+        if taken > limit {
+            return None;
+        }
+        // Set the sweep line to the events y value
+        let sweep_line = event.point.y;
+
+        match event.event_type {
+            EventType::Start => {
+                // Find the gap before the first edge that sorts after the new one
+                let gap = sl_list.insert_position(event.point, event.edge_left, precision);
+
+                // The new edge splits the trapezoid deferred between its neighbours, and changes
+                // the winding number of every trapezoid to its left
+                close_traps_left(&mut sl_list, gap, sweep_line, inside, &mut traps);
+
+                let left = event.edge_left.line.min_x_point().x;
+                sl_list.edges.insert(gap, SweepLineEdge::new(sweep_line, left, event.edge_left));
+
+                // Check the new edge against both of its neighbours
+                check_for_intersection(sweep_line, &sl_list, gap, &mut events, precision);
+                check_for_intersection(sweep_line, &sl_list, gap + 1, &mut events, precision);
+            },
+            EventType::End => {
+                let index = match sl_list.position(&event.edge_left.line, event.point) {
+                    Some(index) => index,
+                    None => continue,
+                };
+
+                // Close the trapezoids on either side of the ending edge, and those further left
+                // whose winding number it changes
+                close_traps_left(&mut sl_list, index, sweep_line, inside, &mut traps);
+                close_trap(&mut sl_list, index + 1, sweep_line, inside, &mut traps);
+                sl_list.edges.remove(index);
+
+                // Its neighbours are now adjacent and may cross further down
+                check_for_intersection(sweep_line, &sl_list, index, &mut events, precision);
+            },
+            EventType::Intersection => {
+                // Find the gap between the two edges.  The event is stale if one of them has
+                // ended, another edge has come between them, or they have already been swapped.
+                let edge_right = match event.edge_right.first() {
+                    Some(&edge_right) => edge_right,
+                    None => continue,
+                };
+                let gap = match sl_list.position(&edge_right.line, event.point) {
+                    Some(gap) => gap,
+                    None => continue,
+                };
+                let edge_left = match sl_list.neighbours(gap) {
+                    Some((edge_left, _)) if edge_left.line == event.edge_left.line => edge_left,
+                    _ => continue,
+                };
+                if !crosses_below(edge_left.line, edge_right.line, precision) {
+                    continue;
                 }
-                cursor.next();
 
-                // check for intersections after set
-                cursor.next();
-                if cursor.peek_next().is_some() {
-                    check_for_intersection(sweep_line, &mut cursor, &mut events);
+                // Treat the crossing as a vertex: gather every edge through it, not just the pair
+                let (first, last) = sl_list.through_point(gap - 1, gap, event.point);
+
+                // Close the trapezoids left of, between and right of the gathered edges
+                for close_gap in first..=last + 1 {
+                    close_trap(&mut sl_list, close_gap, sweep_line, inside, &mut traps);
                 }
 
-                println!("Finished INTERSECT Case");
-            }
+                // Put them all in their order below the vertex at once, then check the outermost
+                // against their new neighbours.  Those between can't cross again.
+                order_below(&mut sl_list.edges[first..=last], precision);
+                check_for_intersection(sweep_line, &sl_list, first, &mut events, precision);
+                check_for_intersection(sweep_line, &sl_list, last + 1, &mut events, precision);
+            },
+        }
+    }
+    // Return the list of trapezoids
+    Some(traps)
+}
 
+/// Returns true if `line` carries on below `point` and crosses its y at its x, to within the
+/// rounding error of a computed intersection.
+///
+/// An edge that only just misses the point is left for its own intersection event.
+fn passes_through(line: &LineSegment, point: Point) -> bool {
+    let tolerance = 4. * f32::EPSILON * (1. + point.x.abs().max(point.y.abs()));
+    line.max_y_point().y > point.y && (line.current_x_for_y(point.y) - point.x).abs() <= tolerance
+}
 
-        // print the Sweep Line List
-        cursor.reset();
-        println!("***Printing Sweep Line List***");
-        let mut index = 0;
-        while cursor.peek_next().is_some(){
-            let line = cursor.peek_next().unwrap().edge.line.clone();
-            let top = cursor.peek_next().unwrap().trap_top;
-            println!("     Index {}:  x:{}  Top:({},{}) Slope:{}", index, line.current_x_for_y(sweep_line), line.current_x_for_y(top), top, line.slope()) ;
-            index = index + 1;
-            cursor.next();
+/// Sorts edges that meet at a point into their order below it, leftmost first.
+///
+/// An insertion sort of adjacent swaps, so it can't be thrown by predicates that aren't quite
+/// transitive, and edges already in order aren't moved.
+fn order_below(sl_edges: &mut [SweepLineEdge], precision: Precision) {
+    for index in 1..sl_edges.len() {
+        let mut position = index;
+        while position > 0 && crosses_below(sl_edges[position - 1].edge.line,
+                                            sl_edges[position].edge.line, precision) {
+            sl_edges.swap(position - 1, position);
+            position -= 1;
         }
-        println!("********");
+    }
+}
 
-        println!("EVENT COMPLETE at sweep: {}", sweep_line);
-        println!("")
+/// Returns `edge` with its ends snapped to the fixed-point grid.
+fn snap_edge(edge: Edge) -> Edge {
+    let (point1, point2) = (snap_point(edge.line.point1), snap_point(edge.line.point2));
+    let line = LineSegment::from_points(point1, point2);
+    Edge {
+        line,
+        top: line.min_y_point().y,
+        bottom: line.max_y_point().y,
+        direction: edge.direction,
     }
-    // Return the list of trapezoids
-    traps
 }
 
-/// Checks to see if we should add the intersection to the event list
-/// Expects the cursor to be between the two lines that we want to check for intersection
-pub fn check_for_intersection(sweep_line: f32, cursor: &mut Cursor<SweepLineEdge>, events: &mut Vec<Event>)  {
+/// Returns the mask sweep_slabs applies to winding numbers: every bit for the winding rule, so
+/// any non-zero count fills, and only the lowest for even-odd, so only odd counts fill.
+pub fn winding_mask(fill_rule: FillRule) -> i32 {
+    match fill_rule {
+        FillRule::Winding => -1,
+        FillRule::EvenOdd => 1,
+    }
+}
+
+/// Closes the trapezoids deferred on every edge before `gap`, as `close_trap` does.
+///
+/// Winding numbers are counted over the edges to the right of each trapezoid, so an edge starting
+/// or ending changes whether every trapezoid to its left is filled, not just its neighbours'.
+fn close_traps_left(sl_list: &mut SweepLineList, gap: usize, bottom: f32,
+                    inside: &dyn Fn(i32) -> bool, traps: &mut Vec<Slab>) {
+    for left_gap in (1..=gap).rev() {
+        close_trap(sl_list, left_gap, bottom, inside, traps);
+    }
+}
+
+/// Ends the trapezoid deferred on the edge before `gap` at `bottom`, adding it to `traps` if it
+/// is filled by `inside`, and starts the edge's next trapezoid there.
+///
+/// The edge's trapezoid runs to the edge after `gap`, its current right neighbour.  Does nothing
+/// if there is no edge before `gap`.
+fn close_trap(sl_list: &mut SweepLineList, gap: usize, bottom: f32,
+              inside: &dyn Fn(i32) -> bool, traps: &mut Vec<Slab>) {
+    if gap == 0 || gap > sl_list.len() {
+        return;
+    }
+    if gap < sl_list.len() {
+        add_to_traps(sl_list, gap, bottom, inside, traps);
+    }
+    sl_list.edges[gap - 1].trap_top = bottom;
+}
+
+/// Checks to see if we should add the intersection of the edges either side of `gap` to the
+/// event queue.
+///
+/// Crossings on the sweep line itself are only added while the pair is still out of order
+/// below it, which is how several edges crossing at one point get fully reordered without
+/// swapping back and forth.
+pub fn check_for_intersection(sweep_line: f32, sl_list: &SweepLineList, gap: usize,
+                              events: &mut EventQueue, precision: Precision) {
     // Verifies there is a previous and next before we check for intersections
-    if cursor.peek_prev().is_none() || cursor.peek_next().is_none() {
+    let (prev_edge, next_edge) = match sl_list.neighbours(gap) {
+        Some(neighbours) => neighbours,
+        None => return,
+    };
+    let point = match predicates::intersection(&prev_edge.line, &next_edge.line, precision) {
+        Some(point) => point,
+        None => return,
+    };
+    // if the event has already happened, do not add it
+    if point.y < sweep_line || !crosses_below(prev_edge.line, next_edge.line, precision) {
         return;
     }
-    println!("Starting Intersection Checks");
-    let next_line = &cursor.peek_next().unwrap().edge.line.clone();
-    let result = cursor.peek_prev().unwrap().edge.line.intersection(next_line);
-    // Add the event if it exists
-    if result.is_some() {
-        let point = result.unwrap();
-        // if the event has already happened, do not add it
-        if point.y <= sweep_line {
-            println!("Ending Intersection Checks: No Intersection");
-            return;
-        }
-        // if the intersection happens at the end of either line, do not add it
-        if point == cursor.peek_prev().unwrap().edge.line.max_y_point() {
-            println!("Ending Intersection Checks: No Intersection");
-            return;
-        }
-        if point == cursor.peek_next().unwrap().edge.line.max_y_point() {
-            println!("Ending Intersection Checks: No Intersection");
-            return;
-        }
-        // add the intersection
-        println!("Adding intersect to events");
-        events.push(Event::new_intersection(cursor.peek_prev().unwrap().edge, cursor.peek_next().unwrap().edge, &point));
-        events.sort();
-        println!("Ending Intersection Checks: Intersect Added");
+    // if the intersection happens at the end of either line, do not add it
+    if point == prev_edge.line.max_y_point() || point == next_edge.line.max_y_point() {
+        return;
     }
+    // if the pair is already waiting to be swapped, the queue will not add it twice
+    events.push(Event::new_intersection(prev_edge, next_edge, &point));
 }
 
 #[derive(Eq, PartialEq, Debug)]
@@ -580,73 +806,69 @@ pub enum Comparator {
     Empty,
 }
 
-/// Searches the sweep line list for a line matching the one if the edge
-/// point: the current event point
-/// edge: the edge we are trying to find a match to
-/// cursor: will be set to the position before the edge that is equal
-pub fn move_cursor_to_line(point: Point, edge:Edge, cursor: &mut Cursor<SweepLineEdge> ) {
-    println!("Starting move_cursor to line");
-    // If we are at the end of the list move one position back so we have something to compare
-//    if cursor.peek_next().is_none() {
-//        cursor.prev();
-//    }
-//    let mut result = Comparator::Empty;
-//    while result != Comparator::Equal {
-//        result = find_line_place(point, edge, *cursor.peek_next().unwrap());
-//
-//        if result == Comparator::Equal {
-//            break;
-//        } else if result == Comparator::Greater {
-//            cursor.prev();
-//        } else if result == Comparator::Less {
-//            cursor.next();
-//        } else {
-//            break;
-//        }
-//    }
-    // Temp code until we handle all of the edge cases involving horizontal lines and intersections
-    cursor.reset();
-    let mut result = find_line_place(point, edge, *cursor.peek_next().unwrap());
-    while result != Comparator::Equal {
-        cursor.next();
-        result = find_line_place(point, edge, *cursor.peek_next().unwrap());
-    }
-
-    println!("Ending move_cursor to point");
-}
-
 /// Compares a line to the next one in the list
 /// Returns Equal if line and next_sl_edge.line are equal
 /// Returns Greater if Next current x is greater then events, if points are equal compares slopes
 /// Returns Less if Next current x is less then events, if points are equal compares slopes
-pub fn find_line_place(point: Point, edge: Edge, next_sl_edge : SweepLineEdge) -> Comparator {
+pub fn find_line_place(point: Point, edge: Edge, next_sl_edge : SweepLineEdge, precision: Precision) -> Comparator {
     let next_line = next_sl_edge.edge.line;
     if edge.line == next_line {
         return Comparator::Equal;
     }
+    if precision == Precision::Exact && next_line.point1.y != next_line.point2.y {
+        return find_line_place_exact(point, edge.line, next_line);
+    }
     // Get the point on the next line for the current y value we are at since that is how the
     // linked list is sorted.
     let next_x = next_sl_edge.edge.line.current_x_for_y(point.y);
     // if the point is the same as the next point or lines intersect and we need to look at the
-    // slope to determine the sorting order. We already know they have the same y value so we just
-    // look at the x values
+    // direction the lines leave in to determine the sorting order. We already know they have the
+    // same y value so we just look at the x values
     if point.x == next_x {
-        // compare the slopes of the lines
-        if edge.line.slope() < next_line.slope() {
-            return Comparator::Greater;
+        if crosses_below(edge.line, next_line, precision) {
+            Comparator::Less
         }
         else {
-            return Comparator::Less;
+            Comparator::Greater
         }
         // if the point is not on the nextLine we just need to see if it comes before or after
     } else if point.x < next_x {
-        return Comparator::Greater;
+        Comparator::Greater
     } else {
-        return Comparator::Less;
+        Comparator::Less
     }
 
 }
 
+/// Returns true if `line` heads further right than `next_line` below the sweep line, so that if
+/// `line` is left of `next_line` where they meet, they swap order there.
+fn crosses_below(line: LineSegment, next_line: LineSegment, precision: Precision) -> bool {
+    let (top, bottom) = (line.min_y_point(), line.max_y_point());
+    let (next_top, next_bottom) = (next_line.min_y_point(), next_line.max_y_point());
+    predicates::cross_sign(top, bottom, next_top, next_bottom, precision) == Ordering::Greater
+}
+
+/// The exact counterpart of find_line_place for a non-horizontal next_line.
+///
+/// The point's side of next_line is decided with an exact orientation test rather than by
+/// comparing rounded x values.  If the point is on next_line, the edge that heads further left
+/// below the point comes first.
+fn find_line_place_exact(point: Point, line: LineSegment, next_line: LineSegment) -> Comparator {
+    let (next_top, next_bottom) = (next_line.min_y_point(), next_line.max_y_point());
+    // With y pointing down, a point left of a downward line turns counter-clockwise in y-up terms
+    match predicates::orientation(next_top, next_bottom, point, Precision::Exact) {
+        Ordering::Greater => Comparator::Greater,
+        Ordering::Less => Comparator::Less,
+        Ordering::Equal => {
+            let (top, bottom) = (line.min_y_point(), line.max_y_point());
+            match predicates::cross_sign(top, bottom, next_top, next_bottom, Precision::Exact) {
+                Ordering::Less => Comparator::Greater,
+                _ => Comparator::Less,
+            }
+        },
+    }
+}
+
 /*
 add_to_traps(SL_edge edge, float bot, int mask, traps *traps)
     //mask is 0xFFFFFFFF if using winding rule, 0x1 if using even/odd rule
@@ -666,59 +888,49 @@ add_to_traps(SL_edge edge, float bot, int mask, traps *traps)
         right = edge.deferred_trap->right->LineSegment
         traps_push(left, right, edge.deferred_trap.top, bot)
 */
-fn add_to_traps(cursor: &mut Cursor<SweepLineEdge>, bottom: f32, mask: i32, traps: &mut Vec<Trapezoid>) {
-    println!("Starting add_to_traps");
-    if cursor.peek_prev().is_none() || cursor.peek_next().is_none() {
-        println!("Error: add_to_traps called when it shouldnt have");
-    }
-    // We unwrap because it should be considered a bug if this gets called when the value is
-    // incorrect
-    let sl_edge = *cursor.peek_prev().unwrap();
+fn add_to_traps(sl_list: &SweepLineList, gap: usize, bottom: f32,
+                inside: &dyn Fn(i32) -> bool, traps: &mut Vec<Slab>) {
+    // We index directly because it should be considered a bug if this gets called when there
+    // isn't an edge either side of the gap
+    let sl_edge = sl_list.edges[gap - 1];
 
     if sl_edge.trap_top >= bottom {
         return;
     }
 
-    let right = *cursor.peek_next().unwrap();
-    let mut in_out = 0;
-    let mut count = 0;
-    println!("   Starting cursor count loop");
-    while let Some(edge) = cursor.next() {
-        count += 1;
-        in_out += edge.edge.direction;
+    let right = sl_list.edges[gap];
+    let in_out: i32 = sl_list.edges[gap..].iter().map(|edge| edge.edge.direction).sum();
+
+    // Add a trapezoid if the winding number is inside
+    if inside(in_out) {
+        traps.push(Slab {
+            left: sl_edge.edge.line,
+            right: right.edge.line,
+            top: sl_edge.trap_top,
+            bottom,
+        });
     }
-    println!("   Ending cursor count loop");
-
-    in_out &= mask;
-
-    // Add a trapezoid if in_out isn't zero
-    if in_out != 0 {
-        let left = sl_edge.edge.line;
-        let right = right.edge.line;
-        let top_y = sl_edge.trap_top;
-        let trap = bo_trap_from_lines(&left, &right, top_y, bottom);
-        traps.push(trap)
-    }
-    //rewind cursor to starting position (+1 because loop advances past end)
-    cursor.seek_backward(count+1);
-    println!("Ending add_to_traps");
 }
 
+/// Returns the trapezoid between `left` and `right` from `top` to `bottom`, or None if it has no
+/// area, as between two edges that coincide.
 fn bo_trap_from_lines(left: &LineSegment,
                       right: &LineSegment,
                       top: f32,
-                      bottom: f32) -> Trapezoid {
-    println!("Starting Create Trap");
-    println!("left line: {:?}", left);
-    println!("right line: {:?}", right);
-    println!("top: {} bottom: {}", top, bottom);
+                      bottom: f32) -> Option<Trapezoid> {
     let top_left = Point::new(left.current_x_for_y(top),top);
     let top_right = Point::new(right.current_x_for_y(top),top);
     let bottom_left = Point::new(left.current_x_for_y(bottom),bottom);
     let bottom_right = Point::new(right.current_x_for_y(bottom),bottom);
+    if top_left == top_right && bottom_left == bottom_right {
+        return None;
+    }
 
-    println!("Ending Create Trap");
-    Trapezoid::from_points(top_left, top_right, bottom_left, bottom_right)
+    // The bases are known to be horizontal, so build from them rather than searching the four
+    // points for a parallel pair, which fails for triangles
+    let top_base = LineSegment::from_points(top_left, top_right);
+    let bottom_base = LineSegment::from_points(bottom_left, bottom_right);
+    Some(Trapezoid::from_parallel_bases(top_base, bottom_base))
 }
 
 #[cfg(test)]
@@ -738,8 +950,8 @@ mod tests {
 
         Edge{
             line: LineSegment::new(x1, y1, x2, y2),
-            top: top,
-            bottom: bottom,
+            top,
+            bottom,
             direction: dir,
 
         }
@@ -808,22 +1020,39 @@ mod tests {
     }
 
     #[test]
-    fn event_compare_type_greater(){
+    fn event_compare_same_event_equal(){
         let dummy = create_start_event(0., 0., 0., 0., 1);
-        assert_eq!(dummy.cmp(&dummy), Ordering::Greater);
+        assert_eq!(dummy.cmp(&dummy), Ordering::Equal);
+        assert!(dummy == create_start_event(0., 0., 0., 0., 1));
+        assert!(dummy != create_start_event(0., 0., 0., 0., -1));
+    }
+
+    #[test]
+    fn event_compare_heading(){
+        // Events at the same point are ordered by the way their edges leave it, leftmost first
+        let left = create_start_event(2., 0., 0., 4., 1);
+        let down = create_start_event(2., 0., 2., 4., 1);
+        let right = create_start_event(2., 0., 3., 1., 1);
+        assert_eq!(left.cmp(&down), Ordering::Less);
+        assert_eq!(down.cmp(&right), Ordering::Less);
+        assert_eq!(right.cmp(&left), Ordering::Greater);
+        // Ties on the heading fall back to the end points
+        let longer = create_start_event(2., 0., 2., 8., 1);
+        assert_eq!(down.cmp(&longer), Ordering::Less);
+        assert!(down != longer);
     }
 
     #[test]
     fn event_sorting_points() {
         // Verify that the events are sorted by points
-        let mut event_list = vec![
+        let mut event_list = [
             create_start_event(0., 2., 9., 9., 1),
             create_start_event(0., 1., 9., 9., 1),
             create_start_event(0., 3., 9., 9., 1),
         ];
 
         event_list.sort();
-        assert_eq!(event_list.get(0).unwrap().point.y, 1.);
+        assert_eq!(event_list.first().unwrap().point.y, 1.);
         assert_eq!(event_list.get(1).unwrap().point.y, 2.);
         assert_eq!(event_list.get(2).unwrap().point.y, 3.);
     }
@@ -831,7 +1060,7 @@ mod tests {
     #[test]
     fn event_sorting_type() {
         // Verify that the End event precedes the Start event for equal points
-        let mut event_list = vec![
+        let mut event_list = [
         create_start_event(0., 2., 9., 9., 1),
         create_start_event(0., 1., 9., 9., 1),
         create_start_event(0., 3., 9., 9., 1),
@@ -839,8 +1068,8 @@ mod tests {
         ];
 
         event_list.sort();
-        assert_eq!(event_list.get(0).unwrap().point.y, 1.);
-        assert_eq!(event_list.get(0).unwrap().event_type, EventType::End );
+        assert_eq!(event_list.first().unwrap().point.y, 1.);
+        assert_eq!(event_list.first().unwrap().event_type, EventType::End );
         assert_eq!(event_list.get(1).unwrap().point.y, 1.);
         assert_eq!(event_list.get(1).unwrap().event_type, EventType::Start );
     }
@@ -848,7 +1077,7 @@ mod tests {
     #[test]
     fn event_sorting_intersection() {
         // Verify that intersection is between start end end event for equal points
-        let mut event_list = vec![
+        let mut event_list = [
         create_start_event(0., 2., 9., 9., 1),
         create_start_event(0., 1., 9., 9., 1),
         create_intersection_event(0., 0., 0., 0., 1),
@@ -889,7 +1118,7 @@ mod tests {
         ];
 
         let event_list = event_list_from_edges(edges);
-        assert_eq!(event_list.get(0).unwrap().point, Point::new(0., 0.));
+        assert_eq!(event_list.first().unwrap().point, Point::new(0., 0.));
         assert_eq!(event_list.get(1).unwrap().point, Point::new(0., 1.));
         assert_eq!(event_list.get(2).unwrap().point, Point::new(1., 2.));
         assert_eq!(event_list.get(3).unwrap().point, Point::new(3., 4.));
@@ -907,7 +1136,7 @@ mod tests {
         ];
 
         let event_list = event_list_from_edges(edges);
-        assert_eq!(event_list.get(0).unwrap().event_type, EventType::Start);
+        assert_eq!(event_list.first().unwrap().event_type, EventType::Start);
         assert_eq!(event_list.get(1).unwrap().event_type, EventType::Start);
         assert_eq!(event_list.get(2).unwrap().event_type, EventType::Start);
         assert_eq!(event_list.get(3).unwrap().event_type, EventType::End);
@@ -923,12 +1152,66 @@ mod tests {
         ];
 
         let event_list = event_list_from_edges(edges);
-        assert_eq!(event_list.get(0).unwrap().point.x, 1.);
-        assert_eq!(event_list.get(0).unwrap().event_type, EventType::Start);
+        assert_eq!(event_list.first().unwrap().point.x, 1.);
+        assert_eq!(event_list.first().unwrap().event_type, EventType::Start);
         assert_eq!(event_list.get(1).unwrap().point.x, 3.);
         assert_eq!(event_list.get(1).unwrap().event_type, EventType::End);
     }
 
+    #[test]
+    fn event_list_from_edges_degenerate() {
+        // A zero-length edge has no events
+        let event_list = event_list_from_edges(vec![create_edge(2., 2., 2., 2., 0)]);
+        assert!(event_list.is_empty());
+
+        // Duplicates drawn the same way merge into one edge counting twice
+        let edges = vec![
+        create_edge(0., 0., 1., 4., 1),
+        create_edge(1., 4., 0., 0., 1),
+        ];
+        let event_list = event_list_from_edges(edges);
+        assert_eq!(event_list.len(), 2);
+        assert!(event_list.iter().all(|event| event.edge_left.direction == 2));
+
+        // Duplicates drawn opposite ways cancel out
+        let edges = vec![
+        create_edge(0., 0., 1., 4., 1),
+        create_edge(1., 4., 0., 0., -1),
+        ];
+        assert!(event_list_from_edges(edges).is_empty());
+
+        // A horizontal edge, however often it is repeated, gets one Start and one End
+        let edges = vec![
+        create_edge(1., 4., 3., 4., 0),
+        create_edge(3., 4., 1., 4., 0),
+        ];
+        let event_list = event_list_from_edges(edges);
+        assert_eq!(event_list.len(), 2);
+        assert_eq!(event_list[0].event_type, EventType::Start);
+        assert_eq!(event_list[1].event_type, EventType::End);
+    }
+
+    #[test]
+    fn sweep_merges_duplicate_edges() {
+        // Setup
+        // A square with its left side drawn three times, twice down and once back up
+        let square = vec![
+        create_edge(0., 0., 0., 4., 1),
+        create_edge(4., 4., 4., 0., -1),
+        ];
+        let mut repeated = square.clone();
+        repeated.push(create_edge(0., 0., 0., 4., 1));
+        repeated.push(create_edge(0., 4., 0., 0., -1));
+        repeated.push(create_edge(2., 2., 2., 2., 0));
+
+        // Call
+        let traps = sweep_with_fill_rule(repeated, FillRule::EvenOdd);
+
+        // Test
+        assert_eq!(traps.len(), sweep_with_fill_rule(square, FillRule::EvenOdd).len());
+        assert_eq!(traps.len(), 1);
+    }
+
     #[test]
     fn event_constructor() {
         let edge = create_edge(0., 0., 0., 0., 1);
@@ -1013,9 +1296,19 @@ mod tests {
         assert_eq!(traps.len(), 2);
     }
 
+    // Returns the total area of traps, whose bases are horizontal
+    fn traps_area(traps: &[Trapezoid]) -> f32 {
+        traps.iter().map(|trap| {
+            let (top, bottom) = (trap.lines[0], trap.lines[2]);
+            let width = |base: LineSegment| (base.point2.x - base.point1.x).abs();
+            (width(top) + width(bottom)) / 2. * (bottom.point1.y - top.point1.y).abs()
+        }).sum()
+    }
+
     #[test]
     fn sweep_test_intersect_four() {
-        // Expected to make 2 traps with the 2 lines that cross
+        // Expected to make 6 traps with the 4 lines that cross each other in four places: one
+        // above the crossings, two in each band between them, and one below
         let edges = vec![
         create_edge(0., 0., 4., 4., -1),
         create_edge(0., 2., 4., 6., -1),
@@ -1024,7 +1317,309 @@ mod tests {
         ];
 
         let traps = sweep(edges);
-        assert_eq!(traps.len(), 5);
+        assert_eq!(traps.len(), 6);
+        assert_eq!(traps_area(&traps), 12.);
+        assert!(traps.iter().any(|trap| trap.contains_point(&Point::new(1., 2.5))));
+        assert!(!traps.iter().any(|trap| trap.contains_point(&Point::new(2., 2.5))));
+    }
+
+    #[test]
+    fn sweep_test_self_intersecting_bowtie() {
+        // A polygon drawn (0, 0) -> (4, 4) -> (4, 0) -> (0, 4) crosses itself at (2, 2) and
+        // fills two triangles meeting there
+        let edges = vec![
+        create_edge(0., 0., 4., 4., 1),
+        create_edge(4., 4., 4., 0., -1),
+        create_edge(4., 0., 0., 4., 1),
+        create_edge(0., 4., 0., 0., -1),
+        ];
+
+        let traps = sweep(edges);
+        assert_eq!(traps_area(&traps), 8.);
+        let filled = |x, y| traps.iter().any(|trap| trap.contains_point(&Point::new(x, y)));
+        assert!(filled(0.5, 2.) && filled(3.5, 2.));
+        assert!(!filled(2., 0.5) && !filled(2., 3.5));
+    }
+
+    #[test]
+    fn sweep_test_event_limit() {
+        // The bowtie takes a Start and an End event for each of its four edges, and one event
+        // where two of them cross
+        let edges = vec![
+        create_edge(0., 0., 4., 4., 1),
+        create_edge(4., 4., 4., 0., -1),
+        create_edge(4., 0., 0., 4., 1),
+        create_edge(0., 4., 0., 0., -1),
+        ];
+
+        let traps = sweep_with_event_limit(edges.clone(), FillRule::Winding, 9).unwrap();
+        assert_eq!(traps_area(&traps), 8.);
+        assert!(sweep_with_event_limit(edges, FillRule::Winding, 8).is_none());
+    }
+
+    #[test]
+    fn sweep_test_three_cross_at_point() {
+        // Three edges crossing at (2, 2) must end up fully reversed below it
+        let edges = vec![
+        create_edge(0., 0., 4., 4., 1),
+        create_edge(2., 0., 2., 4., 1),
+        create_edge(4., 0., 0., 4., -1),
+        ];
+
+        for precision in [Precision::Fast, Precision::Exact] {
+            let traps = sweep_with_precision(edges.clone(), FillRule::Winding, precision);
+            assert_eq!(traps.len(), 3);
+            assert_eq!(traps_area(&traps), 6.);
+            let filled = |x, y| traps.iter().any(|trap| trap.contains_point(&Point::new(x, y)));
+            assert!(filled(2.5, 1.) && filled(1.5, 3.) && filled(2.5, 3.));
+            assert!(!filled(1.5, 1.));
+        }
+    }
+
+    // Checks that the trapezoids cover a grid of sample points exactly where the edges' winding
+    // number, counted along a ray to the right, is inside by `fill_rule`.  The grid is offset so
+    // that no sample falls on an edge.
+    fn assert_fills_winding(edges: &[Edge], fill_rule: FillRule, traps: &[Trapezoid],
+                            size: usize) {
+        for row in 0..size * 4 {
+            for column in 0..size * 4 {
+                let point = Point::new(column as f32 / 4. + 0.113, row as f32 / 4. + 0.171);
+                let winding: i32 = edges.iter().filter(|edge| {
+                    edge.top <= point.y && point.y < edge.bottom &&
+                    edge.line.current_x_for_y(point.y) > point.x
+                }).map(|edge| edge.direction).sum();
+                let filled = traps.iter().filter(|trap| trap.contains_point(&point)).count();
+                let inside = winding & winding_mask(fill_rule) != 0;
+                assert_eq!(filled, inside as usize, "at {:?}", point);
+            }
+        }
+    }
+
+    #[test]
+    fn sweep_test_six_cross_at_point() {
+        // Six edges through (5, 5), with the directions mixed so that the winding number
+        // changes across every one of them
+        let edges = vec![
+        create_edge(0., 0., 10., 10., 1),
+        create_edge(2., 0., 8., 10., 1),
+        create_edge(4., 0., 6., 10., -1),
+        create_edge(6., 0., 4., 10., 1),
+        create_edge(8., 0., 2., 10., -1),
+        create_edge(10., 0., 0., 10., -1),
+        ];
+
+        for precision in [Precision::Fast, Precision::Exact] {
+            for fill_rule in [FillRule::Winding, FillRule::EvenOdd] {
+                let traps = sweep_with_precision(edges.clone(), fill_rule, precision);
+                assert_fills_winding(&edges, fill_rule, &traps, 10);
+            }
+        }
+    }
+
+    #[test]
+    fn sweep_test_cross_at_inexact_point() {
+        // Four edges through (10/3, 10/3), which f32 can't represent, plus one that only
+        // nearly passes through it and has to be sorted by its own intersections, and one far to
+        // the right that brings the winding number back to zero
+        let edges = vec![
+        create_edge(0., 0., 10., 10., 1),
+        create_edge(10. / 3., 0., 10. / 3., 10., 1),
+        create_edge(5., 0., 0., 10., -1),
+        create_edge(-10. / 3., 0., 50. / 3., 10., 1),
+        create_edge(5.0015, 0., 0., 10., -1),
+        create_edge(20., 0., 20., 10., -1),
+        ];
+
+        let traps = sweep_with_precision(edges.clone(), FillRule::Winding, Precision::Exact);
+        assert_fills_winding(&edges, FillRule::Winding, &traps, 10);
+        let traps = sweep_with_precision(edges.clone(), FillRule::Winding, Precision::Fast);
+        assert_fills_winding(&edges, FillRule::Winding, &traps, 10);
+    }
+
+    #[test]
+    fn sweep_snaps_to_fixed_point() {
+        // The triangle's first two edges meet at a point computed two ways, which only agree to
+        // within rounding error.  Snapped, they meet exactly and every corner is on the grid.
+        let third = 1. / 3.;
+        let edges = vec![
+        create_edge(0., 0., 0.1 + 0.2, 3. * third, 1),
+        create_edge(0.3, 1., 2., 0., 1),
+        create_edge(2., 0., 0., 0., 1),
+        ];
+        let traps = sweep(edges);
+        for trap in &traps {
+            for line in trap.lines() {
+                assert_eq!(snap_point(line.point1), line.point1);
+            }
+        }
+        assert!((traps_area(&traps) - 1.).abs() < 0.01);
+    }
+
+    #[test]
+    fn order_below_sorts_by_heading() {
+        // Setup
+        let mut sl_edges = [
+            SweepLineEdge::new(0., 0., create_edge(0., 0., 4., 4., 1)),
+            SweepLineEdge::new(0., 0., create_edge(1., 0., 3., 4., 1)),
+            SweepLineEdge::new(0., 0., create_edge(2., 0., 2., 4., 1)),
+            SweepLineEdge::new(0., 0., create_edge(4., 0., 0., 4., 1)),
+        ];
+
+        // Call
+        order_below(&mut sl_edges, Precision::Fast);
+
+        // Test
+        let tops: Vec<f32> = sl_edges.iter().map(|sl_edge| sl_edge.edge.line.point1.x).collect();
+        assert_eq!(tops, [4., 2., 1., 0.]);
+    }
+
+    #[test]
+    fn sweep_test_intersect_after_end() {
+        // The outer edges only become neighbours when the short middle edge ends at y = 1, and
+        // must still be swapped where they cross at (2, 2)
+        let edges = vec![
+        create_edge(0., 0., 4., 4., 1),
+        create_edge(1., 0., 1., 1., 0),
+        create_edge(4., 0., 0., 4., -1),
+        ];
+
+        let traps = sweep(edges);
+        assert_eq!(traps_area(&traps), 8.);
+        let filled = |x, y| traps.iter().any(|trap| trap.contains_point(&Point::new(x, y)));
+        assert!(filled(2., 0.5) && filled(2., 3.5));
+        assert!(!filled(0.5, 2.) && !filled(3.5, 2.));
+    }
+
+    #[test]
+    fn sweep_test_fill_rules() {
+        // A square inside another, both drawn clockwise, is wound twice: filled by the winding
+        // rule but a hole under even-odd
+        let mut edges = Vec::new();
+        for &(min, max) in &[(0., 8.), (2., 6.)] {
+            edges.push(create_edge(min, min, max, min, 0));
+            edges.push(create_edge(max, min, max, max, 1));
+            edges.push(create_edge(max, max, min, max, 0));
+            edges.push(create_edge(min, max, min, min, -1));
+        }
+
+        let winding = sweep_with_fill_rule(edges.clone(), FillRule::Winding);
+        let even_odd = sweep_with_fill_rule(edges, FillRule::EvenOdd);
+
+        assert_eq!(traps_area(&winding), 64.);
+        assert_eq!(traps_area(&even_odd), 48.);
+        assert!(winding.iter().any(|trap| trap.contains_point(&Point::new(4., 4.))));
+        assert!(!even_odd.iter().any(|trap| trap.contains_point(&Point::new(4., 4.))));
+        assert!(even_odd.iter().any(|trap| trap.contains_point(&Point::new(1., 4.))));
+    }
+
+    #[test]
+    fn sweep_test_end_changes_winding_to_the_left() {
+        // A wide bar across the tops of two posts, all wound the same way.  Where the bar's
+        // edges end, the gap between the posts stops being filled even though neither of its
+        // own edges ends there.
+        let mut edges = Vec::new();
+        for &(left, top, right, bottom) in &[(3., 2., 12., 4.), (2., 3., 4., 12.),
+                                             (11., 3., 13., 12.)] {
+            edges.push(create_edge(right, top, right, bottom, 1));
+            edges.push(create_edge(left, bottom, left, top, -1));
+        }
+
+        let traps = sweep(edges);
+
+        assert_eq!(traps_area(&traps), 52.);
+        let filled = |x, y| traps.iter().any(|trap| trap.contains_point(&Point::new(x, y)));
+        assert!(filled(7., 3.5));
+        assert!(!filled(7., 8.));
+    }
+
+    #[test]
+    fn check_for_intersection_queues_once() {
+        // Setup
+        let left = create_edge(0., 0., 4., 4., 1);
+        let right = create_edge(4., 0., 0., 4., 1);
+        let sl_list = SweepLineList {
+            edges: vec![SweepLineEdge::new(0., 0., left), SweepLineEdge::new(0., 0., right)],
+        };
+        let mut events = EventQueue::default();
+
+        // Call
+        check_for_intersection(0., &sl_list, 1, &mut events, Precision::Fast);
+        check_for_intersection(0., &sl_list, 1, &mut events, Precision::Fast);
+
+        // Test
+        assert_eq!(events.len(), 1);
+        let event = events.pop().unwrap();
+        assert_eq!(event.event_type, EventType::Intersection);
+        assert_eq!(event.point, Point::new(2., 2.));
+        // Once past the crossing the pair is in order and isn't queued again
+        check_for_intersection(3., &sl_list, 1, &mut events, Precision::Fast);
+        assert!(events.pop().is_none());
+    }
+
+    #[test]
+    fn event_queue_pops_in_order() {
+        // Setup
+        let mut events = EventQueue::new(vec![
+            create_start_event(0., 2., 9., 9., 1),
+            create_end_event(0., 1., 9., 9., 1),
+            create_start_event(0., 1., 9., 9., 1),
+        ]);
+
+        // Call
+        events.push(create_intersection_event(0., 1., 9., 9., 1));
+        events.push(create_start_event(0., 0., 9., 9., 1));
+
+        // Test
+        let order: Vec<(f32, EventType)> = (0..5).map(|_| {
+            let event = events.pop().unwrap();
+            (event.point.y, event.event_type)
+        }).collect();
+        assert_eq!(order, [(0., EventType::Start), (1., EventType::End),
+                           (1., EventType::Intersection), (1., EventType::Start),
+                           (2., EventType::Start)]);
+        assert!(events.pop().is_none());
+
+        // The same intersection is only queued once, but the same start can be queued again
+        let crossing = || {
+            Event::new_intersection(create_edge(0., 0., 4., 4., 1), create_edge(4., 0., 0., 4., 1),
+                                    &Point::new(2., 2.))
+        };
+        assert!(events.push(crossing()));
+        assert!(!events.push(crossing()));
+        assert!(events.push(create_start_event(0., 1., 9., 9., 1)));
+        assert!(events.push(create_start_event(0., 1., 9., 9., 1)));
+        assert_eq!(events.len(), 3);
+
+        // Once it is taken off the queue, it can be queued again
+        while events.pop().is_some() {}
+        assert!(events.push(crossing()));
+        assert_eq!(events.len(), 1);
+    }
+
+    #[test]
+    fn sweep_line_list_finds_edges() {
+        // Setup
+        let edges = [create_edge(0., 0., 0., 4., 1), create_edge(2., 0., 2., 4., -1),
+                     create_edge(4., 0., 4., 4., 1)];
+        let sl_list = SweepLineList {
+            edges: edges.iter().map(|edge| SweepLineEdge::new(0., 0., *edge)).collect(),
+        };
+        let new_edge = create_edge(3., 2., 1., 4., 1);
+
+        // Call
+        let gap = sl_list.insert_position(Point::new(3., 2.), new_edge, Precision::Fast);
+
+        // Test
+        assert_eq!(gap, 2);
+        for (index, edge) in edges.iter().enumerate() {
+            assert_eq!(sl_list.position(&edge.line, Point::new(index as f32 * 2., 2.)),
+                       Some(index));
+            // A point nowhere near the edge only makes the search take longer
+            assert_eq!(sl_list.position(&edge.line, Point::new(10., 2.)), Some(index));
+        }
+        assert_eq!(sl_list.position(&new_edge.line, Point::new(3., 2.)), None);
+        assert_eq!(sl_list.neighbours(1).unwrap().1.line, edges[1].line);
+        assert!(sl_list.neighbours(3).is_none());
     }
 
     #[test]
@@ -1079,8 +1674,8 @@ mod tests {
 
         let traps = sweep(edges);
         assert_eq!(traps.len(), 1);
-        assert!(traps.get(0).unwrap().contains_point(&Point{x:1.,y:1.}));
-        assert!(!traps.get(0).unwrap().contains_point(&Point{x:3.,y:1.}));
+        assert!(traps.first().unwrap().contains_point(&Point{x:1.,y:1.}));
+        assert!(!traps.first().unwrap().contains_point(&Point{x:3.,y:1.}));
     }
 
     #[test]
@@ -1109,8 +1704,8 @@ mod tests {
 
         let traps = sweep(edges);
         assert_eq!(traps.len(), 2);
-        assert!(traps.get(0).unwrap().contains_point(&Point{x:1.,y:1.}));
-        assert!(!traps.get(0).unwrap().contains_point(&Point{x:3.,y:1.}));
+        assert!(traps.first().unwrap().contains_point(&Point{x:1.,y:1.}));
+        assert!(!traps.first().unwrap().contains_point(&Point{x:3.,y:1.}));
         assert!(traps.get(1).unwrap().contains_point(&Point{x:5.,y:1.}));
         assert!(!traps.get(1).unwrap().contains_point(&Point{x:3.,y:1.}));
     }
@@ -1144,6 +1739,59 @@ mod tests {
         assert_eq!(traps.len(), 2);
     }
 
+    #[test]
+    fn sweep_exact_matches_fast() {
+        // On well-behaved input exact predicates make the same decisions as fast ones
+        let shapes = vec![
+            vec![
+            create_edge(0., 0., 2., 0., 0),
+            create_edge(2., 0., 3., 3., 1),
+            create_edge(3., 3., 1., 3., 0),
+            create_edge(1., 3., 0., 0., -1),
+            ],
+            vec![
+            create_edge(2., 0., 4., 2., 1),
+            create_edge(4., 2., 2., 4., 1),
+            create_edge(2., 4., 0., 2., 1),
+            create_edge(0., 2., 2., 0., 1),
+            ],
+        ];
+
+        for edges in shapes {
+            let fast = sweep(edges.clone());
+            let exact = sweep_with_precision(edges, FillRule::Winding, Precision::Exact);
+            assert_eq!(fast.len(), exact.len());
+            for (fast_trap, exact_trap) in fast.iter().zip(exact.iter()) {
+                assert_eq!(fast_trap.lines, exact_trap.lines);
+            }
+        }
+    }
+
+    #[test]
+    fn find_line_place_exact_shared_point() {
+        // Two edges leave (2, 0); the one heading left below it must sort first
+        let point = Point::new(2., 0.);
+        let left = create_edge(2., 0., 0., 2., 1);
+        let right = create_edge(2., 0., 4., 2., 1);
+        let steep = create_edge(2., 0., 2.5, 4., 1);
+        let sl_edge = |edge: Edge| SweepLineEdge::new(0., 0., edge);
+
+        assert_eq!(find_line_place(point, left, sl_edge(right), Precision::Exact), Comparator::Greater);
+        assert_eq!(find_line_place(point, right, sl_edge(left), Precision::Exact), Comparator::Less);
+        assert_eq!(find_line_place(point, steep, sl_edge(right), Precision::Exact), Comparator::Greater);
+        assert_eq!(find_line_place(point, right, sl_edge(right), Precision::Exact), Comparator::Equal);
+    }
+
+    #[test]
+    fn find_line_place_exact_side() {
+        let next = SweepLineEdge::new(0., 0., create_edge(0., 0., 4., 4., 1));
+        let edge = create_edge(0., 0., 0., 0., 1);
+        assert_eq!(find_line_place(Point::new(1., 2.), edge, next, Precision::Exact),
+                   Comparator::Greater);
+        assert_eq!(find_line_place(Point::new(3., 2.), edge, next, Precision::Exact),
+                   Comparator::Less);
+    }
+
     // Tests that add_to_traps doesn't change the traps vector if the SweepLineEdge's top
     // is greater than the `bottom` arg passed in.
     #[test]
@@ -1162,14 +1810,11 @@ mod tests {
 
         // bottom is less than edge.top!
         let bottom = 0.;
-        let mask = 1;
-        let mut traps: Vec<Trapezoid> = Vec::new();
-        let mut sl_list: LinkedList<SweepLineEdge> = LinkedList::new();
-        sl_list.push_front(edge);
-        let mut cursor = sl_list.cursor();
-        cursor.next();
+        let inside = |winding: i32| winding & 1 != 0;
+        let mut traps: Vec<Slab> = Vec::new();
+        let sl_list = SweepLineList { edges: vec![edge, edge] };
         // Call
-        add_to_traps(&mut cursor, bottom, mask, &mut traps);
+        add_to_traps(&sl_list, 1, bottom, &inside, &mut traps);
         assert_eq!(traps.len(), 0);
     }
 
@@ -1198,18 +1843,14 @@ mod tests {
             }
         };
 
-        let mut sl_list: LinkedList<SweepLineEdge> = LinkedList::new();
-        sl_list.push_front(edge1);
-        sl_list.push_back(edge2);
+        let sl_list = SweepLineList { edges: vec![edge1, edge2] };
 
         let bottom = 20.;
-        let mask = 1;
-        let mut traps: Vec<Trapezoid> = Vec::new();
+        let inside = |winding: i32| winding & 1 != 0;
+        let mut traps: Vec<Slab> = Vec::new();
 
-        let mut cursor = sl_list.cursor();
-        cursor.next();
         // Call
-        add_to_traps(&mut cursor, bottom, mask, &mut traps);
-        assert!(traps.len() > 0);
+        add_to_traps(&sl_list, 1, bottom, &inside, &mut traps);
+        assert!(!traps.is_empty());
     }
 }
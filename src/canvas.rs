@@ -0,0 +1,550 @@
+/*
+ * Cairus - a reimplementation of the cairo graphics library in Rust
+ *
+ * Copyright © 2017 CairusOrg
+ *
+ * This library is free software; you can redistribute it and/or
+ * modify it either under the terms of the GNU Lesser General Public
+ * License version 2.1 as published by the Free Software Foundation
+ * (the "LGPL") or, at your option, under the terms of the Mozilla
+ * Public License Version 2.0 (the "MPL"). If you do not alter this
+ * notice, a recipient may use your version of this file under either
+ * the MPL or the LGPL.
+ *
+ * You should have received a copy of the LGPL along with this library
+ * in the file LICENSE-LGPL-2_1; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Suite 500, Boston, MA 02110-1335, USA
+ * You should have received a copy of the MPL along with this library
+ * in the file LICENSE-MPL-2_0
+ *
+ * The contents of this file are subject to the Mozilla Public License
+ * Version 2.0 (the "License"); you may not use this file except in
+ * compliance with the License. You may obtain a copy of the License at
+ * http://www.mozilla.org/MPL/
+ *
+ * This software is distributed on an "AS IS" basis, WITHOUT WARRANTY
+ * OF ANY KIND, either express or implied. See the LGPL or the MPL for
+ * the specific language governing rights and limitations.
+ *
+ * The Original Code is the cairus graphics library.
+ *
+ * Contributor(s):
+ *	CairusOrg
+ *
+ */
+
+//! An HTML5-canvas-style convenience layer over `Context`.
+//!
+//! `Canvas` mirrors the shape of the CanvasRenderingContext2D API (fillRect, strokeRect,
+//! beginPath, arc, globalAlpha, globalCompositeOperation and so on) with Rust naming, so code
+//! ported from JavaScript maps onto cairus line by line.  It is a thin wrapper: every call is
+//! translated into `Context` operations.
+//!
+//! Only what Context can draw today is supported.  Paths can be stroked, but `fill` only fills
+//! the rectangles and full circles in the path, and there is no text or image drawing yet.
+
+use std::f32::consts::PI;
+use common_geometry::{Ellipse, LineSegment, Point, Rectangle};
+use context::Context;
+use operators::Operator;
+use surfaces::ImageSurface;
+use types::ParseNameError;
+
+// The largest distance a flattened arc may stray from the true arc
+const ARC_TOLERANCE: f32 = 0.1;
+
+// A piece of the current path
+enum PathItem {
+    // Connected points, and whether the last connects back to the first
+    Polyline(Vec<Point>, bool),
+    Rectangle(Rectangle),
+    Circle(Ellipse),
+}
+
+/// ## Canvas
+///
+/// Draws on a surface through a `Context`, the way a 2D canvas context draws on a canvas.
+pub struct Canvas<'a> {
+    context: Context<'a>,
+    fill_style: (f32, f32, f32, f32),
+    stroke_style: (f32, f32, f32, f32),
+    global_alpha: f32,
+    path: Vec<PathItem>,
+}
+
+impl<'a> Canvas<'a> {
+    /// Returns a new Canvas drawing on `target`, with canvas defaults: black fill and stroke
+    /// styles, a line width of 1, a global alpha of 1 and "source-over" compositing.
+    pub fn new(target: &'a mut ImageSurface) -> Canvas<'a> {
+        let mut context = Context::create(target);
+        context.set_line_width(1.);
+        Canvas {
+            context,
+            fill_style: (0., 0., 0., 1.),
+            stroke_style: (0., 0., 0., 1.),
+            global_alpha: 1.,
+            path: Vec::new(),
+        }
+    }
+
+    /// Returns the underlying Context, for anything the canvas API doesn't cover.  Its matrix
+    /// applies to everything the canvas draws, as a canvas's transform does.
+    pub fn context(&mut self) -> &mut Context<'a> {
+        &mut self.context
+    }
+
+    /// Sets the color used by fill_rect() and fill().  Like `fillStyle`.
+    pub fn set_fill_style(&mut self, red: f32, green: f32, blue: f32, alpha: f32) {
+        self.fill_style = (red, green, blue, alpha);
+    }
+
+    /// Sets the color used by stroke_rect() and stroke().  Like `strokeStyle`.
+    pub fn set_stroke_style(&mut self, red: f32, green: f32, blue: f32, alpha: f32) {
+        self.stroke_style = (red, green, blue, alpha);
+    }
+
+    /// Sets the width of stroked lines.  Like `lineWidth`.
+    pub fn set_line_width(&mut self, width: f32) {
+        self.context.set_line_width(width);
+    }
+
+    /// Returns the width of stroked lines.
+    pub fn line_width(&self) -> f32 {
+        self.context.get_line_width()
+    }
+
+    /// Sets an opacity applied to everything drawn, clamped to [0, 1].  Like `globalAlpha`.
+    pub fn set_global_alpha(&mut self, alpha: f32) {
+        self.global_alpha = alpha.clamp(0., 1.);
+    }
+
+    /// Returns the opacity applied to everything drawn.
+    pub fn global_alpha(&self) -> f32 {
+        self.global_alpha
+    }
+
+    /// Sets the compositing operation by its canvas name.  Like `globalCompositeOperation`.
+    ///
+    /// Canvas names the operators differently from cairo: "source-over" is `Operator::Over`,
+    /// "copy" is `Operator::Source`, "destination-in" is `Operator::DestIn`, "lighter" is
+    /// `Operator::Add`, "hue" is `Operator::HslHue` and so on, while the separable blend modes
+    /// share their names.  Unknown or unsupported
+    /// names leave the operation unchanged and return an error.
+    pub fn set_global_composite_operation(&mut self, name: &str) -> Result<(), ParseNameError> {
+        let operator = match name.trim() {
+            "source-over" => Operator::Over,
+            "source-in" => Operator::In,
+            "copy" => Operator::Source,
+            "source-out" => Operator::Out,
+            "source-atop" => Operator::Atop,
+            "destination-over" => Operator::DestOver,
+            "destination-in" => Operator::DestIn,
+            "destination-out" => Operator::DestOut,
+            "destination-atop" => Operator::DestAtop,
+            "xor" => Operator::Xor,
+            "lighter" => Operator::Add,
+            "multiply" => Operator::Multiply,
+            "screen" => Operator::Screen,
+            "overlay" => Operator::Overlay,
+            "darken" => Operator::Darken,
+            "lighten" => Operator::Lighten,
+            "color-dodge" => Operator::ColorDodge,
+            "color-burn" => Operator::ColorBurn,
+            "hard-light" => Operator::HardLight,
+            "soft-light" => Operator::SoftLight,
+            "difference" => Operator::Difference,
+            "exclusion" => Operator::Exclusion,
+            "hue" => Operator::HslHue,
+            "saturation" => Operator::HslSaturation,
+            "color" => Operator::HslColor,
+            "luminosity" => Operator::HslLuminosity,
+            _ => return Err(ParseNameError::new("composite operation", name)),
+        };
+        self.context.set_operator(operator);
+        Ok(())
+    }
+
+    /// Returns the canvas name of the current compositing operation.
+    ///
+    /// Separable blend modes, and operators canvas has no name for set through `context()`,
+    /// report their cairo name.
+    pub fn global_composite_operation(&self) -> &'static str {
+        match *self.context.get_operator() {
+            Operator::Over => "source-over",
+            Operator::In => "source-in",
+            Operator::Source => "copy",
+            Operator::Out => "source-out",
+            Operator::Atop => "source-atop",
+            Operator::DestOver => "destination-over",
+            Operator::DestIn => "destination-in",
+            Operator::DestOut => "destination-out",
+            Operator::DestAtop => "destination-atop",
+            Operator::Xor => "xor",
+            Operator::Add => "lighter",
+            Operator::HslHue => "hue",
+            Operator::HslSaturation => "saturation",
+            Operator::HslColor => "color",
+            Operator::HslLuminosity => "luminosity",
+            operator => operator.name(),
+        }
+    }
+
+    /// Fills a rectangle with the fill style.  Like `fillRect`.
+    pub fn fill_rect(&mut self, x: f32, y: f32, width: f32, height: f32) {
+        self.use_style(self.fill_style);
+        self.context.fill_rectangles(&[Rectangle::new(x, y, width, height)]);
+    }
+
+    /// Strokes the outline of a rectangle with the stroke style and line width.  Like
+    /// `strokeRect`, the line is centered on the rectangle's edges and the corners are mitered.
+    pub fn stroke_rect(&mut self, x: f32, y: f32, width: f32, height: f32) {
+        let rectangle = Rectangle::new(x, y, width, height).normalized();
+        let half = self.line_width() / 2.;
+        if half <= 0. {
+            return;
+        }
+
+        // The outline is the outer rectangle minus the inner one, as up to four bands
+        let (left, top) = (rectangle.x - half, rectangle.y - half);
+        let (outer_width, side_height) = (rectangle.width + 2. * half, rectangle.height - 2. * half);
+        let mut bands = vec![
+            Rectangle::new(left, top, outer_width, 2. * half),
+            Rectangle::new(left, rectangle.y + rectangle.height - half, outer_width, 2. * half),
+        ];
+        if side_height > 0. {
+            bands.push(Rectangle::new(left, rectangle.y + half, 2. * half, side_height));
+            bands.push(Rectangle::new(rectangle.x + rectangle.width - half, rectangle.y + half,
+                                      2. * half, side_height));
+        }
+        self.use_style(self.stroke_style);
+        self.context.fill_rectangles(&bands);
+    }
+
+    /// Makes a rectangle fully transparent.  Like `clearRect`, this ignores the global alpha
+    /// and compositing operation.
+    pub fn clear_rect(&mut self, x: f32, y: f32, width: f32, height: f32) {
+        let operator = self.global_composite_operation();
+        self.context.set_operator(Operator::Source);
+        self.context.set_source_rgba(0., 0., 0., 0.);
+        self.context.fill_rectangles(&[Rectangle::new(x, y, width, height)]);
+        self.set_global_composite_operation(operator).unwrap();
+    }
+
+    /// Starts a new, empty path.  Like `beginPath`.
+    pub fn begin_path(&mut self) {
+        self.path.clear();
+    }
+
+    /// Starts a new subpath at (x, y).  Like `moveTo`.
+    pub fn move_to(&mut self, x: f32, y: f32) {
+        self.path.push(PathItem::Polyline(vec![Point::new(x, y)], false));
+    }
+
+    /// Adds a straight line to (x, y) to the current subpath.  Like `lineTo`.
+    pub fn line_to(&mut self, x: f32, y: f32) {
+        self.current_polyline().push(Point::new(x, y));
+    }
+
+    /// Closes the current subpath with a line back to its start.  Like `closePath`.
+    pub fn close_path(&mut self) {
+        if let Some(&mut PathItem::Polyline(ref points, ref mut closed)) = self.path.last_mut() {
+            if points.len() > 1 {
+                *closed = true;
+            }
+        }
+    }
+
+    /// Adds a closed rectangle subpath.  Like `rect`.
+    pub fn rect(&mut self, x: f32, y: f32, width: f32, height: f32) {
+        self.path.push(PathItem::Rectangle(Rectangle::new(x, y, width, height)));
+    }
+
+    /// Adds a circular arc centered on (x, y) to the current subpath.  Like `arc`.
+    ///
+    /// Angles are in radians, measured clockwise on screen from the positive x axis, and the arc
+    /// runs clockwise unless `anticlockwise` is true.  A full circle that starts its own subpath
+    /// can also be filled.
+    pub fn arc(&mut self, x: f32, y: f32, radius: f32, start_angle: f32, end_angle: f32,
+               anticlockwise: bool) {
+        let radius = radius.abs();
+        let full_turn = 2. * PI;
+        let mut sweep = end_angle - start_angle;
+        if !anticlockwise && sweep < 0. {
+            sweep = sweep % full_turn + full_turn;
+        } else if anticlockwise && sweep > 0. {
+            sweep = sweep % full_turn - full_turn;
+        }
+        let sweep = sweep.clamp(-full_turn, full_turn);
+
+        let starts_subpath = match self.path.last() {
+            Some(&PathItem::Polyline(ref points, false)) => points.len() < 2,
+            _ => true,
+        };
+        if starts_subpath && sweep.abs() >= full_turn && radius > 0. {
+            self.path.push(PathItem::Circle(Ellipse::circle(x, y, radius)));
+            return;
+        }
+
+        // Flatten the arc into chords that stay within ARC_TOLERANCE of it
+        let step = if radius > ARC_TOLERANCE {
+            2. * (1. - ARC_TOLERANCE / radius).acos()
+        } else {
+            full_turn
+        };
+        let chords = ((sweep.abs() / step).ceil() as usize).max(1);
+        for chord in 0..chords + 1 {
+            let angle = start_angle + sweep * chord as f32 / chords as f32;
+            let point = Point::new(x + radius * angle.cos(), y + radius * angle.sin());
+            if chord == 0 && starts_subpath {
+                self.move_to(point.x, point.y);
+            } else {
+                self.line_to(point.x, point.y);
+            }
+        }
+    }
+
+    /// Fills the current path with the fill style.  Like `fill`, but only the rectangles and
+    /// full circles in the path are filled for now; other subpaths are ignored.
+    pub fn fill(&mut self) {
+        let mut rectangles = Vec::new();
+        let mut circles = Vec::new();
+        for item in self.path.iter() {
+            match *item {
+                PathItem::Rectangle(rectangle) => rectangles.push(rectangle),
+                PathItem::Circle(circle) => circles.push(circle),
+                PathItem::Polyline(..) => {},
+            }
+        }
+        self.use_style(self.fill_style);
+        self.context.fill_rectangles(&rectangles);
+        self.context.fill_ellipses(&circles);
+    }
+
+    /// Strokes the current path with the stroke style and line width.  Like `stroke`, except
+    /// that segments are stroked with butt ends and no joins.
+    pub fn stroke(&mut self) {
+        let mut segments = Vec::new();
+        for item in self.path.iter() {
+            let (points, closed) = match *item {
+                PathItem::Polyline(ref points, closed) => (points.clone(), closed),
+                PathItem::Rectangle(r) => (vec![Point::new(r.x, r.y),
+                                                Point::new(r.x + r.width, r.y),
+                                                Point::new(r.x + r.width, r.y + r.height),
+                                                Point::new(r.x, r.y + r.height)], true),
+                PathItem::Circle(circle) => (circle_points(&circle), true),
+            };
+            for pair in points.windows(2) {
+                segments.push(LineSegment::from_points(pair[0], pair[1]));
+            }
+            if closed && points.len() > 1 {
+                segments.push(LineSegment::from_points(points[points.len() - 1], points[0]));
+            }
+        }
+        self.use_style(self.stroke_style);
+        self.context.stroke_segments(&segments);
+    }
+
+    // Sets the context's source to `style`, faded by the global alpha.
+    fn use_style(&mut self, style: (f32, f32, f32, f32)) {
+        let (red, green, blue, alpha) = style;
+        self.context.set_source_rgba(red, green, blue, alpha * self.global_alpha);
+    }
+
+    // Returns the points of the current subpath, starting one at the origin if there is none.
+    fn current_polyline(&mut self) -> &mut Vec<Point> {
+        let needs_subpath = match self.path.last() {
+            Some(&PathItem::Polyline(_, closed)) => closed,
+            _ => true,
+        };
+        if needs_subpath {
+            // Like canvas, a line with no current point starts where it is drawn to
+            self.path.push(PathItem::Polyline(Vec::new(), false));
+        }
+        match self.path.last_mut() {
+            Some(&mut PathItem::Polyline(ref mut points, _)) => points,
+            _ => unreachable!(),
+        }
+    }
+}
+
+// Flattens a circle into points within ARC_TOLERANCE of it.
+fn circle_points(circle: &Ellipse) -> Vec<Point> {
+    let radius = circle.radius_x;
+    let step = if radius > ARC_TOLERANCE {
+        2. * (1. - ARC_TOLERANCE / radius).acos()
+    } else {
+        2. * PI
+    };
+    let chords = ((2. * PI / step).ceil() as usize).max(3);
+    (0..chords)
+        .map(|chord| {
+            let angle = 2. * PI * chord as f32 / chords as f32;
+            Point::new(circle.center.x + radius * angle.cos(), circle.center.y + radius * angle.sin())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use surfaces::{Format, ImageSurface};
+    use types::Rgba;
+    use operators::Operator;
+    use super::Canvas;
+
+    #[test]
+    fn canvas_defaults() {
+        let mut surface = ImageSurface::create_for_format(Format::ARGB32, 10, 10);
+        let canvas = Canvas::new(&mut surface);
+        assert_eq!(canvas.line_width(), 1.);
+        assert_eq!(canvas.global_alpha(), 1.);
+        assert_eq!(canvas.global_composite_operation(), "source-over");
+    }
+
+    #[test]
+    fn canvas_fill_rect_with_global_alpha() {
+        // Setup
+        let mut surface = ImageSurface::create_for_format(Format::ARGB32, 10, 10);
+
+        // Call
+        {
+            let mut canvas = Canvas::new(&mut surface);
+            canvas.set_fill_style(1., 0., 0., 1.);
+            canvas.set_global_alpha(0.5);
+            canvas.fill_rect(2., 2., 4., 4.);
+        }
+
+        // Test
+        assert_eq!(*surface.get(3, 3).unwrap(), Rgba::new(1., 0., 0., 0.5));
+        assert_eq!(surface.get(7, 7).unwrap().alpha, 0.);
+    }
+
+    #[test]
+    fn canvas_fill_rect_follows_context_matrix() {
+        // Setup
+        let mut surface = ImageSurface::create_for_format(Format::ARGB32, 10, 10);
+
+        // Call
+        {
+            let mut canvas = Canvas::new(&mut surface);
+            canvas.context().translate(1., 0.);
+            canvas.context().scale(2., 2.);
+            canvas.fill_rect(1., 1., 2., 2.);
+        }
+
+        // Test
+        // The rect covers 3..7 across and 2..6 down
+        for y in 0..10 {
+            for x in 0..10 {
+                let inside = (3..7).contains(&x) && (2..6).contains(&y);
+                let expected = if inside { 1. } else { 0. };
+                assert_eq!(surface.get(x, y).unwrap().alpha, expected, "({}, {})", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn canvas_stroke_rect() {
+        // A 2 wide stroke is centered on the edges, so a 4x4 rect at (3, 3) covers 2..8 with a
+        // hole from 4 to 6
+        let mut surface = ImageSurface::create_for_format(Format::ARGB32, 10, 10);
+        {
+            let mut canvas = Canvas::new(&mut surface);
+            canvas.set_line_width(2.);
+            canvas.stroke_rect(3., 3., 4., 4.);
+        }
+
+        for y in 0..10 {
+            for x in 0..10 {
+                let outer = (2..8).contains(&x) && (2..8).contains(&y);
+                let inner = (4..6).contains(&x) && (4..6).contains(&y);
+                let expected = if outer && !inner { 1. } else { 0. };
+                assert_eq!(surface.get(x, y).unwrap().alpha, expected, "({}, {})", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn canvas_clear_rect() {
+        let mut surface = ImageSurface::create_for_format(Format::ARGB32, 10, 10);
+        {
+            let mut canvas = Canvas::new(&mut surface);
+            canvas.fill_rect(0., 0., 10., 10.);
+            canvas.clear_rect(0., 0., 5., 10.);
+            assert_eq!(canvas.global_composite_operation(), "source-over");
+        }
+
+        assert_eq!(surface.get(2, 2).unwrap().alpha, 0.);
+        assert_eq!(surface.get(7, 2).unwrap().alpha, 1.);
+    }
+
+    #[test]
+    fn canvas_composite_operation_names() {
+        let mut surface = ImageSurface::create_for_format(Format::ARGB32, 1, 1);
+        let mut canvas = Canvas::new(&mut surface);
+
+        assert!(canvas.set_global_composite_operation("copy").is_ok());
+        assert_eq!(canvas.global_composite_operation(), "copy");
+        assert!(canvas.set_global_composite_operation("source-under").is_err());
+        assert_eq!(canvas.global_composite_operation(), "copy");
+        assert!(canvas.set_global_composite_operation("lighter").is_ok());
+        assert_eq!(*canvas.context().get_operator(), Operator::Add);
+        assert_eq!(canvas.global_composite_operation(), "lighter");
+        assert!(canvas.set_global_composite_operation("color-burn").is_ok());
+        assert_eq!(canvas.global_composite_operation(), "color-burn");
+
+        // Operators without a canvas name report their cairo name
+        canvas.context().set_operator(Operator::Saturate);
+        assert_eq!(canvas.global_composite_operation(), "saturate");
+    }
+
+    #[test]
+    fn canvas_path_fill_and_stroke() {
+        // Setup
+        let mut surface = ImageSurface::create_for_format(Format::ARGB32, 20, 20);
+
+        // Call
+        {
+            let mut canvas = Canvas::new(&mut surface);
+            canvas.set_fill_style(0., 0., 1., 1.);
+            canvas.begin_path();
+            canvas.arc(5., 5., 3., 0., 7., false);
+            canvas.rect(12., 12., 4., 4.);
+            canvas.fill();
+
+            canvas.begin_path();
+            canvas.move_to(0., 18.5);
+            canvas.line_to(10., 18.5);
+            canvas.stroke();
+        }
+
+        // Test
+        assert_eq!(*surface.get(5, 5).unwrap(), Rgba::new(0., 0., 1., 1.));
+        assert_eq!(*surface.get(13, 13).unwrap(), Rgba::new(0., 0., 1., 1.));
+        assert_eq!(*surface.get(5, 18).unwrap(), Rgba::new(0., 0., 0., 1.));
+        assert_eq!(surface.get(15, 18).unwrap().alpha, 0.);
+    }
+
+    #[test]
+    fn canvas_stroke_arc_and_closed_path() {
+        let mut surface = ImageSurface::create_for_format(Format::ARGB32, 20, 20);
+        {
+            let mut canvas = Canvas::new(&mut surface);
+            canvas.set_line_width(2.);
+            canvas.arc(10., 10., 6., 0., ::std::f32::consts::PI, false);
+            canvas.stroke();
+
+            canvas.begin_path();
+            canvas.move_to(1., 1.);
+            canvas.line_to(5., 1.);
+            canvas.line_to(5., 5.);
+            canvas.close_path();
+            canvas.stroke();
+        }
+
+        // The bottom of the half circle, but not the top
+        assert_eq!(surface.get(10, 15).unwrap().alpha, 1.);
+        assert_eq!(surface.get(10, 4).unwrap().alpha, 0.);
+        // The closing diagonal of the triangle
+        assert!(surface.get(3, 3).unwrap().alpha > 0.5);
+    }
+}
@@ -0,0 +1,858 @@
+/*
+ * Cairus - a reimplementation of the cairo graphics library in Rust
+ *
+ * Copyright © 2017 CairusOrg
+ *
+ * This library is free software; you can redistribute it and/or
+ * modify it either under the terms of the GNU Lesser General Public
+ * License version 2.1 as published by the Free Software Foundation
+ * (the "LGPL") or, at your option, under the terms of the Mozilla
+ * Public License Version 2.0 (the "MPL"). If you do not alter this
+ * notice, a recipient may use your version of this file under either
+ * the MPL or the LGPL.
+ *
+ * You should have received a copy of the LGPL along with this library
+ * in the file LICENSE-LGPL-2_1; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Suite 500, Boston, MA 02110-1335, USA
+ * You should have received a copy of the MPL along with this library
+ * in the file LICENSE-MPL-2_0
+ *
+ * The contents of this file are subject to the Mozilla Public License
+ * Version 2.0 (the "License"); you may not use this file except in
+ * compliance with the License. You may obtain a copy of the License at
+ * http://www.mozilla.org/MPL/
+ *
+ * This software is distributed on an "AS IS" basis, WITHOUT WARRANTY
+ * OF ANY KIND, either express or implied. See the LGPL or the MPL for
+ * the specific language governing rights and limitations.
+ *
+ * The Original Code is the cairus graphics library.
+ *
+ * Contributor(s):
+ *	CairusOrg
+ *
+ */
+
+//! Deferred rendering through recorded commands.
+//!
+//! A `CommandBuffer` records the same calls a `Context` accepts, without a surface.  The recorded
+//! commands are plain data, so a buffer can be built on one thread, validated, batched and sent
+//! to a render thread, and executed against a surface there.  The drawing code doesn't change:
+//! it calls the same methods on a buffer instead of a context.
+//!
+//! Everything that affects what is drawn is recorded: sources, operators, line and fill
+//! settings, transformations, paths, clipping and the drawing operations themselves.  Settings
+//! that only choose how a context renders, such as its rasterizer and thread count, are left to
+//! the context the buffer is executed on.
+
+use std::error::Error;
+use std::fmt;
+use common_geometry::{Ellipse, LineSegment, Point, Rectangle};
+use context::Context;
+use dash::DashPattern;
+use matrix::Matrix;
+use operators::Operator;
+use path::Path;
+use pattern::Pattern;
+use text::FontFace;
+use types::{Antialias, FillRule, FontSlant, FontWeight, LineCap, LineJoin, Rgba, SubpixelOrder};
+
+/// A single recorded `Context` operation.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    SetSourceRgba(f32, f32, f32, f32),
+    SetSource(Pattern),
+    SetOperator(Operator),
+    SetLineWidth(f32),
+    SetLineJoin(LineJoin),
+    SetLineCap(LineCap),
+    SetMiterLimit(f32),
+    SetDash(Vec<f64>, f64),
+    SetFillRule(FillRule),
+    SetAntialias(Antialias),
+    SetSubpixelOrder(SubpixelOrder),
+    SetTolerance(f32),
+    SetCoverageOnly(bool),
+    Translate(f32, f32),
+    Scale(f32, f32),
+    Rotate(f32),
+    Transform(Matrix),
+    SetMatrix(Matrix),
+    IdentityMatrix,
+    NewPath,
+    MoveTo(f32, f32),
+    LineTo(f32, f32),
+    CurveTo(f32, f32, f32, f32, f32, f32),
+    QuadTo(f32, f32, f32, f32),
+    RelMoveTo(f32, f32),
+    RelLineTo(f32, f32),
+    RelCurveTo(f32, f32, f32, f32, f32, f32),
+    Arc(f32, f32, f32, f32, f32),
+    ArcNegative(f32, f32, f32, f32, f32),
+    ClosePath,
+    AppendPath(Path),
+    Fill,
+    Stroke,
+    Clip,
+    ClipPreserve,
+    ResetClip,
+    FillRectangles(Vec<Rectangle>),
+    FillEllipses(Vec<Ellipse>),
+    StrokeSegments(Vec<LineSegment>),
+    StrokePolylineGradient(Vec<Point>, Rgba),
+    Paint,
+    Mask(Pattern),
+    PushGroup,
+    PopGroupToSource,
+    SelectFontFace(String, FontSlant, FontWeight),
+    SetFontFace(FontFace),
+    SetFontSize(f32),
+    SetFontMatrix(Matrix),
+    ShowText(String),
+    TextPath(String),
+    ShowPage,
+    CopyPage,
+}
+
+impl Command {
+    /// Performs the command on `context`, with `base` standing in for the identity
+    /// transformation, as `CommandBuffer::execute` describes.
+    pub fn execute(&self, context: &mut Context, base: &Matrix) {
+        match *self {
+            Command::SetSourceRgba(red, green, blue, alpha) => {
+                context.set_source_rgba(red, green, blue, alpha)
+            },
+            Command::SetSource(ref source) => context.set_source(source.clone()),
+            Command::SetOperator(operator) => context.set_operator(operator),
+            Command::SetLineWidth(width) => context.set_line_width(width),
+            Command::SetLineJoin(line_join) => context.set_line_join(line_join),
+            Command::SetLineCap(line_cap) => context.set_line_cap(line_cap),
+            Command::SetMiterLimit(limit) => context.set_miter_limit(limit),
+            // An invalid pattern leaves the current one in place, as it would have when
+            // recorded against a context
+            Command::SetDash(ref dashes, offset) => {
+                let _ = context.set_dash(dashes, offset);
+            },
+            Command::SetFillRule(fill_rule) => context.set_fill_rule(fill_rule),
+            Command::SetAntialias(antialias) => context.set_antialias(antialias),
+            Command::SetSubpixelOrder(order) => context.set_subpixel_order(order),
+            Command::SetTolerance(tolerance) => context.set_tolerance(tolerance),
+            Command::SetCoverageOnly(coverage_only) => context.set_coverage_only(coverage_only),
+            Command::Translate(tx, ty) => context.translate(tx, ty),
+            Command::Scale(sx, sy) => context.scale(sx, sy),
+            Command::Rotate(radians) => context.rotate(radians),
+            Command::Transform(ref matrix) => context.transform(matrix),
+            Command::SetMatrix(ref matrix) => context.set_matrix(matrix.multiply(base)),
+            Command::IdentityMatrix => context.set_matrix(*base),
+            Command::NewPath => context.new_path(),
+            Command::MoveTo(x, y) => context.move_to(x, y),
+            Command::LineTo(x, y) => context.line_to(x, y),
+            Command::CurveTo(x1, y1, x2, y2, x3, y3) => {
+                context.curve_to(x1, y1, x2, y2, x3, y3)
+            },
+            Command::QuadTo(x1, y1, x2, y2) => context.quad_to(x1, y1, x2, y2),
+            Command::RelMoveTo(dx, dy) => context.rel_move_to(dx, dy),
+            Command::RelLineTo(dx, dy) => context.rel_line_to(dx, dy),
+            Command::RelCurveTo(dx1, dy1, dx2, dy2, dx3, dy3) => {
+                context.rel_curve_to(dx1, dy1, dx2, dy2, dx3, dy3)
+            },
+            Command::Arc(xc, yc, radius, angle1, angle2) => {
+                context.arc(xc, yc, radius, angle1, angle2)
+            },
+            Command::ArcNegative(xc, yc, radius, angle1, angle2) => {
+                context.arc_negative(xc, yc, radius, angle1, angle2)
+            },
+            Command::ClosePath => context.close_path(),
+            Command::AppendPath(ref path) => context.append_path(path),
+            Command::Fill => context.fill(),
+            Command::Stroke => context.stroke(),
+            Command::Clip => context.clip(),
+            Command::ClipPreserve => context.clip_preserve(),
+            Command::ResetClip => context.reset_clip(),
+            Command::FillRectangles(ref rectangles) => context.fill_rectangles(rectangles),
+            Command::FillEllipses(ref ellipses) => context.fill_ellipses(ellipses),
+            Command::StrokeSegments(ref segments) => context.stroke_segments(segments),
+            Command::StrokePolylineGradient(ref points, ref end_color) => {
+                context.stroke_polyline_gradient(points, end_color)
+            },
+            Command::Paint => context.paint(),
+            Command::Mask(ref mask) => context.mask(mask),
+            Command::PushGroup => context.push_group(),
+            Command::PopGroupToSource => context.pop_group_to_source(),
+            Command::SelectFontFace(ref family, slant, weight) => {
+                context.select_font_face(family, slant, weight)
+            },
+            Command::SetFontFace(ref face) => context.set_font_face(face.clone()),
+            Command::SetFontSize(size) => context.set_font_size(size),
+            Command::SetFontMatrix(matrix) => context.set_font_matrix(matrix),
+            Command::ShowText(ref text) => context.show_text(text),
+            Command::TextPath(ref text) => context.text_path(text),
+            Command::ShowPage => context.show_page(),
+            Command::CopyPage => context.copy_page(),
+        }
+    }
+}
+
+/// The error returned by `CommandBuffer::validate` for a command that can't be executed
+/// meaningfully.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InvalidCommand {
+    /// Position of the offending command in the buffer.
+    pub index: usize,
+    /// What is wrong with it.
+    pub reason: &'static str,
+}
+
+impl fmt::Display for InvalidCommand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "command {}: {}", self.index, self.reason)
+    }
+}
+
+impl Error for InvalidCommand {}
+
+/// ## CommandBuffer
+///
+/// A recorded list of `Context` operations, replayed in order by `execute`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CommandBuffer {
+    commands: Vec<Command>,
+}
+
+impl CommandBuffer {
+    /// Returns a new, empty CommandBuffer.
+    pub fn new() -> CommandBuffer {
+        CommandBuffer { commands: Vec::new() }
+    }
+
+    /// Returns the recorded commands, in order.
+    pub fn commands(&self) -> &[Command] {
+        &self.commands
+    }
+
+    /// Returns the number of recorded commands.
+    pub fn len(&self) -> usize {
+        self.commands.len()
+    }
+
+    /// Returns true if nothing has been recorded.
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+
+    /// Removes every recorded command, keeping the allocation for reuse.
+    pub fn clear(&mut self) {
+        self.commands.clear();
+    }
+
+    /// Records a command directly.
+    pub fn push(&mut self, command: Command) {
+        self.commands.push(command);
+    }
+
+    /// Records `Context::set_source_rgba`.
+    pub fn set_source_rgba(&mut self, red: f32, green: f32, blue: f32, alpha: f32) {
+        self.push(Command::SetSourceRgba(red, green, blue, alpha));
+    }
+
+    /// Records `Context::set_source`.
+    pub fn set_source(&mut self, source: Pattern) {
+        self.push(Command::SetSource(source));
+    }
+
+    /// Records `Context::set_operator`.
+    pub fn set_operator(&mut self, operator: Operator) {
+        self.push(Command::SetOperator(operator));
+    }
+
+    /// Records `Context::set_line_width`.
+    pub fn set_line_width(&mut self, width: f32) {
+        self.push(Command::SetLineWidth(width));
+    }
+
+    /// Records `Context::set_line_join`.
+    pub fn set_line_join(&mut self, line_join: LineJoin) {
+        self.push(Command::SetLineJoin(line_join));
+    }
+
+    /// Records `Context::set_line_cap`.
+    pub fn set_line_cap(&mut self, line_cap: LineCap) {
+        self.push(Command::SetLineCap(line_cap));
+    }
+
+    /// Records `Context::set_miter_limit`.
+    pub fn set_miter_limit(&mut self, limit: f32) {
+        self.push(Command::SetMiterLimit(limit));
+    }
+
+    /// Records `Context::set_dash`.  A pattern the context would refuse is recorded anyway, and
+    /// reported by `validate`.
+    pub fn set_dash(&mut self, dashes: &[f64], offset: f64) {
+        self.push(Command::SetDash(dashes.to_vec(), offset));
+    }
+
+    /// Records `Context::set_fill_rule`.
+    pub fn set_fill_rule(&mut self, fill_rule: FillRule) {
+        self.push(Command::SetFillRule(fill_rule));
+    }
+
+    /// Records `Context::set_antialias`.
+    pub fn set_antialias(&mut self, antialias: Antialias) {
+        self.push(Command::SetAntialias(antialias));
+    }
+
+    /// Records `Context::set_subpixel_order`.
+    pub fn set_subpixel_order(&mut self, order: SubpixelOrder) {
+        self.push(Command::SetSubpixelOrder(order));
+    }
+
+    /// Records `Context::set_tolerance`.
+    pub fn set_tolerance(&mut self, tolerance: f32) {
+        self.push(Command::SetTolerance(tolerance));
+    }
+
+    /// Records `Context::set_coverage_only`.
+    pub fn set_coverage_only(&mut self, coverage_only: bool) {
+        self.push(Command::SetCoverageOnly(coverage_only));
+    }
+
+    /// Records `Context::translate`.
+    pub fn translate(&mut self, tx: f32, ty: f32) {
+        self.push(Command::Translate(tx, ty));
+    }
+
+    /// Records `Context::scale`.
+    pub fn scale(&mut self, sx: f32, sy: f32) {
+        self.push(Command::Scale(sx, sy));
+    }
+
+    /// Records `Context::rotate`.
+    pub fn rotate(&mut self, radians: f32) {
+        self.push(Command::Rotate(radians));
+    }
+
+    /// Records `Context::transform`.
+    pub fn transform(&mut self, matrix: &Matrix) {
+        self.push(Command::Transform(*matrix));
+    }
+
+    /// Records `Context::set_matrix`.
+    pub fn set_matrix(&mut self, matrix: Matrix) {
+        self.push(Command::SetMatrix(matrix));
+    }
+
+    /// Records `Context::identity_matrix`.
+    pub fn identity_matrix(&mut self) {
+        self.push(Command::IdentityMatrix);
+    }
+
+    /// Records `Context::new_path`.
+    pub fn new_path(&mut self) {
+        self.push(Command::NewPath);
+    }
+
+    /// Records `Context::move_to`.
+    pub fn move_to(&mut self, x: f32, y: f32) {
+        self.push(Command::MoveTo(x, y));
+    }
+
+    /// Records `Context::line_to`.
+    pub fn line_to(&mut self, x: f32, y: f32) {
+        self.push(Command::LineTo(x, y));
+    }
+
+    /// Records `Context::curve_to`.
+    pub fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x3: f32, y3: f32) {
+        self.push(Command::CurveTo(x1, y1, x2, y2, x3, y3));
+    }
+
+    /// Records `Context::quad_to`.
+    pub fn quad_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32) {
+        self.push(Command::QuadTo(x1, y1, x2, y2));
+    }
+
+    /// Records `Context::rel_move_to`.
+    pub fn rel_move_to(&mut self, dx: f32, dy: f32) {
+        self.push(Command::RelMoveTo(dx, dy));
+    }
+
+    /// Records `Context::rel_line_to`.
+    pub fn rel_line_to(&mut self, dx: f32, dy: f32) {
+        self.push(Command::RelLineTo(dx, dy));
+    }
+
+    /// Records `Context::rel_curve_to`.
+    pub fn rel_curve_to(&mut self, dx1: f32, dy1: f32, dx2: f32, dy2: f32, dx3: f32, dy3: f32) {
+        self.push(Command::RelCurveTo(dx1, dy1, dx2, dy2, dx3, dy3));
+    }
+
+    /// Records `Context::arc`.
+    pub fn arc(&mut self, xc: f32, yc: f32, radius: f32, angle1: f32, angle2: f32) {
+        self.push(Command::Arc(xc, yc, radius, angle1, angle2));
+    }
+
+    /// Records `Context::arc_negative`.
+    pub fn arc_negative(&mut self, xc: f32, yc: f32, radius: f32, angle1: f32, angle2: f32) {
+        self.push(Command::ArcNegative(xc, yc, radius, angle1, angle2));
+    }
+
+    /// Records `Context::close_path`.
+    pub fn close_path(&mut self) {
+        self.push(Command::ClosePath);
+    }
+
+    /// Records `Context::append_path`.
+    pub fn append_path(&mut self, path: &Path) {
+        self.push(Command::AppendPath(path.clone()));
+    }
+
+    /// Records `Context::fill`.
+    pub fn fill(&mut self) {
+        self.push(Command::Fill);
+    }
+
+    /// Records `Context::stroke`.
+    pub fn stroke(&mut self) {
+        self.push(Command::Stroke);
+    }
+
+    /// Records `Context::clip`.
+    pub fn clip(&mut self) {
+        self.push(Command::Clip);
+    }
+
+    /// Records `Context::clip_preserve`.
+    pub fn clip_preserve(&mut self) {
+        self.push(Command::ClipPreserve);
+    }
+
+    /// Records `Context::reset_clip`.
+    pub fn reset_clip(&mut self) {
+        self.push(Command::ResetClip);
+    }
+
+    /// Records `Context::fill_rectangles`.
+    pub fn fill_rectangles(&mut self, rectangles: &[Rectangle]) {
+        self.push(Command::FillRectangles(rectangles.to_vec()));
+    }
+
+    /// Records `Context::fill_ellipses`.
+    pub fn fill_ellipses(&mut self, ellipses: &[Ellipse]) {
+        self.push(Command::FillEllipses(ellipses.to_vec()));
+    }
+
+    /// Records `Context::stroke_segments`.
+    pub fn stroke_segments(&mut self, segments: &[LineSegment]) {
+        self.push(Command::StrokeSegments(segments.to_vec()));
+    }
+
+    /// Records `Context::stroke_polyline_gradient`.
+    pub fn stroke_polyline_gradient(&mut self, points: &[Point], end_color: &Rgba) {
+        self.push(Command::StrokePolylineGradient(points.to_vec(), *end_color));
+    }
+
+    /// Records `Context::paint`.
+    pub fn paint(&mut self) {
+        self.push(Command::Paint);
+    }
+
+    /// Records `Context::mask`.
+    pub fn mask(&mut self, mask: Pattern) {
+        self.push(Command::Mask(mask));
+    }
+
+    /// Records `Context::select_font_face`.
+    pub fn select_font_face(&mut self, family: &str, slant: FontSlant, weight: FontWeight) {
+        self.push(Command::SelectFontFace(family.to_string(), slant, weight));
+    }
+
+    /// Records `Context::set_font_face`.
+    pub fn set_font_face(&mut self, face: FontFace) {
+        self.push(Command::SetFontFace(face));
+    }
+
+    /// Records `Context::set_font_size`.
+    pub fn set_font_size(&mut self, size: f32) {
+        self.push(Command::SetFontSize(size));
+    }
+
+    /// Records `Context::set_font_matrix`.
+    pub fn set_font_matrix(&mut self, matrix: Matrix) {
+        self.push(Command::SetFontMatrix(matrix));
+    }
+
+    /// Records `Context::push_group`.
+    pub fn push_group(&mut self) {
+        self.push(Command::PushGroup);
+    }
+
+    /// Records `Context::pop_group_to_source`.
+    pub fn pop_group_to_source(&mut self) {
+        self.push(Command::PopGroupToSource);
+    }
+
+    /// Records `Context::show_text`.
+    pub fn show_text(&mut self, text: &str) {
+        self.push(Command::ShowText(text.to_string()));
+    }
+
+    /// Records `Context::text_path`.
+    pub fn text_path(&mut self, text: &str) {
+        self.push(Command::TextPath(text.to_string()));
+    }
+
+    /// Records `Context::show_page`.
+    pub fn show_page(&mut self) {
+        self.push(Command::ShowPage);
+    }
+
+    /// Records `Context::copy_page`.
+    pub fn copy_page(&mut self) {
+        self.push(Command::CopyPage);
+    }
+
+    /// Checks every command for values that can't be drawn meaningfully, such as NaN or infinite
+    /// coordinates and negative line widths, and returns the first problem found.
+    ///
+    /// Context quietly tolerates most of these, so validating is optional; it is meant for
+    /// buffers built from untrusted input, before they are sent off to be executed.
+    pub fn validate(&self) -> Result<(), InvalidCommand> {
+        for (index, command) in self.commands.iter().enumerate() {
+            let invalid = |reason| Err(InvalidCommand { index, reason });
+            let finite = |values: &[f32]| values.iter().all(|value| value.is_finite());
+            match *command {
+                Command::SetSourceRgba(red, green, blue, alpha) => {
+                    if !finite(&[red, green, blue, alpha]) {
+                        return invalid("source color is not finite");
+                    }
+                },
+                Command::SetLineWidth(width) => {
+                    if !width.is_finite() || width < 0. {
+                        return invalid("line width is negative or not finite");
+                    }
+                },
+                Command::FillRectangles(ref rectangles) => {
+                    if !rectangles.iter().all(|r| finite(&[r.x, r.y, r.width, r.height])) {
+                        return invalid("rectangle is not finite");
+                    }
+                },
+                Command::FillEllipses(ref ellipses) => {
+                    if !ellipses.iter().all(|e| {
+                        finite(&[e.center.x, e.center.y, e.radius_x, e.radius_y])
+                    }) {
+                        return invalid("ellipse is not finite");
+                    }
+                },
+                Command::StrokeSegments(ref segments) => {
+                    if !segments.iter().all(|s| {
+                        finite(&[s.point1.x, s.point1.y, s.point2.x, s.point2.y])
+                    }) {
+                        return invalid("segment is not finite");
+                    }
+                },
+                Command::StrokePolylineGradient(ref points, color) => {
+                    if !points.iter().all(|p| finite(&[p.x, p.y])) {
+                        return invalid("polyline point is not finite");
+                    }
+                    if !finite(&[color.red, color.green, color.blue, color.alpha]) {
+                        return invalid("gradient end color is not finite");
+                    }
+                },
+                Command::SetMiterLimit(value) | Command::SetTolerance(value) |
+                Command::Rotate(value) | Command::SetFontSize(value) => {
+                    if !value.is_finite() {
+                        return invalid("value is not finite");
+                    }
+                },
+                Command::SetDash(ref dashes, offset) => {
+                    let dashes: Vec<f32> = dashes.iter().map(|&dash| dash as f32).collect();
+                    if !dashes.is_empty() && DashPattern::new(&dashes, offset as f32).is_none() {
+                        return invalid("dash pattern is invalid");
+                    }
+                },
+                Command::Translate(x, y) | Command::Scale(x, y) | Command::MoveTo(x, y) |
+                Command::LineTo(x, y) | Command::RelMoveTo(x, y) | Command::RelLineTo(x, y) => {
+                    if !finite(&[x, y]) {
+                        return invalid("coordinate is not finite");
+                    }
+                },
+                Command::CurveTo(x1, y1, x2, y2, x3, y3) |
+                Command::RelCurveTo(x1, y1, x2, y2, x3, y3) => {
+                    if !finite(&[x1, y1, x2, y2, x3, y3]) {
+                        return invalid("coordinate is not finite");
+                    }
+                },
+                Command::QuadTo(x1, y1, x2, y2) => {
+                    if !finite(&[x1, y1, x2, y2]) {
+                        return invalid("coordinate is not finite");
+                    }
+                },
+                Command::Arc(xc, yc, radius, angle1, angle2) |
+                Command::ArcNegative(xc, yc, radius, angle1, angle2) => {
+                    if !finite(&[xc, yc, radius, angle1, angle2]) {
+                        return invalid("arc is not finite");
+                    }
+                },
+                Command::Transform(matrix) | Command::SetMatrix(matrix) |
+                Command::SetFontMatrix(matrix) => {
+                    if !finite(&[matrix.xx, matrix.yx, matrix.xy, matrix.yy, matrix.x0,
+                                 matrix.y0]) {
+                        return invalid("matrix is not finite");
+                    }
+                },
+                Command::SetSource(_) | Command::SetOperator(_) | Command::SetLineJoin(_) |
+                Command::SetLineCap(_) | Command::SetFillRule(_) | Command::SetAntialias(_) |
+                Command::SetSubpixelOrder(_) | Command::SetCoverageOnly(_) |
+                Command::IdentityMatrix | Command::NewPath |
+                Command::ClosePath | Command::AppendPath(_) | Command::Fill | Command::Stroke |
+                Command::Clip | Command::ClipPreserve | Command::ResetClip | Command::Paint |
+                Command::Mask(_) | Command::PushGroup | Command::PopGroupToSource |
+                Command::SelectFontFace(..) | Command::SetFontFace(_) |
+                Command::ShowText(_) | Command::TextPath(_) | Command::ShowPage |
+                Command::CopyPage => {},
+            }
+        }
+        Ok(())
+    }
+
+    /// Merges commands that can be executed together without changing the result.
+    ///
+    /// Consecutive state changes of the same kind collapse into the last one.  Consecutive fills
+    /// or strokes of the same kind merge into a single batch when their pixel bounds don't
+    /// overlap; overlapping ones stay separate, because compositing twice where they overlap is
+    /// not the same as filling their union once.  Strokes are only merged after the buffer has
+    /// set a line width, since their extent depends on it.
+    pub fn batch(&mut self) {
+        let mut batched: Vec<Command> = Vec::with_capacity(self.commands.len());
+        let mut line_width = None;
+        for command in self.commands.drain(..) {
+            if let Command::SetLineWidth(width) = command {
+                line_width = Some(width.max(0.));
+            }
+            let merged = match (batched.last_mut(), command) {
+                (Some(&mut Command::SetSourceRgba(..)), next @ Command::SetSourceRgba(..)) |
+                (Some(&mut Command::SetOperator(_)), next @ Command::SetOperator(_)) |
+                (Some(&mut Command::SetLineWidth(_)), next @ Command::SetLineWidth(_)) |
+                (Some(&mut Command::SetCoverageOnly(_)), next @ Command::SetCoverageOnly(_)) |
+                // Font sizes and font matrices both replace the font matrix
+                (Some(&mut Command::SetFontSize(_)), next @ Command::SetFontSize(_)) |
+                (Some(&mut Command::SetFontSize(_)), next @ Command::SetFontMatrix(_)) |
+                (Some(&mut Command::SetFontMatrix(_)), next @ Command::SetFontSize(_)) |
+                (Some(&mut Command::SetFontMatrix(_)), next @ Command::SetFontMatrix(_)) => {
+                    *batched.last_mut().unwrap() = next;
+                    None
+                },
+                (Some(&mut Command::FillRectangles(ref mut batch)),
+                 Command::FillRectangles(next)) => {
+                    merge_disjoint(batch, next, rectangle_bounds, 0., Command::FillRectangles)
+                },
+                (Some(&mut Command::FillEllipses(ref mut batch)), Command::FillEllipses(next)) => {
+                    merge_disjoint(batch, next, ellipse_bounds, 0., Command::FillEllipses)
+                },
+                (Some(&mut Command::StrokeSegments(ref mut batch)),
+                 Command::StrokeSegments(next)) if line_width.is_some() => {
+                    // Half the width, plus a pixel for antialiasing
+                    let margin = line_width.unwrap() / 2. + 1.;
+                    merge_disjoint(batch, next, segment_bounds, margin, Command::StrokeSegments)
+                },
+                (_, command) => Some(command),
+            };
+            if let Some(command) = merged {
+                batched.push(command);
+            }
+        }
+        self.commands = batched;
+    }
+
+    /// Replays every command, in order, on `context`.
+    ///
+    /// The context's transformation when execution starts stands in for the identity: recorded
+    /// set_matrix() and identity_matrix() calls are applied on top of it, so a buffer can be
+    /// executed scaled or moved by transforming the context first.
+    pub fn execute(&self, context: &mut Context) {
+        let base = context.get_matrix();
+        for command in self.commands.iter() {
+            command.execute(context, &base);
+        }
+    }
+}
+
+// Pixel bounds as (left, top, right, bottom), grown to whole pixels.
+type Bounds = (f32, f32, f32, f32);
+
+fn rectangle_bounds(rectangle: &Rectangle) -> Bounds {
+    let r = rectangle.normalized();
+    (r.x, r.y, r.x + r.width, r.y + r.height)
+}
+
+fn ellipse_bounds(ellipse: &Ellipse) -> Bounds {
+    let (x, y) = (ellipse.center.x, ellipse.center.y);
+    (x - ellipse.radius_x, y - ellipse.radius_y, x + ellipse.radius_x, y + ellipse.radius_y)
+}
+
+fn segment_bounds(segment: &LineSegment) -> Bounds {
+    let (a, b) = (segment.point1, segment.point2);
+    (a.x.min(b.x), a.y.min(b.y), a.x.max(b.x), a.y.max(b.y))
+}
+
+// Returns the union of the bounds of `shapes`, grown to whole pixels plus `margin`.
+fn batch_bounds<T, F: Fn(&T) -> Bounds>(shapes: &[T], bounds: F, margin: f32) -> Option<Bounds> {
+    shapes.iter().map(bounds).fold(None, |total, (left, top, right, bottom)| {
+        let grown = ((left - margin).floor(), (top - margin).floor(),
+                     (right + margin).ceil(), (bottom + margin).ceil());
+        Some(match total {
+            None => grown,
+            Some((l, t, r, b)) => (l.min(grown.0), t.min(grown.1), r.max(grown.2), b.max(grown.3)),
+        })
+    })
+}
+
+// Appends `next` to `batch` if their pixel bounds, grown by `margin`, don't overlap, otherwise returns `next` as a
+// command of its own.
+fn merge_disjoint<T, F, C>(batch: &mut Vec<T>, next: Vec<T>, bounds: F, margin: f32, command: C)
+                           -> Option<Command>
+    where F: Fn(&T) -> Bounds, C: Fn(Vec<T>) -> Command {
+    let disjoint = match (batch_bounds(batch, &bounds, margin),
+                          batch_bounds(&next, &bounds, margin)) {
+        (Some(a), Some(b)) => a.2 <= b.0 || b.2 <= a.0 || a.3 <= b.1 || b.3 <= a.1,
+        _ => true,
+    };
+    if disjoint {
+        batch.extend(next);
+        None
+    } else {
+        Some(command(next))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use common_geometry::{Ellipse, LineSegment, Point, Rectangle};
+    use context::Context;
+    use operators::Operator;
+    use surfaces::{Format, ImageSurface};
+    use types::Rgba;
+    use super::{Command, CommandBuffer, InvalidCommand};
+
+    // Records the same drawing into a buffer for the tests below
+    fn scene() -> CommandBuffer {
+        let mut buffer = CommandBuffer::new();
+        buffer.set_source_rgba(1., 0., 0., 1.);
+        buffer.fill_rectangles(&[Rectangle::new(0., 0., 4., 4.)]);
+        buffer.set_source_rgba(0., 1., 0., 1.);
+        buffer.set_source_rgba(0., 0., 1., 0.5);
+        buffer.fill_ellipses(&[Ellipse::circle(150., 150., 5.)]);
+        buffer.fill_ellipses(&[Ellipse::circle(10., 10., 5.)]);
+        buffer.set_line_width(1.);
+        buffer.stroke_segments(&[LineSegment::new(0., 15.5, 20., 15.5)]);
+        buffer.stroke_polyline_gradient(&[Point::new(0., 18.), Point::new(20., 18.)],
+                                        &Rgba::new(1., 1., 1., 1.));
+        buffer
+    }
+
+    #[test]
+    fn command_buffer_records() {
+        let buffer = scene();
+        assert_eq!(buffer.len(), 9);
+        assert_eq!(buffer.commands()[0], Command::SetSourceRgba(1., 0., 0., 1.));
+        assert_eq!(buffer.commands()[8],
+                   Command::StrokePolylineGradient(vec![Point::new(0., 18.), Point::new(20., 18.)],
+                                                   Rgba::new(1., 1., 1., 1.)));
+    }
+
+    #[test]
+    fn command_buffer_execute_matches_context() {
+        // Setup
+        let buffer = scene();
+        let mut recorded = ImageSurface::create_for_format(Format::ARGB32, 20, 20);
+        let mut direct = ImageSurface::create_for_format(Format::ARGB32, 20, 20);
+
+        // Call
+        buffer.execute(&mut Context::create(&mut recorded));
+        {
+            let mut context = Context::create(&mut direct);
+            context.set_source_rgba(1., 0., 0., 1.);
+            context.fill_rectangles(&[Rectangle::new(0., 0., 4., 4.)]);
+            context.set_source_rgba(0., 0., 1., 0.5);
+            context.fill_ellipses(&[Ellipse::circle(150., 150., 5.)]);
+            context.fill_ellipses(&[Ellipse::circle(10., 10., 5.)]);
+            context.set_line_width(1.);
+            context.stroke_segments(&[LineSegment::new(0., 15.5, 20., 15.5)]);
+            context.stroke_polyline_gradient(&[Point::new(0., 18.), Point::new(20., 18.)],
+                                             &Rgba::new(1., 1., 1., 1.));
+        }
+
+        // Test
+        for (a, b) in recorded.iter().zip(direct.iter()) {
+            assert_eq!(a, b);
+        }
+    }
+
+    #[test]
+    fn command_buffer_batch() {
+        // Setup
+        let mut buffer = scene();
+        let mut unbatched = ImageSurface::create_for_format(Format::ARGB32, 20, 20);
+        scene().execute(&mut Context::create(&mut unbatched));
+
+        // Call
+        buffer.batch();
+
+        // Test
+        // The two source changes collapse, and the two far apart ellipse fills merge
+        assert_eq!(buffer.len(), 7);
+        assert_eq!(buffer.commands()[2], Command::SetSourceRgba(0., 0., 1., 0.5));
+        assert_eq!(buffer.commands()[3], Command::FillEllipses(vec![Ellipse::circle(150., 150., 5.),
+                                                                    Ellipse::circle(10., 10., 5.)]));
+        let mut batched = ImageSurface::create_for_format(Format::ARGB32, 20, 20);
+        buffer.execute(&mut Context::create(&mut batched));
+        for (a, b) in batched.iter().zip(unbatched.iter()) {
+            assert_eq!(a, b);
+        }
+    }
+
+    #[test]
+    fn command_buffer_batch_keeps_overlaps_apart() {
+        let mut buffer = CommandBuffer::new();
+        buffer.fill_rectangles(&[Rectangle::new(0., 0., 4., 4.)]);
+        buffer.fill_rectangles(&[Rectangle::new(2., 2., 4., 4.)]);
+        buffer.set_operator(Operator::Source);
+
+        buffer.batch();
+
+        assert_eq!(buffer.len(), 3);
+    }
+
+    #[test]
+    fn command_buffer_validate() {
+        assert_eq!(scene().validate(), Ok(()));
+
+        let mut buffer = CommandBuffer::new();
+        buffer.paint();
+        buffer.set_line_width(-2.);
+        assert_eq!(buffer.validate(),
+                   Err(InvalidCommand { index: 1, reason: "line width is negative or not finite" }));
+
+        buffer.clear();
+        buffer.stroke_segments(&[LineSegment::new(0., f32::NAN, 1., 1.)]);
+        assert_eq!(buffer.validate().unwrap_err().index, 0);
+
+        buffer.clear();
+        buffer.move_to(0., 0.);
+        buffer.line_to(f32::INFINITY, 1.);
+        assert_eq!(buffer.validate(),
+                   Err(InvalidCommand { index: 1, reason: "coordinate is not finite" }));
+
+        buffer.clear();
+        buffer.set_dash(&[], 0.);
+        buffer.set_dash(&[0., 0.], 0.);
+        assert_eq!(buffer.validate(),
+                   Err(InvalidCommand { index: 1, reason: "dash pattern is invalid" }));
+    }
+
+    #[test]
+    fn command_buffer_is_send() {
+        fn assert_send<T: Send>(_: T) {}
+        assert_send(scene());
+    }
+}
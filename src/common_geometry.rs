@@ -39,6 +39,9 @@ use std::ops::{Add, Sub};
 use std::f32;
 use types::{Pixel, IntoPixels};
 
+/// Relative tolerance `LineSegment::intersection` allows for f32 rounding.
+const INTERSECTION_EPSILON: f64 = 4. * f32::EPSILON as f64;
+
 /// ## Point
 ///
 /// Defines a point by two floating points x and y.
@@ -59,8 +62,8 @@ impl Point{
     ///Creates a Point with user defined values
     pub fn new(x:f32, y:f32)->Point{
         Point{
-            x: x,
-            y: y,
+            x,
+            y,
         }
     }
 }
@@ -79,6 +82,123 @@ impl Sub for Point {
     }
 }
 
+/// ## Rectangle
+///
+/// Defines an axis-aligned rectangle by its top-left corner, width and height.  Analogous to
+/// cairo_rectangle_t.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Rectangle {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Rectangle {
+    /// Returns a new Rectangle with its top-left corner at (x, y).
+    pub fn new(x: f32, y: f32, width: f32, height: f32) -> Rectangle {
+        Rectangle {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// Returns this rectangle with a negative width or height flipped to be positive, so that
+    /// (x, y) is always the top-left corner.
+    pub fn normalized(&self) -> Rectangle {
+        let (x, width) = if self.width < 0. {
+            (self.x + self.width, -self.width)
+        } else {
+            (self.x, self.width)
+        };
+        let (y, height) = if self.height < 0. {
+            (self.y + self.height, -self.height)
+        } else {
+            (self.y, self.height)
+        };
+        Rectangle::new(x, y, width, height)
+    }
+
+    /// Returns true if the rectangle covers no area.
+    pub fn is_empty(&self) -> bool {
+        self.width == 0. || self.height == 0.
+    }
+}
+
+/// ## RectangleInt
+///
+/// An axis-aligned rectangle on whole pixels, by its top-left corner, width and height.
+/// Analogous to cairo_rectangle_int_t, and the building block of a `Region`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct RectangleInt {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl RectangleInt {
+    /// Returns a new RectangleInt with its top-left corner at (x, y).
+    pub fn new(x: i32, y: i32, width: i32, height: i32) -> RectangleInt {
+        RectangleInt {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// Returns true if the rectangle covers no pixels, including when its width or height is
+    /// negative.
+    pub fn is_empty(&self) -> bool {
+        self.width <= 0 || self.height <= 0
+    }
+}
+
+/// ## Ellipse
+///
+/// Defines an axis-aligned ellipse by its center and its horizontal and vertical radii.  A circle
+/// is an ellipse with equal radii.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Ellipse {
+    pub center: Point,
+    pub radius_x: f32,
+    pub radius_y: f32,
+}
+
+impl Ellipse {
+    /// Returns a new Ellipse centered on (x, y).  Negative radii are treated as positive.
+    pub fn new(x: f32, y: f32, radius_x: f32, radius_y: f32) -> Ellipse {
+        Ellipse {
+            center: Point::new(x, y),
+            radius_x: radius_x.abs(),
+            radius_y: radius_y.abs(),
+        }
+    }
+
+    /// Returns a new circle centered on (x, y).
+    pub fn circle(x: f32, y: f32, radius: f32) -> Ellipse {
+        Ellipse::new(x, y, radius, radius)
+    }
+
+    /// Returns true if the ellipse covers no area.
+    pub fn is_empty(&self) -> bool {
+        self.radius_x == 0. || self.radius_y == 0.
+    }
+
+    /// Returns the horizontal extent of the ellipse at height `y`, or None if `y` misses it.
+    pub fn chord_at(&self, y: f32) -> Option<(f32, f32)> {
+        let dy = (y - self.center.y) / self.radius_y;
+        if dy.abs() >= 1. || self.is_empty() {
+            return None;
+        }
+        let half = self.radius_x * (1. - dy * dy).sqrt();
+        Some((self.center.x - half, self.center.x + half))
+    }
+}
+
 /// ## LineSegment
 ///
 /// Defines a line by two points.
@@ -100,8 +220,8 @@ impl LineSegment {
     // Returns a line.  Constructed from two points.
     pub fn from_points(point1: Point, point2: Point) -> LineSegment {
         LineSegment {
-            point1: point1,
-            point2: point2,
+            point1,
+            point2,
         }
     }
 
@@ -179,37 +299,98 @@ impl LineSegment {
         }
     }
 
+    /// Returns a point where `self` and `line2` meet, or None if they don't.
+    ///
+    /// Segments that touch at an endpoint, including a T-junction where one ends on the other,
+    /// return that endpoint exactly.  Collinear segments that overlap return the end of the
+    /// overlap nearest `self.point1`.  Lines whose directions differ by less than the rounding
+    /// error of an f32 are treated as parallel, so near-parallel segments either overlap or miss
+    /// rather than crossing at some far-off, meaningless point.
     pub fn intersection(&self, line2 : &LineSegment) -> Option<Point> {
-        //check if the one line is entirely to the left of the other
-        if self.min_x_point().x < line2.min_x_point().x {
-            if self.max_x_point().x <= line2.min_x_point().x {
+        let cross = |ax: f64, ay: f64, bx: f64, by: f64| ax * by - ay * bx;
+        let (px, py) = (self.point1.x as f64, self.point1.y as f64);
+        let (rx, ry) = (self.point2.x as f64 - px, self.point2.y as f64 - py);
+        let (qx, qy) = (line2.point1.x as f64, line2.point1.y as f64);
+        let (sx, sy) = (line2.point2.x as f64 - qx, line2.point2.y as f64 - qy);
+        let (ox, oy) = (qx - px, qy - py);
+        let r_length = rx.hypot(ry);
+        let s_length = sx.hypot(sy);
+
+        // a segment that is a single point meets the other only if it lies on it
+        if r_length == 0. || s_length == 0. {
+            let (point, line) = if r_length == 0. {
+                (self.point1, line2)
+            } else {
+                (line2.point1, self)
+            };
+            return if line.contains_point(point) { Some(point) } else { None };
+        }
+
+        let denominator = cross(rx, ry, sx, sy);
+        if denominator.abs() <= INTERSECTION_EPSILON * r_length * s_length {
+            // parallel lines only meet if they lie on the same line
+            if cross(ox, oy, rx, ry).abs() > INTERSECTION_EPSILON * r_length * ox.hypot(oy) {
                 return None;
             }
-        } else {
-            if self.min_x_point().x >= line2.max_x_point().x {
+            // project line2 onto self, and clip it to self
+            let t0 = (ox * rx + oy * ry) / (r_length * r_length);
+            let t1 = t0 + (sx * rx + sy * ry) / (r_length * r_length);
+            let start = t0.min(t1).max(0.);
+            if start > t0.max(t1).min(1.) + INTERSECTION_EPSILON {
                 return None;
             }
+            let point = if start == t0 {
+                line2.point1
+            } else if start == t1 {
+                line2.point2
+            } else {
+                self.point_at(start)
+            };
+            return Some(point);
         }
-        // mx+b=y
-        // b = y-mx
-        // mx+b-y = 0
-        //if the line's bounding boxes do overlap, we need to look at slopes
-        let m1 = self.slope();
-        let m2 = line2.slope();
-        let b1 = self.point1.y - m1*self.point1.x;
-        let b2 = line2.point1.y - m2*line2.point1.x;
-        if m1 == m2 {
-            //parallel lines
-            //if b1 == b2 {
-                //colinear lines
-            //}
+
+        // self.point1 + t * r == line2.point1 + u * s
+        let t = cross(ox, oy, sx, sy) / denominator;
+        let u = cross(ox, oy, rx, ry) / denominator;
+        let within = |v: f64| (-INTERSECTION_EPSILON..=1. + INTERSECTION_EPSILON).contains(&v);
+        if !within(t) || !within(u) {
             return None;
         }
-        else {
-            let intersection_x = (b2 - b1) / (m1 - m2);
-            Some(Point::new(intersection_x,
-                m1*intersection_x + b1))
+        // crossings at an end are snapped to it, so segments sharing an endpoint return it exactly
+        let near = |v: f64, end: f64| (v - end).abs() <= INTERSECTION_EPSILON;
+        let point = if near(t, 0.) {
+            self.point1
+        } else if near(t, 1.) {
+            self.point2
+        } else if near(u, 0.) {
+            line2.point1
+        } else if near(u, 1.) {
+            line2.point2
+        } else {
+            self.point_at(t)
+        };
+        Some(point)
+    }
+
+    /// Returns the point a fraction `t` of the way from `point1` to `point2`.
+    fn point_at(&self, t: f64) -> Point {
+        let (x1, y1) = (self.point1.x as f64, self.point1.y as f64);
+        let (x2, y2) = (self.point2.x as f64, self.point2.y as f64);
+        Point::new((x1 + t * (x2 - x1)) as f32, (y1 + t * (y2 - y1)) as f32)
+    }
+
+    /// Returns true if `point` lies on the segment, allowing for f32 rounding.
+    fn contains_point(&self, point: Point) -> bool {
+        let (px, py) = (self.point1.x as f64, self.point1.y as f64);
+        let (rx, ry) = (self.point2.x as f64 - px, self.point2.y as f64 - py);
+        let (ox, oy) = (point.x as f64 - px, point.y as f64 - py);
+        let length = rx.hypot(ry);
+        if length == 0. {
+            return point == self.point1;
         }
+        let t = (ox * rx + oy * ry) / (length * length);
+        (ox * ry - oy * rx).abs() <= INTERSECTION_EPSILON * length * ox.hypot(oy)
+            && (-INTERSECTION_EPSILON..=1. + INTERSECTION_EPSILON).contains(&t)
     }
 
     // return x value of line for a given y value
@@ -325,6 +506,26 @@ impl Clone for Edge {
     fn clone(&self) -> Edge { *self }
 }
 
+impl Edge {
+    /// Returns the Edge drawn from `from` to `to`, with its top, bottom and direction worked out
+    /// from the order of the points.
+    pub fn from_points(from: Point, to: Point) -> Edge {
+        let direction = if to.y > from.y {
+            1
+        } else if to.y < from.y {
+            -1
+        } else {
+            0
+        };
+        Edge {
+            line: LineSegment::from_points(from, to),
+            top: from.y.min(to.y),
+            bottom: from.y.max(to.y),
+            direction,
+        }
+    }
+}
+
 /// ## Vector
 ///
 /// Defines a vector by (x, y) direction.
@@ -337,8 +538,8 @@ struct Vector {
 impl Vector {
     pub fn new(x: f32, y: f32) -> Vector {
         Vector {
-            x: x,
-            y: y,
+            x,
+            y,
         }
     }
 
@@ -378,7 +579,7 @@ impl PartialEq for Vector {
 
 #[cfg(test)]
 mod tests {
-    use super::{LineSegment, Point, Vector};
+    use super::{Edge, Ellipse, LineSegment, Point, Rectangle, Vector};
     use std::f32;
     use types::{Pixel, IntoPixels};
 
@@ -390,6 +591,43 @@ mod tests {
         assert_eq!(p1 - p2, Point{x: -1., y: -1.});
     }
 
+    // Tests that a rectangle with negative dimensions gets flipped to have a top-left origin.
+    #[test]
+    fn rectangle_normalized() {
+        let rect = Rectangle::new(5., 5., -2., -3.);
+        assert_eq!(rect.normalized(), Rectangle::new(3., 2., 2., 3.));
+        assert_eq!(rect.normalized().normalized(), rect.normalized());
+    }
+
+    // Tests that zero-area rectangles are empty.
+    #[test]
+    fn rectangle_is_empty() {
+        assert!(Rectangle::new(1., 1., 0., 4.).is_empty());
+        assert!(Rectangle::new(1., 1., 4., 0.).is_empty());
+        assert!(!Rectangle::new(1., 1., 4., 4.).is_empty());
+    }
+
+    // Tests that ellipse radii are made positive and circles have equal radii.
+    #[test]
+    fn ellipse_new() {
+        let ellipse = Ellipse::new(1., 2., -3., 4.);
+        assert_eq!(ellipse.center, Point::new(1., 2.));
+        assert_eq!((ellipse.radius_x, ellipse.radius_y), (3., 4.));
+        assert_eq!(Ellipse::circle(0., 0., 2.), Ellipse::new(0., 0., 2., 2.));
+        assert!(Ellipse::new(0., 0., 0., 2.).is_empty());
+    }
+
+    // Tests the horizontal extent of an ellipse at various heights.
+    #[test]
+    fn ellipse_chord_at() {
+        let ellipse = Ellipse::new(10., 10., 4., 2.);
+        assert_eq!(ellipse.chord_at(10.), Some((6., 14.)));
+        assert_eq!(ellipse.chord_at(12.), None);
+        assert_eq!(ellipse.chord_at(7.), None);
+        let (left, right) = ellipse.chord_at(11.).unwrap();
+        assert!((right - left - 8. * 0.75f32.sqrt()).abs() < 1e-5);
+    }
+
     // Tests that LineSegment's constructor is working.
     #[test]
     fn line_new() {
@@ -529,7 +767,7 @@ mod tests {
     #[test]
     fn self_left_of_line2() {
         let line1 = LineSegment::new(0., 0., 1., 1.);
-        let line2 = LineSegment::new(1., 1., 3., 1.);
+        let line2 = LineSegment::new(2., 1., 3., 1.);
         assert_eq!(line1.intersection(&line2), None);
     }
 
@@ -537,7 +775,7 @@ mod tests {
     #[test]
     fn self_right_of_line2() {
         let line2 = LineSegment::new(0., 0., 1., 1.);
-        let line1 = LineSegment::new(1., 1., 3., 1.);
+        let line1 = LineSegment::new(2., 1., 3., 1.);
         assert_eq!(line1.intersection(&line2), None);
     }
 
@@ -553,8 +791,53 @@ mod tests {
     #[test]
     fn colinear_intersection() {
         let line1 = LineSegment::new(0., 0., 1., 1.);
-        let line2 = LineSegment::new(0., 0., 2., 2.);
-        assert_eq!(line1.intersection(&line2), None);
+        let line2 = LineSegment::new(-1., -1., 2., 2.);
+        assert_eq!(line1.intersection(&line2), Some(Point::new(0., 0.)));
+        assert_eq!(line2.intersection(&line1), Some(Point::new(0., 0.)));
+        // collinear, but the segments don't overlap
+        let line3 = LineSegment::new(2., 2., 3., 3.);
+        assert_eq!(line1.intersection(&line3), None);
+        // overlapping in the opposite direction
+        let line4 = LineSegment::new(2., 2., 0.5, 0.5);
+        assert_eq!(line1.intersection(&line4), Some(Point::new(0.5, 0.5)));
+    }
+
+    // Test segments that only touch at their ends
+    #[test]
+    fn intersection_at_shared_endpoint() {
+        let line1 = LineSegment::new(0., 0., 1., 1.);
+        let line2 = LineSegment::new(1., 1., 3., 1.);
+        assert_eq!(line1.intersection(&line2), Some(Point::new(1., 1.)));
+        assert_eq!(line2.intersection(&line1), Some(Point::new(1., 1.)));
+        // collinear segments end to end
+        let line3 = LineSegment::new(1., 1., 2., 2.);
+        assert_eq!(line1.intersection(&line3), Some(Point::new(1., 1.)));
+    }
+
+    // Test a segment that ends on the middle of another
+    #[test]
+    fn intersection_t_junction() {
+        let line1 = LineSegment::new(0., 0.3, 3., 0.3);
+        let line2 = LineSegment::new(0.7, 0.3, 0.7, 5.);
+        assert_eq!(line1.intersection(&line2), Some(Point::new(0.7, 0.3)));
+        assert_eq!(line2.intersection(&line1), Some(Point::new(0.7, 0.3)));
+    }
+
+    // Test lines too close to parallel to cross anywhere meaningful
+    #[test]
+    fn intersection_near_parallel() {
+        // Setup
+        let line1 = LineSegment::new(0., 0., 1000., 1.);
+        let apart = LineSegment::new(0., 1., 1000., 2.0000001);
+        let on = LineSegment::new(500., 0.5, 2000., 2.0000001);
+
+        // Test
+        assert_eq!(line1.intersection(&apart), None);
+        assert_eq!(line1.intersection(&on), Some(Point::new(500., 0.5)));
+        // a point is only met by a segment through it
+        let point = LineSegment::new(500., 0.5, 500., 0.5);
+        assert_eq!(line1.intersection(&point), Some(Point::new(500., 0.5)));
+        assert_eq!(point.intersection(&apart), None);
     }
 
     // Test intersection of lines
@@ -565,6 +848,23 @@ mod tests {
         assert_eq!(line1.intersection(&line2).unwrap(), Point::new(0.5,0.5));
     }
 
+    // Test intersection with a vertical line
+    #[test]
+    fn intersection_of_vertical_line() {
+        let line1 = LineSegment::new(0., 0., 4., 4.);
+        let line2 = LineSegment::new(2., 0., 2., 4.);
+        assert_eq!(line1.intersection(&line2).unwrap(), Point::new(2., 2.));
+        assert_eq!(line2.intersection(&line1).unwrap(), Point::new(2., 2.));
+    }
+
+    // Test lines that cross beyond the end of one segment
+    #[test]
+    fn intersection_beyond_segment() {
+        let line1 = LineSegment::new(0., 0., 4., 4.);
+        let line2 = LineSegment::new(4., 0., 3., 1.);
+        assert_eq!(line1.intersection(&line2), None);
+    }
+
     // Test current x for y of line
     #[test]
     fn test_current_x() {
@@ -625,7 +925,7 @@ mod tests {
             (4, 1),
             (5, 1),
             (6, 1)
-          ].into_iter().map(|(x, y)| Pixel{x: x, y: y, is_edge: true}).collect::<Vec<Pixel>>();
+          ].into_iter().map(|(x, y)| Pixel{x, y, is_edge: true}).collect::<Vec<Pixel>>();
 
           let pixels = line.into_pixels();
           for coordinate in expected {
@@ -678,7 +978,7 @@ mod tests {
           let b = Point{x: 0., y: 10.};
           let line = LineSegment{point1: a, point2: b};
           let coordinates = line.into_pixels();
-          assert!(coordinates.len() != 0);
+          assert!(!coordinates.is_empty());
           for (idx, coordinate) in coordinates.iter().enumerate() {
               let expected_coordinate = Pixel::new(0, idx as i32 + 1);
               assert_eq!(*coordinate, expected_coordinate);
@@ -692,10 +992,20 @@ mod tests {
           let b = Point{x: 10., y: 0.};
           let line = LineSegment{point1: a, point2: b};
           let coordinates = line.into_pixels();
-          assert!(coordinates.len() != 0);
+          assert!(!coordinates.is_empty());
           for (idx, coordinate) in coordinates.iter().enumerate() {
               let expected_coordinate = Pixel::new(idx as i32 + 1, 0);
               assert_eq!(*coordinate, expected_coordinate);
           }
       }
+
+    // Tests that an Edge's direction follows the order its points were drawn in
+    #[test]
+    fn edge_from_points() {
+        let down = Edge::from_points(Point::new(1., 0.), Point::new(0., 4.));
+        assert_eq!((down.top, down.bottom, down.direction), (0., 4., 1));
+        let up = Edge::from_points(Point::new(0., 4.), Point::new(1., 0.));
+        assert_eq!((up.top, up.bottom, up.direction), (0., 4., -1));
+        assert_eq!(Edge::from_points(Point::new(0., 2.), Point::new(3., 2.)).direction, 0);
+    }
 }
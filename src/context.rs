@@ -36,27 +36,152 @@
  *
  */
 
-use surfaces::ImageSurface;
-use types::Rgba;
+use surfaces::{Composite, Format, ImageSurface, Surface};
+use types::{Antialias, Extend, FillRule, Filter, FontSlant, FontWeight, LineCap, LineJoin,
+            Rasterizer, Rgba, SubpixelOrder};
+use common_geometry::{Ellipse, LineSegment, Point, Rectangle};
 use operators::Operator;
-use operators::fetch_operator;
+use std::f32::consts::PI;
+use std::mem;
+use path::{Path, PathElement, Segment, arc_segments};
+use dash::{DashPattern, InvalidDash};
+use matrix::Matrix;
+use stroke::{DEFAULT_MITER_LIMIT, StrokeStyle};
+use subpixel;
+use bo_trap;
+use predicates::Precision;
+use glyph_cache;
+use image_filter;
+use pattern::{Pattern, SolidPattern, SurfacePattern};
+use text::{FontFace, TextExtents};
+use trapezoid_rasterizer::{Trapezoid, mask_from_trapezoids_with_antialias};
+
+/// The default largest distance allowed between a curve and the line segments it is drawn with,
+/// as in cairo.
+pub const DEFAULT_TOLERANCE: f32 = 0.1;
+
+/// The font size a context starts with, as in cairo.
+const DEFAULT_FONT_SIZE: f32 = 10.;
+
+/// The smallest tolerance set_tolerance() accepts, cairo's fixed point resolution.
+const MIN_TOLERANCE: f32 = 1. / 256.;
+
+/// ## Clip
+///
+/// The area drawing is clipped to: the intersection of the areas inside every path clipped to
+/// since the clip was last reset.
+pub struct Clip {
+    // In device space, with the fill rule each was clipped with
+    paths: Vec<(Path, FillRule)>,
+    // The trapezoids of every path, for in_clip() to test points against exactly.  Text clipped
+    // to from the glyph cache is only tessellated once in_clip() tests a point against it.
+    regions: Vec<Option<Vec<Trapezoid>>>,
+    // The coverage of their intersection, for surfaces with extents
+    mask: Option<ImageSurface>,
+}
+
+impl Clip {
+    /// Returns every path clipped to, in device space, with the fill rule it was clipped with.
+    /// Drawing is clipped to the area inside all of them.
+    pub fn paths(&self) -> &[(Path, FillRule)] {
+        &self.paths
+    }
+
+    /// Returns the coverage of the clip as the alpha of each pixel of the target's extents, or
+    /// None if the target has none.
+    pub fn mask(&self) -> Option<&ImageSurface> {
+        self.mask.as_ref()
+    }
+
+    // Returns the clip of the `height` rows of the target from `top` down, moved up so they
+    // start at row zero, for surfaces that draw a band of rows at a time
+    pub(crate) fn rows(&self, top: usize, height: usize) -> Clip {
+        let up = Matrix::translation(0., -(top as f32));
+        Clip {
+            paths: self.paths.iter()
+                             .map(|(path, fill_rule)| (path.transform(&up), *fill_rule))
+                             .collect(),
+            regions: self.paths.iter().map(|_| None).collect(),
+            mask: self.mask.as_ref().map(|mask| mask.copy_rows(top, height)),
+        }
+    }
+}
 
 /// Struct defined for context
 pub struct Context<'a>{
-    pub rgba: Rgba,
-    target: &'a mut ImageSurface,
+    // The color fill_rectangles(), fill_ellipses() and the stroke_* helpers draw with: the last
+    // solid source set
+    rgba: Rgba,
+    source: Pattern,
+    // The matrix in place when the source was set, which fixes the source in device space
+    source_matrix: Matrix,
+    target: &'a mut dyn Surface,
     operator: Operator,
+    line_width: f32,
+    line_join: LineJoin,
+    line_cap: LineCap,
+    miter_limit: f32,
+    dash: Option<DashPattern>,
+    coverage_only: bool,
+    fill_rule: FillRule,
+    antialias: Antialias,
+    subpixel_order: SubpixelOrder,
+    rasterizer: Rasterizer,
+    precision: Precision,
+    threads: usize,
+    tolerance: f32,
+    matrix: Matrix,
+    clip: Option<Clip>,
+    font_face: FontFace,
+    font_matrix: Matrix,
+    // Held in device space, transformed by the matrix as each point is added
+    path: Path,
+    // The text text_path() added to the path, each with the matrix it was set with, and the path
+    // as it stood after the last of it, so clip_preserve() can tell the path is nothing but text
+    text_runs: Option<(Path, Vec<(String, Matrix)>)>,
+    // The surfaces of the groups pushed by push_group(), innermost last, which drawing goes to
+    // instead of the target
+    groups: Vec<ImageSurface>,
 }
 
 /// Implementation of methods for context
 impl<'a> Context<'a> {
-    //Creates a new cairo context with rgba values set to zeroes with passed ImageSurface as target surface
-    //When new context is created a target surface needs to be passed in.
-    pub fn create(target: &'a mut ImageSurface )-> Context {
+    /// Returns a new context drawing onto `target`, with a transparent source, the Over
+    /// operator and the identity matrix.
+    ///
+    /// Analogous to cairo_create().  The context borrows `target` mutably for as long as it
+    /// lives, as the `'a` on the returned Context spells out, so drop the context to use the
+    /// target again.
+    ///
+    /// # Usage
+    /// let mut context = Context::create(&mut surface);
+    pub fn create(target: &'a mut dyn Surface )-> Context<'a> {
         Context{
             rgba: Rgba::new(0., 0., 0., 0.),
-            target: target,
-            operator: Operator::Over
+            source: Pattern::rgba(0., 0., 0., 0.),
+            source_matrix: Matrix::identity(),
+            target,
+            operator: Operator::Over,
+            line_width: 2.,
+            line_join: LineJoin::Miter,
+            line_cap: LineCap::Butt,
+            miter_limit: DEFAULT_MITER_LIMIT,
+            dash: None,
+            coverage_only: false,
+            fill_rule: FillRule::Winding,
+            antialias: Antialias::Default,
+            subpixel_order: SubpixelOrder::Default,
+            rasterizer: Rasterizer::Trapezoids,
+            precision: Precision::Fast,
+            threads: 1,
+            tolerance: DEFAULT_TOLERANCE,
+            matrix: Matrix::identity(),
+            clip: None,
+            font_face: FontFace::default(),
+            font_matrix: Matrix::scaling(DEFAULT_FONT_SIZE, DEFAULT_FONT_SIZE),
+            path: Path::new(),
+            text_runs: None,
+            groups: Vec::new(),
         }
     }
 
@@ -68,6 +193,52 @@ impl<'a> Context<'a> {
         self.rgba.blue = blue * alpha;
         self.rgba.alpha = alpha;
         self.rgba.correct();
+        self.source = Pattern::Solid(SolidPattern::new(self.rgba));
+        self.source_matrix = self.matrix;
+    }
+
+    /// Sets the pattern that fill(), stroke() and paint() composite onto the target.
+    ///
+    /// Analogous to cairo_set_source().  The pattern is placed in user space as it is when the
+    /// source is set, so later changes to the matrix don't move it.  A solid pattern also becomes
+    /// the color that fill_rectangles(), fill_ellipses() and the stroke_* helpers draw with;
+    /// those only draw solid colors and keep the last one set while a surface is the source.
+    ///
+    /// # Arguments
+    /// * `&mut self` - Reference to the `Context` whose source changes.
+    /// * `source` - The new source.
+    ///
+    /// # Usage
+    /// context.set_source(Pattern::for_surface(image));
+    pub fn set_source(&mut self, source: Pattern) {
+        if let Pattern::Solid(ref solid) = source {
+            self.rgba = solid.color;
+        }
+        self.source = source;
+        self.source_matrix = self.matrix;
+    }
+
+    /// Sets `surface` as the source, with its top left corner at (x, y) in user space.
+    ///
+    /// Analogous to cairo_set_source_surface().  As with set_source(), the current matrix places
+    /// the surface, so a scaled or rotated context draws it scaled or rotated.  Each pixel drawn
+    /// is mapped back into the surface and read with bilinear filtering.
+    ///
+    /// # Usage
+    /// context.rotate(0.3);
+    /// context.set_source_surface(image, 10., 10.);
+    /// context.paint();
+    pub fn set_source_surface(&mut self, surface: ImageSurface, x: f32, y: f32) {
+        let mut pattern = SurfacePattern::new(surface);
+        pattern.set_matrix(Matrix::translation(-x, -y));
+        self.set_source(Pattern::Surface(pattern));
+    }
+
+    /// Returns the pattern fill(), stroke() and paint() composite.
+    ///
+    /// Analogous to cairo_get_source().
+    pub fn get_source(&self) -> &Pattern {
+        &self.source
     }
 
     ///Set Operator function
@@ -83,7 +254,7 @@ impl<'a> Context<'a> {
     ///
     ///# Usage
     ///set_operator(&context, op_enum);
-    fn set_operator(&mut self, operator: Operator){
+    pub fn set_operator(&mut self, operator: Operator){
         self.operator = operator;
     }
 
@@ -96,120 +267,3241 @@ impl<'a> Context<'a> {
     ///
     /// # Usage
     /// let op_enum = get_operator();
-    fn get_operator(&self)-> &Operator{
+    pub fn get_operator(&self)-> &Operator{
         &self.operator
     }
 
-    /// Paints this context's Rgba on the destination surface with the over operator.
+    /// Switches coverage-only rendering on or off.
     ///
-    /// This is a completely naive, and frankly useless implementation.  It is a place holder for
-    /// the real paint function to later be implemented.  It operates on every 'pixel' of the
-    /// destination surface.
-    pub fn paint(&mut self) {
-        let op = Operator::Over;
-        let operator = fetch_operator(&op);
-        for mut pixel in self.target.iter_mut() {
-            operator(&self.rgba, pixel);
+    /// In coverage-only mode drawing ignores the source color and alpha and composites an opaque
+    /// alpha-only source instead, so the target ends up holding just the coverage of what was
+    /// drawn: the same kind of alpha mask `mask_from_trapezoids` builds, ready to be reused as a
+    /// mask, a hit-test bitmap or the base of a shadow.  `ImageSurface::to_alpha_bytes` extracts
+    /// it as A8 data.  The operator still applies, so Over accumulates the union of everything
+    /// drawn.
+    ///
+    /// # Arguments
+    /// * `&mut self` - Reference to the `Context` to change.
+    /// * `coverage_only` - True to render coverage only, false to render color again.
+    ///
+    /// # Usage
+    /// context.set_coverage_only(true);
+    pub fn set_coverage_only(&mut self, coverage_only: bool) {
+        self.coverage_only = coverage_only;
+    }
+
+    /// Returns true if this context renders coverage only.
+    pub fn get_coverage_only(&self) -> bool {
+        self.coverage_only
+    }
+
+    // Returns the Rgba that drawing operations composite: the source color, or an opaque
+    // alpha-only source when rendering coverage only.
+    fn source(&self) -> Rgba {
+        if self.coverage_only {
+            Rgba::new(0., 0., 0., 1.)
+        } else {
+            self.rgba
         }
     }
-}
 
-/// # References
-/// [Cairo Operators]: https://www.cairographics.org/operators/
+    // Returns the surface drawing goes to: the innermost group, or the target outside groups
+    fn surface(&mut self) -> &mut dyn Surface {
+        match self.groups.last_mut() {
+            Some(group) => group,
+            None => &mut *self.target,
+        }
+    }
 
-#[cfg(test)]
-mod tests{
+    // Returns the surface drawing goes to, and how drawing composites the source onto it with
+    // `operator`
+    fn target_and_composite(&mut self, operator: Operator) -> (&mut dyn Surface, Composite<'_>) {
+        let composite = Composite {
+            operator,
+            source: &self.source,
+            source_matrix: self.source_matrix,
+            coverage_only: self.coverage_only,
+            antialias: self.antialias,
+            rasterizer: self.rasterizer,
+            precision: self.precision,
+            threads: self.threads,
+            clip: self.clip.as_ref(),
+        };
+        let target: &mut dyn Surface = match self.groups.last_mut() {
+            Some(group) => group,
+            None => &mut *self.target,
+        };
+        (target, composite)
+    }
 
-    use surfaces::ImageSurface;
-    use types::Rgba;
-    use operators::Operator;
-    use super::Context;
+    /// Returns true if the point (x, y) is inside the area that drawing would be clipped to.
+    ///
+    /// Analogous to cairo_in_clip(), for hit testing alongside in_fill()/in_stroke().  The point
+    /// is in user space, and is inside if every path clipped to so far covers it.  An unclipped
+    /// context is unbounded in cairo (drawing outside the surface is simply discarded), so then
+    /// every point but NaN is inside.
+    ///
+    /// # Arguments
+    /// * `&self` - Reference to the `Context` whose clip is tested.
+    /// * `x` - X coordinate of the point to test.
+    /// * `y` - Y coordinate of the point to test.
+    ///
+    /// # Usage
+    /// let visible = context.in_clip(10., 20.);
+    pub fn in_clip(&self, x: f32, y: f32) -> bool {
+        if x.is_nan() || y.is_nan() {
+            return false;
+        }
 
-    #[test]
-    fn test_get_default_operator(){
-        // Setup
-        let mut surface = ImageSurface::create(255, 255);
-        let context = Context::create( &mut surface );
+        let point = self.matrix.transform_point(Point::new(x, y));
+        match self.clip {
+            Some(ref clip) => clip.regions.iter().zip(&clip.paths).all(|(region, clipped)| {
+                let contains = |region: &[Trapezoid]| {
+                    region.iter().any(|trapezoid| trapezoid.contains_point(&point))
+                };
+                match *region {
+                    Some(ref region) => contains(region),
+                    None => contains(&tessellate(&clipped.0, clipped.1, self.precision,
+                                                       self.tolerance)),
+                }
+            }),
+            None => true,
+        }
+    }
 
-        // Call
-        let op = context.get_operator();
+    /// Restricts drawing to the area inside the current path, then clears the path.
+    ///
+    /// Analogous to cairo_clip().  The path is filled according to the fill rule and antialias
+    /// setting, and the result is intersected with the clip that was already in place, so each
+    /// clip can only shrink the drawable area.  Everything drawn afterwards is clipped, including
+    /// the batched fill_rectangles(), fill_ellipses() and stroke_* helpers, which draw through
+    /// fill() and stroke() while a clip is in place.
+    ///
+    /// # Usage
+    /// context.move_to(0., 0.);
+    /// context.line_to(10., 0.);
+    /// context.line_to(10., 10.);
+    /// context.clip();
+    pub fn clip(&mut self) {
+        self.clip_preserve();
+        self.path.clear();
+    }
 
-        // Assert
-        assert_eq!( &Operator::Over, op );
+    /// Restricts drawing to the area inside the current path, like clip(), but keeps the path.
+    ///
+    /// Analogous to cairo_clip_preserve().  A path that is nothing but text from text_path(),
+    /// set upright, is clipped to from the shared glyph cache, so clipping to the same glyphs
+    /// again doesn't rasterize them again.
+    pub fn clip_preserve(&mut self) {
+        let (mask, trapezoids) = match self.text_clip_mask() {
+            Some(mask) => (Some(mask), None),
+            None => {
+                let trapezoids = tessellate(&self.path, self.fill_rule, self.precision,
+                                            self.tolerance);
+                // Only a surface with extents has pixels for the clip's coverage to be kept for
+                let mask = self.target.extents().map(|extents| {
+                    let width = (extents.x + extents.width).max(0) as usize;
+                    let height = (extents.y + extents.height).max(0) as usize;
+                    mask_from_trapezoids_with_antialias(&trapezoids, self.antialias, width,
+                                                        height)
+                });
+                (mask, Some(trapezoids))
+            },
+        };
+        let path = (self.path.clone(), self.fill_rule);
+
+        self.clip = Some(match self.clip.take() {
+            Some(mut clip) => {
+                if let (Some(clip_mask), Some(mask)) = (clip.mask.as_mut(), mask) {
+                    for (coverage, new_coverage) in clip_mask.iter_mut().zip(mask.iter()) {
+                        coverage.alpha *= new_coverage.alpha;
+                    }
+                }
+                clip.paths.push(path);
+                clip.regions.push(trapezoids);
+                clip
+            },
+            None => Clip { paths: vec![path], regions: vec![trapezoids], mask },
+        });
     }
 
-    #[test]
-    fn test_set_operator(){
-        // Setup
-        let mut surface = ImageSurface::create(255, 255);
-        let mut context = Context::create( &mut surface );
+    // Returns the coverage of the clip to the current path, put together from cached glyphs, or
+    // None if the path isn't only text the glyph cache can hold or the target has no extents
+    fn text_clip_mask(&self) -> Option<ImageSurface> {
+        if self.fill_rule != FillRule::Winding || self.antialias == Antialias::None {
+            return None;
+        }
+        let runs = self.text_runs()?;
+        let extents = self.target.extents()?;
+        let width = (extents.x + extents.width).max(0) as usize;
+        let height = (extents.y + extents.height).max(0) as usize;
 
-        // Call
-        context.set_operator(Operator::In);
-        let op = context.get_operator();
+        let cache = glyph_cache::global().lock();
+        let mut cache = cache.unwrap_or_else(|error| error.into_inner());
+        let mut mask = ImageSurface::create_for_format(Format::ARGB32, width, height);
+        for (text, matrix) in runs {
+            let glyphs = glyph_cache::text_mask(&mut cache, &self.font_face, text, matrix)?;
+            let columns = glyphs.x.max(0)..(glyphs.x + glyphs.width as i32).min(width as i32);
+            let rows = glyphs.y.max(0)..(glyphs.y + glyphs.height as i32).min(height as i32);
+            for y in rows {
+                for x in columns.clone() {
+                    if let Some(pixel) = mask.get_mut(x as usize, y as usize) {
+                        let coverage = glyphs.get(x, y) as f32 / 255.;
+                        pixel.alpha = (pixel.alpha + coverage).min(1.);
+                    }
+                }
+            }
+        }
+        Some(mask)
+    }
 
-        // Assert
-        assert_eq!( &Operator::In, op );
+    // Returns the text text_path() added to the current path, with the matrix each was set
+    // with, or None if anything else was added to the path since
+    fn text_runs(&self) -> Option<&[(String, Matrix)]> {
+        let (text_path, runs) = self.text_runs.as_ref()?;
+        let elements = self.path.elements();
+        let rest = elements.get(text_path.elements().len()..)?;
+        // A move on its own adds nothing to the area inside the path
+        let text_only = elements.starts_with(text_path.elements())
+            && rest.iter().all(|element| matches!(*element, PathElement::MoveTo(_)));
+        if text_only { Some(runs) } else { None }
     }
 
-    // This tests that naive paint covers the target.  It does two calls, in order to check that
-    // multiple mutable borrows (via paint) work fine too.
-    #[test]
-    fn test_paint() {
-        // Setup
-        let mut target = ImageSurface::create(100, 100);
+    /// Removes the clip, so drawing can reach the whole surface again.
+    ///
+    /// Analogous to cairo_reset_clip().
+    pub fn reset_clip(&mut self) {
+        self.clip = None;
+    }
 
-        // Call
-        {
-            let mut context = Context::create(&mut target);
-            context.set_source_rgba(1., 0., 0., 1.);
-            context.paint();
-            context.set_source_rgba(0., 1., 0., 1.);
-            context.paint();
+    /// Fills every rectangle in `rectangles` with this context's Rgba and operator.
+    ///
+    /// Analogous to a batched cairo_rectangle() + cairo_fill(), but the rectangles are composited
+    /// in a single pass without building a path, so thousands of rectangles stay cheap.  See
+    /// `ImageSurface::fill_rectangles` for how overlaps and partial pixels are handled.
+    ///
+    /// The rectangles are in user space.  Under a matrix that rotates or skews them, with a
+    /// source other than a single color, or while drawing is clipped, they are filled as a path
+    /// instead.
+    pub fn fill_rectangles(&mut self, rectangles: &[Rectangle]) {
+        let matrix = self.matrix;
+        match self.batched_color() {
+            Some(color) if matrix.is_axis_aligned() => {
+                let rectangles: Vec<Rectangle> = rectangles.iter().map(|rectangle| {
+                    let corner1 = matrix.transform_point(Point::new(rectangle.x, rectangle.y));
+                    let corner2 = matrix.transform_point(
+                        Point::new(rectangle.x + rectangle.width, rectangle.y + rectangle.height));
+                    Rectangle::new(corner1.x, corner1.y, corner2.x - corner1.x,
+                                   corner2.y - corner1.y)
+                }).collect();
+                let operator = self.operator;
+                self.surface().fill_rectangles(&operator, &color, &rectangles);
+            },
+            _ => self.fill_batched_path(&Path::from_rectangles(rectangles)),
         }
+    }
 
-        // Test
-        let expected = Rgba::new(0., 1., 0., 1.);
-        for pixel in target.iter() {
-            assert_eq!(*pixel, expected);
+    /// Fills every ellipse in `ellipses` with this context's Rgba and operator.
+    ///
+    /// Analogous to drawing a full cairo_arc() (under a scale for ellipses) and filling it, but
+    /// without flattening the arcs.  See `ImageSurface::fill_ellipses`.
+    ///
+    /// The ellipses are in user space.  Under a matrix that rotates or skews them into ellipses
+    /// that aren't axis-aligned, with a source other than a single color, or while drawing is
+    /// clipped, they are filled as a path instead.  Circles stay circles under any rotation and
+    /// uniform scale.
+    pub fn fill_ellipses(&mut self, ellipses: &[Ellipse]) {
+        let matrix = self.matrix;
+        let scale = if matrix.is_axis_aligned() {
+            Some((matrix.xx.abs(), matrix.yy.abs()))
+        } else {
+            matrix.uniform_scale()
+                  .filter(|_| ellipses.iter().all(|ellipse| ellipse.radius_x == ellipse.radius_y))
+                  .map(|scale| (scale, scale))
+        };
+        match (self.batched_color(), scale) {
+            (Some(color), Some((scale_x, scale_y))) => {
+                let ellipses: Vec<Ellipse> = ellipses.iter().map(|ellipse| {
+                    let center = matrix.transform_point(ellipse.center);
+                    Ellipse::new(center.x, center.y, ellipse.radius_x * scale_x,
+                                 ellipse.radius_y * scale_y)
+                }).collect();
+                let operator = self.operator;
+                self.surface().fill_ellipses(&operator, &color, &ellipses);
+            },
+            _ => {
+                let tolerance = self.tolerance / self.matrix.max_scale();
+                self.fill_batched_path(&Path::from_ellipses(ellipses, tolerance));
+            },
         }
     }
 
-    #[test]
-    fn test_set_rgba_happy(){
-        let mut surface = ImageSurface::create(100, 100);
-        let mut context = Context::create(&mut surface);
-        context.set_source_rgba(0.1, 0.2, 0.3, 1.);
-        assert_eq!(context.rgba.red, 0.1);
-        assert_eq!(context.rgba.green, 0.2);
-        assert_eq!(context.rgba.blue, 0.3);
-        assert_eq!(context.rgba.alpha, 1.);
+    // Returns the single color the batched helpers composite, or None if they have to draw
+    // through fill() or stroke() because the source varies or drawing is clipped
+    fn batched_color(&self) -> Option<Rgba> {
+        match self.source {
+            _ if self.clip.is_some() => None,
+            _ if self.coverage_only => Some(self.source()),
+            Pattern::Solid(ref solid) => Some(solid.color),
+            _ => None,
+        }
+    }
 
-        // Test Rbga premultiply
-        context.set_source_rgba(0.2, 0.4, 0.6, 0.5);
-        assert_eq!(context.rgba.red, 0.1);
-        assert_eq!(context.rgba.green, 0.2);
-        assert_eq!(context.rgba.blue, 0.3);
-        assert_eq!(context.rgba.alpha, 0.5);
+    // Fills `path`, which is in user space, as fill() would with the winding rule, for the
+    // batched helpers that can't draw their shapes directly
+    fn fill_batched_path(&mut self, path: &Path) {
+        let path = path.transform(&self.matrix);
+        let tolerance = self.tolerance;
+        let (target, composite) = self.target_and_composite(self.operator);
+        target.fill(&composite, &path, FillRule::Winding, tolerance);
     }
 
-    #[test]
-    fn test_set_rgba_out_of_bounds_values(){
-        let mut surface = ImageSurface::create(100, 100);
-        let mut context = Context::create(&mut surface);
+    // Strokes `segments`, which are in user space, as stroke() would with butt caps and no
+    // joins, for the batched helpers that can't draw them directly
+    fn stroke_batched_segments(&mut self, segments: &[LineSegment], source: Option<&Pattern>) {
+        let style = StrokeStyle { cap: LineCap::Butt, ..StrokeStyle::new(self.line_width) };
+        let path = Path::from_segments(segments);
+        let composite = Composite {
+            operator: self.operator,
+            source: source.unwrap_or(&self.source),
+            source_matrix: if source.is_some() { Matrix::identity() } else { self.source_matrix },
+            coverage_only: self.coverage_only,
+            antialias: self.antialias,
+            rasterizer: self.rasterizer,
+            precision: self.precision,
+            threads: self.threads,
+            clip: self.clip.as_ref(),
+        };
+        let target: &mut dyn Surface = match self.groups.last_mut() {
+            Some(group) => group,
+            None => &mut *self.target,
+        };
+        target.stroke(&composite, &path, &self.matrix, &style, None, self.tolerance);
+    }
 
-        // Test negative alpha value pre-multiplting to zero
-        context.set_source_rgba(1., 1., 1., -10.);
-        assert_eq!(context.rgba.red, 0.);
-        assert_eq!(context.rgba.green, 0.);
-        assert_eq!(context.rgba.blue, 0.);
-        assert_eq!(context.rgba.alpha, 0.);
+    /// Sets the line width used by stroking operations.
+    ///
+    /// Analogous to cairo_set_line_width().  The default is 2.0, as in cairo.  Negative widths are
+    /// clamped to zero, which strokes nothing.
+    ///
+    /// # Arguments
+    /// * `&mut self` - Reference to the `Context` whose line width changes.
+    /// * `width` - The new line width, in surface units.
+    ///
+    /// # Usage
+    /// context.set_line_width(1.);
+    pub fn set_line_width(&mut self, width: f32) {
+        self.line_width = width.max(0.);
+    }
 
-        // Test bound to range [0,1]
-        context.set_source_rgba(-22.,22.,-22.,9.);
-        assert_eq!(context.rgba.red, 0.);
-        assert_eq!(context.rgba.green, 1.);
-        assert_eq!(context.rgba.blue, 0.);
-        assert_eq!(context.rgba.alpha, 1.);
+    /// Returns the line width used by stroking operations.
+    pub fn get_line_width(&self) -> f32 {
+        self.line_width
+    }
+
+    /// Sets how stroke() draws the corners between segments.
+    ///
+    /// Analogous to cairo_set_line_join().  The default is LineJoin::Miter, as in cairo.
+    ///
+    /// # Usage
+    /// context.set_line_join(LineJoin::Round);
+    pub fn set_line_join(&mut self, line_join: LineJoin) {
+        self.line_join = line_join;
+    }
+
+    /// Returns how stroke() draws the corners between segments.
+    pub fn get_line_join(&self) -> LineJoin {
+        self.line_join
+    }
+
+    /// Sets how long a miter join may be, as a multiple of the line width, before stroke() bevels
+    /// the corner instead.
+    ///
+    /// Analogous to cairo_set_miter_limit().  The default is 10, as in cairo, which bevels
+    /// corners sharper than about 11 degrees.
+    ///
+    /// # Usage
+    /// context.set_miter_limit(2.);
+    pub fn set_miter_limit(&mut self, limit: f32) {
+        self.miter_limit = limit;
+    }
+
+    /// Returns how long a miter join may be before stroke() bevels the corner instead.
+    pub fn get_miter_limit(&self) -> f32 {
+        self.miter_limit
+    }
+
+    /// Sets the dash pattern stroke() walks along every subpath.
+    ///
+    /// Analogous to cairo_set_dash().  `dashes` alternates the lengths of "on" and "off" dashes,
+    /// starting with "on", and an odd number of lengths is repeated to make an even one.
+    /// `offset` is how far into the pattern each subpath starts, and wraps around the pattern in
+    /// either direction.  An empty `dashes` turns dashing off, which is the default.
+    ///
+    /// Returns InvalidDash, and leaves the current pattern in place, if any length is negative or
+    /// not finite, or if every length is zero.
+    ///
+    /// # Arguments
+    /// * `&mut self` - Reference to the `Context` whose dash pattern changes.
+    /// * `dashes` - Alternating on and off lengths, in user space units.
+    /// * `offset` - How far into the pattern to start.
+    ///
+    /// # Usage
+    /// context.set_dash(&[4., 2.], 0.).unwrap();
+    pub fn set_dash(&mut self, dashes: &[f64], offset: f64) -> Result<(), InvalidDash> {
+        let dashes: Vec<f32> = dashes.iter().map(|dash| *dash as f32).collect();
+        let offset = offset as f32;
+        if dashes.is_empty() {
+            self.dash = None;
+            return Ok(());
+        }
+        match DashPattern::new(&dashes, offset) {
+            Some(pattern) => {
+                self.dash = Some(pattern);
+                Ok(())
+            },
+            None => Err(InvalidDash { dashes, offset }),
+        }
+    }
+
+    /// Returns the dash pattern stroke() uses, or None if strokes are solid.
+    pub fn get_dash(&self) -> Option<&DashPattern> {
+        self.dash.as_ref()
+    }
+
+    /// Sets how stroke() draws the ends of open subpaths.
+    ///
+    /// Analogous to cairo_set_line_cap().  The default is LineCap::Butt, as in cairo.
+    ///
+    /// # Usage
+    /// context.set_line_cap(LineCap::Round);
+    pub fn set_line_cap(&mut self, line_cap: LineCap) {
+        self.line_cap = line_cap;
+    }
+
+    /// Returns how stroke() draws the ends of open subpaths.
+    pub fn get_line_cap(&self) -> LineCap {
+        self.line_cap
+    }
+
+    /// Strokes every segment in `segments` with this context's Rgba, operator and line width.
+    ///
+    /// A fast path for large batches of independent segments (ticks, scatter plots, wireframes):
+    /// no path is built, and all segments share a single rasterization pass.  See
+    /// `ImageSurface::stroke_segments` for how caps and overlaps are handled.
+    ///
+    /// The segments and line width are in user space.  Under a matrix that skews or stretches
+    /// them unevenly, with a source other than a single color, or while drawing is clipped,
+    /// they are stroked as a path instead.
+    pub fn stroke_segments(&mut self, segments: &[LineSegment]) {
+        let matrix = self.matrix;
+        match (self.batched_color(), matrix.uniform_scale()) {
+            (Some(color), Some(scale)) => {
+                let segments: Vec<LineSegment> = segments.iter().map(|segment| {
+                    LineSegment::from_points(matrix.transform_point(segment.point1),
+                                             matrix.transform_point(segment.point2))
+                }).collect();
+                let (operator, line_width) = (self.operator, self.line_width * scale);
+                self.surface().stroke_segments(&operator, &color, &segments, line_width);
+            },
+            _ => self.stroke_batched_segments(segments, None),
+        }
+    }
+
+    /// Strokes the polyline through `points` with this context's line width and operator, fading
+    /// from this context's Rgba at the first point to `end_color` at the last.
+    ///
+    /// The fade follows the stroke's length, which suits map routes and progress arcs.  See
+    /// `ImageSurface::stroke_polyline_gradient`.
+    ///
+    /// The points and line width are in user space.  Under a matrix that skews or stretches
+    /// them unevenly, or while drawing is clipped, the colors along the polyline are laid out
+    /// in an image and it is stroked as a path with them.
+    pub fn stroke_polyline_gradient(&mut self, points: &[Point], end_color: &Rgba) {
+        let (start_color, end_color) = if self.coverage_only {
+            (self.source(), self.source())
+        } else {
+            (self.rgba, *end_color)
+        };
+        let matrix = self.matrix;
+        if let (Some(scale), None) = (matrix.uniform_scale(), self.clip.as_ref()) {
+            let points: Vec<Point> = points.iter()
+                .map(|&point| matrix.transform_point(point))
+                .collect();
+            let (operator, line_width) = (self.operator, self.line_width * scale);
+            self.surface().stroke_polyline_gradient(&operator, &start_color, &end_color, &points,
+                                                    line_width);
+            return;
+        }
+
+        let segments: Vec<LineSegment> = points.windows(2)
+            .map(|pair| LineSegment::from_points(pair[0], pair[1]))
+            .collect();
+        let extents = self.surface().extents();
+        let source = match extents {
+            Some(extents) => {
+                let width = (extents.x + extents.width).max(0) as usize;
+                let height = (extents.y + extents.height).max(0) as usize;
+                let colors = polyline_gradient_colors(&segments, &start_color, &end_color,
+                                                      &matrix, self.line_width, width, height);
+                let mut pattern = SurfacePattern::new(colors);
+                pattern.set_filter(Filter::Nearest);
+                Pattern::Surface(pattern)
+            },
+            None => Pattern::Solid(SolidPattern::new(start_color)),
+        };
+        self.stroke_batched_segments(&segments, Some(&source));
+    }
+
+    /// Sets the rule that decides which areas of a self-intersecting path fill() covers.
+    ///
+    /// Analogous to cairo_set_fill_rule().  The default is FillRule::Winding, as in cairo.
+    ///
+    /// # Arguments
+    /// * `&mut self` - Reference to the `Context` whose fill rule changes.
+    /// * `fill_rule` - The new fill rule.
+    ///
+    /// # Usage
+    /// context.set_fill_rule(FillRule::EvenOdd);
+    pub fn set_fill_rule(&mut self, fill_rule: FillRule) {
+        self.fill_rule = fill_rule;
+    }
+
+    /// Returns the rule fill() uses for self-intersecting paths.
+    pub fn get_fill_rule(&self) -> FillRule {
+        self.fill_rule
+    }
+
+    /// Sets how the edges of filled shapes are antialiased.
+    ///
+    /// Analogous to cairo_set_antialias().  The default is Antialias::Default, which samples a
+    /// grid of points in each edge pixel; Antialias::None samples only the pixel's center, for
+    /// hard edges.  Antialias::Subpixel draws text on a raster target for an LCD panel whose
+    /// stripes are in the subpixel order, and draws everything else like Antialias::Gray.
+    ///
+    /// # Arguments
+    /// * `&mut self` - Reference to the `Context` whose antialiasing changes.
+    /// * `antialias` - The new antialiasing mode.
+    ///
+    /// # Usage
+    /// context.set_antialias(Antialias::None);
+    pub fn set_antialias(&mut self, antialias: Antialias) {
+        self.antialias = antialias;
+    }
+
+    /// Returns how the edges of filled shapes are antialiased.
+    pub fn get_antialias(&self) -> Antialias {
+        self.antialias
+    }
+
+    /// Sets the order of the color stripes across the pixels of the panel that text antialiased
+    /// with Antialias::Subpixel is drawn for.
+    ///
+    /// Analogous to cairo_font_options_set_subpixel_order().  The default is
+    /// SubpixelOrder::Default, which is SubpixelOrder::Rgb.
+    ///
+    /// # Usage
+    /// context.set_antialias(Antialias::Subpixel);
+    /// context.set_subpixel_order(SubpixelOrder::Bgr);
+    pub fn set_subpixel_order(&mut self, order: SubpixelOrder) {
+        self.subpixel_order = order;
+    }
+
+    /// Returns the order of the color stripes text is antialiased for.
+    pub fn get_subpixel_order(&self) -> SubpixelOrder {
+        self.subpixel_order
+    }
+
+    /// Sets how fill() and stroke() turn shapes into pixel coverage.
+    ///
+    /// The default, Rasterizer::Trapezoids, tessellates each shape.  Rasterizer::Spans scan
+    /// converts its edges instead, which avoids tessellating paths with many crossings; the
+    /// coverage of edge pixels differs slightly between the two.
+    ///
+    /// # Usage
+    /// context.set_rasterizer(Rasterizer::Spans);
+    pub fn set_rasterizer(&mut self, rasterizer: Rasterizer) {
+        self.rasterizer = rasterizer;
+    }
+
+    /// Returns how fill() and stroke() turn shapes into pixel coverage.
+    pub fn get_rasterizer(&self) -> Rasterizer {
+        self.rasterizer
+    }
+
+    /// Sets how fill(), stroke() and clip() decide where the edges of shapes cross.
+    ///
+    /// The default, Precision::Fast, tessellates with plain f32 arithmetic, which can leave
+    /// cracks or overlaps on nearly degenerate paths.  Precision::Exact computes those decisions
+    /// exactly, for watertight output at some cost in speed.  Only Rasterizer::Trapezoids
+    /// tessellates, so Rasterizer::Spans draws the same either way.
+    ///
+    /// # Usage
+    /// context.set_precision(Precision::Exact);
+    pub fn set_precision(&mut self, precision: Precision) {
+        self.precision = precision;
+    }
+
+    /// Returns how fill(), stroke() and clip() decide where the edges of shapes cross.
+    pub fn get_precision(&self) -> Precision {
+        self.precision
+    }
+
+    /// Sets how many threads fill() and stroke() render with.
+    ///
+    /// With more than one, the target is cut into tiles of whole rows that are rasterized and
+    /// composited in parallel, which draws exactly what one thread does.  The default is 1,
+    /// which renders on the calling thread, and 0 is taken as 1.  The count is kept as it is
+    /// given, whatever the processor can run at once, but a target never gets more threads than
+    /// it has tiles.  Only Rasterizer::Trapezoids renders in tiles so far.
+    ///
+    /// # Usage
+    /// let threads = thread::available_parallelism().map_or(1, |threads| threads.get());
+    /// context.set_threads(threads);
+    pub fn set_threads(&mut self, threads: usize) {
+        self.threads = threads.max(1);
+    }
+
+    /// Returns how many threads fill() and stroke() render with.
+    pub fn get_threads(&self) -> usize {
+        self.threads
+    }
+
+    /// Sets how far curves may stray from the line segments they are drawn with.
+    ///
+    /// Analogous to cairo_set_tolerance().  The default is 0.1, as in cairo.  Smaller values give
+    /// smoother curves at the cost of more segments; values below 1/256 are raised to it.
+    ///
+    /// The tolerance is measured in device space, whatever the matrix, and bounds the error of
+    /// every curve fill(), stroke() and clip() draw: each point of a Bézier curve is within the
+    /// tolerance of the lines it is drawn with.  Arcs, and the round joins and caps of strokes,
+    /// are made of Bézier curves within the tolerance of the circle, so they are drawn within
+    /// twice the tolerance of it.
+    ///
+    /// # Arguments
+    /// * `&mut self` - Reference to the `Context` whose tolerance changes.
+    /// * `tolerance` - The largest distance allowed, in surface units.
+    ///
+    /// # Usage
+    /// context.set_tolerance(0.5);
+    pub fn set_tolerance(&mut self, tolerance: f32) {
+        self.tolerance = tolerance.max(MIN_TOLERANCE);
+    }
+
+    /// Returns how far curves may stray from the line segments they are drawn with.
+    pub fn get_tolerance(&self) -> f32 {
+        self.tolerance
+    }
+
+    /// Moves the origin of user space by (tx, ty) user space units.
+    ///
+    /// Analogous to cairo_translate().  Like the other transformations it applies to points added
+    /// to the path afterwards, and to the pen stroke() draws with.
+    ///
+    /// # Usage
+    /// context.translate(10., 20.);
+    pub fn translate(&mut self, tx: f32, ty: f32) {
+        self.matrix.translate(tx, ty);
+    }
+
+    /// Scales the axes of user space by `sx` and `sy`.  Analogous to cairo_scale().
+    ///
+    /// # Usage
+    /// context.scale(2., 2.);
+    pub fn scale(&mut self, sx: f32, sy: f32) {
+        self.matrix.scale(sx, sy);
+    }
+
+    /// Rotates the axes of user space by `radians`, from the positive x axis towards the positive
+    /// y axis.  Analogous to cairo_rotate().
+    ///
+    /// # Usage
+    /// context.rotate(std::f32::consts::FRAC_PI_4);
+    pub fn rotate(&mut self, radians: f32) {
+        self.matrix.rotate(radians);
+    }
+
+    /// Applies `matrix` to user space, before the current transformation.
+    ///
+    /// Analogous to cairo_transform().
+    ///
+    /// # Usage
+    /// context.transform(&Matrix::new(1., 0., 0.5, 1., 0., 0.));
+    pub fn transform(&mut self, matrix: &Matrix) {
+        self.matrix = matrix.multiply(&self.matrix);
+    }
+
+    /// Replaces the transformation from user space to device space.
+    ///
+    /// Analogous to cairo_set_matrix().  The default is the identity, which makes user space
+    /// units surface pixels.  A matrix that can't be inverted collapses the pen, so stroke()
+    /// draws nothing while it is set.
+    ///
+    /// # Usage
+    /// context.set_matrix(Matrix::scaling(2., 2.));
+    pub fn set_matrix(&mut self, matrix: Matrix) {
+        self.matrix = matrix;
+    }
+
+    /// Returns the transformation from user space to device space.
+    ///
+    /// Analogous to cairo_get_matrix().
+    pub fn get_matrix(&self) -> Matrix {
+        self.matrix
+    }
+
+    /// Resets the transformation to the identity.  Analogous to cairo_identity_matrix().
+    pub fn identity_matrix(&mut self) {
+        self.matrix = Matrix::identity();
+    }
+
+    /// Clears the current path.  Analogous to cairo_new_path().
+    pub fn new_path(&mut self) {
+        self.path.clear();
+    }
+
+    /// Begins a new subpath at (x, y).  Analogous to cairo_move_to().
+    ///
+    /// # Arguments
+    /// * `&mut self` - Reference to the `Context` whose path is extended.
+    /// * `x` - X coordinate of the new current point.
+    /// * `y` - Y coordinate of the new current point.
+    ///
+    /// # Usage
+    /// context.move_to(10., 10.);
+    pub fn move_to(&mut self, x: f32, y: f32) {
+        let point = self.matrix.transform_point(Point::new(x, y));
+        self.path.move_to(point.x, point.y);
+    }
+
+    /// Adds a line from the current point to (x, y), which becomes the current point.
+    ///
+    /// Analogous to cairo_line_to().  Without a current point this behaves like move_to().
+    ///
+    /// # Arguments
+    /// * `&mut self` - Reference to the `Context` whose path is extended.
+    /// * `x` - X coordinate of the end of the line.
+    /// * `y` - Y coordinate of the end of the line.
+    ///
+    /// # Usage
+    /// context.line_to(20., 10.);
+    pub fn line_to(&mut self, x: f32, y: f32) {
+        let point = self.matrix.transform_point(Point::new(x, y));
+        self.path.line_to(point.x, point.y);
+    }
+
+    /// Adds a cubic Bézier curve from the current point to (x3, y3), using (x1, y1) and (x2, y2)
+    /// as control points.
+    ///
+    /// Analogous to cairo_curve_to().  Without a current point the curve starts at (x1, y1).  The
+    /// curve is flattened into lines when it is drawn, to within the tolerance.
+    ///
+    /// # Arguments
+    /// * `&mut self` - Reference to the `Context` whose path is extended.
+    /// * `x1`, `y1` - The first control point.
+    /// * `x2`, `y2` - The second control point.
+    /// * `x3`, `y3` - The end of the curve.
+    ///
+    /// # Usage
+    /// context.curve_to(20., 0., 30., 20., 40., 10.);
+    pub fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x3: f32, y3: f32) {
+        let control1 = self.matrix.transform_point(Point::new(x1, y1));
+        let control2 = self.matrix.transform_point(Point::new(x2, y2));
+        let end = self.matrix.transform_point(Point::new(x3, y3));
+        self.path.curve_to(control1.x, control1.y, control2.x, control2.y, end.x, end.y);
+    }
+
+    /// Adds a quadratic Bézier curve from the current point to (x2, y2), using (x1, y1) as the
+    /// control point.
+    ///
+    /// cairo has no quadratic curves; this is the same as curve_to() with the cubic that traces
+    /// the quadratic exactly, as for the Q command of SVG paths or TrueType outlines.
+    ///
+    /// # Usage
+    /// context.quad_to(20., 0., 40., 10.);
+    pub fn quad_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32) {
+        let control = self.matrix.transform_point(Point::new(x1, y1));
+        let end = self.matrix.transform_point(Point::new(x2, y2));
+        self.path.quad_to(control.x, control.y, end.x, end.y);
+    }
+
+    /// Begins a new subpath offset by (dx, dy) from the current point.
+    ///
+    /// Analogous to cairo_rel_move_to().  The offset is in user space, and nothing happens
+    /// without a current point.
+    ///
+    /// # Usage
+    /// context.rel_move_to(5., 0.);
+    pub fn rel_move_to(&mut self, dx: f32, dy: f32) {
+        let offset = self.matrix.transform_distance(Point::new(dx, dy));
+        self.path.rel_move_to(offset.x, offset.y);
+    }
+
+    /// Adds a line from the current point to the point offset by (dx, dy) from it.
+    ///
+    /// Analogous to cairo_rel_line_to().  The offset is in user space, and nothing happens
+    /// without a current point.
+    ///
+    /// # Usage
+    /// context.rel_line_to(10., 0.);
+    pub fn rel_line_to(&mut self, dx: f32, dy: f32) {
+        let offset = self.matrix.transform_distance(Point::new(dx, dy));
+        self.path.rel_line_to(offset.x, offset.y);
+    }
+
+    /// Adds a cubic Bézier curve like curve_to(), with every point given as an offset from the
+    /// current point.
+    ///
+    /// Analogous to cairo_rel_curve_to().  The offsets are in user space, and nothing happens
+    /// without a current point.
+    ///
+    /// # Usage
+    /// context.rel_curve_to(10., -10., 20., 10., 30., 0.);
+    pub fn rel_curve_to(&mut self, dx1: f32, dy1: f32, dx2: f32, dy2: f32, dx3: f32, dy3: f32) {
+        let offset1 = self.matrix.transform_distance(Point::new(dx1, dy1));
+        let offset2 = self.matrix.transform_distance(Point::new(dx2, dy2));
+        let offset3 = self.matrix.transform_distance(Point::new(dx3, dy3));
+        self.path.rel_curve_to(offset1.x, offset1.y, offset2.x, offset2.y, offset3.x, offset3.y);
+    }
+
+    /// Adds a circular arc of `radius` around (xc, yc), from `angle1` to `angle2` in the direction
+    /// of increasing angles.
+    ///
+    /// Analogous to cairo_arc().  Angles are in radians, measured from the positive x axis towards
+    /// the positive y axis, and `angle2` is moved up by whole turns until it is at least `angle1`.
+    /// If there is a current point, a line joins it to the start of the arc; otherwise the arc
+    /// begins a new subpath.  The arc is made of Bézier curves that stay within the tolerance of
+    /// the circle, and a radius of zero or less adds a line to the center instead.
+    ///
+    /// # Arguments
+    /// * `&mut self` - Reference to the `Context` whose path is extended.
+    /// * `xc`, `yc` - The center of the circle.
+    /// * `radius` - The radius of the circle.
+    /// * `angle1` - The angle the arc starts at.
+    /// * `angle2` - The angle the arc ends at.
+    ///
+    /// # Usage
+    /// context.arc(50., 50., 20., 0., 2. * std::f32::consts::PI);
+    pub fn arc(&mut self, xc: f32, yc: f32, radius: f32, angle1: f32, mut angle2: f32) {
+        while angle2 < angle1 {
+            angle2 += 2. * PI;
+        }
+        self.add_arc(xc, yc, radius, angle1, angle2);
+    }
+
+    /// Adds a circular arc of `radius` around (xc, yc), from `angle1` to `angle2` in the direction
+    /// of decreasing angles.
+    ///
+    /// Analogous to cairo_arc_negative().  `angle2` is moved down by whole turns until it is at
+    /// most `angle1`.  See arc().
+    ///
+    /// # Usage
+    /// context.arc_negative(50., 50., 20., std::f32::consts::PI, 0.);
+    pub fn arc_negative(&mut self, xc: f32, yc: f32, radius: f32, angle1: f32, mut angle2: f32) {
+        while angle2 > angle1 {
+            angle2 -= 2. * PI;
+        }
+        self.add_arc(xc, yc, radius, angle1, angle2);
+    }
+
+    // Adds the arc from `angle1` to `angle2`, whichever way round that is, as arc() describes.
+    fn add_arc(&mut self, xc: f32, yc: f32, radius: f32, angle1: f32, mut angle2: f32) {
+        if radius <= 0. || !(angle1.is_finite() && angle2.is_finite()) {
+            self.line_to(xc, yc);
+            return;
+        }
+        // As in cairo, arcs of more than two turns only need to go round twice to look the same
+        while (angle2 - angle1).abs() > 4. * PI {
+            angle2 -= 2. * PI * (angle2 - angle1).signum();
+        }
+
+        let start = Point::new(xc + radius * angle1.cos(), yc + radius * angle1.sin());
+        self.line_to(start.x, start.y);
+        // The path is drawn in device space, so the curves have to be that much closer there
+        let tolerance = self.tolerance / self.matrix.max_scale();
+        for segment in arc_segments(Point::new(xc, yc), radius, angle1, angle2, tolerance) {
+            if let Segment::Curve(_, control1, control2, end) = segment {
+                self.curve_to(control1.x, control1.y, control2.x, control2.y, end.x, end.y);
+            }
+        }
+    }
+
+    /// Closes the current subpath with a line back to its start, which becomes the current
+    /// point.  Analogous to cairo_close_path().
+    pub fn close_path(&mut self) {
+        self.path.close_path();
+    }
+
+    /// Returns the current point in user space, if there is one.
+    ///
+    /// Analogous to cairo_get_current_point().  Without a user space to map it to, because the
+    /// transformation can't be inverted, there is no current point either.
+    pub fn get_current_point(&self) -> Option<Point> {
+        let inverse = self.matrix.invert()?;
+        self.path.current_point().map(|point| inverse.transform_point(point))
+    }
+
+    /// Returns the current path, in device space.
+    ///
+    /// See copy_path() for the path in user space.
+    pub fn get_path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns a copy of the current path, in user space.
+    ///
+    /// Analogous to cairo_copy_path().  The path is mapped back through the current
+    /// transformation, so append_path() with the same transformation adds the same path again.
+    /// If the transformation can't be inverted there is no user space to map to, and the copy
+    /// is empty.
+    ///
+    /// # Usage
+    /// let path = context.copy_path();
+    pub fn copy_path(&self) -> Path {
+        match self.matrix.invert() {
+            Some(inverse) => self.path.transform(&inverse),
+            None => Path::new(),
+        }
+    }
+
+    /// Adds every element of `path`, which is in user space, to the current path.
+    ///
+    /// Analogous to cairo_append_path().  The elements are added as move_to(), line_to(),
+    /// curve_to() and close_path() would add them, so they go through the current transformation
+    /// and a path that starts with a line continues from the current point.
+    ///
+    /// # Usage
+    /// let path = context.copy_path();
+    /// context.append_path(&path);
+    pub fn append_path(&mut self, path: &Path) {
+        for element in path {
+            match *element {
+                PathElement::MoveTo(point) => self.move_to(point.x, point.y),
+                PathElement::LineTo(point) => self.line_to(point.x, point.y),
+                PathElement::CurveTo(control1, control2, end) => {
+                    self.curve_to(control1.x, control1.y, control2.x, control2.y, end.x, end.y)
+                }
+                PathElement::ClosePath => self.close_path(),
+            }
+        }
+    }
+
+    /// Fills the current path with this context's source and operator, then clears it.
+    ///
+    /// Analogous to cairo_fill().  Every subpath is implicitly closed, and self-intersecting
+    /// paths are filled according to the fill rule.  An image surface fills a path that is one
+    /// rectangle with its sides on pixel boundaries row by row, without tessellating it.  A path
+    /// that is one full circle from arc(), or an ellipse under a scale, filled with a single
+    /// color is handed to fill_ellipses(), which fills it analytically.
+    pub fn fill(&mut self) {
+        self.fill_preserve();
+        self.path.clear();
+    }
+
+    /// Fills the current path like fill(), but keeps the path.
+    ///
+    /// Analogous to cairo_fill_preserve().
+    ///
+    /// # Usage
+    /// context.fill_preserve();
+    /// context.stroke();
+    pub fn fill_preserve(&mut self) {
+        let analytic = self.antialias != Antialias::None && self.antialias != Antialias::Subpixel;
+        if let (true, Some(color), Some(ellipse)) = (analytic, self.batched_color(),
+                                                     self.path.as_ellipse()) {
+            let operator = self.operator;
+            self.surface().fill_ellipses(&operator, &color, &[ellipse]);
+            return;
+        }
+        let path = mem::take(&mut self.path);
+        let (fill_rule, tolerance) = (self.fill_rule, self.tolerance);
+        let (target, composite) = self.target_and_composite(self.operator);
+        target.fill(&composite, &path, fill_rule, tolerance);
+        self.path = path;
+    }
+
+    /// Strokes the current path with this context's source, operator, line width, join and cap,
+    /// then clears it.
+    ///
+    /// Analogous to cairo_stroke().  The outline of the stroke is built by the `stroke` module and
+    /// filled like fill() does, so overlapping parts of the stroke are only drawn once.
+    ///
+    /// The line width, dashes and caps are measured in user space, so the path is taken back to
+    /// user space to be stroked and its outline is brought to device space to be filled.  A
+    /// scaled or skewed matrix therefore draws a stretched stroke, as in cairo.
+    ///
+    /// A stroke one pixel wide in device space, with butt caps, no dashes and a single color
+    /// composited with Operator::Over, is drawn as hairlines by stroke_segments() instead, which
+    /// an image surface draws with Xiaolin Wu's line algorithm.
+    pub fn stroke(&mut self) {
+        self.stroke_preserve();
+        self.path.clear();
+    }
+
+    /// Strokes the current path like stroke(), but keeps the path.
+    ///
+    /// Analogous to cairo_stroke_preserve().
+    pub fn stroke_preserve(&mut self) {
+        if let Some((color, segments)) = self.hairline() {
+            self.surface().stroke_segments(&Operator::Over, &color, &segments, 1.);
+            return;
+        }
+
+        let style = StrokeStyle {
+            width: self.line_width,
+            join: self.line_join,
+            cap: self.line_cap,
+            miter_limit: self.miter_limit,
+        };
+        let user_path = match self.matrix.invert() {
+            Some(inverse) => self.path.transform(&inverse),
+            None => return,
+        };
+        let (matrix, dash, tolerance) = (self.matrix, self.dash.clone(), self.tolerance);
+        let (target, composite) = self.target_and_composite(self.operator);
+        target.stroke(&composite, &user_path, &matrix, &style, dash.as_ref(), tolerance);
+    }
+
+    // Returns the color and the lines of the current path, in device space, if stroking it can
+    // draw them as hairlines: one pixel wide single color lines with butt caps composited with
+    // Over, where the joins are too small to see
+    fn hairline(&self) -> Option<(Rgba, Vec<LineSegment>)> {
+        let width = self.matrix.uniform_scale().map(|scale| self.line_width * scale);
+        let hairline = width == Some(1.) && self.operator == Operator::Over &&
+                       self.line_cap == LineCap::Butt && self.dash.is_none() &&
+                       self.antialias != Antialias::None;
+        let color = self.batched_color().filter(|_| hairline)?;
+
+        let mut segments = Vec::new();
+        for polyline in self.path.flatten(self.tolerance) {
+            let points = &polyline.points;
+            for pair in points.windows(2) {
+                segments.push(LineSegment::from_points(pair[0], pair[1]));
+            }
+            if polyline.closed && points.len() > 2 {
+                segments.push(LineSegment::from_points(points[points.len() - 1], points[0]));
+            }
+        }
+        Some((color, segments))
+    }
+
+    /// Selects the font text is drawn in, by family, slant and weight.
+    ///
+    /// Analogous to cairo_select_font_face().  This selects the builtin font, which keeps the
+    /// family but doesn't change the glyphs for it.  See the `text` module, and set_font_face()
+    /// for drawing with a loaded font.
+    ///
+    /// # Usage
+    /// context.select_font_face("sans-serif", FontSlant::Normal, FontWeight::Bold);
+    pub fn select_font_face(&mut self, family: &str, slant: FontSlant, weight: FontWeight) {
+        self.font_face = FontFace::new(family, slant, weight);
+    }
+
+    /// Sets the font text is drawn in.
+    ///
+    /// Analogous to cairo_set_font_face().
+    ///
+    /// # Usage
+    /// context.set_font_face(FontFace::from_font(Font::from_file(path)?));
+    pub fn set_font_face(&mut self, face: FontFace) {
+        self.font_face = face;
+    }
+
+    /// Returns the font text is drawn in.
+    pub fn get_font_face(&self) -> &FontFace {
+        &self.font_face
+    }
+
+    /// Sets the size text is drawn at, the height of an em in user space.  Analogous to
+    /// cairo_set_font_size().  The default is 10, as in cairo.  This replaces the font matrix
+    /// with a scaling by `size`.
+    pub fn set_font_size(&mut self, size: f32) {
+        self.font_matrix = Matrix::scaling(size, size);
+    }
+
+    /// Returns the size text is drawn at, the length of an em's height in user space once the
+    /// font matrix has transformed it.
+    pub fn get_font_size(&self) -> f32 {
+        self.font_matrix.xy.hypot(self.font_matrix.yy)
+    }
+
+    /// Sets the transformation from the glyphs' space, where an em is one unit, to user space.
+    ///
+    /// Analogous to cairo_set_font_matrix().  The font matrix scales, slants, turns or moves
+    /// text on its own, before the current transformation, and the text advances along the
+    /// baseline as it transforms it.  The default is a scaling by 10.
+    ///
+    /// # Usage
+    /// // Text 12 units wide to the em and 20 high, leaning forward
+    /// context.set_font_matrix(Matrix::new(12., 0., -4., 20., 0., 0.));
+    pub fn set_font_matrix(&mut self, matrix: Matrix) {
+        self.font_matrix = matrix;
+    }
+
+    /// Returns the transformation from the glyphs' space to user space.
+    pub fn get_font_matrix(&self) -> Matrix {
+        self.font_matrix
+    }
+
+    /// Returns the extents `text` would have in user space if it were drawn with show_text().
+    ///
+    /// Analogous to cairo_text_extents().  The extents are measured from the point the text
+    /// starts at, on its baseline.  When the font matrix slants or turns the text, the ink
+    /// extents are the box around the transformed ink box.
+    pub fn text_extents(&self, text: &str) -> TextExtents {
+        let extents = self.font_face.text_extents(text);
+        let matrix = &self.font_matrix;
+        let corners = [(extents.x_bearing, extents.y_bearing),
+                       (extents.x_bearing + extents.width, extents.y_bearing),
+                       (extents.x_bearing, extents.y_bearing + extents.height),
+                       (extents.x_bearing + extents.width, extents.y_bearing + extents.height)];
+        let corners: Vec<Point> = corners.iter()
+            .map(|&(x, y)| matrix.transform_point(Point::new(x, y)))
+            .collect();
+        let left = corners.iter().fold(f32::INFINITY, |left, corner| left.min(corner.x));
+        let top = corners.iter().fold(f32::INFINITY, |top, corner| top.min(corner.y));
+        let right = corners.iter().fold(f32::NEG_INFINITY, |right, corner| right.max(corner.x));
+        let bottom = corners.iter().fold(f32::NEG_INFINITY, |bottom, corner| bottom.max(corner.y));
+        let advance = matrix.transform_distance(Point::new(extents.x_advance, extents.y_advance));
+        TextExtents {
+            x_bearing: left,
+            y_bearing: top,
+            width: right - left,
+            height: bottom - top,
+            x_advance: advance.x,
+            y_advance: advance.y,
+        }
+    }
+
+    /// Fills `text` with this context's source and operator, starting at the current point on
+    /// the baseline, then moves the current point past it.
+    ///
+    /// Analogous to cairo_show_text().  The text starts at the origin if there is no current
+    /// point.  It is set in the selected font at the font size, in user space, so the current
+    /// transformation scales and turns it, and the current path is otherwise left alone.
+    /// Unless the text is turned or antialiasing is off, a raster target draws it from the
+    /// shared glyph cache.  See the `glyph_cache` module.  With Antialias::Subpixel a raster
+    /// target draws it for an LCD panel instead, see the `subpixel` module.
+    ///
+    /// # Usage
+    /// context.move_to(10., 20.);
+    /// context.show_text("Hello");
+    pub fn show_text(&mut self, text: &str) {
+        let origin = self.text_origin();
+        let to_device = self.text_matrix();
+        if self.antialias == Antialias::Subpixel && self.surface().extents().is_some() {
+            let outline = self.font_face.text_path_with_matrix(text, &to_device, self.tolerance);
+            let mask = subpixel::subpixel_mask(&outline, FillRule::Winding, self.subpixel_order,
+                                               self.tolerance);
+            let (target, composite) = self.target_and_composite(self.operator);
+            target.mask_subpixel(&composite, &mask);
+            self.move_past(origin, text);
+            return;
+        }
+        // Raster targets composite cached glyphs, where the text is only scaled and moved
+        let cached = if self.antialias != Antialias::None && self.surface().extents().is_some() {
+            let cache = glyph_cache::global().lock();
+            let mut cache = cache.unwrap_or_else(|error| error.into_inner());
+            glyph_cache::text_mask(&mut cache, &self.font_face, text, &to_device)
+        } else {
+            None
+        };
+        match cached {
+            Some(mask) => {
+                let (target, composite) = self.target_and_composite(self.operator);
+                target.mask_coverage(&composite, &mask);
+            },
+            None => {
+                let outline = self.font_face.text_path_with_matrix(text, &to_device,
+                                                                   self.tolerance);
+                if !outline.is_empty() {
+                    let tolerance = self.tolerance;
+                    let (target, composite) = self.target_and_composite(self.operator);
+                    target.fill(&composite, &outline, FillRule::Winding, tolerance);
+                }
+            },
+        }
+        self.move_past(origin, text);
+    }
+
+    /// Adds the outline of `text` to the current path, where show_text() would draw it, then
+    /// moves the current point past it.
+    ///
+    /// Analogous to cairo_text_path().  The outline is closed subpaths to fill with
+    /// `FillRule::Winding`, so text can be filled with any source, clipped to, or stroked.  The
+    /// builtin font's glyphs are overlapping strokes, so a stroke of their outline traces the
+    /// overlaps as well.
+    ///
+    /// # Usage
+    /// context.move_to(10., 40.);
+    /// context.text_path("Hello");
+    /// context.stroke();
+    pub fn text_path(&mut self, text: &str) {
+        let origin = self.text_origin();
+        let matrix = self.text_matrix();
+        let outline = self.font_face.text_path_with_matrix(text, &matrix, self.tolerance);
+        let blank = self.path.iter().all(|element| matches!(*element, PathElement::MoveTo(_)));
+        let runs = match self.text_runs() {
+            Some(runs) => Some(runs.to_vec()),
+            None if blank => Some(Vec::new()),
+            None => None,
+        };
+        // The outline is already in device space
+        for element in &outline {
+            match *element {
+                PathElement::MoveTo(point) => { self.path.move_to(point.x, point.y); },
+                PathElement::LineTo(point) => { self.path.line_to(point.x, point.y); },
+                PathElement::CurveTo(control1, control2, end) => {
+                    self.path.curve_to(control1.x, control1.y, control2.x, control2.y, end.x,
+                                       end.y);
+                },
+                PathElement::ClosePath => { self.path.close_path(); },
+            }
+        }
+        self.text_runs = runs.map(|mut runs| {
+            runs.push((text.to_owned(), matrix));
+            (self.path.clone(), runs)
+        });
+        self.move_past(origin, text);
+    }
+
+    // Returns where text starts: the current point, or the origin without one
+    fn text_origin(&self) -> Point {
+        self.get_current_point().unwrap_or_else(|| Point::new(0., 0.))
+    }
+
+    // Returns the matrix taking the ems of text set from text_origin() to device space
+    pub(crate) fn text_matrix(&self) -> Matrix {
+        let origin = self.text_origin();
+        self.font_matrix
+            .multiply(&Matrix::translation(origin.x, origin.y))
+            .multiply(&self.matrix)
+    }
+
+    // Moves the current point past `text`, set from `origin`
+    fn move_past(&mut self, origin: Point, text: &str) {
+        let extents = self.text_extents(text);
+        self.move_to(origin.x + extents.x_advance, origin.y + extents.y_advance);
+    }
+
+    /// Redirects drawing to a new transparent group, until the matching pop_group().
+    ///
+    /// Analogous to cairo_push_group().  Everything drawn while the group is pushed lands on an
+    /// intermediate surface the size of the target instead of the target, so the result can be
+    /// composited back as a whole, for example with a single alpha.  Groups nest.  The clip still
+    /// applies inside the group.  A target without extents, such as a recording, has no pixels
+    /// for the group to cover, so its groups are empty.
+    ///
+    /// # Usage
+    /// context.push_group();
+    /// context.fill();
+    /// context.pop_group_to_source();
+    /// context.mask(&Pattern::rgba(0., 0., 0., 0.5));
+    pub fn push_group(&mut self) {
+        let (width, height) = match self.target.extents() {
+            Some(extents) => ((extents.x + extents.width).max(0) as usize,
+                              (extents.y + extents.height).max(0) as usize),
+            None => (0, 0),
+        };
+        // Surfaces can't be empty, so an empty group is a single pixel
+        self.groups.push(ImageSurface::create_for_format(Format::ARGB32, width.max(1),
+                                                         height.max(1)));
+    }
+
+    /// Ends the innermost group push_group() started, and returns what was drawn in it.
+    ///
+    /// Analogous to cairo_pop_group().  The group comes back as a surface pattern placed so that,
+    /// set as the source with the current matrix, it lands where it was drawn.  Drawing goes
+    /// back to the enclosing group, or the target.  Returns None if no group was pushed.
+    ///
+    /// # Usage
+    /// let group = context.pop_group().unwrap();
+    pub fn pop_group(&mut self) -> Option<Pattern> {
+        self.groups.pop().map(|surface| {
+            let mut pattern = SurfacePattern::new(surface);
+            pattern.set_matrix(self.matrix);
+            Pattern::Surface(pattern)
+        })
+    }
+
+    /// Ends the innermost group push_group() started, and sets it as the source.
+    ///
+    /// Analogous to cairo_pop_group_to_source().  Does nothing if no group was pushed.
+    ///
+    /// # Usage
+    /// context.pop_group_to_source();
+    /// context.paint();
+    pub fn pop_group_to_source(&mut self) {
+        if let Some(group) = self.pop_group() {
+            self.set_source(group);
+        }
+    }
+
+    /// Finishes the target's current page and clears it for the next.
+    ///
+    /// Analogous to cairo_show_page().  The context's state, including its path and clip, carries
+    /// on to the next page.  See `ImageSurface::show_page`.
+    pub fn show_page(&mut self) {
+        self.target.show_page();
+    }
+
+    /// Finishes the target's current page and starts the next from a copy of it.
+    ///
+    /// Analogous to cairo_copy_page().  See `ImageSurface::copy_page`.
+    pub fn copy_page(&mut self) {
+        self.target.copy_page();
+    }
+
+    /// Paints this context's source on the destination surface with the over operator.
+    ///
+    /// This is a completely naive, and frankly useless implementation.  It is a place holder for
+    /// the real paint function to later be implemented.  It operates on every 'pixel' of the
+    /// destination surface, or every pixel inside the clip.
+    pub fn paint(&mut self) {
+        let (target, composite) = self.target_and_composite(Operator::Over);
+        target.paint(&composite);
+    }
+
+    /// Composites this context's source onto the target through the alpha channel of `mask`.
+    ///
+    /// Analogous to cairo_mask().  Each pixel gets (source IN mask) OP destination: the source
+    /// is composited with the operator as far as the mask's alpha at that pixel, and the clip,
+    /// let it.  The mask is placed in user space by the current matrix, and its color channels
+    /// are ignored.
+    ///
+    /// # Arguments
+    /// * `&mut self` - Reference to the `Context` whose target is drawn on.
+    /// * `mask` - The pattern whose alpha channel the source is composited through.
+    ///
+    /// # Usage
+    /// context.mask(&Pattern::rgba(0., 0., 0., 0.5));
+    pub fn mask(&mut self, mask: &Pattern) {
+        let matrix = self.matrix;
+        let (target, composite) = self.target_and_composite(self.operator);
+        target.mask(&composite, mask, &matrix);
+    }
+
+    /// Composites this context's source onto the target through the alpha channel of `surface`,
+    /// with its top left corner at (surface_x, surface_y) in user space.
+    ///
+    /// Analogous to cairo_mask_surface().  An A8 surface is the usual mask, but the alpha of any
+    /// format will do.  See mask().
+    ///
+    /// # Usage
+    /// context.mask_surface(alpha, 10., 10.);
+    pub fn mask_surface(&mut self, surface: ImageSurface, surface_x: f32, surface_y: f32) {
+        let mut pattern = SurfacePattern::new(surface);
+        pattern.set_matrix(Matrix::translation(-surface_x, -surface_y));
+        self.mask(&Pattern::Surface(pattern));
+    }
+
+    /// Draws a blurred, offset copy of the current path's shape in `color`, and keeps the path,
+    /// so that a fill() straight afterwards draws the shape over its own shadow.
+    ///
+    /// The shape is rendered as an alpha mask with the fill rule, blurred by a Gaussian of
+    /// standard deviation `blur`, moved by (`offset_x`, `offset_y`) and composited in `color`
+    /// with the over operator, through the clip.  The offset and blur are in device pixels, like
+    /// a CSS box-shadow, so the shadow doesn't stretch or turn with the current matrix.
+    ///
+    /// # Arguments
+    /// * `&mut self` - Reference to the `Context` whose current path casts the shadow.
+    /// * `offset_x` - How far right of the shape the shadow falls.
+    /// * `offset_y` - How far below the shape the shadow falls.
+    /// * `blur` - The standard deviation of the blur, or 0 for a hard shadow.
+    /// * `color` - The premultiplied color of the shadow.
+    ///
+    /// # Usage
+    /// context.arc(50., 50., 20., 0., 2. * PI);
+    /// context.drop_shadow(4., 4., 3., &Rgba::new(0., 0., 0., 0.5));
+    /// context.fill();
+    pub fn drop_shadow(&mut self, offset_x: f32, offset_y: f32, blur: f32, color: &Rgba) {
+        let extents = match self.path.extents() {
+            Some(extents) => extents,
+            None => return,
+        };
+        // The blur spreads the shape three standard deviations, see gaussian_blur()
+        let margin = if blur > 0. && blur.is_finite() { (3. * blur).ceil() + 1. } else { 1. };
+        let (left, top) = ((extents.x - margin).floor(), (extents.y - margin).floor());
+        let right = (extents.x + extents.width + margin).ceil();
+        let bottom = (extents.y + extents.height + margin).ceil();
+        if !(left.is_finite() && top.is_finite() && right.is_finite() && bottom.is_finite()) {
+            return;
+        }
+
+        let mut mask = ImageSurface::create_for_format(Format::A8, (right - left) as usize,
+                                                       (bottom - top) as usize);
+        let opaque = Pattern::rgba(0., 0., 0., 1.);
+        let shape = Composite {
+            operator: Operator::Over,
+            source: &opaque,
+            source_matrix: Matrix::identity(),
+            coverage_only: false,
+            antialias: self.antialias,
+            rasterizer: self.rasterizer,
+            precision: self.precision,
+            threads: self.threads,
+            clip: None,
+        };
+        let path = self.path.transform(&Matrix::translation(-left, -top));
+        mask.fill(&shape, &path, self.fill_rule, self.tolerance);
+        let mut shadow = SurfacePattern::new(image_filter::gaussian_blur(&mask, blur,
+                                                                         Extend::None));
+        shadow.set_matrix(Matrix::translation(-(left + offset_x), -(top + offset_y)));
+
+        let tint = Pattern::Solid(SolidPattern::new(*color));
+        let composite = Composite {
+            operator: Operator::Over,
+            source: &tint,
+            source_matrix: Matrix::identity(),
+            coverage_only: self.coverage_only,
+            antialias: self.antialias,
+            rasterizer: self.rasterizer,
+            precision: self.precision,
+            threads: self.threads,
+            clip: self.clip.as_ref(),
+        };
+        let target: &mut dyn Surface = match self.groups.last_mut() {
+            Some(group) => group,
+            None => &mut *self.target,
+        };
+        target.mask(&composite, &Pattern::Surface(shadow), &Matrix::identity());
+    }
+}
+
+// # References
+// [Cairo Operators]: https://www.cairographics.org/operators/
+
+// Returns an image of the colors stroke_polyline_gradient() gives each device pixel it might
+// touch: the color `start_color` fades to `end_color` by at the nearest point along `segments`,
+// measured in user space, which `matrix` takes to device space
+fn polyline_gradient_colors(segments: &[LineSegment], start_color: &Rgba, end_color: &Rgba,
+                            matrix: &Matrix, line_width: f32, width: usize,
+                            height: usize) -> ImageSurface {
+    let mut colors = ImageSurface::create_for_format(Format::ARGB32, width, height);
+    let length = |segment: &LineSegment| {
+        (segment.point2.x - segment.point1.x).hypot(segment.point2.y - segment.point1.y)
+    };
+    let segments: Vec<&LineSegment> = segments.iter()
+        .filter(|segment| length(segment) > 0.)
+        .collect();
+    let total_length: f32 = segments.iter().map(|segment| length(segment)).sum();
+    let to_user = match matrix.invert() {
+        Some(to_user) if total_length > 0. => to_user,
+        _ => return colors,
+    };
+
+    // Only pixels within half a line width of the polyline can be stroked
+    let reach = line_width / 2. * matrix.max_scale() + 1.;
+    let (mut left, mut top, mut right, mut bottom) = (f32::MAX, f32::MAX, f32::MIN, f32::MIN);
+    for segment in segments.iter() {
+        for &point in [segment.point1, segment.point2].iter() {
+            let point = matrix.transform_point(point);
+            left = left.min(point.x - reach);
+            top = top.min(point.y - reach);
+            right = right.max(point.x + reach);
+            bottom = bottom.max(point.y + reach);
+        }
+    }
+    let columns = left.max(0.) as usize..(right.max(0.).ceil() as usize).min(width);
+    let rows = top.max(0.) as usize..(bottom.max(0.).ceil() as usize).min(height);
+
+    for y in rows {
+        for x in columns.clone() {
+            let point = to_user.transform_point(Point::new(x as f32 + 0.5, y as f32 + 0.5));
+            let (mut nearest, mut distance, mut offset) = (f32::MAX, 0., 0.);
+            for segment in segments.iter() {
+                let length = length(segment);
+                let (dx, dy) = (segment.point2.x - segment.point1.x,
+                                segment.point2.y - segment.point1.y);
+                let (px, py) = (point.x - segment.point1.x, point.y - segment.point1.y);
+                let t = ((px * dx + py * dy) / (length * length)).clamp(0., 1.);
+                let away = (px - t * dx).hypot(py - t * dy);
+                if away < nearest {
+                    nearest = away;
+                    distance = offset + t * length;
+                }
+                offset += length;
+            }
+            if let Some(pixel) = colors.get_mut(x, y) {
+                *pixel = start_color.interpolate(end_color, distance / total_length);
+            }
+        }
+    }
+    colors
+}
+
+// Returns the trapezoids covering the area inside `path`, which is in device space
+fn tessellate(path: &Path, fill_rule: FillRule, precision: Precision, tolerance: f32)
+              -> Vec<Trapezoid> {
+    let edges = path.edges(tolerance);
+    if edges.is_empty() {
+        Vec::new()
+    } else {
+        bo_trap::sweep_with_precision(edges, fill_rule, precision)
+    }
+}
+
+#[cfg(test)]
+mod tests{
+
+    use surfaces::{Format, ImageSurface};
+    use types::Rgba;
+    use operators::Operator;
+    use common_geometry::{Ellipse, LineSegment, Point, Rectangle};
+    use types::{Antialias, FillRule, Filter, FontSlant, FontWeight, LineCap, LineJoin,
+                Rasterizer, SubpixelOrder};
+    use matrix::Matrix;
+    use path::PathElement;
+    use pattern::{Pattern, SurfacePattern};
+    use std::f32::consts::{FRAC_PI_2, PI};
+    use Precision;
+    use std::thread;
+    use glyph_cache::{self, GlyphKey};
+    use text::FontFace;
+    use super::Context;
+
+    #[test]
+    fn test_get_default_operator(){
+        // Setup
+        let mut surface = ImageSurface::create_for_format(Format::ARGB32, 255, 255);
+        let context = Context::create( &mut surface );
+
+        // Call
+        let op = context.get_operator();
+
+        // Assert
+        assert_eq!( &Operator::Over, op );
+    }
+
+    #[test]
+    fn test_set_operator(){
+        // Setup
+        let mut surface = ImageSurface::create_for_format(Format::ARGB32, 255, 255);
+        let mut context = Context::create( &mut surface );
+
+        // Call
+        context.set_operator(Operator::In);
+        let op = context.get_operator();
+
+        // Assert
+        assert_eq!( &Operator::In, op );
+    }
+
+    // This tests that naive paint covers the target.  It does two calls, in order to check that
+    // multiple mutable borrows (via paint) work fine too.
+    #[test]
+    fn test_paint() {
+        // Setup
+        let mut target = ImageSurface::create_for_format(Format::ARGB32, 100, 100);
+
+        // Call
+        {
+            let mut context = Context::create(&mut target);
+            context.set_source_rgba(1., 0., 0., 1.);
+            context.paint();
+            context.set_source_rgba(0., 1., 0., 1.);
+            context.paint();
+        }
+
+        // Test
+        let expected = Rgba::new(0., 1., 0., 1.);
+        for pixel in target.iter() {
+            assert_eq!(*pixel, expected);
+        }
+    }
+
+    #[test]
+    fn test_fill_rectangles() {
+        // Setup
+        let mut target = ImageSurface::create_for_format(Format::ARGB32, 20, 20);
+        let rectangles = vec![
+            Rectangle::new(0., 0., 5., 5.),
+            Rectangle::new(10., 10., 5., 5.),
+        ];
+
+        // Call
+        {
+            let mut context = Context::create(&mut target);
+            context.set_source_rgba(1., 0., 0., 1.);
+            context.fill_rectangles(&rectangles);
+        }
+
+        // Test
+        let expected = Rgba::new(1., 0., 0., 1.);
+        assert_eq!(*target.get(2, 2).unwrap(), expected);
+        assert_eq!(*target.get(12, 12).unwrap(), expected);
+        assert_eq!(target.get(7, 7).unwrap().alpha, 0.);
+    }
+
+    #[test]
+    fn test_fill_rectangles_uses_operator() {
+        // Source replaces the destination inside the rectangles, even with a transparent source
+        let mut target = ImageSurface::create_for_format(Format::ARGB32, 10, 10);
+        {
+            let mut context = Context::create(&mut target);
+            context.set_source_rgba(0., 1., 0., 1.);
+            context.paint();
+            context.set_operator(Operator::Source);
+            context.set_source_rgba(0., 0., 0., 0.);
+            context.fill_rectangles(&[Rectangle::new(0., 0., 5., 10.)]);
+        }
+
+        assert_eq!(target.get(2, 2).unwrap().alpha, 0.);
+        assert_eq!(*target.get(7, 2).unwrap(), Rgba::new(0., 1., 0., 1.));
+    }
+
+    #[test]
+    fn test_fill_ellipses() {
+        let mut target = ImageSurface::create_for_format(Format::ARGB32, 10, 10);
+        {
+            let mut context = Context::create(&mut target);
+            context.set_source_rgba(0., 1., 0., 1.);
+            context.fill_ellipses(&[Ellipse::circle(5., 5., 3.)]);
+        }
+
+        assert_eq!(*target.get(5, 5).unwrap(), Rgba::new(0., 1., 0., 1.));
+        assert_eq!(target.get(0, 0).unwrap().alpha, 0.);
+    }
+
+    // Returns the largest difference between any channel of any pixel of `a` and `b`
+    fn largest_difference(a: &ImageSurface, b: &ImageSurface) -> f32 {
+        a.iter().zip(b.iter()).map(|(a, b)| {
+            (a.red - b.red).abs().max((a.green - b.green).abs())
+                                 .max((a.blue - b.blue).abs())
+                                 .max((a.alpha - b.alpha).abs())
+        }).fold(0., f32::max)
+    }
+
+    #[test]
+    fn test_fill_rectangles_follows_matrix() {
+        // Setup
+        let mut target = ImageSurface::create_for_format(Format::ARGB32, 20, 20);
+
+        // Call
+        {
+            let mut context = Context::create(&mut target);
+            context.translate(5., 5.);
+            context.scale(2., 2.);
+            context.set_source_rgba(1., 0., 0., 1.);
+            context.fill_rectangles(&[Rectangle::new(0., 0., 2., 2.)]);
+        }
+
+        // Test
+        assert_eq!(*target.get(5, 5).unwrap(), Rgba::new(1., 0., 0., 1.));
+        assert_eq!(*target.get(8, 8).unwrap(), Rgba::new(1., 0., 0., 1.));
+        assert_eq!(target.get(4, 4).unwrap().alpha, 0.);
+        assert_eq!(target.get(9, 9).unwrap().alpha, 0.);
+    }
+
+    #[test]
+    fn test_batched_helpers_under_rotation_and_skew_match_paths() {
+        // Each helper, under a matrix it can't draw through directly, draws what the path
+        // functions do
+        let draw = |batched: bool| {
+            let mut target = ImageSurface::create_for_format(Format::ARGB32, 40, 40);
+            {
+                let mut context = Context::create(&mut target);
+                context.translate(20., 20.);
+                context.rotate(0.5);
+                context.set_source_rgba(1., 0., 0., 0.5);
+                if batched {
+                    context.fill_rectangles(&[Rectangle::new(-10., -10., 8., 5.)]);
+                } else {
+                    context.move_to(-10., -10.);
+                    context.line_to(-2., -10.);
+                    context.line_to(-2., -5.);
+                    context.line_to(-10., -5.);
+                    context.close_path();
+                    context.fill();
+                }
+
+                context.transform(&Matrix::new(1., 0., 0.5, 1., 0., 0.));
+                context.set_source_rgba(0., 0., 1., 1.);
+                context.set_line_width(3.);
+                if batched {
+                    context.stroke_segments(&[LineSegment::new(0., 5., 12., 10.)]);
+                } else {
+                    context.move_to(0., 5.);
+                    context.line_to(12., 10.);
+                    context.stroke();
+                }
+            }
+            target
+        };
+
+        assert!(largest_difference(&draw(true), &draw(false)) < 1e-4);
+    }
+
+    #[test]
+    fn test_fill_ellipses_follows_matrix() {
+        // A circle under a rotation is drawn analytically, and an ellipse under one as a path,
+        // both where arc() under the same matrix would draw them
+        let draw = |batched: bool| {
+            let mut target = ImageSurface::create_for_format(Format::ARGB32, 40, 40);
+            {
+                let mut context = Context::create(&mut target);
+                context.translate(20., 20.);
+                context.rotate(0.7);
+                context.scale(2., 2.);
+                context.set_source_rgba(0., 1., 0., 1.);
+                if batched {
+                    context.fill_ellipses(&[Ellipse::circle(4., 0., 3.)]);
+                } else {
+                    context.arc(4., 0., 3., 0., 2. * PI);
+                    context.fill();
+                }
+                context.scale(2., 1.);
+                if batched {
+                    context.fill_ellipses(&[Ellipse::circle(-4., 0., 2.)]);
+                } else {
+                    context.arc(-4., 0., 2., 0., 2. * PI);
+                    context.fill();
+                }
+            }
+            target
+        };
+
+        assert!(largest_difference(&draw(true), &draw(false)) < 0.1);
+    }
+
+    #[test]
+    fn test_stroke_polyline_gradient_follows_matrix() {
+        // Setup
+        let mut target = ImageSurface::create_for_format(Format::ARGB32, 40, 20);
+        let points = [Point::new(0., 5.), Point::new(20., 5.)];
+
+        // Call
+        {
+            let mut context = Context::create(&mut target);
+            context.scale(2., 1.);
+            context.set_source_rgba(1., 1., 1., 1.);
+            context.set_line_width(2.);
+            context.stroke_polyline_gradient(&points, &Rgba::new(0., 0., 0., 1.));
+        }
+
+        // Test
+        // The stroke is stretched across the surface and fades along its user space length
+        assert!((target.get(0, 4).unwrap().green - 0.9875).abs() < 1e-5);
+        assert!((target.get(20, 5).unwrap().green - 0.4875).abs() < 1e-5);
+        assert!((target.get(39, 4).unwrap().green - 0.0125).abs() < 1e-5);
+        assert_eq!(target.get(39, 4).unwrap().alpha, 1.);
+        assert_eq!(target.get(20, 6).unwrap().alpha, 0.);
+    }
+
+    #[test]
+    fn test_batched_helpers_are_clipped() {
+        // Setup
+        let mut target = ImageSurface::create_for_format(Format::ARGB32, 20, 20);
+
+        // Call
+        {
+            let mut context = Context::create(&mut target);
+            context.move_to(0., 0.);
+            context.line_to(10., 0.);
+            context.line_to(10., 20.);
+            context.line_to(0., 20.);
+            context.close_path();
+            context.clip();
+            context.set_source_rgba(1., 0., 0., 1.);
+            context.fill_rectangles(&[Rectangle::new(0., 0., 20., 4.)]);
+            context.fill_ellipses(&[Ellipse::circle(10., 8., 3.)]);
+            context.set_line_width(2.);
+            context.stroke_segments(&[LineSegment::new(0., 13., 20., 13.)]);
+            context.stroke_polyline_gradient(&[Point::new(0., 17.), Point::new(20., 17.)],
+                                             &Rgba::new(0., 0., 1., 1.));
+        }
+
+        // Test
+        for &y in [2, 8, 12, 16].iter() {
+            assert_eq!(target.get(8, y).unwrap().alpha, 1., "row {}", y);
+            assert_eq!(target.get(12, y).unwrap().alpha, 0., "row {}", y);
+        }
+        // The gradient still fades along the whole polyline, and pixel 4 is 22.5% of the way
+        assert!((target.get(4, 16).unwrap().red - 0.775).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_fill_full_arc_is_analytic() {
+        // A full circle from arc() fills as fill_ellipses() does, under a rotation and
+        // uniform scale too
+        let draw = |analytic: bool| {
+            let mut target = ImageSurface::create_for_format(Format::ARGB32, 40, 40);
+            {
+                let mut context = Context::create(&mut target);
+                context.translate(20., 20.);
+                context.rotate(0.3);
+                context.scale(1.5, 1.5);
+                context.set_source_rgba(0., 0., 1., 0.8);
+                if analytic {
+                    context.fill_ellipses(&[Ellipse::circle(3., 1., 7.3)]);
+                } else {
+                    context.arc(3., 1., 7.3, 0.4, 0.4 + 2. * PI);
+                    context.fill();
+                }
+            }
+            target
+        };
+        let difference = largest_difference(&draw(true), &draw(false));
+        assert!(difference < 1e-3);
+    }
+
+    #[test]
+    fn test_in_clip_unclipped() {
+        // Without a clip every point is inside, even ones off the surface
+        let mut surface = ImageSurface::create_for_format(Format::ARGB32, 10, 10);
+        let context = Context::create(&mut surface);
+
+        assert!(context.in_clip(5., 5.));
+        assert!(context.in_clip(-100., 250.));
+        assert!(!context.in_clip(f32::NAN, 0.));
+    }
+
+    // Adds the rectangle from (x, y) to (x + width, y + height) to the context's path
+    fn rectangle(context: &mut Context, x: f32, y: f32, width: f32, height: f32) {
+        context.move_to(x, y);
+        context.line_to(x + width, y);
+        context.line_to(x + width, y + height);
+        context.line_to(x, y + height);
+        context.close_path();
+    }
+
+    #[test]
+    fn test_in_clip() {
+        // Setup
+        let mut surface = ImageSurface::create_for_format(Format::ARGB32, 10, 10);
+        let mut context = Context::create(&mut surface);
+
+        // Call
+        rectangle(&mut context, 2., 2., 6., 6.);
+        context.clip();
+        rectangle(&mut context, 4., 0., 6., 10.);
+        context.clip_preserve();
+
+        // Test
+        // The clip is the intersection of both rectangles, from 4 to 8 across and 2 to 8 down
+        assert!(context.in_clip(5., 5.));
+        assert!(!context.in_clip(3., 5.));
+        assert!(!context.in_clip(9., 5.));
+        assert!(!context.in_clip(5., 1.));
+        assert!(!context.get_path().is_empty());
+        // The point is in user space
+        context.translate(-2., 0.);
+        assert!(context.in_clip(7., 5.));
+        assert!(!context.in_clip(5., 5.));
+
+        context.reset_clip();
+        assert!(context.in_clip(-100., 250.));
+    }
+
+    #[test]
+    fn test_clip_fill_and_stroke() {
+        // Setup
+        let mut target = ImageSurface::create_for_format(Format::ARGB32, 10, 10);
+
+        // Call
+        {
+            let mut context = Context::create(&mut target);
+            context.set_source_rgba(1., 0., 0., 1.);
+            rectangle(&mut context, 0., 0., 5., 10.);
+            context.clip();
+            rectangle(&mut context, 2., 2., 6., 2.);
+            context.fill();
+            context.move_to(0., 7.);
+            context.line_to(10., 7.);
+            context.stroke();
+        }
+
+        // Test
+        assert_eq!(target.get(3, 3).unwrap().alpha, 1.);
+        assert_eq!(target.get(6, 3).unwrap().alpha, 0.);
+        assert_eq!(target.get(4, 6).unwrap().alpha, 1.);
+        assert_eq!(target.get(5, 6).unwrap().alpha, 0.);
+    }
+
+    #[test]
+    fn test_clip_paint() {
+        // Setup
+        let mut target = ImageSurface::create_for_format(Format::ARGB32, 10, 10);
+
+        // Call
+        {
+            let mut context = Context::create(&mut target);
+            context.set_source_rgba(0., 0., 1., 1.);
+            rectangle(&mut context, 0., 0., 4.5, 10.);
+            context.clip();
+            context.paint();
+        }
+
+        // Test
+        assert_eq!(target.get(3, 3).unwrap().alpha, 1.);
+        assert!((target.get(4, 3).unwrap().alpha - 0.5).abs() < 0.05);
+        assert_eq!(target.get(5, 3).unwrap().alpha, 0.);
+    }
+
+    #[test]
+    fn test_clip_to_empty_path() {
+        // Clipping to an empty path leaves nothing to draw on, until the clip is reset
+        let mut target = ImageSurface::create_for_format(Format::ARGB32, 10, 10);
+        {
+            let mut context = Context::create(&mut target);
+            context.set_source_rgba(0., 0., 1., 1.);
+            context.clip();
+            assert!(!context.in_clip(5., 5.));
+            context.paint();
+            context.reset_clip();
+            rectangle(&mut context, 0., 0., 1., 1.);
+            context.fill();
+        }
+        assert_eq!(target.get(0, 0).unwrap().alpha, 1.);
+        assert_eq!(target.get(5, 5).unwrap().alpha, 0.);
+    }
+
+    #[test]
+    fn test_clip_to_text_from_glyph_cache() {
+        // Clips to an H, or to the H and a line of no length, which isn't only text and so is
+        // tessellated, then paints
+        let draw = |line: bool| {
+            let mut target = ImageSurface::create_for_format(Format::ARGB32, 40, 30);
+            {
+                let mut context = Context::create(&mut target);
+                context.set_source_rgba(0., 0., 0., 1.);
+                context.select_font_face("serif", FontSlant::Normal, FontWeight::Bold);
+                context.set_font_size(16.);
+                context.move_to(4.25, 20.);
+                context.text_path("H");
+                if line {
+                    context.line_to(14.25, 20.);
+                }
+                context.clip();
+                assert!(context.in_clip(5., 16.));
+                assert!(!context.in_clip(9., 16.));
+                context.paint();
+            }
+            target
+        };
+
+        // Call
+        let cached = draw(false);
+        let tessellated = draw(true);
+
+        // Test
+        // The H's glyph was cached at a quarter pixel's phase across
+        let face = FontFace::new("serif", FontSlant::Normal, FontWeight::Bold);
+        let index = face.text_to_glyphs("H")[0].index;
+        let key = GlyphKey::new(&face, 16., 16., index, (1, 0));
+        assert!(glyph_cache::global().lock().unwrap().get(&key).is_some());
+        assert_eq!(cached.get(5, 16).unwrap().alpha, 1.);
+        assert_eq!(cached.get(9, 16).unwrap().alpha, 0.);
+        assert!(largest_difference(&cached, &tessellated) < 0.1);
+    }
+
+    // Returns a 2x2 surface with red, green, blue and white pixels
+    fn checker() -> ImageSurface {
+        let mut surface = ImageSurface::create_for_format(Format::ARGB32, 2, 2);
+        surface.set_pixel(0, 0, Rgba::new(1., 0., 0., 1.)).unwrap();
+        surface.set_pixel(1, 0, Rgba::new(0., 1., 0., 1.)).unwrap();
+        surface.set_pixel(0, 1, Rgba::new(0., 0., 1., 1.)).unwrap();
+        surface.set_pixel(1, 1, Rgba::new(1., 1., 1., 1.)).unwrap();
+        surface
+    }
+
+    #[test]
+    fn test_set_source() {
+        let mut surface = ImageSurface::create_for_format(Format::ARGB32, 10, 10);
+        let mut context = Context::create(&mut surface);
+        context.set_source(Pattern::rgba(0., 1., 0., 1.));
+        assert_eq!(context.rgba, Rgba::new(0., 1., 0., 1.));
+
+        context.set_source(Pattern::for_surface(checker()));
+        match *context.get_source() {
+            Pattern::Surface(ref pattern) => assert_eq!(pattern.surface().width, 2),
+            _ => panic!("expected a surface pattern"),
+        }
+        // The last solid color stays in place for the solid-only helpers
+        assert_eq!(context.rgba, Rgba::new(0., 1., 0., 1.));
+
+        context.set_source_rgba(1., 0., 0., 1.);
+        match *context.get_source() {
+            Pattern::Solid(ref pattern) => assert_eq!(pattern.color, Rgba::new(1., 0., 0., 1.)),
+            _ => panic!("expected a solid pattern"),
+        }
+    }
+
+    #[test]
+    fn test_fill_with_surface_pattern() {
+        // Setup
+        let mut target = ImageSurface::create_for_format(Format::ARGB32, 10, 10);
+
+        // Call
+        {
+            let mut context = Context::create(&mut target);
+            // The pattern is placed at (4, 4), twice the size, and stays there when the matrix
+            // changes afterwards
+            context.translate(4., 4.);
+            context.scale(2., 2.);
+            let mut pattern = SurfacePattern::new(checker());
+            pattern.set_filter(Filter::Nearest);
+            context.set_source(Pattern::Surface(pattern));
+            context.identity_matrix();
+            rectangle(&mut context, 0., 0., 10., 10.);
+            context.fill();
+        }
+
+        // Test
+        assert_eq!(*target.get(4, 5).unwrap(), Rgba::new(1., 0., 0., 1.));
+        assert_eq!(*target.get(7, 4).unwrap(), Rgba::new(0., 1., 0., 1.));
+        assert_eq!(*target.get(5, 6).unwrap(), Rgba::new(0., 0., 1., 1.));
+        assert_eq!(*target.get(7, 7).unwrap(), Rgba::new(1., 1., 1., 1.));
+        // Outside the pattern's surface nothing is drawn
+        assert_eq!(target.get(3, 3).unwrap().alpha, 0.);
+        assert_eq!(target.get(8, 8).unwrap().alpha, 0.);
+    }
+
+    #[test]
+    fn test_scaled_surface_pattern_is_filtered() {
+        // Setup
+        let mut target = ImageSurface::create_for_format(Format::ARGB32, 4, 4);
+
+        // Call
+        {
+            let mut context = Context::create(&mut target);
+            context.scale(2., 2.);
+            context.set_source(Pattern::for_surface(checker()));
+            context.paint();
+        }
+
+        // Test
+        // The default filter blends neighbouring pixels rather than repeating them
+        let between = *target.get(1, 1).unwrap();
+        assert!(between.red > 0.5 && between.green > 0. && between.blue > 0.);
+        assert!(target.get(2, 1).unwrap().green > target.get(1, 1).unwrap().green);
+    }
+
+    #[test]
+    fn test_set_source_surface_transformed() {
+        // Setup
+        let mut image = ImageSurface::create_for_format(Format::ARGB32, 4, 2);
+        for pixel in image.iter_mut() {
+            *pixel = Rgba::new(1., 0., 0., 1.);
+        }
+        let mut target = ImageSurface::create_for_format(Format::ARGB32, 12, 12);
+
+        // Call
+        {
+            let mut context = Context::create(&mut target);
+            context.translate(6., 2.);
+            context.rotate(FRAC_PI_2);
+            context.scale(2., 2.);
+            context.set_source_surface(image, 0., -1.);
+            context.paint();
+        }
+
+        // Test
+        // Scaled up and turned a quarter, the 4x2 image covers 2 columns either side of x = 6,
+        // down 8 rows from y = 2
+        assert_eq!(*target.get(5, 3).unwrap(), Rgba::new(1., 0., 0., 1.));
+        assert_eq!(*target.get(6, 8).unwrap(), Rgba::new(1., 0., 0., 1.));
+        assert_eq!(target.get(2, 5).unwrap().alpha, 0.);
+        assert_eq!(target.get(6, 11).unwrap().alpha, 0.);
+        // Bilinear filtering softens the edges
+        let edge = target.get(3, 5).unwrap().alpha;
+        assert!(edge > 0. && edge < 1.);
+    }
+
+    #[test]
+    fn test_set_source_surface_reduced() {
+        // Setup
+        let mut stripes = ImageSurface::create_for_format(Format::ARGB32, 8, 8);
+        for (position, pixel) in stripes.iter_mut().enumerate() {
+            let shade = if position % 2 == 0 { 1. } else { 0. };
+            *pixel = Rgba::new(shade, shade, shade, 1.);
+        }
+        let mut target = ImageSurface::create_for_format(Format::ARGB32, 2, 2);
+
+        // Call
+        {
+            let mut context = Context::create(&mut target);
+            context.scale(0.25, 0.25);
+            context.set_source_surface(stripes, 0., 0.);
+            context.paint();
+        }
+
+        // Test
+        // Each target pixel averages the 4x4 block of stripes it covers
+        for pixel in target.iter() {
+            assert_eq!(*pixel, Rgba::new(0.5, 0.5, 0.5, 1.));
+        }
+    }
+
+    #[test]
+    fn test_mask() {
+        // Setup
+        let mut target = ImageSurface::create_for_format(Format::ARGB32, 4, 4);
+
+        // Call
+        {
+            let mut context = Context::create(&mut target);
+            context.set_source_rgba(1., 0., 0., 1.);
+            context.mask(&Pattern::rgba(0., 0., 1., 0.25));
+        }
+
+        // Test
+        // Only the mask's alpha matters
+        assert!(target.iter().all(|pixel| *pixel == Rgba::new(1., 0., 0., 0.25)));
+    }
+
+    #[test]
+    fn test_mask_surface() {
+        // Setup
+        let mut target = ImageSurface::create_for_format(Format::ARGB32, 6, 6);
+        let mut alpha = ImageSurface::create_for_format(Format::A8, 2, 2);
+        alpha.set_pixel(0, 0, Rgba::new(0., 0., 0., 1.)).unwrap();
+        alpha.set_pixel(1, 0, Rgba::new(0., 0., 0., 0.5)).unwrap();
+        for pixel in target.iter_mut() {
+            *pixel = Rgba::new(0., 0., 1., 1.);
+        }
+
+        // Call
+        {
+            let mut context = Context::create(&mut target);
+            context.set_source_rgba(1., 0., 0., 1.);
+            context.set_operator(Operator::Source);
+            let mut mask = SurfacePattern::new(alpha);
+            mask.set_filter(Filter::Nearest);
+            context.translate(2., 2.);
+            context.mask(&Pattern::Surface(mask));
+        }
+
+        // Test
+        // The mask sits at (2, 2), and its clear pixels leave the destination alone even though
+        // the operator is Source
+        assert_eq!(*target.get(2, 2).unwrap(), Rgba::new(1., 0., 0., 1.));
+        assert_eq!(*target.get(3, 2).unwrap(), Rgba::new(0.5, 0., 0.5, 1.));
+        assert_eq!(*target.get(2, 3).unwrap(), Rgba::new(0., 0., 1., 1.));
+        assert_eq!(*target.get(1, 2).unwrap(), Rgba::new(0., 0., 1., 1.));
+    }
+
+    #[test]
+    fn test_mask_surface_is_clipped() {
+        // Setup
+        let mut target = ImageSurface::create_for_format(Format::ARGB32, 4, 4);
+        let mut alpha = ImageSurface::create_for_format(Format::A8, 4, 4);
+        for pixel in alpha.iter_mut() {
+            *pixel = Rgba::new(0., 0., 0., 1.);
+        }
+
+        // Call
+        {
+            let mut context = Context::create(&mut target);
+            context.set_source_rgba(1., 0., 0., 1.);
+            rectangle(&mut context, 0., 0., 2., 4.);
+            context.clip();
+            context.mask_surface(alpha, 1., 0.);
+        }
+
+        // Test
+        assert_eq!(target.get(0, 0).unwrap().alpha, 0.);
+        assert_eq!(target.get(1, 0).unwrap().alpha, 1.);
+        assert_eq!(target.get(2, 0).unwrap().alpha, 0.);
+    }
+
+    #[test]
+    fn test_drop_shadow() {
+        // Setup
+        let mut surface = ImageSurface::create_for_format(Format::ARGB32, 20, 20);
+
+        // Call
+        {
+            let mut context = Context::create(&mut surface);
+            context.set_source_rgba(1., 0., 0., 1.);
+            rectangle(&mut context, 4., 4., 6., 6.);
+            context.drop_shadow(4., 4., 0., &Rgba::new(0., 0., 0., 0.5));
+            context.fill();
+        }
+
+        // Test
+        // The fill lands on top of the shadow, which shows where the fill doesn't
+        assert_eq!(surface.get_pixel(6, 6).unwrap(), Rgba::new(1., 0., 0., 1.));
+        assert_eq!(surface.get_pixel(12, 12).unwrap(), Rgba::new(0., 0., 0., 0.5));
+        assert_eq!(surface.get_pixel(14, 12).unwrap(), Rgba::new(0., 0., 0., 0.));
+        assert_eq!(surface.get_pixel(2, 2).unwrap(), Rgba::new(0., 0., 0., 0.));
+    }
+
+    #[test]
+    fn test_drop_shadow_blurred() {
+        // Setup
+        let mut surface = ImageSurface::create_for_format(Format::ARGB32, 20, 20);
+
+        // Call
+        {
+            let mut context = Context::create(&mut surface);
+            context.scale(2., 2.);
+            rectangle(&mut context, 2., 2., 3., 3.);
+            context.drop_shadow(4., 0., 1.5, &Rgba::new(0., 0., 1., 1.));
+        }
+
+        // Test
+        // The blur is in device pixels, whatever the matrix, and the shadow fades out from the
+        // middle of the shape to beyond its edges
+        let middle = surface.get_pixel(11, 7).unwrap();
+        let edge = surface.get_pixel(14, 7).unwrap();
+        let outside = surface.get_pixel(16, 7).unwrap();
+        assert!(middle.alpha > edge.alpha && edge.alpha > outside.alpha && outside.alpha > 0.);
+        assert_eq!(outside.blue, outside.alpha);
+        assert_eq!(surface.get_pixel(11, 18).unwrap(), Rgba::new(0., 0., 0., 0.));
+    }
+
+    #[test]
+    fn test_group_composites_as_a_whole() {
+        // Setup
+        let mut target = ImageSurface::create_for_format(Format::ARGB32, 6, 2);
+
+        // Call
+        {
+            let mut context = Context::create(&mut target);
+            context.translate(1., 0.);
+            context.push_group();
+            context.set_source_rgba(1., 0., 0., 1.);
+            rectangle(&mut context, 0., 0., 2., 2.);
+            context.fill();
+            rectangle(&mut context, 1., 0., 2., 2.);
+            context.fill();
+            context.pop_group_to_source();
+            context.mask(&Pattern::rgba(0., 0., 0., 0.5));
+        }
+
+        // Test
+        // The overlapping rectangles only cover the group once, and it lands where it was drawn
+        let expected = [0., 0.5, 0.5, 0.5, 0., 0.];
+        for (x, alpha) in expected.iter().enumerate() {
+            assert_eq!(*target.get(x, 0).unwrap(), Rgba::new(1., 0., 0., *alpha));
+        }
+    }
+
+    #[test]
+    fn test_pop_group() {
+        // Setup
+        let mut target = ImageSurface::create_for_format(Format::ARGB32, 2, 2);
+        let mut context = Context::create(&mut target);
+
+        // Call
+        context.push_group();
+        let group = context.pop_group();
+
+        // Test
+        match group {
+            Some(Pattern::Surface(ref pattern)) => assert_eq!(pattern.surface().width, 2),
+            _ => panic!("expected the group's surface"),
+        }
+        assert!(context.pop_group().is_none());
+    }
+
+    #[test]
+    fn test_unbounded_operators_clear_outside_the_shape() {
+        for rasterizer in [Rasterizer::Trapezoids, Rasterizer::Spans] {
+            // Setup
+            let blue = Rgba::new(0., 0., 1., 0.5);
+            let mut target = ImageSurface::create_for_format(Format::ARGB32, 6, 4);
+            for pixel in target.iter_mut() {
+                *pixel = blue;
+            }
+
+            // Call
+            {
+                let mut context = Context::create(&mut target);
+                context.set_rasterizer(rasterizer);
+                rectangle(&mut context, 0., 0., 4., 4.);
+                context.clip();
+                context.set_source_rgba(1., 0., 0., 1.);
+                context.set_operator(Operator::In);
+                rectangle(&mut context, 1., 1., 2., 2.);
+                context.fill();
+            }
+
+            // Test
+            // In draws the source where the destination was, and clears the rest of the clip
+            // where there is no source.  Outside the clip is left alone.
+            for (position, pixel) in target.iter().enumerate() {
+                let (x, y) = (position % 6, position / 6);
+                let expected = if x >= 4 {
+                    blue
+                } else if (1..3).contains(&x) && (1..3).contains(&y) {
+                    Rgba::new(1., 0., 0., 0.5)
+                } else {
+                    Rgba::new(0., 0., 0., 0.)
+                };
+                assert_eq!(*pixel, expected, "{:?} at ({}, {})", rasterizer, x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn test_bounded_operators_leave_outside_the_shape() {
+        // Setup
+        let blue = Rgba::new(0., 0., 1., 1.);
+        let mut target = ImageSurface::create_for_format(Format::ARGB32, 4, 4);
+        for pixel in target.iter_mut() {
+            *pixel = blue;
+        }
+
+        // Call
+        {
+            let mut context = Context::create(&mut target);
+            context.set_source_rgba(1., 0., 0., 0.5);
+            context.set_operator(Operator::Source);
+            rectangle(&mut context, 1., 1., 2., 2.);
+            context.fill();
+        }
+
+        // Test
+        assert_eq!(*target.get(1, 1).unwrap(), Rgba::new(1., 0., 0., 0.5));
+        assert_eq!(*target.get(0, 0).unwrap(), blue);
+        assert_eq!(*target.get(3, 2).unwrap(), blue);
+    }
+
+    #[test]
+    fn test_unbounded_operator_edges_scale_the_source() {
+        // Setup
+        let mut target = ImageSurface::create_for_format(Format::ARGB32, 4, 1);
+        for pixel in target.iter_mut() {
+            *pixel = Rgba::new(0., 0., 1., 1.);
+        }
+
+        // Call
+        {
+            let mut context = Context::create(&mut target);
+            context.set_source_rgba(1., 0., 0., 1.);
+            context.set_operator(Operator::In);
+            rectangle(&mut context, 0., 0., 1.5, 1.);
+            context.fill();
+        }
+
+        // Test
+        // Half covered, In leaves half the source and none of the destination, as in cairo
+        assert_eq!(*target.get(0, 0).unwrap(), Rgba::new(1., 0., 0., 1.));
+        let edge = *target.get(1, 0).unwrap();
+        assert!((edge.red - 0.5).abs() < 0.05 && edge.blue.abs() < 1e-6);
+        assert_eq!(edge.alpha, edge.red);
+        assert_eq!(*target.get(3, 0).unwrap(), Rgba::new(0., 0., 0., 0.));
+    }
+
+    #[test]
+    fn test_paint_with_surface_pattern() {
+        // Setup
+        let mut target = ImageSurface::create_for_format(Format::ARGB32, 4, 4);
+
+        // Call
+        {
+            let mut context = Context::create(&mut target);
+            context.set_source(Pattern::for_surface(checker()));
+            context.paint();
+        }
+
+        // Test
+        assert_eq!(*target.get(1, 1).unwrap(), Rgba::new(1., 1., 1., 1.));
+        assert_eq!(target.get(2, 1).unwrap().alpha, 0.);
+    }
+
+    #[test]
+    fn test_coverage_only() {
+        // Setup
+        let mut target = ImageSurface::create_for_format(Format::ARGB32, 10, 10);
+
+        // Call
+        {
+            let mut context = Context::create(&mut target);
+            assert!(!context.get_coverage_only());
+            context.set_coverage_only(true);
+            context.set_source_rgba(1., 0., 0., 0.25);
+            context.fill_rectangles(&[Rectangle::new(0., 0., 4., 10.),
+                                      Rectangle::new(2., 0., 0.5, 10.)]);
+            context.set_line_width(1.);
+            context.stroke_segments(&[LineSegment::new(0., 8., 10., 8.)]);
+        }
+
+        // Test
+        // Only coverage lands: full inside the rectangles, half on the hairline's edge rows
+        assert_eq!(*target.get(1, 1).unwrap(), Rgba::new(0., 0., 0., 1.));
+        assert_eq!(*target.get(2, 5).unwrap(), Rgba::new(0., 0., 0., 1.));
+        assert_eq!(target.get(6, 7).unwrap().alpha, 0.5);
+        assert_eq!(target.get(6, 8).unwrap().alpha, 0.5);
+        assert_eq!(target.get(6, 5).unwrap().alpha, 0.);
+    }
+
+    #[test]
+    fn test_line_width() {
+        let mut surface = ImageSurface::create_for_format(Format::ARGB32, 10, 10);
+        let mut context = Context::create(&mut surface);
+        assert_eq!(context.get_line_width(), 2.);
+
+        context.set_line_width(4.5);
+        assert_eq!(context.get_line_width(), 4.5);
+
+        context.set_line_width(-1.);
+        assert_eq!(context.get_line_width(), 0.);
+    }
+
+    #[test]
+    fn test_stroke_segments() {
+        // Setup
+        let mut target = ImageSurface::create_for_format(Format::ARGB32, 10, 10);
+        let segments = [LineSegment::new(0., 5., 10., 5.)];
+
+        // Call
+        {
+            let mut context = Context::create(&mut target);
+            context.set_source_rgba(0., 0., 1., 1.);
+            context.set_line_width(4.);
+            context.stroke_segments(&segments);
+        }
+
+        // Test
+        let expected = Rgba::new(0., 0., 1., 1.);
+        for y in 0..10 {
+            let inside = (3..7).contains(&y);
+            assert_eq!(target.get(4, y).unwrap().alpha, if inside { 1. } else { 0. });
+        }
+        assert_eq!(*target.get(0, 3).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_stroke_one_pixel_wide_is_hairline() {
+        // Setup
+        let points = [Point::new(2., 3.), Point::new(17., 9.), Point::new(6., 18.)];
+        let mut expected = ImageSurface::create_for_format(Format::ARGB32, 20, 20);
+        let segments = [LineSegment::from_points(points[0], points[1]),
+                        LineSegment::from_points(points[1], points[2])];
+        expected.stroke_segments(&Operator::Over, &Rgba::new(0., 0., 0., 1.), &segments, 1.);
+        let mut target = ImageSurface::create_for_format(Format::ARGB32, 20, 20);
+
+        // Call
+        {
+            let mut context = Context::create(&mut target);
+            context.scale(0.5, 0.5);
+            context.set_source_rgba(0., 0., 0., 1.);
+            context.set_line_width(2.);
+            context.move_to(4., 6.);
+            context.line_to(34., 18.);
+            context.line_to(12., 36.);
+            context.stroke();
+        }
+
+        // Test
+        assert!(target.iter().zip(expected.iter()).all(|(actual, expected)| actual == expected));
+    }
+
+    #[test]
+    fn test_stroke_polyline_gradient() {
+        // Setup
+        let mut target = ImageSurface::create_for_format(Format::ARGB32, 10, 10);
+        let points = [Point::new(0., 5.), Point::new(10., 5.)];
+
+        // Call
+        {
+            let mut context = Context::create(&mut target);
+            context.set_source_rgba(1., 1., 1., 1.);
+            context.stroke_polyline_gradient(&points, &Rgba::new(0., 0., 0., 1.));
+        }
+
+        // Test
+        // Pixel centers half a unit from either end are 5% of the way along
+        assert!((target.get(0, 4).unwrap().green - 0.95).abs() < 1e-5);
+        assert!((target.get(9, 5).unwrap().green - 0.05).abs() < 1e-5);
+        assert_eq!(target.get(9, 5).unwrap().alpha, 1.);
+    }
+
+    #[test]
+    fn test_set_rgba_happy(){
+        let mut surface = ImageSurface::create_for_format(Format::ARGB32, 100, 100);
+        let mut context = Context::create(&mut surface);
+        context.set_source_rgba(0.1, 0.2, 0.3, 1.);
+        assert_eq!(context.rgba.red, 0.1);
+        assert_eq!(context.rgba.green, 0.2);
+        assert_eq!(context.rgba.blue, 0.3);
+        assert_eq!(context.rgba.alpha, 1.);
+
+        // Test Rbga premultiply
+        context.set_source_rgba(0.2, 0.4, 0.6, 0.5);
+        assert_eq!(context.rgba.red, 0.1);
+        assert_eq!(context.rgba.green, 0.2);
+        assert_eq!(context.rgba.blue, 0.3);
+        assert_eq!(context.rgba.alpha, 0.5);
+    }
+
+    #[test]
+    fn test_set_rgba_out_of_bounds_values(){
+        let mut surface = ImageSurface::create_for_format(Format::ARGB32, 100, 100);
+        let mut context = Context::create(&mut surface);
+
+        // Test negative alpha value pre-multiplting to zero
+        context.set_source_rgba(1., 1., 1., -10.);
+        assert_eq!(context.rgba.red, 0.);
+        assert_eq!(context.rgba.green, 0.);
+        assert_eq!(context.rgba.blue, 0.);
+        assert_eq!(context.rgba.alpha, 0.);
+
+        // Test bound to range [0,1]
+        context.set_source_rgba(-22.,22.,-22.,9.);
+        assert_eq!(context.rgba.red, 0.);
+        assert_eq!(context.rgba.green, 1.);
+        assert_eq!(context.rgba.blue, 0.);
+        assert_eq!(context.rgba.alpha, 1.);
+    }
+
+    #[test]
+    fn test_fill_path() {
+        // Setup
+        let mut target = ImageSurface::create_for_format(Format::ARGB32, 20, 20);
+
+        // Call
+        {
+            let mut context = Context::create(&mut target);
+            context.set_source_rgba(1., 0., 0., 1.);
+            context.move_to(2., 2.);
+            context.line_to(12., 2.);
+            context.line_to(12., 12.);
+            context.line_to(2., 12.);
+            context.fill();
+            // The path is cleared, so filling again draws nothing
+            context.set_source_rgba(0., 0., 1., 1.);
+            context.fill();
+        }
+
+        // Test
+        assert_eq!(*target.get(7, 7).unwrap(), Rgba::new(1., 0., 0., 1.));
+        assert_eq!(target.get(15, 7).unwrap().alpha, 0.);
+        assert_eq!(target.get(7, 15).unwrap().alpha, 0.);
+    }
+
+    #[test]
+    fn test_fill_preserve_and_stroke_preserve() {
+        // Setup
+        let mut target = ImageSurface::create_for_format(Format::ARGB32, 20, 20);
+
+        // Call
+        {
+            let mut context = Context::create(&mut target);
+            context.move_to(4., 4.);
+            context.line_to(16., 4.);
+            context.line_to(16., 16.);
+            context.line_to(4., 16.);
+            context.close_path();
+            context.set_source_rgba(1., 0., 0., 1.);
+            context.fill_preserve();
+            context.set_source_rgba(0., 0., 1., 1.);
+            context.set_line_width(2.);
+            context.stroke_preserve();
+            assert!(context.get_current_point().is_some());
+            context.stroke();
+            assert!(context.get_current_point().is_none());
+        }
+
+        // Test
+        assert_eq!(*target.get(10, 10).unwrap(), Rgba::new(1., 0., 0., 1.));
+        assert_eq!(*target.get(4, 10).unwrap(), Rgba::new(0., 0., 1., 1.));
+        assert_eq!(target.get(1, 10).unwrap().alpha, 0.);
+    }
+
+    #[test]
+    fn test_fill_rule() {
+        // Two squares drawn the same way round: the inner one is a hole only under even-odd
+        let fill = |fill_rule| {
+            let mut target = ImageSurface::create_for_format(Format::ARGB32, 20, 20);
+            {
+                let mut context = Context::create(&mut target);
+                context.set_source_rgba(0., 1., 0., 1.);
+                context.set_fill_rule(fill_rule);
+                for &(min, max) in &[(2., 18.), (6., 14.)] {
+                    context.move_to(min, min);
+                    context.line_to(max, min);
+                    context.line_to(max, max);
+                    context.line_to(min, max);
+                    context.close_path();
+                }
+                context.fill();
+            }
+            (target.get(10, 10).unwrap().alpha, target.get(4, 10).unwrap().alpha)
+        };
+
+        assert_eq!(fill(FillRule::Winding), (1., 1.));
+        assert_eq!(fill(FillRule::EvenOdd), (0., 1.));
+    }
+
+    #[test]
+    fn test_fill_precision() {
+        // A star whose edges cross at points f32 can't represent fills the same either way
+        let fill = |precision| {
+            let mut target = ImageSurface::create_for_format(Format::ARGB32, 40, 40);
+            {
+                let mut context = Context::create(&mut target);
+                context.set_precision(precision);
+                assert_eq!(context.get_precision(), precision);
+                context.set_source_rgba(0., 0., 1., 1.);
+                context.move_to(20., 1.);
+                for index in 1..7 {
+                    let angle = index as f32 * 6. * PI / 7.;
+                    context.line_to(20. + 19. * angle.sin(), 20. - 19. * angle.cos());
+                }
+                context.close_path();
+                context.fill();
+            }
+            target
+        };
+
+        let fast = fill(Precision::Fast);
+        let exact = fill(Precision::Exact);
+        assert_eq!(fast.get(20, 20).unwrap().alpha, 1.);
+        assert!(largest_difference(&fast, &exact) < 0.01);
+    }
+
+    #[test]
+    fn test_fill_antialias() {
+        // A triangle's slanted edge is partly covered unless antialiasing is off
+        let fill = |antialias| {
+            let mut target = ImageSurface::create_for_format(Format::ARGB32, 20, 20);
+            {
+                let mut context = Context::create(&mut target);
+                context.set_source_rgba(0., 1., 0., 1.);
+                context.set_antialias(antialias);
+                assert_eq!(context.get_antialias(), antialias);
+                context.move_to(2., 2.);
+                context.line_to(18., 2.);
+                context.line_to(2., 13.);
+                context.close_path();
+                context.fill();
+            }
+            target.iter().filter(|pixel| pixel.alpha > 0. && pixel.alpha < 1.).count()
+        };
+
+        assert!(fill(Antialias::Default) > 0);
+        assert_eq!(fill(Antialias::None), 0);
+    }
+
+    #[test]
+    fn test_fill_rasterizers() {
+        // Both rasterizers fill a star with the fill rule, clipped, and agree to within the
+        // coverage of edge pixels
+        let fill = |rasterizer| {
+            let mut target = ImageSurface::create_for_format(Format::ARGB32, 20, 20);
+            {
+                let mut context = Context::create(&mut target);
+                context.set_rasterizer(rasterizer);
+                assert_eq!(context.get_rasterizer(), rasterizer);
+                rectangle(&mut context, 0., 0., 20., 15.);
+                context.clip();
+                context.set_source_rgba(1., 0., 0., 1.);
+                context.set_fill_rule(FillRule::EvenOdd);
+                context.move_to(10., 1.);
+                context.line_to(16., 19.);
+                context.line_to(1., 7.);
+                context.line_to(19., 7.);
+                context.line_to(4., 19.);
+                context.close_path();
+                context.fill();
+            }
+            target
+        };
+
+        let trapezoids = fill(Rasterizer::Trapezoids);
+        let spans = fill(Rasterizer::Spans);
+        for (trapezoid, span) in trapezoids.iter().zip(spans.iter()) {
+            assert!((trapezoid.alpha - span.alpha).abs() < 0.1);
+        }
+        // The pentagon in the middle is outside under the even-odd rule, and the clip cuts off
+        // the points at the bottom
+        assert_eq!(spans.get(10, 10).unwrap().alpha, 0.);
+        assert_eq!(spans.get(10, 4).unwrap().alpha, 1.);
+        assert_eq!(spans.get(15, 17).unwrap().alpha, 0.);
+        assert!(trapezoids.get(14, 14).unwrap().alpha > 0.);
+    }
+
+    #[test]
+    fn test_fill_threads() {
+        // Rendering in tiles on several threads draws exactly what one thread does
+        let fill = |threads| {
+            let mut target = ImageSurface::create_for_format(Format::ARGB32, 120, 90);
+            {
+                let mut context = Context::create(&mut target);
+                context.set_threads(threads);
+                context.set_source_rgba(0., 0.5, 1., 0.8);
+                context.arc(60., 45., 40., 0., 2. * PI);
+                context.fill();
+                context.set_line_width(7.);
+                context.move_to(3., 80.);
+                context.line_to(117., 5.);
+                context.stroke();
+            }
+            target
+        };
+
+        let single = fill(1);
+        assert!(single.iter().any(|pixel| pixel.alpha > 0.));
+        assert!(single.iter().eq(fill(4).iter()));
+    }
+
+    #[test]
+    fn test_set_threads_keeps_count() {
+        // Setup
+        let mut target = ImageSurface::create_for_format(Format::ARGB32, 2, 2);
+        let mut context = Context::create(&mut target);
+        let available = thread::available_parallelism().map_or(1, |available| available.get());
+
+        // Call
+        context.set_threads(available + 3);
+
+        // Test
+        assert_eq!(context.get_threads(), available + 3);
+    }
+
+    #[test]
+    fn test_set_threads_takes_zero_as_one() {
+        let mut target = ImageSurface::create_for_format(Format::ARGB32, 2, 2);
+        let mut context = Context::create(&mut target);
+        context.set_threads(0);
+        assert_eq!(context.get_threads(), 1);
+    }
+
+    #[test]
+    fn test_fill_curve() {
+        // A lens bounded by two curves covers its middle but not the corners of its bounding box
+        let mut target = ImageSurface::create_for_format(Format::ARGB32, 20, 20);
+        {
+            let mut context = Context::create(&mut target);
+            context.set_source_rgba(0., 0., 1., 1.);
+            context.move_to(2., 10.);
+            context.curve_to(2., 2., 18., 2., 18., 10.);
+            context.curve_to(18., 18., 2., 18., 2., 10.);
+            context.fill();
+        }
+        assert_eq!(*target.get(10, 10).unwrap(), Rgba::new(0., 0., 1., 1.));
+        assert_eq!(target.get(3, 3).unwrap().alpha, 0.);
+        assert_eq!(target.get(16, 16).unwrap().alpha, 0.);
+    }
+
+    #[test]
+    fn test_stroke_path() {
+        // Setup
+        let mut target = ImageSurface::create_for_format(Format::ARGB32, 20, 20);
+
+        // Call
+        {
+            let mut context = Context::create(&mut target);
+            context.set_source_rgba(1., 0., 0., 1.);
+            context.move_to(2., 4.);
+            context.line_to(16., 4.);
+            context.line_to(16., 16.);
+            context.close_path();
+            context.stroke();
+        }
+
+        // Test
+        let expected = Rgba::new(1., 0., 0., 1.);
+        assert_eq!(*target.get(8, 3).unwrap(), expected);
+        assert_eq!(*target.get(8, 4).unwrap(), expected);
+        assert_eq!(*target.get(16, 10).unwrap(), expected);
+        // The closing line runs diagonally back to (2, 4)
+        assert!(target.get(9, 10).unwrap().alpha > 0.);
+        assert_eq!(target.get(12, 7).unwrap().alpha, 0.);
+    }
+
+    #[test]
+    fn test_line_to_without_current_point() {
+        // line_to without a current point only moves there, so the path strokes a single line
+        let mut target = ImageSurface::create_for_format(Format::ARGB32, 10, 10);
+        {
+            let mut context = Context::create(&mut target);
+            context.set_source_rgba(1., 1., 1., 1.);
+            context.line_to(0., 5.);
+            context.line_to(10., 5.);
+            context.stroke();
+        }
+        assert_eq!(target.get(5, 4).unwrap().alpha, 1.);
+        assert_eq!(target.get(5, 1).unwrap().alpha, 0.);
+    }
+
+    #[test]
+    fn test_line_join_and_cap() {
+        let mut surface = ImageSurface::create_for_format(Format::ARGB32, 10, 10);
+        let mut context = Context::create(&mut surface);
+        assert_eq!(context.get_line_join(), LineJoin::Miter);
+        assert_eq!(context.get_line_cap(), LineCap::Butt);
+        context.set_line_join(LineJoin::Bevel);
+        context.set_line_cap(LineCap::Square);
+        assert_eq!(context.get_line_join(), LineJoin::Bevel);
+        assert_eq!(context.get_line_cap(), LineCap::Square);
+        assert_eq!(context.get_miter_limit(), 10.);
+        context.set_miter_limit(2.);
+        assert_eq!(context.get_miter_limit(), 2.);
+    }
+
+    #[test]
+    fn test_set_dash() {
+        let mut surface = ImageSurface::create_for_format(Format::ARGB32, 10, 10);
+        let mut context = Context::create(&mut surface);
+        assert!(context.get_dash().is_none());
+
+        context.set_dash(&[1., 2., 3.], -1.).unwrap();
+        let pattern = context.get_dash().unwrap();
+        assert_eq!(pattern.dashes(), &[1., 2., 3., 1., 2., 3.]);
+        assert_eq!(pattern.offset(), -1.);
+
+        // Invalid patterns leave the current one in place
+        let error = context.set_dash(&[0., 0.], 0.).unwrap_err();
+        assert_eq!(error.dashes, vec![0., 0.]);
+        assert!(context.set_dash(&[1., -1.], 0.).is_err());
+        assert_eq!(context.get_dash().unwrap().offset(), -1.);
+
+        context.set_dash(&[], 0.).unwrap();
+        assert!(context.get_dash().is_none());
+    }
+
+    #[test]
+    fn test_stroke_dashed() {
+        // Setup
+        let mut target = ImageSurface::create_for_format(Format::ARGB32, 10, 4);
+
+        // Call
+        {
+            let mut context = Context::create(&mut target);
+            context.set_source_rgba(1., 0., 0., 1.);
+            context.set_dash(&[2., 1.], 0.).unwrap();
+            context.move_to(0., 2.);
+            context.line_to(10., 2.);
+            context.stroke();
+        }
+
+        // Test
+        let row: Vec<f32> = (0..10).map(|x| target.get(x, 1).unwrap().alpha).collect();
+        assert_eq!(row, vec![1., 1., 0., 1., 1., 0., 1., 1., 0., 1.]);
+    }
+
+    #[test]
+    fn test_matrix() {
+        let mut surface = ImageSurface::create_for_format(Format::ARGB32, 10, 10);
+        let mut context = Context::create(&mut surface);
+        assert_eq!(context.get_matrix(), Matrix::identity());
+
+        context.translate(1., 2.);
+        context.scale(2., 3.);
+        let mut expected = Matrix::translation(1., 2.);
+        expected.scale(2., 3.);
+        assert_eq!(context.get_matrix(), expected);
+
+        context.transform(&Matrix::translation(1., 0.));
+        assert_eq!(context.get_matrix(), Matrix::new(2., 0., 0., 3., 3., 2.));
+        context.identity_matrix();
+        assert_eq!(context.get_matrix(), Matrix::identity());
+        context.set_matrix(Matrix::scaling(4., 4.));
+        assert_eq!(context.get_matrix(), Matrix::scaling(4., 4.));
+    }
+
+    #[test]
+    fn test_path_is_transformed() {
+        let mut surface = ImageSurface::create_for_format(Format::ARGB32, 10, 10);
+        let mut context = Context::create(&mut surface);
+        context.translate(5., 5.);
+        context.rotate(FRAC_PI_2);
+        context.move_to(1., 0.);
+        context.line_to(1., 2.);
+        context.curve_to(0., 0., 0., 0., 0., 0.);
+        let points: Vec<PathElement> = context.get_path().elements().to_vec();
+        assert_eq!(points[0], PathElement::MoveTo(Point::new(5., 6.)));
+        match points[1] {
+            PathElement::LineTo(point) => {
+                assert!((point.x - 3.).abs() < 1e-5 && (point.y - 6.).abs() < 1e-5);
+            },
+            _ => panic!("expected a line"),
+        }
+        assert_eq!(points[2], PathElement::CurveTo(Point::new(5., 5.), Point::new(5., 5.),
+                                                   Point::new(5., 5.)));
+    }
+
+    #[test]
+    fn test_fill_scaled() {
+        // Setup
+        let mut target = ImageSurface::create_for_format(Format::ARGB32, 10, 10);
+
+        // Call
+        {
+            let mut context = Context::create(&mut target);
+            context.set_source_rgba(1., 0., 0., 1.);
+            context.scale(2., 2.);
+            context.move_to(1., 1.);
+            context.line_to(3., 1.);
+            context.line_to(3., 3.);
+            context.line_to(1., 3.);
+            context.fill();
+        }
+
+        // Test
+        // The square covers 2 to 6 on the surface
+        assert_eq!(target.get(2, 2).unwrap().alpha, 1.);
+        assert_eq!(target.get(5, 5).unwrap().alpha, 1.);
+        assert_eq!(target.get(6, 5).unwrap().alpha, 0.);
+        assert_eq!(target.get(1, 2).unwrap().alpha, 0.);
+    }
+
+    #[test]
+    fn test_stroke_scaled() {
+        // Setup
+        let mut target = ImageSurface::create_for_format(Format::ARGB32, 12, 12);
+
+        // Call
+        {
+            let mut context = Context::create(&mut target);
+            context.set_source_rgba(1., 0., 0., 1.);
+            // The line width is in user space, so it becomes 2 wide and 4 high
+            context.scale(1., 2.);
+            context.set_line_width(2.);
+            context.move_to(6., 1.);
+            context.line_to(6., 5.);
+            context.move_to(2., 5.);
+            context.line_to(10., 5.);
+            context.stroke();
+        }
+
+        // Test
+        // The vertical line covers x from 5 to 7, the horizontal one y from 8 to 12
+        assert_eq!(target.get(5, 3).unwrap().alpha, 1.);
+        assert_eq!(target.get(7, 3).unwrap().alpha, 0.);
+        assert_eq!(target.get(3, 8).unwrap().alpha, 1.);
+        assert_eq!(target.get(3, 11).unwrap().alpha, 1.);
+        assert_eq!(target.get(3, 7).unwrap().alpha, 0.);
+    }
+
+    #[test]
+    fn test_stroke_with_singular_matrix() {
+        let mut target = ImageSurface::create_for_format(Format::ARGB32, 10, 10);
+        {
+            let mut context = Context::create(&mut target);
+            context.set_source_rgba(1., 0., 0., 1.);
+            context.move_to(1., 5.);
+            context.line_to(9., 5.);
+            context.set_matrix(Matrix::scaling(0., 1.));
+            context.stroke();
+            assert!(context.get_path().is_empty());
+        }
+        assert!(target.into_iter().all(|pixel| pixel.alpha == 0.));
+    }
+
+    #[test]
+    fn test_quad_to() {
+        let mut surface = ImageSurface::create_for_format(Format::ARGB32, 10, 10);
+        let mut context = Context::create(&mut surface);
+        context.translate(1., 0.);
+        context.move_to(0., 0.);
+        context.quad_to(3., 6., 6., 0.);
+        assert_eq!(context.get_path().elements()[1],
+                   PathElement::CurveTo(Point::new(3., 4.), Point::new(5., 4.),
+                                        Point::new(7., 0.)));
+    }
+
+    #[test]
+    fn test_relative_elements() {
+        let mut surface = ImageSurface::create_for_format(Format::ARGB32, 10, 10);
+        let mut context = Context::create(&mut surface);
+        context.scale(2., 3.);
+        context.rel_line_to(1., 1.);
+        assert!(context.get_path().is_empty());
+
+        context.move_to(1., 1.);
+        context.rel_line_to(1., 0.);
+        context.rel_curve_to(0., 1., 1., 1., 1., 0.);
+        context.rel_move_to(0., 1.);
+        assert_eq!(context.get_path().elements(),
+                   &[PathElement::MoveTo(Point::new(2., 3.)),
+                     PathElement::LineTo(Point::new(4., 3.)),
+                     PathElement::CurveTo(Point::new(4., 6.), Point::new(6., 6.),
+                                          Point::new(6., 3.)),
+                     PathElement::MoveTo(Point::new(6., 6.))]);
+    }
+
+    #[test]
+    fn test_copy_and_append_path() {
+        let mut surface = ImageSurface::create_for_format(Format::ARGB32, 10, 10);
+        let mut context = Context::create(&mut surface);
+        context.translate(1., 2.);
+        context.scale(2., 2.);
+        context.move_to(1., 1.);
+        context.line_to(3., 1.);
+        context.curve_to(3., 2., 2., 3., 1., 3.);
+        context.close_path();
+
+        let copy = context.copy_path();
+        assert_eq!(copy.elements(),
+                   &[PathElement::MoveTo(Point::new(1., 1.)),
+                     PathElement::LineTo(Point::new(3., 1.)),
+                     PathElement::CurveTo(Point::new(3., 2.), Point::new(2., 3.),
+                                          Point::new(1., 3.)),
+                     PathElement::ClosePath]);
+
+        // Replaying the copy under the same transformation gives the same device space path
+        let device = context.get_path().clone();
+        context.new_path();
+        context.append_path(&copy);
+        assert_eq!(context.get_path(), &device);
+
+        // A different transformation moves it
+        context.new_path();
+        context.identity_matrix();
+        context.append_path(&copy);
+        assert_eq!(context.get_path(), &copy);
+
+        context.scale(0., 1.);
+        assert!(context.copy_path().is_empty());
+    }
+
+    #[test]
+    fn test_arc() {
+        // Setup
+        let mut surface = ImageSurface::create_for_format(Format::ARGB32, 10, 10);
+        let mut context = Context::create(&mut surface);
+
+        // Call
+        context.move_to(0., 0.);
+        context.arc(5., 5., 2., 0., PI);
+
+        // Test
+        // A line joins the current point to the start, and the arc ends on the far side
+        let elements = context.get_path().elements().to_vec();
+        assert_eq!(elements[1], PathElement::LineTo(Point::new(7., 5.)));
+        let end = context.get_path().current_point().unwrap();
+        assert!((end.x - 3.).abs() < 1e-5 && (end.y - 5.).abs() < 1e-5);
+        // Going the positive way round passes below the center, in y-down device space
+        match elements[2] {
+            PathElement::CurveTo(control1, _, _) => assert!(control1.y > 5.),
+            _ => panic!("expected a curve"),
+        }
+    }
+
+    #[test]
+    fn test_arc_negative() {
+        let mut surface = ImageSurface::create_for_format(Format::ARGB32, 10, 10);
+        let mut context = Context::create(&mut surface);
+
+        // Without a current point the arc starts a new subpath
+        context.arc_negative(5., 5., 2., 0., PI);
+        let elements = context.get_path().elements().to_vec();
+        assert_eq!(elements[0], PathElement::MoveTo(Point::new(7., 5.)));
+        match elements[1] {
+            PathElement::CurveTo(control1, _, _) => assert!(control1.y < 5.),
+            _ => panic!("expected a curve"),
+        }
+
+        // A radius of zero adds a line to the center
+        context.arc(1., 1., 0., 0., PI);
+        assert_eq!(context.get_path().current_point(), Some(Point::new(1., 1.)));
+    }
+
+    #[test]
+    fn test_fill_circle() {
+        // Setup
+        let mut target = ImageSurface::create_for_format(Format::ARGB32, 20, 20);
+
+        // Call
+        {
+            let mut context = Context::create(&mut target);
+            context.set_source_rgba(0., 0., 1., 1.);
+            context.set_tolerance(0.01);
+            context.arc(10., 10., 6., 0., 2. * PI);
+            context.fill();
+        }
+
+        // Test
+        let area: f32 = target.iter().map(|pixel| pixel.alpha).sum();
+        assert!((area - 36. * PI).abs() < 0.5);
+        assert_eq!(target.get(10, 10).unwrap().alpha, 1.);
+        assert_eq!(target.get(15, 15).unwrap().alpha, 0.);
+    }
+
+    #[test]
+    fn test_tolerance_bounds_arcs() {
+        // Setup
+        let mut target = ImageSurface::create_for_format(Format::ARGB32, 10, 10);
+        let mut context = Context::create(&mut target);
+        context.scale(4., 4.);
+        let mut counts = Vec::new();
+
+        for &tolerance in [1., 0.1, 0.01].iter() {
+            // Call
+            context.new_path();
+            context.set_tolerance(tolerance);
+            context.arc(10., 10., 5., 0., 2. * PI);
+            let polylines = context.get_path().flatten(tolerance);
+
+            // Test
+            // The circle has a radius of 20 around (40, 40) in device space
+            let points = &polylines[polylines.len() - 1].points;
+            assert!(points.iter().all(|point| {
+                ((point.x - 40.).hypot(point.y - 40.) - 20.).abs() <= 2. * tolerance + 1e-3
+            }));
+            counts.push(points.len());
+        }
+        // A tighter tolerance takes more lines
+        assert!(counts[0] < counts[1] && counts[1] < counts[2]);
+    }
+
+    #[test]
+    fn test_tolerance() {
+        let mut surface = ImageSurface::create_for_format(Format::ARGB32, 10, 10);
+        let mut context = Context::create(&mut surface);
+        assert_eq!(context.get_tolerance(), 0.1);
+        context.set_tolerance(0.5);
+        assert_eq!(context.get_tolerance(), 0.5);
+        context.set_tolerance(0.);
+        assert_eq!(context.get_tolerance(), 1. / 256.);
+        context.set_tolerance(f32::NAN);
+        assert_eq!(context.get_tolerance(), 1. / 256.);
+    }
+
+    #[test]
+    fn test_show_text() {
+        // Setup
+        let mut target = ImageSurface::create_for_format(Format::ARGB32, 40, 30);
+
+        // Call
+        {
+            let mut context = Context::create(&mut target);
+            context.set_source_rgba(0., 0., 0., 1.);
+            context.select_font_face("serif", FontSlant::Normal, FontWeight::Bold);
+            context.set_font_size(16.);
+            assert_eq!(context.get_font_face().get_family(), "serif");
+            assert_eq!(context.text_extents("H").x_advance, 10.);
+            context.move_to(4., 20.);
+            context.show_text("H");
+
+            // The current point moves on to where the next character goes
+            assert_eq!(context.get_current_point(), Some(Point::new(14., 20.)));
+        }
+
+        // Test
+        // The H's uprights are centered on x = 5 and 13 and its bar on y = 14, all 2 pixels wide
+        assert_eq!(target.get(5, 16).unwrap().alpha, 1.);
+        assert_eq!(target.get(13, 16).unwrap().alpha, 1.);
+        assert_eq!(target.get(9, 13).unwrap().alpha, 1.);
+        assert_eq!(target.get(9, 16).unwrap().alpha, 0.);
+        assert_eq!(target.get(9, 5).unwrap().alpha, 0.);
+        assert_eq!(target.get(20, 16).unwrap().alpha, 0.);
+    }
+
+    #[test]
+    fn test_show_text_subpixel() {
+        // Draws an H a third of a pixel right of test_show_text's, in black on white
+        let draw = |order: SubpixelOrder| {
+            let mut target = ImageSurface::create_for_format(Format::ARGB32, 40, 30);
+            {
+                let mut context = Context::create(&mut target);
+                context.set_source_rgba(1., 1., 1., 1.);
+                context.paint();
+                context.set_source_rgba(0., 0., 0., 1.);
+                context.set_antialias(Antialias::Subpixel);
+                context.set_subpixel_order(order);
+                assert_eq!(context.get_subpixel_order(), order);
+                context.select_font_face("serif", FontSlant::Normal, FontWeight::Bold);
+                context.set_font_size(16.);
+                context.move_to(4. + 1. / 3., 20.);
+                context.show_text("H");
+            }
+            target
+        };
+
+        // Call
+        let rgb = draw(SubpixelOrder::Rgb);
+        let bgr = draw(SubpixelOrder::Bgr);
+
+        // Test
+        // The left upright runs from a third into pixel 4 to a third into pixel 6, so the left
+        // edge misses pixel 4's red stripe and the right edge covers only pixel 6's.  Pixel 5
+        // is covered, though the filter spreads a little of its blue stripe past the edge.
+        let middle = rgb.get(5, 16).unwrap();
+        assert!(middle.red == 0. && middle.green == 0. && middle.blue < 0.05);
+        let (left, right) = (rgb.get(4, 16).unwrap(), rgb.get(6, 16).unwrap());
+        assert!(left.red > left.green && left.green > left.blue);
+        assert!(right.red < right.green && right.green < right.blue);
+        assert_eq!(left.alpha, 1.);
+        let left = bgr.get(4, 16).unwrap();
+        assert!(left.red < left.green && left.green < left.blue);
+        assert_eq!(*rgb.get(9, 5).unwrap(), Rgba::new(1., 1., 1., 1.));
+    }
+
+    #[test]
+    fn test_text_path() {
+        // Setup
+        let mut shown = ImageSurface::create_for_format(Format::ARGB32, 40, 30);
+        let mut filled = ImageSurface::create_for_format(Format::ARGB32, 40, 30);
+        {
+            let mut context = Context::create(&mut shown);
+            context.set_font_size(16.);
+            context.set_source_rgba(0., 0., 0., 1.);
+            context.move_to(4., 20.);
+            context.show_text("Ok");
+        }
+
+        // Call
+        {
+            let mut context = Context::create(&mut filled);
+            context.set_font_size(16.);
+            context.set_source_rgba(0., 0., 0., 1.);
+            context.move_to(0., 0.);
+            context.line_to(1., 0.);
+            context.line_to(1., 1.);
+            context.line_to(0., 1.);
+            context.close_path();
+            context.move_to(4., 20.);
+            context.text_path("Ok");
+
+            // The outline joins the path, which carries on past the text
+            assert_eq!(context.get_current_point(), Some(Point::new(24., 20.)));
+            let elements = context.get_path().elements();
+            assert_eq!(elements[0], PathElement::MoveTo(Point::new(0., 0.)));
+            assert!(elements.len() > 20);
+            context.fill();
+        }
+
+        // Test
+        // Filling the outline draws the text as show_text() does
+        for (shown, filled) in shown.iter().zip(filled.iter()).skip(1) {
+            assert!((shown.alpha - filled.alpha).abs() < 0.05);
+        }
+        assert_eq!(filled.get(0, 0).unwrap().alpha, 1.);
+        assert!(filled.iter().filter(|pixel| pixel.alpha > 0.5).count() > 30);
+    }
+
+    #[test]
+    fn test_font_matrix() {
+        // Setup
+        let mut target = ImageSurface::create_for_format(Format::ARGB32, 30, 30);
+        let mut context = Context::create(&mut target);
+        assert_eq!(context.get_font_matrix(), Matrix::scaling(10., 10.));
+
+        // Call
+        context.set_font_size(16.);
+        let sized = context.get_font_matrix();
+        context.set_font_matrix(Matrix::new(12., 0., 0., 24., 0., 0.));
+
+        // Test
+        assert_eq!(sized, Matrix::scaling(16., 16.));
+        assert_eq!(context.get_font_size(), 24.);
+        let extents = context.text_extents("H");
+        assert_eq!(extents.x_advance, 7.5);
+        assert!((extents.height - 24. * (0.75 + 0.6 / 8.)).abs() < 1e-5);
+        assert!((extents.width - 12. * (0.5 + 0.6 / 8.)).abs() < 1e-5);
+
+        // Turned a quarter turn, the text runs down the page with its top to the right
+        context.set_font_matrix(Matrix::rotation(FRAC_PI_2).multiply(&Matrix::scaling(16., 16.)));
+        assert!((context.get_font_size() - 16.).abs() < 1e-5);
+        let extents = context.text_extents("H");
+        assert!(extents.x_advance.abs() < 1e-5 && (extents.y_advance - 10.).abs() < 1e-5);
+        assert!(extents.y_bearing > 0. && extents.x_bearing + extents.width > 12.);
+        context.set_source_rgba(0., 0., 0., 1.);
+        context.move_to(8., 5.);
+        context.show_text("H");
+        let point = context.get_current_point().unwrap();
+        assert!((point.x - 8.).abs() < 1e-4 && (point.y - 15.).abs() < 1e-4);
+        drop(context);
+        let covered = |x, y| target.get(x, y).unwrap().alpha > 0.;
+        // The uprights run across on y = 6 and 14 from x = 8 to 20, and the bar down x = 14
+        assert!(covered(11, 6) && covered(11, 13) && covered(14, 10));
+        assert!(!covered(11, 10) && !covered(6, 6) && !covered(22, 6));
     }
 }
@@ -0,0 +1,503 @@
+/*
+ * Cairus - a reimplementation of the cairo graphics library in Rust
+ *
+ * Copyright © 2017 CairusOrg
+ *
+ * This library is free software; you can redistribute it and/or
+ * modify it either under the terms of the GNU Lesser General Public
+ * License version 2.1 as published by the Free Software Foundation
+ * (the "LGPL") or, at your option, under the terms of the Mozilla
+ * Public License Version 2.0 (the "MPL"). If you do not alter this
+ * notice, a recipient may use your version of this file under either
+ * the MPL or the LGPL.
+ *
+ * You should have received a copy of the LGPL along with this library
+ * in the file LICENSE-LGPL-2_1; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Suite 500, Boston, MA 02110-1335, USA
+ * You should have received a copy of the MPL along with this library
+ * in the file LICENSE-MPL-2_0
+ *
+ * The contents of this file are subject to the Mozilla Public License
+ * Version 2.0 (the "License"); you may not use this file except in
+ * compliance with the License. You may obtain a copy of the License at
+ * http://www.mozilla.org/MPL/
+ *
+ * This software is distributed on an "AS IS" basis, WITHOUT WARRANTY
+ * OF ANY KIND, either express or implied. See the LGPL or the MPL for
+ * the specific language governing rights and limitations.
+ *
+ * The Original Code is the cairus graphics library.
+ *
+ * Contributor(s):
+ *	CairusOrg
+ *
+ */
+
+//! Row-by-row coverage for simple shapes.
+//!
+//! Shapes that know their own exact (or box-filtered) pixel coverage can skip path construction
+//! and tessellation entirely.  `ImageSurface` sweeps a batch of `RowCoverage` shapes down the
+//! surface one row at a time, so each covered pixel is composited once per batch.
+
+use std::mem;
+use common_geometry::{Ellipse, LineSegment, Point, Rectangle};
+
+/// A shape that can report how much of each pixel in a row it covers.
+pub trait RowCoverage {
+    /// The topmost y coordinate touched by the shape.
+    fn top(&self) -> f32;
+
+    /// The bottommost y coordinate touched by the shape.
+    fn bottom(&self) -> f32;
+
+    /// Adds this shape's coverage of `row` into `coverage`, one entry per column.
+    ///
+    /// Overlapping shapes are combined by taking the maximum, so a batch fills its union.  Returns
+    /// the `(start, end)` column span that was touched, with `start >= end` meaning nothing was.
+    fn accumulate(&self, row: usize, coverage: &mut [f32]) -> (usize, usize);
+}
+
+impl RowCoverage for Rectangle {
+    fn top(&self) -> f32 {
+        self.y
+    }
+
+    fn bottom(&self) -> f32 {
+        self.y + self.height
+    }
+
+    // Exact area coverage, assuming the rectangle has been normalized.
+    fn accumulate(&self, row: usize, coverage: &mut [f32]) -> (usize, usize) {
+        let (row_top, row_bottom) = (row as f32, row as f32 + 1.);
+        let vertical = self.bottom().min(row_bottom) - self.y.max(row_top);
+        let (left, right) = (self.x.max(0.), (self.x + self.width).min(coverage.len() as f32));
+        if vertical <= 0. || left >= right {
+            return (0, 0);
+        }
+
+        let first_column = left.floor() as usize;
+        let last_column = (right.ceil() as usize).min(coverage.len());
+        for (column, pixel_coverage) in coverage.iter_mut().enumerate()
+                                                .take(last_column).skip(first_column) {
+            let horizontal = right.min(column as f32 + 1.) - left.max(column as f32);
+            *pixel_coverage = pixel_coverage.max(vertical * horizontal);
+        }
+        (first_column, last_column)
+    }
+}
+
+// Sub-scanlines per row sampled by the ellipse filler
+const ELLIPSE_SUBROWS: usize = 16;
+
+impl RowCoverage for Ellipse {
+    fn top(&self) -> f32 {
+        self.center.y - self.radius_y
+    }
+
+    fn bottom(&self) -> f32 {
+        self.center.y + self.radius_y
+    }
+
+    // Coverage is exact horizontally: the row is split into sub-scanlines, each sub-scanline's
+    // chord is intersected with every pixel it touches, and the results are averaged.  Pixels
+    // inside every chord are filled outright, so only the two edges of a row cost anything.
+    fn accumulate(&self, row: usize, coverage: &mut [f32]) -> (usize, usize) {
+        let width = coverage.len() as f32;
+        let mut chords = [(0f32, 0f32); ELLIPSE_SUBROWS];
+        let (mut outer_left, mut outer_right) = (f32::INFINITY, f32::NEG_INFINITY);
+        let (mut inner_left, mut inner_right) = (f32::NEG_INFINITY, f32::INFINITY);
+        for (subrow, chord) in chords.iter_mut().enumerate() {
+            let y = row as f32 + (subrow as f32 + 0.5) / ELLIPSE_SUBROWS as f32;
+            *chord = match self.chord_at(y) {
+                Some((left, right)) => (left.max(0.), right.min(width)),
+                None => (0., 0.),
+            };
+            outer_left = outer_left.min(chord.0);
+            outer_right = outer_right.max(chord.1);
+            inner_left = inner_left.max(chord.0);
+            inner_right = inner_right.min(chord.1);
+        }
+        if outer_left >= outer_right {
+            return (0, 0);
+        }
+
+        let first_column = outer_left.floor() as usize;
+        let last_column = (outer_right.ceil() as usize).min(coverage.len());
+        for (column, pixel_coverage) in coverage.iter_mut().enumerate()
+                                                .take(last_column).skip(first_column) {
+            let (left, right) = (column as f32, column as f32 + 1.);
+            let value = if left >= inner_left && right <= inner_right {
+                1.
+            } else {
+                chords.iter().fold(0., |total, &(start, end)| {
+                    total + (end.min(right) - start.max(left)).max(0.)
+                }) / ELLIPSE_SUBROWS as f32
+            };
+            *pixel_coverage = pixel_coverage.max(value);
+        }
+        (first_column, last_column)
+    }
+}
+
+/// ## ThickSegment
+///
+/// A line segment stroked to `width` with butt caps.
+///
+/// Coverage is box filtered: each pixel is treated as a unit square centered on its center, and
+/// the overlap is taken separately across and along the segment.  This is exact for axis-aligned
+/// segments and a close approximation otherwise.
+#[derive(Debug, Copy, Clone)]
+pub struct ThickSegment {
+    start: Point,
+    // Unit vector from start to end
+    direction: (f32, f32),
+    length: f32,
+    half_width: f32,
+    // Distance along the stroke at which this segment starts
+    offset: f32,
+    // Corners of the stroked quad grown by half a pixel, i.e. everything the filter can touch
+    corners: [Point; 4],
+}
+
+impl ThickSegment {
+    /// Returns the stroke of `segment` at `width`, or None if it would cover nothing.
+    pub fn new(segment: &LineSegment, width: f32) -> Option<ThickSegment> {
+        let (dx, dy) = (segment.point2.x - segment.point1.x, segment.point2.y - segment.point1.y);
+        let length = (dx * dx + dy * dy).sqrt();
+        if length == 0. || width <= 0. || !length.is_finite() || !width.is_finite() {
+            return None;
+        }
+
+        let direction = (dx / length, dy / length);
+        let half_width = width / 2.;
+        let (along, across) = (0.5, half_width + 0.5);
+        let corner = |s: f32, d: f32| {
+            Point::new(segment.point1.x + direction.0 * s - direction.1 * d,
+                       segment.point1.y + direction.1 * s + direction.0 * d)
+        };
+        Some(ThickSegment {
+            start: segment.point1,
+            direction,
+            length,
+            half_width,
+            offset: 0.,
+            corners: [corner(-along, -across),
+                      corner(length + along, -across),
+                      corner(length + along, across),
+                      corner(-along, across)],
+        })
+    }
+
+    /// Returns the length of the segment.
+    pub fn length(&self) -> f32 {
+        self.length
+    }
+
+    /// Returns this segment starting `offset` units along a longer stroke.
+    pub fn with_offset(self, offset: f32) -> ThickSegment {
+        ThickSegment { offset, ..self }
+    }
+
+    /// Like `RowCoverage::accumulate`, but also records how far along the stroke each pixel is.
+    ///
+    /// Wherever this segment covers a pixel more than `coverage` already does, `distance` is set
+    /// to the distance along the stroke of the nearest point on the segment, counting from this
+    /// segment's offset.  Pixels are attributed to whichever segment covers them most.
+    pub fn accumulate_distance(&self, row: usize, coverage: &mut [f32], distance: &mut [f32])
+                               -> (usize, usize) {
+        let columns = coverage.len().min(distance.len());
+        let (offset, length) = (self.offset, self.length);
+        self.walk_row(row, columns, |column, value, along| {
+            if value > coverage[column] {
+                coverage[column] = value;
+                distance[column] = offset + along.clamp(0., length);
+            }
+        })
+    }
+
+    // Calls `visit` with the column, coverage and position along the segment of every pixel in
+    // `row` that the segment may touch, and returns the span of those columns.
+    fn walk_row<F: FnMut(usize, f32, f32)>(&self, row: usize, columns: usize, mut visit: F)
+                                            -> (usize, usize) {
+        // Pixel centers in this row only sample the quad between row + 0.5 +/- 0.5
+        let (left, right) = match self.x_range(row as f32, row as f32 + 1.) {
+            Some(range) => range,
+            None => return (0, 0),
+        };
+        let first_column = left.floor().max(0.) as usize;
+        let last_column = (right.ceil().max(0.) as usize).min(columns);
+
+        let center_y = row as f32 + 0.5 - self.start.y;
+        for column in first_column..last_column {
+            let center_x = column as f32 + 0.5 - self.start.x;
+            let along = center_x * self.direction.0 + center_y * self.direction.1;
+            let across = center_y * self.direction.0 - center_x * self.direction.1;
+            let value = overlap(across - 0.5, across + 0.5, -self.half_width, self.half_width) *
+                        overlap(along - 0.5, along + 0.5, 0., self.length);
+            visit(column, value, along);
+        }
+        (first_column, last_column)
+    }
+
+    // Returns the horizontal extent of the grown quad between two y coordinates.
+    fn x_range(&self, y0: f32, y1: f32) -> Option<(f32, f32)> {
+        let (mut min_x, mut max_x) = (f32::INFINITY, f32::NEG_INFINITY);
+        for i in 0..4 {
+            let (a, b) = (self.corners[i], self.corners[(i + 1) % 4]);
+            if a.y >= y0 && a.y <= y1 {
+                min_x = min_x.min(a.x);
+                max_x = max_x.max(a.x);
+            }
+            for &y in &[y0, y1] {
+                if (a.y - y) * (b.y - y) < 0. {
+                    let x = a.x + (b.x - a.x) * (y - a.y) / (b.y - a.y);
+                    min_x = min_x.min(x);
+                    max_x = max_x.max(x);
+                }
+            }
+        }
+        if min_x <= max_x {
+            Some((min_x, max_x))
+        } else {
+            None
+        }
+    }
+}
+
+// Length of the overlap between [a0, a1] and [b0, b1].
+fn overlap(a0: f32, a1: f32, b0: f32, b1: f32) -> f32 {
+    (a1.min(b1) - a0.max(b0)).max(0.)
+}
+
+impl RowCoverage for ThickSegment {
+    fn top(&self) -> f32 {
+        self.corners.iter().fold(f32::INFINITY, |top, corner| top.min(corner.y))
+    }
+
+    fn bottom(&self) -> f32 {
+        self.corners.iter().fold(f32::NEG_INFINITY, |bottom, corner| bottom.max(corner.y))
+    }
+
+    fn accumulate(&self, row: usize, coverage: &mut [f32]) -> (usize, usize) {
+        let columns = coverage.len();
+        self.walk_row(row, columns, |column, value, _| {
+            coverage[column] = coverage[column].max(value);
+        })
+    }
+}
+
+/// Walks an antialiased one pixel wide line along `segment` with Xiaolin Wu's algorithm.
+///
+/// `plot` is called with the column, row and coverage of every pixel the line touches, at most
+/// twice per step along the major axis.  This is much cheaper than stroking the segment as a
+/// quad, at the cost of slightly thinner diagonals, so it is only used for hairlines.  Pixels
+/// may fall outside the surface; `plot` is responsible for clipping.
+///
+/// # Arguments
+/// * `segment` - The line to draw, in surface coordinates.
+/// * `plot` - Called with `(column, row, coverage)` for each touched pixel.
+///
+/// # Usage
+/// wu_line(&segment, |x, y, coverage| { ... });
+pub fn wu_line<F: FnMut(i64, i64, f32)>(segment: &LineSegment, mut plot: F) {
+    // Wu's algorithm puts pixel centers on integer coordinates, cairus puts them on halves
+    let (mut x0, mut y0) = (segment.point1.x - 0.5, segment.point1.y - 0.5);
+    let (mut x1, mut y1) = (segment.point2.x - 0.5, segment.point2.y - 0.5);
+    if !(x0.is_finite() && y0.is_finite() && x1.is_finite() && y1.is_finite()) ||
+       (x0 == x1 && y0 == y1) {
+        return;
+    }
+
+    let steep = (y1 - y0).abs() > (x1 - x0).abs();
+    if steep {
+        mem::swap(&mut x0, &mut y0);
+        mem::swap(&mut x1, &mut y1);
+    }
+    if x0 > x1 {
+        mem::swap(&mut x0, &mut x1);
+        mem::swap(&mut y0, &mut y1);
+    }
+    let mut plot = |major: i64, minor: i64, coverage: f32| {
+        if coverage > 0. {
+            if steep {
+                plot(minor, major, coverage);
+            } else {
+                plot(major, minor, coverage);
+            }
+        }
+    };
+    let gradient = (y1 - y0) / (x1 - x0);
+
+    // Plots the two pixels straddling `y` in column `x`, weighted by `gap`
+    let mut plot_pair = |x: i64, y: f32, gap: f32| {
+        let fraction = y - y.floor();
+        plot(x, y.floor() as i64, (1. - fraction) * gap);
+        plot(x, y.floor() as i64 + 1, fraction * gap);
+    };
+
+    let first = x0.round();
+    let last = x1.round();
+    if first == last {
+        // The whole line falls within one column
+        plot_pair(first as i64, (y0 + y1) / 2., x1 - x0);
+        return;
+    }
+
+    // Endpoints are weighted by how much of their column the line actually spans
+    plot_pair(first as i64, y0 + gradient * (first - x0), first + 0.5 - x0);
+    plot_pair(last as i64, y0 + gradient * (last - x0), x1 - (last - 0.5));
+
+    let mut y = y0 + gradient * (first + 1. - x0);
+    for x in (first as i64 + 1)..(last as i64) {
+        plot_pair(x, y, 1.);
+        y += gradient;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use common_geometry::{Ellipse, LineSegment, Rectangle};
+    use super::{RowCoverage, ThickSegment, wu_line};
+
+    #[test]
+    fn rectangle_accumulate_partial() {
+        // Setup
+        let rectangle = Rectangle::new(1.5, 0.25, 2., 1.);
+        let mut coverage = vec![0.; 5];
+
+        // Call
+        let span = rectangle.accumulate(0, &mut coverage);
+
+        // Test
+        assert_eq!(span, (1, 4));
+        assert_eq!(coverage, vec![0., 0.375, 0.75, 0.375, 0.]);
+    }
+
+    #[test]
+    fn ellipse_accumulate_circle_area() {
+        // The coverage of a circle adds up to its area, and its center is solid
+        let circle = Ellipse::circle(8., 8., 5.);
+        let mut total = 0.;
+        for row in 0..16 {
+            let mut coverage = vec![0.; 16];
+            circle.accumulate(row, &mut coverage);
+            total += coverage.iter().fold(0., |total, value| total + value);
+            if row == 8 {
+                assert_eq!(coverage[8], 1.);
+                assert_eq!(coverage[2], 0.);
+            }
+        }
+        assert!((total - ::std::f32::consts::PI * 25.).abs() < 0.1, "{}", total);
+    }
+
+    #[test]
+    fn ellipse_accumulate_outside_row() {
+        let mut coverage = vec![0.; 8];
+        assert_eq!(Ellipse::new(4., 4., 3., 1.).accumulate(6, &mut coverage), (0, 0));
+        assert_eq!(coverage, vec![0.; 8]);
+    }
+
+    #[test]
+    fn thick_segment_degenerate() {
+        assert!(ThickSegment::new(&LineSegment::new(3., 3., 3., 3.), 2.).is_none());
+        assert!(ThickSegment::new(&LineSegment::new(0., 0., 3., 3.), 0.).is_none());
+    }
+
+    #[test]
+    fn thick_segment_horizontal() {
+        // A horizontal segment of width 2 on y = 5 covers rows 4 and 5 exactly
+        let segment = ThickSegment::new(&LineSegment::new(2., 5., 6., 5.), 2.).unwrap();
+        assert!(segment.top() <= 4. && segment.bottom() >= 6.);
+
+        for row in 3..7 {
+            let mut coverage = vec![0.; 10];
+            segment.accumulate(row, &mut coverage);
+            for (column, value) in coverage.iter().enumerate() {
+                let inside = (row == 4 || row == 5) && (2..6).contains(&column);
+                assert_eq!(*value, if inside { 1. } else { 0. }, "({}, {})", column, row);
+            }
+        }
+    }
+
+    #[test]
+    fn thick_segment_clips_to_row() {
+        // Columns left of the surface are dropped rather than wrapping around
+        let segment = ThickSegment::new(&LineSegment::new(-5., 1., 2., 1.), 2.).unwrap();
+        let mut coverage = vec![0.; 4];
+
+        let (start, end) = segment.accumulate(0, &mut coverage);
+
+        assert_eq!((start, end), (0, 3));
+        assert_eq!(coverage, vec![1., 1., 0., 0.]);
+    }
+
+    #[test]
+    fn thick_segment_accumulate_distance() {
+        // Distance runs along the segment from its offset, clamped at the ends
+        let segment = ThickSegment::new(&LineSegment::new(2., 1., 6., 1.), 2.).unwrap()
+            .with_offset(10.);
+        let mut coverage = vec![0.; 8];
+        let mut distance = vec![0.; 8];
+
+        segment.accumulate_distance(0, &mut coverage, &mut distance);
+
+        assert_eq!(segment.length(), 4.);
+        assert_eq!(&distance[2..6], &[10.5, 11.5, 12.5, 13.5]);
+        assert_eq!((distance[1], distance[6]), (0., 0.));
+
+        // A segment covering less than what is already there doesn't take the pixel over
+        coverage[3] = 1.;
+        distance[3] = -1.;
+        segment.accumulate_distance(0, &mut coverage, &mut distance);
+        assert_eq!(distance[3], -1.);
+    }
+
+    // Collects everything wu_line plots into a (column, row) -> coverage map
+    fn plot_wu(segment: &LineSegment) -> Vec<((i64, i64), f32)> {
+        let mut plotted = Vec::new();
+        wu_line(segment, |x, y, coverage| plotted.push(((x, y), coverage)));
+        plotted
+    }
+
+    #[test]
+    fn wu_line_horizontal_on_pixel_centers() {
+        // Setup
+        let segment = LineSegment::new(1., 2.5, 5., 2.5);
+
+        // Call
+        let plotted = plot_wu(&segment);
+
+        // Test
+        assert_eq!(plotted, vec![((1, 2), 1.), ((2, 2), 1.), ((3, 2), 1.), ((4, 2), 1.)]);
+    }
+
+    #[test]
+    fn wu_line_between_rows_is_split() {
+        let plotted = plot_wu(&LineSegment::new(0., 3., 3., 3.));
+
+        assert_eq!(plotted.len(), 6);
+        for &((_, y), coverage) in plotted.iter() {
+            assert!(y == 2 || y == 3);
+            assert_eq!(coverage, 0.5);
+        }
+    }
+
+    #[test]
+    fn wu_line_steep_has_one_unit_per_row() {
+        // Every row crossed in the middle of a steep line gets a total coverage of one
+        let plotted = plot_wu(&LineSegment::new(2., 0., 4.5, 10.));
+
+        for row in 1..9 {
+            let total = plotted.iter()
+                .filter(|&&((_, y), _)| y == row)
+                .fold(0., |total, &(_, coverage)| total + coverage);
+            assert!((total - 1.).abs() < 1e-5, "row {}: {}", row, total);
+        }
+    }
+
+    #[test]
+    fn wu_line_degenerate() {
+        assert!(plot_wu(&LineSegment::new(1., 1., 1., 1.)).is_empty());
+        assert!(plot_wu(&LineSegment::new(1., 1., f32::NAN, 1.)).is_empty());
+    }
+}
@@ -0,0 +1,344 @@
+/*
+ * Cairus - a reimplementation of the cairo graphics library in Rust
+ *
+ * Copyright © 2017 CairusOrg
+ *
+ * This library is free software; you can redistribute it and/or
+ * modify it either under the terms of the GNU Lesser General Public
+ * License version 2.1 as published by the Free Software Foundation
+ * (the "LGPL") or, at your option, under the terms of the Mozilla
+ * Public License Version 2.0 (the "MPL"). If you do not alter this
+ * notice, a recipient may use your version of this file under either
+ * the MPL or the LGPL.
+ *
+ * You should have received a copy of the LGPL along with this library
+ * in the file LICENSE-LGPL-2_1; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Suite 500, Boston, MA 02110-1335, USA
+ * You should have received a copy of the MPL along with this library
+ * in the file LICENSE-MPL-2_0
+ *
+ * The contents of this file are subject to the Mozilla Public License
+ * Version 2.0 (the "License"); you may not use this file except in
+ * compliance with the License. You may obtain a copy of the License at
+ * http://www.mozilla.org/MPL/
+ *
+ * This software is distributed on an "AS IS" basis, WITHOUT WARRANTY
+ * OF ANY KIND, either express or implied. See the LGPL or the MPL for
+ * the specific language governing rights and limitations.
+ *
+ * The Original Code is the cairus graphics library.
+ *
+ * Contributor(s):
+ *	CairusOrg
+ *
+ */
+
+//! Dash patterns for stroking.
+//!
+//! A `DashPattern` is built once from the dash lengths and offset given to set_dash(), and then
+//! reused by every stroke.  The cumulative dash lengths and the state at the offset are computed
+//! up front, so starting a subpath is a lookup rather than a walk through the pattern.
+
+use std::error::Error;
+use std::fmt;
+use common_geometry::{LineSegment, Point};
+
+/// The error returned by `Context::set_dash` for lengths that don't describe a usable pattern.
+///
+/// Analogous to CAIRO_STATUS_INVALID_DASH.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InvalidDash {
+    /// The dash lengths that were rejected.
+    pub dashes: Vec<f32>,
+    /// The offset that was rejected with them.
+    pub offset: f32,
+}
+
+impl fmt::Display for InvalidDash {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid dash pattern {:?} with offset {}", self.dashes, self.offset)
+    }
+}
+
+impl Error for InvalidDash {}
+
+/// Where a walk along a dash pattern currently is.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DashState {
+    /// Index of the current dash.
+    pub index: usize,
+    /// Length left in the current dash.
+    pub remaining: f32,
+    /// True while the current dash is drawn, false while it is a gap.
+    pub on: bool,
+}
+
+/// ## DashPattern
+///
+/// An alternating sequence of "on" and "off" lengths, starting with "on", and an offset into it.
+/// Analogous to the arguments of cairo_set_dash(), with the same rules: an odd number of dashes
+/// is repeated to make an even one, and the offset wraps around the pattern's period.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DashPattern {
+    dashes: Vec<f32>,
+    // The distance at which each dash ends, measured from the start of the pattern
+    cumulative: Vec<f32>,
+    period: f32,
+    offset: f32,
+    // The state at the start of every subpath, i.e. at `offset`
+    start: DashState,
+}
+
+impl DashPattern {
+    /// Returns a new DashPattern, or None if `dashes` doesn't describe a usable pattern.
+    ///
+    /// As in cairo, a pattern is invalid if any length is negative or not finite, or if every
+    /// length is zero.  An empty `dashes` means no dashing, so it returns None too.
+    ///
+    /// # Arguments
+    /// * `dashes` - Alternating on and off lengths, starting with on.
+    /// * `offset` - How far into the pattern each subpath starts.
+    ///
+    /// # Usage
+    /// let pattern = DashPattern::new(&[4., 2.], 1.).unwrap();
+    pub fn new(dashes: &[f32], offset: f32) -> Option<DashPattern> {
+        if dashes.is_empty() || !offset.is_finite() ||
+           dashes.iter().any(|dash| *dash < 0. || !dash.is_finite()) {
+            return None;
+        }
+
+        let mut dashes = dashes.to_vec();
+        if dashes.len() % 2 == 1 {
+            dashes.extend_from_slice(&dashes.clone());
+        }
+        let mut cumulative = Vec::with_capacity(dashes.len());
+        let mut period = 0.;
+        for dash in dashes.iter() {
+            period += *dash;
+            cumulative.push(period);
+        }
+        if period <= 0. {
+            return None;
+        }
+
+        let mut pattern = DashPattern {
+            dashes,
+            cumulative,
+            period,
+            offset,
+            start: DashState { index: 0, remaining: 0., on: true },
+        };
+        pattern.start = pattern.state_at(0.);
+        Some(pattern)
+    }
+
+    /// Returns the dash lengths, with an odd-length pattern already doubled.
+    pub fn dashes(&self) -> &[f32] {
+        &self.dashes
+    }
+
+    /// Returns the offset the pattern was created with.
+    pub fn offset(&self) -> f32 {
+        self.offset
+    }
+
+    /// Returns the total length of one repetition of the pattern.
+    pub fn period(&self) -> f32 {
+        self.period
+    }
+
+    /// Returns the state at the start of a subpath.
+    pub fn start(&self) -> DashState {
+        self.start
+    }
+
+    /// Returns the state `distance` units along a subpath.
+    pub fn state_at(&self, distance: f32) -> DashState {
+        let phase = (self.offset + distance).rem_euclid(self.period);
+        // The first dash that ends after `phase`.  Like cairo, a walk starting exactly at the
+        // beginning of the pattern stays in the first dash even if it is zero-length, and
+        // rem_euclid can round up to the period itself.
+        let index = if phase > 0. {
+            self.cumulative.iter().position(|end| *end > phase).unwrap_or(0)
+        } else {
+            0
+        };
+        let remaining = if index > 0 || phase < self.cumulative[0] {
+            self.cumulative[index] - phase
+        } else {
+            self.dashes[0]
+        };
+        DashState {
+            index,
+            remaining,
+            on: index.is_multiple_of(2),
+        }
+    }
+
+    /// Returns an iterator over the "on" intervals of a subpath `length` units long.
+    pub fn intervals(&self, length: f32) -> DashIntervals<'_> {
+        self.intervals_from(self.start, length)
+    }
+
+    /// Returns an iterator over the "on" intervals of `length` units of path, starting from
+    /// `state`.  Once it is exhausted, `DashIntervals::state` is where the next piece of path
+    /// should continue from.
+    pub fn intervals_from(&self, state: DashState, length: f32) -> DashIntervals<'_> {
+        DashIntervals {
+            pattern: self,
+            state,
+            position: 0.,
+            length,
+        }
+    }
+
+    /// Splits the open polyline through `points` into the line segments that are dashed "on".
+    ///
+    /// The pattern carries on from one segment to the next, so corners don't restart it.  The
+    /// result can be passed straight to `stroke_segments`.
+    pub fn dash_polyline(&self, points: &[Point]) -> Vec<LineSegment> {
+        let mut dashed = Vec::new();
+        let mut state = self.start;
+        for pair in points.windows(2) {
+            let (start, end) = (pair[0], pair[1]);
+            let (dx, dy) = (end.x - start.x, end.y - start.y);
+            let length = (dx * dx + dy * dy).sqrt();
+            if length == 0. {
+                continue;
+            }
+
+            let point_at = |distance: f32| {
+                Point::new(start.x + dx * distance / length, start.y + dy * distance / length)
+            };
+            let mut intervals = self.intervals_from(state, length);
+            for (from, to) in intervals.by_ref() {
+                dashed.push(LineSegment::from_points(point_at(from), point_at(to)));
+            }
+            state = intervals.state();
+        }
+        dashed
+    }
+}
+
+/// Iterator over the `(start, end)` distances of the "on" parts of a piece of path.
+///
+/// Zero-length "on" dashes are reported as empty intervals, since they still get caps.
+pub struct DashIntervals<'a> {
+    pattern: &'a DashPattern,
+    state: DashState,
+    position: f32,
+    length: f32,
+}
+
+impl<'a> DashIntervals<'a> {
+    /// Returns the dash state at the current position.
+    pub fn state(&self) -> DashState {
+        self.state
+    }
+}
+
+impl<'a> Iterator for DashIntervals<'a> {
+    type Item = (f32, f32);
+
+    fn next(&mut self) -> Option<(f32, f32)> {
+        loop {
+            if self.position >= self.length && self.state.remaining > 0. {
+                return None;
+            }
+
+            let on = self.state.on;
+            let start = self.position;
+            let dash_end = self.position + self.state.remaining;
+            if dash_end <= self.length {
+                // This dash finishes within the path; move on to the next one
+                let index = (self.state.index + 1) % self.pattern.dashes.len();
+                self.state = DashState {
+                    index,
+                    remaining: self.pattern.dashes[index],
+                    on: index.is_multiple_of(2),
+                };
+                self.position = dash_end;
+            } else {
+                self.state.remaining = dash_end - self.length;
+                self.position = self.length;
+            }
+
+            if on {
+                return Some((start, self.position));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use common_geometry::{LineSegment, Point};
+    use super::{DashPattern, DashState};
+
+    #[test]
+    fn dash_pattern_invalid() {
+        assert!(DashPattern::new(&[], 0.).is_none());
+        assert!(DashPattern::new(&[0., 0.], 0.).is_none());
+        assert!(DashPattern::new(&[1., -1.], 0.).is_none());
+        assert!(DashPattern::new(&[1., 2.], f32::NAN).is_none());
+    }
+
+    #[test]
+    fn dash_pattern_odd_is_doubled() {
+        let pattern = DashPattern::new(&[1., 2., 3.], 0.).unwrap();
+        assert_eq!(pattern.dashes(), &[1., 2., 3., 1., 2., 3.]);
+        assert_eq!(pattern.period(), 12.);
+    }
+
+    #[test]
+    fn dash_pattern_state_at() {
+        // Setup
+        let pattern = DashPattern::new(&[4., 2.], 1.).unwrap();
+
+        // Test
+        assert_eq!(pattern.start(), DashState { index: 0, remaining: 3., on: true });
+        assert_eq!(pattern.state_at(4.), DashState { index: 1, remaining: 1., on: false });
+        // Offsets wrap in both directions
+        assert_eq!(pattern.state_at(6.), pattern.start());
+        assert_eq!(DashPattern::new(&[4., 2.], -5.).unwrap().start(), pattern.start());
+    }
+
+    #[test]
+    fn dash_intervals() {
+        // Setup
+        let pattern = DashPattern::new(&[4., 2.], 1.).unwrap();
+
+        // Call
+        let intervals = pattern.intervals(14.).collect::<Vec<(f32, f32)>>();
+
+        // Test
+        assert_eq!(intervals, vec![(0., 3.), (5., 9.), (11., 14.)]);
+    }
+
+    #[test]
+    fn dash_intervals_zero_length_dashes() {
+        // Zero-length "on" dashes still show up, as dots for round or square caps
+        let pattern = DashPattern::new(&[0., 2.], 0.).unwrap();
+        let intervals = pattern.intervals(5.).collect::<Vec<(f32, f32)>>();
+        assert_eq!(intervals, vec![(0., 0.), (2., 2.), (4., 4.)]);
+    }
+
+    #[test]
+    fn dash_polyline_continues_around_corners() {
+        // Setup
+        let pattern = DashPattern::new(&[3., 1.], 0.).unwrap();
+        let points = [Point::new(0., 0.), Point::new(5., 0.), Point::new(5., 5.)];
+
+        // Call
+        let segments = pattern.dash_polyline(&points);
+
+        // Test
+        let expected = vec![
+            LineSegment::new(0., 0., 3., 0.),
+            LineSegment::new(4., 0., 5., 0.),
+            LineSegment::new(5., 0., 5., 2.),
+            LineSegment::new(5., 3., 5., 5.),
+        ];
+        assert_eq!(segments, expected);
+    }
+}
@@ -72,7 +72,7 @@ macro_rules! debug_render {
     ($lines:expr, $color:expr) => {
         {
             use $crate::types::Rgba;
-            use surfaces::ImageSurface;
+            use surfaces::{Format, ImageSurface};
             use debug_utils::get_target_dir;
             use types::{Pixel, IntoPixels};
             use std::env;
@@ -105,7 +105,8 @@ macro_rules! debug_render {
             max_x = max_x + 20;
             max_y = max_y + 20;
 
-            let mut surface = ImageSurface::create(max_x as usize, max_y as usize);
+            let mut surface = ImageSurface::create_for_format(Format::ARGB32, max_x as usize,
+                                                              max_y as usize);
 
             // Actually color in the pixels
             for line in $lines.iter() {
@@ -155,7 +156,7 @@ macro_rules! debug_render {
             let filename = filename + &count.to_string();
             let extension = ".png";
             path.push(filename + &extension.to_string());
-            surface.to_file(path.as_path());
+            surface.to_file(path.as_path()).expect("error: debug render can't be written");
             path
         }
     }
@@ -166,7 +167,7 @@ macro_rules! debug_render_traps {
     ($traps:expr, $color:expr) => {
         {
             use $crate::types::Rgba;
-            use surfaces::ImageSurface;
+            use surfaces::{Format, ImageSurface};
             use debug_utils::get_target_dir;
             use types::IntoPixels;
             use trapezoid_rasterizer::mask_from_trapezoids;
@@ -192,9 +193,11 @@ macro_rules! debug_render_traps {
             max_x = max_x + 20;
             max_y = max_y + 20;
 
-            let mut destination = ImageSurface::create(max_x as usize, max_y as usize);
+            let mut destination = ImageSurface::create_for_format(Format::ARGB32, max_x as usize,
+                                                                  max_y as usize);
             let mut mask = mask_from_trapezoids(&$traps, max_x as usize, max_y as usize);
-            let mut source = ImageSurface::create(max_x as usize, max_y as usize);
+            let mut source = ImageSurface::create_for_format(Format::ARGB32, max_x as usize,
+                                                             max_y as usize);
 
 
 
@@ -264,7 +267,8 @@ macro_rules! debug_render_traps {
             let filename = filename + &count.to_string();
             let extension = ".png";
             path.push(filename + &extension.to_string());
-            destination.to_file(path.as_path());
+            destination.to_file(path.as_path())
+                .expect("error: debug render can't be written");
             path
         }
     }
@@ -285,6 +289,7 @@ pub fn get_target_dir() -> PathBuf {
 // This is here so that when the '--feature debug-tesselator' flag is not set
 // the compiler will still compile but this macro won't generate any code.
 #[cfg(not(feature = "debug-tesselator"))]
+#[allow(unused_macros)]
 macro_rules! debug_render {
     ($lines:expr, $color:expr) => {
         {
@@ -385,7 +390,7 @@ mod tests {
         path.push("images");
         path.push("debug_utils_246.png"); // Must be line number of debug_render! call
         // Cleanup
-        assert_eq!(path.exists(), false);
+        assert!(!path.exists());
     }
 
     // Tests that an image is output when the debug-tesselator feature flag is set
@@ -396,7 +401,7 @@ mod tests {
         let base2 = LineSegment::new(100., 500., 300., 500.);
 
         // Setup
-        let trapezoids = vec![Trapezoid::from_bases(base1, base2)];
+        let trapezoids = vec![Trapezoid::from_parallel_bases(base1, base2)];
 
         // Test
         let path = debug_render!(trapezoids, "red");
@@ -451,7 +456,8 @@ mod tests {
         let base4 = LineSegment::new(700., 1000., 800., 1000.);
 
         // Setup
-        let trapezoids = vec![Trapezoid::from_bases(base1, base2), Trapezoid::from_bases(base3, base4)];
+        let trapezoids = vec![Trapezoid::from_parallel_bases(base1, base2),
+                              Trapezoid::from_parallel_bases(base3, base4)];
 
         // Test
         let path = debug_render_traps!(trapezoids, "red");
@@ -34,7 +34,7 @@
  */
 
 //use std::f32;
-use common_geometry::Point;
+use common_geometry::{Edge, LineSegment, Point};
 
 ///SplineKnots for bezier curves
 pub struct SplineKnots{
@@ -47,7 +47,7 @@ pub struct SplineKnots{
 ///Implements SplineKnots methods
 impl SplineKnots{
     ///Creates a new SplineKnots with user defined points
-    fn create(a: &Point, b: &Point, c: &Point, d: &Point)->SplineKnots{
+    pub fn create(a: &Point, b: &Point, c: &Point, d: &Point)->SplineKnots{
         SplineKnots{
             a:Point::new(a.x, a.y),
             b:Point::new(b.x, b.y),
@@ -55,6 +55,32 @@ impl SplineKnots{
             d:Point::new(d.x, d.y),
         }
     }
+
+    ///Flattens the curve into line segments from a to d, none of which strays more than
+    ///`tolerance` from the curve.  See flatten().
+    ///
+    ///# Usage
+    ///let segments = knots.flatten(0.1);
+    pub fn flatten(&self, tolerance: f32)->Vec<LineSegment>{
+        let points = flatten(&self.a, &self.b, &self.c, &self.d, tolerance);
+        let mut start = self.a;
+        points.into_iter().map(|end| {
+            let segment = LineSegment::from_points(start, end);
+            start = end;
+            segment
+        }).collect()
+    }
+
+    ///Flattens the curve into edges drawn from a to d, ready for bo_trap::sweep() alongside the
+    ///rest of a path's edges.
+    ///
+    ///# Usage
+    ///edges.extend(knots.edges(0.1));
+    pub fn edges(&self, tolerance: f32)->Vec<Edge>{
+        self.flatten(tolerance).into_iter()
+            .map(|segment| Edge::from_points(segment.point1, segment.point2))
+            .collect()
+    }
 }
 
 ///This function takes two end points which are interpolated providing the intermediate point
@@ -107,12 +133,76 @@ impl DeCasteljauPoints {
     }
 }
 
+/// The deepest flatten() subdivides, which bounds the output at 2^16 segments per curve even for
+/// a tolerance that can't be met, such as with infinite control points.
+const MAX_DEPTH: u32 = 16;
+
+///Returns how far the control points b and c stray from the chord a-d
+///
+///The distance is to the chord itself rather than the line through it, so that a curve doubling
+///back past one of its ends isn't taken for flat.
+fn flatness(knots: &SplineKnots) -> f32 {
+    let (dx, dy) = (knots.d.x - knots.a.x, knots.d.y - knots.a.y);
+    let length_squared = dx * dx + dy * dy;
+    let distance = |p: &Point| {
+        let (px, py) = (p.x - knots.a.x, p.y - knots.a.y);
+        let t = if length_squared == 0. {
+            0.
+        } else {
+            ((px * dx + py * dy) / length_squared).clamp(0., 1.)
+        };
+        (px - t * dx).hypot(py - t * dy)
+    };
+    distance(&knots.b).max(distance(&knots.c))
+}
+
+///Flattens the bezier curve from a to d, with control points b and c, into a polyline
+///
+///The curve is split in half with de Casteljau's algorithm until every piece's control points
+///are within `tolerance` of its chord.  Returns the points after a, ending with d.
+///
+///Each piece of the curve lies inside the hull of its control points, which all lie within
+///`tolerance` of the piece's chord, so every point of the curve is within `tolerance` of the
+///polyline.  The only exception is a curve that can't be split finely enough, such as one with
+///infinite or NaN control points, or one needing more than 2^16 segments.
+///
+///# Arguments
+///* `a` - The start of the curve.
+///* `b` - The first control point.
+///* `c` - The second control point.
+///* `d` - The end of the curve.
+///* `tolerance` - The largest distance allowed between the curve and the polyline.
+///
+///# Usage
+///let points = flatten(&a, &b, &c, &d, 0.1);
+pub fn flatten(a: &Point, b: &Point, c: &Point, d: &Point, tolerance: f32) -> Vec<Point> {
+    let mut points = Vec::new();
+    let mut casteljau = DeCasteljauPoints::create();
+    // Pieces still to flatten, last one first, with their depth
+    let mut pending = vec![(SplineKnots::create(a, b, c, d), 0)];
+    while let Some((mut first, depth)) = pending.pop() {
+        let flat = flatness(&first);
+        if depth >= MAX_DEPTH || flat.is_nan() || flat <= tolerance {
+            points.push(first.d);
+            continue;
+        }
+        let mut second = SplineKnots::create(&first.d, &first.d, &first.d, &first.d);
+        casteljau.create_spline(&mut first, &mut second);
+        pending.push((second, depth + 1));
+        pending.push((first, depth + 1));
+    }
+    points
+}
+
 #[cfg(test)]
 mod tests{
-    use::common_geometry::Point;
+    use::common_geometry::{LineSegment, Point};
     use::decasteljau::SplineKnots;
     use::decasteljau::DeCasteljauPoints;
     use::decasteljau::lerp_half;
+    use::decasteljau::flatten;
+    use::common_geometry::Edge;
+    use::bo_trap::sweep;
 
 
     #[test]
@@ -557,7 +647,122 @@ mod tests{
         assert_eq!(s1.d.x, d1.fin.x);
         assert_eq!(s1.d.y, d1.fin.y);
     }
-}
 
+    #[test]
+    fn test_flatten_straight(){
+        //A curve whose control points lie on its chord is a single segment
+        let points = flatten(&Point::new(0., 0.), &Point::new(1., 1.), &Point::new(2., 2.),
+                             &Point::new(3., 3.), 0.1);
+        assert_eq!(points, vec![Point::new(3., 3.)]);
+    }
+
+    #[test]
+    fn test_flatten_within_tolerance(){
+        //Setup
+        let (a, b, c, d) = (Point::new(0., 0.), Point::new(0., 10.), Point::new(10., 10.),
+                            Point::new(10., 0.));
+
+        //Call
+        let points = flatten(&a, &b, &c, &d, 0.1);
+
+        //Test
+        assert!(points.len() > 4);
+        assert_eq!(*points.last().unwrap(), d);
+        //The curve's midpoint is (5, 7.5), and must be on the polyline
+        assert!(points.iter().any(|p| (p.x - 5.).abs() < 1e-4 && (p.y - 7.5).abs() < 1e-4));
+        //Every point is on the curve, whose y never passes 7.5
+        assert!(points.iter().all(|p| p.y <= 7.5 + 1e-4));
+    }
+
+    #[test]
+    fn test_flatten_doubling_back(){
+        //Control points on the line through the chord, but past its end, aren't flat
+        let points = flatten(&Point::new(0., 0.), &Point::new(20., 0.), &Point::new(20., 0.),
+                             &Point::new(10., 0.), 0.1);
+        assert!(points.len() > 1);
+        //The curve turns back at x = 15, which the polyline must reach
+        assert!(points.iter().any(|p| p.x > 14.9));
+    }
+
+    #[test]
+    fn test_flatten_error_bound(){
+        //Setup
+        let curves = [
+            [Point::new(0., 0.), Point::new(0., 10.), Point::new(10., 10.), Point::new(10., 0.)],
+            [Point::new(0., 0.), Point::new(30., 5.), Point::new(-20., 5.), Point::new(10., 0.)],
+            [Point::new(0., 0.), Point::new(20., 0.), Point::new(20., 0.), Point::new(10., 0.)],
+            [Point::new(5., 5.), Point::new(-40., 60.), Point::new(90., 60.), Point::new(5., 5.)],
+        ];
+        let distance_to_segment = |p: Point, a: Point, b: Point| {
+            let (dx, dy) = (b.x - a.x, b.y - a.y);
+            let length_squared = dx * dx + dy * dy;
+            let t = if length_squared == 0. {
+                0.
+            } else {
+                (((p.x - a.x) * dx + (p.y - a.y) * dy) / length_squared).clamp(0., 1.)
+            };
+            (p.x - a.x - t * dx).hypot(p.y - a.y - t * dy)
+        };
+
+        for curve in curves.iter() {
+            for &tolerance in [1., 0.1, 0.01].iter() {
+                //Call
+                let mut polyline = vec![curve[0]];
+                polyline.extend(flatten(&curve[0], &curve[1], &curve[2], &curve[3], tolerance));
+
+                //Test
+                //Every point sampled along the curve is within the tolerance of the polyline
+                for step in 0..=1000 {
+                    let t = step as f32 / 1000.;
+                    let u = 1. - t;
+                    let weights = [u * u * u, 3. * u * u * t, 3. * u * t * t, t * t * t];
+                    let point = Point::new(
+                        (0..4).map(|i| weights[i] * curve[i].x).sum(),
+                        (0..4).map(|i| weights[i] * curve[i].y).sum());
+                    let distance = polyline.windows(2)
+                        .map(|pair| distance_to_segment(point, pair[0], pair[1]))
+                        .fold(f32::INFINITY, f32::min);
+                    assert!(distance <= tolerance * 1.001 + 1e-4);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_splineknots_flatten(){
+        //The segments join up from the first knot to the last
+        let knots = SplineKnots::create(&Point::new(0., 0.), &Point::new(0., 10.),
+                                        &Point::new(10., 10.), &Point::new(10., 0.));
+        let segments = knots.flatten(0.1);
+        assert_eq!(segments[0].point1, knots.a);
+        assert_eq!(segments[segments.len() - 1].point2, knots.d);
+        for pair in segments.windows(2) {
+            assert_eq!(pair[0].point2, pair[1].point1);
+        }
+        //A coarser tolerance needs fewer segments
+        assert!(knots.flatten(1.).len() < segments.len());
+    }
+
+    #[test]
+    fn test_splineknots_edges_sweep(){
+        //Setup
+        //A curve bulging below the line from (0, 0) to (10, 0), closed by that line
+        let knots = SplineKnots::create(&Point::new(0., 0.), &Point::new(0., 8.),
+                                        &Point::new(10., 8.), &Point::new(10., 0.));
+        let mut edges = knots.edges(0.05);
+        edges.push(Edge::from_points(knots.d, knots.a));
 
+        //Call
+        let traps = sweep(edges);
 
+        //Test
+        //The exact area between the curve and the line is 48
+        let area: f32 = traps.iter().map(|trap| {
+            let (top, bottom) = (trap.lines[0], trap.lines[2]);
+            let width = |base: LineSegment| (base.point2.x - base.point1.x).abs();
+            (width(top) + width(bottom)) / 2. * (bottom.point1.y - top.point1.y).abs()
+        }).sum();
+        assert!((area - 48.).abs() < 0.5);
+        assert!(traps.iter().any(|trap| trap.contains_point(&Point::new(5., 3.))));
+    }
+}
@@ -0,0 +1,254 @@
+/*
+ * Cairus - a reimplementation of the cairo graphics library in Rust
+ *
+ * Copyright © 2017 CairusOrg
+ *
+ * This library is free software; you can redistribute it and/or
+ * modify it either under the terms of the GNU Lesser General Public
+ * License version 2.1 as published by the Free Software Foundation
+ * (the "LGPL") or, at your option, under the terms of the Mozilla
+ * Public License Version 2.0 (the "MPL"). If you do not alter this
+ * notice, a recipient may use your version of this file under either
+ * the MPL or the LGPL.
+ *
+ * You should have received a copy of the LGPL along with this library
+ * in the file LICENSE-LGPL-2_1; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Suite 500, Boston, MA 02110-1335, USA
+ * You should have received a copy of the MPL along with this library
+ * in the file LICENSE-MPL-2_0
+ *
+ * The contents of this file are subject to the Mozilla Public License
+ * Version 2.0 (the "License"); you may not use this file except in
+ * compliance with the License. You may obtain a copy of the License at
+ * http://www.mozilla.org/MPL/
+ *
+ * This software is distributed on an "AS IS" basis, WITHOUT WARRANTY
+ * OF ANY KIND, either express or implied. See the LGPL or the MPL for
+ * the specific language governing rights and limitations.
+ *
+ * The Original Code is the cairus graphics library.
+ *
+ * Contributor(s):
+ *	CairusOrg
+ *
+ */
+
+//! The crate-wide error type.
+//!
+//! Fallible functions return the error type of their module, such as SurfaceError or
+//! InvalidDash, which says exactly what went wrong.  Each converts into `cairus::Error`, so
+//! code calling into several modules can use `?` with one error type, and match on the variants,
+//! which are named after the cairo_status_t cairo would report for the same failure.
+//!
+//! # Usage
+//! fn thumbnail(path: &Path) -> cairus::error::Result<()> {
+//!     let mut surface = ImageSurface::try_create_for_format(Format::ARGB32, 64, 64)?;
+//!     Context::create(&mut surface).set_dash(&[4., 2.], 0.)?;
+//!     surface.to_file(path)?;
+//!     Ok(())
+//! }
+
+use std::error;
+use std::fmt;
+use std::result;
+use command_buffer::InvalidCommand;
+use dash::InvalidDash;
+use font::InvalidFont;
+use image_filter::InvalidKernel;
+use status::Status;
+use surfaces::{InvalidData, OutOfBounds, SurfaceError};
+use types::{InvalidColor, ParseNameError};
+
+/// A Result whose error is `cairus::Error`.
+pub type Result<T> = result::Result<T, Error>;
+
+/// Any error Cairus returns.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    /// Memory for a surface couldn't be allocated.
+    NoMemory,
+    /// A matrix that can't be inverted was given where one has to be undone.
+    InvalidMatrix,
+    /// Path data that can't describe a shape, and why.
+    InvalidPathData(&'static str),
+    /// A surface couldn't be written out, and why.
+    WriteError(&'static str),
+    /// A surface format that isn't supported.
+    InvalidFormat,
+    /// A surface size that is zero or too big, and why.
+    InvalidSize(&'static str),
+    /// A buffer that can't hold the image it was given for.
+    InvalidStride(InvalidData),
+    /// Dash lengths and offset that can't be used.
+    InvalidDash(InvalidDash),
+    /// A pixel coordinate outside a surface.
+    InvalidIndex(OutOfBounds),
+    /// A color that can't be converted or parsed.
+    InvalidColor(InvalidColor),
+    /// A name that doesn't name any value of an option.
+    InvalidName(ParseNameError),
+    /// Font data that can't be loaded.
+    InvalidFont(InvalidFont),
+    /// A convolution kernel that can't be used.
+    InvalidKernel(InvalidKernel),
+    /// A recorded command that can't be executed.
+    InvalidCommand(InvalidCommand),
+    /// A surface that failed with a status none of the other variants stand for.
+    Surface(SurfaceError),
+}
+
+impl Error {
+    /// Returns the cairo status closest to the error.  Analogous to cairo_status().
+    pub fn status(&self) -> Status {
+        match *self {
+            Error::NoMemory => Status::NoMemory,
+            Error::InvalidMatrix => Status::InvalidMatrix,
+            Error::InvalidPathData(_) => Status::InvalidPathData,
+            Error::WriteError(_) => Status::WriteError,
+            Error::InvalidFormat => Status::InvalidFormat,
+            Error::InvalidSize(_) => Status::InvalidSize,
+            Error::InvalidStride(_) => Status::InvalidStride,
+            Error::InvalidDash(_) => Status::InvalidDash,
+            Error::InvalidIndex(_) => Status::InvalidIndex,
+            Error::InvalidColor(_) | Error::InvalidName(_) => Status::InvalidString,
+            Error::InvalidFont(_) => Status::FontTypeMismatch,
+            Error::InvalidKernel(_) | Error::InvalidCommand(_) => Status::InvalidStatus,
+            Error::Surface(ref error) => error.status,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::NoMemory | Error::InvalidMatrix | Error::InvalidFormat => {
+                write!(f, "{}", self.status())
+            },
+            Error::InvalidPathData(reason) | Error::WriteError(reason) |
+            Error::InvalidSize(reason) => write!(f, "{}: {}", self.status(), reason),
+            Error::InvalidStride(ref error) => error.fmt(f),
+            Error::InvalidDash(ref error) => error.fmt(f),
+            Error::InvalidIndex(ref error) => error.fmt(f),
+            Error::InvalidColor(ref error) => error.fmt(f),
+            Error::InvalidName(ref error) => error.fmt(f),
+            Error::InvalidFont(ref error) => error.fmt(f),
+            Error::InvalidKernel(ref error) => error.fmt(f),
+            Error::InvalidCommand(ref error) => error.fmt(f),
+            Error::Surface(ref error) => error.fmt(f),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            Error::InvalidStride(ref error) => Some(error),
+            Error::InvalidDash(ref error) => Some(error),
+            Error::InvalidIndex(ref error) => Some(error),
+            Error::InvalidColor(ref error) => Some(error),
+            Error::InvalidName(ref error) => Some(error),
+            Error::InvalidFont(ref error) => Some(error),
+            Error::InvalidKernel(ref error) => Some(error),
+            Error::InvalidCommand(ref error) => Some(error),
+            Error::Surface(ref error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/// SurfaceError becomes the variant for its status, or Error::Surface, keeping the status, if
+/// no variant stands for it.
+impl From<SurfaceError> for Error {
+    fn from(error: SurfaceError) -> Error {
+        match error.status {
+            Status::NoMemory => Error::NoMemory,
+            Status::InvalidMatrix => Error::InvalidMatrix,
+            Status::InvalidPathData => Error::InvalidPathData(error.reason),
+            Status::WriteError => Error::WriteError(error.reason),
+            Status::InvalidFormat => Error::InvalidFormat,
+            Status::InvalidSize => Error::InvalidSize(error.reason),
+            Status::Success | Status::InvalidRestore | Status::InvalidPopGroup |
+            Status::NoCurrentPoint | Status::InvalidStatus | Status::NullPointer |
+            Status::InvalidString | Status::ReadError | Status::SurfaceFinished |
+            Status::SurfaceTypeMismatch | Status::PatternTypeMismatch | Status::InvalidContent |
+            Status::InvalidVisual | Status::FileNotFound | Status::InvalidDash |
+            Status::InvalidDscComment | Status::InvalidIndex | Status::ClipNotRepresentable |
+            Status::TempFileError | Status::InvalidStride | Status::FontTypeMismatch |
+            Status::UserFontImmutable | Status::UserFontError | Status::NegativeCount |
+            Status::InvalidClusters | Status::InvalidSlant | Status::InvalidWeight => {
+                Error::Surface(error)
+            },
+        }
+    }
+}
+
+// Implements From for the error types that become a variant as they are
+macro_rules! impl_from {
+    ($($source:ident => $variant:ident),+ $(,)*) => {
+        $(
+            impl From<$source> for Error {
+                fn from(error: $source) -> Error {
+                    Error::$variant(error)
+                }
+            }
+        )+
+    }
+}
+
+impl_from!(
+    InvalidData => InvalidStride,
+    InvalidDash => InvalidDash,
+    OutOfBounds => InvalidIndex,
+    InvalidColor => InvalidColor,
+    ParseNameError => InvalidName,
+    InvalidFont => InvalidFont,
+    InvalidKernel => InvalidKernel,
+    InvalidCommand => InvalidCommand,
+);
+
+#[cfg(test)]
+mod tests {
+    use super::{Error, Result};
+    use status::Status;
+    use surfaces::{Format, ImageSurface, OutOfBounds, SurfaceError};
+    use types::{FillRule, Rgba};
+
+    // Fails at each step it is told to, through `?`
+    fn steps(width: usize, color: &str, rule: &str) -> Result<FillRule> {
+        let mut surface = ImageSurface::try_create_for_format(Format::ARGB32, width, 2)?;
+        surface.set_pixel(0, 1, color.parse::<Rgba>()?)?;
+        Ok(rule.parse::<FillRule>()?)
+    }
+
+    #[test]
+    fn test_errors_convert() {
+        assert_eq!(steps(2, "red", "even-odd"), Ok(FillRule::EvenOdd));
+        assert_eq!(steps(0, "red", "even-odd"), Err(Error::InvalidSize("a dimension is zero")));
+        assert_eq!(steps(usize::MAX / 64, "red", "even-odd"), Err(Error::NoMemory));
+        assert_eq!(steps(2, "blurple", "even-odd").unwrap_err().status(), Status::InvalidString);
+        assert_eq!(steps(2, "red", "odd").unwrap_err().to_string(), "unknown fill rule \"odd\"");
+    }
+
+    #[test]
+    fn test_error_display() {
+        assert_eq!(Error::InvalidMatrix.to_string(), "invalid matrix (not invertible)");
+        assert_eq!(Error::WriteError("file can't be created").to_string(),
+                   "error while writing to output stream: file can't be created");
+        let error = Error::from(OutOfBounds { x: 4, y: 0, width: 4, height: 3 });
+        assert_eq!(error.to_string(), "pixel (4, 0) is outside the 4x3 surface");
+        assert_eq!(error.status(), Status::InvalidIndex);
+    }
+
+    #[test]
+    fn test_surface_error_keeps_its_status() {
+        let error = Error::from(SurfaceError { status: Status::InvalidSize,
+                                               reason: "surface is too big" });
+        assert_eq!(error, Error::InvalidSize("surface is too big"));
+        let surface_error = SurfaceError { status: Status::ReadError,
+                                           reason: "file can't be read" };
+        let error = Error::from(surface_error.clone());
+        assert_eq!(error, Error::Surface(surface_error));
+        assert_eq!(error.status(), Status::ReadError);
+        assert_eq!(error.to_string(), "error while reading from input stream: file can't be read");
+    }
+}
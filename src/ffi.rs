@@ -0,0 +1,1064 @@
+/*
+ * Cairus - a reimplementation of the cairo graphics library in Rust
+ *
+ * Copyright © 2017 CairusOrg
+ *
+ * This library is free software; you can redistribute it and/or
+ * modify it either under the terms of the GNU Lesser General Public
+ * License version 2.1 as published by the Free Software Foundation
+ * (the "LGPL") or, at your option, under the terms of the Mozilla
+ * Public License Version 2.0 (the "MPL"). If you do not alter this
+ * notice, a recipient may use your version of this file under either
+ * the MPL or the LGPL.
+ *
+ * You should have received a copy of the LGPL along with this library
+ * in the file LICENSE-LGPL-2_1; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Suite 500, Boston, MA 02110-1335, USA
+ * You should have received a copy of the MPL along with this library
+ * in the file LICENSE-MPL-2_0
+ *
+ * The contents of this file are subject to the Mozilla Public License
+ * Version 2.0 (the "License"); you may not use this file except in
+ * compliance with the License. You may obtain a copy of the License at
+ * http://www.mozilla.org/MPL/
+ *
+ * This software is distributed on an "AS IS" basis, WITHOUT WARRANTY
+ * OF ANY KIND, either express or implied. See the LGPL or the MPL for
+ * the specific language governing rights and limitations.
+ *
+ * The Original Code is the cairus graphics library.
+ *
+ * Contributor(s):
+ *	CairusOrg
+ *
+ */
+
+//! A C API mirroring a subset of cairo's, so C programs written against cairo can be built and
+//! run against cairus.  `include/cairo.h` declares it; build the crate's cdylib and link against
+//! that instead of libcairo.
+//!
+//! The subset covers image surfaces, creating contexts for them, their drawing state, building
+//! paths and filling, stroking, painting and clipping with them.  Objects behave as in cairo:
+//!
+//! * Surfaces and contexts are reference counted, and a context holds a reference to its target,
+//!   so the surface can be destroyed first.
+//! * A call that fails puts the object into an error status, which cairo_status() and
+//!   cairo_surface_status() report.  The first error sticks, and every later call on the object
+//!   does nothing.  Creating an object never returns NULL; a failed creation returns an object
+//!   in an error status.
+//! * Enum arguments take cairo's numbers.  A number cairo doesn't define is ignored.
+//!
+//! Where cairus differs:
+//!
+//! * Surfaces can't have a width or height of zero; such surfaces are created in
+//!   CAIRO_STATUS_INVALID_SIZE.
+//! * cairo_set_source_surface() copies the surface when it is called, so drawing on it afterwards
+//!   doesn't change the source.
+//! * cairo_paint() always paints with CAIRO_OPERATOR_OVER, as `Context::paint` does.
+//!
+//! # Safety
+//! As in C, every function trusts its arguments.  A cairo_t or cairo_surface_t pointer must be
+//! NULL or one returned by this module whose references haven't all been released.  Strings must
+//! be NUL-terminated, and arrays must hold as many elements as their count says.  NULL objects
+//! are ignored, as cairo ignores its nil objects.
+#![allow(clippy::missing_safety_doc)]
+
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_double, c_int, c_uchar, c_uint};
+use std::path::Path;
+use std::ptr;
+use std::slice;
+use std::sync::OnceLock;
+use common_geometry::{Ellipse, LineSegment, Point, Rectangle, RectangleInt};
+use context::Context;
+use dash::DashPattern;
+use matrix::Matrix;
+use operators::Operator;
+use path;
+use pattern::Pattern;
+use status::Status;
+use stroke::StrokeStyle;
+use surfaces::{BitDepth, Composite, CoverageMask, Format, ImageSurface, SubpixelMask,
+               Surface};
+use types::{Antialias, FillRule, FontSlant, FontWeight, LineCap, LineJoin, Rgba};
+
+/// An image surface.  Analogous to cairo_surface_t, for image surfaces only.
+#[allow(non_camel_case_types)]
+pub struct cairo_surface_t {
+    // None when the surface couldn't be created, with the status saying why
+    surface: Option<ImageSurface>,
+    status: Status,
+    references: usize,
+}
+
+/// A drawing context.  Analogous to cairo_t.
+#[allow(non_camel_case_types)]
+pub struct cairo_t {
+    // None when the context was created in an error status
+    context: Option<Context<'static>>,
+    target: *mut cairo_surface_t,
+    // The Target the context draws through, freed once the context is
+    drawing: *mut Target,
+    status: Status,
+    references: usize,
+}
+
+// cairo_operator_t, in cairo's order
+const OPERATORS: [Operator; 29] = [
+    Operator::Clear, Operator::Source, Operator::Over, Operator::In, Operator::Out,
+    Operator::Atop, Operator::Dest, Operator::DestOver, Operator::DestIn, Operator::DestOut,
+    Operator::DestAtop, Operator::Xor, Operator::Add, Operator::Saturate, Operator::Multiply,
+    Operator::Screen, Operator::Overlay, Operator::Darken, Operator::Lighten,
+    Operator::ColorDodge, Operator::ColorBurn, Operator::HardLight, Operator::SoftLight,
+    Operator::Difference, Operator::Exclusion, Operator::HslHue, Operator::HslSaturation,
+    Operator::HslColor, Operator::HslLuminosity,
+];
+
+// Returns the value a cairo enum numbers `number`, from its values in cairo's order.
+fn nth<T>(values: &[T], number: c_int) -> Option<&T> {
+    if number < 0 {
+        None
+    } else {
+        values.get(number as usize)
+    }
+}
+
+// Returns the Format a cairo_format_t numbers.
+fn format_from_c(format: c_int) -> Format {
+    match format {
+        0 => Format::ARGB32,
+        1 => Format::RGB24,
+        2 => Format::A8,
+        3 => Format::A1,
+        4 => Format::RGB16_565,
+        5 => Format::RGB30,
+        _ => Format::Invalid,
+    }
+}
+
+// Returns the cairo_format_t numbering `format`.
+fn format_to_c(format: Format) -> c_int {
+    match format {
+        Format::Invalid => -1,
+        Format::ARGB32 => 0,
+        Format::RGB24 => 1,
+        Format::A8 => 2,
+        Format::A1 => 3,
+        Format::RGB16_565 => 4,
+        Format::RGB30 => 5,
+    }
+}
+
+// The surface a context draws on: the image of a cairo_surface_t, reached through the pointer on
+// every call so that C can use the surface between drawing calls.
+struct Target(*mut cairo_surface_t);
+
+impl Target {
+    fn image(&self) -> &ImageSurface {
+        // cairo_create only draws on surfaces that have an image, and holds a reference to them
+        unsafe { (*self.0).surface.as_ref().expect("target has no image") }
+    }
+
+    fn image_mut(&mut self) -> &mut ImageSurface {
+        unsafe { (*self.0).surface.as_mut().expect("target has no image") }
+    }
+}
+
+impl Surface for Target {
+    fn extents(&self) -> Option<RectangleInt> {
+        Surface::extents(self.image())
+    }
+
+    fn paint(&mut self, composite: &Composite) {
+        Surface::paint(self.image_mut(), composite);
+    }
+
+    fn mask(&mut self, composite: &Composite, mask: &Pattern, matrix: &Matrix) {
+        Surface::mask(self.image_mut(), composite, mask, matrix);
+    }
+
+    fn fill(&mut self, composite: &Composite, path: &path::Path, fill_rule: FillRule,
+            tolerance: f32) {
+        Surface::fill(self.image_mut(), composite, path, fill_rule, tolerance);
+    }
+
+    fn stroke(&mut self, composite: &Composite, path: &path::Path, matrix: &Matrix,
+              style: &StrokeStyle, dash: Option<&DashPattern>, tolerance: f32) {
+        Surface::stroke(self.image_mut(), composite, path, matrix, style, dash, tolerance);
+    }
+
+    fn fill_rectangles(&mut self, operator: &Operator, color: &Rgba, rectangles: &[Rectangle]) {
+        Surface::fill_rectangles(self.image_mut(), operator, color, rectangles);
+    }
+
+    fn fill_ellipses(&mut self, operator: &Operator, color: &Rgba, ellipses: &[Ellipse]) {
+        Surface::fill_ellipses(self.image_mut(), operator, color, ellipses);
+    }
+
+    fn stroke_segments(&mut self, operator: &Operator, color: &Rgba, segments: &[LineSegment],
+                       line_width: f32) {
+        Surface::stroke_segments(self.image_mut(), operator, color, segments, line_width);
+    }
+
+    fn stroke_polyline_gradient(&mut self, operator: &Operator, start_color: &Rgba,
+                                end_color: &Rgba, points: &[Point], line_width: f32) {
+        Surface::stroke_polyline_gradient(self.image_mut(), operator, start_color, end_color,
+                                          points, line_width);
+    }
+
+    fn mask_coverage(&mut self, composite: &Composite, mask: &CoverageMask) {
+        Surface::mask_coverage(self.image_mut(), composite, mask);
+    }
+
+    fn mask_subpixel(&mut self, composite: &Composite, mask: &SubpixelMask) {
+        Surface::mask_subpixel(self.image_mut(), composite, mask);
+    }
+
+    fn flush(&mut self) {
+        Surface::flush(self.image_mut());
+    }
+
+    fn finish(&mut self) {
+        Surface::finish(self.image_mut());
+    }
+
+    fn show_page(&mut self) {
+        Surface::show_page(self.image_mut());
+    }
+
+    fn copy_page(&mut self) {
+        Surface::copy_page(self.image_mut());
+    }
+}
+
+// Returns the image of `surface`, unless it is NULL or in an error status.
+unsafe fn image<'a>(surface: *mut cairo_surface_t) -> Option<&'a mut ImageSurface> {
+    surface.as_mut().and_then(|surface| surface.surface.as_mut())
+}
+
+// Returns the context of `cr`, unless it is NULL or in an error status.
+unsafe fn context<'a>(cr: *mut cairo_t) -> Option<&'a mut Context<'static>> {
+    match cr.as_mut() {
+        Some(cr) if cr.status.is_success() => cr.context.as_mut(),
+        _ => None,
+    }
+}
+
+// Puts `cr` into `status`, unless it is already in an error status.
+unsafe fn set_error(cr: *mut cairo_t, status: Status) {
+    if let Some(cr) = cr.as_mut() {
+        if cr.status.is_success() {
+            cr.status = status;
+        }
+    }
+}
+
+// Puts `cr` into CAIRO_STATUS_INVALID_MATRIX if its transformation can't be inverted.
+unsafe fn check_matrix(cr: *mut cairo_t) {
+    let invertible = context(cr).map(|context| context.get_matrix().invert().is_some());
+    if invertible == Some(false) {
+        set_error(cr, Status::InvalidMatrix);
+    }
+}
+
+/// Returns a description of `status`.  Analogous to cairo_status_to_string().
+#[no_mangle]
+pub extern "C" fn cairo_status_to_string(status: c_int) -> *const c_char {
+    static DESCRIPTIONS: OnceLock<Vec<CString>> = OnceLock::new();
+    let descriptions = DESCRIPTIONS.get_or_init(|| {
+        Status::all().iter()
+            .map(|status| CString::new(status.description()).expect("descriptions have no NUL"))
+            .collect()
+    });
+    match nth(descriptions, status) {
+        Some(description) => description.as_ptr(),
+        None => b"<unknown error status>\0".as_ptr() as *const c_char,
+    }
+}
+
+/// Returns the stride of a row of `width` pixels in `format`, or -1 if there is none.
+/// Analogous to cairo_format_stride_for_width().
+#[no_mangle]
+pub extern "C" fn cairo_format_stride_for_width(format: c_int, width: c_int) -> c_int {
+    if width < 0 {
+        return -1;
+    }
+    format_from_c(format).stride_for_width(width as usize)
+        .filter(|&stride| stride <= c_int::MAX as usize)
+        .map_or(-1, |stride| stride as c_int)
+}
+
+/// Creates a `width` by `height` image surface in `format`, cleared to zero.  Analogous to
+/// cairo_image_surface_create().
+#[no_mangle]
+pub extern "C" fn cairo_image_surface_create(format: c_int, width: c_int, height: c_int)
+                                             -> *mut cairo_surface_t {
+    let created = if width < 0 || height < 0 {
+        Err(Status::InvalidSize)
+    } else {
+        ImageSurface::try_create_for_format(format_from_c(format), width as usize,
+                                            height as usize)
+            .map_err(|error| error.status)
+    };
+    let (surface, status) = match created {
+        Ok(surface) => (Some(surface), Status::Success),
+        Err(status) => (None, status),
+    };
+    Box::into_raw(Box::new(cairo_surface_t { surface, status, references: 1 }))
+}
+
+/// Adds a reference to `surface`, and returns it.  Analogous to cairo_surface_reference().
+#[no_mangle]
+pub unsafe extern "C" fn cairo_surface_reference(surface: *mut cairo_surface_t)
+                                                 -> *mut cairo_surface_t {
+    if let Some(surface) = surface.as_mut() {
+        surface.references += 1;
+    }
+    surface
+}
+
+/// Releases a reference to `surface`, freeing it once none are left.  Analogous to
+/// cairo_surface_destroy().
+#[no_mangle]
+pub unsafe extern "C" fn cairo_surface_destroy(surface: *mut cairo_surface_t) {
+    if let Some(referenced) = surface.as_mut() {
+        referenced.references -= 1;
+        if referenced.references == 0 {
+            drop(Box::from_raw(surface));
+        }
+    }
+}
+
+/// Returns the number of references to `surface`, or 0 for NULL.  Analogous to
+/// cairo_surface_get_reference_count().
+#[no_mangle]
+pub unsafe extern "C" fn cairo_surface_get_reference_count(surface: *mut cairo_surface_t)
+                                                           -> c_uint {
+    surface.as_ref().map_or(0, |surface| surface.references as c_uint)
+}
+
+/// Returns the status of `surface`.  Analogous to cairo_surface_status().
+#[no_mangle]
+pub unsafe extern "C" fn cairo_surface_status(surface: *mut cairo_surface_t) -> c_int {
+    surface.as_ref().map_or(Status::NullPointer, |surface| surface.status) as c_int
+}
+
+/// Finishes any drawing on `surface` and brings the bytes cairo_image_surface_get_data() returns
+/// up to date.  Analogous to cairo_surface_flush().
+#[no_mangle]
+pub unsafe extern "C" fn cairo_surface_flush(surface: *mut cairo_surface_t) {
+    if let Some(image) = image(surface) {
+        Surface::flush(image);
+        image.data_ptr();
+    }
+}
+
+/// Reads back what was written to the bytes cairo_image_surface_get_data() returns, which
+/// drawing would otherwise overwrite.  Analogous to cairo_surface_mark_dirty().
+#[no_mangle]
+pub unsafe extern "C" fn cairo_surface_mark_dirty(surface: *mut cairo_surface_t) {
+    if let Some(image) = image(surface) {
+        image.mark_dirty();
+    }
+}
+
+/// Writes `surface` to `filename` as a PNG, and returns the status of writing it.  Analogous to
+/// cairo_surface_write_to_png().
+#[no_mangle]
+pub unsafe extern "C" fn cairo_surface_write_to_png(surface: *mut cairo_surface_t,
+                                                    filename: *const c_char) -> c_int {
+    let surface = match surface.as_ref() {
+        Some(surface) => surface,
+        None => return Status::NullPointer as c_int,
+    };
+    let image = match surface.surface {
+        Some(ref image) => image,
+        None => return surface.status as c_int,
+    };
+    if filename.is_null() {
+        return Status::NullPointer as c_int;
+    }
+    let status = match CStr::from_ptr(filename).to_str() {
+        Ok(filename) => image.to_png(Path::new(filename), BitDepth::Eight).err()
+            .map_or(Status::Success, |error| error.status),
+        Err(_) => Status::InvalidString,
+    };
+    status as c_int
+}
+
+/// Returns the pixels of `surface`, packed as `ImageSurface::data` describes, or NULL if it is in
+/// an error status.  Analogous to cairo_image_surface_get_data().
+///
+/// The bytes stay where they are for the life of the surface.  Call cairo_surface_flush() before
+/// reading them after drawing, and cairo_surface_mark_dirty() after writing them.
+#[no_mangle]
+pub unsafe extern "C" fn cairo_image_surface_get_data(surface: *mut cairo_surface_t)
+                                                      -> *mut c_uchar {
+    image(surface).map_or(ptr::null_mut(), |image| image.data_ptr())
+}
+
+/// Returns the format of `surface`.  Analogous to cairo_image_surface_get_format().
+#[no_mangle]
+pub unsafe extern "C" fn cairo_image_surface_get_format(surface: *mut cairo_surface_t) -> c_int {
+    format_to_c(image(surface).map_or(Format::Invalid, |image| image.get_format()))
+}
+
+/// Returns the width of `surface`, in pixels.  Analogous to cairo_image_surface_get_width().
+#[no_mangle]
+pub unsafe extern "C" fn cairo_image_surface_get_width(surface: *mut cairo_surface_t) -> c_int {
+    image(surface).map_or(0, |image| image.dimensions().0 as c_int)
+}
+
+/// Returns the height of `surface`, in pixels.  Analogous to cairo_image_surface_get_height().
+#[no_mangle]
+pub unsafe extern "C" fn cairo_image_surface_get_height(surface: *mut cairo_surface_t) -> c_int {
+    image(surface).map_or(0, |image| image.dimensions().1 as c_int)
+}
+
+/// Returns the number of bytes between the starts of rows of the data.  Analogous to
+/// cairo_image_surface_get_stride().
+#[no_mangle]
+pub unsafe extern "C" fn cairo_image_surface_get_stride(surface: *mut cairo_surface_t) -> c_int {
+    image(surface).map_or(0, |image| image.get_stride() as c_int)
+}
+
+/// Creates a context drawing on `target`, with cairo's defaults: an opaque black source, the
+/// over operator and a line width of 2.  Analogous to cairo_create().
+#[no_mangle]
+pub unsafe extern "C" fn cairo_create(target: *mut cairo_surface_t) -> *mut cairo_t {
+    let status = target.as_ref().map_or(Status::NullPointer, |surface| surface.status);
+    let cr = if status.is_success() {
+        let drawing = Box::into_raw(Box::new(Target(cairo_surface_reference(target))));
+        let mut context = Context::create(&mut *drawing);
+        context.set_source_rgba(0., 0., 0., 1.);
+        cairo_t { context: Some(context), target, drawing, status, references: 1 }
+    } else {
+        let (target, drawing) = (ptr::null_mut(), ptr::null_mut());
+        cairo_t { context: None, target, drawing, status, references: 1 }
+    };
+    Box::into_raw(Box::new(cr))
+}
+
+/// Adds a reference to `cr`, and returns it.  Analogous to cairo_reference().
+#[no_mangle]
+pub unsafe extern "C" fn cairo_reference(cr: *mut cairo_t) -> *mut cairo_t {
+    if let Some(referenced) = cr.as_mut() {
+        referenced.references += 1;
+    }
+    cr
+}
+
+/// Releases a reference to `cr`, freeing it and releasing its target once none are left.
+/// Analogous to cairo_destroy().
+#[no_mangle]
+pub unsafe extern "C" fn cairo_destroy(cr: *mut cairo_t) {
+    let referenced = match cr.as_mut() {
+        Some(referenced) => referenced,
+        None => return,
+    };
+    referenced.references -= 1;
+    if referenced.references > 0 {
+        return;
+    }
+    let cr = *Box::from_raw(cr);
+    // The context borrows the Target, so it goes first
+    drop(cr.context);
+    if !cr.drawing.is_null() {
+        drop(Box::from_raw(cr.drawing));
+    }
+    cairo_surface_destroy(cr.target);
+}
+
+/// Returns the number of references to `cr`, or 0 for NULL.  Analogous to
+/// cairo_get_reference_count().
+#[no_mangle]
+pub unsafe extern "C" fn cairo_get_reference_count(cr: *mut cairo_t) -> c_uint {
+    cr.as_ref().map_or(0, |cr| cr.references as c_uint)
+}
+
+/// Returns the status of `cr`.  Analogous to cairo_status().
+#[no_mangle]
+pub unsafe extern "C" fn cairo_status(cr: *mut cairo_t) -> c_int {
+    cr.as_ref().map_or(Status::NullPointer, |cr| cr.status) as c_int
+}
+
+/// Returns the surface `cr` draws on, without adding a reference, or NULL if it is in an error
+/// status.  Analogous to cairo_get_target().
+#[no_mangle]
+pub unsafe extern "C" fn cairo_get_target(cr: *mut cairo_t) -> *mut cairo_surface_t {
+    cr.as_ref().map_or(ptr::null_mut(), |cr| cr.target)
+}
+
+/// Sets the source to an opaque color.  Analogous to cairo_set_source_rgb().
+#[no_mangle]
+pub unsafe extern "C" fn cairo_set_source_rgb(cr: *mut cairo_t, red: c_double, green: c_double,
+                                              blue: c_double) {
+    cairo_set_source_rgba(cr, red, green, blue, 1.);
+}
+
+/// Sets the source to a translucent color.  Analogous to cairo_set_source_rgba().
+#[no_mangle]
+pub unsafe extern "C" fn cairo_set_source_rgba(cr: *mut cairo_t, red: c_double, green: c_double,
+                                               blue: c_double, alpha: c_double) {
+    if let Some(context) = context(cr) {
+        context.set_source_rgba(red as f32, green as f32, blue as f32, alpha as f32);
+    }
+}
+
+/// Sets the source to a copy of `surface`, with its origin at (x, y) in user space.  Analogous to
+/// cairo_set_source_surface().
+#[no_mangle]
+pub unsafe extern "C" fn cairo_set_source_surface(cr: *mut cairo_t,
+                                                  surface: *mut cairo_surface_t, x: c_double,
+                                                  y: c_double) {
+    let status = surface.as_ref().map_or(Status::NullPointer, |surface| surface.status);
+    match (image(surface), context(cr)) {
+        (Some(image), Some(context)) => {
+            context.set_source_surface(image.clone(), x as f32, y as f32);
+        },
+        (None, Some(_)) => set_error(cr, status),
+        _ => (),
+    }
+}
+
+/// Sets the operator drawing composites with.  Analogous to cairo_set_operator().
+#[no_mangle]
+pub unsafe extern "C" fn cairo_set_operator(cr: *mut cairo_t, op: c_int) {
+    if let (Some(context), Some(&operator)) = (context(cr), nth(&OPERATORS, op)) {
+        context.set_operator(operator);
+    }
+}
+
+/// Sets the width of stroked lines, in user space.  Analogous to cairo_set_line_width().
+#[no_mangle]
+pub unsafe extern "C" fn cairo_set_line_width(cr: *mut cairo_t, width: c_double) {
+    if let Some(context) = context(cr) {
+        context.set_line_width(width as f32);
+    }
+}
+
+/// Returns the width of stroked lines.  Analogous to cairo_get_line_width().
+#[no_mangle]
+pub unsafe extern "C" fn cairo_get_line_width(cr: *mut cairo_t) -> c_double {
+    context(cr).map_or(2., |context| context.get_line_width() as c_double)
+}
+
+/// Sets how the ends of stroked lines are drawn.  Analogous to cairo_set_line_cap().
+#[no_mangle]
+pub unsafe extern "C" fn cairo_set_line_cap(cr: *mut cairo_t, line_cap: c_int) {
+    if let (Some(context), Some(&line_cap)) = (context(cr), nth(LineCap::all(), line_cap)) {
+        context.set_line_cap(line_cap);
+    }
+}
+
+/// Sets how the corners of stroked lines are drawn.  Analogous to cairo_set_line_join().
+#[no_mangle]
+pub unsafe extern "C" fn cairo_set_line_join(cr: *mut cairo_t, line_join: c_int) {
+    if let (Some(context), Some(&line_join)) = (context(cr), nth(LineJoin::all(), line_join)) {
+        context.set_line_join(line_join);
+    }
+}
+
+/// Sets the limit beyond which mitered joins are beveled.  Analogous to cairo_set_miter_limit().
+#[no_mangle]
+pub unsafe extern "C" fn cairo_set_miter_limit(cr: *mut cairo_t, limit: c_double) {
+    if let Some(context) = context(cr) {
+        context.set_miter_limit(limit as f32);
+    }
+}
+
+/// Sets the dashes of stroked lines, or turns them off when `num_dashes` is 0.  Analogous to
+/// cairo_set_dash().
+#[no_mangle]
+pub unsafe extern "C" fn cairo_set_dash(cr: *mut cairo_t, dashes: *const c_double,
+                                        num_dashes: c_int, offset: c_double) {
+    let dashes = match (num_dashes, dashes.is_null()) {
+        (0, _) => &[][..],
+        (count, false) if count > 0 => slice::from_raw_parts(dashes, count as usize),
+        _ => return set_error(cr, Status::InvalidDash),
+    };
+    if let Some(context) = context(cr) {
+        if context.set_dash(dashes, offset).is_err() {
+            set_error(cr, Status::InvalidDash);
+        }
+    }
+}
+
+/// Sets which areas inside a path are filled.  Analogous to cairo_set_fill_rule().
+#[no_mangle]
+pub unsafe extern "C" fn cairo_set_fill_rule(cr: *mut cairo_t, fill_rule: c_int) {
+    if let (Some(context), Some(&fill_rule)) = (context(cr), nth(FillRule::all(), fill_rule)) {
+        context.set_fill_rule(fill_rule);
+    }
+}
+
+/// Sets how far flattened curves may stray from the curve, in device pixels.  Analogous to
+/// cairo_set_tolerance().
+#[no_mangle]
+pub unsafe extern "C" fn cairo_set_tolerance(cr: *mut cairo_t, tolerance: c_double) {
+    if let Some(context) = context(cr) {
+        context.set_tolerance(tolerance as f32);
+    }
+}
+
+/// Sets the antialiasing of drawing.  Analogous to cairo_set_antialias().
+#[no_mangle]
+pub unsafe extern "C" fn cairo_set_antialias(cr: *mut cairo_t, antialias: c_int) {
+    if let (Some(context), Some(&antialias)) = (context(cr), nth(Antialias::all(), antialias)) {
+        context.set_antialias(antialias);
+    }
+}
+
+/// Moves user space by (tx, ty).  Analogous to cairo_translate().
+#[no_mangle]
+pub unsafe extern "C" fn cairo_translate(cr: *mut cairo_t, tx: c_double, ty: c_double) {
+    if let Some(context) = context(cr) {
+        context.translate(tx as f32, ty as f32);
+    }
+    check_matrix(cr);
+}
+
+/// Scales user space by (sx, sy).  Analogous to cairo_scale().
+#[no_mangle]
+pub unsafe extern "C" fn cairo_scale(cr: *mut cairo_t, sx: c_double, sy: c_double) {
+    if let Some(context) = context(cr) {
+        context.scale(sx as f32, sy as f32);
+    }
+    check_matrix(cr);
+}
+
+/// Rotates user space by `angle` radians.  Analogous to cairo_rotate().
+#[no_mangle]
+pub unsafe extern "C" fn cairo_rotate(cr: *mut cairo_t, angle: c_double) {
+    if let Some(context) = context(cr) {
+        context.rotate(angle as f32);
+    }
+    check_matrix(cr);
+}
+
+/// Makes user space device space again.  Analogous to cairo_identity_matrix().
+#[no_mangle]
+pub unsafe extern "C" fn cairo_identity_matrix(cr: *mut cairo_t) {
+    if let Some(context) = context(cr) {
+        context.identity_matrix();
+    }
+}
+
+/// Clears the current path.  Analogous to cairo_new_path().
+#[no_mangle]
+pub unsafe extern "C" fn cairo_new_path(cr: *mut cairo_t) {
+    if let Some(context) = context(cr) {
+        context.new_path();
+    }
+}
+
+/// Begins a new subpath at (x, y).  Analogous to cairo_move_to().
+#[no_mangle]
+pub unsafe extern "C" fn cairo_move_to(cr: *mut cairo_t, x: c_double, y: c_double) {
+    if let Some(context) = context(cr) {
+        context.move_to(x as f32, y as f32);
+    }
+}
+
+/// Adds a line to (x, y).  Analogous to cairo_line_to().
+#[no_mangle]
+pub unsafe extern "C" fn cairo_line_to(cr: *mut cairo_t, x: c_double, y: c_double) {
+    if let Some(context) = context(cr) {
+        context.line_to(x as f32, y as f32);
+    }
+}
+
+/// Adds a cubic Bézier curve to (x3, y3).  Analogous to cairo_curve_to().
+#[no_mangle]
+pub unsafe extern "C" fn cairo_curve_to(cr: *mut cairo_t, x1: c_double, y1: c_double,
+                                        x2: c_double, y2: c_double, x3: c_double, y3: c_double) {
+    if let Some(context) = context(cr) {
+        context.curve_to(x1 as f32, y1 as f32, x2 as f32, y2 as f32, x3 as f32, y3 as f32);
+    }
+}
+
+// Returns the context of `cr` if it has a current point, and otherwise puts it into
+// CAIRO_STATUS_NO_CURRENT_POINT, as cairo's relative path calls do.
+unsafe fn context_with_current_point<'a>(cr: *mut cairo_t) -> Option<&'a mut Context<'static>> {
+    let has_current_point = context(cr).map(|context| context.get_current_point().is_some());
+    if has_current_point == Some(false) {
+        set_error(cr, Status::NoCurrentPoint);
+    }
+    context(cr)
+}
+
+/// Begins a new subpath offset by (dx, dy) from the current point.  Analogous to
+/// cairo_rel_move_to().
+#[no_mangle]
+pub unsafe extern "C" fn cairo_rel_move_to(cr: *mut cairo_t, dx: c_double, dy: c_double) {
+    if let Some(context) = context_with_current_point(cr) {
+        context.rel_move_to(dx as f32, dy as f32);
+    }
+}
+
+/// Adds a line to the point offset by (dx, dy) from the current point.  Analogous to
+/// cairo_rel_line_to().
+#[no_mangle]
+pub unsafe extern "C" fn cairo_rel_line_to(cr: *mut cairo_t, dx: c_double, dy: c_double) {
+    if let Some(context) = context_with_current_point(cr) {
+        context.rel_line_to(dx as f32, dy as f32);
+    }
+}
+
+/// Adds a cubic Bézier curve with its points offset from the current point.  Analogous to
+/// cairo_rel_curve_to().
+#[no_mangle]
+pub unsafe extern "C" fn cairo_rel_curve_to(cr: *mut cairo_t, dx1: c_double, dy1: c_double,
+                                            dx2: c_double, dy2: c_double, dx3: c_double,
+                                            dy3: c_double) {
+    if let Some(context) = context_with_current_point(cr) {
+        context.rel_curve_to(dx1 as f32, dy1 as f32, dx2 as f32, dy2 as f32, dx3 as f32,
+                             dy3 as f32);
+    }
+}
+
+/// Adds a clockwise arc around (xc, yc).  Analogous to cairo_arc().
+#[no_mangle]
+pub unsafe extern "C" fn cairo_arc(cr: *mut cairo_t, xc: c_double, yc: c_double,
+                                   radius: c_double, angle1: c_double, angle2: c_double) {
+    if let Some(context) = context(cr) {
+        context.arc(xc as f32, yc as f32, radius as f32, angle1 as f32, angle2 as f32);
+    }
+}
+
+/// Adds a counterclockwise arc around (xc, yc).  Analogous to cairo_arc_negative().
+#[no_mangle]
+pub unsafe extern "C" fn cairo_arc_negative(cr: *mut cairo_t, xc: c_double, yc: c_double,
+                                            radius: c_double, angle1: c_double,
+                                            angle2: c_double) {
+    if let Some(context) = context(cr) {
+        context.arc_negative(xc as f32, yc as f32, radius as f32, angle1 as f32, angle2 as f32);
+    }
+}
+
+/// Adds a closed rectangle with its corner at (x, y).  Analogous to cairo_rectangle().
+#[no_mangle]
+pub unsafe extern "C" fn cairo_rectangle(cr: *mut cairo_t, x: c_double, y: c_double,
+                                         width: c_double, height: c_double) {
+    if let Some(context) = context(cr) {
+        let (width, height) = (width as f32, height as f32);
+        context.move_to(x as f32, y as f32);
+        context.rel_line_to(width, 0.);
+        context.rel_line_to(0., height);
+        context.rel_line_to(-width, 0.);
+        context.close_path();
+    }
+}
+
+/// Closes the current subpath.  Analogous to cairo_close_path().
+#[no_mangle]
+pub unsafe extern "C" fn cairo_close_path(cr: *mut cairo_t) {
+    if let Some(context) = context(cr) {
+        context.close_path();
+    }
+}
+
+/// Stores the current point, in user space, in `x` and `y`, or (0, 0) without one.  Analogous to
+/// cairo_get_current_point().  Either pointer may be NULL.
+#[no_mangle]
+pub unsafe extern "C" fn cairo_get_current_point(cr: *mut cairo_t, x: *mut c_double,
+                                                 y: *mut c_double) {
+    let point = context(cr).and_then(|context| context.get_current_point())
+        .unwrap_or(Point::new(0., 0.));
+    if let Some(x) = x.as_mut() {
+        *x = point.x as c_double;
+    }
+    if let Some(y) = y.as_mut() {
+        *y = point.y as c_double;
+    }
+}
+
+/// Paints the source everywhere inside the clip.  Analogous to cairo_paint().
+#[no_mangle]
+pub unsafe extern "C" fn cairo_paint(cr: *mut cairo_t) {
+    if let Some(context) = context(cr) {
+        context.paint();
+    }
+}
+
+/// Paints the source everywhere inside the clip at `alpha`.  Analogous to
+/// cairo_paint_with_alpha().
+#[no_mangle]
+pub unsafe extern "C" fn cairo_paint_with_alpha(cr: *mut cairo_t, alpha: c_double) {
+    if let Some(context) = context(cr) {
+        context.mask(&Pattern::rgba(0., 0., 0., alpha as f32));
+    }
+}
+
+/// Fills the current path, then clears it.  Analogous to cairo_fill().
+#[no_mangle]
+pub unsafe extern "C" fn cairo_fill(cr: *mut cairo_t) {
+    if let Some(context) = context(cr) {
+        context.fill();
+    }
+}
+
+/// Fills the current path, keeping it.  Analogous to cairo_fill_preserve().
+#[no_mangle]
+pub unsafe extern "C" fn cairo_fill_preserve(cr: *mut cairo_t) {
+    if let Some(context) = context(cr) {
+        context.fill_preserve();
+    }
+}
+
+/// Strokes the current path, then clears it.  Analogous to cairo_stroke().
+#[no_mangle]
+pub unsafe extern "C" fn cairo_stroke(cr: *mut cairo_t) {
+    if let Some(context) = context(cr) {
+        context.stroke();
+    }
+}
+
+/// Strokes the current path, keeping it.  Analogous to cairo_stroke_preserve().
+#[no_mangle]
+pub unsafe extern "C" fn cairo_stroke_preserve(cr: *mut cairo_t) {
+    if let Some(context) = context(cr) {
+        context.stroke_preserve();
+    }
+}
+
+/// Restricts drawing to the current path, then clears it.  Analogous to cairo_clip().
+#[no_mangle]
+pub unsafe extern "C" fn cairo_clip(cr: *mut cairo_t) {
+    if let Some(context) = context(cr) {
+        context.clip();
+    }
+}
+
+/// Restricts drawing to the current path, keeping it.  Analogous to cairo_clip_preserve().
+#[no_mangle]
+pub unsafe extern "C" fn cairo_clip_preserve(cr: *mut cairo_t) {
+    if let Some(context) = context(cr) {
+        context.clip_preserve();
+    }
+}
+
+/// Removes the clip.  Analogous to cairo_reset_clip().
+#[no_mangle]
+pub unsafe extern "C" fn cairo_reset_clip(cr: *mut cairo_t) {
+    if let Some(context) = context(cr) {
+        context.reset_clip();
+    }
+}
+
+/// Redirects drawing to a new group.  Analogous to cairo_push_group().
+#[no_mangle]
+pub unsafe extern "C" fn cairo_push_group(cr: *mut cairo_t) {
+    if let Some(context) = context(cr) {
+        context.push_group();
+    }
+}
+
+/// Ends the innermost group and sets it as the source, or puts `cr` into
+/// CAIRO_STATUS_INVALID_POP_GROUP without one.  Analogous to cairo_pop_group_to_source().
+#[no_mangle]
+pub unsafe extern "C" fn cairo_pop_group_to_source(cr: *mut cairo_t) {
+    if let Some(context) = context(cr) {
+        match context.pop_group() {
+            Some(group) => context.set_source(group),
+            None => set_error(cr, Status::InvalidPopGroup),
+        }
+    }
+}
+
+/// Selects the font text is drawn in.  Analogous to cairo_select_font_face().
+#[no_mangle]
+pub unsafe extern "C" fn cairo_select_font_face(cr: *mut cairo_t, family: *const c_char,
+                                                slant: c_int, weight: c_int) {
+    if family.is_null() {
+        return set_error(cr, Status::NullPointer);
+    }
+    let family = match CStr::from_ptr(family).to_str() {
+        Ok(family) => family,
+        Err(_) => return set_error(cr, Status::InvalidString),
+    };
+    let slant = match nth(FontSlant::all(), slant) {
+        Some(&slant) => slant,
+        None => return set_error(cr, Status::InvalidSlant),
+    };
+    let weight = match nth(FontWeight::all(), weight) {
+        Some(&weight) => weight,
+        None => return set_error(cr, Status::InvalidWeight),
+    };
+    if let Some(context) = context(cr) {
+        context.select_font_face(family, slant, weight);
+    }
+}
+
+/// Sets the size text is drawn at.  Analogous to cairo_set_font_size().
+#[no_mangle]
+pub unsafe extern "C" fn cairo_set_font_size(cr: *mut cairo_t, size: c_double) {
+    if let Some(context) = context(cr) {
+        context.set_font_size(size as f32);
+    }
+}
+
+/// Draws `utf8` at the current point.  Analogous to cairo_show_text(); NULL draws nothing.
+#[no_mangle]
+pub unsafe extern "C" fn cairo_show_text(cr: *mut cairo_t, utf8: *const c_char) {
+    if utf8.is_null() {
+        return;
+    }
+    match CStr::from_ptr(utf8).to_str() {
+        Ok(text) => {
+            if let Some(context) = context(cr) {
+                context.show_text(text);
+            }
+        },
+        Err(_) => set_error(cr, Status::InvalidString),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::f64::consts::PI;
+    use std::fs;
+
+    // Returns pixel (x, y) of an ARGB32 surface as cairo packs it.
+    unsafe fn argb32_pixel(surface: *mut cairo_surface_t, x: usize, y: usize) -> u32 {
+        cairo_surface_flush(surface);
+        let data = cairo_image_surface_get_data(surface);
+        let stride = cairo_image_surface_get_stride(surface) as usize;
+        let start = y * stride + x * 4;
+        let bytes = slice::from_raw_parts(data.add(start), 4);
+        u32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+    }
+
+    #[test]
+    fn test_draw_through_c_api() {
+        unsafe {
+            // Setup
+            let surface = cairo_image_surface_create(0, 20, 20);
+            let cr = cairo_create(surface);
+
+            // Call
+            cairo_rectangle(cr, 2., 2., 8., 8.);
+            cairo_set_source_rgb(cr, 1., 0., 0.);
+            cairo_fill_preserve(cr);
+            cairo_set_source_rgba(cr, 0., 0., 1., 1.);
+            cairo_set_line_width(cr, 2.);
+            cairo_stroke(cr);
+            cairo_arc(cr, 15., 15., 3., 0., 2. * PI);
+            cairo_fill(cr);
+
+            // Test
+            assert_eq!(cairo_status(cr), 0);
+            assert_eq!(cairo_image_surface_get_width(surface), 20);
+            assert_eq!(cairo_image_surface_get_stride(surface), 80);
+            assert_eq!(argb32_pixel(surface, 5, 5), 0xffff0000);
+            assert_eq!(argb32_pixel(surface, 2, 5), 0xff0000ff);
+            assert_eq!(argb32_pixel(surface, 15, 15), 0xff0000ff);
+            assert_eq!(argb32_pixel(surface, 0, 19), 0);
+
+            // The context holds a reference, so the surface outlives it either way round
+            cairo_surface_destroy(surface);
+            assert_eq!(cairo_surface_get_reference_count(surface), 1);
+            cairo_destroy(cr);
+        }
+    }
+
+    #[test]
+    fn test_data_is_read_back_after_mark_dirty() {
+        unsafe {
+            // Setup
+            let surface = cairo_image_surface_create(2, 4, 4);
+            let data = cairo_image_surface_get_data(surface);
+
+            // Call
+            *data.add(5) = 255;
+            cairo_surface_mark_dirty(surface);
+            let cr = cairo_create(surface);
+            cairo_rectangle(cr, 0., 0., 1., 1.);
+            cairo_fill(cr);
+            cairo_surface_flush(surface);
+
+            // Test
+            assert_eq!(cairo_image_surface_get_format(surface), 2);
+            assert_eq!(*data, 255);
+            assert_eq!(*data.add(5), 255);
+            assert_eq!(*data.add(6), 0);
+            cairo_destroy(cr);
+            cairo_surface_destroy(surface);
+        }
+    }
+
+    #[test]
+    fn test_errors_put_objects_into_error_status() {
+        unsafe {
+            let invalid_size = cairo_image_surface_create(0, 0, 10);
+            assert_eq!(cairo_surface_status(invalid_size), Status::InvalidSize as c_int);
+            assert!(cairo_image_surface_get_data(invalid_size).is_null());
+            let cr = cairo_create(invalid_size);
+            assert_eq!(cairo_status(cr), Status::InvalidSize as c_int);
+            cairo_destroy(cr);
+            cairo_surface_destroy(invalid_size);
+
+            let invalid_format = cairo_image_surface_create(4, 10, 10);
+            assert_eq!(cairo_surface_status(invalid_format), Status::InvalidFormat as c_int);
+            cairo_surface_destroy(invalid_format);
+            let cr = cairo_create(ptr::null_mut());
+            assert_eq!(cairo_status(cr), Status::NullPointer as c_int);
+            cairo_destroy(cr);
+
+            // The first error sticks, and drawing does nothing afterwards
+            let surface = cairo_image_surface_create(0, 10, 10);
+            let cr = cairo_create(surface);
+            cairo_rel_line_to(cr, 5., 5.);
+            assert_eq!(cairo_status(cr), Status::NoCurrentPoint as c_int);
+            cairo_pop_group_to_source(cr);
+            cairo_scale(cr, 0., 1.);
+            assert_eq!(cairo_status(cr), Status::NoCurrentPoint as c_int);
+            cairo_paint(cr);
+            assert_eq!(argb32_pixel(surface, 5, 5), 0);
+            cairo_destroy(cr);
+
+            let cr = cairo_create(surface);
+            cairo_pop_group_to_source(cr);
+            assert_eq!(cairo_status(cr), Status::InvalidPopGroup as c_int);
+            cairo_destroy(cr);
+            cairo_surface_destroy(surface);
+        }
+    }
+
+    #[test]
+    fn test_status_to_string() {
+        let description = |status| unsafe {
+            CStr::from_ptr(cairo_status_to_string(status)).to_str().unwrap()
+        };
+        assert_eq!(description(1), "out of memory");
+        assert_eq!(description(32), Status::InvalidSize.description());
+        assert_eq!(description(-1), "<unknown error status>");
+        assert_eq!(cairo_format_stride_for_width(0, 10), 40);
+        assert_eq!(cairo_format_stride_for_width(3, 10), 4);
+        assert_eq!(cairo_format_stride_for_width(-1, 10), -1);
+    }
+
+    #[test]
+    fn test_write_to_png() {
+        unsafe {
+            // Setup
+            let surface = cairo_image_surface_create(0, 4, 4);
+            let path = env::temp_dir().join("cairus_ffi_write_to_png.png");
+            let filename = CString::new(path.to_str().unwrap()).unwrap();
+            let missing = CString::new("/nonexistent/directory/out.png").unwrap();
+
+            // Call
+            let written = cairo_surface_write_to_png(surface, filename.as_ptr());
+            let unwritten = cairo_surface_write_to_png(surface, missing.as_ptr());
+
+            // Test
+            assert_eq!(written, 0);
+            assert!(path.exists());
+            assert_eq!(unwritten, Status::WriteError as c_int);
+            fs::remove_file(path).unwrap();
+            cairo_surface_destroy(surface);
+        }
+    }
+}
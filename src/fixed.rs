@@ -0,0 +1,128 @@
+/*
+ * Cairus - a reimplementation of the cairo graphics library in Rust
+ *
+ * Copyright © 2017 CairusOrg
+ *
+ * This library is free software; you can redistribute it and/or
+ * modify it either under the terms of the GNU Lesser General Public
+ * License version 2.1 as published by the Free Software Foundation
+ * (the "LGPL") or, at your option, under the terms of the Mozilla
+ * Public License Version 2.0 (the "MPL"). If you do not alter this
+ * notice, a recipient may use your version of this file under either
+ * the MPL or the LGPL.
+ *
+ * You should have received a copy of the LGPL along with this library
+ * in the file LICENSE-LGPL-2_1; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Suite 500, Boston, MA 02110-1335, USA
+ * You should have received a copy of the MPL along with this library
+ * in the file LICENSE-MPL-2_0
+ *
+ * The contents of this file are subject to the Mozilla Public License
+ * Version 2.0 (the "License"); you may not use this file except in
+ * compliance with the License. You may obtain a copy of the License at
+ * http://www.mozilla.org/MPL/
+ *
+ * This software is distributed on an "AS IS" basis, WITHOUT WARRANTY
+ * OF ANY KIND, either express or implied. See the LGPL or the MPL for
+ * the specific language governing rights and limitations.
+ *
+ * The Original Code is the cairus graphics library.
+ *
+ * Contributor(s):
+ *	CairusOrg
+ *
+ */
+
+//! Fixed-point coordinates.
+//!
+//! As cairo does with cairo_fixed_t, the tessellator and rasterizer snap coordinates to a grid of
+//! 1/256 of a pixel before comparing them.  Points that are meant to coincide then compare equal
+//! exactly, rather than differing in their last bits depending on how they were computed, so
+//! the order of events in the sweep and the edges shared by neighbouring trapezoids don't
+//! depend on rounding error.
+
+use common_geometry::Point;
+
+/// ## Fixed
+///
+/// A signed 24.8 fixed-point number: 24 bits of integer and 8 bits of fraction.  Analogous to
+/// cairo_fixed_t.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Fixed(i32);
+
+impl Fixed {
+    /// The number of bits after the binary point.
+    pub const FRACTION_BITS: u32 = 8;
+
+    /// One, in fixed point.
+    pub const ONE: Fixed = Fixed(1 << Fixed::FRACTION_BITS);
+
+    /// Returns the fixed-point number nearest to `value`.
+    ///
+    /// Values out of range saturate, and NaN becomes zero, as Rust's casts from floating point
+    /// to integers do.
+    pub fn from_f32(value: f32) -> Fixed {
+        Fixed((value * Fixed::ONE.0 as f32).round() as i32)
+    }
+
+    /// Returns the fixed-point number whose bits are `raw`.
+    pub fn from_raw(raw: i32) -> Fixed {
+        Fixed(raw)
+    }
+
+    /// Returns the bits of the fixed-point number.
+    pub fn raw(self) -> i32 {
+        self.0
+    }
+
+    /// Returns the value of the fixed-point number.
+    ///
+    /// This is exact while the value is under 65536 in magnitude, beyond which f32 can't hold
+    /// all 8 fraction bits.
+    pub fn to_f32(self) -> f32 {
+        self.0 as f32 / Fixed::ONE.0 as f32
+    }
+}
+
+/// Returns `value` rounded to the nearest 1/256.
+pub fn snap(value: f32) -> f32 {
+    Fixed::from_f32(value).to_f32()
+}
+
+/// Returns `point` with both of its coordinates rounded to the nearest 1/256.
+pub fn snap_point(point: Point) -> Point {
+    Point::new(snap(point.x), snap(point.y))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fixed_round_trip() {
+        assert_eq!(Fixed::from_f32(1.), Fixed::ONE);
+        assert_eq!(Fixed::from_f32(-2.5).raw(), -640);
+        assert_eq!(Fixed::from_raw(384).to_f32(), 1.5);
+        // Values between steps round to the nearest one
+        assert_eq!(Fixed::from_f32(0.3).raw(), 77);
+        assert_eq!(Fixed::from_f32(1. / 512. + 1e-6).raw(), 1);
+        assert!(Fixed::from_f32(0.1) < Fixed::from_f32(0.2));
+        assert_eq!(Fixed::from_f32(f32::INFINITY).raw(), i32::MAX);
+        assert_eq!(Fixed::from_f32(f32::NAN).raw(), 0);
+    }
+
+    #[test]
+    fn snap_makes_computed_points_equal() {
+        // Setup
+        let a = 0.1 + 0.2;
+        let b = 0.3;
+
+        // Call
+        let point = snap_point(Point::new(a, 1. / 3.));
+
+        // Test
+        assert_eq!(snap(a), snap(b));
+        assert_eq!(point, Point::new(0.30078125, 0.33203125));
+        assert_eq!(snap(point.x), point.x);
+    }
+}
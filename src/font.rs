@@ -0,0 +1,1394 @@
+/*
+ * Cairus - a reimplementation of the cairo graphics library in Rust
+ *
+ * Copyright © 2017 CairusOrg
+ *
+ * This library is free software; you can redistribute it and/or
+ * modify it either under the terms of the GNU Lesser General Public
+ * License version 2.1 as published by the Free Software Foundation
+ * (the "LGPL") or, at your option, under the terms of the Mozilla
+ * Public License Version 2.0 (the "MPL"). If you do not alter this
+ * notice, a recipient may use your version of this file under either
+ * the MPL or the LGPL.
+ *
+ * You should have received a copy of the LGPL along with this library
+ * in the file LICENSE-LGPL-2_1; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Suite 500, Boston, MA 02110-1335, USA
+ * You should have received a copy of the MPL along with this library
+ * in the file LICENSE-MPL-2_0
+ *
+ * The contents of this file are subject to the Mozilla Public License
+ * Version 2.0 (the "License"); you may not use this file except in
+ * compliance with the License. You may obtain a copy of the License at
+ * http://www.mozilla.org/MPL/
+ *
+ * This software is distributed on an "AS IS" basis, WITHOUT WARRANTY
+ * OF ANY KIND, either express or implied. See the LGPL or the MPL for
+ * the specific language governing rights and limitations.
+ *
+ * The Original Code is the cairus graphics library.
+ *
+ * Contributor(s):
+ *	CairusOrg
+ *
+ */
+
+//! Loading TrueType fonts and reading their glyph outlines.
+//!
+//! A font file is a set of tables.  The ones read here are `head`, `maxp`, `hhea` and `hmtx`
+//! for the font's measurements, `cmap` for mapping characters to glyphs, and `loca` and `glyf`
+//! for the outlines.  Outlines are contours of quadratic Bézier curves, where two off-curve
+//! points in a row imply an on-curve point halfway between them.  They are converted to paths
+//! with `Path::quad_to`, which stores each quadratic as the cubic that traces it exactly.
+//!
+//! Variable fonts vary their outlines along design axes, such as weight and width, which `fvar`
+//! lists along with named instances, points in the design space such as "Bold".  A font is set
+//! to a point in that space with `Font::with_variations` or `Font::with_named_instance`.  Axis
+//! coordinates are normalized, remapped by `avar` if the font has one, and each glyph's points
+//! are moved by the deltas `gvar` holds for them, which also move its advance.
+//!
+//! OpenType fonts with CFF outlines are not supported.  Hinting instructions are ignored, as
+//! outlines are drawn unhinted, and so are `HVAR` and the other variation tables for metrics.
+
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::ops::Range;
+use std::path;
+use std::sync::Arc;
+use common_geometry::{Point, Rectangle};
+use matrix::Matrix;
+use path::Path;
+
+// How deeply composite glyphs may nest, which also stops glyphs that contain themselves
+const MAX_COMPONENT_DEPTH: u32 = 8;
+
+/// The error returned when a font can't be loaded.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InvalidFont {
+    /// What is wrong with the font.
+    pub reason: &'static str,
+}
+
+impl fmt::Display for InvalidFont {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid font: {}", self.reason)
+    }
+}
+
+impl Error for InvalidFont {}
+
+/// ## VariationAxis
+///
+/// An axis a variable font's design varies along, such as `wght` for its weight or `wdth` for
+/// its width, with the range of coordinates the font takes on it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VariationAxis {
+    /// The axis' four letter tag.
+    pub tag: [u8; 4],
+    /// The least coordinate on the axis.
+    pub min: f32,
+    /// The coordinate the font is drawn at unless another is set.
+    pub default: f32,
+    /// The greatest coordinate on the axis.
+    pub max: f32,
+}
+
+/// ## NamedInstance
+///
+/// A point in a variable font's design space that the font names, such as "Bold Condensed".
+#[derive(Debug, Clone, PartialEq)]
+pub struct NamedInstance {
+    /// The entry of the font's `name` table that names the instance.
+    pub name_id: u16,
+    /// The instance's coordinate on each of the font's axes, in order.
+    pub coordinates: Vec<f32>,
+}
+
+/// ## Font
+///
+/// A TrueType font loaded from the bytes of a `.ttf` file, or of the first font in a `.ttc`
+/// collection.  Measurements and outlines are in ems, with y pointing down and the origin on
+/// the baseline, like the rest of cairus' text.
+#[derive(Clone, PartialEq)]
+pub struct Font {
+    // Shared by every instance of a variable font
+    data: Arc<Vec<u8>>,
+    units_per_em: u16,
+    num_glyphs: u16,
+    long_offsets: bool,
+    loca: Range<usize>,
+    glyf: Range<usize>,
+    hmtx: Range<usize>,
+    num_metrics: u16,
+    // The offset and format of the cmap subtable characters are looked up in
+    cmap: Option<(usize, u16)>,
+    ascent: i16,
+    descent: i16,
+    // The variation tables, if the font is variable
+    fvar: Option<Range<usize>>,
+    gvar: Option<Range<usize>>,
+    avar: Option<Range<usize>>,
+    // The font's normalized coordinate on each axis, 0 for the default
+    coordinates: Vec<f32>,
+}
+
+impl Font {
+    /// Loads a font from the contents of a font file.
+    ///
+    /// # Usage
+    /// let font = Font::from_bytes(fs::read("DejaVuSans.ttf")?)?;
+    pub fn from_bytes(data: Vec<u8>) -> Result<Font, InvalidFont> {
+        let mut start = 0;
+        match read_u32(&data, 0) {
+            Some(0x0001_0000) | Some(0x7472_7565) => {} // 1.0 and "true"
+            Some(0x7474_6366) => {                    // "ttcf", a collection
+                start = read_u32(&data, 12).ok_or(truncated())? as usize;
+            }
+            Some(0x4f54_544f) => {                    // "OTTO"
+                return Err(InvalidFont { reason: "CFF outlines are not supported" });
+            }
+            _ => return Err(InvalidFont { reason: "not a TrueType font" }),
+        }
+
+        let table = |tag: &[u8; 4]| table_range(&data, start, tag);
+        let head = table(b"head")?;
+        let maxp = table(b"maxp")?;
+        let hhea = table(b"hhea")?;
+        let hmtx = table(b"hmtx")?;
+        let loca = table(b"loca")?;
+        let glyf = table(b"glyf")?;
+        let cmap = table(b"cmap").ok().and_then(|cmap| find_cmap_subtable(&data, cmap));
+
+        let field = |range: &Range<usize>, offset: usize| {
+            read_u16(&data[range.clone()], offset).ok_or(truncated())
+        };
+        let units_per_em = field(&head, 18)?;
+        if !(16..=16384).contains(&units_per_em) {
+            return Err(InvalidFont { reason: "units per em is out of range" });
+        }
+        let long_offsets = field(&head, 50)? != 0;
+        let num_glyphs = field(&maxp, 4)?;
+        let num_metrics = field(&hhea, 34)?;
+        if num_metrics == 0 || hmtx.len() < 4 * num_metrics as usize {
+            return Err(InvalidFont { reason: "horizontal metrics are missing" });
+        }
+        let offset_size = if long_offsets { 4 } else { 2 };
+        if loca.len() < offset_size * (num_glyphs as usize + 1) {
+            return Err(InvalidFont { reason: "glyph locations are missing" });
+        }
+        let ascent = field(&hhea, 4)? as i16;
+        let descent = field(&hhea, 6)? as i16;
+        let fvar = table(b"fvar").ok();
+        let gvar = table(b"gvar").ok();
+        let avar = table(b"avar").ok();
+        let num_axes = fvar.as_ref().and_then(|fvar| read_u16(&data[fvar.clone()], 8));
+
+        Ok(Font {
+            data: Arc::new(data),
+            units_per_em,
+            num_glyphs,
+            long_offsets,
+            loca,
+            glyf,
+            hmtx,
+            num_metrics,
+            cmap,
+            ascent,
+            descent,
+            fvar,
+            gvar,
+            avar,
+            coordinates: vec![0.; num_axes.unwrap_or(0) as usize],
+        })
+    }
+
+    /// Loads a font from the font file at `path`.
+    pub fn from_file(path: &path::Path) -> Result<Font, InvalidFont> {
+        let data = fs::read(path).map_err(|_| InvalidFont { reason: "file could not be read" })?;
+        Font::from_bytes(data)
+    }
+
+    /// Returns the number of font units to the em, which the font is designed on.
+    pub fn units_per_em(&self) -> u16 {
+        self.units_per_em
+    }
+
+    /// Returns the number of glyphs in the font.
+    pub fn num_glyphs(&self) -> u16 {
+        self.num_glyphs
+    }
+
+    /// Returns how far the font reaches above the baseline, in ems.
+    pub fn ascent(&self) -> f32 {
+        self.ascent as f32 / self.units_per_em as f32
+    }
+
+    /// Returns how far the font reaches below the baseline, in ems, as a positive distance.
+    pub fn descent(&self) -> f32 {
+        -(self.descent as f32) / self.units_per_em as f32
+    }
+
+    /// Returns the axes a variable font varies along, in the order its coordinates are given in,
+    /// or nothing for a font that doesn't vary.
+    pub fn variation_axes(&self) -> Vec<VariationAxis> {
+        let fvar = match self.fvar {
+            Some(ref fvar) => &self.data[fvar.clone()],
+            None => return Vec::new(),
+        };
+        let axes = read_u16(fvar, 4).unwrap_or(0) as usize;
+        let axis_size = read_u16(fvar, 10).unwrap_or(0) as usize;
+        (0..self.coordinates.len()).map_while(|index| {
+            let record = axes + axis_size * index;
+            let tag = fvar.get(record..record + 4)?;
+            Some(VariationAxis {
+                tag: [tag[0], tag[1], tag[2], tag[3]],
+                min: read_fixed(fvar, record + 4)?,
+                default: read_fixed(fvar, record + 8)?,
+                max: read_fixed(fvar, record + 12)?,
+            })
+        }).collect()
+    }
+
+    /// Returns the points in its design space a variable font names, or nothing for a font
+    /// that doesn't vary.
+    pub fn named_instances(&self) -> Vec<NamedInstance> {
+        let fvar = match self.fvar {
+            Some(ref fvar) => &self.data[fvar.clone()],
+            None => return Vec::new(),
+        };
+        let num_axes = self.coordinates.len();
+        let instances = read_u16(fvar, 4).unwrap_or(0) as usize
+            + read_u16(fvar, 10).unwrap_or(0) as usize * num_axes;
+        let num_instances = read_u16(fvar, 12).unwrap_or(0) as usize;
+        let instance_size = read_u16(fvar, 14).unwrap_or(0) as usize;
+        (0..num_instances).map_while(|index| {
+            let record = instances + instance_size * index;
+            let coordinates = (0..num_axes).map(|axis| read_fixed(fvar, record + 4 + 4 * axis))
+                                           .collect::<Option<Vec<f32>>>()?;
+            Some(NamedInstance { name_id: read_u16(fvar, record)?, coordinates })
+        }).collect()
+    }
+
+    /// Returns this font set to the coordinates in `variations` on the axes they are tagged
+    /// with, and to the default on the rest.
+    ///
+    /// Analogous to setting cairo_font_options_set_variations().  Coordinates are clamped to
+    /// their axis' range, and tags of axes the font doesn't have are ignored, so a font that
+    /// doesn't vary comes back as it was.  The font's data is shared with the font returned.
+    ///
+    /// # Usage
+    /// let bold = font.with_variations(&[(*b"wght", 700.), (*b"wdth", 90.)]);
+    pub fn with_variations(&self, variations: &[([u8; 4], f32)]) -> Font {
+        let coordinates = self.variation_axes().iter().map(|axis| {
+            // The last coordinate given for an axis wins
+            let value = variations.iter().rev()
+                                  .find(|&&(tag, _)| tag == axis.tag)
+                                  .map_or(axis.default, |&(_, value)| value);
+            let value = value.max(axis.min).min(axis.max);
+            if value < axis.default {
+                (value - axis.default) / (axis.default - axis.min)
+            } else if value > axis.default {
+                (value - axis.default) / (axis.max - axis.default)
+            } else {
+                0.
+            }
+        }).enumerate().map(|(axis, coordinate)| {
+            // Coordinates are kept to the precision the font's tables are in
+            (self.remap_coordinate(axis, coordinate) * 16384.).round() / 16384.
+        }).collect();
+        Font { coordinates, ..self.clone() }
+    }
+
+    /// Returns this font set to its named instance `index`, or None if it has no such instance.
+    ///
+    /// # Usage
+    /// let instance = font.with_named_instance(0).unwrap();
+    pub fn with_named_instance(&self, index: usize) -> Option<Font> {
+        let instance = self.named_instances().into_iter().nth(index)?;
+        let variations: Vec<([u8; 4], f32)> = self.variation_axes().iter()
+            .zip(instance.coordinates)
+            .map(|(axis, coordinate)| (axis.tag, coordinate))
+            .collect();
+        Some(self.with_variations(&variations))
+    }
+
+    /// Returns the glyph the font draws `character` with.  Characters the font doesn't have get
+    /// glyph 0, the font's "missing character" glyph.
+    pub fn glyph_index(&self, character: char) -> u16 {
+        let code = character as u32;
+        let (offset, format) = match self.cmap {
+            Some(cmap) => cmap,
+            None => return 0,
+        };
+        let glyph = match format {
+            4 => cmap_format_4(&self.data[offset..], code),
+            12 => cmap_format_12(&self.data[offset..], code),
+            _ => None,
+        };
+        glyph.filter(|&glyph| glyph < self.num_glyphs).unwrap_or(0)
+    }
+
+    /// Returns how far `glyph` moves the next glyph along, in ems.
+    pub fn glyph_advance(&self, glyph: u16) -> f32 {
+        // Glyphs past the last metric share its advance, which suits monospaced fonts
+        let index = glyph.min(self.num_metrics - 1) as usize;
+        let advance = read_u16(&self.data[self.hmtx.clone()], 4 * index).unwrap_or(0);
+        // Variations move the advance with the second of the phantom points after the glyph's
+        // own, relative to the first
+        let varied = self.glyph_point_count(glyph).and_then(|count| {
+            let deltas = self.glyph_deltas(glyph, &vec![Point::new(0., 0.); count + 4], &[])?;
+            Some(deltas[count + 1].x - deltas[count].x)
+        });
+        (advance as f32 + varied.unwrap_or(0.)) / self.units_per_em as f32
+    }
+
+    /// Returns the bounding box the font records for `glyph`, in ems, or None if the glyph has
+    /// no outline, like a space.
+    pub fn glyph_bounds(&self, glyph: u16) -> Option<Rectangle> {
+        let data = self.glyph_data(glyph)?;
+        let units = |offset| read_u16(data, offset).map(|value| value as i16 as f32);
+        let (x_min, y_min, x_max, y_max) = if self.is_varied() {
+            // The box recorded is the default instance's, so a varied glyph is measured
+            let mut contours = Vec::new();
+            self.glyph_contours(glyph, &Matrix::identity(), 0, &mut contours)?;
+            let mut points = contours.iter().flatten().map(|&(point, _)| point);
+            let first = points.next()?;
+            points.fold((first.x, first.y, first.x, first.y), |(left, top, right, bottom), point| {
+                (left.min(point.x), top.min(point.y), right.max(point.x), bottom.max(point.y))
+            })
+        } else {
+            (units(2)?, units(4)?, units(6)?, units(8)?)
+        };
+        let scale = 1. / self.units_per_em as f32;
+        Some(Rectangle::new(x_min * scale, -y_max * scale, (x_max - x_min) * scale,
+                            (y_max - y_min) * scale))
+    }
+
+    /// Returns the outline of `glyph` one em high, with its origin at the origin, to fill with
+    /// `FillRule::Winding`.  A glyph that can't be read has an empty outline.
+    pub fn glyph_path(&self, glyph: u16) -> Path {
+        let mut path = Path::new();
+        self.push_glyph(&mut path, glyph, Point::new(0., 0.));
+        path
+    }
+
+    /// Returns the outline of `text` one em high, with each character's glyph set after the one
+    /// before by its advance, starting from the origin.
+    pub fn text_path(&self, text: &str) -> Path {
+        let mut path = Path::new();
+        let mut pen = Point::new(0., 0.);
+        for character in text.chars() {
+            let glyph = self.glyph_index(character);
+            self.push_glyph(&mut path, glyph, pen);
+            pen.x += self.glyph_advance(glyph);
+        }
+        path
+    }
+
+    // Adds the contours of `glyph` to `path`, with its origin at `origin` in ems
+    fn push_glyph(&self, path: &mut Path, glyph: u16, origin: Point) {
+        let mut contours = Vec::new();
+        if self.glyph_contours(glyph, &Matrix::identity(), 0, &mut contours).is_none() {
+            return;
+        }
+        let scale = 1. / self.units_per_em as f32;
+        let to_ems = |point: Point| Point::new(origin.x + point.x * scale,
+                                                origin.y - point.y * scale);
+        for contour in contours {
+            let contour: Vec<(Point, bool)> = contour.into_iter()
+                .map(|(point, on_curve)| (to_ems(point), on_curve))
+                .collect();
+            push_contour(path, &contour);
+        }
+    }
+
+    // Returns the bytes of `glyph`'s entry in the glyf table, or None for a glyph without one
+    fn glyph_data(&self, glyph: u16) -> Option<&[u8]> {
+        if glyph >= self.num_glyphs {
+            return None;
+        }
+        let loca = &self.data[self.loca.clone()];
+        let index = glyph as usize;
+        let (start, end) = if self.long_offsets {
+            (read_u32(loca, 4 * index)? as usize, read_u32(loca, 4 * index + 4)? as usize)
+        } else {
+            (2 * read_u16(loca, 2 * index)? as usize, 2 * read_u16(loca, 2 * index + 2)? as usize)
+        };
+        // An empty entry is a glyph with no outline
+        if start >= end {
+            return None;
+        }
+        self.data[self.glyf.clone()].get(start..end)
+    }
+
+    // Adds the contours of `glyph`, in font units and transformed by `transform`, to
+    // `contours`.  Returns None if the glyph is malformed.
+    fn glyph_contours(&self, glyph: u16, transform: &Matrix, depth: u32,
+                      contours: &mut Vec<Vec<(Point, bool)>>) -> Option<()> {
+        let data = match self.glyph_data(glyph) {
+            Some(data) => data,
+            None => return Some(()),
+        };
+        let num_contours = read_u16(data, 0)? as i16;
+        if num_contours >= 0 {
+            let mut simple_contours = simple_glyph_contours(data, num_contours as usize)?;
+            self.vary_contours(glyph, &mut simple_contours);
+            for contour in simple_contours {
+                let contour = contour.into_iter()
+                    .map(|(point, on_curve)| (transform.transform_point(point), on_curve))
+                    .collect();
+                contours.push(contour);
+            }
+            return Some(());
+        }
+        if depth >= MAX_COMPONENT_DEPTH {
+            return None;
+        }
+
+        // A composite glyph places other glyphs, each with its own transformation.  Variations
+        // move each component's offset, followed by the four phantom points.
+        let components = composite_components(data)?;
+        let mut offsets: Vec<Point> = components.iter().map(|component| component.offset)
+                                                .collect();
+        offsets.extend(vec![Point::new(0., 0.); 4]);
+        let deltas = self.glyph_deltas(glyph, &offsets, &[]);
+        for (index, component) in components.iter().enumerate() {
+            let delta = deltas.as_ref().map_or(Point::new(0., 0.), |deltas| deltas[index]);
+            // Components placed by matching points rather than by offsets are left in place
+            let (dx, dy) = if component.by_offset {
+                (component.offset.x + delta.x, component.offset.y + delta.y)
+            } else {
+                (0., 0.)
+            };
+            let (xx, yx, xy, yy) = component.transform;
+            let component_transform = Matrix::new(xx, yx, xy, yy, dx, dy).multiply(transform);
+            self.glyph_contours(component.glyph, &component_transform, depth + 1, contours)?;
+        }
+        Some(())
+    }
+
+    // Returns the number of points of `glyph`, or of components for a composite glyph, which
+    // its variations give deltas for ahead of the phantom points.  Returns None if the glyph is
+    // malformed.
+    fn glyph_point_count(&self, glyph: u16) -> Option<usize> {
+        let data = match self.glyph_data(glyph) {
+            Some(data) => data,
+            None => return Some(0),
+        };
+        match read_u16(data, 0)? as i16 {
+            0 => Some(0),
+            num_contours if num_contours > 0 => {
+                Some(read_u16(data, 10 + 2 * (num_contours as usize - 1))? as usize + 1)
+            },
+            _ => Some(composite_components(data)?.len()),
+        }
+    }
+
+    // Returns true if the font is set away from its default instance
+    fn is_varied(&self) -> bool {
+        self.coordinates.iter().any(|&coordinate| coordinate != 0.)
+    }
+
+    // Returns the normalized `coordinate` on axis `axis` remapped by the font's avar table, or
+    // as it is without one
+    fn remap_coordinate(&self, axis: usize, coordinate: f32) -> f32 {
+        let avar = match self.avar {
+            Some(ref avar) => &self.data[avar.clone()],
+            None => return coordinate,
+        };
+        // Each axis' map is a count and then pairs of coordinates, from and to
+        let mut offset = 8;
+        for _ in 0..axis {
+            match read_u16(avar, offset) {
+                Some(count) => offset += 2 + 4 * count as usize,
+                None => return coordinate,
+            }
+        }
+        let count = read_u16(avar, offset).unwrap_or(0) as usize;
+        let pairs: Option<Vec<(f32, f32)>> = (0..count).map(|index| {
+            let pair = offset + 2 + 4 * index;
+            Some((read_f2dot14(avar, pair)?, read_f2dot14(avar, pair + 2)?))
+        }).collect();
+        let pairs = match pairs {
+            Some(ref pairs) if !pairs.is_empty() => pairs,
+            _ => return coordinate,
+        };
+        if coordinate <= pairs[0].0 {
+            return coordinate - pairs[0].0 + pairs[0].1;
+        }
+        for window in pairs.windows(2) {
+            let ((from1, to1), (from2, to2)) = (window[0], window[1]);
+            if coordinate <= from2 {
+                return if from1 == from2 {
+                    to1
+                } else {
+                    to1 + (coordinate - from1) / (from2 - from1) * (to2 - to1)
+                };
+            }
+        }
+        let (from, to) = pairs[pairs.len() - 1];
+        coordinate - from + to
+    }
+
+    // Moves the points of the simple glyph `glyph`'s contours by its variations
+    fn vary_contours(&self, glyph: u16, contours: &mut [Vec<(Point, bool)>]) {
+        if !self.is_varied() {
+            return;
+        }
+        let mut points: Vec<Point> = contours.iter().flatten().map(|&(point, _)| point).collect();
+        let ends: Vec<usize> = contours.iter().scan(0, |count, contour| {
+            *count += contour.len();
+            Some(*count - 1)
+        }).collect();
+        points.extend(vec![Point::new(0., 0.); 4]);
+        if let Some(deltas) = self.glyph_deltas(glyph, &points, &ends) {
+            for (point, delta) in contours.iter_mut().flatten().zip(deltas) {
+                point.0 = Point::new(point.0.x + delta.x, point.0.y + delta.y);
+            }
+        }
+    }
+
+    // Returns how far the font's variations move each of `points`, a glyph's points or
+    // component offsets followed by its four phantom points, in font units.  `ends` are the
+    // last points of the glyph's contours, between which points a variation leaves out are
+    // moved along with those it moves.  Returns None if the glyph doesn't vary, or its
+    // variations are malformed.
+    fn glyph_deltas(&self, glyph: u16, points: &[Point], ends: &[usize]) -> Option<Vec<Point>> {
+        if !self.is_varied() {
+            return None;
+        }
+        let gvar = &self.data[self.gvar.clone()?];
+        let num_axes = read_u16(gvar, 4)? as usize;
+        if num_axes != self.coordinates.len() || glyph >= read_u16(gvar, 12)? {
+            return None;
+        }
+        let shared_tuples = (read_u16(gvar, 6)? as usize, read_u32(gvar, 8)? as usize);
+        let array = read_u32(gvar, 16)? as usize;
+        let index = glyph as usize;
+        let (start, end) = if read_u16(gvar, 14)? & LONG_OFFSETS != 0 {
+            (read_u32(gvar, 20 + 4 * index)? as usize, read_u32(gvar, 24 + 4 * index)? as usize)
+        } else {
+            (2 * read_u16(gvar, 20 + 2 * index)? as usize,
+             2 * read_u16(gvar, 22 + 2 * index)? as usize)
+        };
+        if start >= end {
+            return None;
+        }
+        let data = gvar.get(array + start..array + end)?;
+
+        let count = read_u16(data, 0)?;
+        let mut serialized = read_u16(data, 2)? as usize;
+        // Points are numbered in runs, and None stands for every point
+        let mut shared_points = None;
+        if count & SHARED_POINT_NUMBERS != 0 {
+            let (numbers, next) = read_point_numbers(data, serialized)?;
+            shared_points = numbers;
+            serialized = next;
+        }
+
+        let mut deltas = vec![Point::new(0., 0.); points.len()];
+        let mut header = 4;
+        for _ in 0..count & TUPLE_COUNT_MASK {
+            let size = read_u16(data, header)? as usize;
+            let tuple_index = read_u16(data, header + 2)?;
+            header += 4;
+            let peak = if tuple_index & EMBEDDED_PEAK_TUPLE != 0 {
+                header += 2 * num_axes;
+                read_tuple(data, header - 2 * num_axes, num_axes)?
+            } else {
+                let (num_shared, shared_offset) = shared_tuples;
+                let shared = (tuple_index & TUPLE_INDEX_MASK) as usize;
+                if shared >= num_shared {
+                    return None;
+                }
+                read_tuple(gvar, shared_offset + 2 * num_axes * shared, num_axes)?
+            };
+            let intermediate = if tuple_index & INTERMEDIATE_REGION != 0 {
+                header += 4 * num_axes;
+                Some((read_tuple(data, header - 4 * num_axes, num_axes)?,
+                      read_tuple(data, header - 2 * num_axes, num_axes)?))
+            } else {
+                None
+            };
+            let tuple = data.get(serialized..serialized + size)?;
+            serialized += size;
+            let scalar = self.tuple_scalar(&peak, intermediate.as_ref());
+            if scalar == 0. {
+                continue;
+            }
+
+            let (numbers, offset) = if tuple_index & PRIVATE_POINT_NUMBERS != 0 {
+                read_point_numbers(tuple, 0)?
+            } else {
+                (shared_points.clone(), 0)
+            };
+            let count = numbers.as_ref().map_or(points.len(), |numbers| numbers.len());
+            let (xs, offset) = read_deltas(tuple, offset, count)?;
+            let (ys, _) = read_deltas(tuple, offset, count)?;
+            let mut tuple_deltas = vec![None; points.len()];
+            match numbers {
+                Some(numbers) => {
+                    for (index, number) in numbers.into_iter().enumerate() {
+                        if let Some(delta) = tuple_deltas.get_mut(number) {
+                            *delta = Some(Point::new(xs[index], ys[index]));
+                        }
+                    }
+                    infer_deltas(&mut tuple_deltas, points, ends);
+                },
+                None => {
+                    for (index, delta) in tuple_deltas.iter_mut().enumerate() {
+                        *delta = Some(Point::new(xs[index], ys[index]));
+                    }
+                },
+            }
+            for (delta, tuple_delta) in deltas.iter_mut().zip(tuple_deltas) {
+                if let Some(tuple_delta) = tuple_delta {
+                    delta.x += tuple_delta.x * scalar;
+                    delta.y += tuple_delta.y * scalar;
+                }
+            }
+        }
+        Some(deltas)
+    }
+
+    // Returns how much of a variation, at its `peak` and within the `intermediate` region it
+    // spans, applies at the font's coordinates
+    fn tuple_scalar(&self, peak: &[f32], intermediate: Option<&(Vec<f32>, Vec<f32>)>) -> f32 {
+        let mut scalar = 1.;
+        for (axis, (&peak, &coordinate)) in peak.iter().zip(&self.coordinates).enumerate() {
+            if peak == 0. {
+                continue;
+            }
+            if coordinate == 0. {
+                return 0.;
+            }
+            // Without an intermediate region a variation spans from the default to its peak
+            let (start, end) = match intermediate {
+                Some((starts, ends)) => (starts[axis], ends[axis]),
+                None => (peak.min(0.), peak.max(0.)),
+            };
+            // A region that is malformed, or crosses the default, doesn't limit the variation
+            if start > peak || peak > end || (start < 0. && end > 0.) {
+                continue;
+            }
+            if coordinate < start || coordinate > end {
+                return 0.;
+            }
+            if coordinate < peak {
+                scalar *= (coordinate - start) / (peak - start);
+            } else if coordinate > peak {
+                scalar *= (end - coordinate) / (end - peak);
+            }
+        }
+        scalar
+    }
+}
+
+impl fmt::Debug for Font {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Font")
+            .field("units_per_em", &self.units_per_em)
+            .field("num_glyphs", &self.num_glyphs)
+            .finish()
+    }
+}
+
+// Flags of a glyph's variations in gvar, and of each of its tuples of variations
+const LONG_OFFSETS: u16 = 0x0001;
+const SHARED_POINT_NUMBERS: u16 = 0x8000;
+const TUPLE_COUNT_MASK: u16 = 0x0fff;
+const EMBEDDED_PEAK_TUPLE: u16 = 0x8000;
+const INTERMEDIATE_REGION: u16 = 0x4000;
+const PRIVATE_POINT_NUMBERS: u16 = 0x2000;
+const TUPLE_INDEX_MASK: u16 = 0x0fff;
+
+// Flags of runs of packed point numbers and deltas
+const POINTS_ARE_WORDS: u8 = 0x80;
+const POINT_RUN_COUNT_MASK: u8 = 0x7f;
+const DELTAS_ARE_ZERO: u8 = 0x80;
+const DELTAS_ARE_WORDS: u8 = 0x40;
+const DELTA_RUN_COUNT_MASK: u8 = 0x3f;
+
+// Flags of a composite glyph's components
+const ARGS_ARE_WORDS: u16 = 0x0001;
+const ARGS_ARE_XY_VALUES: u16 = 0x0002;
+const WE_HAVE_A_SCALE: u16 = 0x0008;
+const MORE_COMPONENTS: u16 = 0x0020;
+const WE_HAVE_AN_X_AND_Y_SCALE: u16 = 0x0040;
+const WE_HAVE_A_TWO_BY_TWO: u16 = 0x0080;
+
+// Flags of a simple glyph's points
+const ON_CURVE: u8 = 0x01;
+const X_IS_BYTE: u8 = 0x02;
+const Y_IS_BYTE: u8 = 0x04;
+const REPEAT: u8 = 0x08;
+const X_SAME_OR_POSITIVE: u8 = 0x10;
+const Y_SAME_OR_POSITIVE: u8 = 0x20;
+
+// Returns the error for a file that ends before a table it describes
+fn truncated() -> InvalidFont {
+    InvalidFont { reason: "file is truncated" }
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    data.get(offset..offset + 2).map(|bytes| (bytes[0] as u16) << 8 | bytes[1] as u16)
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    Some((read_u16(data, offset)? as u32) << 16 | read_u16(data, offset + 2)? as u32)
+}
+
+// Reads a 16.16 fixed point number
+fn read_fixed(data: &[u8], offset: usize) -> Option<f32> {
+    read_u32(data, offset).map(|value| value as i32 as f32 / 65536.)
+}
+
+// Reads a 2.14 fixed point number
+fn read_f2dot14(data: &[u8], offset: usize) -> Option<f32> {
+    read_u16(data, offset).map(|value| value as i16 as f32 / 16384.)
+}
+
+// Reads a coordinate on each of `num_axes` axes
+fn read_tuple(data: &[u8], offset: usize, num_axes: usize) -> Option<Vec<f32>> {
+    (0..num_axes).map(|axis| read_f2dot14(data, offset + 2 * axis)).collect()
+}
+
+// Reads packed point numbers at `offset`, returning them, or None for every point, and the
+// offset past them
+fn read_point_numbers(data: &[u8], offset: usize) -> Option<(Option<Vec<usize>>, usize)> {
+    let first = *data.get(offset)? as usize;
+    let mut offset = offset + 1;
+    if first == 0 {
+        return Some((None, offset));
+    }
+    let count = if first & POINTS_ARE_WORDS as usize != 0 {
+        offset += 1;
+        (first & POINT_RUN_COUNT_MASK as usize) << 8 | *data.get(offset - 1)? as usize
+    } else {
+        first
+    };
+    // Each number is stored as the step from the one before
+    let mut numbers = Vec::with_capacity(count);
+    let mut number = 0;
+    while numbers.len() < count {
+        let control = *data.get(offset)?;
+        offset += 1;
+        for _ in 0..(control & POINT_RUN_COUNT_MASK) as usize + 1 {
+            number += if control & POINTS_ARE_WORDS != 0 {
+                offset += 2;
+                read_u16(data, offset - 2)? as usize
+            } else {
+                offset += 1;
+                *data.get(offset - 1)? as usize
+            };
+            numbers.push(number);
+        }
+    }
+    numbers.truncate(count);
+    Some((Some(numbers), offset))
+}
+
+// Reads `count` packed deltas at `offset`, returning them and the offset past them
+fn read_deltas(data: &[u8], offset: usize, count: usize) -> Option<(Vec<f32>, usize)> {
+    let mut offset = offset;
+    let mut deltas = Vec::with_capacity(count);
+    while deltas.len() < count {
+        let control = *data.get(offset)?;
+        offset += 1;
+        for _ in 0..(control & DELTA_RUN_COUNT_MASK) as usize + 1 {
+            let delta = if control & DELTAS_ARE_ZERO != 0 {
+                0.
+            } else if control & DELTAS_ARE_WORDS != 0 {
+                offset += 2;
+                read_u16(data, offset - 2)? as i16 as f32
+            } else {
+                offset += 1;
+                *data.get(offset - 1)? as i8 as f32
+            };
+            deltas.push(delta);
+        }
+    }
+    deltas.truncate(count);
+    Some((deltas, offset))
+}
+
+// Fills in the deltas of the points of each contour ending at `ends` that a variation leaves
+// out, from the nearest points before and after it on the contour that the variation moves.
+// Along each axis, a point between those two moves in proportion, and a point beyond them
+// moves with the nearer.  A contour the variation doesn't touch stays put.
+fn infer_deltas(deltas: &mut [Option<Point>], points: &[Point], ends: &[usize]) {
+    let interpolate = |coordinate: f32, in1: f32, in2: f32, delta1: f32, delta2: f32| {
+        if in1 == in2 {
+            return if delta1 == delta2 { delta1 } else { 0. };
+        }
+        let (in1, delta1, in2, delta2) = if in1 < in2 {
+            (in1, delta1, in2, delta2)
+        } else {
+            (in2, delta2, in1, delta1)
+        };
+        if coordinate <= in1 {
+            delta1
+        } else if coordinate >= in2 {
+            delta2
+        } else {
+            delta1 + (coordinate - in1) / (in2 - in1) * (delta2 - delta1)
+        }
+    };
+
+    let mut start = 0;
+    for &end in ends {
+        if end < start || end >= deltas.len() {
+            return;
+        }
+        let touched: Vec<usize> = (start..=end).filter(|&index| deltas[index].is_some())
+                                              .collect();
+        if let (Some(&first), Some(&last)) = (touched.first(), touched.last()) {
+            for index in start..=end {
+                if touched.binary_search(&index).is_ok() {
+                    continue;
+                }
+                let next = touched.iter().cloned().find(|&other| other > index).unwrap_or(first);
+                let previous = touched.iter().rev().cloned().find(|&other| other < index)
+                                      .unwrap_or(last);
+                let (delta1, delta2) = (deltas[previous].unwrap(), deltas[next].unwrap());
+                let (point, point1, point2) = (points[index], points[previous], points[next]);
+                deltas[index] = Some(Point::new(
+                    interpolate(point.x, point1.x, point2.x, delta1.x, delta2.x),
+                    interpolate(point.y, point1.y, point2.y, delta1.y, delta2.y)));
+            }
+        }
+        start = end + 1;
+    }
+}
+
+// A component of a composite glyph: the glyph it places, its offset in font units, whether it
+// is placed by that offset rather than by matching points, and its 2x2 transformation
+struct Component {
+    glyph: u16,
+    offset: Point,
+    by_offset: bool,
+    transform: (f32, f32, f32, f32),
+}
+
+// Returns the components of the composite glyph in `data`
+fn composite_components(data: &[u8]) -> Option<Vec<Component>> {
+    let word = |offset| read_u16(data, offset).map(|value| value as i16 as f32);
+    let f2dot14 = |offset| read_f2dot14(data, offset);
+    let mut components = Vec::new();
+    let mut offset = 10;
+    loop {
+        let flags = read_u16(data, offset)?;
+        let glyph = read_u16(data, offset + 2)?;
+        offset += 4;
+        let (dx, dy) = if flags & ARGS_ARE_WORDS != 0 {
+            offset += 4;
+            (word(offset - 4)?, word(offset - 2)?)
+        } else {
+            offset += 2;
+            (*data.get(offset - 2)? as i8 as f32, *data.get(offset - 1)? as i8 as f32)
+        };
+        let transform = if flags & WE_HAVE_A_SCALE != 0 {
+            offset += 2;
+            let scale = f2dot14(offset - 2)?;
+            (scale, 0., 0., scale)
+        } else if flags & WE_HAVE_AN_X_AND_Y_SCALE != 0 {
+            offset += 4;
+            (f2dot14(offset - 4)?, 0., 0., f2dot14(offset - 2)?)
+        } else if flags & WE_HAVE_A_TWO_BY_TWO != 0 {
+            offset += 8;
+            (f2dot14(offset - 8)?, f2dot14(offset - 6)?, f2dot14(offset - 4)?,
+             f2dot14(offset - 2)?)
+        } else {
+            (1., 0., 0., 1.)
+        };
+        components.push(Component {
+            glyph,
+            offset: Point::new(dx, dy),
+            by_offset: flags & ARGS_ARE_XY_VALUES != 0,
+            transform,
+        });
+        if flags & MORE_COMPONENTS == 0 {
+            return Some(components);
+        }
+    }
+}
+
+// Returns where the table tagged `tag` lies in `data`, looking in the table directory at `start`
+fn table_range(data: &[u8], start: usize, tag: &[u8; 4]) -> Result<Range<usize>, InvalidFont> {
+    let num_tables = read_u16(data, start + 4).ok_or(truncated())? as usize;
+    for index in 0..num_tables {
+        let record = start + 12 + 16 * index;
+        if data.get(record..record + 4).ok_or(truncated())? != tag {
+            continue;
+        }
+        let offset = read_u32(data, record + 8).ok_or(truncated())? as usize;
+        let length = read_u32(data, record + 12).ok_or(truncated())? as usize;
+        return match offset.checked_add(length) {
+            Some(end) if end <= data.len() => Ok(offset..end),
+            _ => Err(truncated()),
+        };
+    }
+    Err(InvalidFont { reason: "a required table is missing" })
+}
+
+// Returns the offset and format of the best Unicode subtable in the cmap table at `cmap`.  A
+// full-repertoire subtable is preferred over one limited to the Basic Multilingual Plane.
+fn find_cmap_subtable(data: &[u8], cmap: Range<usize>) -> Option<(usize, u16)> {
+    let table = &data[cmap.clone()];
+    let mut best = None;
+    for index in 0..read_u16(table, 2)? as usize {
+        let record = 4 + 8 * index;
+        let platform = read_u16(table, record)?;
+        let encoding = read_u16(table, record + 2)?;
+        let offset = read_u32(table, record + 4)? as usize;
+        let unicode = platform == 0 || (platform == 3 && (encoding == 1 || encoding == 10));
+        let format = read_u16(table, offset);
+        match format {
+            Some(12) if unicode => return Some((cmap.start + offset, 12)),
+            Some(4) if unicode && best.is_none() => best = Some((cmap.start + offset, 4)),
+            _ => {}
+        }
+    }
+    best
+}
+
+// Looks `code` up in a format 4 cmap subtable, which maps ranges of the BMP
+fn cmap_format_4(table: &[u8], code: u32) -> Option<u16> {
+    if code > 0xffff {
+        return None;
+    }
+    let code = code as u16;
+    let segments = read_u16(table, 6)? as usize / 2;
+    let ends = 14;
+    let starts = ends + 2 * segments + 2;
+    let deltas = starts + 2 * segments;
+    let range_offsets = deltas + 2 * segments;
+    for segment in 0..segments {
+        if read_u16(table, ends + 2 * segment)? < code {
+            continue;
+        }
+        let start = read_u16(table, starts + 2 * segment)?;
+        if start > code {
+            return None;
+        }
+        let delta = read_u16(table, deltas + 2 * segment)?;
+        let range_offset = read_u16(table, range_offsets + 2 * segment)? as usize;
+        if range_offset == 0 {
+            return Some(code.wrapping_add(delta));
+        }
+        // The range offset is relative to where it is stored, and points into the glyph ids
+        let position = range_offsets + 2 * segment + range_offset + 2 * (code - start) as usize;
+        let glyph = read_u16(table, position)?;
+        return if glyph == 0 { None } else { Some(glyph.wrapping_add(delta)) };
+    }
+    None
+}
+
+// Looks `code` up in a format 12 cmap subtable, which maps ranges of all of Unicode
+fn cmap_format_12(table: &[u8], code: u32) -> Option<u16> {
+    let groups = read_u32(table, 12)? as usize;
+    for group in 0..groups {
+        let record = 16 + 12 * group;
+        let start = read_u32(table, record)?;
+        let end = read_u32(table, record + 4)?;
+        if start <= code && code <= end {
+            return Some((read_u32(table, record + 8)? + code - start) as u16);
+        }
+    }
+    None
+}
+
+// Returns the contours of the simple glyph in `data`, as points in font units each flagged
+// with whether it is on the curve
+fn simple_glyph_contours(data: &[u8], num_contours: usize) -> Option<Vec<Vec<(Point, bool)>>> {
+    let mut ends = Vec::with_capacity(num_contours);
+    for index in 0..num_contours {
+        ends.push(read_u16(data, 10 + 2 * index)? as usize);
+    }
+    let num_points = match ends.last() {
+        Some(&last) => last + 1,
+        None => return Some(Vec::new()),
+    };
+    let instructions = read_u16(data, 10 + 2 * num_contours)? as usize;
+    let mut offset = 12 + 2 * num_contours + instructions;
+
+    let mut flags = Vec::with_capacity(num_points);
+    while flags.len() < num_points {
+        let flag = *data.get(offset)?;
+        offset += 1;
+        flags.push(flag);
+        if flag & REPEAT != 0 {
+            let repeats = *data.get(offset)?;
+            offset += 1;
+            flags.extend((0..repeats).map(|_| flag));
+        }
+    }
+    flags.truncate(num_points);
+
+    // Coordinates are deltas from the previous point, each a byte with its sign in the flags,
+    // a word, or left out when it repeats the previous coordinate
+    let mut read_coordinates = |is_byte: u8, same_or_positive: u8| {
+        let mut value = 0i32;
+        let mut coordinates = Vec::with_capacity(num_points);
+        for &flag in &flags {
+            if flag & is_byte != 0 {
+                let delta = *data.get(offset)? as i32;
+                offset += 1;
+                value += if flag & same_or_positive != 0 { delta } else { -delta };
+            } else if flag & same_or_positive == 0 {
+                value += read_u16(data, offset)? as i16 as i32;
+                offset += 2;
+            }
+            coordinates.push(value as f32);
+        }
+        Some(coordinates)
+    };
+    let xs = read_coordinates(X_IS_BYTE, X_SAME_OR_POSITIVE)?;
+    let ys = read_coordinates(Y_IS_BYTE, Y_SAME_OR_POSITIVE)?;
+
+    let mut contours = Vec::with_capacity(num_contours);
+    let mut start = 0;
+    for end in ends {
+        if end < start || end >= num_points {
+            return None;
+        }
+        contours.push((start..=end).map(|index| {
+            (Point::new(xs[index], ys[index]), flags[index] & ON_CURVE != 0)
+        }).collect());
+        start = end + 1;
+    }
+    Some(contours)
+}
+
+// Adds a closed contour of on- and off-curve points to `path`.  Between two off-curve points
+// lies an implied on-curve point, halfway between them.
+fn push_contour(path: &mut Path, contour: &[(Point, bool)]) {
+    let midpoint = |a: Point, b: Point| Point::new((a.x + b.x) / 2., (a.y + b.y) / 2.);
+    let (first, first_on) = match contour.first() {
+        Some(&first) => first,
+        None => return,
+    };
+    let (last, last_on) = contour[contour.len() - 1];
+    // The contour starts from an on-curve point, taking the last one or an implied one if the
+    // first point is off the curve
+    let (start, rest) = if first_on {
+        (first, &contour[1..])
+    } else if last_on {
+        (last, &contour[..contour.len() - 1])
+    } else {
+        (midpoint(last, first), contour)
+    };
+
+    path.move_to(start.x, start.y);
+    let mut control: Option<Point> = None;
+    for &(point, on_curve) in rest {
+        match (control, on_curve) {
+            (Some(previous), true) => {
+                path.quad_to(previous.x, previous.y, point.x, point.y);
+                control = None;
+            }
+            (None, true) => {
+                path.line_to(point.x, point.y);
+            }
+            (Some(previous), false) => {
+                let implied = midpoint(previous, point);
+                path.quad_to(previous.x, previous.y, implied.x, implied.y);
+                control = Some(point);
+            }
+            (None, false) => control = Some(point),
+        }
+    }
+    if let Some(previous) = control {
+        path.quad_to(previous.x, previous.y, start.x, start.y);
+    }
+    path.close_path();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Font, InvalidFont, NamedInstance, VariationAxis};
+    use common_geometry::{Point, Rectangle};
+    use context::Context;
+    use path::PathElement;
+    use surfaces::{Format, ImageSurface};
+    use text::FontFace;
+
+    fn words(values: &[i32]) -> Vec<u8> {
+        values.iter().flat_map(|&value| vec![(value >> 8) as u8, value as u8]).collect()
+    }
+
+    // Returns a font of four glyphs on 1000 units to the em: an empty .notdef, a square of
+    // on-curve points for 'A', a diamond of off-curve points for 'B', and for 'C' a composite of
+    // the square moved up and the diamond at half size
+    fn test_font() -> Vec<u8> {
+        font_with_tables(Vec::new())
+    }
+
+    // Returns test_font() varying in weight from 100 to 900, with 400 the default and 900 named
+    // "Black".  Towards 900, 'A''s square grows 50 units left and right and 100 up, and its
+    // advance 100.  Towards 100, its corners at (100, 0) and (400, 300) move 20 units inwards,
+    // and the corners between them go with them.  Towards 900, 'C''s square moves 100 right.
+    fn variable_test_font() -> Vec<u8> {
+        let fixed = |value: i32| words(&[value, 0]);
+        let fvar = [words(&[1, 0, 16, 2, 1, 20, 1, 8]), b"wght".to_vec(), fixed(100),
+                    fixed(400), fixed(900), words(&[0, 256, 257, 0]), fixed(900)].concat();
+        // Each of the glyphs' tuples of deltas has its own peak and point numbers
+        let square = [words(&[2, 16, 35, 0xa000, 16384, 14, 0xa000, -16384]),
+                      vec![0, 0x47], words(&[-50, 50, 50, -50, 0, 100, 0, 0]),
+                      vec![0x47], words(&[0, 0, 100, 100, 0, 0, 0, 0]),
+                      vec![2, 0x01, 0, 2, 0x41], words(&[20, -20]), vec![0x41],
+                      words(&[20, -20]), vec![0]].concat();
+        let composite = [words(&[1, 10, 15, 0xa000, 16384]), vec![0, 0x45],
+                         words(&[100, 0, 0, 0, 0, 0]), vec![0x85, 0]].concat();
+        let offsets = words(&[0, 0, square.len() as i32 / 2, square.len() as i32 / 2,
+                              (square.len() + composite.len()) as i32 / 2]);
+        let gvar = [words(&[1, 0, 1, 0, 0, 20, 4, 0, 0, 30]), offsets, square, composite]
+                   .concat();
+        font_with_tables(vec![(b"fvar", fvar), (b"gvar", gvar)])
+    }
+
+    // Returns test_font() with `extra` tables
+    fn font_with_tables(extra: Vec<(&[u8; 4], Vec<u8>)>) -> Vec<u8> {
+        let mut head = vec![0; 54];
+        head[18..20].copy_from_slice(&words(&[1000]));
+        let maxp = words(&[1, 0, 4]);
+        let mut hhea = vec![0; 36];
+        hhea[4..8].copy_from_slice(&words(&[800, -200]));
+        hhea[34..36].copy_from_slice(&words(&[2]));
+        let hmtx = words(&[500, 0, 600, 0]);
+        // One segment maps 'A' to 'C' onto glyphs 1 to 3, and the last ends the table
+        let cmap = [words(&[0, 1, 3, 1]), vec![0, 0, 0, 12], words(&[4, 32, 0, 4, 0, 0, 0]),
+                    words(&[0x43, 0xffff, 0, 0x41, 0xffff, -0x40, 1, 0, 0])].concat();
+        let square = [words(&[1, 100, 0, 400, 300, 3, 0]), vec![0x09, 3],
+                      words(&[100, 300, 0, -300, 0, 0, 300, 0])].concat();
+        let diamond = [words(&[1, 0, 0, 500, 500, 3, 0]), vec![0x32, 0x36, 0x26, 0x06],
+                       vec![250, 250, 250, 250, 250, 250, 250, 0]].concat();
+        let composite = words(&[-1, 0, 0, 400, 700, 0x23, 1, 0, 400, 0x0a, 2, 0x0a14, 0x2000]);
+        let glyf = [square.clone(), diamond.clone(), composite.clone()].concat();
+        let ends = [0, square.len(), square.len() + diamond.len(), glyf.len()];
+        let loca = words(&[0, ends[0] as i32 / 2, ends[1] as i32 / 2, ends[2] as i32 / 2,
+                           ends[3] as i32 / 2]);
+
+        let mut tables: Vec<(&[u8; 4], Vec<u8>)> = vec![(b"cmap", cmap), (b"glyf", glyf),
+                                                       (b"head", head), (b"hhea", hhea),
+                                                       (b"hmtx", hmtx), (b"loca", loca),
+                                                       (b"maxp", maxp)];
+        tables.extend(extra);
+        let mut font = [words(&[1, 0, tables.len() as i32]), vec![0; 6]].concat();
+        let mut offset = 12 + 16 * tables.len();
+        for &(tag, ref table) in &tables {
+            font.extend_from_slice(tag);
+            let (start, length) = (offset as i32, table.len() as i32);
+            font.extend(words(&[0, 0, start >> 16, start, length >> 16, length]));
+            offset += table.len();
+        }
+        for (_, table) in tables {
+            font.extend(table);
+        }
+        font
+    }
+
+    #[test]
+    fn font_metrics() {
+        // Call
+        let font = Font::from_bytes(test_font()).unwrap();
+
+        // Test
+        assert_eq!(font.units_per_em(), 1000);
+        assert_eq!(font.num_glyphs(), 4);
+        assert_eq!(font.ascent(), 0.8);
+        assert_eq!(font.descent(), 0.2);
+        assert_eq!(font.glyph_advance(0), 0.5);
+        assert_eq!(font.glyph_advance(1), 0.6);
+        // Glyphs past the last metric share its advance
+        assert_eq!(font.glyph_advance(3), 0.6);
+    }
+
+    #[test]
+    fn glyph_index() {
+        let font = Font::from_bytes(test_font()).unwrap();
+        assert_eq!(font.glyph_index('A'), 1);
+        assert_eq!(font.glyph_index('C'), 3);
+        assert_eq!(font.glyph_index('D'), 0);
+        assert_eq!(font.glyph_index('@'), 0);
+        assert_eq!(font.glyph_index('\u{1f600}'), 0);
+    }
+
+    #[test]
+    fn glyph_path_of_lines() {
+        // Setup
+        let font = Font::from_bytes(test_font()).unwrap();
+
+        // Call
+        let path = font.glyph_path(1);
+
+        // Test
+        assert_eq!(path.elements(), &[PathElement::MoveTo(Point::new(0.1, 0.)),
+                                      PathElement::LineTo(Point::new(0.4, 0.)),
+                                      PathElement::LineTo(Point::new(0.4, -0.3)),
+                                      PathElement::LineTo(Point::new(0.1, -0.3)),
+                                      PathElement::ClosePath]);
+        assert_eq!(font.glyph_bounds(1), Some(Rectangle::new(0.1, -0.3, 0.3, 0.3)));
+        assert!(font.glyph_path(0).is_empty());
+        assert_eq!(font.glyph_bounds(0), None);
+    }
+
+    #[test]
+    fn glyph_path_of_implied_points() {
+        // Setup
+        let font = Font::from_bytes(test_font()).unwrap();
+
+        // Call
+        let path = font.glyph_path(2);
+
+        // Test
+        // With every point off the curve, the curves meet halfway between them
+        let elements = path.elements();
+        assert_eq!(elements.len(), 6);
+        assert_eq!(elements[0], PathElement::MoveTo(Point::new(0.125, -0.125)));
+        match elements[1] {
+            PathElement::CurveTo(control1, control2, end) => {
+                // The quadratic through (0.25, 0) is raised to a cubic exactly
+                let close = |point: Point, x: f32, y: f32| {
+                    (point.x - x).abs() < 1e-6 && (point.y - y).abs() < 1e-6
+                };
+                assert!(close(control1, 0.125 + 0.25 / 3., -0.125 / 3.));
+                assert!(close(control2, 0.375 - 0.25 / 3., -0.125 / 3.));
+                assert_eq!(end, Point::new(0.375, -0.125));
+            },
+            _ => panic!("expected a curve"),
+        }
+        assert_eq!(path.current_point(), Some(Point::new(0.125, -0.125)));
+    }
+
+    #[test]
+    fn glyph_path_of_composite() {
+        // Setup
+        let font = Font::from_bytes(test_font()).unwrap();
+
+        // Call
+        let path = font.glyph_path(3);
+
+        // Test
+        let starts: Vec<Point> = path.iter().filter_map(|element| match *element {
+            PathElement::MoveTo(point) => Some(point),
+            _ => None,
+        }).collect();
+        assert_eq!(starts.len(), 2);
+        assert!((starts[0].x - 0.1).abs() < 1e-6 && (starts[0].y + 0.4).abs() < 1e-6);
+        // The diamond is halved, then moved by (10, 20) units
+        assert!((starts[1].x - 0.0725).abs() < 1e-6 && (starts[1].y + 0.0825).abs() < 1e-6);
+    }
+
+    #[test]
+    fn invalid_fonts() {
+        let reason = |data: Vec<u8>| Font::from_bytes(data).unwrap_err().reason;
+        assert_eq!(reason(Vec::new()), "not a TrueType font");
+        assert_eq!(reason(b"OTTO\0\0\0\0".to_vec()), "CFF outlines are not supported");
+        assert_eq!(reason(test_font()[..60].to_vec()), "file is truncated");
+        let mut missing = test_font();
+        missing[12..16].copy_from_slice(b"cmaq");
+        assert!(Font::from_bytes(missing).is_ok());
+        let mut missing = test_font();
+        missing[28..32].copy_from_slice(b"glyg");
+        assert_eq!(reason(missing), "a required table is missing");
+        assert_eq!(InvalidFont { reason: "file is truncated" }.to_string(),
+                   "invalid font: file is truncated");
+    }
+
+    #[test]
+    fn font_face_from_font() {
+        // Setup
+        let face = FontFace::from_font(Font::from_bytes(test_font()).unwrap());
+        let mut target = ImageSurface::create_for_format(Format::ARGB32, 100, 120);
+
+        // Call
+        let extents = face.text_extents("AB");
+        {
+            let mut context = Context::create(&mut target);
+            context.set_source_rgba(0., 0., 0., 1.);
+            context.set_font_face(face.clone());
+            context.set_font_size(100.);
+            context.move_to(0., 100.);
+            context.show_text("A");
+            let point = context.get_current_point().unwrap();
+            assert!((point.x - 60.).abs() < 1e-4 && point.y == 100.);
+        }
+
+        // Test
+        assert_eq!(face.get_font().map(|font| font.num_glyphs()), Some(4));
+        assert!((extents.x_bearing - 0.1).abs() < 1e-6);
+        assert!((extents.y_bearing + 0.5).abs() < 1e-6);
+        assert!((extents.width - 1.).abs() < 1e-6);
+        assert!((extents.height - 0.5).abs() < 1e-6);
+        assert!((extents.x_advance - 1.2).abs() < 1e-6);
+        // The square covers (10, 70) to (40, 100)
+        assert_eq!(target.get(25, 85).unwrap().alpha, 1.);
+        assert_eq!(target.get(5, 85).unwrap().alpha, 0.);
+        assert_eq!(target.get(25, 65).unwrap().alpha, 0.);
+    }
+
+    #[test]
+    fn variation_axes_and_instances() {
+        // Setup
+        let font = Font::from_bytes(variable_test_font()).unwrap();
+
+        // Call
+        let axes = font.variation_axes();
+        let instances = font.named_instances();
+
+        // Test
+        assert_eq!(axes, vec![VariationAxis { tag: *b"wght", min: 100., default: 400.,
+                                              max: 900. }]);
+        assert_eq!(instances, vec![NamedInstance { name_id: 257, coordinates: vec![900.] }]);
+        let plain = Font::from_bytes(test_font()).unwrap();
+        assert!(plain.variation_axes().is_empty() && plain.named_instances().is_empty());
+        assert_eq!(plain.with_variations(&[(*b"wght", 900.)]), plain);
+    }
+
+    #[test]
+    fn glyph_path_varies() {
+        // Setup
+        let font = Font::from_bytes(variable_test_font()).unwrap();
+        let corners = |font: &Font| -> Vec<Point> {
+            font.glyph_path(1).iter().filter_map(|element| match *element {
+                PathElement::MoveTo(point) | PathElement::LineTo(point) => {
+                    // Rounded to whole units, as deltas are
+                    Some(Point::new((point.x * 1000.).round(), (point.y * 1000.).round()))
+                },
+                _ => None,
+            }).collect()
+        };
+
+        // Call
+        let black = font.with_variations(&[(*b"wght", 900.)]);
+        let semibold = font.with_variations(&[(*b"wght", 650.)]);
+        let thin = font.with_variations(&[(*b"wght", 100.)]);
+        let clamped = font.with_variations(&[(*b"wght", 2000.), (*b"wdth", 50.)]);
+
+        // Test
+        assert_eq!(corners(&font), corners(&font.with_variations(&[])));
+        assert_eq!(corners(&black), vec![Point::new(50., 0.), Point::new(450., 0.),
+                                         Point::new(450., -400.), Point::new(50., -400.)]);
+        assert_eq!(corners(&semibold), vec![Point::new(75., 0.), Point::new(425., 0.),
+                                            Point::new(425., -350.), Point::new(75., -350.)]);
+        // The corners thin leaves out move with the ones it moves
+        assert_eq!(corners(&thin), vec![Point::new(120., -20.), Point::new(380., -20.),
+                                        Point::new(380., -280.), Point::new(120., -280.)]);
+        assert_eq!(corners(&clamped), corners(&black));
+        assert_eq!(corners(&font.with_named_instance(0).unwrap()), corners(&black));
+        assert!(font.with_named_instance(1).is_none());
+        assert!((black.glyph_advance(1) - 0.7).abs() < 1e-6);
+        assert!((semibold.glyph_advance(1) - 0.65).abs() < 1e-6);
+        let bounds = black.glyph_bounds(1).unwrap();
+        assert!((bounds.x - 0.05).abs() < 1e-6 && (bounds.width - 0.4).abs() < 1e-6);
+        assert!((bounds.y + 0.4).abs() < 1e-6 && (bounds.height - 0.4).abs() < 1e-6);
+        // The composite's square is moved as a whole
+        let start = match black.glyph_path(3).elements()[0] {
+            PathElement::MoveTo(point) => point,
+            _ => panic!("expected a move"),
+        };
+        assert!((start.x - 0.15).abs() < 1e-6 && (start.y + 0.4).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,420 @@
+/*
+ * Cairus - a reimplementation of the cairo graphics library in Rust
+ *
+ * Copyright © 2017 CairusOrg
+ *
+ * This library is free software; you can redistribute it and/or
+ * modify it either under the terms of the GNU Lesser General Public
+ * License version 2.1 as published by the Free Software Foundation
+ * (the "LGPL") or, at your option, under the terms of the Mozilla
+ * Public License Version 2.0 (the "MPL"). If you do not alter this
+ * notice, a recipient may use your version of this file under either
+ * the MPL or the LGPL.
+ *
+ * You should have received a copy of the LGPL along with this library
+ * in the file LICENSE-LGPL-2_1; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Suite 500, Boston, MA 02110-1335, USA
+ * You should have received a copy of the MPL along with this library
+ * in the file LICENSE-MPL-2_0
+ *
+ * The contents of this file are subject to the Mozilla Public License
+ * Version 2.0 (the "License"); you may not use this file except in
+ * compliance with the License. You may obtain a copy of the License at
+ * http://www.mozilla.org/MPL/
+ *
+ * This software is distributed on an "AS IS" basis, WITHOUT WARRANTY
+ * OF ANY KIND, either express or implied. See the LGPL or the MPL for
+ * the specific language governing rights and limitations.
+ *
+ * The Original Code is the cairus graphics library.
+ *
+ * Contributor(s):
+ *	CairusOrg
+ *
+ */
+
+//! A cache of glyphs rasterized into coverage masks.
+//!
+//! Text drawn on a raster surface would otherwise have every glyph's outline flattened and
+//! rasterized each time it is drawn.  Instead each glyph is rasterized once for its face, its
+//! scale and its subpixel phase, and the mask is kept in a cache that every Context shares, to
+//! be composited wherever the glyph appears again.  Cairo keeps glyphs in a similar cache for
+//! each scaled font.
+//!
+//! A glyph's origin is rounded to the nearest of `SUBPIXEL_PHASES` positions across a pixel in
+//! each direction, its phase, so a glyph has at most sixteen masks at each scale.  Cached
+//! glyphs are rasterized to within the default tolerance.  Only text that is scaled and moved,
+//! not rotated, skewed or flipped, can be drawn from the cache.
+
+use std::collections::{HashMap, VecDeque};
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex, OnceLock};
+use common_geometry::{Edge, Point};
+use context::DEFAULT_TOLERANCE;
+use font::Font;
+use matrix::Matrix;
+use path::Path;
+use surfaces::CoverageMask;
+use text::FontFace;
+use trapezoid_rasterizer::mask_from_edges;
+use types::{FillRule, FontSlant, FontWeight};
+
+/// The number of positions across a pixel a glyph's origin is rounded to, in each direction.
+pub const SUBPIXEL_PHASES: u8 = 4;
+
+/// The bytes of coverage the shared cache holds before it evicts glyphs.
+pub const DEFAULT_CAPACITY: usize = 4 << 20;
+
+// Identifies a face's glyphs.  Loaded fonts are compared by identity, and the key holds on to
+// its font so another font can't be loaded in its place while its glyphs are cached.
+#[derive(Debug, Clone)]
+enum FaceKey {
+    Builtin(FontSlant, FontWeight),
+    Font(Arc<Font>),
+}
+
+impl PartialEq for FaceKey {
+    fn eq(&self, other: &FaceKey) -> bool {
+        match (self, other) {
+            (FaceKey::Builtin(slant, weight), FaceKey::Builtin(other_slant, other_weight)) => {
+                slant == other_slant && weight == other_weight
+            },
+            (FaceKey::Font(font), FaceKey::Font(other_font)) => Arc::ptr_eq(font, other_font),
+            _ => false,
+        }
+    }
+}
+
+impl Eq for FaceKey {}
+
+impl Hash for FaceKey {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        match *self {
+            FaceKey::Builtin(slant, weight) => (slant, weight).hash(state),
+            FaceKey::Font(ref font) => (Arc::as_ptr(font) as usize).hash(state),
+        }
+    }
+}
+
+/// ## GlyphKey
+///
+/// Identifies a rasterized glyph by its face, its scale, its index and its subpixel phase.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct GlyphKey {
+    face: FaceKey,
+    // The bits of the scales, so that keys compare them exactly
+    scale: (u32, u32),
+    index: u32,
+    phase: (u8, u8),
+}
+
+impl GlyphKey {
+    /// Returns the key of glyph `index` of `face`, drawn `x_scale` pixels to the em across and
+    /// `y_scale` down, with its origin `phase` positions right of and below a pixel's corner.
+    pub fn new(face: &FontFace, x_scale: f32, y_scale: f32, index: u32, phase: (u8, u8))
+               -> GlyphKey {
+        let face = match face.font_handle() {
+            Some(font) => FaceKey::Font(font.clone()),
+            None => FaceKey::Builtin(face.get_slant(), face.get_weight()),
+        };
+        GlyphKey { face, scale: (x_scale.to_bits(), y_scale.to_bits()), index, phase }
+    }
+}
+
+/// ## GlyphCache
+///
+/// Glyph masks by key, holding up to a capacity in bytes of coverage.  Caching a glyph that
+/// would go over capacity evicts the glyphs cached longest ago first.
+#[derive(Debug)]
+pub struct GlyphCache {
+    masks: HashMap<GlyphKey, Arc<CoverageMask>>,
+    // The keys in the order their glyphs were cached, oldest first
+    order: VecDeque<GlyphKey>,
+    size: usize,
+    capacity: usize,
+}
+
+impl GlyphCache {
+    /// Returns an empty cache that holds up to `capacity` bytes of coverage.
+    pub fn new(capacity: usize) -> GlyphCache {
+        GlyphCache {
+            masks: HashMap::new(),
+            order: VecDeque::new(),
+            size: 0,
+            capacity,
+        }
+    }
+
+    /// Returns the number of glyphs cached.
+    pub fn len(&self) -> usize {
+        self.masks.len()
+    }
+
+    /// Returns true if no glyphs are cached.
+    pub fn is_empty(&self) -> bool {
+        self.masks.is_empty()
+    }
+
+    /// Returns the bytes of coverage the cached glyphs take up.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Returns the bytes of coverage the cache holds before it evicts glyphs.
+    pub fn get_capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Sets the bytes of coverage the cache holds, evicting glyphs until they fit.
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        self.evict();
+    }
+
+    /// Returns the mask cached for `key`, if there is one.
+    pub fn get(&self, key: &GlyphKey) -> Option<Arc<CoverageMask>> {
+        self.masks.get(key).cloned()
+    }
+
+    /// Returns the mask cached for `key`, rasterizing it with `render` and caching it if there
+    /// isn't one yet.
+    ///
+    /// # Usage
+    /// let mask = cache.get_or_insert_with(key, || rasterize_glyph(&path, 16., 16., (0, 0)));
+    pub fn get_or_insert_with<F: FnOnce() -> CoverageMask>(&mut self, key: GlyphKey, render: F)
+                                                           -> Arc<CoverageMask> {
+        if let Some(mask) = self.masks.get(&key) {
+            return mask.clone();
+        }
+        let mask = Arc::new(render());
+        self.size += mask.coverage.len();
+        self.order.push_back(key.clone());
+        self.masks.insert(key, mask.clone());
+        self.evict();
+        mask
+    }
+
+    /// Evicts every glyph.
+    pub fn clear(&mut self) {
+        self.masks.clear();
+        self.order.clear();
+        self.size = 0;
+    }
+
+    // Evicts the glyphs cached longest ago until the rest fit in the capacity
+    fn evict(&mut self) {
+        while self.size > self.capacity {
+            let key = match self.order.pop_front() {
+                Some(key) => key,
+                None => break,
+            };
+            if let Some(mask) = self.masks.remove(&key) {
+                self.size -= mask.coverage.len();
+            }
+        }
+    }
+}
+
+/// Returns the glyph cache every Context draws text from, which holds `DEFAULT_CAPACITY` bytes
+/// of coverage unless its capacity is changed.
+///
+/// # Usage
+/// glyph_cache::global().lock().unwrap().set_capacity(1 << 20);
+pub fn global() -> &'static Mutex<GlyphCache> {
+    static CACHE: OnceLock<Mutex<GlyphCache>> = OnceLock::new();
+    CACHE.get_or_init(|| Mutex::new(GlyphCache::new(DEFAULT_CAPACITY)))
+}
+
+/// Returns the coverage of the glyph outline `path`, which is in ems, drawn `x_scale` pixels to
+/// the em across and `y_scale` down.  The glyph's origin is `phase` positions right of and below
+/// the corner of a pixel, and the mask is placed relative to that pixel.
+pub fn rasterize_glyph(path: &Path, x_scale: f32, y_scale: f32, phase: (u8, u8))
+                       -> CoverageMask {
+    let phases = SUBPIXEL_PHASES as f32;
+    let matrix = Matrix::new(x_scale, 0., 0., y_scale, phase.0 as f32 / phases,
+                             phase.1 as f32 / phases);
+    let edges = path.transform(&matrix).edges(DEFAULT_TOLERANCE);
+    if edges.is_empty() {
+        return CoverageMask::default();
+    }
+
+    let first = edges[0].line.point1;
+    let (mut left, mut top, mut right, mut bottom) = (first.x, first.y, first.x, first.y);
+    for edge in &edges {
+        for point in &[edge.line.point1, edge.line.point2] {
+            left = left.min(point.x);
+            top = top.min(point.y);
+            right = right.max(point.x);
+            bottom = bottom.max(point.y);
+        }
+    }
+    let (left, top) = (left.floor(), top.floor());
+    let width = (right.ceil() - left) as usize;
+    let height = (bottom.ceil() - top) as usize;
+    let edges = edges.iter().map(|edge| {
+        let shift = |point: Point| Point::new(point.x - left, point.y - top);
+        Edge::from_points(shift(edge.line.point1), shift(edge.line.point2))
+    }).collect();
+
+    let image = mask_from_edges(edges, FillRule::Winding, width, height);
+    CoverageMask {
+        x: left as i32,
+        y: top as i32,
+        width,
+        height,
+        coverage: image.iter().map(|pixel| (pixel.alpha.clamp(0., 1.) * 255.).round() as u8)
+                              .collect(),
+    }
+}
+
+/// Returns the coverage of `text` set in `face`, with `matrix` taking ems to device space, put
+/// together from glyphs in `cache`.
+///
+/// Where glyphs overlap, their coverage adds up.  Returns None if `matrix` rotates, skews or
+/// flips the text, which cached glyphs can't show, so that the text's outline can be filled
+/// instead.
+pub fn text_mask(cache: &mut GlyphCache, face: &FontFace, text: &str, matrix: &Matrix)
+                 -> Option<CoverageMask> {
+    let finite = [matrix.xx, matrix.yy, matrix.x0, matrix.y0].iter().all(|v| v.is_finite());
+    if !finite || matrix.xy != 0. || matrix.yx != 0. || matrix.xx <= 0. || matrix.yy <= 0. {
+        return None;
+    }
+
+    let phases = SUBPIXEL_PHASES as f32;
+    let tolerance = DEFAULT_TOLERANCE / matrix.xx.max(matrix.yy);
+    let mut placed = Vec::new();
+    for glyph in face.text_to_glyphs(text) {
+        // The origin is rounded to the nearest phase, then split into its pixel and phase
+        let origin = matrix.transform_point(Point::new(glyph.x, glyph.y));
+        let (x, y) = ((origin.x * phases).round(), (origin.y * phases).round());
+        let pixel = ((x / phases).floor() as i32, (y / phases).floor() as i32);
+        let phase = (x.rem_euclid(phases) as u8, y.rem_euclid(phases) as u8);
+        let key = GlyphKey::new(face, matrix.xx, matrix.yy, glyph.index, phase);
+        let mask = cache.get_or_insert_with(key, || {
+            rasterize_glyph(&face.glyph_path(glyph.index, tolerance), matrix.xx, matrix.yy, phase)
+        });
+        if !mask.coverage.is_empty() {
+            placed.push((pixel, mask));
+        }
+    }
+
+    let mut bounds: Option<(i32, i32, i32, i32)> = None;
+    for &((x, y), ref mask) in &placed {
+        let (left, top) = (x + mask.x, y + mask.y);
+        let (right, bottom) = (left + mask.width as i32, top + mask.height as i32);
+        bounds = Some(match bounds {
+            Some((l, t, r, b)) => (l.min(left), t.min(top), r.max(right), b.max(bottom)),
+            None => (left, top, right, bottom),
+        });
+    }
+    let (left, top, right, bottom) = bounds.unwrap_or((0, 0, 0, 0));
+    let mut combined = CoverageMask::new(left, top, (right - left) as usize,
+                                         (bottom - top) as usize);
+    for ((x, y), mask) in placed {
+        combined.add(&mask, x, y);
+    }
+    Some(combined)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{GlyphCache, GlyphKey, text_mask};
+    use std::sync::Arc;
+    use context::DEFAULT_TOLERANCE;
+    use matrix::Matrix;
+    use surfaces::CoverageMask;
+    use text::FontFace;
+    use trapezoid_rasterizer::mask_from_edges;
+    use types::{FillRule, FontSlant, FontWeight};
+
+    #[test]
+    fn text_mask_reuses_glyphs() {
+        // Setup
+        let mut cache = GlyphCache::new(1 << 20);
+        let face = FontFace::default();
+        let matrix = Matrix::new(16., 0., 0., 16., 2., 20.);
+
+        // Call
+        text_mask(&mut cache, &face, "aa", &matrix).unwrap();
+
+        // Test
+        // Each a advances 10 pixels, so both land on the same phase
+        assert_eq!(cache.len(), 1);
+        let key = GlyphKey::new(&face, 16., 16., 'a' as u32, (0, 0));
+        let mask = cache.get(&key).unwrap();
+        text_mask(&mut cache, &face, "ab a", &matrix).unwrap();
+        assert_eq!(cache.len(), 3);
+        assert!(Arc::ptr_eq(&mask, &cache.get(&key).unwrap()));
+
+        // A quarter pixel along is another phase, and another size is another scale
+        text_mask(&mut cache, &face, "a", &Matrix::new(16., 0., 0., 16., 2.25, 20.)).unwrap();
+        assert!(cache.get(&GlyphKey::new(&face, 16., 16., 'a' as u32, (1, 0))).is_some());
+        text_mask(&mut cache, &face, "a", &Matrix::new(12., 0., 0., 16., 2., 20.)).unwrap();
+        assert_eq!(cache.len(), 5);
+        let bold = FontFace::new("serif", FontSlant::Normal, FontWeight::Bold);
+        assert!(cache.get(&GlyphKey::new(&bold, 16., 16., 'a' as u32, (0, 0))).is_none());
+    }
+
+    #[test]
+    fn text_mask_matches_outline() {
+        // Setup
+        let mut cache = GlyphCache::new(1 << 20);
+        let face = FontFace::new("serif", FontSlant::Italic, FontWeight::Bold);
+        let matrix = Matrix::new(20., 0., 0., 24., 3.5, 25.25);
+
+        // Call
+        let mask = text_mask(&mut cache, &face, "Hg", &matrix).unwrap();
+
+        // Test
+        let edges = face.text_path_with_matrix("Hg", &matrix, DEFAULT_TOLERANCE)
+                        .edges(DEFAULT_TOLERANCE);
+        let outline = mask_from_edges(edges, FillRule::Winding, 60, 40);
+        let mut covered = 0;
+        for y in 0..40 {
+            for x in 0..60 {
+                let expected = outline.get(x, y).unwrap().alpha;
+                let coverage = mask.get(x as i32, y as i32) as f32 / 255.;
+                assert!((coverage - expected).abs() < 0.02, "({}, {})", x, y);
+                covered += (coverage > 0.) as usize;
+            }
+        }
+        assert!(covered > 100);
+    }
+
+    #[test]
+    fn text_mask_needs_upright_text() {
+        let mut cache = GlyphCache::new(1 << 20);
+        let face = FontFace::default();
+        let rotated = Matrix::rotation(0.5).multiply(&Matrix::scaling(16., 16.));
+        assert_eq!(text_mask(&mut cache, &face, "a", &rotated), None);
+        assert_eq!(text_mask(&mut cache, &face, "a", &Matrix::scaling(16., -16.)), None);
+        assert!(cache.is_empty());
+        assert_eq!(text_mask(&mut cache, &face, " ", &Matrix::scaling(16., 16.)),
+                   Some(CoverageMask::default()));
+    }
+
+    #[test]
+    fn cache_evicts_oldest_glyphs() {
+        // Setup
+        let mut cache = GlyphCache::new(100);
+        let face = FontFace::default();
+        let key = |index| GlyphKey::new(&face, 1., 1., index, (0, 0));
+
+        // Call
+        for index in 0..3 {
+            cache.get_or_insert_with(key(index), || CoverageMask::new(0, 0, 8, 5));
+        }
+
+        // Test
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.size(), 80);
+        assert!(cache.get(&key(0)).is_none());
+        // A cached glyph isn't rendered again
+        cache.get_or_insert_with(key(1), || panic!("glyph 1 is cached"));
+        cache.set_capacity(50);
+        assert_eq!(cache.len(), 1);
+        assert!(cache.get(&key(2)).is_some());
+        cache.clear();
+        assert_eq!((cache.len(), cache.size(), cache.get_capacity()), (0, 0, 50));
+    }
+}
@@ -0,0 +1,391 @@
+/*
+ * Cairus - a reimplementation of the cairo graphics library in Rust
+ *
+ * Copyright © 2017 CairusOrg
+ *
+ * This library is free software; you can redistribute it and/or
+ * modify it either under the terms of the GNU Lesser General Public
+ * License version 2.1 as published by the Free Software Foundation
+ * (the "LGPL") or, at your option, under the terms of the Mozilla
+ * Public License Version 2.0 (the "MPL"). If you do not alter this
+ * notice, a recipient may use your version of this file under either
+ * the MPL or the LGPL.
+ *
+ * You should have received a copy of the LGPL along with this library
+ * in the file LICENSE-LGPL-2_1; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Suite 500, Boston, MA 02110-1335, USA
+ * You should have received a copy of the MPL along with this library
+ * in the file LICENSE-MPL-2_0
+ *
+ * The contents of this file are subject to the Mozilla Public License
+ * Version 2.0 (the "License"); you may not use this file except in
+ * compliance with the License. You may obtain a copy of the License at
+ * http://www.mozilla.org/MPL/
+ *
+ * This software is distributed on an "AS IS" basis, WITHOUT WARRANTY
+ * OF ANY KIND, either express or implied. See the LGPL or the MPL for
+ * the specific language governing rights and limitations.
+ *
+ * The Original Code is the cairus graphics library.
+ *
+ * Contributor(s):
+ *	CairusOrg
+ *
+ */
+
+//! Convolution filters for image surfaces.
+//!
+//! Every filter here reads one `ImageSurface` and returns a new one of the same size, format and
+//! color space: a Gaussian or box blur, a sharpen, or a convolution with any `Kernel`.  Pixels
+//! are convolved as they are stored, premultiplied by alpha, so a blurred edge fades to
+//! transparent rather than to a dark fringe.  Where a kernel reaches past the edge of the
+//! surface, the pixels it reads there are chosen by an `Extend`, as for a surface pattern.
+
+use std::error::Error;
+use std::fmt;
+use pattern::extend_position;
+use surfaces::ImageSurface;
+use types::{Extend, Rgba};
+
+/// The error returned for weights that don't describe a usable kernel.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InvalidKernel {
+    pub reason: &'static str,
+}
+
+impl fmt::Display for InvalidKernel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid kernel: {}", self.reason)
+    }
+}
+
+impl Error for InvalidKernel {}
+
+/// ## Kernel
+///
+/// A grid of weights to convolve an image with.  Each output pixel is the sum of the pixels
+/// around it, each multiplied by the weight over it when the kernel is centered on the output
+/// pixel.  The kernel is not flipped, so the weight `width` cells into the grid is the one for
+/// the pixel to the right.  Analogous to the parameters of pixman's convolution filter.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Kernel {
+    width: usize,
+    height: usize,
+    // Row by row from the top left
+    weights: Vec<f32>,
+}
+
+impl Kernel {
+    /// Returns a `width` by `height` kernel, or an error if the weights don't fit it.
+    ///
+    /// Both dimensions have to be odd, so the kernel has a center, and every weight finite.
+    ///
+    /// # Arguments
+    /// * `weights` - The weights, row by row from the top left.
+    ///
+    /// # Usage
+    /// let emboss = Kernel::new(3, 3, &[-2., -1., 0., -1., 1., 1., 0., 1., 2.])?;
+    pub fn new(width: usize, height: usize, weights: &[f32]) -> Result<Kernel, InvalidKernel> {
+        if width.is_multiple_of(2) || height.is_multiple_of(2) {
+            return Err(InvalidKernel { reason: "kernel dimensions must be odd" });
+        }
+        if weights.len() != width * height {
+            return Err(InvalidKernel { reason: "kernel needs one weight for each cell" });
+        }
+        if weights.iter().any(|weight| !weight.is_finite()) {
+            return Err(InvalidKernel { reason: "kernel weights must be finite" });
+        }
+        Ok(Kernel { width, height, weights: weights.to_vec() })
+    }
+
+    /// Returns a 3x3 kernel that sharpens by `amount`, 0 leaving the image as it is.
+    ///
+    /// Each pixel is pushed away from the average of its four neighbours by `amount` times the
+    /// difference.  The weights sum to one, so flat areas don't change.
+    pub fn sharpen(amount: f32) -> Kernel {
+        let edge = -amount;
+        Kernel {
+            width: 3,
+            height: 3,
+            weights: vec![0., edge, 0., edge, 1. + 4. * amount, edge, 0., edge, 0.],
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Returns the weights, row by row from the top left.
+    pub fn weights(&self) -> &[f32] {
+        &self.weights
+    }
+}
+
+/// Returns `surface` convolved with `kernel`, reading past its edges as `extend` does.
+///
+/// Channels are clamped afterwards so the pixels stay valid premultiplied colors, which matters
+/// for kernels with negative weights.
+///
+/// # Usage
+/// let sharpened = image_filter::convolve(&surface, &Kernel::sharpen(1.), Extend::Pad);
+pub fn convolve(surface: &ImageSurface, kernel: &Kernel, extend: Extend) -> ImageSurface {
+    let pixels: Vec<Rgba> = surface.iter().cloned().collect();
+    let pixels = convolve_pixels(&pixels, surface.width, surface.height, kernel, extend);
+    with_pixels(surface, pixels)
+}
+
+/// Returns `surface` convolved with `horizontal` along each row and then `vertical` along each
+/// column, or an error if either doesn't have an odd number of finite weights.
+///
+/// This is the same as convolving with the kernel whose weights are the products of the two,
+/// but takes time proportional to their sum rather than their product.
+///
+/// # Usage
+/// let smeared = image_filter::convolve_separable(&surface, &[0.25, 0.5, 0.25], &[1.],
+///                                                Extend::None)?;
+pub fn convolve_separable(surface: &ImageSurface, horizontal: &[f32], vertical: &[f32],
+                          extend: Extend) -> Result<ImageSurface, InvalidKernel> {
+    let horizontal = Kernel::new(horizontal.len(), 1, horizontal)?;
+    let vertical = Kernel::new(1, vertical.len(), vertical)?;
+    let (width, height) = (surface.width, surface.height);
+    let pixels: Vec<Rgba> = surface.iter().cloned().collect();
+    let pixels = convolve_pixels(&pixels, width, height, &horizontal, extend);
+    let pixels = convolve_pixels(&pixels, width, height, &vertical, extend);
+    Ok(with_pixels(surface, pixels))
+}
+
+/// Returns `surface` blurred with a Gaussian of standard deviation `sigma` pixels.
+///
+/// The kernel reaches three standard deviations either side, and is applied as two passes.  A
+/// `sigma` that isn't positive and finite leaves the image as it is.
+///
+/// # Usage
+/// let blurred = image_filter::gaussian_blur(&surface, 2., Extend::None);
+pub fn gaussian_blur(surface: &ImageSurface, sigma: f32, extend: Extend) -> ImageSurface {
+    let weights = gaussian_weights(sigma);
+    convolve_separable(surface, &weights, &weights, extend)
+        .expect("Gaussian weights are odd in number and finite")
+}
+
+/// Returns `surface` with each pixel replaced by the average of the square of pixels up to
+/// `radius` away from it.
+///
+/// # Usage
+/// let blurred = image_filter::box_blur(&surface, 2, Extend::Pad);
+pub fn box_blur(surface: &ImageSurface, radius: usize, extend: Extend) -> ImageSurface {
+    let size = 2 * radius + 1;
+    let weights = vec![1. / size as f32; size];
+    convolve_separable(surface, &weights, &weights, extend)
+        .expect("box weights are odd in number and finite")
+}
+
+/// Returns `surface` sharpened by `amount`, see `Kernel::sharpen`.
+///
+/// # Usage
+/// let sharpened = image_filter::sharpen(&surface, 0.5, Extend::Pad);
+pub fn sharpen(surface: &ImageSurface, amount: f32, extend: Extend) -> ImageSurface {
+    convolve(surface, &Kernel::sharpen(amount), extend)
+}
+
+// Returns the weights of a normalized Gaussian of standard deviation `sigma`, out to three
+// standard deviations either side of the center.
+fn gaussian_weights(sigma: f32) -> Vec<f32> {
+    if sigma <= 0. || !sigma.is_finite() {
+        return vec![1.];
+    }
+    let radius = (3. * sigma).ceil() as i32;
+    let weights: Vec<f32> = (-radius..=radius)
+        .map(|offset| (-(offset * offset) as f32 / (2. * sigma * sigma)).exp())
+        .collect();
+    let total: f32 = weights.iter().sum();
+    weights.iter().map(|weight| weight / total).collect()
+}
+
+// Returns `pixels`, a `width` by `height` image, convolved with `kernel`.
+fn convolve_pixels(pixels: &[Rgba], width: usize, height: usize, kernel: &Kernel,
+                   extend: Extend) -> Vec<Rgba> {
+    let (half_width, half_height) = ((kernel.width / 2) as f32, (kernel.height / 2) as f32);
+    let mut result = Vec::with_capacity(pixels.len());
+    for y in 0..height {
+        for x in 0..width {
+            let mut sum = Rgba { red: 0., green: 0., blue: 0., alpha: 0. };
+            for (kernel_y, weights) in kernel.weights.chunks(kernel.width).enumerate() {
+                let row = y as f32 + kernel_y as f32 - half_height;
+                let row = match extend_position(row, height, extend) {
+                    Some(row) => row,
+                    None => continue,
+                };
+                for (kernel_x, weight) in weights.iter().enumerate() {
+                    let column = x as f32 + kernel_x as f32 - half_width;
+                    let column = match extend_position(column, width, extend) {
+                        Some(column) if *weight != 0. => column,
+                        _ => continue,
+                    };
+                    let pixel = &pixels[row * width + column];
+                    sum.red += weight * pixel.red;
+                    sum.green += weight * pixel.green;
+                    sum.blue += weight * pixel.blue;
+                    sum.alpha += weight * pixel.alpha;
+                }
+            }
+            result.push(sum);
+        }
+    }
+    result
+}
+
+// Returns a surface like `surface`, but holding `pixels` clamped to valid premultiplied colors
+// and kept as the surface's format keeps them.
+fn with_pixels(surface: &ImageSurface, pixels: Vec<Rgba>) -> ImageSurface {
+    let mut result = ImageSurface::create_for_format(surface.get_format(), surface.width,
+                                                     surface.height);
+    result.set_color_space(surface.get_color_space());
+    result.set_linear_blending(surface.get_linear_blending());
+    for (pixel, filtered) in result.iter_mut().zip(pixels) {
+        let alpha = filtered.alpha.clamp(0., 1.);
+        *pixel = Rgba {
+            red: filtered.red.clamp(0., alpha),
+            green: filtered.green.clamp(0., alpha),
+            blue: filtered.blue.clamp(0., alpha),
+            alpha,
+        };
+    }
+    result.keep_format(0..result.height);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use surfaces::{Format, ImageSurface};
+    use types::{Extend, Rgba};
+    use super::{Kernel, box_blur, convolve, convolve_separable, gaussian_blur, gaussian_weights,
+                sharpen};
+
+    // Returns a 5x5 transparent surface with one opaque red pixel in the middle
+    fn dot() -> ImageSurface {
+        let mut surface = ImageSurface::create_for_format(Format::ARGB32, 5, 5);
+        surface.set_pixel(2, 2, Rgba::new(1., 0., 0., 1.)).unwrap();
+        surface
+    }
+
+    fn total_alpha(surface: &ImageSurface) -> f32 {
+        surface.iter().map(|pixel| pixel.alpha).sum()
+    }
+
+    #[test]
+    fn test_kernel_new() {
+        assert!(Kernel::new(3, 1, &[1., 2., 1.]).is_ok());
+        assert!(Kernel::new(2, 1, &[1., 1.]).is_err());
+        assert!(Kernel::new(3, 3, &[1., 2., 1.]).is_err());
+        assert!(Kernel::new(1, 1, &[f32::NAN]).is_err());
+        assert!(Kernel::new(0, 0, &[]).is_err());
+    }
+
+    #[test]
+    fn test_convolve_is_not_flipped() {
+        // Setup
+        // Each pixel takes the value of the pixel to its right
+        let kernel = Kernel::new(3, 1, &[0., 0., 1.]).unwrap();
+
+        // Call
+        let shifted = convolve(&dot(), &kernel, Extend::None);
+
+        // Test
+        assert_eq!(shifted.get_pixel(1, 2).unwrap(), Rgba::new(1., 0., 0., 1.));
+        assert_eq!(shifted.get_pixel(2, 2).unwrap(), Rgba::new(0., 0., 0., 0.));
+    }
+
+    #[test]
+    fn test_gaussian_blur() {
+        // Setup
+        let surface = dot();
+
+        // Call
+        let blurred = gaussian_blur(&surface, 0.8, Extend::None);
+
+        // Test
+        // The dot is spread out, but nothing is lost off the edges
+        assert!((total_alpha(&blurred) - 1.).abs() < 0.01);
+        let center = blurred.get_pixel(2, 2).unwrap();
+        let beside = blurred.get_pixel(3, 2).unwrap();
+        assert!(center.alpha < 1. && beside.alpha > 0. && beside.alpha < center.alpha);
+        assert_eq!(blurred.get_pixel(1, 2).unwrap(), beside);
+        // Premultiplied, so the color stays red however transparent it gets
+        assert_eq!(beside.red, beside.alpha);
+        assert_eq!(beside.green, 0.);
+    }
+
+    #[test]
+    fn test_gaussian_weights() {
+        assert_eq!(gaussian_weights(0.), vec![1.]);
+        assert_eq!(gaussian_weights(f32::NAN), vec![1.]);
+        let weights = gaussian_weights(1.);
+        assert_eq!(weights.len(), 7);
+        assert!((weights.iter().sum::<f32>() - 1.).abs() < 1e-6);
+        assert_eq!(weights[2], weights[4]);
+        assert!(weights[3] > weights[2]);
+    }
+
+    #[test]
+    fn test_box_blur_extend() {
+        // Setup
+        let mut surface = ImageSurface::create_for_format(Format::ARGB32, 3, 1);
+        for x in 0..3 {
+            surface.set_pixel(x, 0, Rgba::new(0., 0., 1., 1.)).unwrap();
+        }
+
+        // Call
+        let none = box_blur(&surface, 1, Extend::None);
+        let pad = box_blur(&surface, 1, Extend::Pad);
+
+        // Test
+        // Past the edge is transparent with no extend, but a copy of the edge with pad.  Rows
+        // above and below count too, as the blur is square.
+        assert!((none.get_pixel(0, 0).unwrap().alpha - 2. / 9.).abs() < 1e-6);
+        assert!((none.get_pixel(1, 0).unwrap().alpha - 3. / 9.).abs() < 1e-6);
+        assert!((pad.get_pixel(0, 0).unwrap().alpha - 1.).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_sharpen() {
+        // Setup
+        let mut surface = ImageSurface::create_for_format(Format::ARGB32, 3, 1);
+        surface.set_pixel(0, 0, Rgba::new(0.5, 0.5, 0.5, 1.)).unwrap();
+        surface.set_pixel(1, 0, Rgba::new(0.6, 0.6, 0.6, 1.)).unwrap();
+        surface.set_pixel(2, 0, Rgba::new(0.5, 0.5, 0.5, 1.)).unwrap();
+
+        // Call
+        let sharpened = sharpen(&surface, 1., Extend::Pad);
+
+        // Test
+        // The bright pixel gets brighter and its neighbours darker, but the alpha stays clamped
+        let middle = sharpened.get_pixel(1, 0).unwrap();
+        assert!(middle.red > 0.6 && middle.red <= 1.);
+        assert!(sharpened.get_pixel(0, 0).unwrap().red < 0.5);
+        assert_eq!(middle.alpha, 1.);
+        assert_eq!(sharpen(&surface, 0., Extend::Pad), surface);
+    }
+
+    #[test]
+    fn test_convolve_separable() {
+        // Setup
+        let surface = dot();
+        let (horizontal, vertical) = ([0.25, 0.5, 0.25], [0.5, 0.5, 0.]);
+        let mut weights = Vec::new();
+        for v in vertical.iter() {
+            weights.extend(horizontal.iter().map(|h| h * v));
+        }
+        let kernel = Kernel::new(3, 3, &weights).unwrap();
+
+        // Call
+        let separable = convolve_separable(&surface, &horizontal, &vertical, Extend::None);
+
+        // Test
+        assert_eq!(separable.unwrap(), convolve(&surface, &kernel, Extend::None));
+        assert!(convolve_separable(&surface, &[0.5, 0.5], &[1.], Extend::None).is_err());
+    }
+}
@@ -48,10 +48,13 @@
 mod debug_utils;
 
 #[allow(dead_code)]
-pub mod operators;
+#[macro_use]
+mod types;
+
+mod color_names;
 
 #[allow(dead_code)]
-mod types;
+pub mod operators;
 
 #[allow(dead_code)]
 pub mod surfaces;
@@ -65,8 +68,88 @@ pub mod context;
 #[allow(dead_code)]
 mod trapezoid_rasterizer;
 
+mod spans;
+
 #[allow(dead_code)]
-mod common_geometry;
+pub mod common_geometry;
 
 #[allow(dead_code)]
 mod bo_trap;
+
+#[allow(dead_code)]
+mod predicates;
+
+mod outline;
+
+#[allow(dead_code)]
+mod fixed;
+
+mod coverage;
+
+mod subpixel;
+
+mod srgb;
+
+pub mod status;
+
+pub mod error;
+
+pub mod dash;
+
+pub mod matrix;
+
+pub mod region;
+
+pub mod pattern;
+
+pub mod stroke;
+
+pub mod path;
+
+pub mod font;
+
+pub mod text;
+
+pub mod glyph_cache;
+
+pub mod canvas;
+
+pub mod image_filter;
+
+pub mod command_buffer;
+
+pub mod recording;
+
+pub mod paginated;
+
+pub mod ps;
+
+pub mod ffi;
+
+/// The tessellator and trapezoid rasterizer, for the benchmarks and fuzz targets, which are
+/// built as separate crates and can only reach what is public.  Not part of the API.
+#[doc(hidden)]
+pub mod internals {
+    pub use bo_trap::{EventQueue, sweep, sweep_with_event_limit, sweep_with_fill_rule,
+                      sweep_with_precision};
+    pub use trapezoid_rasterizer::{mask_from_trapezoids, rasterize_trapezoids};
+}
+
+pub use types::{Antialias, Extend, FillRule, Filter, FontSlant, FontWeight, InvalidColor,
+                LineCap, LineJoin, ParseNameError, Rasterizer, Rgba, SubpixelOrder};
+pub use surfaces::{Composite, CoverageMask, Format, ImageData, ImageSurface, InvalidData,
+                   OutOfBounds, PackedImageSurface, SubSurface, SubSurfaceMut, SubpixelMask,
+                   Surface, SurfaceError};
+pub use status::Status;
+pub use error::Error;
+pub use dash::InvalidDash;
+pub use font::{Font, InvalidFont, NamedInstance, VariationAxis};
+pub use image_filter::{InvalidKernel, Kernel};
+pub use matrix::Matrix;
+pub use predicates::Precision;
+pub use region::{Overlap, Region};
+pub use pattern::{Pattern, SolidPattern, SurfacePattern};
+pub use recording::RecordingSurface;
+pub use ps::PsSurface;
+pub use trapezoid_rasterizer::{Trapezoid, TrapezoidSpans};
+pub use spans::Span;
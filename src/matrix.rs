@@ -0,0 +1,324 @@
+/*
+ * Cairus - a reimplementation of the cairo graphics library in Rust
+ *
+ * Copyright © 2017 CairusOrg
+ *
+ * This library is free software; you can redistribute it and/or
+ * modify it either under the terms of the GNU Lesser General Public
+ * License version 2.1 as published by the Free Software Foundation
+ * (the "LGPL") or, at your option, under the terms of the Mozilla
+ * Public License Version 2.0 (the "MPL"). If you do not alter this
+ * notice, a recipient may use your version of this file under either
+ * the MPL or the LGPL.
+ *
+ * You should have received a copy of the LGPL along with this library
+ * in the file LICENSE-LGPL-2_1; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Suite 500, Boston, MA 02110-1335, USA
+ * You should have received a copy of the MPL along with this library
+ * in the file LICENSE-MPL-2_0
+ *
+ * The contents of this file are subject to the Mozilla Public License
+ * Version 2.0 (the "License"); you may not use this file except in
+ * compliance with the License. You may obtain a copy of the License at
+ * http://www.mozilla.org/MPL/
+ *
+ * This software is distributed on an "AS IS" basis, WITHOUT WARRANTY
+ * OF ANY KIND, either express or implied. See the LGPL or the MPL for
+ * the specific language governing rights and limitations.
+ *
+ * The Original Code is the cairus graphics library.
+ *
+ * Contributor(s):
+ *	CairusOrg
+ *
+ */
+
+//! Affine transformations between coordinate spaces.
+//!
+//! A `Matrix` maps user space, the coordinates drawing commands are given in, to device space,
+//! the pixels of the target surface.  As in cairo, a point (x, y) is mapped to
+//!
+//! ```text
+//! x' = xx * x + xy * y + x0
+//! y' = yx * x + yy * y + y0
+//! ```
+//!
+//! and the methods that add a transformation, such as `translate`, apply it before the ones
+//! already in the matrix, so that it acts on user space.
+
+use common_geometry::Point;
+use error::Error;
+
+/// ## Matrix
+///
+/// An affine transformation.  Analogous to cairo_matrix_t, with the same field names.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Matrix {
+    /// The x component of the transformed x axis.
+    pub xx: f32,
+    /// The y component of the transformed x axis.
+    pub yx: f32,
+    /// The x component of the transformed y axis.
+    pub xy: f32,
+    /// The y component of the transformed y axis.
+    pub yy: f32,
+    /// The x translation.
+    pub x0: f32,
+    /// The y translation.
+    pub y0: f32,
+}
+
+impl Matrix {
+    /// Returns the Matrix with the given components.  Analogous to cairo_matrix_init().
+    ///
+    /// # Usage
+    /// let shear = Matrix::new(1., 0., 0.5, 1., 0., 0.);
+    pub fn new(xx: f32, yx: f32, xy: f32, yy: f32, x0: f32, y0: f32) -> Matrix {
+        Matrix { xx, yx, xy, yy, x0, y0 }
+    }
+
+    /// Returns the Matrix that leaves every point where it is.
+    ///
+    /// Analogous to cairo_matrix_init_identity().
+    pub fn identity() -> Matrix {
+        Matrix::new(1., 0., 0., 1., 0., 0.)
+    }
+
+    /// Returns the Matrix that moves every point by (tx, ty).
+    ///
+    /// Analogous to cairo_matrix_init_translate().
+    pub fn translation(tx: f32, ty: f32) -> Matrix {
+        Matrix::new(1., 0., 0., 1., tx, ty)
+    }
+
+    /// Returns the Matrix that scales x by `sx` and y by `sy`.
+    ///
+    /// Analogous to cairo_matrix_init_scale().
+    pub fn scaling(sx: f32, sy: f32) -> Matrix {
+        Matrix::new(sx, 0., 0., sy, 0., 0.)
+    }
+
+    /// Returns the Matrix that rotates by `radians`, from the positive x axis towards the
+    /// positive y axis.  Analogous to cairo_matrix_init_rotate().
+    pub fn rotation(radians: f32) -> Matrix {
+        let (sin, cos) = radians.sin_cos();
+        Matrix::new(cos, sin, -sin, cos, 0., 0.)
+    }
+
+    /// Returns the Matrix that applies this transformation first and then `other`.
+    ///
+    /// Analogous to cairo_matrix_multiply(result, self, other).
+    ///
+    /// # Arguments
+    /// * `&self` - The transformation applied first.
+    /// * `other` - The transformation applied second.
+    ///
+    /// # Usage
+    /// let scale_then_move = Matrix::scaling(2., 2.).multiply(&Matrix::translation(5., 0.));
+    pub fn multiply(&self, other: &Matrix) -> Matrix {
+        Matrix {
+            xx: self.xx * other.xx + self.yx * other.xy,
+            yx: self.xx * other.yx + self.yx * other.yy,
+            xy: self.xy * other.xx + self.yy * other.xy,
+            yy: self.xy * other.yx + self.yy * other.yy,
+            x0: self.x0 * other.xx + self.y0 * other.xy + other.x0,
+            y0: self.x0 * other.yx + self.y0 * other.yy + other.y0,
+        }
+    }
+
+    /// Applies a translation by (tx, ty) before this transformation.
+    ///
+    /// Analogous to cairo_matrix_translate().
+    pub fn translate(&mut self, tx: f32, ty: f32) {
+        *self = Matrix::translation(tx, ty).multiply(self);
+    }
+
+    /// Applies a scaling by (sx, sy) before this transformation.
+    ///
+    /// Analogous to cairo_matrix_scale().
+    pub fn scale(&mut self, sx: f32, sy: f32) {
+        *self = Matrix::scaling(sx, sy).multiply(self);
+    }
+
+    /// Applies a rotation by `radians` before this transformation.
+    ///
+    /// Analogous to cairo_matrix_rotate().
+    pub fn rotate(&mut self, radians: f32) {
+        *self = Matrix::rotation(radians).multiply(self);
+    }
+
+    /// Returns the determinant of the linear part of the transformation.
+    pub fn determinant(&self) -> f32 {
+        self.xx * self.yy - self.yx * self.xy
+    }
+
+    /// Returns the transformation that undoes this one, or None if it collapses the plane onto
+    /// a line or a point and so can't be undone.  Analogous to cairo_matrix_invert().
+    pub fn invert(&self) -> Option<Matrix> {
+        let determinant = self.determinant();
+        if determinant == 0. || !determinant.is_finite() {
+            return None;
+        }
+
+        Some(Matrix {
+            xx: self.yy / determinant,
+            yx: -self.yx / determinant,
+            xy: -self.xy / determinant,
+            yy: self.xx / determinant,
+            x0: (self.xy * self.y0 - self.yy * self.x0) / determinant,
+            y0: (self.yx * self.x0 - self.xx * self.y0) / determinant,
+        })
+    }
+
+    /// Returns the transformation that undoes this one, as `invert` does, or Error::InvalidMatrix
+    /// if it can't be undone, as cairo_matrix_invert() returns CAIRO_STATUS_INVALID_MATRIX.
+    ///
+    /// # Usage
+    /// let to_user = matrix.try_invert()?;
+    pub fn try_invert(&self) -> Result<Matrix, Error> {
+        self.invert().ok_or(Error::InvalidMatrix)
+    }
+
+    /// Returns `point` transformed.  Analogous to cairo_matrix_transform_point().
+    pub fn transform_point(&self, point: Point) -> Point {
+        let distance = self.transform_distance(point);
+        Point::new(distance.x + self.x0, distance.y + self.y0)
+    }
+
+    /// Returns the vector `distance` transformed, which ignores the translation.
+    ///
+    /// Analogous to cairo_matrix_transform_distance().
+    pub fn transform_distance(&self, distance: Point) -> Point {
+        Point::new(self.xx * distance.x + self.xy * distance.y,
+                   self.yx * distance.x + self.yy * distance.y)
+    }
+
+    /// Returns the most the transformation stretches any distance by, the larger singular value
+    /// of its linear part.
+    ///
+    /// A tolerance in device space divided by this is a tolerance in user space that is never
+    /// too coarse.
+    pub fn max_scale(&self) -> f32 {
+        let squares = self.xx * self.xx + self.yx * self.yx + self.xy * self.xy +
+                      self.yy * self.yy;
+        let determinant = self.determinant();
+        let discriminant = (squares * squares - 4. * determinant * determinant).max(0.);
+        ((squares + discriminant.sqrt()) / 2.).sqrt()
+    }
+
+    /// Returns true if the transformation only scales and translates along the axes, so that
+    /// axis-aligned rectangles and ellipses stay axis-aligned.
+    pub fn is_axis_aligned(&self) -> bool {
+        self.xy == 0. && self.yx == 0.
+    }
+
+    /// Returns how much the transformation stretches every distance by, or None if distances in
+    /// different directions are stretched differently.
+    ///
+    /// Only similarities, made of rotations, reflections, translations and uniform scales, have
+    /// a single scale, and they keep circles circular.
+    pub fn uniform_scale(&self) -> Option<f32> {
+        let rotation = self.xx == self.yy && self.xy == -self.yx;
+        let reflection = self.xx == -self.yy && self.xy == self.yx;
+        if rotation || reflection {
+            Some(self.xx.hypot(self.yx))
+        } else {
+            None
+        }
+    }
+}
+
+impl Default for Matrix {
+    fn default() -> Matrix {
+        Matrix::identity()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::FRAC_PI_2;
+    use common_geometry::Point;
+    use error::Error;
+    use super::Matrix;
+
+    fn assert_close(actual: Point, expected: Point) {
+        assert!((actual.x - expected.x).abs() < 1e-5 && (actual.y - expected.y).abs() < 1e-5,
+                "{:?} is not {:?}", actual, expected);
+    }
+
+    #[test]
+    fn test_transform_point_and_distance() {
+        // Setup
+        let matrix = Matrix::new(2., 1., 0.5, 3., 10., 20.);
+
+        // Call
+        let point = matrix.transform_point(Point::new(1., 2.));
+        let distance = matrix.transform_distance(Point::new(1., 2.));
+
+        // Test
+        assert_eq!(point, Point::new(13., 27.));
+        assert_eq!(distance, Point::new(3., 7.));
+    }
+
+    #[test]
+    fn test_multiply_applies_self_first() {
+        let scale_then_move = Matrix::scaling(2., 2.).multiply(&Matrix::translation(5., 0.));
+        let move_then_scale = Matrix::translation(5., 0.).multiply(&Matrix::scaling(2., 2.));
+        assert_eq!(scale_then_move.transform_point(Point::new(1., 1.)), Point::new(7., 2.));
+        assert_eq!(move_then_scale.transform_point(Point::new(1., 1.)), Point::new(12., 2.));
+    }
+
+    #[test]
+    fn test_translate_scale_rotate_act_on_user_space() {
+        // As in cairo, the last transformation added is the first one applied to a point
+        let mut matrix = Matrix::identity();
+        matrix.translate(10., 0.);
+        matrix.scale(2., 2.);
+        matrix.rotate(FRAC_PI_2);
+        assert_close(matrix.transform_point(Point::new(1., 0.)), Point::new(10., 2.));
+    }
+
+    #[test]
+    fn test_invert() {
+        // Setup
+        let matrix = Matrix::new(2., 1., 0.5, 3., 10., 20.);
+
+        // Call
+        let inverse = matrix.invert().unwrap();
+
+        // Test
+        let point = Point::new(-4., 7.);
+        assert_close(inverse.transform_point(matrix.transform_point(point)), point);
+        let identity = matrix.multiply(&inverse);
+        assert_close(identity.transform_point(point), point);
+        assert!(Matrix::scaling(0., 1.).invert().is_none());
+        assert!(Matrix::new(1., 2., 2., 4., 0., 0.).invert().is_none());
+        assert_eq!(matrix.try_invert(), Ok(inverse));
+        assert_eq!(Matrix::scaling(0., 1.).try_invert(), Err(Error::InvalidMatrix));
+    }
+
+    #[test]
+    fn test_max_scale() {
+        assert_eq!(Matrix::identity().max_scale(), 1.);
+        assert_eq!(Matrix::scaling(3., -0.5).max_scale(), 3.);
+        let mut rotated = Matrix::scaling(2., 1.);
+        rotated.rotate(0.7);
+        assert!((rotated.max_scale() - 2.).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_is_axis_aligned_and_uniform_scale() {
+        let mut rotated = Matrix::scaling(2., 2.);
+        rotated.rotate(0.7);
+        let mut sheared = Matrix::identity();
+        sheared.xy = 0.5;
+
+        assert!(Matrix::scaling(3., -0.5).is_axis_aligned());
+        assert!(!rotated.is_axis_aligned());
+        assert_eq!(Matrix::translation(4., 5.).uniform_scale(), Some(1.));
+        assert_eq!(Matrix::scaling(2., -2.).uniform_scale(), Some(2.));
+        assert!((rotated.uniform_scale().unwrap() - 2.).abs() < 1e-5);
+        assert_eq!(Matrix::scaling(3., 1.).uniform_scale(), None);
+        assert_eq!(sheared.uniform_scale(), None);
+    }
+}
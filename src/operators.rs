@@ -46,14 +46,52 @@
 //!
 //! # Supported Operators:
 //! * Over - Cairus's default operator.  Blends a source onto a destination, similar to overlapping
-//!          two semi-transparent slides.  If the source is opaque, the over operation will make
-//!          the destination opaque as well.
+//!   two semi-transparent slides.  If the source is opaque, the over operation will make
+//!   the destination opaque as well.
 //! * Source - Overwrites the destination with the source. Result color & alpha is equal to source.
 //! * In - The destination object is removed and the source object is only drawn where the
-//! destination was.
+//!   destination was.
+//! * Clear - Clears the destination, leaving it fully transparent.
+//! * Out - The source is only drawn where the destination wasn't, and the destination is removed.
+//! * Atop - The source is only drawn where the destination was, on top of it.
+//! * Dest - Leaves the destination untouched, ignoring the source.
+//! * DestOver - Draws the destination on top of the source, so the source only shows through
+//!   where the destination is transparent.
+//! * DestIn - Keeps the destination only where the source is.
+//! * DestOut - Keeps the destination only where the source isn't.
+//! * DestAtop - Keeps the destination only where the source is, on top of the source.
+//! * Xor - Keeps the source and destination only where they don't overlap.
+//! * Add - Adds the source and destination together.
+//! * Saturate - Like Over, but the source only fills in as much as the destination leaves
+//!   transparent, as when drawing disjoint shapes that share edges.
+//!
+//! # Supported Blend Modes:
+//! Blend modes draw the source over the destination like Over, but where the two overlap the
+//! colors are mixed by a blend function rather than the source covering the destination.  They
+//! follow the separable blend modes of the PDF specification, as cairo does.
+//! * Multiply - Multiplies the colors, darkening.
+//! * Screen - Multiplies the complements of the colors, lightening.
+//! * Overlay - Multiplies or screens, depending on the destination.
+//! * Darken - Keeps the darker of the colors.
+//! * Lighten - Keeps the lighter of the colors.
+//! * ColorDodge - Brightens the destination to reflect the source.
+//! * ColorBurn - Darkens the destination to reflect the source.
+//! * HardLight - Multiplies or screens, depending on the source.
+//! * SoftLight - Darkens or lightens, depending on the source.
+//! * Difference - Takes the difference of the colors.
+//! * Exclusion - Like Difference, but with lower contrast.
+//!
+//! The non-separable blend modes mix whole colors instead of one channel at a time, combining the
+//! hue, saturation and luminosity of one color with the rest of the other's:
+//! * HslHue - The source's hue.
+//! * HslSaturation - The source's saturation.
+//! * HslColor - The source's hue and saturation.
+//! * HslLuminosity - The source's luminosity.
+//!
 //! Descriptions/formulas for Cairo operators:
 //! [Cairo Operators](https://www.cairographics.org/operators/)
 
+use std::cmp::Ordering;
 use types::Rgba;
 
 // Image Compositing Operations
@@ -65,48 +103,90 @@ use types::Rgba;
 // to any context via `fetch_operator`.
 
 /// The supported image compositing operators in Cairus.
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Operator {
     /// Cairus's default operator.  Draws source layer on top of destination layer.
     Over,
     ///Needed for stroke implementation. Draw source layer where there was destination layer.
     In,
-    ///Source will be the next operator to implement. It replaces the destination later.
+    ///Replaces the destination layer.
     Source,
-
-
-
-    //Remaining operators enumerated for later implementation
-    //options pulled from Cairo Graphics Library
-    //reference: https://www.cairographics.org/manual/cairo-cairo-t.html#CAIRO-OPERATOR-OVER:CAPS
-/*
+    /// Clears the destination layer.
     Clear,
+    /// Draws source layer where there was no destination layer, removing the destination.
     Out,
+    /// Draws source layer on top of destination layer, but only where there was destination.
     Atop,
+    /// Ignores the source layer.
     Dest,
+    /// Draws destination layer on top of source layer.
     DestOver,
+    /// Keeps destination layer where there was source layer.
     DestIn,
+    /// Keeps destination layer where there was no source layer.
     DestOut,
+    /// Draws destination layer on top of source layer, but only where there was source.
     DestAtop,
+    /// Keeps source and destination layers where they don't overlap.
     Xor,
+    /// Adds source and destination layers together.
     Add,
+    /// Draws source layer over destination layer, limited to the transparency left in it.
     Saturate,
+    /// Blends by multiplying the colors.
     Multiply,
+    /// Blends by multiplying the complements of the colors.
     Screen,
+    /// Blends by multiplying or screening, depending on the destination color.
     Overlay,
+    /// Blends by keeping the darker color.
     Darken,
+    /// Blends by keeping the lighter color.
     Lighten,
+    /// Blends by brightening the destination color to reflect the source color.
     ColorDodge,
+    /// Blends by darkening the destination color to reflect the source color.
     ColorBurn,
+    /// Blends by multiplying or screening, depending on the source color.
     HardLight,
+    /// Blends by darkening or lightening, depending on the source color.
     SoftLight,
+    /// Blends by taking the difference of the colors.
     Difference,
+    /// Blends like Difference, but with lower contrast.
     Exclusion,
-    HSLHue,
-    HSLSaturation,
-    HSLColor,
-    HSLLuminosity,
-*/
+    /// Blends the hue of the source color with the saturation and luminosity of the destination.
+    HslHue,
+    /// Blends the saturation of the source color with the hue and luminosity of the destination.
+    HslSaturation,
+    /// Blends the hue and saturation of the source color with the luminosity of the destination.
+    HslColor,
+    /// Blends the luminosity of the source color with the hue and saturation of the destination.
+    HslLuminosity,
+}
+
+impl_names!(Operator, "operator", [
+    Over => "over", In => "in", Source => "source", Clear => "clear", Out => "out",
+    Atop => "atop", Dest => "dest", DestOver => "dest-over", DestIn => "dest-in",
+    DestOut => "dest-out", DestAtop => "dest-atop", Xor => "xor", Add => "add",
+    Saturate => "saturate", Multiply => "multiply", Screen => "screen", Overlay => "overlay",
+    Darken => "darken", Lighten => "lighten", ColorDodge => "color-dodge",
+    ColorBurn => "color-burn", HardLight => "hard-light", SoftLight => "soft-light",
+    Difference => "difference", Exclusion => "exclusion", HslHue => "hsl-hue",
+    HslSaturation => "hsl-saturation", HslColor => "hsl-color", HslLuminosity => "hsl-luminosity"
+]);
+
+impl Operator {
+    /// Returns true if the operator leaves the destination alone wherever a shape doesn't cover
+    /// it.  Analogous to cairo's _cairo_operator_bounded_by_mask().
+    ///
+    /// In, Out, DestIn and DestAtop are unbounded: their result depends on the source being
+    /// there, so where a shape has no coverage they composite a clear source, which clears the
+    /// destination, out to the edges of the clip.  Every other operator, Source and Clear
+    /// included, only changes what the shape covers.
+    pub fn is_bounded(&self) -> bool {
+        !matches!(*self, Operator::In | Operator::Out | Operator::DestIn | Operator::DestAtop)
+    }
 }
 
 /// Returns an image compositing function that corresponds to an Operator enum.
@@ -131,13 +211,98 @@ pub fn fetch_operator(op: &Operator) -> fn(&Rgba, &mut Rgba) {
         Operator::Over      => operator_over,
         Operator::In        => operator_in,
         Operator::Source    => operator_source,
+        Operator::Clear     => operator_clear,
+        Operator::Out       => operator_out,
+        Operator::Atop      => operator_atop,
+        Operator::Dest      => operator_dest,
+        Operator::DestOver  => operator_dest_over,
+        Operator::DestIn    => operator_dest_in,
+        Operator::DestOut   => operator_dest_out,
+        Operator::DestAtop  => operator_dest_atop,
+        Operator::Xor       => operator_xor,
+        Operator::Add       => operator_add,
+        Operator::Saturate  => operator_saturate,
+        Operator::Multiply  => operator_multiply,
+        Operator::Screen    => operator_screen,
+        Operator::Overlay   => operator_overlay,
+        Operator::Darken    => operator_darken,
+        Operator::Lighten   => operator_lighten,
+        Operator::ColorDodge => operator_color_dodge,
+        Operator::ColorBurn => operator_color_burn,
+        Operator::HardLight => operator_hard_light,
+        Operator::SoftLight => operator_soft_light,
+        Operator::Difference => operator_difference,
+        Operator::Exclusion => operator_exclusion,
+        Operator::HslHue    => operator_hsl_hue,
+        Operator::HslSaturation => operator_hsl_saturation,
+        Operator::HslColor  => operator_hsl_color,
+        Operator::HslLuminosity => operator_hsl_luminosity,
+    }
+}
+
+/// Applies `operator` to `destination`, weighted by `coverage`.
+///
+/// Coverage is how much of the destination pixel a shape covers, from 0 (none) to 1 (all).
+/// Following cairo, the result is the operator's result where the shape is, and the untouched
+/// destination where it isn't:
+///
+/// `result = coverage * operator(source, destination) + (1 - coverage) * destination`
+///
+/// For Over this is the same as scaling the source by the coverage, but unlike that shortcut it is
+/// also correct for operators such as Source.  Operators that aren't bounded (see
+/// `Operator::is_bounded`) are the exception: cairo scales their source by the coverage, and
+/// keeps this weighting for the clip.
+///
+/// # Arguments
+/// * `operator` - A compositing function, as returned by `fetch_operator`.
+/// * `source` - The source Rgba to be applied to the destination Rgba.
+/// * `destination` - The destination Rgba that holds the resulting composition.
+/// * `coverage` - The fraction of the pixel covered, between 0 and 1.
+pub fn composite_with_coverage(operator: fn(&Rgba, &mut Rgba), source: &Rgba,
+                               destination: &mut Rgba, coverage: f32) {
+    if coverage <= 0. {
+        return;
+    }
+
+    let original = *destination;
+    operator(source, destination);
+    if coverage < 1. {
+        destination.red = coverage * destination.red + (1. - coverage) * original.red;
+        destination.green = coverage * destination.green + (1. - coverage) * original.green;
+        destination.blue = coverage * destination.blue + (1. - coverage) * original.blue;
+        destination.alpha = coverage * destination.alpha + (1. - coverage) * original.alpha;
     }
 }
 
+/// Applies an operator that isn't bounded (see `Operator::is_bounded`) to `destination`, as
+/// cairo does.
+///
+/// The source is scaled by `coverage`, the shape's coverage of the pixel, which can be 0 where
+/// the shape isn't.  The result is then weighted by `clip`, the clip's coverage, the same way
+/// `composite_with_coverage` weights it:
+///
+/// `result = clip * operator(coverage * source, destination) + (1 - clip) * destination`
+///
+/// # Arguments
+/// * `operator` - A compositing function, as returned by `fetch_operator`.
+/// * `source` - The source Rgba to be applied to the destination Rgba.
+/// * `destination` - The destination Rgba that holds the resulting composition.
+/// * `coverage` - The fraction of the pixel the shape covers, between 0 and 1.
+/// * `clip` - The fraction of the pixel the clip lets through, between 0 and 1.
+pub fn composite_unbounded(operator: fn(&Rgba, &mut Rgba), source: &Rgba,
+                           destination: &mut Rgba, coverage: f32, clip: f32) {
+    let source = Rgba {
+        red: source.red * coverage,
+        green: source.green * coverage,
+        blue: source.blue * coverage,
+        alpha: source.alpha * coverage,
+    };
+    composite_with_coverage(operator, &source, destination, clip);
+}
 
-/// # Operator Formulas
-/// The following functions are implementations of the Porter Duff operator formulas. (See below
-/// for the Porter Duff paper in the references section, or the Cairo operator documentation page).
+// # Operator Formulas
+// The following functions are implementations of the Porter Duff operator formulas. (See below
+// for the Porter Duff paper in the references section, or the Cairo operator documentation page).
 
 /// Composites `source` over `destination`.
 ///
@@ -155,6 +320,128 @@ pub fn operator_over(source: &Rgba, destination: &mut Rgba) {
     destination.blue = source.blue + destination.blue * (1. - source.alpha);
 }
 
+/// Composites `source` onto every pixel of `destination` with `operator`, at full coverage.
+///
+/// The result is the same as calling the operator on each pixel, but the cases that come up
+/// most are done in bulk: Source, and Over with an opaque source, just fill the run with the
+/// source, and any other Over goes to `over_span`.
+///
+/// # Arguments
+/// * `operator` - Reference to the `Operator` to composite with.
+/// * `source` - The source Rgba to be applied to every destination Rgba.
+/// * `destination` - The run of destination Rgbas that hold the resulting composition.
+///
+/// # Usage
+/// composite_span(&Operator::Over, &Rgba::new(1., 0., 0., 1.), &mut pixels);
+pub fn composite_span(operator: &Operator, source: &Rgba, destination: &mut [Rgba]) {
+    match *operator {
+        Operator::Source => destination.fill(*source),
+        Operator::Over if source.alpha >= 1. => destination.fill(*source),
+        Operator::Over => over_span(source, destination),
+        _ => {
+            let operator = fetch_operator(operator);
+            for pixel in destination {
+                operator(source, pixel);
+            }
+        },
+    }
+}
+
+/// Composites `source` onto `destination` with `operator`, as far as the matching entry of
+/// `coverage` covers each pixel.
+///
+/// Like `composite_with_coverage` on each pair, except that runs of fully covered pixels are
+/// handed to `composite_span` together.
+///
+/// # Arguments
+/// * `operator` - Reference to the `Operator` to composite with.
+/// * `source` - The source Rgba to be applied to every destination Rgba.
+/// * `destination` - The run of destination Rgbas that hold the resulting composition.
+/// * `coverage` - The fraction of each destination pixel covered, between 0 and 1.
+pub fn composite_span_with_coverage(operator: &Operator, source: &Rgba,
+                                    destination: &mut [Rgba], coverage: &[f32]) {
+    let compose = fetch_operator(operator);
+    let length = destination.len().min(coverage.len());
+    let mut start = 0;
+    while start < length {
+        let covered = coverage[start..length].iter().take_while(|&&coverage| coverage >= 1.)
+                                                   .count();
+        if covered > 0 {
+            composite_span(operator, source, &mut destination[start..start + covered]);
+            start += covered;
+        } else {
+            composite_with_coverage(compose, source, &mut destination[start], coverage[start]);
+            start += 1;
+        }
+    }
+}
+
+/// Composites `source` over every pixel of `destination`, as `operator_over` does for one.
+///
+/// Runs of pixels under one color are what fills and paints spend their time compositing, so
+/// this uses SSE2 or NEON instructions to do each pixel's four channels at once when the
+/// processor has them, which is checked at run time.  Otherwise it falls back to calling
+/// `operator_over` on each pixel.  Either way the results are exactly the same.
+///
+/// # Arguments
+/// * `source` - The source Rgba to be applied to every destination Rgba.
+/// * `destination` - The run of destination Rgbas that hold the resulting composition.
+///
+/// # Usage
+/// over_span(&Rgba::new(1., 0., 0., 0.5), &mut pixels);
+pub fn over_span(source: &Rgba, destination: &mut [Rgba]) {
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    {
+        if is_x86_feature_detected!("sse2") {
+            unsafe { over_span_sse2(source, destination) };
+            return;
+        }
+    }
+    #[cfg(target_arch = "aarch64")]
+    {
+        if ::std::arch::is_aarch64_feature_detected!("neon") {
+            unsafe { over_span_neon(source, destination) };
+            return;
+        }
+    }
+    for pixel in destination {
+        operator_over(source, pixel);
+    }
+}
+
+// over_span with SSE2.  Each lane computes source + destination * (1 - source alpha), the same
+// operations in the same order as operator_over, so the results match it bit for bit.  Rgba is
+// laid out as four f32s, so each pixel is loaded and stored in place.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "sse2")]
+unsafe fn over_span_sse2(source: &Rgba, destination: &mut [Rgba]) {
+    #[cfg(target_arch = "x86")]
+    use std::arch::x86::*;
+    #[cfg(target_arch = "x86_64")]
+    use std::arch::x86_64::*;
+
+    let source_lanes = _mm_loadu_ps(source as *const Rgba as *const f32);
+    let remaining = _mm_set1_ps(1. - source.alpha);
+    for pixel in destination {
+        let lanes = pixel as *mut Rgba as *mut f32;
+        _mm_storeu_ps(lanes, _mm_add_ps(source_lanes, _mm_mul_ps(_mm_loadu_ps(lanes), remaining)));
+    }
+}
+
+// over_span with NEON, computed as over_span_sse2 is.
+#[cfg(target_arch = "aarch64")]
+#[target_feature(enable = "neon")]
+unsafe fn over_span_neon(source: &Rgba, destination: &mut [Rgba]) {
+    use std::arch::aarch64::*;
+
+    let source_lanes = vld1q_f32(source as *const Rgba as *const f32);
+    let remaining = vdupq_n_f32(1. - source.alpha);
+    for pixel in destination {
+        let lanes = pixel as *mut Rgba as *mut f32;
+        vst1q_f32(lanes, vaddq_f32(source_lanes, vmulq_f32(vld1q_f32(lanes), remaining)));
+    }
+}
+
 /// Source operator. The destination object is overwritten with the source object. Result is
 /// equal to the source in both color values and alpha.
 pub fn operator_source(source: &Rgba, destination: &mut Rgba) {
@@ -168,30 +455,438 @@ pub fn operator_source(source: &Rgba, destination: &mut Rgba) {
 ///drawn where the destination was.
 ///Note: The transparency of the first object is still taken in to account.
 ///The effect of the IN operator depends on the interpretation of the source.
-///This operator is unbounded.
-///This function currently assumes post-multiplied alpha values, the alpha value
-///must be factored out
+///This operator is unbounded.  Assumes pre-multiplied alpha.
 pub fn operator_in(source: &Rgba, destination: &mut Rgba) {
-    destination.alpha = source.alpha * destination.alpha;
-    destination.red = source.red;
-    destination.green = source.green;
-    destination.blue = source.blue;
+    let destination_alpha = destination.alpha;
+    porter_duff(source, destination, destination_alpha, 0.);
+}
+
+// Sets every channel of `destination` to `source_factor * source + destination_factor *
+// destination`, the general form of the Porter Duff operators for pre-multiplied alpha.
+fn porter_duff(source: &Rgba, destination: &mut Rgba, source_factor: f32,
+               destination_factor: f32) {
+    destination.alpha = source.alpha * source_factor + destination.alpha * destination_factor;
+    destination.red = source.red * source_factor + destination.red * destination_factor;
+    destination.green = source.green * source_factor + destination.green * destination_factor;
+    destination.blue = source.blue * source_factor + destination.blue * destination_factor;
+}
+
+/// Clear operator.  The destination is cleared to transparent, whatever the source.
+pub fn operator_clear(_source: &Rgba, destination: &mut Rgba) {
+    *destination = Rgba::new(0., 0., 0., 0.);
+}
+
+/// Out operator.  The source is drawn only where the destination wasn't, and the destination is
+/// removed.  This operator is unbounded.  Assumes pre-multiplied alpha.
+pub fn operator_out(source: &Rgba, destination: &mut Rgba) {
+    let destination_alpha = destination.alpha;
+    porter_duff(source, destination, 1. - destination_alpha, 0.);
+}
+
+/// Atop operator.  The source is drawn on top of the destination, but only where the destination
+/// was, so the result keeps the destination's alpha.  Assumes pre-multiplied alpha.
+pub fn operator_atop(source: &Rgba, destination: &mut Rgba) {
+    let destination_alpha = destination.alpha;
+    porter_duff(source, destination, destination_alpha, 1. - source.alpha);
+}
+
+/// Dest operator.  The destination is left as it is.
+pub fn operator_dest(_source: &Rgba, _destination: &mut Rgba) {}
+
+/// DestOver operator.  The destination is drawn over the source, so the source only shows where
+/// the destination is transparent.  Assumes pre-multiplied alpha.
+pub fn operator_dest_over(source: &Rgba, destination: &mut Rgba) {
+    let destination_alpha = destination.alpha;
+    porter_duff(source, destination, 1. - destination_alpha, 1.);
 }
 
-/// # References
-/// [Porter Duff]: https://keithp.com/~keithp/porterduff/p253-porter.pdf).
-/// [Nvidia]: https://developer.nvidia.com/content/alpha-blending-pre-or-not-pre
-/// [Cairo Operators]: https://www.cairographics.org/operators/
+/// DestIn operator.  The destination is kept only where the source is.  This operator is
+/// unbounded.  Assumes pre-multiplied alpha.
+pub fn operator_dest_in(source: &Rgba, destination: &mut Rgba) {
+    porter_duff(source, destination, 0., source.alpha);
+}
+
+/// DestOut operator.  The destination is kept only where the source isn't.  Assumes
+/// pre-multiplied alpha.
+pub fn operator_dest_out(source: &Rgba, destination: &mut Rgba) {
+    porter_duff(source, destination, 0., 1. - source.alpha);
+}
+
+/// DestAtop operator.  The destination is drawn on top of the source, but only where the source
+/// is, so the result takes the source's alpha.  This operator is unbounded.  Assumes
+/// pre-multiplied alpha.
+pub fn operator_dest_atop(source: &Rgba, destination: &mut Rgba) {
+    let destination_alpha = destination.alpha;
+    porter_duff(source, destination, 1. - destination_alpha, source.alpha);
+}
+
+/// Xor operator.  The source and destination are each kept only where the other isn't.
+/// Assumes pre-multiplied alpha.
+pub fn operator_xor(source: &Rgba, destination: &mut Rgba) {
+    let destination_alpha = destination.alpha;
+    porter_duff(source, destination, 1. - destination_alpha, 1. - source.alpha);
+}
+
+/// Add operator.  The source and destination are added together, clamped to 1.  Assumes
+/// pre-multiplied alpha.
+pub fn operator_add(source: &Rgba, destination: &mut Rgba) {
+    porter_duff(source, destination, 1., 1.);
+    destination.correct();
+}
+
+/// Saturate operator.  Like Over, but the source is scaled down to fit in the transparency the
+/// destination has left, so drawing the same shape twice doesn't darken its edges.  Assumes
+/// pre-multiplied alpha.
+pub fn operator_saturate(source: &Rgba, destination: &mut Rgba) {
+    let room = 1. - destination.alpha;
+    let source_factor = if source.alpha > room { room / source.alpha } else { 1. };
+    porter_duff(source, destination, source_factor, 1.);
+}
+
+// # Blend Mode Formulas
+// The following functions implement the blend modes of the PDF specification (see the PDF blend
+// modes in the references section).  Separable blend functions mix one channel of the source and
+// destination colors at a time, non-separable ones whole colors; both without pre-multiplied
+// alpha.
+
+// A color's red, green and blue, without pre-multiplied alpha
+type Color = [f32; 3];
+
+// Composites `source` onto `destination` with the blend function `blend`.
+//
+// Where both are opaque the result is the blend of their colors; elsewhere each shows through in
+// proportion to the other's transparency, as with Over:
+//
+// `result = (1 - αd) * source + (1 - αs) * destination + αs * αd * blend(cs, cd)`
+//
+// where cs and cd are the colors with the alpha factored out.
+fn blend_colors<F: Fn(Color, Color) -> Color>(source: &Rgba, destination: &mut Rgba, blend: F) {
+    let (source_alpha, destination_alpha) = (source.alpha, destination.alpha);
+    let both = source_alpha * destination_alpha;
+    let source_color = [source.red, source.green, source.blue];
+    let destination_color = [destination.red, destination.green, destination.blue];
+    let blended = if both > 0. {
+        let unmultiply = |color: Color, alpha: f32| color.map(|channel| channel / alpha);
+        blend(unmultiply(source_color, source_alpha),
+              unmultiply(destination_color, destination_alpha))
+    } else {
+        [0.; 3]
+    };
+    let channel = |index: usize| {
+        (1. - destination_alpha) * source_color[index] +
+        (1. - source_alpha) * destination_color[index] + both * blended[index]
+    };
+    destination.red = channel(0);
+    destination.green = channel(1);
+    destination.blue = channel(2);
+    destination.alpha = source_alpha + destination_alpha - both;
+}
+
+// Composites `source` onto `destination` with the separable blend function `blend`, applied to
+// each channel.
+fn blend_separable(source: &Rgba, destination: &mut Rgba, blend: fn(f32, f32) -> f32) {
+    blend_colors(source, destination, |source, destination| {
+        [blend(source[0], destination[0]), blend(source[1], destination[1]),
+         blend(source[2], destination[2])]
+    });
+}
+
+fn blend_multiply(source: f32, destination: f32) -> f32 {
+    source * destination
+}
+
+fn blend_screen(source: f32, destination: f32) -> f32 {
+    source + destination - source * destination
+}
+
+fn blend_hard_light(source: f32, destination: f32) -> f32 {
+    if source <= 0.5 {
+        blend_multiply(destination, 2. * source)
+    } else {
+        blend_screen(destination, 2. * source - 1.)
+    }
+}
+
+fn blend_color_dodge(source: f32, destination: f32) -> f32 {
+    if destination <= 0. {
+        0.
+    } else if source >= 1. {
+        1.
+    } else {
+        (destination / (1. - source)).min(1.)
+    }
+}
+
+fn blend_color_burn(source: f32, destination: f32) -> f32 {
+    if destination >= 1. {
+        1.
+    } else if source <= 0. {
+        0.
+    } else {
+        1. - ((1. - destination) / source).min(1.)
+    }
+}
+
+fn blend_soft_light(source: f32, destination: f32) -> f32 {
+    if source <= 0.5 {
+        destination - (1. - 2. * source) * destination * (1. - destination)
+    } else {
+        let d = if destination <= 0.25 {
+            ((16. * destination - 12.) * destination + 4.) * destination
+        } else {
+            destination.sqrt()
+        };
+        destination + (2. * source - 1.) * (d - destination)
+    }
+}
+
+// Returns the luminosity of `color`, the weighted sum of its channels the PDF specification uses
+// in place of HSL lightness
+fn luminosity(color: Color) -> f32 {
+    0.3 * color[0] + 0.59 * color[1] + 0.11 * color[2]
+}
+
+// Returns the saturation of `color`, the spread between its largest and smallest channels
+fn saturation(color: Color) -> f32 {
+    color.iter().cloned().fold(f32::MIN, f32::max) - color.iter().cloned().fold(f32::MAX, f32::min)
+}
+
+// Returns `color` shifted to luminosity `lum`, pulled back towards grey if that takes any channel
+// out of range
+fn with_luminosity(color: Color, lum: f32) -> Color {
+    let shift = lum - luminosity(color);
+    let color = color.map(|channel| channel + shift);
+    let lum = luminosity(color);
+    let min = color.iter().cloned().fold(f32::MAX, f32::min);
+    let max = color.iter().cloned().fold(f32::MIN, f32::max);
+    if min < 0. {
+        color.map(|channel| lum + (channel - lum) * lum / (lum - min))
+    } else if max > 1. {
+        color.map(|channel| lum + (channel - lum) * (1. - lum) / (max - lum))
+    } else {
+        color
+    }
+}
+
+// Returns `color` with its saturation changed to `sat`, keeping the order of its channels
+fn with_saturation(color: Color, sat: f32) -> Color {
+    let mut order = [0, 1, 2];
+    order.sort_by(|&a, &b| color[a].partial_cmp(&color[b]).unwrap_or(Ordering::Equal));
+    let (min, mid, max) = (order[0], order[1], order[2]);
+    let mut result = [0.; 3];
+    if color[max] > color[min] {
+        result[mid] = (color[mid] - color[min]) * sat / (color[max] - color[min]);
+        result[max] = sat;
+    }
+    result
+}
+
+/// Multiply blend mode.  Multiplies the source and destination colors, which darkens the
+/// destination unless the source is white.  Assumes pre-multiplied alpha.
+pub fn operator_multiply(source: &Rgba, destination: &mut Rgba) {
+    blend_separable(source, destination, blend_multiply);
+}
+
+/// Screen blend mode.  Multiplies the complements of the source and destination colors, which
+/// lightens the destination unless the source is black.  Assumes pre-multiplied alpha.
+pub fn operator_screen(source: &Rgba, destination: &mut Rgba) {
+    blend_separable(source, destination, blend_screen);
+}
+
+/// Overlay blend mode.  Multiplies dark destination colors and screens light ones, keeping the
+/// destination's highlights and shadows.  Assumes pre-multiplied alpha.
+pub fn operator_overlay(source: &Rgba, destination: &mut Rgba) {
+    blend_separable(source, destination, |source, destination| {
+        blend_hard_light(destination, source)
+    });
+}
+
+/// Darken blend mode.  Keeps the darker of the source and destination colors.  Assumes
+/// pre-multiplied alpha.
+pub fn operator_darken(source: &Rgba, destination: &mut Rgba) {
+    blend_separable(source, destination, f32::min);
+}
+
+/// Lighten blend mode.  Keeps the lighter of the source and destination colors.  Assumes
+/// pre-multiplied alpha.
+pub fn operator_lighten(source: &Rgba, destination: &mut Rgba) {
+    blend_separable(source, destination, f32::max);
+}
+
+/// ColorDodge blend mode.  Brightens the destination color to reflect the source color.
+/// Assumes pre-multiplied alpha.
+pub fn operator_color_dodge(source: &Rgba, destination: &mut Rgba) {
+    blend_separable(source, destination, blend_color_dodge);
+}
+
+/// ColorBurn blend mode.  Darkens the destination color to reflect the source color.  Assumes
+/// pre-multiplied alpha.
+pub fn operator_color_burn(source: &Rgba, destination: &mut Rgba) {
+    blend_separable(source, destination, blend_color_burn);
+}
+
+/// HardLight blend mode.  Multiplies where the source is dark and screens where it is light, as
+/// if shining a harsh light of the source's color on the destination.  Assumes pre-multiplied
+/// alpha.
+pub fn operator_hard_light(source: &Rgba, destination: &mut Rgba) {
+    blend_separable(source, destination, blend_hard_light);
+}
+
+/// SoftLight blend mode.  Darkens where the source is dark and lightens where it is light, as if
+/// shining a diffused light of the source's color on the destination.  Assumes pre-multiplied
+/// alpha.
+pub fn operator_soft_light(source: &Rgba, destination: &mut Rgba) {
+    blend_separable(source, destination, blend_soft_light);
+}
+
+/// Difference blend mode.  Takes the absolute difference of the source and destination colors.
+/// Assumes pre-multiplied alpha.
+pub fn operator_difference(source: &Rgba, destination: &mut Rgba) {
+    blend_separable(source, destination, |source, destination| (source - destination).abs());
+}
+
+/// Exclusion blend mode.  Like Difference, but with lower contrast.  Assumes pre-multiplied
+/// alpha.
+pub fn operator_exclusion(source: &Rgba, destination: &mut Rgba) {
+    blend_separable(source, destination, |source, destination| {
+        source + destination - 2. * source * destination
+    });
+}
+
+/// Hue blend mode.  Takes the hue of the source color, with the saturation and luminosity of
+/// the destination color.  Assumes pre-multiplied alpha.
+pub fn operator_hsl_hue(source: &Rgba, destination: &mut Rgba) {
+    blend_colors(source, destination, |source, destination| {
+        with_luminosity(with_saturation(source, saturation(destination)), luminosity(destination))
+    });
+}
+
+/// Saturation blend mode.  Takes the saturation of the source color, with the hue and luminosity
+/// of the destination color.  Assumes pre-multiplied alpha.
+pub fn operator_hsl_saturation(source: &Rgba, destination: &mut Rgba) {
+    blend_colors(source, destination, |source, destination| {
+        with_luminosity(with_saturation(destination, saturation(source)), luminosity(destination))
+    });
+}
+
+/// Color blend mode.  Takes the hue and saturation of the source color, with the luminosity of
+/// the destination color, which tints the destination.  Assumes pre-multiplied alpha.
+pub fn operator_hsl_color(source: &Rgba, destination: &mut Rgba) {
+    blend_colors(source, destination, |source, destination| {
+        with_luminosity(source, luminosity(destination))
+    });
+}
+
+/// Luminosity blend mode.  Takes the luminosity of the source color, with the hue and saturation
+/// of the destination color.  Assumes pre-multiplied alpha.
+pub fn operator_hsl_luminosity(source: &Rgba, destination: &mut Rgba) {
+    blend_colors(source, destination, |source, destination| {
+        with_luminosity(destination, luminosity(source))
+    });
+}
+
+// # References
+// [Porter Duff]: https://keithp.com/~keithp/porterduff/p253-porter.pdf).
+// [Nvidia]: https://developer.nvidia.com/content/alpha-blending-pre-or-not-pre
+// [Cairo Operators]: https://www.cairographics.org/operators/
+// [PDF blend modes]: https://www.adobe.com/content/dam/acom/en/devnet/pdf/pdfs/PDF32000_2008.pdf
+//     (section 11.3.5)
 
 #[cfg(test)]
 mod tests {
     use super::Operator;
     use super::operator_over;
+    use super::over_span;
+    use super::{composite_span, composite_span_with_coverage};
     use super::operator_in;
     use super::operator_source;
     use super::fetch_operator;
+    use super::porter_duff;
+    use super::{luminosity, saturation, with_luminosity, with_saturation};
+    use super::composite_with_coverage;
     use types::Rgba;
 
+    #[test]
+    fn test_operator_names() {
+        for operator in Operator::all() {
+            assert_eq!(&operator.to_string().parse::<Operator>().unwrap(), operator);
+        }
+        assert_eq!(Operator::all().len(), 29);
+        assert_eq!("color-dodge".parse::<Operator>(), Ok(Operator::ColorDodge));
+        assert_eq!("dest-over".parse::<Operator>(), Ok(Operator::DestOver));
+        assert_eq!(Operator::DestAtop.to_string(), "dest-atop");
+        assert_eq!("SOURCE".parse::<Operator>(), Ok(Operator::Source));
+        assert!("overr".parse::<Operator>().is_err());
+    }
+
+    #[test]
+    fn test_over_span_matches_over() {
+        // Setup
+        let source = Rgba::new(0.3, 0.6, 0.9, 0.45);
+        let mut destination: Vec<Rgba> = (0..37).map(|index| {
+            let value = index as f32 / 37.;
+            Rgba::new(value, 1. - value, value * value, 1. - value / 2.)
+        }).collect();
+        let mut expected = destination.clone();
+
+        // Call
+        over_span(&source, &mut destination);
+        for pixel in &mut expected {
+            operator_over(&source, pixel);
+        }
+
+        // Test
+        for (pixel, expected) in destination.iter().zip(&expected) {
+            assert_eq!(pixel.red.to_bits(), expected.red.to_bits());
+            assert_eq!(pixel.green.to_bits(), expected.green.to_bits());
+            assert_eq!(pixel.blue.to_bits(), expected.blue.to_bits());
+            assert_eq!(pixel.alpha.to_bits(), expected.alpha.to_bits());
+        }
+        over_span(&source, &mut []);
+    }
+
+    #[test]
+    fn test_composite_span_matches_operators() {
+        let sources = [Rgba::new(0.2, 0.4, 0.6, 1.), Rgba::new(0.2, 0.4, 0.6, 0.5)];
+        let operators = [Operator::Over, Operator::Source, Operator::Xor];
+        for source in sources.iter() {
+            for operator in operators.iter() {
+                // Setup
+                let mut destination: Vec<Rgba> = (0..9).map(|index| {
+                    Rgba::new(index as f32 / 9., 0.5, 1. - index as f32 / 9., index as f32 / 8.)
+                }).collect();
+                let mut expected = destination.clone();
+
+                // Call
+                composite_span(operator, source, &mut destination);
+                for pixel in &mut expected {
+                    fetch_operator(operator)(source, pixel);
+                }
+
+                // Test
+                assert_eq!(destination, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_composite_span_with_coverage_matches_per_pixel() {
+        // Setup
+        let source = Rgba::new(0.9, 0.1, 0.3, 1.);
+        let coverage = [0., 1., 1., 0.25, 1., 0.5, 0., 1.];
+        let mut destination = vec![Rgba::new(0.1, 0.5, 0.8, 0.5); coverage.len()];
+        let mut expected = destination.clone();
+
+        // Call
+        composite_span_with_coverage(&Operator::Over, &source, &mut destination, &coverage);
+        for (pixel, &coverage) in expected.iter_mut().zip(coverage.iter()) {
+            composite_with_coverage(operator_over, &source, pixel, coverage);
+        }
+
+        // Test
+        assert_eq!(destination, expected);
+    }
+
     #[test]
     fn test_over_operator_semi_transparent_source() {
         let source = Rgba::new(1., 0., 0., 0.5);
@@ -254,9 +949,9 @@ mod tests {
         let mut destination = Rgba::new(0., 1., 0., 0.5);
         fetch_operator(&Operator::In)(&source, &mut destination);
         let test_rgba = Rgba{
-            red:0.5,
-            green:0.5,
-            blue:0.5,
+            red:0.25,
+            green:0.25,
+            blue:0.25,
             alpha:0.25
         };
         assert_eq!(destination, test_rgba);
@@ -268,9 +963,9 @@ mod tests {
         let mut destination = Rgba::new(1., 1., 1., 0.5);
         operator_in(&source, &mut destination);
         let test_rgba =  Rgba{
-            red:0.5,
-            green:0.5,
-            blue:0.5,
+            red:0.25,
+            green:0.25,
+            blue:0.25,
             alpha:0.5
         };
         assert_eq!(destination, test_rgba);
@@ -306,15 +1001,253 @@ mod tests {
         let mut destination = Rgba::new(1.0, 1.0, 1.0, 0.0);
         operator_in(&source, &mut destination);
         let test_rgba = Rgba{
-            red:0.5,
-            green:0.5,
-            blue:0.5,
+            red:0.0,
+            green:0.0,
+            blue:0.0,
             alpha:0.0
         };
         assert_eq!(destination, test_rgba);
     }
 
 
+    // Returns an Rgba from channels that are already pre-multiplied
+    fn rgba(red: f32, green: f32, blue: f32, alpha: f32) -> Rgba {
+        Rgba { red, green, blue, alpha }
+    }
+
+    // Composites a half transparent red source onto a half transparent green destination
+    fn composite(operator: Operator) -> Rgba {
+        let source = rgba(0.5, 0., 0., 0.5);
+        let mut destination = rgba(0., 0.5, 0., 0.5);
+        fetch_operator(&operator)(&source, &mut destination);
+        destination
+    }
+
+    #[test]
+    fn test_porter_duff() {
+        let mut destination = rgba(0., 0.5, 0., 0.5);
+        porter_duff(&rgba(0.5, 0., 0., 0.5), &mut destination, 0.5, 0.25);
+        assert_eq!(destination, rgba(0.25, 0.125, 0., 0.375));
+    }
+
+    #[test]
+    fn test_clear_operator() {
+        assert_eq!(composite(Operator::Clear), rgba(0., 0., 0., 0.));
+    }
+
+    #[test]
+    fn test_out_operator() {
+        assert_eq!(composite(Operator::Out), rgba(0.25, 0., 0., 0.25));
+    }
+
+    #[test]
+    fn test_atop_operator() {
+        assert_eq!(composite(Operator::Atop), rgba(0.25, 0.25, 0., 0.5));
+    }
+
+    #[test]
+    fn test_dest_operator() {
+        assert_eq!(composite(Operator::Dest), rgba(0., 0.5, 0., 0.5));
+    }
+
+    #[test]
+    fn test_dest_over_operator() {
+        assert_eq!(composite(Operator::DestOver), rgba(0.25, 0.5, 0., 0.75));
+    }
+
+    #[test]
+    fn test_dest_in_operator() {
+        assert_eq!(composite(Operator::DestIn), rgba(0., 0.25, 0., 0.25));
+    }
+
+    #[test]
+    fn test_dest_out_operator() {
+        assert_eq!(composite(Operator::DestOut), rgba(0., 0.25, 0., 0.25));
+    }
+
+    #[test]
+    fn test_dest_atop_operator() {
+        assert_eq!(composite(Operator::DestAtop), rgba(0.25, 0.25, 0., 0.5));
+    }
+
+    #[test]
+    fn test_xor_operator() {
+        assert_eq!(composite(Operator::Xor), rgba(0.25, 0.25, 0., 0.5));
+    }
+
+    #[test]
+    fn test_add_operator() {
+        assert_eq!(composite(Operator::Add), rgba(0.5, 0.5, 0., 1.));
+
+        // Sums past 1 are clamped
+        let mut destination = rgba(0.75, 0., 0., 0.75);
+        fetch_operator(&Operator::Add)(&rgba(0.5, 0., 0., 0.5), &mut destination);
+        assert_eq!(destination, rgba(1., 0., 0., 1.));
+    }
+
+    #[test]
+    fn test_saturate_operator() {
+        // The source fits in the room left by the destination, so this is the same as Add
+        assert_eq!(composite(Operator::Saturate), rgba(0.5, 0.5, 0., 1.));
+
+        // Only a quarter of an opaque source fits over a destination of alpha 3/4
+        let mut destination = rgba(0., 0.5, 0., 0.75);
+        fetch_operator(&Operator::Saturate)(&rgba(1., 0., 0., 1.), &mut destination);
+        assert_eq!(destination, rgba(0.25, 0.5, 0., 1.));
+    }
+
+    // Blends opaque colors, where the result is the blend function alone
+    fn blend_opaque(operator: Operator, source: (f32, f32, f32), destination: (f32, f32, f32))
+                    -> Rgba {
+        let source = rgba(source.0, source.1, source.2, 1.);
+        let mut destination = rgba(destination.0, destination.1, destination.2, 1.);
+        fetch_operator(&operator)(&source, &mut destination);
+        destination
+    }
+
+    #[test]
+    fn test_blend_transparency() {
+        // Where only one of source and destination is present, blend modes act like Over
+        for operator in &[Operator::Multiply, Operator::Screen, Operator::Difference] {
+            let mut destination = rgba(0., 0., 0., 0.);
+            fetch_operator(operator)(&rgba(0.25, 0.5, 0., 0.5), &mut destination);
+            assert_eq!(destination, rgba(0.25, 0.5, 0., 0.5));
+
+            let mut destination = rgba(0.25, 0.5, 0., 0.5);
+            fetch_operator(operator)(&rgba(0., 0., 0., 0.), &mut destination);
+            assert_eq!(destination, rgba(0.25, 0.5, 0., 0.5));
+        }
+
+        // Half transparent over half transparent: a quarter is each alone, a quarter blended
+        let mut destination = rgba(0.5, 0.5, 0.5, 0.5);
+        fetch_operator(&Operator::Multiply)(&rgba(0.5, 0., 0.25, 0.5), &mut destination);
+        assert_eq!(destination, rgba(0.75, 0.25, 0.5, 0.75));
+    }
+
+    #[test]
+    fn test_multiply_operator() {
+        let result = blend_opaque(Operator::Multiply, (0.5, 1., 0.), (0.5, 0.5, 0.5));
+        assert_eq!(result, rgba(0.25, 0.5, 0., 1.));
+    }
+
+    #[test]
+    fn test_screen_operator() {
+        let result = blend_opaque(Operator::Screen, (0.5, 1., 0.), (0.5, 0.5, 0.5));
+        assert_eq!(result, rgba(0.75, 1., 0.5, 1.));
+    }
+
+    #[test]
+    fn test_overlay_operator() {
+        // Multiplies dark destinations (doubled) and screens light ones
+        let result = blend_opaque(Operator::Overlay, (0.5, 0.5, 0.5), (0.25, 0.75, 0.5));
+        assert_eq!(result, rgba(0.25, 0.75, 0.5, 1.));
+        let result = blend_opaque(Operator::Overlay, (1., 0., 1.), (0.25, 0.75, 0.5));
+        assert_eq!(result, rgba(0.5, 0.5, 1., 1.));
+    }
+
+    #[test]
+    fn test_darken_lighten_operators() {
+        let darken = blend_opaque(Operator::Darken, (0.25, 1., 0.5), (0.5, 0.5, 0.5));
+        let lighten = blend_opaque(Operator::Lighten, (0.25, 1., 0.5), (0.5, 0.5, 0.5));
+        assert_eq!(darken, rgba(0.25, 0.5, 0.5, 1.));
+        assert_eq!(lighten, rgba(0.5, 1., 0.5, 1.));
+    }
+
+    #[test]
+    fn test_color_dodge_operator() {
+        let result = blend_opaque(Operator::ColorDodge, (0.5, 1., 0.75), (0.25, 0.5, 0.));
+        assert_eq!(result, rgba(0.5, 1., 0., 1.));
+    }
+
+    #[test]
+    fn test_color_burn_operator() {
+        let result = blend_opaque(Operator::ColorBurn, (0.5, 0., 1.), (0.75, 0.5, 1.));
+        assert_eq!(result, rgba(0.5, 0., 1., 1.));
+    }
+
+    #[test]
+    fn test_hard_light_operator() {
+        // Overlay with source and destination swapped
+        let result = blend_opaque(Operator::HardLight, (0.25, 0.75, 0.5), (1., 0., 1.));
+        assert_eq!(result, rgba(0.5, 0.5, 1., 1.));
+    }
+
+    #[test]
+    fn test_soft_light_operator() {
+        // Neutral grey leaves the destination alone; darker and lighter sources move it
+        let result = blend_opaque(Operator::SoftLight, (0.5, 0., 1.), (0.25, 0.5, 0.25));
+        assert_eq!(result, rgba(0.25, 0.25, 0.5, 1.));
+        let result = blend_opaque(Operator::SoftLight, (1., 1., 1.), (0.04, 0.5, 1.));
+        assert!((result.red - 0.141824).abs() < 1e-6);
+        assert!((result.green - 0.70710677).abs() < 1e-6);
+        assert_eq!(result.blue, 1.);
+    }
+
+    #[test]
+    fn test_difference_exclusion_operators() {
+        let difference = blend_opaque(Operator::Difference, (0.25, 1., 0.5), (0.75, 0.5, 0.5));
+        let exclusion = blend_opaque(Operator::Exclusion, (0.25, 1., 0.5), (0.75, 0.5, 0.5));
+        assert_eq!(difference, rgba(0.5, 0.5, 0., 1.));
+        assert_eq!(exclusion, rgba(0.625, 0.5, 0.5, 1.));
+    }
+
+    #[test]
+    fn test_luminosity_and_saturation() {
+        assert!((luminosity([1., 1., 1.]) - 1.).abs() < 1e-6);
+        assert_eq!(luminosity([0., 1., 0.]), 0.59);
+        assert_eq!(saturation([0.25, 1., 0.5]), 0.75);
+        let color = with_saturation([0.25, 1., 0.5], 0.5);
+        assert_eq!((color[0], color[1]), (0., 0.5));
+        assert!((color[2] - 1. / 6.).abs() < 1e-6);
+        assert_eq!(with_saturation([0.5, 0.5, 0.5], 0.5), [0., 0., 0.]);
+
+        // Shifting luminosity out of range pulls the color back towards grey
+        let color = with_luminosity([0., 1., 0.], 0.8);
+        assert!((luminosity(color) - 0.8).abs() < 1e-6);
+        assert!(color.iter().all(|&channel| (0. ..=1.).contains(&channel)));
+    }
+
+    #[test]
+    fn test_hsl_hue_operator() {
+        // A red hue on a grey destination has no saturation to take, so stays grey
+        let result = blend_opaque(Operator::HslHue, (1., 0., 0.), (0.5, 0.5, 0.5));
+        assert_eq!(result, rgba(0.5, 0.5, 0.5, 1.));
+        // On a saturated blue, it becomes a red of the same saturation and luminosity
+        let result = blend_opaque(Operator::HslHue, (1., 0., 0.), (0., 0., 0.5));
+        assert!((result.red - 0.183333).abs() < 1e-5);
+        assert!(result.green.abs() < 1e-6 && result.blue.abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_hsl_saturation_operator() {
+        // A grey source removes all saturation, leaving the destination's luminosity
+        let result = blend_opaque(Operator::HslSaturation, (0.5, 0.5, 0.5), (1., 0., 0.));
+        assert!((result.red - 0.3).abs() < 1e-6);
+        assert!((result.green - 0.3).abs() < 1e-6);
+        assert!((result.blue - 0.3).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_hsl_color_operator() {
+        // Tinting white with a color gives the lightest shade of the color that fits
+        let result = blend_opaque(Operator::HslColor, (0., 0., 1.), (1., 1., 1.));
+        assert!((result.red - 1.).abs() < 1e-6);
+        assert!((result.blue - 1.).abs() < 1e-6);
+
+        // Tinting grey with grey changes nothing
+        let result = blend_opaque(Operator::HslColor, (0.25, 0.25, 0.25), (0.5, 0.5, 0.5));
+        assert!((result.red - 0.5).abs() < 1e-6 && (result.green - 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_hsl_luminosity_operator() {
+        // A white source makes the destination white, a black one black
+        let result = blend_opaque(Operator::HslLuminosity, (1., 1., 1.), (0., 0., 1.));
+        assert!((luminosity([result.red, result.green, result.blue]) - 1.).abs() < 1e-6);
+        let result = blend_opaque(Operator::HslLuminosity, (0., 0., 0.), (0., 0., 1.));
+        assert_eq!(result, rgba(0., 0., 0., 1.));
+    }
+
     #[test]
     fn test_rgba_into_bytes_all_ones() {
         let color = Rgba::new(1., 1., 1., 1.);
@@ -329,10 +1262,18 @@ mod tests {
         assert_eq!(color.into_bytes(), expected);
     }
 
+    #[test]
+    fn test_operator_is_bounded() {
+        let unbounded = [Operator::In, Operator::Out, Operator::DestIn, Operator::DestAtop];
+        for operator in Operator::all() {
+            assert_eq!(operator.is_bounded(), !unbounded.contains(operator));
+        }
+    }
+
     #[test]
     fn test_rgba_into_bytes_all_half() {
         let color = Rgba::new(0.5, 0.5, 0.5, 0.5);
-        let expected = vec![127, 127, 127, 127];
+        let expected = vec![128, 128, 128, 128];
         assert_eq!(color.into_bytes(), expected);
     }
 
@@ -350,6 +1291,45 @@ mod tests {
         assert_eq!(color, Rgba::new(0., 0., 0., 0.));
     }
 
+    #[test]
+    fn test_composite_with_full_coverage() {
+        // Full coverage is the same as applying the operator directly
+        let source = Rgba::new(1., 0., 0., 0.5);
+        let mut destination = Rgba::new(0., 1., 0., 0.5);
+        let mut expected = destination;
+        operator_over(&source, &mut expected);
+        composite_with_coverage(operator_over, &source, &mut destination, 1.);
+        assert_eq!(destination, expected);
+    }
+
+    #[test]
+    fn test_composite_with_no_coverage() {
+        // Zero coverage leaves the destination untouched, even for Source
+        let source = Rgba::new(1., 0., 0., 1.);
+        let mut destination = Rgba::new(0., 1., 0., 0.5);
+        composite_with_coverage(operator_source, &source, &mut destination, 0.);
+        assert_eq!(destination, Rgba::new(0., 1., 0., 0.5));
+    }
+
+    #[test]
+    fn test_composite_with_half_coverage_over() {
+        // Half coverage with Over is the same as a half-transparent source
+        let source = Rgba::new(1., 0., 0., 1.);
+        let mut destination = Rgba::new(0., 0., 1., 1.);
+        composite_with_coverage(operator_over, &source, &mut destination, 0.5);
+        assert_eq!(destination, Rgba::new(0.5, 0., 0.5, 1.));
+    }
+
+    #[test]
+    fn test_composite_with_half_coverage_source() {
+        // Half coverage with Source blends between the source and the destination instead of
+        // making the destination half-transparent.
+        let source = Rgba::new(1., 0., 0., 1.);
+        let mut destination = Rgba::new(0., 0., 1., 1.);
+        composite_with_coverage(operator_source, &source, &mut destination, 0.5);
+        assert_eq!(destination, Rgba::new(0.5, 0., 0.5, 1.));
+    }
+
     #[test]
     fn test_fetch_operator() {
         let source = Rgba::new(1., 0., 0., 0.5);
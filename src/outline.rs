@@ -0,0 +1,261 @@
+/*
+ * Cairus - a reimplementation of the cairo graphics library in Rust
+ *
+ * Copyright © 2017 CairusOrg
+ *
+ * This library is free software; you can redistribute it and/or
+ * modify it either under the terms of the GNU Lesser General Public
+ * License version 2.1 as published by the Free Software Foundation
+ * (the "LGPL") or, at your option, under the terms of the Mozilla
+ * Public License Version 2.0 (the "MPL"). If you do not alter this
+ * notice, a recipient may use your version of this file under either
+ * the MPL or the LGPL.
+ *
+ * You should have received a copy of the LGPL along with this library
+ * in the file LICENSE-LGPL-2_1; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Suite 500, Boston, MA 02110-1335, USA
+ * You should have received a copy of the MPL along with this library
+ * in the file LICENSE-MPL-2_0
+ *
+ * The contents of this file are subject to the Mozilla Public License
+ * Version 2.0 (the "License"); you may not use this file except in
+ * compliance with the License. You may obtain a copy of the License at
+ * http://www.mozilla.org/MPL/
+ *
+ * This software is distributed on an "AS IS" basis, WITHOUT WARRANTY
+ * OF ANY KIND, either express or implied. See the LGPL or the MPL for
+ * the specific language governing rights and limitations.
+ *
+ * The Original Code is the cairus graphics library.
+ *
+ * Contributor(s):
+ *	CairusOrg
+ *
+ */
+//! Outlines of the area the trapezoid sweep fills.
+//!
+//! The slabs `bo_trap::sweep_slabs` returns tile the filled area without overlapping.  Walking
+//! each slab's sides the same way round, the sides two slabs share are walked once each way, so
+//! adding the sides up as signed intervals along each edge and each horizontal cancels them,
+//! and leaves only the boundary of the area.  That boundary is then chained into closed loops.
+//!
+//! Every corner comes from `LineSegment::current_x_for_y` on the slabs' edges, so pieces that
+//! meet share their end points exactly, and the chaining compares points bit for bit.
+
+use std::collections::HashMap;
+use bo_trap::Slab;
+use common_geometry::{LineSegment, Point};
+use path::Path;
+
+// What a piece of the outline lies along, and which way it runs along it.  Consecutive pieces
+// along the same thing the same way are joined into one line.
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Support {
+    Horizontal(u32, bool),
+    Edge(usize, bool),
+}
+
+// A piece of the outline, from one point to the next
+#[derive(Debug, Copy, Clone)]
+struct Piece {
+    from: Point,
+    to: Point,
+    support: Support,
+}
+
+/// Returns closed polygons around the area `slabs` cover.
+///
+/// The polygons don't cross themselves or each other, though they can touch at a corner.  Outer
+/// boundaries are wound so that their signed area is positive, and holes the other way, so the
+/// result fills the same area under either fill rule.
+pub fn outline(slabs: &[Slab]) -> Path {
+    let pieces = boundary_pieces(slabs);
+    let mut path = Path::new();
+    for ring in chain(&pieces) {
+        let corners = corners(&ring);
+        if corners.len() < 3 {
+            continue;
+        }
+        path.move_to(corners[0].x, corners[0].y);
+        for corner in corners[1..].iter() {
+            path.line_to(corner.x, corner.y);
+        }
+        path.close_path();
+    }
+    path
+}
+
+// Returns the key points are compared by, with -0 and 0 the same
+fn point_key(point: Point) -> (u32, u32) {
+    ((point.x + 0.).to_bits(), (point.y + 0.).to_bits())
+}
+
+fn line_key(line: &LineSegment) -> [u32; 4] {
+    let (point1, point2) = (point_key(line.point1), point_key(line.point2));
+    [point1.0, point1.1, point2.0, point2.1]
+}
+
+// Adds up signed intervals, given as (start, end, count), and returns the pieces where the
+// count isn't zero, with their counts, in order
+fn sum_intervals(intervals: &[(f32, f32, i32)]) -> Vec<(f32, f32, i32)> {
+    let mut changes: Vec<(f32, i32)> = Vec::with_capacity(intervals.len() * 2);
+    for &(start, end, count) in intervals.iter().filter(|interval| interval.0 < interval.1) {
+        changes.push((start, count));
+        changes.push((end, -count));
+    }
+    changes.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let mut sums = Vec::new();
+    let mut count = 0;
+    for (index, &(position, change)) in changes.iter().enumerate() {
+        count += change;
+        if let Some(&(next, _)) = changes.get(index + 1) {
+            if count != 0 && next > position {
+                sums.push((position, next, count));
+            }
+        }
+    }
+    sums
+}
+
+// Returns the pieces of the slabs' sides that aren't shared with another slab, each slab walked
+// with its top left to right, its right side down, its bottom right to left and its left side
+// up, which is the way round with a positive signed area
+fn boundary_pieces(slabs: &[Slab]) -> Vec<Piece> {
+    let mut lines: Vec<LineSegment> = Vec::new();
+    let mut line_indices: HashMap<[u32; 4], usize> = HashMap::new();
+    let mut sides: Vec<Vec<(f32, f32, i32)>> = Vec::new();
+    let mut bases: HashMap<u32, Vec<(f32, f32, i32)>> = HashMap::new();
+
+    for slab in slabs.iter().filter(|slab| slab.top < slab.bottom) {
+        for &(line, count) in [(slab.left, -1), (slab.right, 1)].iter() {
+            let index = *line_indices.entry(line_key(&line)).or_insert_with(|| {
+                lines.push(line);
+                sides.push(Vec::new());
+                lines.len() - 1
+            });
+            sides[index].push((slab.top, slab.bottom, count));
+        }
+        for &(y, count) in [(slab.top, 1), (slab.bottom, -1)].iter() {
+            let (left, right) = (slab.left.current_x_for_y(y), slab.right.current_x_for_y(y));
+            let (start, end, count) = if left <= right {
+                (left, right, count)
+            } else {
+                (right, left, -count)
+            };
+            bases.entry((y + 0.).to_bits()).or_default().push((start, end, count));
+        }
+    }
+
+    let mut pieces = Vec::new();
+    let mut push = |from: Point, to: Point, count: i32, support: Support| {
+        let (from, to) = if count > 0 { (from, to) } else { (to, from) };
+        for _ in 0..count.abs() {
+            pieces.push(Piece { from, to, support });
+        }
+    };
+    for (index, line) in lines.iter().enumerate() {
+        for (top, bottom, count) in sum_intervals(&sides[index]) {
+            let top_point = Point::new(line.current_x_for_y(top), top);
+            let bottom_point = Point::new(line.current_x_for_y(bottom), bottom);
+            push(top_point, bottom_point, count, Support::Edge(index, count > 0));
+        }
+    }
+    // In order down the page, so the result doesn't depend on the order of the hash map
+    let mut bases: Vec<_> = bases.into_iter().collect();
+    bases.sort_by(|a, b| f32::from_bits(a.0).total_cmp(&f32::from_bits(b.0)));
+    for (y_key, intervals) in bases {
+        let y = f32::from_bits(y_key);
+        for (start, end, count) in sum_intervals(&intervals) {
+            push(Point::new(start, y), Point::new(end, y), count,
+                 Support::Horizontal(y_key, count > 0));
+        }
+    }
+    pieces
+}
+
+// Chains the pieces into closed rings, following each piece with one that starts where it ends
+fn chain(pieces: &[Piece]) -> Vec<Vec<Piece>> {
+    let mut starting_at: HashMap<(u32, u32), Vec<usize>> = HashMap::new();
+    for (index, piece) in pieces.iter().enumerate().rev() {
+        starting_at.entry(point_key(piece.from)).or_default().push(index);
+    }
+
+    let mut used = vec![false; pieces.len()];
+    let mut rings = Vec::new();
+    for first in 0..pieces.len() {
+        if used[first] {
+            continue;
+        }
+        let start = point_key(pieces[first].from);
+        let mut ring = Vec::new();
+        let mut current = Some(first);
+        while let Some(index) = current {
+            used[index] = true;
+            ring.push(pieces[index]);
+            let end = point_key(pieces[index].to);
+            if end == start {
+                break;
+            }
+            // Every point is left as often as it is reached, so this only runs out for rings
+            // that rounding has broken, which are dropped
+            current = starting_at.get_mut(&end).and_then(|indices| {
+                while let Some(next) = indices.pop() {
+                    if !used[next] {
+                        return Some(next);
+                    }
+                }
+                None
+            });
+        }
+        if current.is_some() {
+            rings.extend(split_at_repeats(ring));
+        }
+    }
+    rings
+}
+
+// Splits `ring` wherever it comes back to a point it has already left, so that rings touching
+// at a corner come out separately rather than as one ring through the corner twice
+fn split_at_repeats(ring: Vec<Piece>) -> Vec<Vec<Piece>> {
+    let mut rings = Vec::new();
+    let mut remaining: Vec<Piece> = Vec::with_capacity(ring.len());
+    // Where in `remaining` the piece leaving each point is
+    let mut leaving: HashMap<(u32, u32), usize> = HashMap::new();
+    for piece in ring {
+        if let Some(&position) = leaving.get(&point_key(piece.from)) {
+            let inner = remaining.split_off(position);
+            for inner_piece in inner.iter() {
+                leaving.remove(&point_key(inner_piece.from));
+            }
+            rings.push(inner);
+        }
+        leaving.insert(point_key(piece.from), remaining.len());
+        remaining.push(piece);
+    }
+    rings.push(remaining);
+    rings
+}
+
+// Returns the corners of `ring`, starting from the top left one, and leaving out the points
+// between pieces that carry on along the same line, or along different lines that run exactly
+// the same way
+fn corners(ring: &[Piece]) -> Vec<Point> {
+    let mut corners: Vec<Point> = (0..ring.len())
+        .filter(|&index| {
+            let (previous, piece) = (ring[(index + ring.len() - 1) % ring.len()], ring[index]);
+            let (dx, dy) = (previous.to.x - previous.from.x, previous.to.y - previous.from.y);
+            let (next_dx, next_dy) = (piece.to.x - piece.from.x, piece.to.y - piece.from.y);
+            let straight = dx * next_dy == dy * next_dx && dx * next_dx + dy * next_dy > 0.;
+            previous.support != piece.support && !straight
+        })
+        .map(|index| ring[index].from)
+        .collect();
+    let top_left = (0..corners.len()).min_by(|&a, &b| {
+        corners[a].y.total_cmp(&corners[b].y).then(corners[a].x.total_cmp(&corners[b].x))
+    });
+    if let Some(top_left) = top_left {
+        corners.rotate_left(top_left);
+    }
+    corners
+}
@@ -0,0 +1,442 @@
+/*
+ * Cairus - a reimplementation of the cairo graphics library in Rust
+ *
+ * Copyright © 2017 CairusOrg
+ *
+ * This library is free software; you can redistribute it and/or
+ * modify it either under the terms of the GNU Lesser General Public
+ * License version 2.1 as published by the Free Software Foundation
+ * (the "LGPL") or, at your option, under the terms of the Mozilla
+ * Public License Version 2.0 (the "MPL"). If you do not alter this
+ * notice, a recipient may use your version of this file under either
+ * the MPL or the LGPL.
+ *
+ * You should have received a copy of the LGPL along with this library
+ * in the file LICENSE-LGPL-2_1; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Suite 500, Boston, MA 02110-1335, USA
+ * You should have received a copy of the MPL along with this library
+ * in the file LICENSE-MPL-2_0
+ *
+ * The contents of this file are subject to the Mozilla Public License
+ * Version 2.0 (the "License"); you may not use this file except in
+ * compliance with the License. You may obtain a copy of the License at
+ * http://www.mozilla.org/MPL/
+ *
+ * This software is distributed on an "AS IS" basis, WITHOUT WARRANTY
+ * OF ANY KIND, either express or implied. See the LGPL or the MPL for
+ * the specific language governing rights and limitations.
+ *
+ * The Original Code is the cairus graphics library.
+ *
+ * Contributor(s):
+ *	CairusOrg
+ *
+ */
+
+//! The shared core of the vector output backends.
+//!
+//! A vector backend, such as PostScript, writes drawing out as drawing rather than pixels, and
+//! can only do so once a page is complete.  A `PaginatedSurface` records a document's drawing as
+//! a `CommandBuffer`, with show_page() and copy_page() marking where each page ends.  When the
+//! document is written, `render_document` replays it and hands every drawing operation to the
+//! backend's `VectorRenderer` page by page, already reduced to paths in page coordinates with the
+//! source, operator and clip in effect, so backends only have to say how to write fills,
+//! strokes, paints and clips.
+//!
+//! The replay runs each command on a `Context` over a one pixel scratch surface, so paths,
+//! transformations and every other piece of state follow exactly the rules drawing on an image
+//! does.
+
+use command_buffer::{Command, CommandBuffer};
+use context::Context;
+use dash::DashPattern;
+use matrix::Matrix;
+use operators::Operator;
+use path::Path;
+use pattern::Pattern;
+use stroke::StrokeStyle;
+use surfaces::{Format, ImageSurface};
+use types::{FillRule, LineCap};
+
+/// ## VectorRenderer
+///
+/// Writes drawing operations out in a vector format.  Paths are in page coordinates, with the
+/// origin at the top left of the page and y pointing down, unless stated otherwise.
+pub trait VectorRenderer {
+    /// Starts page `index`, counting from 0.  Every page starts unclipped.
+    fn begin_page(&mut self, index: usize);
+
+    /// Finishes the page started by the last `begin_page`.  The clip has been removed by then.
+    fn end_page(&mut self);
+
+    /// Fills the area inside `path` under `fill_rule` with `source`.
+    fn fill(&mut self, path: &Path, fill_rule: FillRule, source: &Pattern, operator: Operator);
+
+    /// Strokes `path`, which is in user space, with `matrix` taking user space to the page.  The
+    /// line width and dashes are in user space too.
+    fn stroke(&mut self, path: &Path, matrix: &Matrix, style: &StrokeStyle,
+              dash: Option<&DashPattern>, source: &Pattern, operator: Operator);
+
+    /// Paints `source` everywhere inside the clip.
+    fn paint(&mut self, source: &Pattern, operator: Operator);
+
+    /// Intersects the clip with the area inside `path` under `fill_rule`.
+    fn clip(&mut self, path: &Path, fill_rule: FillRule);
+
+    /// Removes the clip.
+    fn reset_clip(&mut self);
+}
+
+/// ## PaginatedSurface
+///
+/// A document of pages `width` by `height` units each, recorded for a vector backend.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PaginatedSurface {
+    width: f32,
+    height: f32,
+    commands: CommandBuffer,
+}
+
+impl PaginatedSurface {
+    /// Returns a document with one empty page of `width` by `height` units.
+    pub fn new(width: f32, height: f32) -> PaginatedSurface {
+        PaginatedSurface {
+            width,
+            height,
+            commands: CommandBuffer::new(),
+        }
+    }
+
+    /// Returns the size of a page as (width, height).
+    pub fn get_size(&self) -> (f32, f32) {
+        (self.width, self.height)
+    }
+
+    /// Returns the document's drawing, for recording onto the end of it.
+    pub fn record(&mut self) -> &mut CommandBuffer {
+        &mut self.commands
+    }
+
+    /// Finishes the current page; the next starts blank.  Analogous to cairo_show_page().
+    pub fn show_page(&mut self) {
+        self.commands.show_page();
+    }
+
+    /// Finishes the current page; the next starts with a copy of its drawing.  Analogous to
+    /// cairo_copy_page().
+    pub fn copy_page(&mut self) {
+        self.commands.copy_page();
+    }
+
+    /// Returns the number of pages in the document: one for each finished page, and one more
+    /// for the current page if anything has been recorded on it, or if it is the only page.
+    pub fn num_pages(&self) -> usize {
+        let mut pages = 0;
+        let mut drawn = false;
+        for command in self.commands.commands() {
+            match *command {
+                Command::ShowPage | Command::CopyPage => {
+                    pages += 1;
+                    drawn = false;
+                },
+                _ => drawn = true,
+            }
+        }
+        if drawn || pages == 0 {
+            pages += 1;
+        }
+        pages
+    }
+}
+
+// A drawing operation waiting for the end of its page, as handed to a VectorRenderer
+#[derive(Clone)]
+enum DrawOp {
+    Fill(Path, FillRule, Pattern, Operator),
+    Stroke(Path, Matrix, StrokeStyle, Option<DashPattern>, Pattern, Operator),
+    Paint(Pattern, Operator),
+    Clip(Path, FillRule),
+    ResetClip,
+}
+
+/// Replays `document` and hands each page's drawing operations to `renderer`, in order, between
+/// calls to `begin_page` and `end_page`.
+///
+/// The pages are those counted by `PaginatedSurface::num_pages`.  A page begun by show_page()
+/// starts with just the clip in effect, and one begun by copy_page() with everything drawn on
+/// the page before it.  Batched helpers are turned into paths: fill_rectangles() and
+/// fill_ellipses() become fills, and stroke_segments() and stroke_polyline_gradient() strokes
+/// with butt caps, the latter in its starting color.  As with Context, they are in page
+/// coordinates.  show_text() becomes a fill of the text's outline.  mask() is not passed on, so
+/// backends draw nothing for it.
+pub fn render_document<R: VectorRenderer>(document: &PaginatedSurface, renderer: &mut R) {
+    let mut scratch = ImageSurface::create_for_format(Format::ARGB32, 1, 1);
+    let mut context = Context::create(&mut scratch);
+    let base = Matrix::identity();
+    // The current page's operations, and those setting up the clip in effect
+    let mut page = Vec::new();
+    let mut clip = Vec::new();
+    let mut index = 0;
+    let mut drawn = false;
+    for command in document.commands.commands() {
+        match *command {
+            Command::ShowPage | Command::CopyPage => {
+                render_page(renderer, index, &page);
+                if *command == Command::ShowPage {
+                    page = clip.clone();
+                }
+                index += 1;
+                drawn = false;
+                // The scratch surface has no use for pages of its own
+                continue;
+            },
+            _ => drawn = true,
+        }
+        let source = if context.get_coverage_only() {
+            Pattern::rgba(0., 0., 0., 1.)
+        } else {
+            context.get_source().clone()
+        };
+        let operator = *context.get_operator();
+        let fill_rule = context.get_fill_rule();
+        match *command {
+            Command::Fill => page.push(DrawOp::Fill(context.get_path().clone(), fill_rule,
+                                                    source, operator)),
+            Command::Stroke => {
+                let matrix = context.get_matrix();
+                if let Some(inverse) = matrix.invert() {
+                    let style = StrokeStyle {
+                        width: context.get_line_width(),
+                        join: context.get_line_join(),
+                        cap: context.get_line_cap(),
+                        miter_limit: context.get_miter_limit(),
+                    };
+                    page.push(DrawOp::Stroke(context.get_path().transform(&inverse), matrix,
+                                             style, context.get_dash().cloned(), source,
+                                             operator));
+                }
+            },
+            Command::Paint => page.push(DrawOp::Paint(source, operator)),
+            Command::Clip | Command::ClipPreserve => {
+                let op = DrawOp::Clip(context.get_path().clone(), fill_rule);
+                page.push(op.clone());
+                clip.push(op);
+            },
+            Command::ResetClip if !clip.is_empty() => {
+                page.push(DrawOp::ResetClip);
+                clip.clear();
+            },
+            Command::FillRectangles(ref rectangles) => {
+                let path = Path::from_rectangles(rectangles);
+                if !path.is_empty() {
+                    page.push(DrawOp::Fill(path, FillRule::Winding, source, operator));
+                }
+            },
+            Command::FillEllipses(ref ellipses) => {
+                let path = Path::from_ellipses(ellipses, context.get_tolerance());
+                if !path.is_empty() {
+                    page.push(DrawOp::Fill(path, FillRule::Winding, source, operator));
+                }
+            },
+            Command::ShowText(ref text) => {
+                let to_page = context.text_matrix();
+                let path = context.get_font_face().text_path_with_matrix(text, &to_page,
+                                                                         context.get_tolerance());
+                if !path.is_empty() {
+                    page.push(DrawOp::Fill(path, FillRule::Winding, source, operator));
+                }
+            },
+            Command::StrokeSegments(ref segments) => {
+                let path = Path::from_segments(segments);
+                stroke_in_page(&mut page, path, context.get_line_width(), source, operator);
+            },
+            Command::StrokePolylineGradient(ref points, _) => {
+                let path = Path::from_polyline(points);
+                stroke_in_page(&mut page, path, context.get_line_width(), source, operator);
+            },
+            _ => {},
+        }
+        command.execute(&mut context, &base);
+    }
+    if drawn || index == 0 {
+        render_page(renderer, index, &page);
+    }
+}
+
+// Hands the operations of page `index` to `renderer`, removing any clip left at the end
+fn render_page<R: VectorRenderer>(renderer: &mut R, index: usize, page: &[DrawOp]) {
+    renderer.begin_page(index);
+    let mut clipped = false;
+    for op in page {
+        match *op {
+            DrawOp::Fill(ref path, fill_rule, ref source, operator) => {
+                renderer.fill(path, fill_rule, source, operator)
+            },
+            DrawOp::Stroke(ref path, ref matrix, ref style, ref dash, ref source, operator) => {
+                renderer.stroke(path, matrix, style, dash.as_ref(), source, operator)
+            },
+            DrawOp::Paint(ref source, operator) => renderer.paint(source, operator),
+            DrawOp::Clip(ref path, fill_rule) => {
+                renderer.clip(path, fill_rule);
+                clipped = true;
+            },
+            DrawOp::ResetClip => {
+                renderer.reset_clip();
+                clipped = false;
+            },
+        }
+    }
+    if clipped {
+        renderer.reset_clip();
+    }
+    renderer.end_page();
+}
+
+// Adds a stroke of `path`, in page coordinates, `width` wide with butt caps, as the batched
+// stroke helpers draw, to `page`.
+fn stroke_in_page(page: &mut Vec<DrawOp>, path: Path, width: f32, source: Pattern,
+                  operator: Operator) {
+    if path.is_empty() {
+        return;
+    }
+    let style = StrokeStyle { cap: LineCap::Butt, ..StrokeStyle::new(width) };
+    page.push(DrawOp::Stroke(path, Matrix::identity(), style, None, source, operator));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PaginatedSurface, VectorRenderer, render_document};
+    use common_geometry::{Ellipse, Point, Rectangle};
+    use dash::DashPattern;
+    use matrix::Matrix;
+    use operators::Operator;
+    use path::{Path, PathElement};
+    use pattern::Pattern;
+    use stroke::StrokeStyle;
+    use types::FillRule;
+
+    // Writes down what it is asked to draw
+    #[derive(Default)]
+    struct Log(Vec<String>);
+
+    impl VectorRenderer for Log {
+        fn begin_page(&mut self, index: usize) {
+            self.0.push(format!("page {}", index));
+        }
+
+        fn end_page(&mut self) {
+            self.0.push("end".to_string());
+        }
+
+        fn fill(&mut self, path: &Path, fill_rule: FillRule, _: &Pattern, _: Operator) {
+            self.0.push(format!("fill {} {:?}", path.elements().len(), fill_rule));
+        }
+
+        fn stroke(&mut self, path: &Path, matrix: &Matrix, style: &StrokeStyle,
+                  _: Option<&DashPattern>, _: &Pattern, _: Operator) {
+            self.0.push(format!("stroke {:?} {} {}", path.elements()[1], matrix.xx, style.width));
+        }
+
+        fn paint(&mut self, source: &Pattern, operator: Operator) {
+            self.0.push(format!("paint {:?} {:?}", source.color_at(Point::new(0., 0.)), operator));
+        }
+
+        fn clip(&mut self, path: &Path, _: FillRule) {
+            self.0.push(format!("clip {}", path.elements().len()));
+        }
+
+        fn reset_clip(&mut self) {
+            self.0.push("reset_clip".to_string());
+        }
+    }
+
+    #[test]
+    fn paginated_surface_num_pages() {
+        let mut document = PaginatedSurface::new(100., 50.);
+        assert_eq!(document.get_size(), (100., 50.));
+        assert_eq!(document.num_pages(), 1);
+        document.record().paint();
+        assert_eq!(document.num_pages(), 1);
+        document.show_page();
+        assert_eq!(document.num_pages(), 1);
+        document.copy_page();
+        assert_eq!(document.num_pages(), 2);
+        document.record().paint();
+        assert_eq!(document.num_pages(), 3);
+    }
+
+    #[test]
+    fn render_page_reduces_to_paths() {
+        // Setup
+        let mut document = PaginatedSurface::new(100., 100.);
+        {
+            let page = document.record();
+            page.set_source_rgba(1., 0., 0., 1.);
+            page.set_operator(Operator::Source);
+            page.paint();
+            page.move_to(0., 0.);
+            page.line_to(10., 0.);
+            page.line_to(10., 10.);
+            page.clip();
+            page.scale(2., 2.);
+            page.set_line_width(3.);
+            page.move_to(1., 1.);
+            page.line_to(5., 1.);
+            page.stroke();
+            page.set_fill_rule(FillRule::EvenOdd);
+            page.fill_rectangles(&[Rectangle::new(0., 0., 2., 2.),
+                                   Rectangle::new(5., 5., 0., 1.)]);
+            page.fill_ellipses(&[Ellipse::circle(20., 20., 5.)]);
+        }
+        let mut log = Log::default();
+
+        // Call
+        render_document(&document, &mut log);
+
+        // Test
+        let stroked = PathElement::LineTo(Point::new(5., 1.));
+        assert_eq!(log.0[0], "page 0");
+        assert_eq!(log.0[1], format!("paint {:?} Source", ::types::Rgba::new(1., 0., 0., 1.)));
+        assert_eq!(log.0[2], "clip 3");
+        assert_eq!(log.0[3], format!("stroke {:?} 2 3", stroked));
+        assert_eq!(log.0[4], "fill 5 Winding");
+        assert!(log.0[5].starts_with("fill "));
+        assert_eq!(log.0[6], "reset_clip");
+        assert_eq!(log.0[7], "end");
+        assert_eq!(log.0.len(), 8);
+    }
+
+    #[test]
+    fn render_document_pages() {
+        // Setup
+        let mut document = PaginatedSurface::new(10., 10.);
+        {
+            let page = document.record();
+            page.move_to(0., 0.);
+            page.line_to(5., 0.);
+            page.line_to(5., 5.);
+            page.clip();
+            page.paint();
+            page.copy_page();
+            page.set_operator(Operator::Source);
+            page.paint();
+            page.show_page();
+            page.reset_clip();
+            page.paint();
+            page.show_page();
+        }
+        let mut log = Log::default();
+
+        // Call
+        render_document(&document, &mut log);
+
+        // Test
+        let paint = format!("paint {:?} Over", ::types::Rgba::new(0., 0., 0., 0.));
+        let source = format!("paint {:?} Source", ::types::Rgba::new(0., 0., 0., 0.));
+        let expected = vec!["page 0", "clip 3", &paint, "reset_clip", "end",
+                            "page 1", "clip 3", &paint, &source, "reset_clip", "end",
+                            "page 2", "clip 3", "reset_clip", &source, "end"];
+        assert_eq!(log.0, expected);
+        assert_eq!(document.num_pages(), 3);
+    }
+}
@@ -0,0 +1,1381 @@
+/*
+ * Cairus - a reimplementation of the cairo graphics library in Rust
+ *
+ * Copyright © 2017 CairusOrg
+ *
+ * This library is free software; you can redistribute it and/or
+ * modify it either under the terms of the GNU Lesser General Public
+ * License version 2.1 as published by the Free Software Foundation
+ * (the "LGPL") or, at your option, under the terms of the Mozilla
+ * Public License Version 2.0 (the "MPL"). If you do not alter this
+ * notice, a recipient may use your version of this file under either
+ * the MPL or the LGPL.
+ *
+ * You should have received a copy of the LGPL along with this library
+ * in the file LICENSE-LGPL-2_1; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Suite 500, Boston, MA 02110-1335, USA
+ * You should have received a copy of the MPL along with this library
+ * in the file LICENSE-MPL-2_0
+ *
+ * The contents of this file are subject to the Mozilla Public License
+ * Version 2.0 (the "License"); you may not use this file except in
+ * compliance with the License. You may obtain a copy of the License at
+ * http://www.mozilla.org/MPL/
+ *
+ * This software is distributed on an "AS IS" basis, WITHOUT WARRANTY
+ * OF ANY KIND, either express or implied. See the LGPL or the MPL for
+ * the specific language governing rights and limitations.
+ *
+ * The Original Code is the cairus graphics library.
+ *
+ * Contributor(s):
+ *	CairusOrg
+ *
+ */
+
+//! Paths: sequences of lines and curves, grouped into subpaths.
+//!
+//! A `Path` records the elements it is built from, as cairo_path_t does, and can be walked as
+//! segments or flattened into polylines and edges for filling and stroking.
+
+use std::f32::consts::{FRAC_PI_2, PI};
+use std::slice;
+use common_geometry::{Edge, Ellipse, LineSegment, Point, Rectangle};
+use decasteljau;
+use matrix::Matrix;
+use bo_trap;
+use outline;
+use predicates::Precision;
+use stroke::{self, StrokeStyle};
+use surfaces::{Format, ImageSurface};
+use trapezoid_rasterizer::{Trapezoid, mask_from_trapezoids_with_antialias};
+use types::{Antialias, FillRule, LineJoin};
+
+// What the directions of the second path's edges are multiplied by when two paths are combined,
+// so that each path's winding number can be told apart in their sum
+const SECOND_PATH_WINDING: i32 = 1 << 16;
+
+/// ## PathElement
+///
+/// A single step of a path.  Analogous to cairo_path_data_type_t.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum PathElement {
+    /// Begins a new subpath at the point.
+    MoveTo(Point),
+    /// A line from the current point to the point.
+    LineTo(Point),
+    /// A cubic Bézier curve from the current point, with two control points and an end point.
+    CurveTo(Point, Point, Point),
+    /// A line back to the start of the current subpath, which closes it.
+    ClosePath,
+}
+
+/// ## Segment
+///
+/// A piece of a path between two points, as yielded by `Path::segments`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Segment {
+    /// A line from the first point to the second.
+    Line(Point, Point),
+    /// A cubic Bézier curve from the first point to the last, with two control points between.
+    Curve(Point, Point, Point, Point),
+}
+
+/// ## Polyline
+///
+/// A subpath flattened into straight lines, as returned by `Path::flatten`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Polyline {
+    /// The points the lines run through, in order.
+    pub points: Vec<Point>,
+    /// Whether the subpath was closed with a line from its last point back to its first.
+    pub closed: bool,
+}
+
+/// ## Path
+///
+/// A path built from move_to, line_to, curve_to and close_path, with cairo's rules for a
+/// missing current point.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Path {
+    elements: Vec<PathElement>,
+    // Where the next element continues from, and where the current subpath started
+    current_point: Option<Point>,
+    subpath_start: Option<Point>,
+    // Set by close_path, so the next line or curve knows to begin a new subpath
+    closed: bool,
+}
+
+impl Path {
+    /// Returns a new, empty Path.
+    pub fn new() -> Path {
+        Path::default()
+    }
+
+    /// Returns a path around every non-empty rectangle in `rectangles`.
+    ///
+    /// Each is drawn the same way round, so their union is filled under either fill rule where
+    /// they don't overlap, and under FillRule::Winding where they do.
+    pub fn from_rectangles(rectangles: &[Rectangle]) -> Path {
+        let mut path = Path::new();
+        for rectangle in rectangles.iter().map(|r| r.normalized()).filter(|r| !r.is_empty()) {
+            let (right, bottom) = (rectangle.x + rectangle.width, rectangle.y + rectangle.height);
+            path.move_to(rectangle.x, rectangle.y)
+                .line_to(right, rectangle.y)
+                .line_to(right, bottom)
+                .line_to(rectangle.x, bottom)
+                .close_path();
+        }
+        path
+    }
+
+    /// Returns a path around every non-empty ellipse in `ellipses`, each made of Bézier curves
+    /// that stay within `tolerance` of it.
+    pub fn from_ellipses(ellipses: &[Ellipse], tolerance: f32) -> Path {
+        let mut path = Path::new();
+        for ellipse in ellipses.iter().filter(|ellipse| !ellipse.is_empty()) {
+            // A unit circle, stretched and moved onto the ellipse
+            let to_ellipse = Matrix::scaling(ellipse.radius_x, ellipse.radius_y)
+                .multiply(&Matrix::translation(ellipse.center.x, ellipse.center.y));
+            let tolerance = tolerance / ellipse.radius_x.max(ellipse.radius_y);
+            let circle = arc_segments(Point::new(0., 0.), 1., 0., 2. * PI, tolerance);
+            for (index, segment) in circle.iter().enumerate() {
+                if let Segment::Curve(start, control1, control2, end) = *segment {
+                    let point = |point: Point| to_ellipse.transform_point(point);
+                    let (start, control1, control2, end) = (point(start), point(control1),
+                                                            point(control2), point(end));
+                    if index == 0 {
+                        path.move_to(start.x, start.y);
+                    }
+                    path.curve_to(control1.x, control1.y, control2.x, control2.y, end.x, end.y);
+                }
+            }
+            path.close_path();
+        }
+        path
+    }
+
+    /// Returns a path with an open subpath along each segment in `segments`.
+    pub fn from_segments(segments: &[LineSegment]) -> Path {
+        let mut path = Path::new();
+        for segment in segments {
+            path.move_to(segment.point1.x, segment.point1.y)
+                .line_to(segment.point2.x, segment.point2.y);
+        }
+        path
+    }
+
+    /// Returns a path with one open subpath through every point in `points`, in order.
+    pub fn from_polyline(points: &[Point]) -> Path {
+        let mut path = Path::new();
+        if let Some((first, rest)) = points.split_first() {
+            path.move_to(first.x, first.y);
+            for point in rest {
+                path.line_to(point.x, point.y);
+            }
+        }
+        path
+    }
+
+    /// Returns the elements the path was built from, in order.
+    pub fn elements(&self) -> &[PathElement] {
+        &self.elements
+    }
+
+    /// Returns an iterator over the path's elements.
+    pub fn iter(&self) -> slice::Iter<'_, PathElement> {
+        self.elements.iter()
+    }
+
+    /// Returns true if nothing has been added to the path.
+    pub fn is_empty(&self) -> bool {
+        self.elements.is_empty()
+    }
+
+    /// Returns the smallest rectangle holding every point of the path, or None if it is empty.
+    ///
+    /// Curves lie inside their control points, so those are included rather than the curves
+    /// being measured, which can make the rectangle larger than the curves need.
+    ///
+    /// # Usage
+    /// let bounds = path.extents().unwrap();
+    pub fn extents(&self) -> Option<Rectangle> {
+        let mut points = self.elements.iter().flat_map(|element| match *element {
+            PathElement::MoveTo(point) | PathElement::LineTo(point) => vec![point],
+            PathElement::CurveTo(control1, control2, end) => vec![control1, control2, end],
+            PathElement::ClosePath => vec![],
+        });
+        let first = points.next()?;
+        let (mut left, mut top, mut right, mut bottom) = (first.x, first.y, first.x, first.y);
+        for point in points {
+            left = left.min(point.x);
+            top = top.min(point.y);
+            right = right.max(point.x);
+            bottom = bottom.max(point.y);
+        }
+        Some(Rectangle::new(left, top, right - left, bottom - top))
+    }
+
+    /// Returns the ellipse the path goes round if it is one closed loop of the Bézier arcs
+    /// `arc_segments` approximates a full turn with, stretched along the axes, or None otherwise.
+    ///
+    /// This finds the circles arc() draws under a matrix that only rotates, scales uniformly and
+    /// translates, and the axis-aligned ellipses it draws under a scale, so they can be filled
+    /// analytically.  The loop must go round exactly once.
+    ///
+    /// # Usage
+    /// let circle = path.as_ellipse();
+    pub fn as_ellipse(&self) -> Option<Ellipse> {
+        // How far, on the unit circle, points may be from where a true arc would put them
+        const EPSILON: f32 = 1e-3;
+
+        let mut elements = self.elements.iter();
+        let start = match elements.next() {
+            Some(&PathElement::MoveTo(start)) => start,
+            _ => return None,
+        };
+        let mut curves = Vec::new();
+        let mut closed = false;
+        for element in elements {
+            match *element {
+                PathElement::CurveTo(control1, control2, end) if !closed => {
+                    curves.push((control1, control2, end));
+                },
+                PathElement::ClosePath if !closed => closed = true,
+                _ => return None,
+            }
+        }
+        if curves.len() < 4 {
+            return None;
+        }
+
+        // The ends of the arcs are evenly spread round the ellipse, so they average to its
+        // center, and each lies on it, which gives the radii by least squares
+        let count = curves.len() as f32;
+        let center = Point::new(curves.iter().map(|curve| curve.2.x).sum::<f32>() / count,
+                                curves.iter().map(|curve| curve.2.y).sum::<f32>() / count);
+        let (mut xx, mut xy, mut yy, mut x1, mut y1) = (0., 0., 0., 0., 0.);
+        for &(_, _, end) in curves.iter() {
+            let (dx2, dy2) = ((end.x - center.x).powi(2), (end.y - center.y).powi(2));
+            xx += dx2 * dx2;
+            xy += dx2 * dy2;
+            yy += dy2 * dy2;
+            x1 += dx2;
+            y1 += dy2;
+        }
+        let determinant = xx * yy - xy * xy;
+        if determinant == 0. || !determinant.is_finite() {
+            return None;
+        }
+        let inverse_x2 = (x1 * yy - y1 * xy) / determinant;
+        let inverse_y2 = (y1 * xx - x1 * xy) / determinant;
+        if !(inverse_x2 > 0. && inverse_y2 > 0.) {
+            return None;
+        }
+        let ellipse = Ellipse::new(center.x, center.y, inverse_x2.sqrt().recip(),
+                                   inverse_y2.sqrt().recip());
+
+        // Each curve must be the Bézier arc_segments gives for the arc between its ends, on the
+        // unit circle the ellipse is stretched from
+        let to_circle = |point: Point| Point::new((point.x - center.x) / ellipse.radius_x,
+                                                  (point.y - center.y) / ellipse.radius_y);
+        let near = |a: Point, b: Point| (a.x - b.x).hypot(a.y - b.y) < EPSILON;
+        let mut from = to_circle(start);
+        let mut turned: f32 = 0.;
+        for &(control1, control2, end) in curves.iter() {
+            let to = to_circle(end);
+            let (from_angle, to_angle) = (from.y.atan2(from.x), to.y.atan2(to.x));
+            let mut sweep = to_angle - from_angle;
+            if sweep > PI {
+                sweep -= 2. * PI;
+            } else if sweep < -PI {
+                sweep += 2. * PI;
+            }
+            let handle = 4. / 3. * (sweep / 4.).tan();
+            let expected1 = Point::new(from.x - handle * from.y, from.y + handle * from.x);
+            let expected2 = Point::new(to.x + handle * to.y, to.y - handle * to.x);
+            let on_circle = (to.x.hypot(to.y) - 1.).abs() < EPSILON;
+            let same_way = turned == 0. || sweep.signum() == turned.signum();
+            if !on_circle || !same_way || !near(to_circle(control1), expected1) ||
+               !near(to_circle(control2), expected2) {
+                return None;
+            }
+            turned += sweep;
+            from = to;
+        }
+        if (turned.abs() - 2. * PI).abs() < EPSILON && near(from, to_circle(start)) {
+            Some(ellipse)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the point the next line or curve will start from, if there is one.
+    ///
+    /// Analogous to cairo_get_current_point().
+    pub fn current_point(&self) -> Option<Point> {
+        self.current_point
+    }
+
+    /// Removes every element from the path.  Analogous to cairo_new_path().
+    pub fn clear(&mut self) {
+        *self = Path::new();
+    }
+
+    /// Begins a new subpath at (x, y).  Analogous to cairo_move_to().
+    ///
+    /// # Arguments
+    /// * `&mut self` - Reference to the `Path` to extend.
+    /// * `x` - X coordinate of the new current point.
+    /// * `y` - Y coordinate of the new current point.
+    ///
+    /// # Usage
+    /// path.move_to(10., 10.).line_to(20., 10.);
+    pub fn move_to(&mut self, x: f32, y: f32) -> &mut Path {
+        let point = Point::new(x, y);
+        self.elements.push(PathElement::MoveTo(point));
+        self.current_point = Some(point);
+        self.subpath_start = Some(point);
+        self.closed = false;
+        self
+    }
+
+    /// Adds a line from the current point to (x, y), which becomes the current point.
+    ///
+    /// Analogous to cairo_line_to().  Without a current point this behaves like move_to().
+    ///
+    /// # Arguments
+    /// * `&mut self` - Reference to the `Path` to extend.
+    /// * `x` - X coordinate of the end of the line.
+    /// * `y` - Y coordinate of the end of the line.
+    ///
+    /// # Usage
+    /// path.line_to(20., 10.);
+    pub fn line_to(&mut self, x: f32, y: f32) -> &mut Path {
+        if !self.continue_subpath() {
+            return self.move_to(x, y);
+        }
+        let point = Point::new(x, y);
+        self.elements.push(PathElement::LineTo(point));
+        self.current_point = Some(point);
+        self
+    }
+
+    /// Adds a cubic Bézier curve from the current point to (x3, y3), using (x1, y1) and (x2, y2)
+    /// as control points.
+    ///
+    /// Analogous to cairo_curve_to().  Without a current point the curve starts at (x1, y1).
+    ///
+    /// # Arguments
+    /// * `&mut self` - Reference to the `Path` to extend.
+    /// * `x1`, `y1` - The first control point.
+    /// * `x2`, `y2` - The second control point.
+    /// * `x3`, `y3` - The end of the curve.
+    ///
+    /// # Usage
+    /// path.curve_to(20., 0., 30., 20., 40., 10.);
+    pub fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x3: f32, y3: f32)
+                    -> &mut Path {
+        if !self.continue_subpath() {
+            self.move_to(x1, y1);
+        }
+        let end = Point::new(x3, y3);
+        self.elements.push(PathElement::CurveTo(Point::new(x1, y1), Point::new(x2, y2), end));
+        self.current_point = Some(end);
+        self
+    }
+
+    /// Adds a quadratic Bézier curve from the current point to (x2, y2), using (x1, y1) as the
+    /// control point.
+    ///
+    /// The curve is stored as the cubic that traces it exactly, with control points two thirds
+    /// of the way from each end to (x1, y1), so everything downstream only deals in cubics.  Like
+    /// curve_to(), without a current point the curve starts at (x1, y1).
+    ///
+    /// # Arguments
+    /// * `&mut self` - Reference to the `Path` to extend.
+    /// * `x1`, `y1` - The control point.
+    /// * `x2`, `y2` - The end of the curve.
+    ///
+    /// # Usage
+    /// path.quad_to(20., 0., 40., 10.);
+    pub fn quad_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32) -> &mut Path {
+        let start = self.current_point.unwrap_or_else(|| Point::new(x1, y1));
+        let elevate = |from: f32, control: f32| from + 2. / 3. * (control - from);
+        self.curve_to(elevate(start.x, x1), elevate(start.y, y1), elevate(x2, x1),
+                      elevate(y2, y1), x2, y2)
+    }
+
+    /// Begins a new subpath offset by (dx, dy) from the current point.
+    ///
+    /// Analogous to cairo_rel_move_to().  Does nothing without a current point.
+    ///
+    /// # Usage
+    /// path.move_to(10., 10.).rel_move_to(5., 0.);
+    pub fn rel_move_to(&mut self, dx: f32, dy: f32) -> &mut Path {
+        match self.current_point {
+            Some(current) => self.move_to(current.x + dx, current.y + dy),
+            None => self,
+        }
+    }
+
+    /// Adds a line from the current point to the point offset by (dx, dy) from it.
+    ///
+    /// Analogous to cairo_rel_line_to().  Does nothing without a current point.
+    ///
+    /// # Usage
+    /// path.move_to(10., 10.).rel_line_to(10., 0.).rel_line_to(0., 10.);
+    pub fn rel_line_to(&mut self, dx: f32, dy: f32) -> &mut Path {
+        match self.current_point {
+            Some(current) => self.line_to(current.x + dx, current.y + dy),
+            None => self,
+        }
+    }
+
+    /// Adds a cubic Bézier curve like curve_to(), with every point given as an offset from the
+    /// current point.
+    ///
+    /// Analogous to cairo_rel_curve_to().  Does nothing without a current point.
+    ///
+    /// # Usage
+    /// path.move_to(10., 10.).rel_curve_to(10., -10., 20., 10., 30., 0.);
+    pub fn rel_curve_to(&mut self, dx1: f32, dy1: f32, dx2: f32, dy2: f32, dx3: f32, dy3: f32)
+                        -> &mut Path {
+        match self.current_point {
+            Some(current) => self.curve_to(current.x + dx1, current.y + dy1, current.x + dx2,
+                                           current.y + dy2, current.x + dx3, current.y + dy3),
+            None => self,
+        }
+    }
+
+    /// Closes the current subpath with a line back to its start, which becomes the current
+    /// point.  Analogous to cairo_close_path().  Does nothing without a current point.
+    pub fn close_path(&mut self) -> &mut Path {
+        if self.current_point.is_some() && !self.closed {
+            self.elements.push(PathElement::ClosePath);
+            self.current_point = self.subpath_start;
+            self.closed = true;
+        }
+        self
+    }
+
+    /// Returns a copy of the path with every point transformed by `matrix`.
+    ///
+    /// Curves stay curves, since an affine transformation of a Bézier curve is the Bézier curve
+    /// of its transformed control points.
+    pub fn transform(&self, matrix: &Matrix) -> Path {
+        let point = |point: Point| matrix.transform_point(point);
+        let elements = self.elements.iter().map(|element| match *element {
+            PathElement::MoveTo(a) => PathElement::MoveTo(point(a)),
+            PathElement::LineTo(a) => PathElement::LineTo(point(a)),
+            PathElement::CurveTo(a, b, c) => PathElement::CurveTo(point(a), point(b), point(c)),
+            PathElement::ClosePath => PathElement::ClosePath,
+        }).collect();
+        Path {
+            elements,
+            current_point: self.current_point.map(point),
+            subpath_start: self.subpath_start.map(point),
+            closed: self.closed,
+        }
+    }
+
+    // Prepares for a line or curve from the current point, returning false if there isn't one.
+    // After close_path, a new subpath is begun at the closed one's start, as cairo does.
+    fn continue_subpath(&mut self) -> bool {
+        match self.current_point {
+            None => false,
+            Some(point) => {
+                if self.closed {
+                    self.move_to(point.x, point.y);
+                }
+                true
+            },
+        }
+    }
+
+    /// Returns an iterator over the lines and curves of the path, including the lines that close
+    /// subpaths.  Moves produce no segments.
+    pub fn segments(&self) -> Segments<'_> {
+        Segments {
+            elements: self.elements.iter(),
+            current_point: Point::origin(),
+            subpath_start: Point::origin(),
+        }
+    }
+
+    /// Flattens every subpath into a polyline, with curves split into lines that stray no more
+    /// than `tolerance` from them.
+    pub fn flatten(&self, tolerance: f32) -> Vec<Polyline> {
+        let mut polylines: Vec<Polyline> = Vec::new();
+        for element in self.elements.iter() {
+            match *element {
+                PathElement::MoveTo(point) => {
+                    polylines.push(Polyline { points: vec![point], closed: false });
+                },
+                PathElement::LineTo(point) => {
+                    polylines.last_mut().unwrap().points.push(point);
+                },
+                PathElement::CurveTo(b, c, d) => {
+                    let polyline = polylines.last_mut().unwrap();
+                    let a = *polyline.points.last().unwrap();
+                    polyline.points.extend(decasteljau::flatten(&a, &b, &c, &d, tolerance));
+                },
+                PathElement::ClosePath => polylines.last_mut().unwrap().closed = true,
+            }
+        }
+        polylines
+    }
+
+    /// Returns the edges bounding the area the path encloses, for filling.
+    ///
+    /// Every subpath is closed, whether close_path was called or not, and curves are flattened
+    /// to within `tolerance`.
+    pub fn edges(&self, tolerance: f32) -> Vec<Edge> {
+        let mut edges = Vec::new();
+        for polyline in self.flatten(tolerance) {
+            let points = polyline.points;
+            let closing = (points[points.len() - 1], points[0]);
+            let lines = points.windows(2).map(|pair| (pair[0], pair[1])).chain(Some(closing));
+            edges.extend(lines.filter(|&(from, to)| from != to)
+                              .map(|(from, to)| Edge::from_points(from, to)));
+        }
+        edges
+    }
+
+    /// Returns the trapezoids fill() covers the area this path fills under `fill_rule` with.
+    ///
+    /// The trapezoids have horizontal bases, don't overlap, and are found with the same sweep
+    /// and snapping to 1/256 that fill() uses, with curves flattened to within `tolerance`.  See
+    /// `Trapezoid::spans` for rasterizing them.
+    ///
+    /// # Usage
+    /// let trapezoids = path.trapezoids(FillRule::Winding, 0.1);
+    pub fn trapezoids(&self, fill_rule: FillRule, tolerance: f32) -> Vec<Trapezoid> {
+        bo_trap::sweep_with_fill_rule(self.edges(tolerance), fill_rule)
+    }
+
+    /// Returns an A8 surface whose alpha is how much of each pixel this path fills under
+    /// `fill_rule`, sampled as `antialias` selects.
+    ///
+    /// Only coverage is rendered, with the same trapezoids and sampling fill() uses, so the mask
+    /// lines up exactly with a fill of the path.  It can be handed to `Context::mask_surface`,
+    /// cached as a clip, or kept as a rasterized glyph.  The surface runs from the origin to the
+    /// bottom right of the path, so pixel (x, y) of the mask is pixel (x, y) of a fill, and any
+    /// part of the path above or left of the origin is left out; translate the path first to
+    /// keep it.  A path that fills nothing gives a single transparent pixel.
+    ///
+    /// # Usage
+    /// let mask = path.render_mask(FillRule::Winding, Antialias::Default, 0.1);
+    pub fn render_mask(&self, fill_rule: FillRule, antialias: Antialias, tolerance: f32)
+                       -> ImageSurface {
+        let trapezoids = self.trapezoids(fill_rule, tolerance);
+        let (mut right, mut bottom) = (0f32, 0f32);
+        for line in trapezoids.iter().flat_map(|trapezoid| trapezoid.lines()) {
+            right = right.max(line.point1.x).max(line.point2.x);
+            bottom = bottom.max(line.point1.y).max(line.point2.y);
+        }
+        // Surfaces can't be empty
+        let (width, height) = ((right.ceil() as usize).max(1), (bottom.ceil() as usize).max(1));
+        mask_from_trapezoids_with_antialias(&trapezoids, antialias, width, height)
+            .convert(Format::A8)
+    }
+
+    /// Returns a path around the area this path fills under `fill_rule`, made of polygons that
+    /// don't cross themselves or each other.
+    ///
+    /// The area is found with the same sweep fill() uses, so curves are flattened to within
+    /// `tolerance` and every point is snapped to cairo's fixed-point grid of 1/256.  Polygons can
+    /// still touch at a corner.  Outer boundaries are wound so that their signed area is
+    /// positive, clockwise on screen, and holes the other way, so the result fills the same area
+    /// under either fill rule.  Overlapping and self-intersecting subpaths come out as clean
+    /// outlines, which suits exporting to formats that don't have fill rules.
+    ///
+    /// # Usage
+    /// let clean = path.simplify(FillRule::EvenOdd, 0.1);
+    pub fn simplify(&self, fill_rule: FillRule, tolerance: f32) -> Path {
+        let slabs = bo_trap::sweep_slabs(self.edges(tolerance), fill_rule, Precision::Exact);
+        outline::outline(&slabs)
+    }
+
+    /// Returns a path around the area either this path or `other` fills under `fill_rule`.
+    ///
+    /// The result is simplified as simplify() describes, and so are the results of intersect(),
+    /// subtract() and xor().  Both paths may cross themselves and each other.  Each path's
+    /// winding number is counted on its own, so they can't wind round any point 32768 times.
+    ///
+    /// # Usage
+    /// let both = circle.union(&square, FillRule::Winding, 0.1);
+    pub fn union(&self, other: &Path, fill_rule: FillRule, tolerance: f32) -> Path {
+        self.combine(other, fill_rule, tolerance, |first, second| first || second)
+    }
+
+    /// Returns a path around the area both this path and `other` fill under `fill_rule`.  See
+    /// union().
+    pub fn intersect(&self, other: &Path, fill_rule: FillRule, tolerance: f32) -> Path {
+        self.combine(other, fill_rule, tolerance, |first, second| first && second)
+    }
+
+    /// Returns a path around the area this path fills under `fill_rule` and `other` doesn't.
+    /// See union().
+    pub fn subtract(&self, other: &Path, fill_rule: FillRule, tolerance: f32) -> Path {
+        self.combine(other, fill_rule, tolerance, |first, second| first && !second)
+    }
+
+    /// Returns a path around the area just one of this path and `other` fills under
+    /// `fill_rule`.  See union().
+    pub fn xor(&self, other: &Path, fill_rule: FillRule, tolerance: f32) -> Path {
+        self.combine(other, fill_rule, tolerance, |first, second| first != second)
+    }
+
+    /// Returns a path around the area this path fills under `fill_rule`, grown outwards by
+    /// `distance`, or shrunk inwards if `distance` is negative.
+    ///
+    /// Corners that stick out after the offset are shaped by `join`, as a stroke's are, with
+    /// miters longer than `miter_limit` times twice the distance beveled instead, as
+    /// cairo_set_miter_limit() describes.  Corners the offset cuts into stay sharp.  Parts
+    /// narrower than twice an inward distance disappear.  The area is first simplified, so
+    /// every subpath counts as closed and the result is simplified as simplify() describes,
+    /// with curves flattened to within `tolerance`.  Suits halos around text and the shapes of
+    /// shadows.
+    ///
+    /// # Arguments
+    /// * `&self` - The path whose area is offset.
+    /// * `distance` - How far to move the outline, outwards if positive and inwards if negative.
+    /// * `join` - How the corners that stick out are drawn.
+    /// * `miter_limit` - The longest a miter may be, as a multiple of twice the distance.
+    /// * `fill_rule` - The rule that decides the area this path fills.
+    /// * `tolerance` - How far flattened curves may stray from the true curve.
+    ///
+    /// # Usage
+    /// let halo = glyphs.offset(2., LineJoin::Round, 10., FillRule::Winding, 0.1);
+    pub fn offset(&self, distance: f32, join: LineJoin, miter_limit: f32, fill_rule: FillRule,
+                  tolerance: f32) -> Path {
+        let area = self.simplify(fill_rule, tolerance);
+        if distance == 0. || distance.is_nan() {
+            return area;
+        }
+        // The band within the distance of the outline, on both sides of it
+        let style = StrokeStyle { width: 2. * distance.abs(), join, miter_limit,
+                                  ..StrokeStyle::new(0.) };
+        let band = stroke::stroke_outline(&area, &style, None, tolerance);
+        if distance > 0. {
+            area.union(&band, FillRule::Winding, tolerance)
+        } else {
+            area.subtract(&band, FillRule::Winding, tolerance)
+        }
+    }
+
+    // Sweeps both paths' edges together, filling where `keep` is true for whether each path
+    // fills under `fill_rule`, and returns the outline of that
+    fn combine(&self, other: &Path, fill_rule: FillRule, tolerance: f32,
+               keep: fn(bool, bool) -> bool) -> Path {
+        let mut edges = self.edges(tolerance);
+        edges.extend(other.edges(tolerance).into_iter().map(|mut edge| {
+            edge.direction *= SECOND_PATH_WINDING;
+            edge
+        }));
+        let mask = bo_trap::winding_mask(fill_rule);
+        let inside = |winding: i32| {
+            let half = SECOND_PATH_WINDING / 2;
+            let first = (winding + half).rem_euclid(SECOND_PATH_WINDING) - half;
+            let second = (winding - first) / SECOND_PATH_WINDING;
+            keep(first & mask != 0, second & mask != 0)
+        };
+        outline::outline(&bo_trap::sweep_slabs_inside(edges, Precision::Exact, &inside))
+    }
+}
+
+impl<'a> IntoIterator for &'a Path {
+    type Item = &'a PathElement;
+    type IntoIter = slice::Iter<'a, PathElement>;
+
+    fn into_iter(self) -> slice::Iter<'a, PathElement> {
+        self.iter()
+    }
+}
+
+/// ## Segments
+///
+/// An iterator over the segments of a Path.  See `Path::segments`.
+pub struct Segments<'a> {
+    elements: slice::Iter<'a, PathElement>,
+    current_point: Point,
+    subpath_start: Point,
+}
+
+impl<'a> Iterator for Segments<'a> {
+    type Item = Segment;
+
+    fn next(&mut self) -> Option<Segment> {
+        for element in self.elements.by_ref() {
+            let from = self.current_point;
+            match *element {
+                PathElement::MoveTo(point) => {
+                    self.current_point = point;
+                    self.subpath_start = point;
+                },
+                PathElement::LineTo(point) => {
+                    self.current_point = point;
+                    return Some(Segment::Line(from, point));
+                },
+                PathElement::CurveTo(b, c, d) => {
+                    self.current_point = d;
+                    return Some(Segment::Curve(from, b, c, d));
+                },
+                PathElement::ClosePath => {
+                    self.current_point = self.subpath_start;
+                    return Some(Segment::Line(from, self.subpath_start));
+                },
+            }
+        }
+        None
+    }
+}
+
+/// Returns cubic Bézier curves that follow the circle of `radius` around `center` from `angle1` to
+/// `angle2`, in radians, to within `tolerance`.
+///
+/// The arc runs towards increasing angles if `angle2` is larger and towards decreasing angles if
+/// it is smaller.  It is split into equal pieces of at most a quarter turn, each approximated by
+/// the Bézier whose control points lie 4/3 tan(φ/4) radii along the tangents, for a piece of
+/// angle φ.  That curve strays from the circle by at most 2/27 sin⁶(φ/4) / cos²(φ/4) radii, the
+/// bound cairo uses, which is tight for pieces this small, and pieces are added until that is
+/// within `tolerance`.  Flattening the curves to within a tolerance adds to that, so arcs drawn
+/// with the same tolerance for both stay within twice the tolerance of the circle.
+///
+/// # Arguments
+/// * `center` - The center of the circle.
+/// * `radius` - The radius of the circle.
+/// * `angle1` - The angle the arc starts at, measured from the positive x axis towards the
+///   positive y axis.
+/// * `angle2` - The angle the arc ends at.
+/// * `tolerance` - How far the curves may stray from the circle.
+///
+/// # Usage
+/// let quarter = arc_segments(Point::new(0., 0.), 10., 0., FRAC_PI_2, 0.1);
+pub fn arc_segments(center: Point, radius: f32, angle1: f32, angle2: f32, tolerance: f32)
+                    -> Vec<Segment> {
+    let sweep = angle2 - angle1;
+    let mut pieces = (sweep.abs() / FRAC_PI_2).ceil().max(1.) as usize;
+    while pieces < 1024 && arc_error(sweep.abs() / pieces as f32) * radius > tolerance {
+        pieces += 1;
+    }
+
+    let piece_angle = sweep / pieces as f32;
+    let handle = 4. / 3. * (piece_angle / 4.).tan() * radius;
+    let point_at = |angle: f32| {
+        Point::new(center.x + radius * angle.cos(), center.y + radius * angle.sin())
+    };
+    (0..pieces).map(|piece| {
+        let from_angle = angle1 + piece_angle * piece as f32;
+        let to_angle = angle1 + piece_angle * (piece + 1) as f32;
+        let (from, to) = (point_at(from_angle), point_at(to_angle));
+        Segment::Curve(from,
+                       Point::new(from.x - handle * from_angle.sin(),
+                                  from.y + handle * from_angle.cos()),
+                       Point::new(to.x + handle * to_angle.sin(), to.y - handle * to_angle.cos()),
+                       to)
+    }).collect()
+}
+
+// Returns how far, in radii, the Bézier for an arc of `angle` radians strays from the circle
+fn arc_error(angle: f32) -> f32 {
+    let (sin, cos) = (angle / 4.).sin_cos();
+    2. / 27. * sin.powi(6) / (cos * cos)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::{FRAC_PI_2, PI};
+    use common_geometry::{Ellipse, LineSegment, Point, Rectangle};
+    use matrix::Matrix;
+    use super::{Path, PathElement, Polyline, Segment, arc_segments};
+    use surfaces::{Format, ImageSurface};
+    use context::Context;
+    use types::{Antialias, FillRule, LineJoin};
+
+    // Returns the signed area of each subpath of `path`, positive for those wound clockwise on
+    // screen
+    fn signed_areas(path: &Path) -> Vec<f32> {
+        path.flatten(0.1).iter().map(|polyline| {
+            let points = &polyline.points;
+            (0..points.len()).map(|index| {
+                let (a, b) = (points[index], points[(index + 1) % points.len()]);
+                a.x * b.y - b.x * a.y
+            }).sum::<f32>() / 2.
+        }).collect()
+    }
+
+    // Returns true if two lines of `path` cross, rather than only meeting at their ends
+    fn crosses_itself(path: &Path) -> bool {
+        let mut lines = Vec::new();
+        for polyline in path.flatten(0.1) {
+            let points = &polyline.points;
+            for index in 0..points.len() {
+                lines.push((points[index], points[(index + 1) % points.len()]));
+            }
+        }
+        let side = |a: Point, b: Point, p: Point| {
+            (b.x - a.x) * (p.y - a.y) - (b.y - a.y) * (p.x - a.x)
+        };
+        lines.iter().enumerate().any(|(index, &(a, b))| {
+            lines[index + 1..].iter().any(|&(c, d)| {
+                side(a, b, c) * side(a, b, d) < 0. && side(c, d, a) * side(c, d, b) < 0.
+            })
+        })
+    }
+
+    #[test]
+    fn path_from_shapes() {
+        // Setup
+        let rectangles = [Rectangle::new(4., 4., -2., -2.), Rectangle::new(0., 0., 0., 1.)];
+        let segments = [LineSegment::new(0., 0., 1., 1.), LineSegment::new(2., 0., 3., 1.)];
+        let points = [Point::new(0., 0.), Point::new(1., 0.), Point::new(1., 1.)];
+
+        // Call
+        let rectangles = Path::from_rectangles(&rectangles);
+        let ellipses = Path::from_ellipses(&[Ellipse::new(10., 10., 4., 2.)], 0.1);
+        let segments = Path::from_segments(&segments);
+        let polyline = Path::from_polyline(&points);
+
+        // Test
+        assert_eq!(rectangles.elements(), &[
+            PathElement::MoveTo(Point::new(2., 2.)),
+            PathElement::LineTo(Point::new(4., 2.)),
+            PathElement::LineTo(Point::new(4., 4.)),
+            PathElement::LineTo(Point::new(2., 4.)),
+            PathElement::ClosePath,
+        ]);
+        assert_eq!(ellipses.elements()[0], PathElement::MoveTo(Point::new(14., 10.)));
+        assert_eq!(ellipses.elements().last(), Some(&PathElement::ClosePath));
+        assert_eq!(segments.elements().len(), 4);
+        assert_eq!(polyline.elements().len(), 3);
+        assert!(Path::from_polyline(&[]).is_empty());
+    }
+
+    #[test]
+    fn path_as_ellipse() {
+        // Setup
+        let arc = |angle1: f32, angle2: f32| {
+            let mut path = Path::new();
+            path.move_to(3., 0.);
+            for segment in arc_segments(Point::new(0., 0.), 3., angle1, angle2, 0.1) {
+                if let Segment::Curve(_, control1, control2, end) = segment {
+                    path.curve_to(control1.x, control1.y, control2.x, control2.y, end.x, end.y);
+                }
+            }
+            path
+        };
+        let mut rotated = Matrix::rotation(0.6);
+        rotated.scale(2., 2.);
+        rotated.translate(10., 5.);
+
+        // Call
+        let ellipse = Path::from_ellipses(&[Ellipse::new(10., 10., 4., 2.)], 0.1).as_ellipse();
+        let circle = arc(0., 2. * PI).transform(&rotated).as_ellipse();
+        let backwards = arc(0., -2. * PI).transform(&Matrix::scaling(1., 0.5)).as_ellipse();
+        let stretched = arc(0., 2. * PI).transform(&Matrix::new(2., 1., 0., 1., 0., 0.));
+
+        // Test
+        let close = |actual: Option<Ellipse>, expected: Ellipse| {
+            let actual = actual.unwrap();
+            assert!((actual.center.x - expected.center.x).abs() < 1e-3 &&
+                    (actual.center.y - expected.center.y).abs() < 1e-3 &&
+                    (actual.radius_x - expected.radius_x).abs() < 1e-3 &&
+                    (actual.radius_y - expected.radius_y).abs() < 1e-3,
+                    "{:?} is not {:?}", actual, expected);
+        };
+        close(ellipse, Ellipse::new(10., 10., 4., 2.));
+        close(circle, Ellipse::circle(rotated.x0, rotated.y0, 6.));
+        close(backwards, Ellipse::new(0., 0., 3., 1.5));
+        assert_eq!(stretched.as_ellipse(), None);
+        assert_eq!(arc(0., PI).as_ellipse(), None);
+        assert_eq!(arc(0., 4. * PI).as_ellipse(), None);
+        assert_eq!(Path::from_rectangles(&[Rectangle::new(0., 0., 2., 2.)]).as_ellipse(), None);
+        let mut open = arc(0., 2. * PI);
+        open.line_to(0., 0.);
+        assert_eq!(open.as_ellipse(), None);
+    }
+
+    #[test]
+    fn path_builder() {
+        // Setup
+        let mut path = Path::new();
+
+        // Call
+        path.move_to(0., 0.).line_to(4., 0.).curve_to(5., 1., 5., 3., 4., 4.).close_path();
+
+        // Test
+        assert_eq!(path.elements(), &[
+            PathElement::MoveTo(Point::new(0., 0.)),
+            PathElement::LineTo(Point::new(4., 0.)),
+            PathElement::CurveTo(Point::new(5., 1.), Point::new(5., 3.), Point::new(4., 4.)),
+            PathElement::ClosePath,
+        ]);
+        assert_eq!(path.current_point(), Some(Point::new(0., 0.)));
+    }
+
+    #[test]
+    fn path_without_current_point() {
+        // line_to moves, and curve_to starts at its first control point
+        let mut path = Path::new();
+        path.line_to(1., 1.);
+        assert_eq!(path.elements(), &[PathElement::MoveTo(Point::new(1., 1.))]);
+
+        path.clear();
+        path.curve_to(1., 1., 2., 2., 3., 1.);
+        assert_eq!(path.elements()[0], PathElement::MoveTo(Point::new(1., 1.)));
+        assert_eq!(path.current_point(), Some(Point::new(3., 1.)));
+
+        // Closing an empty path does nothing
+        path.clear();
+        path.close_path();
+        assert!(path.is_empty());
+    }
+
+    #[test]
+    fn path_continues_after_close() {
+        // Drawing on after close_path begins a new subpath where the closed one started
+        let mut path = Path::new();
+        path.move_to(1., 1.).line_to(3., 1.).close_path().line_to(1., 5.);
+        assert_eq!(&path.elements()[3..], &[
+            PathElement::MoveTo(Point::new(1., 1.)),
+            PathElement::LineTo(Point::new(1., 5.)),
+        ]);
+    }
+
+    #[test]
+    fn path_segments() {
+        let mut path = Path::new();
+        path.move_to(0., 0.).line_to(4., 0.).line_to(4., 4.).close_path();
+        path.move_to(8., 8.).curve_to(9., 8., 10., 9., 10., 10.);
+
+        let segments = path.segments().collect::<Vec<Segment>>();
+
+        assert_eq!(segments, vec![
+            Segment::Line(Point::new(0., 0.), Point::new(4., 0.)),
+            Segment::Line(Point::new(4., 0.), Point::new(4., 4.)),
+            Segment::Line(Point::new(4., 4.), Point::new(0., 0.)),
+            Segment::Curve(Point::new(8., 8.), Point::new(9., 8.), Point::new(10., 9.),
+                           Point::new(10., 10.)),
+        ]);
+    }
+
+    #[test]
+    fn path_flatten_and_edges() {
+        // Setup
+        let mut path = Path::new();
+        path.move_to(0., 0.).line_to(4., 0.).line_to(4., 4.);
+        path.move_to(6., 0.).curve_to(7., 0., 8., 0., 9., 0.).close_path();
+
+        // Call
+        let polylines = path.flatten(0.1);
+        let edges = path.edges(0.1);
+
+        // Test
+        assert_eq!(polylines[0], Polyline {
+            points: vec![Point::new(0., 0.), Point::new(4., 0.), Point::new(4., 4.)],
+            closed: false,
+        });
+        assert_eq!(polylines[1], Polyline {
+            points: vec![Point::new(6., 0.), Point::new(9., 0.)],
+            closed: true,
+        });
+        // The first subpath is closed for filling; the flat curve's edges go there and back
+        assert_eq!(edges.len(), 5);
+        assert_eq!(edges[2].line.point2, Point::new(0., 0.));
+        assert_eq!(edges[1].direction, 1);
+        assert_eq!(edges[2].direction, -1);
+    }
+
+    #[test]
+    fn path_transform() {
+        // Setup
+        let mut path = Path::new();
+        path.move_to(1., 2.).curve_to(3., 2., 3., 4., 1., 4.).close_path();
+
+        // Call
+        let mut moved = path.transform(&Matrix::translation(10., 0.));
+
+        // Test
+        let expected = [
+            PathElement::MoveTo(Point::new(11., 2.)),
+            PathElement::CurveTo(Point::new(13., 2.), Point::new(13., 4.), Point::new(11., 4.)),
+            PathElement::ClosePath,
+        ];
+        assert_eq!(moved.elements(), &expected);
+        assert_eq!(moved.current_point(), Some(Point::new(11., 2.)));
+        // The transformed path carries on from the closed subpath's start
+        moved.line_to(20., 2.);
+        assert_eq!(moved.elements()[3], PathElement::MoveTo(Point::new(11., 2.)));
+    }
+
+    #[test]
+    fn path_quad_to() {
+        // Setup
+        let mut path = Path::new();
+
+        // Call
+        path.move_to(0., 0.).quad_to(3., 6., 6., 0.);
+
+        // Test
+        assert_eq!(path.elements()[1],
+                   PathElement::CurveTo(Point::new(2., 4.), Point::new(4., 4.),
+                                        Point::new(6., 0.)));
+        assert_eq!(path.current_point(), Some(Point::new(6., 0.)));
+        // The cubic peaks where the quadratic does, halfway up to the control point
+        let points = &path.flatten(0.001)[0].points;
+        let peak = points.iter().map(|point| point.y).fold(0., f32::max);
+        assert!((peak - 3.).abs() < 0.01);
+
+        // Without a current point the curve starts at the control point
+        let mut path = Path::new();
+        path.quad_to(3., 6., 6., 0.);
+        assert_eq!(path.elements()[0], PathElement::MoveTo(Point::new(3., 6.)));
+    }
+
+    #[test]
+    fn path_relative_elements() {
+        // Setup
+        let mut path = Path::new();
+
+        // Call
+        path.rel_line_to(5., 5.);
+        path.move_to(1., 1.).rel_line_to(2., 0.).rel_curve_to(1., 1., 2., 1., 3., 0.)
+            .close_path().rel_move_to(0., 4.);
+
+        // Test
+        assert_eq!(path.elements(),
+                   &[PathElement::MoveTo(Point::new(1., 1.)),
+                     PathElement::LineTo(Point::new(3., 1.)),
+                     PathElement::CurveTo(Point::new(4., 2.), Point::new(5., 2.),
+                                          Point::new(6., 1.)),
+                     PathElement::ClosePath,
+                     PathElement::MoveTo(Point::new(1., 5.))]);
+    }
+
+    #[test]
+    fn path_trapezoids() {
+        // Setup
+        // A square with a square hole, which takes a trapezoid each side of the hole
+        let path = Path::from_rectangles(&[Rectangle::new(0., 0., 6., 6.),
+                                           Rectangle::new(2., 2., 2., 2.)]);
+
+        // Call
+        let trapezoids = path.trapezoids(FillRule::EvenOdd, 0.1);
+
+        // Test
+        let area: f32 = trapezoids.iter().map(|trapezoid| {
+            let (left, right) = (trapezoid.left(), trapezoid.right());
+            let widths = (right.point1.x - left.point1.x) + (right.point2.x - left.point2.x);
+            widths / 2. * (trapezoid.bottom() - trapezoid.top())
+        }).sum();
+        assert_eq!(area, 32.);
+        assert_eq!(trapezoids.len(), 4);
+    }
+
+    #[test]
+    fn path_render_mask() {
+        // Setup
+        let path = Path::from_rectangles(&[Rectangle::new(0.5, 1., 3., 2.),
+                                           Rectangle::new(1., 1., 1., 1.)]);
+
+        // Call
+        let mask = path.render_mask(FillRule::EvenOdd, Antialias::Default, 0.1);
+        let aliased = path.render_mask(FillRule::EvenOdd, Antialias::None, 0.1);
+
+        // Test
+        // The mask reaches from the origin to the bottom right of the path
+        assert_eq!(mask.get_format(), Format::A8);
+        assert_eq!(mask.dimensions(), (4, 3));
+        assert_eq!(mask.get_pixel(0, 0).unwrap().alpha, 0.);
+        assert_eq!(mask.get_pixel(1, 1).unwrap().alpha, 0.);
+        assert_eq!(mask.get_pixel(2, 2).unwrap().alpha, 1.);
+        assert!((mask.get_pixel(0, 2).unwrap().alpha - 0.5).abs() < 0.05);
+        let alpha = aliased.get_pixel(0, 2).unwrap().alpha;
+        assert!(alpha == 0. || alpha == 1.);
+
+        // It matches a fill of the path, pixel for pixel
+        let mut filled = ImageSurface::create_for_format(Format::ARGB32, 4, 3);
+        {
+            let mut context = Context::create(&mut filled);
+            context.set_source_rgba(0., 0., 0., 1.);
+            context.set_fill_rule(FillRule::EvenOdd);
+            context.append_path(&path);
+            context.fill();
+        }
+        assert_eq!(filled.convert(Format::A8), mask);
+        assert_eq!(Path::new().render_mask(FillRule::Winding, Antialias::Default, 0.1)
+                       .dimensions(), (1, 1));
+    }
+
+    #[test]
+    fn path_simplify_bowtie() {
+        // Setup
+        // The two halves of a bowtie are wound opposite ways
+        let mut path = Path::new();
+        path.move_to(0., 0.).line_to(4., 4.).line_to(4., 0.).line_to(0., 4.).close_path();
+        assert!(crosses_itself(&path));
+
+        // Call
+        let simple = path.simplify(FillRule::Winding, 0.1);
+
+        // Test
+        assert!(!crosses_itself(&simple));
+        assert_eq!(signed_areas(&simple), vec![4., 4.]);
+        assert!(simple.iter().all(|element| match *element {
+            PathElement::MoveTo(_) | PathElement::LineTo(_) | PathElement::ClosePath => true,
+            PathElement::CurveTo(..) => false,
+        }));
+    }
+
+    #[test]
+    fn path_simplify_overlaps() {
+        // Setup
+        let path = Path::from_rectangles(&[Rectangle::new(0., 0., 4., 4.),
+                                           Rectangle::new(2., 2., 4., 4.)]);
+
+        // Call
+        let union = path.simplify(FillRule::Winding, 0.1);
+        let even_odd = path.simplify(FillRule::EvenOdd, 0.1);
+
+        // Test
+        // The union is a single octagon around both squares
+        assert_eq!(signed_areas(&union), vec![28.]);
+        assert_eq!(union.iter().filter(|e| matches!(e, PathElement::LineTo(_))).count(), 7);
+        // Without the overlap, two L shapes are left, which only touch at their corners
+        assert!(!crosses_itself(&even_odd));
+        assert_eq!(signed_areas(&even_odd).iter().sum::<f32>(), 24.);
+    }
+
+    #[test]
+    fn path_simplify_star() {
+        // Setup
+        // A pentagram, whose middle is wound twice
+        let mut path = Path::new();
+        for point in 0..5 {
+            let angle = (point * 2) as f32 * 2. * PI / 5.;
+            path.line_to(50. + 40. * angle.sin(), 50. - 40. * angle.cos());
+        }
+        path.close_path();
+
+        // Call
+        let winding = path.simplify(FillRule::Winding, 0.1);
+        let even_odd = path.simplify(FillRule::EvenOdd, 0.1);
+
+        // Test
+        // The outline of the star, and its five points without the middle
+        assert!(!crosses_itself(&winding) && !crosses_itself(&even_odd));
+        assert_eq!(winding.iter().filter(|e| matches!(e, PathElement::MoveTo(_))).count(), 1);
+        assert_eq!(winding.iter().filter(|e| matches!(e, PathElement::LineTo(_))).count(), 9);
+        let points = signed_areas(&even_odd);
+        assert_eq!(points.len(), 5);
+        assert!(points.iter().all(|&area| area > 0.));
+        let middle = signed_areas(&winding)[0] - points.iter().sum::<f32>();
+        // The middle pentagon's circumradius is 40 cos(72°) / cos(36°)
+        let radius = 40. * (2. * PI / 5.).cos() / (PI / 5.).cos();
+        let pentagon = 5. / 2. * radius * radius * (2. * PI / 5.).sin();
+        assert!((middle - pentagon).abs() < 0.5, "{} {}", middle, pentagon);
+    }
+
+    #[test]
+    fn path_simplify_hole() {
+        // Setup
+        // Both squares are wound the same way, so the inner one is only a hole under even-odd
+        let path = Path::from_rectangles(&[Rectangle::new(0., 0., 6., 6.),
+                                           Rectangle::new(2., 2., 2., 2.)]);
+
+        // Call
+        let winding = path.simplify(FillRule::Winding, 0.1);
+        let even_odd = path.simplify(FillRule::EvenOdd, 0.1);
+
+        // Test
+        assert_eq!(signed_areas(&winding), vec![36.]);
+        let mut areas = signed_areas(&even_odd);
+        areas.sort_by(|a, b| a.total_cmp(b));
+        assert_eq!(areas, vec![-4., 36.]);
+        // The result fills the same under either rule
+        assert_eq!(even_odd.simplify(FillRule::Winding, 0.1), even_odd);
+    }
+
+    #[test]
+    fn path_boolean_operations() {
+        // Setup
+        let first = Path::from_rectangles(&[Rectangle::new(0., 0., 4., 4.)]);
+        let second = Path::from_rectangles(&[Rectangle::new(2., 2., 4., 4.)]);
+        let area = |path: &Path| signed_areas(path).iter().sum::<f32>();
+
+        // Call
+        let union = first.union(&second, FillRule::Winding, 0.1);
+        let intersection = first.intersect(&second, FillRule::Winding, 0.1);
+        let difference = first.subtract(&second, FillRule::Winding, 0.1);
+        let xor = first.xor(&second, FillRule::Winding, 0.1);
+
+        // Test
+        assert_eq!(area(&union), 28.);
+        assert_eq!(area(&intersection), 4.);
+        assert_eq!(area(&difference), 12.);
+        assert_eq!(area(&xor), 24.);
+        assert!([union, intersection, difference, xor].iter().all(|path| !crosses_itself(path)));
+        // Subtracting in the other order leaves the other corner
+        let other_difference = second.subtract(&first, FillRule::Winding, 0.1);
+        assert_eq!(area(&other_difference), 12.);
+        assert!(other_difference.iter().all(|element| match *element {
+            PathElement::MoveTo(point) | PathElement::LineTo(point) => {
+                point.x >= 2. && point.y >= 2.
+            },
+            _ => true,
+        }));
+    }
+
+    #[test]
+    fn path_boolean_counts_each_path_on_its_own() {
+        // Setup
+        // Two copies of a square wound the same way, and a square wound the other way, which
+        // would cancel one of them out if the winding numbers were added together
+        let square = Path::from_rectangles(&[Rectangle::new(0., 0., 4., 4.)]);
+        let twice = Path::from_rectangles(&[Rectangle::new(0., 0., 4., 4.),
+                                            Rectangle::new(0., 0., 4., 4.)]);
+        let mut backwards = Path::new();
+        backwards.move_to(0., 0.).line_to(0., 4.).line_to(4., 4.).line_to(4., 0.).close_path();
+        let circle = Path::from_ellipses(&[Ellipse::new(2., 2., 2., 2.)], 0.01);
+
+        // Call
+        let union = twice.union(&backwards, FillRule::Winding, 0.1);
+        let even_odd = twice.intersect(&backwards, FillRule::EvenOdd, 0.1);
+        let bite = square.subtract(&circle, FillRule::Winding, 0.01);
+
+        // Test
+        assert_eq!(signed_areas(&union), vec![16.]);
+        // Under even-odd, the doubled square fills nothing
+        assert!(even_odd.is_empty());
+        // The corners left around the circle
+        let area: f32 = signed_areas(&bite).iter().sum();
+        assert!((area - (16. - 4. * PI)).abs() < 0.05, "{}", area);
+        assert!(!crosses_itself(&bite));
+    }
+
+    #[test]
+    fn path_offset() {
+        // Setup
+        let square = Path::from_rectangles(&[Rectangle::new(0., 0., 4., 4.)]);
+        let area = |path: &Path| signed_areas(path).iter().sum::<f32>();
+
+        // Call
+        let miter = square.offset(1., LineJoin::Miter, 10., FillRule::Winding, 0.01);
+        let bevel = square.offset(1., LineJoin::Bevel, 10., FillRule::Winding, 0.01);
+        let round = square.offset(1., LineJoin::Round, 10., FillRule::Winding, 0.01);
+        let inset = square.offset(-1., LineJoin::Round, 10., FillRule::Winding, 0.01);
+
+        // Test
+        assert_eq!(miter, Path::from_rectangles(&[Rectangle::new(-1., -1., 6., 6.)]));
+        // Each corner loses half of a 1 by 1 square, or the part of it outside a quarter circle
+        assert_eq!(area(&bevel), 34.);
+        assert!((area(&round) - (32. + PI)).abs() < 0.05, "{}", area(&round));
+        // Corners cut into stay sharp
+        assert_eq!(inset, Path::from_rectangles(&[Rectangle::new(1., 1., 2., 2.)]));
+        assert!(square.offset(-2., LineJoin::Miter, 10., FillRule::Winding, 0.01).is_empty());
+        assert_eq!(square.offset(0., LineJoin::Miter, 10., FillRule::Winding, 0.01),
+                   square.simplify(FillRule::Winding, 0.01));
+        // A miter limit too short for right angles bevels them
+        assert_eq!(square.offset(1., LineJoin::Miter, 1., FillRule::Winding, 0.01), bevel);
+    }
+
+    #[test]
+    fn path_offset_keeps_holes() {
+        // Setup
+        // A ring, whose hole shrinks as the ring grows
+        let mut ring = Path::from_rectangles(&[Rectangle::new(0., 0., 10., 10.)]);
+        ring.move_to(3., 3.).line_to(3., 7.).line_to(7., 7.).line_to(7., 3.).close_path();
+
+        // Call
+        let grown = ring.offset(1., LineJoin::Miter, 10., FillRule::Winding, 0.1);
+        let closed = ring.offset(2., LineJoin::Miter, 10., FillRule::Winding, 0.1);
+
+        // Test
+        let mut areas = signed_areas(&grown);
+        areas.sort_by(|a, b| a.total_cmp(b));
+        assert_eq!(areas, vec![-4., 144.]);
+        assert_eq!(signed_areas(&closed), vec![196.]);
+    }
+
+    #[test]
+    fn arc_segments_follow_circle() {
+        // Setup
+        let center = Point::new(5., 5.);
+
+        // Call
+        let segments = arc_segments(center, 10., 0., PI, 0.01);
+
+        // Test
+        // A half circle takes at least two pieces, which join up
+        assert!(segments.len() >= 2);
+        let mut previous_end = Point::new(15., 5.);
+        for segment in segments.iter() {
+            match *segment {
+                Segment::Curve(a, _, _, d) => {
+                    assert!((a.x - previous_end.x).abs() < 1e-4);
+                    assert!((a.y - previous_end.y).abs() < 1e-4);
+                    previous_end = d;
+                },
+                _ => panic!("expected curves"),
+            }
+        }
+        assert!((previous_end.x + 5.).abs() < 1e-4 && (previous_end.y - 5.).abs() < 1e-4);
+
+        // The flattened curves stay within the tolerance of the circle
+        let mut path = Path::new();
+        path.move_to(15., 5.);
+        for segment in segments.iter() {
+            if let Segment::Curve(_, b, c, d) = *segment {
+                path.curve_to(b.x, b.y, c.x, c.y, d.x, d.y);
+            }
+        }
+        let points = &path.flatten(0.001)[0].points;
+        assert!(points.iter().all(|point| {
+            ((point.x - 5.).hypot(point.y - 5.) - 10.).abs() < 0.011
+        }));
+        // Going the positive way round from 0 passes through the positive y axis
+        assert!(points.iter().any(|point| point.y > 14.9));
+    }
+
+    #[test]
+    fn arc_segments_error_bound() {
+        for &radius in [1., 10., 500.].iter() {
+            for &tolerance in [1., 0.1, 0.001].iter() {
+                // Call
+                let segments = arc_segments(Point::new(0., 0.), radius, 0., 2. * PI, tolerance);
+
+                // Test
+                // Every point sampled along the curves is within the tolerance of the circle
+                for segment in segments.iter() {
+                    if let Segment::Curve(a, b, c, d) = *segment {
+                        for step in 0..=100 {
+                            let t = step as f32 / 100.;
+                            let u = 1. - t;
+                            let (wa, wb, wc, wd) = (u * u * u, 3. * u * u * t, 3. * u * t * t,
+                                                    t * t * t);
+                            let x = wa * a.x + wb * b.x + wc * c.x + wd * d.x;
+                            let y = wa * a.y + wb * b.y + wc * c.y + wd * d.y;
+                            let error = (x.hypot(y) - radius).abs();
+                            assert!(error <= tolerance + radius * 1e-6, "{} {}", radius, error);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn arc_segments_tolerance() {
+        // A tighter tolerance, or a larger radius, takes more pieces
+        let center = Point::new(0., 0.);
+        assert_eq!(arc_segments(center, 1., 0., FRAC_PI_2, 0.1).len(), 1);
+        assert!(arc_segments(center, 1000., 0., FRAC_PI_2, 0.1).len() > 1);
+        // A negative sweep goes the other way round
+        match arc_segments(center, 1., 0., -FRAC_PI_2, 0.1)[0] {
+            Segment::Curve(_, _, _, d) => assert!(d.x.abs() < 1e-6 && (d.y + 1.).abs() < 1e-6),
+            _ => panic!("expected a curve"),
+        }
+    }
+}
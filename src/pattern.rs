@@ -0,0 +1,483 @@
+/*
+ * Cairus - a reimplementation of the cairo graphics library in Rust
+ *
+ * Copyright © 2017 CairusOrg
+ *
+ * This library is free software; you can redistribute it and/or
+ * modify it either under the terms of the GNU Lesser General Public
+ * License version 2.1 as published by the Free Software Foundation
+ * (the "LGPL") or, at your option, under the terms of the Mozilla
+ * Public License Version 2.0 (the "MPL"). If you do not alter this
+ * notice, a recipient may use your version of this file under either
+ * the MPL or the LGPL.
+ *
+ * You should have received a copy of the LGPL along with this library
+ * in the file LICENSE-LGPL-2_1; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Suite 500, Boston, MA 02110-1335, USA
+ * You should have received a copy of the MPL along with this library
+ * in the file LICENSE-MPL-2_0
+ *
+ * The contents of this file are subject to the Mozilla Public License
+ * Version 2.0 (the "License"); you may not use this file except in
+ * compliance with the License. You may obtain a copy of the License at
+ * http://www.mozilla.org/MPL/
+ *
+ * This software is distributed on an "AS IS" basis, WITHOUT WARRANTY
+ * OF ANY KIND, either express or implied. See the LGPL or the MPL for
+ * the specific language governing rights and limitations.
+ *
+ * The Original Code is the cairus graphics library.
+ *
+ * Contributor(s):
+ *	CairusOrg
+ *
+ */
+
+//! Patterns: what drawing operations composite onto the target.
+//!
+//! A `Pattern` gives the color of every point of user space.  A solid pattern is the same color
+//! everywhere, while a surface pattern reads the pixels of another ImageSurface, placed in user
+//! space by the pattern's matrix, so one image can be composited onto another.
+//!
+//! Points rarely land on pixel centers once a surface pattern is scaled or rotated, so the
+//! pattern's filter decides what color lies between pixels.  Nearest takes the pixel the point
+//! falls in; Bilinear mixes the four pixels whose centers surround the point by how close it is to
+//! each.  Good and Best do the same while the surface is drawn at its own size or larger, but
+//! once it is reduced, and a device pixel spans several of its pixels, they average every pixel
+//! under the device pixel's footprint, weighted by how much of each it covers.  A reduced image
+//! then keeps its fine detail as shades rather than aliasing into moiré.
+//!
+//! The pattern's extend decides what lies outside the surface: nothing, repeated tiles of it,
+//! mirrored tiles, or its edge pixels stretched outwards.  Filtering happens after extending, so
+//! a repeated image blends across the seams between tiles.
+
+use common_geometry::Point;
+use matrix::Matrix;
+use surfaces::ImageSurface;
+use types::{Extend, Filter, Rgba};
+
+/// ## Pattern
+///
+/// A source for drawing operations.  Analogous to cairo_pattern_t.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Pattern {
+    /// A single color everywhere.
+    Solid(SolidPattern),
+    /// The pixels of an ImageSurface.
+    Surface(SurfacePattern),
+}
+
+impl Pattern {
+    /// Returns a solid Pattern of the given color, which is premultiplied as Rgba::new does.
+    ///
+    /// Analogous to cairo_pattern_create_rgba().
+    ///
+    /// # Usage
+    /// let red = Pattern::rgba(1., 0., 0., 1.);
+    pub fn rgba(red: f32, green: f32, blue: f32, alpha: f32) -> Pattern {
+        Pattern::Solid(SolidPattern::new(Rgba::new(red, green, blue, alpha)))
+    }
+
+    /// Returns a Pattern that reads the pixels of `surface`.
+    ///
+    /// Analogous to cairo_pattern_create_for_surface().
+    ///
+    /// # Usage
+    /// let image = Pattern::for_surface(surface);
+    pub fn for_surface(surface: ImageSurface) -> Pattern {
+        Pattern::Surface(SurfacePattern::new(surface))
+    }
+
+    /// Returns the color of the pattern at `point`, in user space, filtered as if a unit of user
+    /// space were a device pixel.
+    pub fn color_at(&self, point: Point) -> Rgba {
+        self.filtered_color_at(point, self.footprint(&Matrix::identity()))
+    }
+
+    // Returns the size, in the source's pixel space, of the area a device pixel covers, given the
+    // matrix from device to user space.  Always a pixel for solid patterns.
+    pub(crate) fn footprint(&self, to_user: &Matrix) -> Point {
+        match *self {
+            Pattern::Solid(_) => Point::new(1., 1.),
+            Pattern::Surface(ref surface) => footprint(&to_user.multiply(&surface.matrix)),
+        }
+    }
+
+    // Returns the color of the pattern at `point`, in user space, filtered over `footprint` as
+    // returned by Pattern::footprint.
+    pub(crate) fn filtered_color_at(&self, point: Point, footprint: Point) -> Rgba {
+        match *self {
+            Pattern::Solid(ref solid) => solid.color,
+            Pattern::Surface(ref surface) => {
+                sample(&surface.surface, surface.matrix.transform_point(point), surface.filter,
+                       surface.extend, footprint)
+            },
+        }
+    }
+}
+
+/// ## SolidPattern
+///
+/// A pattern of a single premultiplied color.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SolidPattern {
+    /// The color of every point.
+    pub color: Rgba,
+}
+
+impl SolidPattern {
+    /// Returns a SolidPattern of `color`.
+    pub fn new(color: Rgba) -> SolidPattern {
+        SolidPattern { color }
+    }
+}
+
+/// ## SurfacePattern
+///
+/// A pattern that reads the pixels of an ImageSurface.
+///
+/// The pattern's matrix maps user space to the surface's pixel space, as in
+/// cairo_pattern_set_matrix(), and is the identity by default, which puts the surface's top left
+/// corner at the user space origin.  Colors between pixels are found with the pattern's filter,
+/// Filter::Good by default, and outside the surface with its extend, Extend::None by default,
+/// both as in cairo.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SurfacePattern {
+    surface: ImageSurface,
+    matrix: Matrix,
+    filter: Filter,
+    extend: Extend,
+}
+
+impl SurfacePattern {
+    /// Returns a SurfacePattern reading `surface`, placed at the user space origin.
+    pub fn new(surface: ImageSurface) -> SurfacePattern {
+        SurfacePattern {
+            surface,
+            matrix: Matrix::identity(),
+            filter: Filter::Good,
+            extend: Extend::None,
+        }
+    }
+
+    /// Returns the surface the pattern reads.
+    pub fn surface(&self) -> &ImageSurface {
+        &self.surface
+    }
+
+    /// Sets the matrix that maps user space to the surface's pixel space.
+    ///
+    /// Analogous to cairo_pattern_set_matrix().  To draw the surface at (x, y) in user space, the
+    /// matrix should translate by (-x, -y).
+    ///
+    /// # Usage
+    /// pattern.set_matrix(Matrix::translation(-10., -10.));
+    pub fn set_matrix(&mut self, matrix: Matrix) {
+        self.matrix = matrix;
+    }
+
+    /// Returns the matrix that maps user space to the surface's pixel space.
+    pub fn get_matrix(&self) -> Matrix {
+        self.matrix
+    }
+
+    /// Sets how the surface is sampled between its pixels.
+    ///
+    /// Analogous to cairo_pattern_set_filter().
+    ///
+    /// # Usage
+    /// pattern.set_filter(Filter::Nearest);
+    pub fn set_filter(&mut self, filter: Filter) {
+        self.filter = filter;
+    }
+
+    /// Returns how the surface is sampled between its pixels.
+    pub fn get_filter(&self) -> Filter {
+        self.filter
+    }
+
+    /// Sets what the pattern shows outside the surface.
+    ///
+    /// Analogous to cairo_pattern_set_extend().
+    ///
+    /// # Usage
+    /// pattern.set_extend(Extend::Repeat);
+    pub fn set_extend(&mut self, extend: Extend) {
+        self.extend = extend;
+    }
+
+    /// Returns what the pattern shows outside the surface.
+    pub fn get_extend(&self) -> Extend {
+        self.extend
+    }
+
+    /// Returns the color of the surface at `point`, in user space, filtered as if a unit of user
+    /// space were a device pixel.
+    pub fn color_at(&self, point: Point) -> Rgba {
+        sample(&self.surface, self.matrix.transform_point(point), self.filter, self.extend,
+               footprint(&self.matrix))
+    }
+}
+
+// Returns the size of the area a device pixel covers in a surface's pixel space, given the
+// matrix `to_source` from device space to the surface's pixel space.  As in pixman, each side is
+// how far the surface coordinate moves for a device step of one pixel in any direction, so a
+// rotation alone keeps a footprint of one pixel.
+pub(crate) fn footprint(to_source: &Matrix) -> Point {
+    Point::new(to_source.xx.hypot(to_source.xy), to_source.yx.hypot(to_source.yy))
+}
+
+// Returns the color of `surface` at `point` in its pixel space, found between pixels with
+// `filter` and outside the surface with `extend`.  `footprint`, from `footprint`, is the size of
+// the area to average over when Filter::Good or Filter::Best reduce the surface.
+pub(crate) fn sample(surface: &ImageSurface, point: Point, filter: Filter, extend: Extend,
+                     footprint: Point) -> Rgba {
+    // The pixel in column `x` and row `y`, which are whole numbers, with positions outside the
+    // surface brought inside as the extend says.  Transparent black if they are left outside.
+    let pixel = |x: f32, y: f32| {
+        let column = extend_position(x, surface.width, extend);
+        let row = extend_position(y, surface.height, extend);
+        match (column, row) {
+            (Some(column), Some(row)) => surface.get_pixel(column, row)
+                .unwrap_or_else(|_| Rgba::new(0., 0., 0., 0.)),
+            _ => Rgba::new(0., 0., 0., 0.),
+        }
+    };
+    let reduced = footprint.x > 1. || footprint.y > 1.;
+    match filter {
+        Filter::Nearest => pixel(point.x.floor(), point.y.floor()),
+        Filter::Good | Filter::Best if reduced => {
+            // A box the size of the footprint, at least a pixel across, which is then the same as
+            // bilinear filtering.  Past the size of the surface it would only average the
+            // surface, or its tiles, again, so it stops there.
+            let width = footprint.x.max(1.).min(surface.width.max(1) as f32);
+            let height = footprint.y.max(1.).min(surface.height.max(1) as f32);
+            let (left, top) = (point.x - width / 2., point.y - height / 2.);
+            let (right, bottom) = (left + width, top + height);
+            let mut color = Rgba::new(0., 0., 0., 0.);
+            let mut row = top.floor();
+            while row < bottom {
+                let row_weight = bottom.min(row + 1.) - top.max(row);
+                let mut column = left.floor();
+                while column < right {
+                    let weight = row_weight * (right.min(column + 1.) - left.max(column));
+                    let sample = pixel(column, row);
+                    color.red += sample.red * weight;
+                    color.green += sample.green * weight;
+                    color.blue += sample.blue * weight;
+                    color.alpha += sample.alpha * weight;
+                    column += 1.;
+                }
+                row += 1.;
+            }
+            let area = width * height;
+            Rgba { red: color.red / area, green: color.green / area, blue: color.blue / area,
+                   alpha: color.alpha / area }
+        },
+        Filter::Bilinear | Filter::Good | Filter::Best => {
+            // Measured from the center of the pixel up and to the left of the point
+            let (x, y) = (point.x - 0.5, point.y - 0.5);
+            let (left, top) = (x.floor(), y.floor());
+            let (right_weight, bottom_weight) = (x - left, y - top);
+            let samples = [
+                (pixel(left, top), (1. - right_weight) * (1. - bottom_weight)),
+                (pixel(left + 1., top), right_weight * (1. - bottom_weight)),
+                (pixel(left, top + 1.), (1. - right_weight) * bottom_weight),
+                (pixel(left + 1., top + 1.), right_weight * bottom_weight),
+            ];
+            let mut color = Rgba::new(0., 0., 0., 0.);
+            for &(sample, weight) in samples.iter() {
+                color.red += sample.red * weight;
+                color.green += sample.green * weight;
+                color.blue += sample.blue * weight;
+                color.alpha += sample.alpha * weight;
+            }
+            color
+        },
+    }
+}
+
+// Returns the position within `0..size` that `position`, a whole number, reads under `extend`, or
+// None if it reads nothing.
+pub(crate) fn extend_position(position: f32, size: usize, extend: Extend) -> Option<usize> {
+    if size == 0 {
+        return None;
+    }
+
+    let size = size as f32;
+    let position = match extend {
+        Extend::None => position,
+        Extend::Repeat => position.rem_euclid(size),
+        Extend::Reflect => {
+            let position = position.rem_euclid(2. * size);
+            if position >= size { 2. * size - 1. - position } else { position }
+        },
+        Extend::Pad => position.max(0.).min(size - 1.),
+    };
+    if position >= 0. && position < size { Some(position as usize) } else { None }
+}
+
+#[cfg(test)]
+mod tests {
+    use common_geometry::Point;
+    use matrix::Matrix;
+    use surfaces::{Format, ImageSurface};
+    use types::{Extend, Filter, Rgba};
+    use super::{Pattern, SurfacePattern, extend_position};
+
+    // Returns a 2x2 surface with red, green, blue and white pixels
+    fn checker() -> ImageSurface {
+        let mut surface = ImageSurface::create_for_format(Format::ARGB32, 2, 2);
+        surface.set_pixel(0, 0, Rgba::new(1., 0., 0., 1.)).unwrap();
+        surface.set_pixel(1, 0, Rgba::new(0., 1., 0., 1.)).unwrap();
+        surface.set_pixel(0, 1, Rgba::new(0., 0., 1., 1.)).unwrap();
+        surface.set_pixel(1, 1, Rgba::new(1., 1., 1., 1.)).unwrap();
+        surface
+    }
+
+    #[test]
+    fn test_solid_pattern() {
+        let pattern = Pattern::rgba(1., 0., 0., 0.5);
+        assert_eq!(pattern.color_at(Point::new(-3., 100.)), Rgba::new(1., 0., 0., 0.5));
+    }
+
+    // Returns a pattern reading `checker()` pixel by pixel
+    fn nearest_checker() -> Pattern {
+        let mut pattern = SurfacePattern::new(checker());
+        pattern.set_filter(Filter::Nearest);
+        Pattern::Surface(pattern)
+    }
+
+    #[test]
+    fn test_surface_pattern_color_at() {
+        // Setup
+        let pattern = nearest_checker();
+
+        // Test
+        assert_eq!(pattern.color_at(Point::new(0.5, 0.5)), Rgba::new(1., 0., 0., 1.));
+        assert_eq!(pattern.color_at(Point::new(1.5, 0.2)), Rgba::new(0., 1., 0., 1.));
+        assert_eq!(pattern.color_at(Point::new(1.9, 1.9)), Rgba::new(1., 1., 1., 1.));
+        // Outside the surface is transparent
+        assert_eq!(pattern.color_at(Point::new(2.5, 0.5)).alpha, 0.);
+        assert_eq!(pattern.color_at(Point::new(-0.5, 0.5)).alpha, 0.);
+    }
+
+    #[test]
+    fn test_surface_pattern_matrix() {
+        // Setup
+        let mut pattern = SurfacePattern::new(checker());
+        pattern.set_filter(Filter::Nearest);
+
+        // Call
+        // Draw the surface at (10, 10), twice the size
+        let mut matrix = Matrix::scaling(0.5, 0.5);
+        matrix.translate(-10., -10.);
+        pattern.set_matrix(matrix);
+
+        // Test
+        assert_eq!(pattern.get_matrix(), matrix);
+        assert_eq!(pattern.color_at(Point::new(11., 11.)), Rgba::new(1., 0., 0., 1.));
+        assert_eq!(pattern.color_at(Point::new(13., 11.)), Rgba::new(0., 1., 0., 1.));
+        assert_eq!(pattern.color_at(Point::new(9., 11.)).alpha, 0.);
+    }
+
+    #[test]
+    fn test_surface_pattern_bilinear() {
+        // Setup
+        let mut pattern = SurfacePattern::new(checker());
+        assert_eq!(pattern.get_filter(), Filter::Good);
+        pattern.set_filter(Filter::Bilinear);
+
+        // Test
+        // Pixel centers keep their own color
+        assert_eq!(pattern.color_at(Point::new(0.5, 0.5)), Rgba::new(1., 0., 0., 1.));
+        // Halfway between the red and green centers is an even mix
+        assert_eq!(pattern.color_at(Point::new(1., 0.5)), Rgba::new(0.5, 0.5, 0., 1.));
+        // The middle mixes all four
+        assert_eq!(pattern.color_at(Point::new(1., 1.)), Rgba::new(0.5, 0.5, 0.5, 1.));
+        // Past the outer pixel centers the surface fades into the transparent outside
+        let edge = pattern.color_at(Point::new(0.25, 0.5));
+        assert_eq!(edge, Rgba::new(1., 0., 0., 0.75));
+    }
+
+    #[test]
+    fn test_good_filter_averages_when_reducing() {
+        // Setup
+        // Alternating white and black columns, drawn at a quarter of their size
+        let mut stripes = ImageSurface::create_for_format(Format::ARGB32, 8, 8);
+        for (position, pixel) in stripes.iter_mut().enumerate() {
+            let shade = if position % 2 == 0 { 1. } else { 0. };
+            *pixel = Rgba::new(shade, shade, shade, 1.);
+        }
+        let mut pattern = SurfacePattern::new(stripes);
+        pattern.set_extend(Extend::Repeat);
+        pattern.set_matrix(Matrix::scaling(4., 4.));
+
+        // Call
+        let good = pattern.color_at(Point::new(0.375, 0.375));
+        pattern.set_filter(Filter::Bilinear);
+        let bilinear = pattern.color_at(Point::new(0.375, 0.375));
+
+        // Test
+        // The point lands on the center of a black column, which is all bilinear filtering sees,
+        // while the box over the four columns a unit spans mixes them to grey
+        assert_eq!(bilinear, Rgba::new(0., 0., 0., 1.));
+        assert_eq!(good, Rgba::new(0.5, 0.5, 0.5, 1.));
+    }
+
+    #[test]
+    fn test_nearest_is_blocky() {
+        let mut pattern = SurfacePattern::new(checker());
+        pattern.set_filter(Filter::Nearest);
+        assert_eq!(pattern.color_at(Point::new(0.9, 0.5)), Rgba::new(1., 0., 0., 1.));
+        assert_eq!(pattern.color_at(Point::new(1.1, 0.5)), Rgba::new(0., 1., 0., 1.));
+    }
+
+    #[test]
+    fn test_extend_position() {
+        // Setup
+        let positions = [-4., -1., 0., 2., 3., 5.];
+
+        // Call
+        let read = |extend| positions.iter()
+            .map(|position| extend_position(*position, 3, extend))
+            .collect::<Vec<Option<usize>>>();
+
+        // Test
+        assert_eq!(read(Extend::None), vec![None, None, Some(0), Some(2), None, None]);
+        assert_eq!(read(Extend::Repeat),
+                   vec![Some(2), Some(2), Some(0), Some(2), Some(0), Some(2)]);
+        assert_eq!(read(Extend::Reflect),
+                   vec![Some(2), Some(0), Some(0), Some(2), Some(2), Some(0)]);
+        assert_eq!(read(Extend::Pad), vec![Some(0), Some(0), Some(0), Some(2), Some(2), Some(2)]);
+        assert_eq!(extend_position(0., 0, Extend::Repeat), None);
+    }
+
+    #[test]
+    fn test_surface_pattern_extend() {
+        // Setup
+        let mut pattern = SurfacePattern::new(checker());
+        pattern.set_filter(Filter::Nearest);
+        let (red, green) = (Rgba::new(1., 0., 0., 1.), Rgba::new(0., 1., 0., 1.));
+        assert_eq!(pattern.get_extend(), Extend::None);
+
+        // Test
+        pattern.set_extend(Extend::Repeat);
+        assert_eq!(pattern.color_at(Point::new(4.5, 0.5)), red);
+        assert_eq!(pattern.color_at(Point::new(-0.5, -1.5)), green);
+        pattern.set_extend(Extend::Reflect);
+        assert_eq!(pattern.color_at(Point::new(2.5, 0.5)), green);
+        assert_eq!(pattern.color_at(Point::new(3.5, 0.5)), red);
+        pattern.set_extend(Extend::Pad);
+        assert_eq!(pattern.color_at(Point::new(100., 0.5)), green);
+        assert_eq!(pattern.color_at(Point::new(-100., -100.)), red);
+    }
+
+    #[test]
+    fn test_repeat_filters_across_seams() {
+        // Bilinear filtering of a repeated surface mixes the last column into the first
+        let mut pattern = SurfacePattern::new(checker());
+        pattern.set_extend(Extend::Repeat);
+        assert_eq!(pattern.color_at(Point::new(0., 0.5)), Rgba::new(0.5, 0.5, 0., 1.));
+    }
+}
@@ -0,0 +1,226 @@
+/*
+ * Cairus - a reimplementation of the cairo graphics library in Rust
+ *
+ * Copyright © 2017 CairusOrg
+ *
+ * This library is free software; you can redistribute it and/or
+ * modify it either under the terms of the GNU Lesser General Public
+ * License version 2.1 as published by the Free Software Foundation
+ * (the "LGPL") or, at your option, under the terms of the Mozilla
+ * Public License Version 2.0 (the "MPL"). If you do not alter this
+ * notice, a recipient may use your version of this file under either
+ * the MPL or the LGPL.
+ *
+ * You should have received a copy of the LGPL along with this library
+ * in the file LICENSE-LGPL-2_1; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Suite 500, Boston, MA 02110-1335, USA
+ * You should have received a copy of the MPL along with this library
+ * in the file LICENSE-MPL-2_0
+ *
+ * The contents of this file are subject to the Mozilla Public License
+ * Version 2.0 (the "License"); you may not use this file except in
+ * compliance with the License. You may obtain a copy of the License at
+ * http://www.mozilla.org/MPL/
+ *
+ * This software is distributed on an "AS IS" basis, WITHOUT WARRANTY
+ * OF ANY KIND, either express or implied. See the LGPL or the MPL for
+ * the specific language governing rights and limitations.
+ *
+ * The Original Code is the cairus graphics library.
+ *
+ * Contributor(s):
+ *	CairusOrg
+ *
+ */
+
+//! Geometric predicates for the tessellator, with an opt-in exact mode.
+//!
+//! The sweep line makes its decisions (which side of an edge a point is on, which of two edges
+//! leaving a point is further left, where two edges cross) with plain f32 arithmetic by default.
+//! That is fast, but rounding can make those decisions contradict each other on nearly
+//! degenerate input, which shows up as cracks or overlaps in the output.  `Precision::Exact`
+//! computes the signs exactly instead, at some cost in speed.
+//!
+//! The exact signs use the fact that the product of two f32s is always exactly representable
+//! as an f64.  Each predicate is expanded into a sum of such products, and the sum is accumulated
+//! without rounding error as a floating-point expansion (J. R. Shewchuk, "Adaptive Precision
+//! Floating-Point Arithmetic and Fast Robust Geometric Predicates", 1997).
+
+use std::cmp::Ordering;
+use common_geometry::{LineSegment, Point};
+
+/// Selects how the tessellator evaluates its geometric predicates.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Precision {
+    /// Plain f32 arithmetic.  The default.
+    #[default]
+    Fast,
+    /// Exactly signed predicates and f64 intersection points, for watertight output on
+    /// pathological input.
+    Exact,
+}
+
+// Returns the sum of a and b and the rounding error of that sum, so that a + b == sum + error
+// exactly.
+fn two_sum(a: f64, b: f64) -> (f64, f64) {
+    let sum = a + b;
+    let b_virtual = sum - a;
+    let a_virtual = sum - b_virtual;
+    (sum, (a - a_virtual) + (b - b_virtual))
+}
+
+// Returns the exact sign of the sum of `terms`, each of which must be exact.
+fn exact_sign(terms: &[f64]) -> Ordering {
+    // Components are non-overlapping and in increasing order of magnitude
+    let mut expansion: Vec<f64> = Vec::with_capacity(terms.len() * 2);
+    for &term in terms {
+        let mut carry = term;
+        for component in expansion.iter_mut() {
+            let (sum, error) = two_sum(carry, *component);
+            *component = error;
+            carry = sum;
+        }
+        expansion.push(carry);
+    }
+
+    // The sign of an expansion is the sign of its most significant non-zero component.  Terms
+    // from non-finite coordinates can leave a NaN, which has no sign and counts as Equal, as in
+    // fast mode.
+    expansion.iter().rev()
+        .find(|component| **component != 0.)
+        .map_or(Ordering::Equal, |component| {
+            component.partial_cmp(&0.).unwrap_or(Ordering::Equal)
+        })
+}
+
+/// Returns the sign of the cross product of the vectors a1 -> a2 and b1 -> b2.
+///
+/// Greater means b turns counter-clockwise from a in a y-up frame (clockwise on screen, where y
+/// points down), Less the opposite, and Equal means they are parallel.
+pub fn cross_sign(a1: Point, a2: Point, b1: Point, b2: Point, precision: Precision) -> Ordering {
+    match precision {
+        Precision::Fast => {
+            let cross = (a2.x - a1.x) * (b2.y - b1.y) - (a2.y - a1.y) * (b2.x - b1.x);
+            cross.partial_cmp(&0.).unwrap_or(Ordering::Equal)
+        },
+        Precision::Exact => {
+            let product = |p: f32, q: f32| p as f64 * q as f64;
+            exact_sign(&[
+                product(a2.x, b2.y), -product(a2.x, b1.y), -product(a1.x, b2.y), product(a1.x, b1.y),
+                -product(a2.y, b2.x), product(a2.y, b1.x), product(a1.y, b2.x), -product(a1.y, b1.x),
+            ])
+        },
+    }
+}
+
+/// Returns which side of the line through `a` and `b` the point `c` is on.
+///
+/// Greater if a, b, c turn counter-clockwise in a y-up frame, Less if clockwise, and Equal if
+/// they are collinear.
+pub fn orientation(a: Point, b: Point, c: Point, precision: Precision) -> Ordering {
+    cross_sign(a, b, a, c, precision)
+}
+
+/// Returns the point where two segments cross, or None if they don't.
+///
+/// In exact mode the crossing test is exact and the point is computed in f64 before rounding,
+/// so segments that only just touch or only just miss are classified correctly.  Fast mode is
+/// `LineSegment::intersection`.  Parallel segments never cross, even where they overlap.
+pub fn intersection(line1: &LineSegment, line2: &LineSegment, precision: Precision)
+                    -> Option<Point> {
+    let (a1, a2, b1, b2) = (line1.point1, line1.point2, line2.point1, line2.point2);
+    if cross_sign(a1, a2, b1, b2, precision) == Ordering::Equal {
+        return None;
+    }
+    if precision == Precision::Fast {
+        return line1.intersection(line2);
+    }
+
+    let straddles = |p: Point, q: Point, r: Point, s: Point| {
+        let side_r = orientation(p, q, r, precision);
+        let side_s = orientation(p, q, s, precision);
+        side_r == Ordering::Equal || side_s == Ordering::Equal || side_r != side_s
+    };
+    if !straddles(a1, a2, b1, b2) || !straddles(b1, b2, a1, a2) {
+        return None;
+    }
+
+    let (dax, day) = (a2.x as f64 - a1.x as f64, a2.y as f64 - a1.y as f64);
+    let (dbx, dby) = (b2.x as f64 - b1.x as f64, b2.y as f64 - b1.y as f64);
+    let (ox, oy) = (b1.x as f64 - a1.x as f64, b1.y as f64 - a1.y as f64);
+    let t = (ox * dby - oy * dbx) / (dax * dby - day * dbx);
+    Some(Point::new((a1.x as f64 + t * dax) as f32, (a1.y as f64 + t * day) as f32))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cmp::Ordering;
+    use common_geometry::{LineSegment, Point};
+    use super::{cross_sign, exact_sign, intersection, orientation, Precision};
+
+    #[test]
+    fn exact_sign_cancellation() {
+        // 1e20 + 1 - 1e20 is 0 in floating point, but the true sum is positive
+        assert_eq!(exact_sign(&[1e20, 1., -1e20]), Ordering::Greater);
+        assert_eq!(exact_sign(&[1e20, -1e20]), Ordering::Equal);
+        assert_eq!(exact_sign(&[]), Ordering::Equal);
+    }
+
+    #[test]
+    fn exact_sign_not_a_number() {
+        assert_eq!(exact_sign(&[1., f64::NAN]), Ordering::Equal);
+        assert_eq!(exact_sign(&[f64::INFINITY, -f64::INFINITY]), Ordering::Equal);
+
+        let (a, b) = (Point::new(0., 0.), Point::new(f32::NAN, 1.));
+        for &precision in &[Precision::Fast, Precision::Exact] {
+            assert_eq!(orientation(a, b, Point::new(1., 0.), precision), Ordering::Equal);
+        }
+    }
+
+    #[test]
+    fn orientation_simple() {
+        let (a, b) = (Point::new(0., 0.), Point::new(1., 0.));
+        for &precision in &[Precision::Fast, Precision::Exact] {
+            assert_eq!(orientation(a, b, Point::new(0.5, 1.), precision), Ordering::Greater);
+            assert_eq!(orientation(a, b, Point::new(0.5, -1.), precision), Ordering::Less);
+            assert_eq!(orientation(a, b, Point::new(7., 0.), precision), Ordering::Equal);
+        }
+    }
+
+    #[test]
+    fn orientation_nearly_collinear() {
+        // c is a hair off the line through a and b, closer than f32 rounding can resolve
+        let a = Point::new(0.5, 0.5);
+        let b = Point::new(12., 12.);
+        let c = Point::new(24., 24.000002);
+        assert_eq!(orientation(a, b, c, Precision::Exact), Ordering::Greater);
+
+        // Exactly collinear points with inexact differences are still collinear
+        let (p, q, r) = (Point::new(0.1, 0.3), Point::new(0.2, 0.6), Point::new(0.3, 0.9));
+        let exact = orientation(p, q, r, Precision::Exact);
+        assert_eq!(exact, orientation(r, p, q, Precision::Exact));
+        assert_eq!(exact, orientation(q, r, p, Precision::Exact));
+    }
+
+    #[test]
+    fn cross_sign_parallel() {
+        let (a1, a2) = (Point::new(0., 0.), Point::new(3., 1.));
+        let (b1, b2) = (Point::new(5., 5.), Point::new(11., 7.));
+        assert_eq!(cross_sign(a1, a2, b1, b2, Precision::Exact), Ordering::Equal);
+    }
+
+    #[test]
+    fn exact_intersection() {
+        let line1 = LineSegment::new(0., 0., 1., 1.);
+        let line2 = LineSegment::new(1., 0., 0., 1.);
+        assert_eq!(intersection(&line1, &line2, Precision::Exact), Some(Point::new(0.5, 0.5)));
+
+        // Segments that would cross if they were longer don't intersect
+        let short = LineSegment::new(1., 0., 0.6, 0.4);
+        assert_eq!(intersection(&line1, &short, Precision::Exact), None);
+
+        // Parallel segments don't intersect
+        let parallel = LineSegment::new(0., 1., 1., 2.);
+        assert_eq!(intersection(&line1, &parallel, Precision::Exact), None);
+    }
+}
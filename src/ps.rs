@@ -0,0 +1,444 @@
+/*
+ * Cairus - a reimplementation of the cairo graphics library in Rust
+ *
+ * Copyright © 2017 CairusOrg
+ *
+ * This library is free software; you can redistribute it and/or
+ * modify it either under the terms of the GNU Lesser General Public
+ * License version 2.1 as published by the Free Software Foundation
+ * (the "LGPL") or, at your option, under the terms of the Mozilla
+ * Public License Version 2.0 (the "MPL"). If you do not alter this
+ * notice, a recipient may use your version of this file under either
+ * the MPL or the LGPL.
+ *
+ * You should have received a copy of the LGPL along with this library
+ * in the file LICENSE-LGPL-2_1; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Suite 500, Boston, MA 02110-1335, USA
+ * You should have received a copy of the MPL along with this library
+ * in the file LICENSE-MPL-2_0
+ *
+ * The contents of this file are subject to the Mozilla Public License
+ * Version 2.0 (the "License"); you may not use this file except in
+ * compliance with the License. You may obtain a copy of the License at
+ * http://www.mozilla.org/MPL/
+ *
+ * This software is distributed on an "AS IS" basis, WITHOUT WARRANTY
+ * OF ANY KIND, either express or implied. See the LGPL or the MPL for
+ * the specific language governing rights and limitations.
+ *
+ * The Original Code is the cairus graphics library.
+ *
+ * Contributor(s):
+ *	CairusOrg
+ *
+ */
+
+//! PostScript and Encapsulated PostScript output.
+//!
+//! A `PsSurface` records drawing page by page on a `PaginatedSurface` and writes it out as a
+//! multi-page PostScript document, analogous to cairo's PostScript surface.  Paths are written as
+//! PostScript paths, so the output stays sharp at any resolution.
+//!
+//! PostScript has no transparency and no compositing operators, so every color is drawn at full
+//! strength, however translucent, and every operator paints as Over does, except that Clear and
+//! Dest draw nothing.  Surface sources and masks are not written yet.
+
+use std::fmt::Write as FmtWrite;
+use std::fs::File;
+use std::io::{self, Write};
+use std::path;
+use command_buffer::CommandBuffer;
+use dash::DashPattern;
+use matrix::Matrix;
+use operators::Operator;
+use paginated::{PaginatedSurface, VectorRenderer, render_document};
+use path::{Path, PathElement};
+use pattern::Pattern;
+use stroke::StrokeStyle;
+use types::{FillRule, LineCap, LineJoin};
+
+/// ## PsSurface
+///
+/// A PostScript document of pages `width` by `height` points, a point being 1/72 inch.
+/// Analogous to cairo_ps_surface_create().
+///
+/// # Usage
+/// let mut document = PsSurface::new(595., 842.);
+/// document.record().set_source_rgba(0., 0., 1., 1.);
+/// document.record().arc(100., 100., 50., 0., 2. * PI);
+/// document.record().fill();
+/// document.show_page();
+/// document.to_file(Path::new("circle.ps"))?;
+#[derive(Debug, Clone, PartialEq)]
+pub struct PsSurface {
+    document: PaginatedSurface,
+    eps: bool,
+}
+
+impl PsSurface {
+    /// Returns a document with one empty page of `width` by `height` points.
+    pub fn new(width: f32, height: f32) -> PsSurface {
+        PsSurface {
+            document: PaginatedSurface::new(width, height),
+            eps: false,
+        }
+    }
+
+    /// Sets whether the document is written as Encapsulated PostScript, for placing in other
+    /// documents, rather than as a PostScript document for printing.  Analogous to
+    /// cairo_ps_surface_set_eps().  An EPS file holds a single page, so only the first is
+    /// written.
+    pub fn set_eps(&mut self, eps: bool) {
+        self.eps = eps;
+    }
+
+    /// Returns true if the document is written as Encapsulated PostScript.
+    pub fn get_eps(&self) -> bool {
+        self.eps
+    }
+
+    /// Returns the size of a page in points, as (width, height).
+    pub fn get_size(&self) -> (f32, f32) {
+        self.document.get_size()
+    }
+
+    /// Returns the document's drawing, for recording onto the end of it.
+    pub fn record(&mut self) -> &mut CommandBuffer {
+        self.document.record()
+    }
+
+    /// Finishes the current page; the next starts blank.  Analogous to cairo_show_page().
+    pub fn show_page(&mut self) {
+        self.document.show_page();
+    }
+
+    /// Finishes the current page; the next starts with a copy of its drawing.  Analogous to
+    /// cairo_copy_page().
+    pub fn copy_page(&mut self) {
+        self.document.copy_page();
+    }
+
+    /// Writes the document to `writer`.
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(self.document_text().as_bytes())
+    }
+
+    /// Writes the document to the file at `path`, replacing it if it exists.
+    pub fn to_file(&self, path: &path::Path) -> io::Result<()> {
+        self.write_to(&mut File::create(path)?)
+    }
+
+    // Returns the whole document
+    fn document_text(&self) -> String {
+        let (width, height) = self.document.get_size();
+        let mut renderer = PsRenderer {
+            text: String::new(),
+            pages: Vec::new(),
+            width,
+            height,
+            clipped: false,
+        };
+        render_document(&self.document, &mut renderer);
+        let mut pages = renderer.pages;
+        if self.eps {
+            pages.truncate(1);
+        }
+
+        let mut text = String::new();
+        if self.eps {
+            text.push_str("%!PS-Adobe-3.0 EPSF-3.0\n");
+        } else {
+            text.push_str("%!PS-Adobe-3.0\n");
+        }
+        text.push_str("%%Creator: cairus\n");
+        text.push_str("%%LanguageLevel: 2\n");
+        let _ = writeln!(text, "%%BoundingBox: 0 0 {} {}", width.ceil(), height.ceil());
+        if !self.eps {
+            let _ = writeln!(text, "%%Pages: {}", pages.len());
+        }
+        text.push_str("%%EndComments\n");
+
+        for (index, page) in pages.iter().enumerate() {
+            if !self.eps {
+                let _ = writeln!(text, "%%Page: {} {}", index + 1, index + 1);
+            }
+            text.push_str(page);
+        }
+        text.push_str("%%Trailer\n%%EOF\n");
+        text
+    }
+}
+
+// Writes drawing operations as PostScript onto the end of `text`, moving it to `pages` as each
+// page ends
+struct PsRenderer {
+    text: String,
+    pages: Vec<String>,
+    width: f32,
+    height: f32,
+    // Whether a gsave has been written to undo the clip with
+    clipped: bool,
+}
+
+impl PsRenderer {
+    // Writes `path` as a new PostScript path
+    fn path(&mut self, path: &Path) {
+        self.text.push_str("newpath\n");
+        for element in path.iter() {
+            let _ = match *element {
+                PathElement::MoveTo(point) => {
+                    writeln!(self.text, "{} {} moveto", number(point.x), number(point.y))
+                },
+                PathElement::LineTo(point) => {
+                    writeln!(self.text, "{} {} lineto", number(point.x), number(point.y))
+                },
+                PathElement::CurveTo(control1, control2, end) => {
+                    writeln!(self.text, "{} {} {} {} {} {} curveto", number(control1.x),
+                             number(control1.y), number(control2.x), number(control2.y),
+                             number(end.x), number(end.y))
+                },
+                PathElement::ClosePath => writeln!(self.text, "closepath"),
+            };
+        }
+    }
+
+    // Sets the color to draw `source` with `operator`, returning false if nothing should be
+    // drawn
+    fn color(&mut self, source: &Pattern, operator: Operator) -> bool {
+        let color = match *source {
+            _ if matches!(operator, Operator::Clear | Operator::Dest) => return false,
+            Pattern::Solid(ref solid) => solid.color,
+            Pattern::Surface(_) => return false,
+        };
+        if color.alpha <= 0. {
+            return false;
+        }
+        let channel = |value: f32| number((value / color.alpha).clamp(0., 1.));
+        let _ = writeln!(self.text, "{} {} {} setrgbcolor", channel(color.red),
+                         channel(color.green), channel(color.blue));
+        true
+    }
+}
+
+impl VectorRenderer for PsRenderer {
+    fn begin_page(&mut self, _: usize) {
+        // PostScript's origin is at the bottom left, with y pointing up
+        let _ = writeln!(self.text, "gsave 0 {} translate 1 -1 scale", number(self.height));
+    }
+
+    fn end_page(&mut self) {
+        self.text.push_str("grestore showpage\n");
+        self.pages.push(::std::mem::take(&mut self.text));
+    }
+
+    fn fill(&mut self, path: &Path, fill_rule: FillRule, source: &Pattern, operator: Operator) {
+        if !self.color(source, operator) {
+            return;
+        }
+        self.path(path);
+        self.text.push_str(match fill_rule {
+            FillRule::Winding => "fill\n",
+            FillRule::EvenOdd => "eofill\n",
+        });
+    }
+
+    fn stroke(&mut self, path: &Path, matrix: &Matrix, style: &StrokeStyle,
+              dash: Option<&DashPattern>, source: &Pattern, operator: Operator) {
+        self.text.push_str("gsave\n");
+        if self.color(source, operator) {
+            let _ = writeln!(self.text, "[{} {} {} {} {} {}] concat", number(matrix.xx),
+                             number(matrix.yx), number(matrix.xy), number(matrix.yy),
+                             number(matrix.x0), number(matrix.y0));
+            self.path(path);
+            let join = match style.join {
+                LineJoin::Miter => 0,
+                LineJoin::Round => 1,
+                LineJoin::Bevel => 2,
+            };
+            let cap = match style.cap {
+                LineCap::Butt => 0,
+                LineCap::Round => 1,
+                LineCap::Square => 2,
+            };
+            let _ = writeln!(self.text, "{} setlinewidth {} setlinejoin {} setlinecap \
+                                         {} setmiterlimit", number(style.width), join, cap,
+                             number(style.miter_limit.max(1.)));
+            if let Some(dash) = dash {
+                let dashes: Vec<String> = dash.dashes().iter().map(|&dash| number(dash))
+                                                                .collect();
+                let _ = writeln!(self.text, "[{}] {} setdash", dashes.join(" "),
+                                 number(dash.offset()));
+            }
+            self.text.push_str("stroke\n");
+        }
+        self.text.push_str("grestore\n");
+    }
+
+    fn paint(&mut self, source: &Pattern, operator: Operator) {
+        if self.color(source, operator) {
+            let _ = writeln!(self.text, "0 0 {} {} rectfill", number(self.width),
+                             number(self.height));
+        }
+    }
+
+    fn clip(&mut self, path: &Path, fill_rule: FillRule) {
+        if !self.clipped {
+            self.text.push_str("gsave\n");
+            self.clipped = true;
+        }
+        self.path(path);
+        self.text.push_str(match fill_rule {
+            FillRule::Winding => "clip newpath\n",
+            FillRule::EvenOdd => "eoclip newpath\n",
+        });
+    }
+
+    fn reset_clip(&mut self) {
+        if self.clipped {
+            self.text.push_str("grestore\n");
+            self.clipped = false;
+        }
+    }
+}
+
+// Returns `value` as a PostScript number, to three decimal places
+fn number(value: f32) -> String {
+    let rounded = (value * 1000.).round() / 1000.;
+    if rounded == 0. {
+        "0".to_string()
+    } else {
+        format!("{}", rounded)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PsSurface, number};
+    use types::{FillRule, LineCap};
+
+    // Returns the document as text
+    fn text(document: &PsSurface) -> String {
+        let mut bytes = Vec::new();
+        document.write_to(&mut bytes).unwrap();
+        String::from_utf8(bytes).unwrap()
+    }
+
+    #[test]
+    fn ps_surface_document_structure() {
+        // Setup
+        let document = PsSurface::new(200., 100.5);
+
+        // Call
+        let text = text(&document);
+
+        // Test
+        assert!(text.starts_with("%!PS-Adobe-3.0\n"));
+        assert!(text.contains("%%BoundingBox: 0 0 200 101\n"));
+        assert!(text.contains("%%Pages: 1\n%%EndComments\n%%Page: 1 1\n"));
+        assert!(text.contains("gsave 0 100.5 translate 1 -1 scale\ngrestore showpage\n"));
+        assert!(text.ends_with("%%Trailer\n%%EOF\n"));
+    }
+
+    #[test]
+    fn ps_surface_fill_and_stroke() {
+        // Setup
+        let mut document = PsSurface::new(100., 100.);
+        {
+            let page = document.record();
+            page.set_source_rgba(1., 0.5, 0., 0.5);
+            page.set_fill_rule(FillRule::EvenOdd);
+            page.move_to(10., 10.);
+            page.line_to(90., 10.);
+            page.curve_to(90., 50., 50., 90., 10., 90.);
+            page.close_path();
+            page.fill();
+            page.scale(2., 2.);
+            page.set_line_width(1.5);
+            page.set_line_cap(LineCap::Round);
+            page.set_dash(&[3., 1.], 0.5);
+            page.move_to(5., 5.);
+            page.line_to(20., 5.);
+            page.stroke();
+        }
+
+        // Call
+        let text = text(&document);
+
+        // Test
+        assert!(text.contains("1 0.5 0 setrgbcolor\nnewpath\n10 10 moveto\n90 10 lineto\n\
+                               90 50 50 90 10 90 curveto\nclosepath\neofill\n"));
+        assert!(text.contains("[2 0 0 2 0 0] concat\nnewpath\n5 5 moveto\n20 5 lineto\n\
+                               1.5 setlinewidth 0 setlinejoin 1 setlinecap 10 setmiterlimit\n\
+                               [3 1] 0.5 setdash\nstroke\ngrestore\n"));
+    }
+
+    #[test]
+    fn ps_surface_clip_and_paint() {
+        // Setup
+        let mut document = PsSurface::new(10., 10.);
+        {
+            let page = document.record();
+            page.move_to(0., 0.);
+            page.line_to(5., 0.);
+            page.line_to(5., 5.);
+            page.clip();
+            page.set_source_rgba(0., 0., 0., 1.);
+            page.paint();
+            page.reset_clip();
+            page.set_source_rgba(0., 0., 0., 0.);
+            page.paint();
+        }
+
+        // Call
+        let text = text(&document);
+
+        // Test
+        assert!(text.contains("gsave\nnewpath\n0 0 moveto\n5 0 lineto\n5 5 lineto\nclip newpath\n\
+                               0 0 0 setrgbcolor\n0 0 10 10 rectfill\ngrestore\n\
+                               grestore showpage"));
+    }
+
+    #[test]
+    fn ps_surface_pages() {
+        // Setup
+        let mut document = PsSurface::new(10., 10.);
+        document.record().set_source_rgba(1., 0., 0., 1.);
+        document.record().paint();
+        document.copy_page();
+        document.show_page();
+        document.record().paint();
+
+        // Call
+        let text = text(&document);
+
+        // Test
+        let page = "gsave 0 10 translate 1 -1 scale\n1 0 0 setrgbcolor\n0 0 10 10 rectfill\n\
+                    grestore showpage\n";
+        assert!(text.contains("%%Pages: 3\n"));
+        assert!(text.contains(&format!("%%Page: 1 1\n{}%%Page: 2 2\n{}%%Page: 3 3\n{}%%Trailer",
+                                       page, page, page)));
+    }
+
+    #[test]
+    fn ps_surface_eps() {
+        // Setup
+        let mut document = PsSurface::new(10., 10.);
+
+        // Call
+        document.set_eps(true);
+
+        // Test
+        let text = text(&document);
+        assert!(document.get_eps());
+        assert!(text.starts_with("%!PS-Adobe-3.0 EPSF-3.0\n"));
+        assert!(!text.contains("%%Page"));
+    }
+
+    #[test]
+    fn ps_number() {
+        assert_eq!(number(1.), "1");
+        assert_eq!(number(0.1 + 0.2), "0.3");
+        assert_eq!(number(-0.0001), "0");
+        assert_eq!(number(-2.5), "-2.5");
+    }
+}
@@ -0,0 +1,492 @@
+/*
+ * Cairus - a reimplementation of the cairo graphics library in Rust
+ *
+ * Copyright © 2017 CairusOrg
+ *
+ * This library is free software; you can redistribute it and/or
+ * modify it either under the terms of the GNU Lesser General Public
+ * License version 2.1 as published by the Free Software Foundation
+ * (the "LGPL") or, at your option, under the terms of the Mozilla
+ * Public License Version 2.0 (the "MPL"). If you do not alter this
+ * notice, a recipient may use your version of this file under either
+ * the MPL or the LGPL.
+ *
+ * You should have received a copy of the LGPL along with this library
+ * in the file LICENSE-LGPL-2_1; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Suite 500, Boston, MA 02110-1335, USA
+ * You should have received a copy of the MPL along with this library
+ * in the file LICENSE-MPL-2_0
+ *
+ * The contents of this file are subject to the Mozilla Public License
+ * Version 2.0 (the "License"); you may not use this file except in
+ * compliance with the License. You may obtain a copy of the License at
+ * http://www.mozilla.org/MPL/
+ *
+ * This software is distributed on an "AS IS" basis, WITHOUT WARRANTY
+ * OF ANY KIND, either express or implied. See the LGPL or the MPL for
+ * the specific language governing rights and limitations.
+ *
+ * The Original Code is the cairus graphics library.
+ *
+ * Contributor(s):
+ *	CairusOrg
+ *
+ */
+
+//! Surfaces that record drawing instead of rendering it.
+//!
+//! A `RecordingSurface` keeps the drawing done to it as a display list, a `CommandBuffer`, and
+//! can replay it onto any number of image surfaces afterwards, at any transformation.  That is
+//! what meta-surfaces, thumbnails and vector export need: the drawing is kept as drawing, and
+//! only rendered, or translated, once the destination is known.  Analogous to cairo's recording
+//! surface.
+
+use std::f32::consts::SQRT_2;
+use command_buffer::{Command, CommandBuffer};
+use common_geometry::{Point, Rectangle};
+use context::Context;
+use matrix::Matrix;
+use surfaces::{Format, ImageSurface};
+use types::LineJoin;
+
+/// ## RecordingSurface
+///
+/// A display list of drawing commands, optionally bounded by extents in user space.
+///
+/// Drawing is recorded through the `CommandBuffer` that `record()` returns, which has the same
+/// methods as `Context`.  As with Context, fill_rectangles(), fill_ellipses() and the stroke_*
+/// helpers are transformed and clipped like every other command when replayed.
+///
+/// # Usage
+/// let mut recording = RecordingSurface::new(Some(Rectangle::new(0., 0., 100., 100.)));
+/// recording.record().set_source_rgba(1., 0., 0., 1.);
+/// recording.record().paint();
+/// let thumbnail = recording.to_image(0.25).unwrap();
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RecordingSurface {
+    extents: Option<Rectangle>,
+    commands: CommandBuffer,
+}
+
+impl RecordingSurface {
+    /// Returns an empty recording, bounded by `extents`, or unbounded for None.
+    ///
+    /// Analogous to cairo_recording_surface_create().  Replays of a bounded recording are
+    /// clipped to its extents.
+    pub fn new(extents: Option<Rectangle>) -> RecordingSurface {
+        RecordingSurface {
+            extents: extents.map(|extents| extents.normalized()),
+            commands: CommandBuffer::new(),
+        }
+    }
+
+    /// Returns the extents the recording is bounded by, or None if it is unbounded.
+    ///
+    /// Analogous to cairo_recording_surface_get_extents().
+    pub fn get_extents(&self) -> Option<Rectangle> {
+        self.extents
+    }
+
+    /// Returns the commands recorded so far.
+    pub fn commands(&self) -> &CommandBuffer {
+        &self.commands
+    }
+
+    /// Returns the display list, for recording drawing onto the end of it.
+    ///
+    /// # Usage
+    /// let commands = recording.record();
+    /// commands.move_to(0., 0.);
+    /// commands.line_to(10., 10.);
+    /// commands.stroke();
+    pub fn record(&mut self) -> &mut CommandBuffer {
+        &mut self.commands
+    }
+
+    /// Replays the recording onto `target`, with user space units as its pixels.
+    pub fn replay(&self, target: &mut ImageSurface) {
+        self.replay_with_matrix(target, &Matrix::identity());
+    }
+
+    /// Replays the recording onto `target`, with `matrix` taking the recording's user space to
+    /// the target's pixels.
+    ///
+    /// The recorded drawing starts from a fresh context, with `matrix` in place of the identity,
+    /// so the recording can be scaled or moved onto the target.  A bounded recording is clipped
+    /// to its extents, until a recorded reset_clip() lifts the clip.
+    ///
+    /// # Usage
+    /// recording.replay_with_matrix(&mut surface, &Matrix::scaling(2., 2.));
+    pub fn replay_with_matrix(&self, target: &mut ImageSurface, matrix: &Matrix) {
+        let mut context = Context::create(target);
+        context.set_matrix(*matrix);
+        if let Some(extents) = self.extents {
+            clip_to(&mut context, &extents);
+        }
+        self.commands.execute(&mut context);
+    }
+
+    /// Replays the part of the recording inside `extents` onto `target`, with user space units
+    /// as its pixels, such as to redraw a damaged area of a scene.
+    ///
+    /// Drawing is clipped to `extents`, even past a recorded reset_clip(), so the target is left
+    /// as it was outside them.  Recorded drawing that can't reach `extents` isn't drawn at all,
+    /// which is what makes redrawing a small area of a large recording cheap.  Inside
+    /// `extents` the target ends up as replay() would leave it.
+    ///
+    /// # Usage
+    /// recording.replay_with_extents(&mut surface, &Rectangle::new(0., 0., 16., 16.));
+    pub fn replay_with_extents(&self, target: &mut ImageSurface, extents: &Rectangle) {
+        let extents = extents.normalized();
+        let mut context = Context::create(target);
+        // Clips to the extents in device space, keeping the path being built
+        let clip = |context: &mut Context| {
+            let (path, matrix) = (context.get_path().clone(), context.get_matrix());
+            context.identity_matrix();
+            context.new_path();
+            if let Some(bounds) = self.extents {
+                clip_to(context, &bounds);
+            }
+            clip_to(context, &extents);
+            context.append_path(&path);
+            context.set_matrix(matrix);
+        };
+        clip(&mut context);
+
+        let base = Matrix::identity();
+        for command in self.commands.commands() {
+            let reaches = match drawing_extents(command, &context) {
+                Some(drawn) => overlaps(&drawn, &extents),
+                None => true,
+            };
+            if reaches {
+                command.execute(&mut context, &base);
+            } else {
+                // What isn't drawn still leaves the path and current point as drawing it would
+                match *command {
+                    Command::Fill | Command::Stroke => context.new_path(),
+                    Command::ShowText(ref text) => {
+                        let origin = context.get_current_point()
+                                            .unwrap_or_else(|| Point::new(0., 0.));
+                        let advance = context.text_extents(text);
+                        context.move_to(origin.x + advance.x_advance,
+                                        origin.y + advance.y_advance);
+                    },
+                    _ => {},
+                }
+            }
+            if let Command::ResetClip = *command {
+                clip(&mut context);
+            }
+        }
+    }
+
+    /// Renders a bounded recording into a new image surface of its extents scaled by `scale`,
+    /// rounded up to whole pixels, such as for a thumbnail.
+    ///
+    /// Returns None for an unbounded recording, or one whose image would have no pixels.
+    ///
+    /// # Usage
+    /// let thumbnail = recording.to_image(0.25).unwrap();
+    pub fn to_image(&self, scale: f32) -> Option<ImageSurface> {
+        let extents = self.extents?;
+        let width = (extents.width * scale).ceil();
+        let height = (extents.height * scale).ceil();
+        if !(width >= 1. && height >= 1.) {
+            return None;
+        }
+
+        let mut image = ImageSurface::create_for_format(Format::ARGB32, width as usize,
+                                                        height as usize);
+        let matrix = Matrix::translation(-extents.x, -extents.y)
+            .multiply(&Matrix::scaling(scale, scale));
+        self.replay_with_matrix(&mut image, &matrix);
+        Some(image)
+    }
+}
+
+// Clips `context` to `rectangle`, in user space
+fn clip_to(context: &mut Context, rectangle: &Rectangle) {
+    let (right, bottom) = (rectangle.x + rectangle.width, rectangle.y + rectangle.height);
+    context.move_to(rectangle.x, rectangle.y);
+    context.line_to(right, rectangle.y);
+    context.line_to(right, bottom);
+    context.line_to(rectangle.x, bottom);
+    context.close_path();
+    context.clip();
+}
+
+// Returns a rectangle in device space that `command` draws inside of, performed on `context`,
+// or None if it doesn't draw, or could draw anywhere
+fn drawing_extents(command: &Command, context: &Context) -> Option<Rectangle> {
+    // Unbounded operators change the target outside what they draw as well
+    if !context.get_operator().is_bounded() {
+        return None;
+    }
+    let matrix = context.get_matrix();
+    let scale = matrix.max_scale();
+    // How far a stroke reaches past its path, at most: a miter's tip, or a square cap's corner
+    let join = match context.get_line_join() {
+        LineJoin::Miter => context.get_miter_limit().max(SQRT_2),
+        _ => SQRT_2,
+    };
+    let reach = context.get_line_width() / 2. * join * scale;
+    let user_bounds = |points: &mut dyn Iterator<Item=Point>| {
+        bounds(&mut points.map(|point| matrix.transform_point(point)))
+    };
+
+    let (drawn, margin) = match *command {
+        Command::Fill => (context.get_path().extents()?, 0.),
+        Command::Stroke => (context.get_path().extents()?, reach),
+        Command::FillRectangles(ref rectangles) => {
+            (user_bounds(&mut rectangles.iter().flat_map(|rectangle| {
+                vec![Point::new(rectangle.x, rectangle.y),
+                     Point::new(rectangle.x + rectangle.width, rectangle.y + rectangle.height),
+                     Point::new(rectangle.x + rectangle.width, rectangle.y),
+                     Point::new(rectangle.x, rectangle.y + rectangle.height)]
+            }))?, 0.)
+        },
+        Command::FillEllipses(ref ellipses) => {
+            (user_bounds(&mut ellipses.iter().flat_map(|ellipse| {
+                let (x, y) = (ellipse.center.x, ellipse.center.y);
+                let (rx, ry) = (ellipse.radius_x.abs(), ellipse.radius_y.abs());
+                vec![Point::new(x - rx, y - ry), Point::new(x + rx, y + ry),
+                     Point::new(x + rx, y - ry), Point::new(x - rx, y + ry)]
+            }))?, 0.)
+        },
+        Command::StrokeSegments(ref segments) => {
+            (user_bounds(&mut segments.iter().flat_map(|segment| {
+                vec![segment.point1, segment.point2]
+            }))?, reach)
+        },
+        Command::StrokePolylineGradient(ref points, _) => {
+            (user_bounds(&mut points.iter().cloned())?, reach)
+        },
+        Command::ShowText(ref text) => {
+            let origin = context.get_current_point().unwrap_or_else(|| Point::new(0., 0.));
+            let ink = context.text_extents(text);
+            let (left, top) = (origin.x + ink.x_bearing, origin.y + ink.y_bearing);
+            let (right, bottom) = (left + ink.width, top + ink.height);
+            (user_bounds(&mut vec![Point::new(left, top), Point::new(right, bottom),
+                                   Point::new(right, top), Point::new(left, bottom)]
+                                   .into_iter())?, 0.)
+        },
+        _ => return None,
+    };
+    // Antialiasing and filtering reach a pixel further
+    let margin = margin + 1.;
+    Some(Rectangle::new(drawn.x - margin, drawn.y - margin, drawn.width + 2. * margin,
+                        drawn.height + 2. * margin))
+}
+
+// Returns the smallest rectangle holding every one of `points`, or None if there are none
+fn bounds(points: &mut dyn Iterator<Item=Point>) -> Option<Rectangle> {
+    let first = points.next()?;
+    let (mut left, mut top, mut right, mut bottom) = (first.x, first.y, first.x, first.y);
+    for point in points {
+        left = left.min(point.x);
+        top = top.min(point.y);
+        right = right.max(point.x);
+        bottom = bottom.max(point.y);
+    }
+    Some(Rectangle::new(left, top, right - left, bottom - top))
+}
+
+// Returns true if the two rectangles share any area
+fn overlaps(a: &Rectangle, b: &Rectangle) -> bool {
+    a.x < b.x + b.width && b.x < a.x + a.width && a.y < b.y + b.height && b.y < a.y + a.height
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RecordingSurface, drawing_extents, overlaps};
+    use command_buffer::Command;
+    use common_geometry::Rectangle;
+    use context::Context;
+    use matrix::Matrix;
+    use pattern::Pattern;
+    use surfaces::{Format, ImageSurface};
+    use types::{FontSlant, FontWeight, Rgba};
+
+    // Draws a triangle and a translucent stroke, under a transformation
+    fn scene(recording: &mut RecordingSurface) {
+        let commands = recording.record();
+        commands.set_source(Pattern::rgba(0.2, 0.4, 0.8, 1.));
+        commands.translate(2., 1.);
+        commands.move_to(1., 1.);
+        commands.line_to(12., 3.);
+        commands.line_to(4., 10.);
+        commands.close_path();
+        commands.fill();
+        commands.set_source_rgba(1., 0., 0., 0.5);
+        commands.set_line_width(1.5);
+        commands.move_to(0., 12.);
+        commands.curve_to(4., 6., 8., 18., 14., 12.);
+        commands.stroke();
+    }
+
+    #[test]
+    fn recording_replay_matches_context() {
+        // Setup
+        let mut recording = RecordingSurface::new(None);
+        scene(&mut recording);
+        let mut replayed = ImageSurface::create_for_format(Format::ARGB32, 20, 20);
+        let mut direct = ImageSurface::create_for_format(Format::ARGB32, 20, 20);
+
+        // Call
+        recording.replay(&mut replayed);
+        {
+            let mut context = Context::create(&mut direct);
+            context.set_source(Pattern::rgba(0.2, 0.4, 0.8, 1.));
+            context.translate(2., 1.);
+            context.move_to(1., 1.);
+            context.line_to(12., 3.);
+            context.line_to(4., 10.);
+            context.close_path();
+            context.fill();
+            context.set_source_rgba(1., 0., 0., 0.5);
+            context.set_line_width(1.5);
+            context.move_to(0., 12.);
+            context.curve_to(4., 6., 8., 18., 14., 12.);
+            context.stroke();
+        }
+
+        // Test
+        assert_eq!(replayed, direct);
+        assert!(replayed.iter().any(|pixel| pixel.alpha > 0.));
+    }
+
+    #[test]
+    fn recording_replays_scaled() {
+        // Setup
+        let mut recording = RecordingSurface::new(None);
+        recording.record().set_source_rgba(0., 0., 1., 1.);
+        recording.record().identity_matrix();
+        recording.record().move_to(1., 1.);
+        recording.record().line_to(3., 1.);
+        recording.record().line_to(3., 2.);
+        recording.record().line_to(1., 2.);
+        recording.record().fill();
+        let mut scaled = ImageSurface::create_for_format(Format::ARGB32, 8, 8);
+
+        // Call
+        recording.replay_with_matrix(&mut scaled, &Matrix::scaling(2., 2.));
+
+        // Test
+        // The rectangle covers (2, 2) to (6, 4) once scaled, despite the recorded identity
+        let blue = Rgba::new(0., 0., 1., 1.);
+        for y in 0..8 {
+            for x in 0..8 {
+                let inside = (2..6).contains(&x) && (2..4).contains(&y);
+                assert_eq!(scaled.get_pixel(x, y).unwrap() == blue, inside, "({}, {})", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn recording_is_clipped_to_extents() {
+        // Setup
+        let mut recording = RecordingSurface::new(Some(Rectangle::new(2., 2., 3., 4.)));
+        recording.record().set_source_rgba(1., 1., 1., 1.);
+        recording.record().paint();
+        let mut surface = ImageSurface::create_for_format(Format::ARGB32, 8, 8);
+
+        // Call
+        recording.replay(&mut surface);
+
+        // Test
+        let covered = surface.iter().filter(|pixel| pixel.alpha > 0.).count();
+        assert_eq!(covered, 12);
+        assert_eq!(surface.get_pixel(2, 2).unwrap(), Rgba::new(1., 1., 1., 1.));
+        assert_eq!(surface.get_pixel(5, 2).unwrap(), Rgba::new(0., 0., 0., 0.));
+    }
+
+    #[test]
+    fn recording_to_image() {
+        // Setup
+        let mut recording = RecordingSurface::new(Some(Rectangle::new(10., 10., 20., 10.)));
+        recording.record().set_source_rgba(0., 1., 0., 1.);
+        recording.record().paint();
+
+        // Call
+        let thumbnail = recording.to_image(0.5).unwrap();
+
+        // Test
+        assert_eq!(thumbnail.dimensions(), (10, 5));
+        assert!(thumbnail.iter().all(|pixel| *pixel == Rgba::new(0., 1., 0., 1.)));
+        assert!(RecordingSurface::new(None).to_image(1.).is_none());
+        assert!(recording.to_image(0.).is_none());
+    }
+
+    #[test]
+    fn recording_replays_within_extents() {
+        // Setup
+        // The scene spreads past the extents replayed: text runs into them from the left, and a
+        // recorded reset_clip() is followed by a paint
+        let mut recording = RecordingSurface::new(None);
+        scene(&mut recording);
+        {
+            let commands = recording.record();
+            commands.identity_matrix();
+            commands.set_source_rgba(0., 0.5, 0., 1.);
+            commands.fill_rectangles(&[Rectangle::new(14., 14., 4., 4.)]);
+            commands.select_font_face("serif", FontSlant::Normal, FontWeight::Bold);
+            commands.set_font_size(16.);
+            commands.move_to(-12., 19.);
+            commands.show_text("H");
+            commands.show_text("H");
+            commands.reset_clip();
+            commands.set_source_rgba(0., 0., 0., 0.25);
+            commands.paint();
+        }
+        let extents = Rectangle::new(0., 5., 10., 15.);
+        let mut full = ImageSurface::create_for_format(Format::ARGB32, 20, 20);
+        let mut part = ImageSurface::create_for_format(Format::ARGB32, 20, 20);
+
+        // Call
+        recording.replay(&mut full);
+        recording.replay_with_extents(&mut part, &extents);
+
+        // Test
+        // Inside the extents the replays agree, but for clipped drawing rounding its coverage,
+        // and outside nothing was drawn
+        let clear = Rgba::new(0., 0., 0., 0.);
+        for y in 0..20 {
+            for x in 0..20 {
+                let inside = x < 10 && (5..20).contains(&y);
+                let expected = if inside { full.get_pixel(x, y).unwrap() } else { clear };
+                let pixel = part.get_pixel(x, y).unwrap();
+                let difference = (pixel.red - expected.red).abs()
+                    .max((pixel.green - expected.green).abs())
+                    .max((pixel.blue - expected.blue).abs())
+                    .max((pixel.alpha - expected.alpha).abs());
+                assert!(difference < 0.01, "({}, {}): {:?} {:?}", x, y, pixel, expected);
+            }
+        }
+        assert!(part.iter().any(|pixel| pixel.alpha == 1.));
+    }
+
+    #[test]
+    fn recording_skips_drawing_outside_extents() {
+        // Setup
+        let mut surface = ImageSurface::create_for_format(Format::ARGB32, 10, 10);
+        let mut context = Context::create(&mut surface);
+        context.move_to(50., 50.);
+        context.line_to(60., 50.);
+        context.line_to(60., 60.);
+        let extents = Rectangle::new(0., 0., 10., 10.);
+
+        // Call
+        let fill = drawing_extents(&Command::Fill, &context).unwrap();
+        let stroke = drawing_extents(&Command::Stroke, &context).unwrap();
+        let rectangles = Command::FillRectangles(vec![Rectangle::new(5., 5., 2., 2.)]);
+        let rectangles = drawing_extents(&rectangles, &context).unwrap();
+
+        // Test
+        assert!(!overlaps(&fill, &extents));
+        assert!(!overlaps(&stroke, &extents));
+        assert!(overlaps(&rectangles, &extents));
+        assert!(drawing_extents(&Command::Paint, &context).is_none());
+        assert!(drawing_extents(&Command::LineTo(0., 0.), &context).is_none());
+    }
+}
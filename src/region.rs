@@ -0,0 +1,380 @@
+/*
+ * Cairus - a reimplementation of the cairo graphics library in Rust
+ *
+ * Copyright © 2017 CairusOrg
+ *
+ * This library is free software; you can redistribute it and/or
+ * modify it either under the terms of the GNU Lesser General Public
+ * License version 2.1 as published by the Free Software Foundation
+ * (the "LGPL") or, at your option, under the terms of the Mozilla
+ * Public License Version 2.0 (the "MPL"). If you do not alter this
+ * notice, a recipient may use your version of this file under either
+ * the MPL or the LGPL.
+ *
+ * You should have received a copy of the LGPL along with this library
+ * in the file LICENSE-LGPL-2_1; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Suite 500, Boston, MA 02110-1335, USA
+ * You should have received a copy of the MPL along with this library
+ * in the file LICENSE-MPL-2_0
+ *
+ * The contents of this file are subject to the Mozilla Public License
+ * Version 2.0 (the "License"); you may not use this file except in
+ * compliance with the License. You may obtain a copy of the License at
+ * http://www.mozilla.org/MPL/
+ *
+ * This software is distributed on an "AS IS" basis, WITHOUT WARRANTY
+ * OF ANY KIND, either express or implied. See the LGPL or the MPL for
+ * the specific language governing rights and limitations.
+ *
+ * The Original Code is the cairus graphics library.
+ *
+ * Contributor(s):
+ *	CairusOrg
+ *
+ */
+
+//! Sets of whole pixels built from rectangles.
+//!
+//! A `Region` is the area covered by any number of `RectangleInt`s, analogous to
+//! cairo_region_t.  Regions can be combined with union, intersection, difference and exclusive
+//! or, and tested against points and rectangles, which is all rectangular clipping and damage
+//! tracking need.
+//!
+//! As in cairo (and pixman), a region is kept as horizontal bands.  Each band covers a run of
+//! rows and holds the sorted, separate spans of columns covered in those rows, and bands next to
+//! each other with the same spans are merged.  That makes the representation of an area unique,
+//! so two regions are equal exactly when they cover the same pixels.
+
+use common_geometry::RectangleInt;
+
+/// ## Region
+///
+/// An area made of whole pixels.  Analogous to cairo_region_t.
+///
+/// # Usage
+/// let damage = Region::from_rectangle(&RectangleInt::new(0, 0, 10, 10))
+///     .union(&Region::from_rectangle(&RectangleInt::new(5, 5, 10, 10)));
+/// assert!(damage.contains_point(12, 12));
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Region {
+    bands: Vec<Band>,
+}
+
+// The rows from `top` up to `bottom` of a region, and the columns covered in them, as
+// [left, right) pairs sorted from left to right that neither overlap nor touch.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Band {
+    top: i32,
+    bottom: i32,
+    spans: Vec<(i32, i32)>,
+}
+
+/// How a rectangle lies relative to a region.  Analogous to cairo_region_overlap_t.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Overlap {
+    /// The rectangle is entirely inside the region.
+    In,
+    /// The rectangle is entirely outside the region.
+    Out,
+    /// Some of the rectangle is inside the region, and some outside.
+    Part,
+}
+
+impl Region {
+    /// Returns an empty region.  Analogous to cairo_region_create().
+    pub fn new() -> Region {
+        Region { bands: Vec::new() }
+    }
+
+    /// Returns the region covered by `rectangle`, which is empty if the rectangle is.
+    /// Analogous to cairo_region_create_rectangle().
+    pub fn from_rectangle(rectangle: &RectangleInt) -> Region {
+        if rectangle.is_empty() {
+            return Region::new();
+        }
+        Region {
+            bands: vec![Band {
+                top: rectangle.y,
+                bottom: rectangle.y + rectangle.height,
+                spans: vec![(rectangle.x, rectangle.x + rectangle.width)],
+            }],
+        }
+    }
+
+    /// Returns the region covered by any of `rectangles`.  Analogous to
+    /// cairo_region_create_rectangles().
+    pub fn from_rectangles(rectangles: &[RectangleInt]) -> Region {
+        rectangles.iter().fold(Region::new(), |region, rectangle| {
+            region.union(&Region::from_rectangle(rectangle))
+        })
+    }
+
+    /// Returns true if the region covers no pixels.
+    pub fn is_empty(&self) -> bool {
+        self.bands.is_empty()
+    }
+
+    /// Returns the smallest rectangle covering the region, or an empty rectangle at the origin
+    /// for an empty region.  Analogous to cairo_region_get_extents().
+    pub fn extents(&self) -> RectangleInt {
+        let (first, last) = match (self.bands.first(), self.bands.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return RectangleInt::new(0, 0, 0, 0),
+        };
+        let left = self.bands.iter().map(|band| band.spans[0].0).min().unwrap();
+        let right = self.bands.iter().map(|band| band.spans[band.spans.len() - 1].1).max()
+                                     .unwrap();
+        RectangleInt::new(left, first.top, right - left, last.bottom - first.top)
+    }
+
+    /// Returns the number of rectangles `rectangles()` returns.  Analogous to
+    /// cairo_region_num_rectangles().
+    pub fn num_rectangles(&self) -> usize {
+        self.bands.iter().map(|band| band.spans.len()).sum()
+    }
+
+    /// Returns the rectangles that make up the region, which don't overlap, from top to bottom
+    /// and left to right within each band.  Analogous to cairo_region_get_rectangle().
+    pub fn rectangles(&self) -> Vec<RectangleInt> {
+        self.bands.iter()
+            .flat_map(|band| band.spans.iter().map(move |&(left, right)| {
+                RectangleInt::new(left, band.top, right - left, band.bottom - band.top)
+            }))
+            .collect()
+    }
+
+    /// Returns true if the pixel at (`x`, `y`) is in the region.  Analogous to
+    /// cairo_region_contains_point().
+    pub fn contains_point(&self, x: i32, y: i32) -> bool {
+        self.bands.iter()
+            .find(|band| band.top <= y && y < band.bottom)
+            .is_some_and(|band| band.spans.iter().any(|&(left, right)| left <= x && x < right))
+    }
+
+    /// Returns whether `rectangle` is inside the region, outside it, or partly both.  An empty
+    /// rectangle is outside every region.  Analogous to cairo_region_contains_rectangle().
+    pub fn contains_rectangle(&self, rectangle: &RectangleInt) -> Overlap {
+        let rectangle = Region::from_rectangle(rectangle);
+        let inside = self.intersect(&rectangle);
+        if inside.is_empty() {
+            Overlap::Out
+        } else if inside == rectangle {
+            Overlap::In
+        } else {
+            Overlap::Part
+        }
+    }
+
+    /// Moves the region by `dx` pixels right and `dy` pixels down.  Analogous to
+    /// cairo_region_translate().
+    pub fn translate(&mut self, dx: i32, dy: i32) {
+        for band in self.bands.iter_mut() {
+            band.top += dy;
+            band.bottom += dy;
+            for span in band.spans.iter_mut() {
+                *span = (span.0 + dx, span.1 + dx);
+            }
+        }
+    }
+
+    /// Returns the region covered by this region or `other`.  Analogous to
+    /// cairo_region_union().
+    pub fn union(&self, other: &Region) -> Region {
+        self.combine(other, |a, b| a || b)
+    }
+
+    /// Returns the region covered by both this region and `other`.  Analogous to
+    /// cairo_region_intersect().
+    pub fn intersect(&self, other: &Region) -> Region {
+        self.combine(other, |a, b| a && b)
+    }
+
+    /// Returns the region covered by this region but not `other`.  Analogous to
+    /// cairo_region_subtract().
+    pub fn subtract(&self, other: &Region) -> Region {
+        self.combine(other, |a, b| a && !b)
+    }
+
+    /// Returns the region covered by exactly one of this region and `other`.  Analogous to
+    /// cairo_region_xor().
+    pub fn xor(&self, other: &Region) -> Region {
+        self.combine(other, |a, b| a != b)
+    }
+
+    // Returns the region covering the pixels for which `keep` is true, given whether each of
+    // this region and `other` covers them.
+    //
+    // The rows are cut at every band edge of either region, so that neither region changes
+    // within a cut, and each cut's spans are combined column by column the same way.
+    fn combine<K: Fn(bool, bool) -> bool>(&self, other: &Region, keep: K) -> Region {
+        let mut rows: Vec<i32> = self.bands.iter().chain(other.bands.iter())
+            .flat_map(|band| vec![band.top, band.bottom])
+            .collect();
+        rows.sort_unstable();
+        rows.dedup();
+
+        let mut combined = Region::new();
+        let (mut a, mut b) = (self.bands.iter().peekable(), other.bands.iter().peekable());
+        for cut in rows.windows(2) {
+            let (top, bottom) = (cut[0], cut[1]);
+            let a_spans = spans_at(&mut a, top);
+            let b_spans = spans_at(&mut b, top);
+            let spans = combine_spans(a_spans, b_spans, &keep);
+            if spans.is_empty() {
+                continue;
+            }
+            match combined.bands.last_mut() {
+                Some(last) if last.bottom == top && last.spans == spans => last.bottom = bottom,
+                _ => combined.bands.push(Band { top, bottom, spans }),
+            }
+        }
+        combined
+    }
+}
+
+// Returns the spans of the band among `bands` that covers row `y`, or none, skipping the bands
+// above it.  Rows must be asked about from the top down.
+fn spans_at<'a, I>(bands: &mut ::std::iter::Peekable<I>, y: i32) -> &'a [(i32, i32)]
+    where I: Iterator<Item = &'a Band> {
+    while bands.peek().is_some_and(|band| band.bottom <= y) {
+        bands.next();
+    }
+    match bands.peek() {
+        Some(band) if band.top <= y => &band.spans,
+        _ => &[],
+    }
+}
+
+// Returns the spans of the columns for which `keep` is true, given whether they are in `a` and
+// in `b`.
+fn combine_spans<K: Fn(bool, bool) -> bool>(a: &[(i32, i32)], b: &[(i32, i32)], keep: &K)
+    -> Vec<(i32, i32)> {
+    let mut columns: Vec<i32> = a.iter().chain(b.iter())
+        .flat_map(|&(left, right)| vec![left, right])
+        .collect();
+    columns.sort_unstable();
+    columns.dedup();
+
+    let inside = |spans: &[(i32, i32)], x: i32| {
+        spans.iter().any(|&(left, right)| left <= x && x < right)
+    };
+    let mut spans: Vec<(i32, i32)> = Vec::new();
+    for cut in columns.windows(2) {
+        let (left, right) = (cut[0], cut[1]);
+        if !keep(inside(a, left), inside(b, left)) {
+            continue;
+        }
+        match spans.last_mut() {
+            Some(last) if last.1 == left => last.1 = right,
+            _ => spans.push((left, right)),
+        }
+    }
+    spans
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Overlap, Region};
+    use common_geometry::RectangleInt;
+
+    fn region(rectangles: &[(i32, i32, i32, i32)]) -> Region {
+        let rectangles: Vec<RectangleInt> = rectangles.iter()
+            .map(|&(x, y, width, height)| RectangleInt::new(x, y, width, height))
+            .collect();
+        Region::from_rectangles(&rectangles)
+    }
+
+    #[test]
+    fn region_union_is_banded() {
+        // Setup
+        let a = region(&[(0, 0, 10, 10)]);
+        let b = region(&[(5, 5, 10, 10)]);
+
+        // Call
+        let union = a.union(&b);
+
+        // Test
+        assert_eq!(union.rectangles(), vec![RectangleInt::new(0, 0, 10, 5),
+                                            RectangleInt::new(0, 5, 15, 5),
+                                            RectangleInt::new(5, 10, 10, 5)]);
+        assert_eq!(union.extents(), RectangleInt::new(0, 0, 15, 15));
+        assert_eq!(union, b.union(&a));
+    }
+
+    #[test]
+    fn region_is_canonical() {
+        // The same square built from halves either way round, and overlapping
+        let square = region(&[(0, 0, 10, 10)]);
+        assert_eq!(region(&[(0, 0, 5, 10), (5, 0, 5, 10)]), square);
+        assert_eq!(region(&[(0, 5, 10, 5), (0, 0, 10, 5)]), square);
+        assert_eq!(region(&[(0, 0, 8, 10), (3, 0, 7, 10), (0, 0, 0, 3)]), square);
+        assert_eq!(square.num_rectangles(), 1);
+    }
+
+    #[test]
+    fn region_intersect() {
+        let a = region(&[(0, 0, 10, 10), (20, 0, 10, 10)]);
+        let b = region(&[(5, 5, 20, 10)]);
+        assert_eq!(a.intersect(&b), region(&[(5, 5, 5, 5), (20, 5, 5, 5)]));
+        assert!(a.intersect(&region(&[(10, 0, 10, 10)])).is_empty());
+    }
+
+    #[test]
+    fn region_subtract_leaves_frame() {
+        // Setup
+        let outer = region(&[(0, 0, 10, 10)]);
+        let hole = region(&[(3, 3, 4, 4)]);
+
+        // Call
+        let frame = outer.subtract(&hole);
+
+        // Test
+        assert_eq!(frame.num_rectangles(), 4);
+        assert!(frame.contains_point(2, 5));
+        assert!(!frame.contains_point(3, 3));
+        assert!(frame.contains_point(7, 6));
+        assert_eq!(frame.union(&hole), outer);
+    }
+
+    #[test]
+    fn region_xor() {
+        let a = region(&[(0, 0, 10, 10)]);
+        let b = region(&[(5, 0, 10, 10)]);
+        assert_eq!(a.xor(&b), region(&[(0, 0, 5, 10), (10, 0, 5, 10)]));
+        assert!(a.xor(&a).is_empty());
+    }
+
+    #[test]
+    fn region_contains_rectangle() {
+        let frame = region(&[(0, 0, 10, 10)]).subtract(&region(&[(3, 3, 4, 4)]));
+        assert_eq!(frame.contains_rectangle(&RectangleInt::new(0, 0, 3, 10)), Overlap::In);
+        assert_eq!(frame.contains_rectangle(&RectangleInt::new(3, 3, 4, 4)), Overlap::Out);
+        assert_eq!(frame.contains_rectangle(&RectangleInt::new(2, 2, 3, 3)), Overlap::Part);
+        assert_eq!(frame.contains_rectangle(&RectangleInt::new(20, 0, 1, 1)), Overlap::Out);
+        assert_eq!(frame.contains_rectangle(&RectangleInt::new(0, 0, 0, 5)), Overlap::Out);
+    }
+
+    #[test]
+    fn region_translate() {
+        // Setup
+        let mut moved = region(&[(0, 0, 4, 4), (6, 2, 2, 2)]);
+
+        // Call
+        moved.translate(-3, 5);
+
+        // Test
+        assert_eq!(moved, region(&[(-3, 5, 4, 4), (3, 7, 2, 2)]));
+        assert!(moved.contains_point(-3, 5));
+        assert!(!moved.contains_point(-3, 4));
+    }
+
+    #[test]
+    fn region_empty() {
+        let empty = Region::new();
+        assert!(empty.is_empty());
+        assert_eq!(empty.extents(), RectangleInt::new(0, 0, 0, 0));
+        assert_eq!(empty.num_rectangles(), 0);
+        assert!(!empty.contains_point(0, 0));
+        assert!(Region::from_rectangle(&RectangleInt::new(1, 1, -2, 5)).is_empty());
+    }
+}
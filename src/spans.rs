@@ -0,0 +1,422 @@
+/*
+ * Cairus - a reimplementation of the cairo graphics library in Rust
+ *
+ * Copyright © 2017 CairusOrg
+ *
+ * This library is free software; you can redistribute it and/or
+ * modify it either under the terms of the GNU Lesser General Public
+ * License version 2.1 as published by the Free Software Foundation
+ * (the "LGPL") or, at your option, under the terms of the Mozilla
+ * Public License Version 2.0 (the "MPL"). If you do not alter this
+ * notice, a recipient may use your version of this file under either
+ * the MPL or the LGPL.
+ *
+ * You should have received a copy of the LGPL along with this library
+ * in the file LICENSE-LGPL-2_1; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Suite 500, Boston, MA 02110-1335, USA
+ * You should have received a copy of the MPL along with this library
+ * in the file LICENSE-MPL-2_0
+ *
+ * The contents of this file are subject to the Mozilla Public License
+ * Version 2.0 (the "License"); you may not use this file except in
+ * compliance with the License. You may obtain a copy of the License at
+ * http://www.mozilla.org/MPL/
+ *
+ * This software is distributed on an "AS IS" basis, WITHOUT WARRANTY
+ * OF ANY KIND, either express or implied. See the LGPL or the MPL for
+ * the specific language governing rights and limitations.
+ *
+ * The Original Code is the cairus graphics library.
+ *
+ * Contributor(s):
+ *	CairusOrg
+ *
+ */
+
+//! Span-based rasterization, an alternative to tessellating fills into trapezoids.
+//!
+//! As in cairo's composite-spans path, a scan converter walks the edges of a fill row by row and
+//! hands a `SpanRenderer` the coverage of each row as spans: runs of pixels that are all covered
+//! alike.  Coverage is accumulated directly from the edges, so a complex path with many
+//! crossings costs no more than its edges, where tessellating it could produce a trapezoid for
+//! every piece between crossings.
+//!
+//! Each row of pixels is sampled along 15 horizontal lines, matching the rows of the subpixel
+//! grid used for trapezoids, and each line's coverage across the pixels is measured exactly.
+//! With `Antialias::None` only the line through the pixel centers is used, and a pixel is
+//! covered exactly when its center is inside the fill.
+
+use common_geometry::Edge;
+use std::cmp::Ordering;
+use std::ops::Range;
+use fixed::snap_point;
+use operators::{Operator, composite_span, composite_with_coverage, fetch_operator};
+use surfaces::{ImageSurface, rows_between};
+use types::{Antialias, FillRule, Rgba};
+
+// The number of lines each row of pixels is sampled along when antialiasing
+const SAMPLE_ROWS: usize = 15;
+
+/// ## Span
+///
+/// A run of pixels in a row that are all covered alike.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Span {
+    /// The column of the first pixel.
+    pub x: usize,
+    /// The number of pixels.
+    pub length: usize,
+    /// How much of each pixel is covered, from 0 to 1.
+    pub coverage: f32,
+}
+
+/// Receives the coverage of a fill from the scan converter, one row at a time.  Analogous to
+/// cairo_span_renderer_t.
+pub trait SpanRenderer {
+    /// Renders the covered spans of row `y`, in order from left to right.  Pixels that aren't in
+    /// any span aren't covered at all, and rows without any spans aren't rendered.
+    fn render_row(&mut self, y: usize, spans: &[Span]);
+}
+
+/// Scan converts the area inside `edges` under `fill_rule`, within a surface of `width` by
+/// `height` pixels, and renders its coverage with `renderer`.
+///
+/// # Arguments
+/// * `edges` - The edges of the fill, in device space.
+/// * `fill_rule` - Decides which areas the edges enclose are inside.
+/// * `antialias` - Selects between sampling many lines per row and only the pixel centers.
+/// * `width`, `height` - The size of the surface; spans never reach outside it.
+/// * `renderer` - Receives the spans.
+///
+/// # Usage
+/// render_edges(&edges, FillRule::Winding, Antialias::Default, 100, 100, &mut renderer);
+pub fn render_edges<R: SpanRenderer>(edges: &[Edge], fill_rule: FillRule, antialias: Antialias,
+                                     width: usize, height: usize, renderer: &mut R) {
+    let mask = match fill_rule {
+        FillRule::Winding => -1,
+        FillRule::EvenOdd => 1,
+    };
+    // Snapped to fixed point as the tessellator does, and ordered by where they start
+    let mut edges: Vec<Edge> = edges.iter()
+        .map(|edge| {
+            let (from, to) = (snap_point(edge.line.point1), snap_point(edge.line.point2));
+            Edge { direction: edge.direction, ..Edge::from_points(from, to) }
+        })
+        .filter(|edge| edge.top < edge.bottom && edge.top < height as f32 && edge.bottom > 0.)
+        .collect();
+    edges.sort_by(|a, b| a.top.partial_cmp(&b.top).unwrap_or(Ordering::Equal));
+    let first_row = match edges.first() {
+        Some(edge) => edge.top.max(0.).floor() as usize,
+        None => return,
+    };
+
+    let samples: Vec<f32> = if antialias == Antialias::None {
+        vec![0.5]
+    } else {
+        (0..SAMPLE_ROWS).map(|row| (row as f32 + 0.5) / SAMPLE_ROWS as f32).collect()
+    };
+    let weight = 1. / samples.len() as f32;
+
+    let mut coverage = vec![0.; width];
+    let mut spans = Vec::new();
+    let mut crossings: Vec<(f32, i32)> = Vec::new();
+    let mut active: Vec<Edge> = Vec::new();
+    let mut next_edge = 0;
+    for y in first_row..height {
+        // Bring in the edges that reach this row, and let go of those that have ended
+        let row_bottom = y as f32 + 1.;
+        while next_edge < edges.len() && edges[next_edge].top < row_bottom {
+            active.push(edges[next_edge]);
+            next_edge += 1;
+        }
+        active.retain(|edge| edge.bottom > y as f32);
+        if active.is_empty() {
+            if next_edge == edges.len() {
+                break;
+            }
+            continue;
+        }
+
+        for sample in &samples {
+            let sample_y = y as f32 + sample;
+            crossings.clear();
+            crossings.extend(active.iter()
+                .filter(|edge| edge.top <= sample_y && sample_y < edge.bottom)
+                .map(|edge| (edge.line.current_x_for_y(sample_y), edge.direction)));
+            crossings.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal));
+
+            let mut winding = 0;
+            for pair in crossings.windows(2) {
+                winding += pair[0].1;
+                if winding & mask == 0 {
+                    continue;
+                }
+                if antialias == Antialias::None {
+                    add_centers(&mut coverage, pair[0].0, pair[1].0);
+                } else {
+                    add_coverage(&mut coverage, pair[0].0, pair[1].0, weight);
+                }
+            }
+        }
+
+        // Gather runs of equal coverage into spans
+        spans.clear();
+        let mut x = 0;
+        while x < width {
+            let value = coverage[x].min(1.);
+            let mut end = x + 1;
+            while end < width && coverage[end].min(1.) == value {
+                end += 1;
+            }
+            if value > 0. {
+                spans.push(Span { x, length: end - x, coverage: value });
+            }
+            x = end;
+        }
+        if !spans.is_empty() {
+            renderer.render_row(y, &spans);
+        }
+        for value in coverage.iter_mut() {
+            *value = 0.;
+        }
+    }
+}
+
+// Adds `weight` times how much of each pixel of the row lies between `left` and `right`.
+fn add_coverage(coverage: &mut [f32], left: f32, right: f32, weight: f32) {
+    let (left, right) = (left.max(0.), right.min(coverage.len() as f32));
+    if left.is_nan() || right.is_nan() || left >= right {
+        return;
+    }
+    let (first, last) = (left.floor() as usize, (right.ceil() as usize).min(coverage.len()) - 1);
+    if first == last {
+        coverage[first] += (right - left) * weight;
+        return;
+    }
+    coverage[first] += (first as f32 + 1. - left) * weight;
+    for value in &mut coverage[first + 1..last] {
+        *value += weight;
+    }
+    coverage[last] += (right - last as f32) * weight;
+}
+
+// Fully covers each pixel of the row whose center lies between `left` and `right`.
+fn add_centers(coverage: &mut [f32], left: f32, right: f32) {
+    let first = (left - 0.5).ceil().max(0.) as usize;
+    let end = ((right - 0.5).ceil().max(0.) as usize).min(coverage.len());
+    for value in coverage.iter_mut().take(end).skip(first) {
+        *value = 1.;
+    }
+}
+
+/// Composites onto `surface` with `operator` wherever the area inside `edges` under `fill_rule`
+/// covers it, taking the color of each pixel from `source`.
+///
+/// The span counterpart of `rasterize_trapezoids_with_source`: the coverage of each pixel is
+/// multiplied by the alpha of `clip` there, if there is a clip, and `source` is only asked
+/// about pixels that are covered.
+pub fn rasterize_edges_with_source<F>(edges: &[Edge], fill_rule: FillRule, source: F,
+                                      operator: &Operator, antialias: Antialias,
+                                      clip: Option<&ImageSurface>, surface: &mut ImageSurface)
+    where F: Fn(usize, usize) -> Rgba {
+    let (width, height) = (surface.width, surface.height);
+    let mut renderer = CompositeSpans {
+        surface,
+        operator: *operator,
+        compose: fetch_operator(operator),
+        source,
+        solid: None,
+        clip,
+    };
+    render_edges(edges, fill_rule, antialias, width, height, &mut renderer);
+    renderer.surface.keep_format(edge_rows(edges, height));
+}
+
+/// Composites `color` onto `surface` as `rasterize_edges_with_source` does.
+///
+/// With one color for every pixel, the fully covered spans outside any clip are composited
+/// in bulk by `composite_span`, which fills them outright for Source, or Over with an opaque
+/// color.
+pub fn rasterize_edges_with_color(edges: &[Edge], fill_rule: FillRule, color: Rgba,
+                                  operator: &Operator, antialias: Antialias,
+                                  clip: Option<&ImageSurface>, surface: &mut ImageSurface) {
+    let (width, height) = (surface.width, surface.height);
+    let mut renderer = CompositeSpans {
+        surface,
+        operator: *operator,
+        compose: fetch_operator(operator),
+        source: |_, _| color,
+        solid: Some(color),
+        clip,
+    };
+    render_edges(edges, fill_rule, antialias, width, height, &mut renderer);
+    renderer.surface.keep_format(edge_rows(edges, height));
+}
+
+// Returns the rows of a surface `height` rows tall that `edges` reach into.
+fn edge_rows(edges: &[Edge], height: usize) -> Range<usize> {
+    let top = edges.iter().map(|edge| edge.top).fold(f32::INFINITY, f32::min);
+    let bottom = edges.iter().map(|edge| edge.bottom).fold(f32::NEG_INFINITY, f32::max);
+    rows_between(top, bottom, height)
+}
+
+// Composites a source onto a surface through the coverage of each span, and the clip.  When
+// the source is known to be one solid color, full spans are composited a row at a time.
+struct CompositeSpans<'a, F> {
+    surface: &'a mut ImageSurface,
+    operator: Operator,
+    compose: fn(&Rgba, &mut Rgba),
+    source: F,
+    solid: Option<Rgba>,
+    clip: Option<&'a ImageSurface>,
+}
+
+impl<'a, F: Fn(usize, usize) -> Rgba> SpanRenderer for CompositeSpans<'a, F> {
+    fn render_row(&mut self, y: usize, spans: &[Span]) {
+        let width = self.surface.width;
+        for span in spans {
+            if let Some(color) = self.solid {
+                if self.clip.is_none() && span.coverage >= 1. {
+                    let start = y * width + span.x.min(width);
+                    let end = y * width + (span.x + span.length).min(width);
+                    let pixels = self.surface.iter_mut().into_slice();
+                    composite_span(&self.operator, &color, &mut pixels[start..end]);
+                    continue;
+                }
+            }
+            for x in span.x..span.x + span.length {
+                let clip = self.clip.and_then(|clip| clip.get(x, y)).map_or(1., |clip| clip.alpha);
+                let coverage = span.coverage * clip;
+                if coverage <= 0. {
+                    continue;
+                }
+                let color = (self.source)(x, y);
+                if let Some(pixel) = self.surface.get_mut(x, y) {
+                    composite_with_coverage(self.compose, &color, pixel, coverage);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common_geometry::Point;
+    use surfaces::Format;
+    use trapezoid_rasterizer::mask_from_edges;
+
+    // Collects every row it is given
+    struct Rows(Vec<(usize, Vec<Span>)>);
+
+    impl SpanRenderer for Rows {
+        fn render_row(&mut self, y: usize, spans: &[Span]) {
+            self.0.push((y, spans.to_vec()));
+        }
+    }
+
+    fn polygon(points: &[(f32, f32)]) -> Vec<Edge> {
+        (0..points.len()).map(|index| {
+            let (from, to) = (points[index], points[(index + 1) % points.len()]);
+            Edge::from_points(Point::new(from.0, from.1), Point::new(to.0, to.1))
+        }).collect()
+    }
+
+    #[test]
+    fn rectangle_spans() {
+        // Setup
+        let edges = polygon(&[(1., 1.), (4.5, 1.), (4.5, 3.), (1., 3.)]);
+        let mut rows = Rows(Vec::new());
+
+        // Call
+        render_edges(&edges, FillRule::Winding, Antialias::Default, 8, 8, &mut rows);
+
+        // Test
+        let spans = vec![Span { x: 1, length: 3, coverage: 1. },
+                         Span { x: 4, length: 1, coverage: 0.5 }];
+        assert_eq!(rows.0, vec![(1, spans.clone()), (2, spans)]);
+    }
+
+    #[test]
+    fn spans_match_trapezoids() {
+        // Setup
+        let edges = polygon(&[(1.3, 0.5), (9.2, 3.7), (2.1, 8.8), (6., 2.)]);
+        let mut mask = ImageSurface::create_for_format(Format::ARGB32, 10, 10);
+
+        // Call
+        rasterize_edges_with_source(&edges, FillRule::Winding, |_, _| Rgba::new(0., 0., 0., 1.),
+                                    &Operator::Add, Antialias::Default, None, &mut mask);
+
+        // Test
+        let trapezoids = mask_from_edges(edges, FillRule::Winding, 10, 10);
+        for (spans, trapezoids) in mask.iter().zip(trapezoids.iter()) {
+            assert!((spans.alpha - trapezoids.alpha).abs() < 0.1);
+        }
+    }
+
+    #[test]
+    fn spans_with_color_match_source() {
+        // Setup
+        let edges = polygon(&[(1.3, 0.5), (9.2, 3.7), (2.1, 8.8), (6., 2.)]);
+        let background = Rgba::new(0.2, 0.7, 0.1, 0.6);
+        let mut by_source = ImageSurface::create_for_format(Format::ARGB32, 10, 10);
+        by_source.iter_mut().for_each(|pixel| *pixel = background);
+        let mut by_color = ImageSurface::create_for_format(Format::ARGB32, 10, 10);
+        by_color.iter_mut().for_each(|pixel| *pixel = background);
+
+        for &color in [Rgba::new(0.5, 0.1, 0.9, 1.), Rgba::new(0.5, 0.1, 0.9, 0.4)].iter() {
+            // Call
+            rasterize_edges_with_source(&edges, FillRule::Winding, |_, _| color, &Operator::Over,
+                                        Antialias::Default, None, &mut by_source);
+            rasterize_edges_with_color(&edges, FillRule::Winding, color, &Operator::Over,
+                                       Antialias::Default, None, &mut by_color);
+
+            // Test
+            assert!(by_source.iter().eq(by_color.iter()));
+        }
+    }
+
+    #[test]
+    fn spans_fill_rules() {
+        // Two overlapping squares drawn the same way round
+        let mut edges = polygon(&[(0., 0.), (4., 0.), (4., 4.), (0., 4.)]);
+        edges.extend(polygon(&[(2., 0.), (6., 0.), (6., 4.), (2., 4.)]));
+        let covered = |fill_rule| {
+            let mut rows = Rows(Vec::new());
+            render_edges(&edges, fill_rule, Antialias::Default, 8, 8, &mut rows);
+            rows.0[0].1.clone()
+        };
+
+        assert_eq!(covered(FillRule::Winding), vec![Span { x: 0, length: 6, coverage: 1. }]);
+        assert_eq!(covered(FillRule::EvenOdd), vec![Span { x: 0, length: 2, coverage: 1. },
+                                                    Span { x: 4, length: 2, coverage: 1. }]);
+    }
+
+    #[test]
+    fn spans_without_antialias() {
+        // Setup
+        let edges = polygon(&[(0.7, 0.), (3.4, 0.), (3.4, 2.), (0.7, 2.)]);
+        let mut rows = Rows(Vec::new());
+
+        // Call
+        render_edges(&edges, FillRule::Winding, Antialias::None, 6, 6, &mut rows);
+
+        // Test
+        let spans = vec![Span { x: 1, length: 2, coverage: 1. }];
+        assert_eq!(rows.0, vec![(0, spans.clone()), (1, spans)]);
+    }
+
+    #[test]
+    fn spans_stay_inside_surface() {
+        // Setup
+        let edges = polygon(&[(-5., -5.), (20., -5.), (20., 20.), (-5., 20.)]);
+        let mut rows = Rows(Vec::new());
+
+        // Call
+        render_edges(&edges, FillRule::Winding, Antialias::Default, 3, 2, &mut rows);
+
+        // Test
+        let spans = vec![Span { x: 0, length: 3, coverage: 1. }];
+        assert_eq!(rows.0, vec![(0, spans.clone()), (1, spans)]);
+    }
+}
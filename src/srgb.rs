@@ -0,0 +1,208 @@
+/*
+ * Cairus - a reimplementation of the cairo graphics library in Rust
+ *
+ * Copyright © 2017 CairusOrg
+ *
+ * This library is free software; you can redistribute it and/or
+ * modify it either under the terms of the GNU Lesser General Public
+ * License version 2.1 as published by the Free Software Foundation
+ * (the "LGPL") or, at your option, under the terms of the Mozilla
+ * Public License Version 2.0 (the "MPL"). If you do not alter this
+ * notice, a recipient may use your version of this file under either
+ * the MPL or the LGPL.
+ *
+ * You should have received a copy of the LGPL along with this library
+ * in the file LICENSE-LGPL-2_1; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Suite 500, Boston, MA 02110-1335, USA
+ * You should have received a copy of the MPL along with this library
+ * in the file LICENSE-MPL-2_0
+ *
+ * The contents of this file are subject to the Mozilla Public License
+ * Version 2.0 (the "License"); you may not use this file except in
+ * compliance with the License. You may obtain a copy of the License at
+ * http://www.mozilla.org/MPL/
+ *
+ * This software is distributed on an "AS IS" basis, WITHOUT WARRANTY
+ * OF ANY KIND, either express or implied. See the LGPL or the MPL for
+ * the specific language governing rights and limitations.
+ *
+ * The Original Code is the cairus graphics library.
+ *
+ * Contributor(s):
+ *	CairusOrg
+ *
+ */
+
+//! Conversions between sRGB encoded and linear light colors.
+//!
+//! Colors handed to Cairus are sRGB encoded, like cairo's, so blending them as they are mixes
+//! perceptual values rather than light: a 50% black over white comes out darker than it should
+//! and antialiased edges look thin.  Surfaces that blend in linear light convert colors with
+//! these functions on the way in, and back to sRGB when their pixels are written out as bytes.
+//!
+//! Converting every channel with `powf` is slow, so the byte conversions go through lookup
+//! tables.  The table from linear light has 4096 entries, which is enough for every byte to
+//! survive a round trip.
+
+use std::sync::OnceLock;
+use types::Rgba;
+
+// Entries in the table from linear light to sRGB bytes
+const ENCODE_TABLE_SIZE: usize = 4096;
+
+/// Returns the linear light value of the sRGB encoded `channel`, both in [0, 1].
+pub fn decode(channel: f32) -> f32 {
+    if channel <= 0.04045 {
+        channel / 12.92
+    } else {
+        ((channel + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Returns the sRGB encoding of the linear light value `channel`, both in [0, 1].
+pub fn encode(channel: f32) -> f32 {
+    if channel <= 0.003_130_8 {
+        channel * 12.92
+    } else {
+        1.055 * channel.powf(1. / 2.4) - 0.055
+    }
+}
+
+/// Returns the linear light value of the sRGB encoded `byte`, looked up in a table.
+pub fn decode_byte(byte: u8) -> f32 {
+    static TABLE: OnceLock<[f32; 256]> = OnceLock::new();
+    let table = TABLE.get_or_init(|| {
+        let mut table = [0.; 256];
+        for (byte, entry) in table.iter_mut().enumerate() {
+            *entry = decode(byte as f32 / 255.);
+        }
+        table
+    });
+    table[byte as usize]
+}
+
+/// Returns the sRGB encoded byte nearest the linear light value `channel`, looked up in a
+/// table.  Values outside [0, 1] are clamped.
+pub fn encode_byte(channel: f32) -> u8 {
+    static TABLE: OnceLock<Vec<u8>> = OnceLock::new();
+    let table = TABLE.get_or_init(|| {
+        (0..ENCODE_TABLE_SIZE)
+            .map(|index| {
+                let channel = index as f32 / (ENCODE_TABLE_SIZE - 1) as f32;
+                (encode(channel) * 255.).round() as u8
+            })
+            .collect()
+    });
+    // NaN clamps to NaN and then casts to the first entry
+    let index = (channel.clamp(0., 1.) * (ENCODE_TABLE_SIZE - 1) as f32).round() as usize;
+    table[index]
+}
+
+/// Returns the premultiplied sRGB encoded `color` in linear light, still premultiplied.
+///
+/// # Usage
+/// let linear = srgb::to_linear(&Rgba::new(0.5, 0.5, 0.5, 1.));
+pub fn to_linear(color: &Rgba) -> Rgba {
+    convert(color, decode)
+}
+
+/// Returns the premultiplied linear light `color` encoded as sRGB, still premultiplied.
+pub fn from_linear(color: &Rgba) -> Rgba {
+    convert(color, encode)
+}
+
+/// Returns the premultiplied linear light `color` as un-premultiplied sRGB encoded red, green,
+/// blue and alpha bytes, the way `Rgba::into_bytes` lays them out.
+pub fn linear_to_bytes(color: &Rgba) -> [u8; 4] {
+    if color.alpha <= 0. {
+        return [0; 4];
+    }
+    let alpha = color.alpha.min(1.);
+    [encode_byte(color.red / alpha), encode_byte(color.green / alpha),
+     encode_byte(color.blue / alpha), (alpha * 255.).round() as u8]
+}
+
+/// Returns un-premultiplied sRGB encoded red, green, blue and alpha bytes as a premultiplied
+/// linear light color.
+pub fn linear_from_bytes(bytes: [u8; 4]) -> Rgba {
+    let alpha = bytes[3] as f32 / 255.;
+    Rgba::new(decode_byte(bytes[0]), decode_byte(bytes[1]), decode_byte(bytes[2]), alpha)
+}
+
+// Un-premultiplies `color`, passes each color channel through `transfer` and premultiplies the
+// result again.  Alpha isn't touched, as it is a proportion of coverage rather than light.
+fn convert(color: &Rgba, transfer: fn(f32) -> f32) -> Rgba {
+    if color.alpha <= 0. {
+        return *color;
+    }
+    let channel = |value: f32| transfer((value / color.alpha).clamp(0., 1.)) * color.alpha;
+    Rgba {
+        red: channel(color.red),
+        green: channel(color.green),
+        blue: channel(color.blue),
+        alpha: color.alpha,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use types::Rgba;
+    use srgb::{decode, decode_byte, encode, encode_byte, from_linear, linear_from_bytes,
+               linear_to_bytes, to_linear};
+
+    #[test]
+    fn test_transfer_functions() {
+        // Black, white and the two sides of the linear toe
+        assert_eq!(decode(0.), 0.);
+        assert!((decode(1.) - 1.).abs() < 1e-6);
+        assert!((decode(0.5) - 0.214).abs() < 1e-3);
+        assert!((encode(0.214) - 0.5).abs() < 1e-3);
+        assert!((decode(0.02) - 0.02 / 12.92).abs() < 1e-7);
+        for step in 0..=100 {
+            let channel = step as f32 / 100.;
+            assert!((encode(decode(channel)) - channel).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn test_bytes_survive_the_tables() {
+        for byte in 0..=255u8 {
+            assert_eq!(encode_byte(decode_byte(byte)), byte);
+        }
+        assert_eq!(encode_byte(-1.), 0);
+        assert_eq!(encode_byte(2.), 255);
+    }
+
+    #[test]
+    fn test_colors_stay_premultiplied() {
+        // Setup
+        let color = Rgba::new(0.5, 1., 0., 0.5);
+
+        // Call
+        let linear = to_linear(&color);
+
+        // Test
+        // Alpha is left alone and the colors are converted un-premultiplied
+        assert_eq!(linear.alpha, 0.5);
+        assert!((linear.red - decode(0.5) * 0.5).abs() < 1e-6);
+        assert!((linear.green - 0.5).abs() < 1e-6);
+        assert_eq!(linear.blue, 0.);
+        let back = from_linear(&linear);
+        assert!((back.red - color.red).abs() < 1e-5);
+        assert_eq!(to_linear(&Rgba::new(0., 0., 0., 0.)), Rgba::new(0., 0., 0., 0.));
+    }
+
+    #[test]
+    fn test_linear_bytes() {
+        // Setup
+        let bytes = [128, 255, 0, 51];
+
+        // Call
+        let color = linear_from_bytes(bytes);
+
+        // Test
+        assert!((color.red - decode_byte(128) * 0.2).abs() < 1e-6);
+        assert_eq!(linear_to_bytes(&color), bytes);
+        assert_eq!(linear_to_bytes(&Rgba::new(1., 1., 1., 0.)), [0; 4]);
+    }
+}
@@ -34,8 +34,19 @@
  *
  */
 
-enum Status{
 
+//! Status codes, analogous to cairo_status_t.
+//!
+//! cairo reports failures by putting an object into an error status and returning the status
+//! from later calls.  Cairus returns errors instead, and the errors that stand for one of cairo's
+//! failures carry its Status, so code ported from cairo can still tell them apart.  The variants
+//! are in cairo's order, so each converts to the same number as its cairo_status_t.
+
+use std::fmt;
+
+/// A status code.  Analogous to cairo_status_t.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Status {
     Success = 0,
     NoMemory,
     InvalidRestore,
@@ -50,10 +61,107 @@ enum Status{
     WriteError,
     SurfaceFinished,
     SurfaceTypeMismatch,
+    PatternTypeMismatch,
     InvalidContent,
     InvalidFormat,
     InvalidVisual,
     FileNotFound,
-    LastStatus
+    InvalidDash,
+    InvalidDscComment,
+    InvalidIndex,
+    ClipNotRepresentable,
+    TempFileError,
+    InvalidStride,
+    FontTypeMismatch,
+    UserFontImmutable,
+    UserFontError,
+    NegativeCount,
+    InvalidClusters,
+    InvalidSlant,
+    InvalidWeight,
+    InvalidSize,
+}
+
+impl Status {
+    /// Returns a description of the status.  Analogous to cairo_status_to_string().
+    pub fn description(&self) -> &'static str {
+        match *self {
+            Status::Success => "no error has occurred",
+            Status::NoMemory => "out of memory",
+            Status::InvalidRestore => "restore() without matching save()",
+            Status::InvalidPopGroup => "no saved group to pop",
+            Status::NoCurrentPoint => "no current point defined",
+            Status::InvalidMatrix => "invalid matrix (not invertible)",
+            Status::InvalidStatus => "invalid value for an input Status",
+            Status::NullPointer => "NULL pointer",
+            Status::InvalidString => "input string not valid UTF-8",
+            Status::InvalidPathData => "input path data not valid",
+            Status::ReadError => "error while reading from input stream",
+            Status::WriteError => "error while writing to output stream",
+            Status::SurfaceFinished => "the target surface has been finished",
+            Status::SurfaceTypeMismatch => "the surface type is not appropriate for the operation",
+            Status::PatternTypeMismatch => "the pattern type is not appropriate for the operation",
+            Status::InvalidContent => "invalid value for an input content",
+            Status::InvalidFormat => "invalid value for an input Format",
+            Status::InvalidVisual => "invalid value for an input visual",
+            Status::FileNotFound => "file not found",
+            Status::InvalidDash => "invalid value for a dash setting",
+            Status::InvalidDscComment => "invalid value for a DSC comment",
+            Status::InvalidIndex => "invalid index passed to getter",
+            Status::ClipNotRepresentable => "clip region not representable in desired format",
+            Status::TempFileError => "error creating or writing to a temporary file",
+            Status::InvalidStride => "invalid value for stride",
+            Status::FontTypeMismatch => "the font type is not appropriate for the operation",
+            Status::UserFontImmutable => "the user-font is immutable",
+            Status::UserFontError => "error occurred in a user-font callback function",
+            Status::NegativeCount => "negative number used where it is not allowed",
+            Status::InvalidClusters => {
+                "input clusters do not represent the accompanying text and glyph arrays"
+            },
+            Status::InvalidSlant => "invalid value for an input FontSlant",
+            Status::InvalidWeight => "invalid value for an input FontWeight",
+            Status::InvalidSize => "invalid value (typically too big) for the size of the input",
+        }
+    }
+
+    /// Returns every status, in cairo's order, so a cairo_status_t indexes it.
+    pub fn all() -> &'static [Status] {
+        use self::Status::*;
+        &[Success, NoMemory, InvalidRestore, InvalidPopGroup, NoCurrentPoint, InvalidMatrix,
+          InvalidStatus, NullPointer, InvalidString, InvalidPathData, ReadError, WriteError,
+          SurfaceFinished, SurfaceTypeMismatch, PatternTypeMismatch, InvalidContent, InvalidFormat,
+          InvalidVisual, FileNotFound, InvalidDash, InvalidDscComment, InvalidIndex,
+          ClipNotRepresentable, TempFileError, InvalidStride, FontTypeMismatch, UserFontImmutable,
+          UserFontError, NegativeCount, InvalidClusters, InvalidSlant, InvalidWeight, InvalidSize]
+    }
+
+    /// Returns whether the status is Success.
+    pub fn is_success(&self) -> bool {
+        *self == Status::Success
+    }
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.description())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Status;
 
-}
\ No newline at end of file
+    #[test]
+    fn test_status_matches_cairo() {
+        assert_eq!(Status::Success as i32, 0);
+        assert_eq!(Status::WriteError as i32, 11);
+        assert_eq!(Status::FileNotFound as i32, 18);
+        assert_eq!(Status::InvalidStride as i32, 24);
+        assert_eq!(Status::InvalidSize as i32, 32);
+        assert_eq!(Status::NoMemory.to_string(), "out of memory");
+        assert!(Status::Success.is_success() && !Status::InvalidSize.is_success());
+        for (number, status) in Status::all().iter().enumerate() {
+            assert_eq!(*status as usize, number);
+        }
+    }
+}
@@ -0,0 +1,791 @@
+/*
+ * Cairus - a reimplementation of the cairo graphics library in Rust
+ *
+ * Copyright © 2017 CairusOrg
+ *
+ * This library is free software; you can redistribute it and/or
+ * modify it either under the terms of the GNU Lesser General Public
+ * License version 2.1 as published by the Free Software Foundation
+ * (the "LGPL") or, at your option, under the terms of the Mozilla
+ * Public License Version 2.0 (the "MPL"). If you do not alter this
+ * notice, a recipient may use your version of this file under either
+ * the MPL or the LGPL.
+ *
+ * You should have received a copy of the LGPL along with this library
+ * in the file LICENSE-LGPL-2_1; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Suite 500, Boston, MA 02110-1335, USA
+ * You should have received a copy of the MPL along with this library
+ * in the file LICENSE-MPL-2_0
+ *
+ * The contents of this file are subject to the Mozilla Public License
+ * Version 2.0 (the "License"); you may not use this file except in
+ * compliance with the License. You may obtain a copy of the License at
+ * http://www.mozilla.org/MPL/
+ *
+ * This software is distributed on an "AS IS" basis, WITHOUT WARRANTY
+ * OF ANY KIND, either express or implied. See the LGPL or the MPL for
+ * the specific language governing rights and limitations.
+ *
+ * The Original Code is the cairus graphics library.
+ *
+ * Contributor(s):
+ *	CairusOrg
+ *
+ */
+
+//! Stroking: turning a path into the outline of the area its stroke covers.
+//!
+//! Every segment of the flattened path is offset by half the line width to either side, giving a
+//! quadrilateral, and the corners between consecutive segments get a join polygon: the triangle
+//! cut off by a bevel, the point of a miter, or a fan of points around a round join.  The pieces
+//! overlap, so their edges are all wound the same way round and filled with the nonzero winding
+//! rule, which fills their union.  Filling the outline with `bo_trap` and the trapezoid
+//! rasterizer then draws the stroke.
+//!
+//! The ends of open subpaths get a cap: nothing for a butt cap, a half-width extension of the
+//! last segment for a square cap, or a semicircle for a round cap.
+//!
+//! A dashed stroke walks the dash pattern along each subpath and strokes every "on" dash as an
+//! open polyline of its own, so dashes are capped at both ends and joined at the corners they
+//! turn.
+//!
+//! Zero-length segments have no direction to offset along, so they are dropped.  As in cairo, a
+//! subpath with no length at all still draws its caps, as a circle for round caps and as a square
+//! aligned with the axes for square caps.
+//!
+//! Round joins and caps are arcs, which are approximated by Bézier curves, one per quarter turn or
+//! less, and flattened to within the curve tolerance like the rest of the path.
+
+use std::f32::consts::PI;
+use common_geometry::{Edge, Point};
+use dash::DashPattern;
+use decasteljau::flatten;
+use path::{Path, Segment, arc_segments};
+use types::{LineCap, LineJoin};
+
+/// The parameters that shape a stroke, analogous to the stroke settings of a cairo context.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StrokeStyle {
+    /// The full width of the stroke.
+    pub width: f32,
+    /// How corners between segments are drawn.
+    pub join: LineJoin,
+    /// How the ends of open subpaths are drawn.
+    pub cap: LineCap,
+    /// The longest a miter may be, as a multiple of the line width, before the join is beveled
+    /// instead.  Analogous to cairo_set_miter_limit().
+    pub miter_limit: f32,
+}
+
+/// The miter limit cairo uses by default, which bevels corners sharper than about 11 degrees.
+pub const DEFAULT_MITER_LIMIT: f32 = 10.;
+
+impl StrokeStyle {
+    /// Returns a StrokeStyle `width` wide with cairo's default join, cap and miter limit.
+    pub fn new(width: f32) -> StrokeStyle {
+        StrokeStyle {
+            width,
+            join: LineJoin::Miter,
+            cap: LineCap::Butt,
+            miter_limit: DEFAULT_MITER_LIMIT,
+        }
+    }
+}
+
+/// Returns the edges of the outline of `path` stroked with `style`.
+///
+/// Curves are flattened to within `tolerance` first.  The edges are meant to be filled with
+/// `FillRule::Winding`, see the module documentation.
+///
+/// # Arguments
+/// * `path` - The path to stroke.
+/// * `style` - The width, join, cap and miter limit of the stroke.
+/// * `tolerance` - How far flattened curves may stray from the true curve.
+///
+/// # Usage
+/// let edges = stroke_edges(&path, &StrokeStyle::new(2.), 0.1);
+/// let trapezoids = bo_trap::sweep_with_fill_rule(edges, FillRule::Winding);
+pub fn stroke_edges(path: &Path, style: &StrokeStyle, tolerance: f32) -> Vec<Edge> {
+    stroke_edges_with_dash(path, style, None, tolerance)
+}
+
+/// Returns the edges of the outline of `path` stroked with `style`, and dashed with `dash` if it
+/// is given.
+///
+/// Each "on" dash is stroked as an open polyline of its own, with caps at both ends and joins at
+/// the corners inside it.  As in cairo, a closed subpath whose first and last dashes meet at its
+/// start is joined there rather than capped.
+///
+/// # Arguments
+/// * `path` - The path to stroke.
+/// * `style` - The width, join, cap and miter limit of the stroke.
+/// * `dash` - The dash pattern to walk along every subpath, or None for a solid stroke.
+/// * `tolerance` - How far flattened curves may stray from the true curve.
+///
+/// # Usage
+/// let pattern = DashPattern::new(&[4., 2.], 0.).unwrap();
+/// let edges = stroke_edges_with_dash(&path, &StrokeStyle::new(2.), Some(&pattern), 0.1);
+pub fn stroke_edges_with_dash(path: &Path, style: &StrokeStyle, dash: Option<&DashPattern>,
+                              tolerance: f32) -> Vec<Edge> {
+    polygon_edges(&stroke_polygons(path, style, dash, tolerance))
+}
+
+/// Returns the outline of `path` stroked with `style`, and dashed with `dash` if it is given,
+/// as a path to fill with `FillRule::Winding`.
+///
+/// The outline is made of closed polygons that overlap, each wound the same way round, which
+/// is what filling the stroke fills.  See `stroke_edges_with_dash`.
+///
+/// # Usage
+/// let outline = stroke_outline(&path, &StrokeStyle::new(2.), None, 0.1);
+pub fn stroke_outline(path: &Path, style: &StrokeStyle, dash: Option<&DashPattern>,
+                      tolerance: f32) -> Path {
+    let mut outline = Path::new();
+    for polygon in stroke_polygons(path, style, dash, tolerance) {
+        outline.move_to(polygon[0].x, polygon[0].y);
+        for point in &polygon[1..] {
+            outline.line_to(point.x, point.y);
+        }
+        outline.close_path();
+    }
+    outline
+}
+
+// Returns the polygons whose union is the stroke of `path`, each wound so that its signed area
+// is positive.
+fn stroke_polygons(path: &Path, style: &StrokeStyle, dash: Option<&DashPattern>,
+                   tolerance: f32) -> Vec<Vec<Point>> {
+    let mut polygons = Vec::new();
+    if style.width <= 0. {
+        return polygons;
+    }
+
+    for polyline in path.flatten(tolerance) {
+        // A subpath that is only a move_to draws nothing, not even caps
+        let drawn = polyline.points.len() > 1;
+        let mut points = polyline.points;
+        points.dedup();
+        if polyline.closed && points.len() > 1 && points.first() == points.last() {
+            points.pop();
+        }
+        if points.len() == 1 && drawn && !polyline.closed {
+            push_dot(&mut polygons, style, points[0], tolerance);
+        }
+        if points.len() < 2 {
+            continue;
+        }
+
+        match dash {
+            Some(pattern) => push_dashes(&mut polygons, style, pattern, points, polyline.closed,
+                                         tolerance),
+            None => push_polyline(&mut polygons, style, &points, polyline.closed, tolerance),
+        }
+    }
+    polygons
+}
+
+// Returns the edges of every polygon in `polygons`
+fn polygon_edges(polygons: &[Vec<Point>]) -> Vec<Edge> {
+    let mut edges = Vec::new();
+    for polygon in polygons {
+        for index in 0..polygon.len() {
+            edges.push(Edge::from_points(polygon[index], polygon[(index + 1) % polygon.len()]));
+        }
+    }
+    edges
+}
+
+// Adds the stroke of the polyline through `points`, which must hold at least two points and no
+// repeated ones.  Open polylines are capped, closed ones are joined back to their first point.
+fn push_polyline(polygons: &mut Vec<Vec<Point>>, style: &StrokeStyle, points: &[Point],
+                 closed: bool, tolerance: f32) {
+    let half_width = style.width / 2.;
+    let mut segments: Vec<(Point, Point)> = points.windows(2)
+        .map(|pair| (pair[0], pair[1]))
+        .collect();
+    if closed && points.len() > 2 {
+        segments.push((points[points.len() - 1], points[0]));
+    }
+
+    for &(from, to) in segments.iter() {
+        let offset = normal(from, to, half_width);
+        push_polygon(polygons, &[add(from, offset), add(to, offset), sub(to, offset),
+                              sub(from, offset)]);
+    }
+
+    let joints = if closed && segments.len() > 2 {
+        segments.len()
+    } else {
+        segments.len() - 1
+    };
+    for index in 0..joints {
+        let (from, pivot) = segments[index];
+        let (_, to) = segments[(index + 1) % segments.len()];
+        push_join(polygons, style, from, pivot, to, tolerance);
+    }
+
+    if !closed {
+        push_cap(polygons, style, points[1], points[0], tolerance);
+        push_cap(polygons, style, points[points.len() - 2], points[points.len() - 1], tolerance);
+    }
+}
+
+// Adds the stroke of the "on" dashes of the polyline through `points`, walking `pattern` along
+// it from the pattern's start.
+fn push_dashes(polygons: &mut Vec<Vec<Point>>, style: &StrokeStyle, pattern: &DashPattern,
+               mut points: Vec<Point>, closed: bool, tolerance: f32) {
+    if closed && points.len() > 2 {
+        let first = points[0];
+        points.push(first);
+    }
+
+    // The dashes found so far, and whether the last one reaches the current vertex and so
+    // carries on into the next segment
+    let mut dashes: Vec<Vec<Point>> = Vec::new();
+    let mut reaches_vertex = false;
+    let mut state = pattern.start();
+    for pair in points.windows(2) {
+        let (start, end) = (pair[0], pair[1]);
+        let direction = sub(end, start);
+        let length = direction.x.hypot(direction.y);
+        let point_at = |distance: f32| add(start, scale(direction, distance / length));
+
+        let mut intervals = pattern.intervals_from(state, length);
+        let mut reaches_end = false;
+        for (from, to) in intervals.by_ref() {
+            if from == 0. && reaches_vertex {
+                dashes.last_mut().unwrap().push(point_at(to));
+            } else if from == to {
+                // A zero-length dash has no extent, but its caps still follow the path
+                let center = point_at(from);
+                push_cap(polygons, style, sub(center, direction), center, tolerance);
+                push_cap(polygons, style, add(center, direction), center, tolerance);
+            } else {
+                dashes.push(vec![point_at(from), point_at(to)]);
+            }
+            reaches_vertex = false;
+            reaches_end = to == length && from != to;
+        }
+        reaches_vertex = reaches_end;
+        state = intervals.state();
+    }
+
+    // The first dash starts exactly where the pattern does, so on a closed polyline the last
+    // dash runs into it
+    let starts_on = pattern.start().on && pattern.start().remaining > 0.;
+    if closed && reaches_vertex && starts_on {
+        if dashes.len() == 1 {
+            // The whole polyline is on
+            points.pop();
+            push_polyline(polygons, style, &points, true, tolerance);
+            return;
+        }
+        let mut last = dashes.pop().unwrap();
+        last.extend_from_slice(&dashes[0][1..]);
+        dashes[0] = last;
+    }
+
+    for mut dash in dashes {
+        dash.dedup();
+        if dash.len() > 1 {
+            push_polyline(polygons, style, &dash, false, tolerance);
+        }
+    }
+}
+
+// Adds the cap at `end`, the end of the segment from `from`, in the style's cap.
+fn push_cap(polygons: &mut Vec<Vec<Point>>, style: &StrokeStyle, from: Point, end: Point,
+            tolerance: f32) {
+    let half_width = style.width / 2.;
+    let offset = normal(from, end, half_width);
+    // The offset turned a quarter turn to point out of the end of the segment
+    let outward = Point::new(offset.y, -offset.x);
+    match style.cap {
+        LineCap::Butt => {},
+        LineCap::Round => {
+            let mut points = vec![add(end, offset)];
+            let angle = turn_angle(offset, scale(offset, -1.), outward);
+            points.extend(arc(end, add(end, offset), angle, tolerance));
+            push_polygon(polygons, &points);
+        },
+        LineCap::Square => {
+            push_polygon(polygons, &[add(end, offset), add(add(end, offset), outward),
+                                  sub(add(end, outward), offset), sub(end, offset)]);
+        },
+    }
+}
+
+// Adds the caps of a subpath that stays at `point`, which have no direction to follow.
+fn push_dot(polygons: &mut Vec<Vec<Point>>, style: &StrokeStyle, point: Point, tolerance: f32) {
+    let half_width = style.width / 2.;
+    match style.cap {
+        LineCap::Butt => {},
+        LineCap::Round => {
+            let start = Point::new(point.x + half_width, point.y);
+            push_polygon(polygons, &arc(point, start, 2. * PI, tolerance));
+        },
+        LineCap::Square => {
+            push_polygon(polygons, &[Point::new(point.x - half_width, point.y - half_width),
+                                  Point::new(point.x + half_width, point.y - half_width),
+                                  Point::new(point.x + half_width, point.y + half_width),
+                                  Point::new(point.x - half_width, point.y + half_width)]);
+        },
+    }
+}
+
+// Adds the polygon covering the outside of the corner at `pivot`, between the segment from `from`
+// and the segment to `to`, in the style's join.
+fn push_join(polygons: &mut Vec<Vec<Point>>, style: &StrokeStyle, from: Point, pivot: Point,
+             to: Point, tolerance: f32) {
+    let half_width = style.width / 2.;
+    let incoming = normal(from, pivot, half_width);
+    let outgoing = normal(pivot, to, half_width);
+    let (before, after) = (sub(pivot, from), sub(to, pivot));
+    let turn = cross(before, after);
+    // The cosine of the angle the path turns through
+    let cosine = dot(before, after) / (before.x.hypot(before.y) * after.x.hypot(after.y));
+    if turn == 0. && cosine > 0. {
+        return;
+    }
+
+    // The outside of the corner is the side the path turns away from.  A path that doubles back
+    // has no outside, and only a round join draws anything there, so either side will do.
+    let side = if turn > 0. { -1. } else { 1. };
+    let incoming = scale(incoming, side);
+    let outgoing = scale(outgoing, side);
+    match style.join {
+        LineJoin::Round => {
+            let mut points = vec![pivot, add(pivot, incoming)];
+            points.extend(arc(pivot, add(pivot, incoming), turn_angle(incoming, outgoing, before),
+                              tolerance));
+            push_polygon(polygons, &points);
+        },
+        // As in cairo, the miter is cut off when its length, 1 / sin(θ / 2) line widths for a
+        // corner of angle θ, exceeds the limit.  θ is π less the turn, so sin(θ / 2) is
+        // cos(turn / 2), and squaring both sides leaves the cosine of the turn.
+        LineJoin::Miter if style.miter_limit * style.miter_limit * (1. + cosine) >= 2. => {
+            let tip = add(pivot, scale(add(incoming, outgoing), 1. / (1. + cosine)));
+            push_polygon(polygons, &[pivot, add(pivot, incoming), tip, add(pivot, outgoing)]);
+        },
+        _ => push_polygon(polygons, &[pivot, add(pivot, incoming), add(pivot, outgoing)]),
+    }
+}
+
+// Returns the signed angle that turns the offset `from` into the offset `to` around the outside
+// of a corner.  When they point in opposite directions the turn goes through `forward`, the way
+// the path was heading.
+fn turn_angle(from: Point, to: Point, forward: Point) -> f32 {
+    let angle = cross(from, to).atan2(dot(from, to));
+    if cross(from, to) == 0. && dot(from, to) < 0. {
+        if cross(from, forward) > 0. { PI } else { -PI }
+    } else {
+        angle
+    }
+}
+
+// Returns points along the circular arc around `center` that starts at `start` and turns through
+// `angle` radians, counterclockwise in y-up coordinates for a positive angle, excluding `start`.
+// The arc is approximated by Bézier curves, which are flattened to within `tolerance`.
+fn arc(center: Point, start: Point, angle: f32, tolerance: f32) -> Vec<Point> {
+    let radius_vector = sub(start, center);
+    let radius = radius_vector.x.hypot(radius_vector.y);
+    let first_angle = radius_vector.y.atan2(radius_vector.x);
+    let mut points = Vec::new();
+    for segment in arc_segments(center, radius, first_angle, first_angle + angle, tolerance) {
+        if let Segment::Curve(from, control1, control2, to) = segment {
+            points.extend(flatten(&from, &control1, &control2, &to, tolerance));
+        }
+    }
+    points
+}
+
+// Adds the closed polygon through `points`, wound so that its signed area is positive, whichever
+// way round the points are given.
+fn push_polygon(polygons: &mut Vec<Vec<Point>>, points: &[Point]) {
+    let area: f32 = (0..points.len())
+        .map(|index| cross(points[index], points[(index + 1) % points.len()]))
+        .sum();
+    if area == 0. {
+        return;
+    }
+
+    let mut polygon = points.to_vec();
+    if area < 0. {
+        polygon.reverse();
+    }
+    polygon.dedup();
+    while polygon.len() > 1 && polygon.first() == polygon.last() {
+        polygon.pop();
+    }
+    polygons.push(polygon);
+}
+
+// Returns the vector `length` long at right angles to the line from `from` to `to`, pointing to
+// its left in y-up coordinates.
+fn normal(from: Point, to: Point, length: f32) -> Point {
+    let delta = sub(to, from);
+    let scale = length / delta.x.hypot(delta.y);
+    Point::new(-delta.y * scale, delta.x * scale)
+}
+
+fn scale(a: Point, factor: f32) -> Point {
+    Point::new(a.x * factor, a.y * factor)
+}
+
+fn add(a: Point, b: Point) -> Point {
+    Point::new(a.x + b.x, a.y + b.y)
+}
+
+fn sub(a: Point, b: Point) -> Point {
+    a - b
+}
+
+fn dot(a: Point, b: Point) -> f32 {
+    a.x * b.x + a.y * b.y
+}
+
+// Returns the z component of the cross product of `a` and `b`
+fn cross(a: Point, b: Point) -> f32 {
+    a.x * b.y - a.y * b.x
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{StrokeStyle, arc, polygon_edges, push_polygon, stroke_edges,
+                stroke_edges_with_dash, stroke_outline};
+    use dash::DashPattern;
+    use std::f32::consts::PI;
+    use common_geometry::{Edge, Point};
+    use path::Path;
+    use trapezoid_rasterizer::mask_from_edges;
+    use types::{FillRule, LineCap, LineJoin};
+
+    // Returns the fraction of each pixel of a `size` by `size` surface covered by `edges`
+    fn coverage(edges: Vec<Edge>, size: usize) -> Vec<f32> {
+        mask_from_edges(edges, FillRule::Winding, size, size).iter()
+            .map(|pixel| pixel.alpha)
+            .collect()
+    }
+
+    #[test]
+    fn test_stroke_line() {
+        // Setup
+        let mut path = Path::new();
+        path.move_to(2., 5.).line_to(8., 5.);
+
+        // Call
+        let edges = stroke_edges(&path, &StrokeStyle::new(2.), 0.1);
+
+        // Test
+        assert_eq!(edges.len(), 4);
+        let coverage = coverage(edges, 10);
+        assert_eq!(coverage[4 * 10 + 5], 1.);
+        assert_eq!(coverage[5 * 10 + 5], 1.);
+        assert_eq!(coverage[6 * 10 + 5], 0.);
+        assert_eq!(coverage[5 * 10 + 1], 0.);
+    }
+
+    #[test]
+    fn test_stroke_corner_is_joined() {
+        // Setup
+        let mut path = Path::new();
+        path.move_to(2., 10.).line_to(10., 10.).line_to(10., 2.);
+
+        // Call
+        let style = StrokeStyle { join: LineJoin::Bevel, ..StrokeStyle::new(4.) };
+        let coverage = coverage(stroke_edges(&path, &style, 0.1), 16);
+
+        // Test
+        // The bevel fills the outside of the corner up to the line between the offset ends
+        assert_eq!(coverage[10 * 16 + 10], 1.);
+        assert!(coverage[10 * 16 + 11] > 0.4 && coverage[10 * 16 + 11] < 0.6);
+        assert_eq!(coverage[11 * 16 + 11], 0.);
+        assert_eq!(coverage[11 * 16 + 5], 1.);
+        assert_eq!(coverage[5 * 16 + 11], 1.);
+    }
+
+    // Strokes the corner of `test_stroke_corner_is_joined` with `join` and `miter_limit`
+    fn corner_coverage(join: LineJoin, miter_limit: f32) -> Vec<f32> {
+        let mut path = Path::new();
+        path.move_to(2., 10.).line_to(10., 10.).line_to(10., 2.);
+        let style = StrokeStyle { join, miter_limit, ..StrokeStyle::new(4.) };
+        coverage(stroke_edges(&path, &style, 0.1), 16)
+    }
+
+    #[test]
+    fn test_miter_join() {
+        // A right angle's miter is 1.41 line widths long, which fills in the square corner
+        let coverage = corner_coverage(LineJoin::Miter, 10.);
+        assert_eq!(coverage[11 * 16 + 11], 1.);
+        assert_eq!(coverage[10 * 16 + 11], 1.);
+        assert_eq!(coverage[12 * 16 + 12], 0.);
+
+        // Under a limit of 1.4 it is beveled instead
+        let beveled = corner_coverage(LineJoin::Miter, 1.4);
+        assert_eq!(beveled, corner_coverage(LineJoin::Bevel, 10.));
+        assert_eq!(beveled[11 * 16 + 11], 0.);
+    }
+
+    #[test]
+    fn test_round_join() {
+        // The round join lies between the bevel and the miter
+        let round = corner_coverage(LineJoin::Round, 10.);
+        let bevel = corner_coverage(LineJoin::Bevel, 10.);
+        let miter = corner_coverage(LineJoin::Miter, 10.);
+        let total = |coverage: &Vec<f32>| coverage.iter().sum::<f32>();
+        assert!(total(&bevel) < total(&round) && total(&round) < total(&miter));
+        // The outside of the corner is a quarter circle of radius 2 around (10, 10), against
+        // the bevel's triangle and the miter's square
+        assert!((total(&round) - total(&bevel) - (PI - 2.)).abs() < 0.1);
+        assert!((total(&miter) - total(&bevel) - 2.).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_round_join_doubling_back() {
+        // A path that turns straight back gets a half circle around the turning point
+        let mut path = Path::new();
+        path.move_to(2., 5.).line_to(8., 5.).line_to(4., 5.);
+        let style = StrokeStyle { join: LineJoin::Round, ..StrokeStyle::new(4.) };
+        let coverage = coverage(stroke_edges(&path, &style, 0.1), 12);
+        assert_eq!(coverage[5 * 12 + 8], 1.);
+        assert!(coverage[5 * 12 + 9] > 0.5);
+        assert_eq!(coverage[5 * 12 + 10], 0.);
+    }
+
+    #[test]
+    fn test_arc() {
+        // A half circle ends where it should, and its points stay on the circle
+        let center = Point::new(0., 0.);
+        let points = arc(center, Point::new(2., 0.), PI, 0.01);
+        let last = points[points.len() - 1];
+        assert!((last.x + 2.).abs() < 1e-5 && last.y.abs() < 1e-5);
+        assert!(points.iter().all(|point| (point.x.hypot(point.y) - 2.).abs() < 0.01));
+        assert!(points.iter().all(|point| point.y > -1e-5));
+    }
+
+    // Strokes a horizontal line from (4, 6) to (8, 6) with `cap`
+    fn line_coverage(cap: LineCap) -> Vec<f32> {
+        let mut path = Path::new();
+        path.move_to(4., 6.).line_to(8., 6.);
+        let style = StrokeStyle { cap, ..StrokeStyle::new(4.) };
+        coverage(stroke_edges(&path, &style, 0.01), 12)
+    }
+
+    #[test]
+    fn test_butt_cap() {
+        // The stroke stops square at the ends of the line
+        let coverage = line_coverage(LineCap::Butt);
+        assert_eq!(coverage[6 * 12 + 4], 1.);
+        assert_eq!(coverage[6 * 12 + 3], 0.);
+        assert_eq!(coverage[6 * 12 + 8], 0.);
+        assert_eq!(coverage.iter().sum::<f32>(), 16.);
+    }
+
+    #[test]
+    fn test_square_cap() {
+        // Each end is extended by half the line width
+        let coverage = line_coverage(LineCap::Square);
+        assert_eq!(coverage[4 * 12 + 2], 1.);
+        assert_eq!(coverage[7 * 12 + 9], 1.);
+        assert_eq!(coverage[6 * 12 + 1], 0.);
+        assert_eq!(coverage[6 * 12 + 10], 0.);
+        assert_eq!(coverage.iter().sum::<f32>(), 32.);
+    }
+
+    #[test]
+    fn test_round_cap() {
+        // Each end gets a semicircle of radius 2, making up a circle of area 4π between them
+        let coverage = line_coverage(LineCap::Round);
+        assert_eq!(coverage[6 * 12 + 3], 1.);
+        assert_eq!(coverage[6 * 12 + 8], 1.);
+        assert!(coverage[4 * 12 + 2] < 0.5);
+        assert_eq!(coverage[6 * 12 + 10], 0.);
+        assert!((coverage.iter().sum::<f32>() - (16. + 4. * PI)).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_caps_on_closed_path() {
+        // A closed path has no ends to cap
+        let mut path = Path::new();
+        path.move_to(3., 3.).line_to(8., 3.).line_to(8., 8.).close_path();
+        let butt = StrokeStyle { join: LineJoin::Bevel, ..StrokeStyle::new(2.) };
+        let round = StrokeStyle { cap: LineCap::Round, ..butt };
+        assert_eq!(stroke_edges(&path, &butt, 0.1).len(), stroke_edges(&path, &round, 0.1).len());
+    }
+
+    #[test]
+    fn test_caps_on_zero_length_path() {
+        let mut path = Path::new();
+        path.move_to(5., 5.).line_to(5., 5.);
+
+        let square = StrokeStyle { cap: LineCap::Square, ..StrokeStyle::new(4.) };
+        let square_coverage = coverage(stroke_edges(&path, &square, 0.1), 10);
+        assert_eq!(square_coverage.iter().sum::<f32>(), 16.);
+        assert_eq!(square_coverage[3 * 10 + 3], 1.);
+
+        let round = StrokeStyle { cap: LineCap::Round, ..StrokeStyle::new(4.) };
+        let round_coverage = coverage(stroke_edges(&path, &round, 0.01), 10);
+        assert!((round_coverage.iter().sum::<f32>() - 4. * PI).abs() < 0.1);
+
+        // A lone move_to has no caps
+        let mut path = Path::new();
+        path.move_to(5., 5.);
+        assert!(stroke_edges(&path, &round, 0.1).is_empty());
+    }
+
+    #[test]
+    fn test_dashed_line() {
+        // Setup
+        let mut path = Path::new();
+        path.move_to(1., 5.).line_to(11., 5.);
+        let pattern = DashPattern::new(&[2., 1.], 0.).unwrap();
+
+        // Call
+        let edges = stroke_edges_with_dash(&path, &StrokeStyle::new(2.), Some(&pattern), 0.1);
+
+        // Test
+        // Dashes cover 1 to 3, 4 to 6, 7 to 9 and 10 to 11
+        let coverage = coverage(edges, 12);
+        let row: Vec<f32> = coverage[5 * 12..6 * 12].to_vec();
+        assert_eq!(row, vec![0., 1., 1., 0., 1., 1., 0., 1., 1., 0., 1., 0.]);
+    }
+
+    #[test]
+    fn test_dashed_line_offset_and_caps() {
+        // The offset shifts the pattern back, and square caps grow each dash by the half width
+        let mut path = Path::new();
+        path.move_to(1., 5.).line_to(11., 5.);
+        let pattern = DashPattern::new(&[2., 4.], 4.).unwrap();
+        let style = StrokeStyle { cap: LineCap::Square, ..StrokeStyle::new(2.) };
+
+        let coverage = coverage(stroke_edges_with_dash(&path, &style, Some(&pattern), 0.1), 12);
+
+        // Dashes cover 3 to 5 and 9 to 11, and one unit more either side
+        let row: Vec<f32> = coverage[5 * 12..6 * 12].to_vec();
+        assert_eq!(row, vec![0., 0., 1., 1., 1., 1., 0., 0., 1., 1., 1., 1.]);
+    }
+
+    #[test]
+    fn test_dash_joined_around_corner() {
+        // A dash that turns a corner is joined there, not capped
+        let mut path = Path::new();
+        path.move_to(2., 10.).line_to(10., 10.).line_to(10., 2.);
+        let pattern = DashPattern::new(&[4., 2.], 0.).unwrap();
+        let style = StrokeStyle { join: LineJoin::Miter, ..StrokeStyle::new(4.) };
+
+        let coverage = coverage(stroke_edges_with_dash(&path, &style, Some(&pattern), 0.1), 16);
+
+        // The second dash runs from (8, 10) to (10, 8), and its miter fills in the corner
+        assert_eq!(coverage[11 * 16 + 11], 1.);
+        assert_eq!(coverage[11 * 16 + 7], 0.);
+    }
+
+    #[test]
+    fn test_dashed_closed_path_joins_at_start() {
+        // Setup
+        let mut path = Path::new();
+        path.move_to(4., 4.).line_to(12., 4.).line_to(12., 10.).line_to(4., 10.).close_path();
+        let style = StrokeStyle { join: LineJoin::Miter, ..StrokeStyle::new(2.) };
+        // The last dash, from 27 to the end at 28, runs into the first dash from 0 to 1
+        let pattern = DashPattern::new(&[2., 2.], 1.).unwrap();
+
+        // Call
+        let dashed = coverage(stroke_edges_with_dash(&path, &style, Some(&pattern), 0.1), 16);
+
+        // Test
+        // The corner at the start is mitered
+        assert_eq!(dashed[3 * 16 + 3], 1.);
+        // While the corner at (12, 10), which falls between dashes, is left empty
+        assert_eq!(dashed[10 * 16 + 12], 0.);
+
+        // A pattern that is on everywhere draws the same as no dashing at all
+        let solid = DashPattern::new(&[100., 1.], 0.).unwrap();
+        let solid_edges = stroke_edges_with_dash(&path, &style, Some(&solid), 0.1);
+        assert_eq!(coverage(solid_edges, 16), coverage(stroke_edges(&path, &style, 0.1), 16));
+    }
+
+    #[test]
+    fn test_zero_length_dashes() {
+        // Zero-length dashes draw only their caps, which are round dots here
+        let mut path = Path::new();
+        path.move_to(2., 5.).line_to(10., 5.);
+        let pattern = DashPattern::new(&[0., 4.], 0.).unwrap();
+        let style = StrokeStyle { cap: LineCap::Round, ..StrokeStyle::new(2.) };
+        let butt = StrokeStyle::new(2.);
+
+        let coverage = coverage(stroke_edges_with_dash(&path, &style, Some(&pattern), 0.01), 12);
+
+        // Dots of radius 1 at 2, 6 and 10
+        assert!((coverage.iter().sum::<f32>() - 3. * PI).abs() < 0.1);
+        assert!(coverage[4 * 12 + 5] > 0.5 && coverage[4 * 12 + 4] < 0.5);
+        assert!(stroke_edges_with_dash(&path, &butt, Some(&pattern), 0.1).is_empty());
+    }
+
+    #[test]
+    fn test_stroke_closed_path() {
+        // A closed square is stroked all the way round, and its middle is left empty
+        let mut path = Path::new();
+        path.move_to(3., 3.).line_to(12., 3.).line_to(12., 12.).line_to(3., 12.).close_path();
+
+        let style = StrokeStyle { join: LineJoin::Bevel, ..StrokeStyle::new(2.) };
+        let coverage = coverage(stroke_edges(&path, &style, 0.1), 16);
+
+        assert_eq!(coverage[2 * 16 + 7], 1.);
+        assert_eq!(coverage[7 * 16 + 2], 1.);
+        assert_eq!(coverage[7 * 16 + 7], 0.);
+        // The corners are beveled
+        assert!(coverage[2 * 16 + 2] > 0.4 && coverage[2 * 16 + 2] < 0.9);
+    }
+
+    #[test]
+    fn test_stroke_skips_degenerate_segments() {
+        let mut path = Path::new();
+        path.move_to(1., 1.).line_to(1., 1.);
+        assert!(stroke_edges(&path, &StrokeStyle::new(2.), 0.1).is_empty());
+
+        // Repeated points don't add segments or joins
+        let mut path = Path::new();
+        path.move_to(2., 5.).line_to(5., 5.).line_to(5., 5.).line_to(8., 5.);
+        assert_eq!(stroke_edges(&path, &StrokeStyle::new(2.), 0.1).len(), 8);
+
+        let mut path = Path::new();
+        path.move_to(2., 5.).line_to(8., 5.);
+        assert!(stroke_edges(&path, &StrokeStyle::new(0.), 0.1).is_empty());
+    }
+
+    #[test]
+    fn test_push_polygon_winding() {
+        // Either orientation gives the same edges, wound the same way
+        let points = [Point::new(0., 0.), Point::new(2., 0.), Point::new(2., 2.)];
+        let mut forward = Vec::new();
+        let mut backward = Vec::new();
+        push_polygon(&mut forward, &points);
+        push_polygon(&mut backward, &[points[2], points[1], points[0]]);
+        let (forward, backward) = (polygon_edges(&forward), polygon_edges(&backward));
+        let directions = |edges: &Vec<Edge>| {
+            let mut directions: Vec<i32> = edges.iter().map(|edge| edge.direction).collect();
+            directions.sort();
+            directions
+        };
+        assert_eq!(directions(&forward), directions(&backward));
+        assert_eq!(forward.len(), 3);
+    }
+
+    #[test]
+    fn test_stroke_outline() {
+        // Filling the outline covers what the stroke covers
+        let mut path = Path::new();
+        path.move_to(3., 3.).line_to(12., 3.).line_to(8., 12.);
+        let style = StrokeStyle { join: LineJoin::Round, cap: LineCap::Square,
+                                  ..StrokeStyle::new(3.) };
+
+        let outline = stroke_outline(&path, &style, None, 0.1);
+
+        let filled = coverage(outline.edges(0.1), 16);
+        let stroked = coverage(stroke_edges(&path, &style, 0.1), 16);
+        for (filled, stroked) in filled.iter().zip(&stroked) {
+            assert!((filled - stroked).abs() < 1e-4);
+        }
+        assert!(stroke_outline(&path, &StrokeStyle::new(0.), None, 0.1).is_empty());
+    }
+}
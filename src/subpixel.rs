@@ -0,0 +1,153 @@
+/*
+ * Cairus - a reimplementation of the cairo graphics library in Rust
+ *
+ * Copyright © 2017 CairusOrg
+ *
+ * This library is free software; you can redistribute it and/or
+ * modify it either under the terms of the GNU Lesser General Public
+ * License version 2.1 as published by the Free Software Foundation
+ * (the "LGPL") or, at your option, under the terms of the Mozilla
+ * Public License Version 2.0 (the "MPL"). If you do not alter this
+ * notice, a recipient may use your version of this file under either
+ * the MPL or the LGPL.
+ *
+ * You should have received a copy of the LGPL along with this library
+ * in the file LICENSE-LGPL-2_1; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Suite 500, Boston, MA 02110-1335, USA
+ * You should have received a copy of the MPL along with this library
+ * in the file LICENSE-MPL-2_0
+ *
+ * The contents of this file are subject to the Mozilla Public License
+ * Version 2.0 (the "License"); you may not use this file except in
+ * compliance with the License. You may obtain a copy of the License at
+ * http://www.mozilla.org/MPL/
+ *
+ * This software is distributed on an "AS IS" basis, WITHOUT WARRANTY
+ * OF ANY KIND, either express or implied. See the LGPL or the MPL for
+ * the specific language governing rights and limitations.
+ *
+ * The Original Code is the cairus graphics library.
+ *
+ * Contributor(s):
+ *	CairusOrg
+ *
+ */
+
+//! Subpixel antialiasing for LCD panels.
+//!
+//! Each pixel of an LCD panel is three colored stripes side by side.  Lighting them separately
+//! triples the horizontal resolution text can be drawn at, which is what
+//! `Antialias::Subpixel` does: the text is rasterized at three times the width, each stripe's
+//! coverage becomes the coverage of its color channel, and the channels are composited apart.
+//!
+//! Coverage that changes sharply from one stripe to the next shows as colored fringes, so the
+//! stripes are first smoothed with FreeType's default LCD filter, which spreads each stripe's
+//! coverage over its two neighbours either side without changing the total.
+
+use path::Path;
+use surfaces::SubpixelMask;
+use trapezoid_rasterizer::mask_from_trapezoids_with_antialias;
+use matrix::Matrix;
+use types::{Antialias, FillRule, SubpixelOrder};
+
+/// The weights, out of 256, of FreeType's default LCD filter, for the stripe itself in the
+/// middle and up to two stripes either side.
+pub const LCD_FILTER: [u32; 5] = [8, 77, 86, 77, 8];
+
+/// Returns the coverage of each color channel of the pixels inside `path`, in device space,
+/// for a panel whose stripes are in `order`.
+///
+/// The path is filled with `fill_rule` and flattened to within `tolerance`.  The mask covers
+/// the path, with an extra column either side for the filter to spread into.
+///
+/// # Usage
+/// let mask = subpixel::subpixel_mask(&outline, FillRule::Winding, SubpixelOrder::Rgb, 0.1);
+pub fn subpixel_mask(path: &Path, fill_rule: FillRule, order: SubpixelOrder, tolerance: f32)
+                     -> SubpixelMask {
+    let extents = match path.extents() {
+        Some(extents) => extents,
+        None => return SubpixelMask::default(),
+    };
+    let (x, y) = (extents.x.floor() - 1., extents.y.floor());
+    let right = (extents.x + extents.width).ceil() + 1.;
+    let bottom = (extents.y + extents.height).ceil();
+    if !(x.is_finite() && y.is_finite() && right.is_finite() && bottom.is_finite()) {
+        return SubpixelMask::default();
+    }
+    let (width, height) = ((right - x) as usize, ((bottom - y) as usize).max(1));
+
+    // Each stripe is a pixel of a mask three times as wide
+    let to_stripes = Matrix::new(3., 0., 0., 1., -3. * x, -y);
+    let trapezoids = path.transform(&to_stripes).trapezoids(fill_rule, tolerance);
+    let stripes = mask_from_trapezoids_with_antialias(&trapezoids, Antialias::Gray, 3 * width,
+                                                      height);
+
+    let stripes: Vec<u32> = stripes.iter().map(|stripe| (stripe.alpha * 255.).round() as u32)
+                                   .collect();
+    let mut mask = SubpixelMask::new(x as i32, y as i32, width, height);
+    for (row, coverage) in stripes.chunks(3 * width).zip(mask.coverage.chunks_mut(width)) {
+        let filtered: Vec<u8> = (0..row.len()).map(|stripe| {
+            let sum: u32 = LCD_FILTER.iter().enumerate().map(|(tap, weight)| {
+                (stripe + tap).checked_sub(2).and_then(|source| row.get(source))
+                    .map_or(0, |coverage| weight * coverage)
+            }).sum();
+            ((sum + 128) / 256).min(255) as u8
+        }).collect();
+        for (pixel, stripes) in coverage.iter_mut().zip(filtered.chunks(3)) {
+            *pixel = match order {
+                SubpixelOrder::Default | SubpixelOrder::Rgb => {
+                    [stripes[0], stripes[1], stripes[2]]
+                },
+                SubpixelOrder::Bgr => [stripes[2], stripes[1], stripes[0]],
+            };
+        }
+    }
+    mask
+}
+
+#[cfg(test)]
+mod tests {
+    use common_geometry::Rectangle;
+    use path::Path;
+    use types::{FillRule, SubpixelOrder};
+    use super::{LCD_FILTER, subpixel_mask};
+
+    #[test]
+    fn lcd_filter_keeps_coverage() {
+        assert_eq!(LCD_FILTER.iter().sum::<u32>(), 256);
+    }
+
+    #[test]
+    fn subpixel_mask_of_a_rectangle() {
+        // Setup
+        // Three pixels wide, starting a third of the way into a pixel, so the first pixel's red
+        // stripe is left out
+        let path = Path::from_rectangles(&[Rectangle::new(2. + 1. / 3., 1., 3., 2.)]);
+
+        // Call
+        let rgb = subpixel_mask(&path, FillRule::Winding, SubpixelOrder::Rgb, 0.1);
+        let bgr = subpixel_mask(&path, FillRule::Winding, SubpixelOrder::Bgr, 0.1);
+
+        // Test
+        assert_eq!((rgb.x, rgb.y, rgb.width, rgb.height), (1, 1, 6, 2));
+        // Inside, every stripe is covered, as are the stripes the filter spreads them over
+        assert_eq!(rgb.get(3, 1), [255, 255, 255]);
+        // On the edges coverage fades towards the outside, and fringes into the next pixel
+        let [red, green, blue] = rgb.get(2, 1);
+        assert!(red < green && green < blue && blue < 255);
+        let [red, green, blue] = rgb.get(5, 2);
+        assert!(red > green && green > blue && blue > 0);
+        let [red, green, blue] = rgb.get(1, 1);
+        assert!(red == 0 && green == 0 && blue > 0);
+        assert_eq!(rgb.get(2, 0), [0, 0, 0]);
+        // BGR panels have the stripes the other way round
+        let [red, green, blue] = rgb.get(2, 1);
+        assert_eq!(bgr.get(2, 1), [blue, green, red]);
+    }
+
+    #[test]
+    fn subpixel_mask_of_nothing() {
+        let mask = subpixel_mask(&Path::new(), FillRule::Winding, SubpixelOrder::Rgb, 0.1);
+        assert_eq!(mask.width * mask.height, 0);
+    }
+}
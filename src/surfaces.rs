@@ -40,18 +40,50 @@
 //! Cairo surfaces are basically raster (bitmap) containers.  They 'receive' operations performed
 //! on them by contexts.  They are the 'canvas' of Cairus.
 
+use std::error::Error;
+use std::f32::consts::SQRT_2;
+use std::fmt;
+use std::fs::File;
+use std::io::Write;
+use std::ops::{Deref, DerefMut, Range};
+#[cfg(feature = "jpeg")]
+use std::io::Read;
 use std::path::Path;
-use std::slice::{IterMut, Iter};
+use std::slice::{ChunksExact, ChunksExactMut, IterMut, Iter};
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
 use std::vec::IntoIter;
-use types::Rgba;
+use types::{Antialias, Extend, FillRule, Filter, LineCap, LineJoin, Rasterizer, Rgba};
+use common_geometry::{Edge, Ellipse, LineSegment, Point, Rectangle, RectangleInt};
+use context::{Clip, DEFAULT_TOLERANCE};
+use coverage::{RowCoverage, ThickSegment, wu_line};
+use dash::DashPattern;
+use matrix::Matrix;
+use operators::{Operator, fetch_operator, composite_span, composite_span_with_coverage,
+                composite_unbounded, composite_with_coverage};
+use path;
+use pattern::{self, Pattern, SolidPattern, SurfacePattern};
+use spans::{rasterize_edges_with_color, rasterize_edges_with_source};
+use srgb;
+use status::Status;
+use stroke::{StrokeStyle, stroke_edges_with_dash};
+use trapezoid_rasterizer::{Trapezoid, rasterize_trapezoids_in_tiles};
+use bo_trap;
+use predicates::Precision;
 extern crate image;
+#[cfg(feature = "jpeg")]
+extern crate jpeg_decoder;
 
 
 
 ///Format enum descriptors for the surface object
 ///These are specifically the format types copied from the C implementation,
 ///analogous to cairo_format_t
+///
+/// ImageSurface composites in floating point whatever its format; the format decides which
+/// channels each pixel keeps.  ARGB32, RGB24, A8 and A1 surfaces can be created, the others are
+/// listed for completeness.
 #[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Format {
     Invalid,
     ARGB32,
@@ -62,6 +94,52 @@ pub enum Format {
     RGB30,
 }
 
+impl Format {
+    /// Returns true if ImageSurfaces can be created in this format.
+    pub fn is_supported(&self) -> bool {
+        match *self {
+            Format::ARGB32 | Format::RGB24 | Format::A8 | Format::A1 => true,
+            Format::Invalid | Format::RGB16_565 | Format::RGB30 => false,
+        }
+    }
+
+    /// Analogous to cairo_format_stride_for_width(), returns the number of bytes a row of `width`
+    /// pixels takes in this format, padded to a multiple of four bytes.  Returns None for
+    /// `Format::Invalid`, or a width too large for the row to be counted, as cairo returns -1.
+    pub fn stride_for_width(&self, width: usize) -> Option<usize> {
+        let bits_per_pixel = match *self {
+            Format::Invalid => return None,
+            Format::ARGB32 | Format::RGB24 | Format::RGB30 => 32,
+            Format::RGB16_565 => 16,
+            Format::A8 => 8,
+            Format::A1 => 1,
+        };
+        let bytes = width.checked_mul(bits_per_pixel)?.div_ceil(8);
+        bytes.div_ceil(4).checked_mul(4)
+    }
+
+    /// Returns `pixel` reduced to the channels this format stores.
+    ///
+    /// * ARGB32 - Keeps every channel.
+    /// * RGB24 - Keeps the colors and makes the pixel opaque, as cairo ignores the alpha byte.
+    /// * A8 - Keeps only the alpha.
+    /// * A1 - Keeps only the alpha, rounded to fully opaque or fully transparent.
+    ///
+    /// # Usage
+    /// let mask_pixel = Format::A8.convert(&pixel);
+    pub fn convert(&self, pixel: &Rgba) -> Rgba {
+        match *self {
+            Format::RGB24 => Rgba { alpha: 1., ..*pixel },
+            Format::A8 => Rgba { red: 0., green: 0., blue: 0., alpha: pixel.alpha },
+            Format::A1 => {
+                let alpha = if pixel.alpha >= 0.5 { 1. } else { 0. };
+                Rgba { red: 0., green: 0., blue: 0., alpha }
+            },
+            _ => *pixel,
+        }
+    }
+}
+
 /// Analogous to cairo_surface_type_t, indicates target drawing type
 pub enum Type {
     Image,
@@ -91,15 +169,159 @@ pub enum Type {
     Cogl,
 }
 
+/// The color space that a surface's pixel values are expressed in.
+///
+/// Cairus does not convert between color spaces; the tag only describes how the stored values
+/// should be interpreted.  Image export writes the matching PNG color chunk so color-managed
+/// viewers display the output consistently.  Blending sRGB colors in linear light is a separate
+/// option, see `ImageSurface::set_linear_blending`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColorSpace {
+    /// The default.  Written as an `sRGB` chunk.
+    Srgb,
+    /// Display P3 primaries with the sRGB transfer function.  Written as a `cICP` chunk.
+    DisplayP3,
+    /// sRGB (BT.709) primaries with a linear transfer function.  Written as a `cICP` chunk.
+    LinearSrgb,
+}
+
+impl ColorSpace {
+    // Returns the PNG chunk type and data that tags an image with this color space.
+    //
+    // cICP data is (colour primaries, transfer function, matrix coefficients, full range flag)
+    // using the code points from ITU-T H.273.
+    fn png_chunk(&self) -> (&'static [u8; 4], Vec<u8>) {
+        match *self {
+            // Rendering intent 0: perceptual
+            ColorSpace::Srgb => (b"sRGB", vec![0]),
+            // Primaries 12: SMPTE EG 432-1 (P3-D65), transfer 13: sRGB
+            ColorSpace::DisplayP3 => (b"cICP", vec![12, 13, 0, 1]),
+            // Primaries 1: BT.709, transfer 8: linear
+            ColorSpace::LinearSrgb => (b"cICP", vec![1, 8, 0, 1]),
+        }
+    }
+}
+
+/// The order that color channels are laid out in when exporting pixels.
+///
+/// Window systems and GPU APIs disagree on byte order, and mixing them up silently swaps red and
+/// blue, so export never assumes one.  For byte exports the order is the order of the bytes in
+/// memory.  For packed `u32` exports it runs from the most to the least significant byte, so
+/// `Argb` packs like cairo's CAIRO_FORMAT_ARGB32; on a little-endian machine those words are
+/// stored as B, G, R, A bytes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChannelOrder {
+    Rgba,
+    Bgra,
+    Argb,
+}
+
+impl ChannelOrder {
+    // Rearranges red, green, blue and alpha bytes into this order.
+    fn arrange(&self, rgba: &[u8]) -> [u8; 4] {
+        let (red, green, blue, alpha) = (rgba[0], rgba[1], rgba[2], rgba[3]);
+        match *self {
+            ChannelOrder::Rgba => [red, green, blue, alpha],
+            ChannelOrder::Bgra => [blue, green, red, alpha],
+            ChannelOrder::Argb => [alpha, red, green, blue],
+        }
+    }
+}
+
+/// The number of bits each channel is written with when a surface is saved.
+///
+/// Surfaces keep each channel as a float, so `Eight` throws precision away.  `Sixteen` keeps
+/// gradients and low-alpha values for scientific or HDR-ish workflows, but only PNG can hold it.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum BitDepth {
+    #[default]
+    Eight,
+    Sixteen,
+}
+
+/// The error returned when a pixel coordinate lies outside an ImageSurface.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OutOfBounds {
+    /// The requested x coordinate.
+    pub x: usize,
+    /// The requested y coordinate.
+    pub y: usize,
+    /// Width of the surface.
+    pub width: usize,
+    /// Height of the surface.
+    pub height: usize,
+}
+
+impl fmt::Display for OutOfBounds {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "pixel ({}, {}) is outside the {}x{} surface", self.x, self.y, self.width,
+               self.height)
+    }
+}
+
+impl Error for OutOfBounds {}
+
+/// The error returned by `ImageSurface::create_for_data` for a buffer that can't hold an image.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InvalidData {
+    /// What is wrong with the buffer.
+    pub reason: &'static str,
+}
+
+impl fmt::Display for InvalidData {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid image data: {}", self.reason)
+    }
+}
+
+impl Error for InvalidData {}
+
+/// The error returned when a surface can't be created or written out.
+///
+/// Analogous to a surface in an error status in cairo: `status` is the status cairo would have
+/// put it in, and `reason` says what went wrong.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SurfaceError {
+    /// The cairo status for the failure.
+    pub status: Status,
+    /// What went wrong.
+    pub reason: &'static str,
+}
+
+impl SurfaceError {
+    fn new(status: Status, reason: &'static str) -> SurfaceError {
+        SurfaceError { status, reason }
+    }
+}
+
+impl fmt::Display for SurfaceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.status, self.reason)
+    }
+}
+
+impl Error for SurfaceError {}
+
 /// A surface needs to hold pixels (Rgba's) and its width and height. The width and height
 /// will be used in rendering to images and calculating clipping, and the pixels will be the things
 /// that actually are operated on by stroke or paint operations.  See the
 /// `test_image_surface_with_operator` test case below for an example of what that might look like.
+#[derive(Clone)]
 pub struct ImageSurface {
     // base is just a collection of pixels
     base: Vec<Rgba>,
     pub width: usize,
     pub height: usize,
+    format: Format,
+    // Bytes per row of `data`
+    stride: usize,
+    // The pixels packed in `format`, see `data()`.  Empty until first asked for.
+    data: Vec<u8>,
+    color_space: ColorSpace,
+    // Whether `base` holds linear light, see set_linear_blending()
+    linear_blending: bool,
+    // The pages finished by show_page() and copy_page(), oldest first
+    pages: Vec<PackedImageSurface>,
 }
 
 /// ImageSurface provides iter(), into_iter(), and iter_mut() so that when a Cairus context calls
@@ -107,303 +329,3355 @@ pub struct ImageSurface {
 /// compositing operator to operate on them.  See `operators.rs` for those operations.
 /// A valid ImageSurface needs tangible height and width dimensions to be successfully created.
 impl ImageSurface {
-    // Analogous to cairo_create(), you pass in a width and height and get in a surface in exchange.
+    /// Returns a `width` by `height` ARGB32 surface, as `create_for_format` does.
+    ///
+    /// # Panics
+    /// If either dimension is zero, or the pixels can't be allocated.
+    #[deprecated(note = "panics on a bad size; use try_create_for_format(Format::ARGB32, ..), \
+                         or create_for_format(Format::ARGB32, ..) to panic")]
     pub fn create(width: usize, height: usize) -> ImageSurface {
-        if width <= 0 || height <=0 {
-            panic!("error: ImageSurface dimensions are not supported.")
-        }
-        else {
-            ImageSurface {
-                base: vec![Rgba::new(0., 0., 0., 0.); width * height],
-                width: width,
-                height: height,
-            }
+        ImageSurface::create_for_format(Format::ARGB32, width, height)
+    }
+
+    /// Analogous to cairo_image_surface_create(), returns a `width` by `height` surface whose
+    /// pixels are stored in `format`.
+    ///
+    /// New surfaces are cleared to zero like cairo's, which leaves an RGB24 surface opaque black
+    /// and every other format transparent.
+    ///
+    /// # Usage
+    /// let mask = ImageSurface::create_for_format(Format::A8, 100, 100);
+    ///
+    /// # Panics
+    /// If either dimension is zero, or `format` is not supported (see `Format::is_supported`), or
+    /// the pixels can't be allocated.  Use `try_create_for_format` to handle these instead.
+    pub fn create_for_format(format: Format, width: usize, height: usize) -> ImageSurface {
+        match ImageSurface::try_create_for_format(format, width, height) {
+            Ok(surface) => surface,
+            Err(error) => panic!("error: ImageSurface can't be created: {}", error),
         }
     }
 
-    pub fn iter(&self) -> Iter<Rgba> {
-        self.base.iter()
+    /// Returns a `width` by `height` surface whose pixels are stored in `format`, as
+    /// `create_for_format` does, or an error rather than a panic if it can't be created.
+    ///
+    /// The error's status is Status::InvalidSize for a dimension of zero or a surface too big to
+    /// address, Status::InvalidFormat for an unsupported format, and Status::NoMemory when the
+    /// pixels can't be allocated.
+    ///
+    /// # Usage
+    /// let surface = ImageSurface::try_create_for_format(Format::ARGB32, width, height)?;
+    pub fn try_create_for_format(format: Format, width: usize, height: usize)
+                                 -> Result<ImageSurface, SurfaceError> {
+        let (pixels, stride) = ImageSurface::layout(format, width, height)?;
+        let mut base = Vec::new();
+        if base.try_reserve_exact(pixels).is_err() {
+            return Err(SurfaceError::new(Status::NoMemory, "pixels can't be allocated"));
+        }
+        base.resize(pixels, format.convert(&Rgba::new(0., 0., 0., 0.)));
+        Ok(ImageSurface::from_parts(base, format, width, height, stride))
     }
 
-    pub fn iter_mut(&mut self) -> IterMut<Rgba> {
-        self.base.iter_mut()
+    // Returns the number of pixels and the stride of a `width` by `height` surface in `format`,
+    // or the error for a surface that can't be created.
+    fn layout(format: Format, width: usize, height: usize)
+              -> Result<(usize, usize), SurfaceError> {
+        if width == 0 || height == 0 {
+            return Err(SurfaceError::new(Status::InvalidSize, "a dimension is zero"));
+        }
+        if !format.is_supported() {
+            return Err(SurfaceError::new(Status::InvalidFormat, "format is not supported"));
+        }
+        match (width.checked_mul(height), format.stride_for_width(width)) {
+            (Some(pixels), Some(stride)) if stride.checked_mul(height).is_some() => {
+                Ok((pixels, stride))
+            },
+            _ => Err(SurfaceError::new(Status::InvalidSize, "surface is too big")),
+        }
     }
 
-    fn into_bytes(& self) -> Vec<u8> {
-        let mut bytes = Vec::new();
-        for pixel in self.base.iter() {
-            for byte in pixel.into_bytes() {
-              bytes.push(byte);
-            }
+    // Returns a surface around `base`, which holds `width * height` pixels
+    fn from_parts(base: Vec<Rgba>, format: Format, width: usize, height: usize, stride: usize)
+                  -> ImageSurface {
+        ImageSurface {
+            base,
+            width,
+            height,
+            format,
+            stride,
+            data: Vec::new(),
+            color_space: ColorSpace::Srgb,
+            linear_blending: false,
+            pages: Vec::new(),
         }
-        bytes
     }
-    ///The external Rust Image Crate is a library under development to read,
-    /// manipulate and write images. At the moment "image" supports reading and writing
-    /// JPG and PNG images. The below functions, to_file(), to_png, and to_jpg use this external
-    /// library to write output image files, provided a valid Cairus ImageSurface.
-    pub fn to_file(&self, path: &Path){
-        let path_extension = path.extension().unwrap();
-        let extension = (String::from(path_extension.to_str().unwrap())).to_lowercase();
-       // let () = extension2;
-        if extension == "png"  {
-            self.to_png(path)
+
+    /// Analogous to cairo_image_surface_create_for_data(), returns a surface holding the image in
+    /// `data`, laid out as described in `data()`.
+    ///
+    /// The buffer becomes the surface's byte buffer, so it isn't copied again by `data()`.
+    ///
+    /// # Arguments
+    /// * `data` - The packed pixels, at least `stride * height` bytes long.
+    /// * `format` - The format the pixels are packed in.
+    /// * `width` - Width of the image in pixels.
+    /// * `height` - Height of the image in pixels.
+    /// * `stride` - Bytes from the start of one row to the start of the next.  A multiple of four
+    ///   that is at least `format.stride_for_width(width)`.
+    ///
+    /// # Usage
+    /// let surface = ImageSurface::create_for_data(frame, Format::ARGB32, 640, 480, 2560)?;
+    pub fn create_for_data(data: Vec<u8>, format: Format, width: usize, height: usize,
+                           stride: usize) -> Result<ImageSurface, InvalidData> {
+        if !format.is_supported() {
+            return Err(InvalidData { reason: "format is not supported" });
         }
-        else if extension == "jpg" {
-            self.to_jpg(path);
+        if width == 0 || height == 0 {
+            return Err(InvalidData { reason: "dimensions are not supported" });
         }
-        else {
-            panic!("error: {:?} output not supported", path_extension);
+        let row_bytes = match format.stride_for_width(width) {
+            Some(row_bytes) => row_bytes,
+            None => return Err(InvalidData { reason: "dimensions are not supported" }),
+        };
+        if !stride.is_multiple_of(4) || stride < row_bytes {
+            return Err(InvalidData { reason: "stride is too small or not a multiple of four" });
+        }
+        if data.len() < stride * height {
+            return Err(InvalidData { reason: "buffer is smaller than stride * height" });
         }
-    }
 
-    fn to_png(&self, path: &Path) {
-        let buffer = self.into_bytes();
-        let _ = image::save_buffer(path, buffer.as_slice(), self.width as u32,
-                                           self.height as u32, image::RGBA(8)).unwrap();
+        let mut surface = ImageSurface::create_for_format(format, width, height);
+        surface.stride = stride;
+        surface.data = data;
+        surface.unpack_data();
+        Ok(surface)
     }
 
-    fn to_jpg(&self, path: &Path) {
-        let buffer = self.into_bytes();
-        let _ = image::save_buffer(path, buffer.as_slice(), self.width as u32,
-                                           self.height as u32, image::RGBA(8)).unwrap();
+    /// Returns an ARGB32 surface holding `pixels`, row by row from the top left, or an error if
+    /// there isn't exactly one pixel for each of the `width * height`.
+    ///
+    /// The pixels are premultiplied colors, as `get_pixel()` and `into_iter()` return them, so a
+    /// surface can go through a map and collect and come back:
+    ///
+    /// # Usage
+    /// let (width, height) = surface.dimensions();
+    /// let pixels = surface.into_iter().map(|pixel| pixel.interpolate(&white, 0.5)).collect();
+    /// let faded = ImageSurface::from_vec(pixels, width, height)?;
+    pub fn from_vec(pixels: Vec<Rgba>, width: usize, height: usize)
+                    -> Result<ImageSurface, SurfaceError> {
+        let (count, stride) = ImageSurface::layout(Format::ARGB32, width, height)?;
+        if pixels.len() != count {
+            return Err(SurfaceError::new(Status::InvalidSize,
+                                         "pixel count is not width * height"));
+        }
+        Ok(ImageSurface::from_parts(pixels, Format::ARGB32, width, height, stride))
     }
 
-    pub fn get(&self, x: usize, y: usize) -> Option<&Rgba> {
-        let position = ImageSurface::calculate_position(self.width, x, y);
-        self.base.get(position)
+    /// Returns an ARGB32 surface holding the pixels `pixels` yields, as `from_vec` does.
+    ///
+    /// An iterator can't be collected straight into a surface, since FromIterator has no way to
+    /// be told the dimensions, so they are given here instead.
+    ///
+    /// # Usage
+    /// let inverted = ImageSurface::from_pixels(surface.iter().map(invert), width, height)?;
+    pub fn from_pixels<I: IntoIterator<Item = Rgba>>(pixels: I, width: usize, height: usize)
+                                                     -> Result<ImageSurface, SurfaceError> {
+        let mut collected = Vec::with_capacity(width.saturating_mul(height));
+        // Stops one past the expected count, which is enough for from_vec to reject it
+        collected.extend(pixels.into_iter().take(width.saturating_mul(height).saturating_add(1)));
+        ImageSurface::from_vec(collected, width, height)
     }
 
-    pub fn get_mut(&mut self, x: usize, y: usize) -> Option<&mut Rgba> {
-        let position = ImageSurface::calculate_position(self.width, x, y);
-        self.base.get_mut(position)
+    /// Returns the number of bytes from the start of one row of `data()` to the start of the next.
+    pub fn get_stride(&self) -> usize {
+        self.stride
     }
 
-    pub fn get_with_index(&self, idx: usize) -> Option<&Rgba> {
-        self.base.get(idx)
+    /// Returns the pixels packed into bytes in the surface's format, like
+    /// cairo_image_surface_get_data().
+    ///
+    /// The image is `height` rows, each starting `get_stride()` bytes after the previous one.
+    /// Pixels are premultiplied, with each channel scaled to 0-255, and packed as cairo packs
+    /// them:
+    /// * ARGB32 - A 32-bit native-endian word per pixel, alpha in the upper 8 bits, then red,
+    ///   green and blue.  Little-endian machines store this as B, G, R, A bytes.
+    /// * RGB24 - As ARGB32, with the upper 8 bits set to 255.
+    /// * A8 - One alpha byte per pixel.
+    /// * A1 - One bit per pixel, packed into 32-bit words.  On little-endian machines the first
+    ///   pixel is the least significant bit, on big-endian machines the most significant.
+    ///
+    /// Any padding at the end of a row is left untouched.
+    ///
+    /// Packing takes a pass over the surface, so keep the slice rather than calling this per
+    /// pixel.
+    pub fn data(&mut self) -> &[u8] {
+        self.pack_data();
+        &self.data
     }
 
-    pub fn get_mut_with_index(&mut self, idx: usize) -> Option<&mut Rgba> {
-        self.base.get_mut(idx)
+    /// Returns the surface's bytes, laid out as described in `data()`, for writing.
+    ///
+    /// Changes are unpacked back into the surface when the returned guard is dropped, which does
+    /// what calling cairo_surface_mark_dirty() after writing does in cairo.
+    ///
+    /// # Usage
+    /// surface.data_mut()[..4].copy_from_slice(&[0, 0, 255, 255]);
+    pub fn data_mut(&mut self) -> ImageData<'_> {
+        self.pack_data();
+        ImageData { surface: self }
     }
 
-    fn calculate_position(width: usize, x: usize, y: usize) -> usize {
-        y.wrapping_mul(width).wrapping_add(x)
+    // Packs the pixels into `data` and returns where they start, for the C API to hand out.  The
+    // bytes stay put until the surface is dropped, and writes through the pointer are only seen
+    // once mark_dirty() is called.
+    pub(crate) fn data_ptr(&mut self) -> *mut u8 {
+        self.pack_data();
+        self.data.as_mut_ptr()
     }
-}
-
-impl IntoIterator for ImageSurface {
-    type Item = Rgba;
-    type IntoIter = IntoIter<Rgba>;
 
-    fn into_iter(self) -> Self::IntoIter {
-        self.base.into_iter()
+    // Reads back the bytes written through data_ptr(), like cairo_surface_mark_dirty().
+    pub(crate) fn mark_dirty(&mut self) {
+        self.unpack_data();
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use types::Rgba;
-    use surfaces::ImageSurface;
-    use operators::{Operator, fetch_operator};
-    use surfaces::image::GenericImage;
+    // Packs the pixels into `data`, allocating it on first use.
+    fn pack_data(&mut self) {
+        let length = self.stride * self.height;
+        if self.data.len() < length {
+            self.data.resize(length, 0);
+        }
+        let (format, linear) = (self.format, self.linear_blending);
+        for (row, pixels) in self.data.chunks_mut(self.stride).zip(self.base.chunks(self.width)) {
+            for (x, pixel) in pixels.iter().enumerate() {
+                if linear {
+                    ImageSurface::pack_linear_pixel(format, row, x, pixel);
+                } else {
+                    ImageSurface::pack_pixel(format, row, x, pixel);
+                }
+            }
+        }
+    }
 
-    use std::fs;
-    use std::path::Path;
-    extern crate image;
+    // Replaces the pixels with the ones packed in `data`.
+    fn unpack_data(&mut self) {
+        let (format, linear) = (self.format, self.linear_blending);
+        let width = self.width;
+        for (row, pixels) in self.data.chunks(self.stride).zip(self.base.chunks_mut(width)) {
+            for (x, pixel) in pixels.iter_mut().enumerate() {
+                *pixel = if linear {
+                    ImageSurface::unpack_linear_pixel(format, row, x)
+                } else {
+                    ImageSurface::unpack_pixel(format, row, x)
+                };
+            }
+        }
+    }
 
+    // Packs `pixel` into pixel `x` of `row`, a row of bytes laid out as described in `data()`.
+    fn pack_pixel(format: Format, row: &mut [u8], x: usize, pixel: &Rgba) {
+        let pixel = format.convert(pixel);
+        let byte = |channel: f32| (channel.clamp(0., 1.) * 255.).round() as u8;
+        match format {
+            Format::A8 => row[x] = byte(pixel.alpha),
+            Format::A1 => {
+                let (index, mask) = ImageSurface::a1_bit(x);
+                if pixel.alpha > 0. {
+                    row[index] |= mask;
+                } else {
+                    row[index] &= !mask;
+                }
+            },
+            _ => {
+                let word = u32::from_be_bytes([byte(pixel.alpha), byte(pixel.red),
+                                               byte(pixel.green), byte(pixel.blue)]);
+                row[x * 4..x * 4 + 4].copy_from_slice(&word.to_ne_bytes());
+            },
+        }
+    }
 
-    #[test]
-    fn test_image_surface_create() {
-        // Test that ImageSurface's IntoIterator is functioning correctly by comparing every pixel
-        // in the surface to the default (which is transparent).
-        let transparent_pixel = Rgba::new(0., 0., 0., 0.);
-        let surface = ImageSurface::create(100, 100);
-        for pixel in surface {
-            assert_eq!(pixel, transparent_pixel);
+    // Returns pixel `x` of `row`, a row of bytes laid out as described in `data()`.
+    fn unpack_pixel(format: Format, row: &[u8], x: usize) -> Rgba {
+        let channel = |byte: u8| byte as f32 / 255.;
+        match format {
+            Format::A8 => Rgba { red: 0., green: 0., blue: 0., alpha: channel(row[x]) },
+            Format::A1 => {
+                let (index, mask) = ImageSurface::a1_bit(x);
+                let alpha = if row[index] & mask != 0 { 1. } else { 0. };
+                Rgba { red: 0., green: 0., blue: 0., alpha }
+            },
+            _ => {
+                let [alpha, red, green, blue] = ImageSurface::word(row, x).to_be_bytes();
+                format.convert(&Rgba {
+                    red: channel(red),
+                    green: channel(green),
+                    blue: channel(blue),
+                    alpha: channel(alpha),
+                })
+            },
         }
     }
 
-    #[test]
-    fn test_image_surface_into_iter() {
-        // Test that the explicit into_iter() call functions correctly.
-        let transparent_pixel = Rgba::new(0., 0., 0., 0.);
-        let surface = ImageSurface::create(100, 100);
-        for pixel in surface.into_iter() {
-            assert_eq!(pixel, transparent_pixel);
+    // Packs the linear light `pixel` into pixel `x` of `row`, encoded as sRGB.
+    //
+    // The colors are un-premultiplied in linear light, encoded through the lookup table and
+    // premultiplied again as bytes, which is how they'd be premultiplied had they been drawn in
+    // sRGB.  Alpha-only formats have nothing to encode.
+    fn pack_linear_pixel(format: Format, row: &mut [u8], x: usize, pixel: &Rgba) {
+        match format {
+            Format::ARGB32 | Format::RGB24 => {
+                let [red, green, blue, alpha] = srgb::linear_to_bytes(&format.convert(pixel));
+                let premultiply = |byte: u8| ((byte as u32 * alpha as u32 + 127) / 255) as u8;
+                let word = u32::from_be_bytes([alpha, premultiply(red), premultiply(green),
+                                               premultiply(blue)]);
+                row[x * 4..x * 4 + 4].copy_from_slice(&word.to_ne_bytes());
+            },
+            _ => ImageSurface::pack_pixel(format, row, x, pixel),
         }
     }
 
-    // TODO: test into_iter().map()
+    // Returns pixel `x` of `row`, decoded from sRGB to linear light.  The reverse of
+    // `pack_linear_pixel`.
+    fn unpack_linear_pixel(format: Format, row: &[u8], x: usize) -> Rgba {
+        match format {
+            Format::ARGB32 | Format::RGB24 => {
+                let [alpha, red, green, blue] = ImageSurface::word(row, x).to_be_bytes();
+                let alpha = if format == Format::RGB24 { 255 } else { alpha };
+                if alpha == 0 {
+                    return Rgba::new(0., 0., 0., 0.);
+                }
+                let unpremultiply = |byte: u8| {
+                    ((byte as u32 * 255 + alpha as u32 / 2) / alpha as u32).min(255) as u8
+                };
+                srgb::linear_from_bytes([unpremultiply(red), unpremultiply(green),
+                                         unpremultiply(blue), alpha])
+            },
+            _ => ImageSurface::unpack_pixel(format, row, x),
+        }
+    }
 
-    #[test]
-    fn test_image_surface_iter() {
-        // Passes if ImageSurface::iter() functions properly
-        let surface = ImageSurface::create(100, 100);
+    // Returns the 32-bit word of pixel `x` of a row of ARGB32 or RGB24 bytes.
+    fn word(row: &[u8], x: usize) -> u32 {
+        let mut word = [0; 4];
+        word.copy_from_slice(&row[x * 4..x * 4 + 4]);
+        u32::from_ne_bytes(word)
+    }
 
-        // Leave pixel.red to default (0.0), change all other channels to 1.0
-        let result = surface.iter()
-            .map(|&pixel| {
-                Rgba {
-                    red: pixel.red,
-                    green: 1.,
-                    blue: 1.,
-                    alpha: 1.,
-                }
-            })
-            .collect::<Vec<Rgba>>();
+    /// Finishes the current page, keeping a packed copy of the surface as the next of its pages,
+    /// and clears the surface for the page after.
+    ///
+    /// Analogous to cairo_surface_show_page().  An image surface keeps its finished pages as the
+    /// frames of a sequence, which `pages()` returns and `to_png_sequence()` writes out.
+    pub fn show_page(&mut self) {
+        self.copy_page();
+        let clear = self.format.convert(&Rgba::new(0., 0., 0., 0.));
+        self.base.fill(clear);
+    }
 
-        let expected = Rgba {
-            red: 0.,
-            green: 1.,
-            blue: 1.,
-            alpha: 1.,
+    /// Finishes the current page as `show_page()` does, but leaves the surface as it is, so the
+    /// next page starts from a copy of this one.  Analogous to cairo_surface_copy_page().
+    pub fn copy_page(&mut self) {
+        self.pack_data();
+        let page = PackedImageSurface {
+            data: self.data.clone(),
+            width: self.width,
+            height: self.height,
+            format: self.format,
+            stride: self.stride,
+            color_space: self.color_space,
+            linear_blending: self.linear_blending,
         };
+        self.pages.push(page);
+    }
 
-        for pixel in result.into_iter() {
-            // Red is 0. because it is the default, the others got set to 1.
-            assert_eq!(pixel, expected);
-        }
+    /// Returns the pages finished by `show_page()` and `copy_page()`, oldest first.
+    pub fn pages(&self) -> &[PackedImageSurface] {
+        &self.pages
     }
 
-    #[test]
-    fn test_image_surface_iter_mut() {
-        // Passes if ImageSurface::iter_mut() functions properly
-        let mut surface = ImageSurface::create(100, 100);
-        let expected = Rgba::new(1., 0., 0., 1.);
+    /// Removes the finished pages from the surface and returns them, oldest first.
+    pub fn take_pages(&mut self) -> Vec<PackedImageSurface> {
+        ::std::mem::take(&mut self.pages)
+    }
 
-        for mut pixel in surface.iter_mut() {
-            pixel.alpha = expected.alpha;
-            pixel.red = expected.red;
+    /// Writes each finished page to a PNG named after `path` with its page number added, so
+    /// `frames/frame.png` becomes `frames/frame-1.png`, `frames/frame-2.png` and so on.
+    ///
+    /// Stops at the first page that can't be written, returning the error for it.
+    pub fn to_png_sequence(&self, path: &Path) -> Result<(), SurfaceError> {
+        let stem = path.file_stem().and_then(|stem| stem.to_str()).unwrap_or("page");
+        for (index, page) in self.pages.iter().enumerate() {
+            let name = format!("{}-{}.png", stem, index + 1);
+            page.clone().unpack().to_png(&path.with_file_name(name), BitDepth::Eight)?;
         }
+        Ok(())
+    }
 
-        for pixel in surface {
-            assert_eq!(pixel, expected);
+    /// Packs this surface into bytes, as laid out in `data()`, for keeping it in a quarter of the
+    /// memory.
+    ///
+    /// Drawing works on the f32 Rgba an ImageSurface keeps for every pixel, which for a 100x100
+    /// surface is 160KB.  Packed, an ARGB32 surface takes four bytes per pixel, 40KB, and an A8
+    /// surface one.  A packed surface can still be drawn on, a band of rows at a time, so
+    /// surfaces that are kept around, such as caches and masks, can stay packed.  Channels are
+    /// rounded to the nearest 1/255 on the way.
+    ///
+    /// # Usage
+    /// let packed = surface.pack();
+    pub fn pack(mut self) -> PackedImageSurface {
+        self.pack_data();
+        PackedImageSurface {
+            data: self.data,
+            width: self.width,
+            height: self.height,
+            format: self.format,
+            stride: self.stride,
+            color_space: self.color_space,
+            linear_blending: self.linear_blending,
         }
     }
 
-    #[test]
-    fn test_image_surface_with_operator() {
-        // Demonstrates usage with an operator
-        //
-        // Our goal here is to take a surface and paint it red.  We use the the surface's iter_mut
-        // function because operators modify the image's pixels in-place.
+    // Returns the byte index within a row, and the mask within that byte, of pixel `x` of an A1
+    // image.
+    fn a1_bit(x: usize) -> (usize, u8) {
+        if cfg!(target_endian = "little") {
+            (x / 8, 1 << (x % 8))
+        } else {
+            (x / 8, 0x80 >> (x % 8))
+        }
+    }
 
-        // Create our source Rgba, destination, and choose an operator
-        let source_rgba = Rgba::new(1., 0., 0., 1.);
-        let mut destination = ImageSurface::create(100, 100);
-        let op = Operator::Over;
+    /// Returns the format this surface's pixels are stored in.
+    pub fn get_format(&self) -> Format {
+        self.format
+    }
 
-        // Using fetch_operator and the Operator enum.
-        let operator = fetch_operator(&op);
-        for mut pixel in destination.iter_mut() {
-            operator(&source_rgba, pixel);
+    /// Returns a copy of this surface with its pixels converted to `format`.
+    ///
+    /// Converting to a format with fewer channels throws the others away, so converting back
+    /// doesn't restore them: an A8 surface converted to ARGB32 is black wherever it was opaque.
+    ///
+    /// # Usage
+    /// let mask = surface.convert(Format::A8);
+    ///
+    /// # Panics
+    /// If `format` is not supported.
+    pub fn convert(&self, format: Format) -> ImageSurface {
+        let mut converted = ImageSurface::create_for_format(format, self.width, self.height);
+        for (converted, pixel) in converted.base.iter_mut().zip(self.pixels()) {
+            *converted = format.convert(&pixel);
         }
+        converted.color_space = self.color_space;
+        converted.set_linear_blending(self.linear_blending);
+        converted
+    }
 
-        // Check that the resulting pixels in destination are red RGBA(1, 0, 0, 1)
-        let expected = Rgba::new(1., 0., 0., 1.);
-        for pixel in destination {
-            assert_eq!(pixel, expected);
-        }
+    // Returns the pixels as this surface's format stores them, encoded as sRGB.
+    //
+    // Drawing composites on full Rgba values whatever the format, so channels the format doesn't
+    // keep are dropped here, as the pixels are read out.
+    fn pixels(&self) -> impl Iterator<Item = Rgba> + '_ {
+        let (format, linear) = (self.format, self.linear_blending);
+        self.base.iter().map(move |pixel| {
+            let pixel = format.convert(pixel);
+            if linear { srgb::from_linear(&pixel) } else { pixel }
+        })
     }
 
-    #[test]
-    fn test_into_bytes() {
-        // verifies that into bytes returns the correct number of bytes and all bytes are correct
+    // Returns the pixels as un-premultiplied red, green, blue and alpha bytes, as
+    // `Rgba::into_bytes` lays them out.  Linear light pixels are encoded through the lookup
+    // table rather than with `pixels()`.
+    fn pixel_bytes(&self) -> impl Iterator<Item = [u8; 4]> + '_ {
+        let (format, linear) = (self.format, self.linear_blending);
+        self.base.iter().map(move |pixel| {
+            let pixel = format.convert(pixel);
+            if linear {
+                srgb::linear_to_bytes(&pixel)
+            } else {
+                let bytes = pixel.into_bytes();
+                [bytes[0], bytes[1], bytes[2], bytes[3]]
+            }
+        })
+    }
 
-        // Setup
-        let surface = ImageSurface::create(100, 100);
+    /// Tags this surface's pixels as being in `color_space`.
+    ///
+    /// This does not modify any pixel values, it only changes how they are described on export.
+    pub fn set_color_space(&mut self, color_space: ColorSpace) {
+        self.color_space = color_space;
+    }
 
-        // Call
-        let result = surface.into_bytes();
+    /// Returns the color space this surface's pixels are tagged with.
+    pub fn get_color_space(&self) -> ColorSpace {
+        self.color_space
+    }
 
-        // Test
-        assert_eq!(40000, result.len(), "Error: Byte count incorrect");
-        for byte in result {
-            assert_eq!(0, byte, "Error: Byte value incorrect");
+    /// Sets whether drawing on this surface blends in linear light.
+    ///
+    /// Colors are sRGB encoded, and by default they are blended as they are, like cairo blends
+    /// them.  That darkens the middle of blends: a 50% black over white comes out as a quarter
+    /// of the light rather than half, and antialiased edges look thin.  With linear blending,
+    /// the surface keeps its pixels in linear light, converts every source color to linear light
+    /// before compositing it, and encodes the pixels back to sRGB whenever they are written out
+    /// as bytes, by `data()`, `pack()`, `to_bytes()` and image export.  Turning it on or off
+    /// converts the pixels already drawn, so the image itself doesn't change.
+    ///
+    /// `get_pixel()` and `set_pixel()` stay in sRGB, while `get()`, `iter()` and the other
+    /// accessors that hand out references to pixels see them as stored, in linear light.
+    ///
+    /// # Usage
+    /// surface.set_linear_blending(true);
+    pub fn set_linear_blending(&mut self, linear: bool) {
+        if linear == self.linear_blending {
+            return;
+        }
+        let convert = if linear { srgb::to_linear } else { srgb::from_linear };
+        for pixel in self.base.iter_mut() {
+            *pixel = convert(pixel);
         }
+        self.linear_blending = linear;
     }
 
-    #[test]
-    fn test_to_file_output_correct_dimensions() {
-        // Writes image surface to file then verifies image in file has correct dimensions.
+    /// Returns whether drawing on this surface blends in linear light.
+    pub fn get_linear_blending(&self) -> bool {
+        self.linear_blending
+    }
 
-        //setup
-        let surface = ImageSurface::create(100, 100);
-        let path = Path::new("test2.png");
-        let expected_width = surface.width as u32;
-        let expected_height = surface.height as u32;
+    // Drops the channels this surface's format doesn't keep from every pixel of `rows`.
+    // Drawing composites full Rgba values whatever the format, and this is called on the rows it
+    // reached once it is done, so the pixels are stored as the format keeps them.
+    pub(crate) fn keep_format(&mut self, rows: Range<usize>) {
+        let format = self.format;
+        let end = rows.end.min(self.height);
+        let start = rows.start.min(end);
+        if format != Format::ARGB32 {
+            for pixel in &mut self.base[start * self.width..end * self.width] {
+                *pixel = format.convert(pixel);
+            }
+        }
+    }
 
-        //call
-        surface.to_file(path);
-        let img = image::open(path).unwrap();
-        let (result_width, result_height) = img.dimensions();
+    // Returns `color` in the space this surface blends in.
+    fn blend_color(&self, color: &Rgba) -> Rgba {
+        if self.linear_blending { srgb::to_linear(color) } else { *color }
+    }
 
-        //test
-        assert_eq!(result_width, expected_width, "Error: width was not as expected");
-        assert_eq!(result_height, expected_height, "Error: width was not as expected");
+    // Returns the composite's solid color, in the space this surface blends in.
+    fn blend_solid_color(&self, composite: &Composite) -> Option<Rgba> {
+        composite.solid_color().map(|color| self.blend_color(&color))
+    }
 
-        // Cleanup
-        let _ = fs::remove_file(path).unwrap();
+    // Returns the composite's sampler, giving colors in the space this surface blends in.
+    fn blend_sampler<'a>(&self, composite: &Composite<'a>)
+                         -> impl Fn(usize, usize) -> Rgba + Sync + 'a {
+        let (sampler, linear) = (composite.sampler(), self.linear_blending);
+        move |x, y| {
+            let color = sampler(x, y);
+            if linear { srgb::to_linear(&color) } else { color }
+        }
     }
 
-    #[test]
-    fn test_int_to_file_integrity_per_pixel() {
-        // Writes image surface to file then verifies file content is as expected
+    /// Returns the surface's pixels, row by row from the top left, as the surface holds them:
+    /// with only the channels its format keeps, and in linear light if it blends in linear
+    /// light.  get_pixel() gives a pixel encoded as sRGB.
+    pub fn iter(&self) -> Iter<'_, Rgba> {
+        self.base.iter()
+    }
 
-        // Setup
-        let surface = ImageSurface::create(100, 100);
-        let transparent_pixel = Rgba::new(0.,0.,0.,0.);
-        let path = Path::new("test3.png");
+    /// Returns the surface's pixels mutably, row by row from the top left.
+    ///
+    /// Pixels are written as they are given, so they should be colors the format can hold, as
+    /// `Format::convert` gives them.  Drawing drops whatever channels the format doesn't keep.
+    pub fn iter_mut(&mut self) -> IterMut<'_, Rgba> {
+        self.base.iter_mut()
+    }
 
-        // Call
-        surface.to_file(path);
-        let img = image::open(path).unwrap().to_rgba();
+    /// Returns the surface's rows from the top, each a slice of `width` pixels.  Like `iter()`,
+    /// the pixels are as the surface holds them.
+    ///
+    /// # Usage
+    /// for (y, row) in surface.rows().enumerate() { ... }
+    pub fn rows(&self) -> ChunksExact<'_, Rgba> {
+        self.base.chunks_exact(self.width)
+    }
 
-        // Test
-        for pixel in img.pixels() {
-            let (r,g,b,a) = (pixel.data[0], pixel.data[1], pixel.data[2], pixel.data[3]);
-            let result = Rgba::new(r as f32, g as f32, b as f32, a as f32);
-            assert_eq!(result,transparent_pixel, "Error: Image integrity failed");
-        }
-        // Cleanup
-        let _ = fs::remove_file(path).unwrap();
+    /// Returns the surface's rows from the top, each a mutable slice of `width` pixels.
+    ///
+    /// # Usage
+    /// for row in surface.rows_mut() { row.reverse(); }
+    pub fn rows_mut(&mut self) -> ChunksExactMut<'_, Rgba> {
+        self.base.chunks_exact_mut(self.width)
     }
 
-    #[test]
-    fn test_int_to_jpg_file_created() {
-        // Writes image surface to file and verifies file was created
+    // Returns a copy of the `height` rows from `top` down, as a surface of their own.
+    pub(crate) fn copy_rows(&self, top: usize, height: usize) -> ImageSurface {
+        let pixels = self.base[top * self.width..(top + height) * self.width].to_vec();
+        let mut rows = ImageSurface::from_parts(pixels, self.format, self.width, height,
+                                                self.stride);
+        rows.color_space = self.color_space;
+        rows.linear_blending = self.linear_blending;
+        rows
+    }
 
-        // Setup
-        let surface = ImageSurface::create(100, 100);
-        let path = Path::new("test3.jpg");
+    /// Returns a view of the `width` by `height` pixels with their top left corner at (`x`, `y`),
+    /// or an error naming the last pixel if the rectangle doesn't fit in the surface.  A
+    /// rectangle with no area gives an empty view, of dimensions (0, 0).
+    ///
+    /// # Usage
+    /// let tile = surface.sub_surface(64, 0, 64, 64)?;
+    pub fn sub_surface(&self, x: usize, y: usize, width: usize, height: usize)
+                       -> Result<SubSurface<'_>, OutOfBounds> {
+        let (pixels, width, height) = self.sub_surface_pixels(x, y, width, height)?;
+        Ok(SubSurface { pixels: &self.base[pixels], stride: self.width, width, height })
+    }
 
-        // Call
-        surface.to_file(path);
+    /// Returns a mutable view of the `width` by `height` pixels with their top left corner at
+    /// (`x`, `y`), or an error naming the last pixel if the rectangle doesn't fit in the surface.
+    ///
+    /// # Usage
+    /// let mut tile = surface.sub_surface_mut(64, 0, 64, 64)?;
+    pub fn sub_surface_mut(&mut self, x: usize, y: usize, width: usize, height: usize)
+                           -> Result<SubSurfaceMut<'_>, OutOfBounds> {
+        let (pixels, width, height) = self.sub_surface_pixels(x, y, width, height)?;
+        Ok(SubSurfaceMut { pixels: &mut self.base[pixels], stride: self.width, width, height })
+    }
 
-        // Test
-        assert!(Path::new(path).exists(), "Error: JPG file was not created");
+    // Returns the range of `base` from a sub-surface's top left pixel to its bottom right one,
+    // and its width and height, both 0 if it has no area, checking that the whole rectangle
+    // fits.
+    fn sub_surface_pixels(&self, x: usize, y: usize, width: usize, height: usize)
+                          -> Result<(Range<usize>, usize, usize), OutOfBounds> {
+        let right = x.checked_add(width).filter(|&right| right <= self.width);
+        let bottom = y.checked_add(height).filter(|&bottom| bottom <= self.height);
+        if right.is_none() || bottom.is_none() {
+            return Err(OutOfBounds {
+                x: x.saturating_add(width.max(1) - 1),
+                y: y.saturating_add(height.max(1) - 1),
+                width: self.width,
+                height: self.height,
+            });
+        }
+        if width == 0 || height == 0 {
+            return Ok((0..0, 0, 0));
+        }
+        let start = y * self.width + x;
+        Ok((start..start + (height - 1) * self.width + width, width, height))
+    }
 
-        // Cleanup
-        let _ = fs::remove_file(path).unwrap();
+    #[allow(clippy::wrong_self_convention)]
+    fn into_bytes(& self) -> Vec<u8> {
+        self.to_bytes(ChannelOrder::Rgba)
     }
 
-    #[test]
+    /// Returns the pixels as bytes, four per pixel in `order`, row by row from the top left.
+    ///
+    /// Like image export, colors are un-premultiplied and each channel is scaled to 0-255.
+    ///
+    /// # Arguments
+    /// * `&self` - Reference to the `ImageSurface` to export.
+    /// * `order` - The order of the four channel bytes within each pixel.
+    ///
+    /// # Usage
+    /// let bytes = surface.to_bytes(ChannelOrder::Bgra);
+    pub fn to_bytes(&self, order: ChannelOrder) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.base.len() * 4);
+        for pixel in self.pixel_bytes() {
+            bytes.extend_from_slice(&order.arrange(&pixel));
+        }
+        bytes
+    }
+
+    /// Returns the alpha channel as one byte per pixel, like cairo's CAIRO_FORMAT_A8.
+    ///
+    /// This is how coverage masks rendered with `Context::set_coverage_only` are handed to code
+    /// that expects an 8-bit mask.
+    pub fn to_alpha_bytes(&self) -> Vec<u8> {
+        self.pixels().map(|pixel| (pixel.alpha.clamp(0., 1.) * 255.).round() as u8).collect()
+    }
+
+    /// Returns the pixels packed into one `u32` each, with channels in `order` from the most to
+    /// the least significant byte.
+    ///
+    /// The words are native integers, so call `to_be()`/`to_le()` or `to_be_bytes()`/
+    /// `to_le_bytes()` on them to control the layout in memory.
+    pub fn to_u32(&self, order: ChannelOrder) -> Vec<u32> {
+        self.pixel_bytes()
+            .map(|pixel| u32::from_be_bytes(order.arrange(&pixel)))
+            .collect()
+    }
+
+    // Returns the pixels as 16-bit big-endian words, which is the sample layout PNG expects for
+    // 16 bits per channel.
+    #[allow(clippy::wrong_self_convention)]
+    fn into_words_be(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.base.len() * 8);
+        for pixel in self.pixels() {
+            for word in pixel.into_words() {
+                bytes.push((word >> 8) as u8);
+                bytes.push(word as u8);
+            }
+        }
+        bytes
+    }
+
+    ///The external Rust Image Crate is a library under development to read,
+    /// manipulate and write images. At the moment "image" supports reading and writing
+    /// JPG and PNG images. The below functions, to_file(), to_png, and to_jpg use this external
+    /// library to write output image files, provided a valid Cairus ImageSurface.
+    ///
+    /// The format is chosen by the extension of `path`: "png", or "jpg" or "jpeg" for JPEG.
+    /// Returns an error with Status::WriteError if the extension is none of these, or the file
+    /// can't be written.
+    pub fn to_file(&self, path: &Path) -> Result<(), SurfaceError> {
+        self.to_file_with_depth(path, BitDepth::Eight)
+    }
+
+    /// Like `to_file`, but writes each channel with `depth` bits.  Returns an error with
+    /// Status::WriteError for 16 bits to any format but PNG.
+    pub fn to_file_with_depth(&self, path: &Path, depth: BitDepth) -> Result<(), SurfaceError> {
+        let extension = path.extension()
+            .and_then(|extension| extension.to_str())
+            .map(|extension| extension.to_lowercase());
+        match (extension.as_deref(), depth) {
+            (Some("png"), _) => self.to_png(path, depth),
+            (Some("jpg"), BitDepth::Eight) | (Some("jpeg"), BitDepth::Eight) => self.to_jpg(path),
+            (Some("jpg"), BitDepth::Sixteen) | (Some("jpeg"), BitDepth::Sixteen) => {
+                Err(SurfaceError::new(Status::WriteError, "JPEG can't hold 16 bits per channel"))
+            },
+            _ => Err(SurfaceError::new(Status::WriteError, "output format is not supported")),
+        }
+    }
+
+    pub(crate) fn to_png(&self, path: &Path, depth: BitDepth) -> Result<(), SurfaceError> {
+        match depth {
+            BitDepth::Eight => self.write_png(path, &self.into_bytes(), image::RGBA(8)),
+            BitDepth::Sixteen => self.write_png(path, &self.into_words_be(), image::RGBA(16)),
+        }
+    }
+
+    // Encodes `buffer` as a PNG tagged with this surface's color space, and writes it to `path`.
+    //
+    // The image crate's encoder has no way to add ancillary chunks, so the color chunk is
+    // spliced in right after IHDR, which is where the PNG spec requires it to be.
+    fn write_png(&self, path: &Path, buffer: &[u8], color: image::ColorType)
+                 -> Result<(), SurfaceError> {
+        let mut encoded = Vec::new();
+        image::png::PNGEncoder::new(&mut encoded)
+            .encode(buffer, self.width as u32, self.height as u32, color)
+            .map_err(|_| SurfaceError::new(Status::WriteError, "image can't be encoded"))?;
+
+        // 8 byte signature, then IHDR: 4 byte length, 4 byte type, 13 bytes of data, 4 byte CRC
+        let ihdr_end = 8 + 4 + 4 + 13 + 4;
+        let (chunk_type, data) = self.color_space.png_chunk();
+        let mut file = File::create(path)
+            .map_err(|_| SurfaceError::new(Status::WriteError, "file can't be created"))?;
+        file.write_all(&encoded[..ihdr_end])
+            .and_then(|_| file.write_all(&png_chunk(chunk_type, &data)))
+            .and_then(|_| file.write_all(&encoded[ihdr_end..]))
+            .map_err(|_| SurfaceError::new(Status::WriteError, "file can't be written"))
+    }
+
+    fn to_jpg(&self, path: &Path) -> Result<(), SurfaceError> {
+        let buffer = self.into_bytes();
+        image::save_buffer(path, buffer.as_slice(), self.width as u32,
+                                           self.height as u32, image::RGBA(8))
+            .map_err(|_| SurfaceError::new(Status::WriteError, "file can't be written"))
+    }
+
+    /// Analogous to cairo_image_surface_create_from_png(), but for JPEG data.
+    ///
+    /// Decodes a JPEG stream from `reader` into a new ImageSurface.  Decoding is done by the
+    /// pure-Rust jpeg-decoder crate, so no system libraries are needed.  JPEGs carry no alpha
+    /// channel, so every pixel of the resulting surface is opaque.  Grayscale and CMYK images
+    /// are converted to RGB.
+    ///
+    /// Only available when Cairus is built with the `jpeg` feature.
+    #[cfg(feature = "jpeg")]
+    pub fn create_from_jpeg<R: Read>(reader: R) -> image::ImageResult<ImageSurface> {
+        use self::image::ImageError;
+        use self::jpeg_decoder::{Decoder, Error, PixelFormat};
+
+        let error = |error: Error| match error {
+            Error::Io(error) => ImageError::IoError(error),
+            Error::Unsupported(feature) => {
+                ImageError::UnsupportedError(format!("{:?}", feature))
+            },
+            other => ImageError::FormatError(other.to_string()),
+        };
+        let mut decoder = Decoder::new(reader);
+        let data = decoder.decode().map_err(error)?;
+        let info = decoder.info().ok_or(ImageError::NotEnoughData)?;
+        let (width, height) = (info.width as usize, info.height as usize);
+        if width == 0 || height == 0 {
+            return Err(ImageError::DimensionError);
+        }
+
+        let channels = match info.pixel_format {
+            PixelFormat::L8 => 1,
+            PixelFormat::RGB24 => 3,
+            PixelFormat::CMYK32 => 4,
+        };
+        let mut surface = ImageSurface::create_for_format(Format::ARGB32, width, height);
+        for (pixel, bytes) in surface.base.iter_mut().zip(data.chunks(channels)) {
+            let (red, green, blue) = match *bytes {
+                [gray] => (gray, gray, gray),
+                [red, green, blue] => (red, green, blue),
+                // Ink darkens the white of the page, and black darkens it further
+                [cyan, magenta, yellow, black] => {
+                    let ink = |ink: u8| {
+                        let (ink, black) = (ink as f32 / 255., black as f32 / 255.);
+                        ((1. - (ink * (1. - black) + black)) * 255.) as u8
+                    };
+                    (ink(cyan), ink(magenta), ink(yellow))
+                },
+                _ => return Err(ImageError::NotEnoughData),
+            };
+            *pixel = Rgba::from_bytes(&[red, green, blue, 255]);
+        }
+
+        Ok(surface)
+    }
+
+    /// Composites `color` onto every rectangle in `rectangles` with `operator`, in one pass.
+    ///
+    /// This is much faster than building and filling a path per rectangle, which matters for
+    /// things like selection highlights and heatmaps.  The rectangles are clipped to the surface,
+    /// sorted by their top edge, and swept row by row; each row's coverage is gathered into spans
+    /// and every covered pixel is composited exactly once.  Edges that don't fall on pixel
+    /// boundaries are antialiased by the fraction of the pixel they cover.  Where rectangles
+    /// overlap, the pixel takes the larger coverage, so the union of the rectangles is filled
+    /// rather than each rectangle being composited on top of the others.
+    pub fn fill_rectangles(&mut self, operator: &Operator, color: &Rgba,
+                           rectangles: &[Rectangle]) {
+        let rectangles = rectangles.iter()
+            .map(|rectangle| rectangle.normalized())
+            .filter(|rectangle| !rectangle.is_empty())
+            .collect();
+        self.composite_shapes(operator, color, rectangles);
+    }
+
+    /// Composites `color` onto every ellipse in `ellipses` with `operator`, in one pass.
+    ///
+    /// A direct scanline filler for whole circles and ellipses, which would otherwise be
+    /// flattened into dozens of segments and tessellated.  Each row's chord extents are computed
+    /// from the ellipse equation, pixels inside the chords are filled outright and the edge
+    /// pixels are antialiased by their covered fraction.  Overlapping ellipses fill their union,
+    /// as with `fill_rectangles`.
+    pub fn fill_ellipses(&mut self, operator: &Operator, color: &Rgba, ellipses: &[Ellipse]) {
+        let ellipses = ellipses.iter()
+            .filter(|ellipse| !ellipse.is_empty())
+            .cloned()
+            .collect();
+        self.composite_shapes(operator, color, ellipses);
+    }
+
+    /// Strokes every segment in `segments` at `line_width` with `color` and `operator`, in one
+    /// pass.
+    ///
+    /// Meant for large batches of independent segments, such as tick marks, scatter plots and
+    /// wireframes, where building, stroking and tessellating a path per segment would dominate.
+    /// Each segment is stroked with butt caps and no joins.  Coverage is box filtered per pixel,
+    /// and overlapping segments fill their union just like `fill_rectangles`.  Zero length
+    /// segments draw nothing.
+    ///
+    /// Hairlines, one unit wide and composited with `Operator::Over`, skip the sweep altogether
+    /// and are drawn directly with Xiaolin Wu's antialiased line algorithm.  Since Over is
+    /// associative, compositing each segment separately looks the same apart from where segments
+    /// cross, which is hard to see at that width.
+    pub fn stroke_segments(&mut self, operator: &Operator, color: &Rgba,
+                           segments: &[LineSegment], line_width: f32) {
+        if line_width == 1. && *operator == Operator::Over {
+            let color = self.blend_color(color);
+            for segment in segments {
+                self.draw_hairline(&color, segment);
+            }
+            return;
+        }
+
+        let segments = segments.iter()
+            .filter_map(|segment| ThickSegment::new(segment, line_width))
+            .collect();
+        self.composite_shapes(operator, color, segments);
+    }
+
+    // Composites a Wu line along `segment` with the over operator, clipped to the surface.
+    fn draw_hairline(&mut self, color: &Rgba, segment: &LineSegment) {
+        let over = fetch_operator(&Operator::Over);
+        let (width, height) = (self.width as i64, self.height as i64);
+        let base = &mut self.base;
+        wu_line(segment, |x, y, coverage| {
+            if x >= 0 && x < width && y >= 0 && y < height {
+                let pixel = &mut base[(y * width + x) as usize];
+                composite_with_coverage(over, color, pixel, coverage.min(1.));
+            }
+        });
+        // The line's end pixels can reach a row past its end points
+        let (top, bottom) = (segment.point1.y.min(segment.point2.y),
+                             segment.point1.y.max(segment.point2.y));
+        let rows = rows_between(top - 1., bottom + 1., self.height);
+        self.keep_format(rows);
+    }
+
+    /// Strokes the open polyline through `points` at `line_width`, with a color that fades from
+    /// `start_color` to `end_color` along its length.
+    ///
+    /// The gradient follows the stroke's arc length rather than its bounding box, so a winding
+    /// route or a progress arc fades evenly from its first point to its last however it bends.
+    /// Each pixel takes its color from the point along the stroke nearest to it, and segments are
+    /// stroked and joined the same way as `stroke_segments`.
+    pub fn stroke_polyline_gradient(&mut self, operator: &Operator, start_color: &Rgba,
+                                    end_color: &Rgba, points: &[Point], line_width: f32) {
+        let mut segments = Vec::new();
+        let mut total_length = 0.;
+        for pair in points.windows(2) {
+            let line = LineSegment::from_points(pair[0], pair[1]);
+            if let Some(segment) = ThickSegment::new(&line, line_width) {
+                segments.push(segment.with_offset(total_length));
+                total_length += segment.length();
+            }
+        }
+        if !operator.is_bounded() {
+            self.composite_through_scratch(operator, None, |scratch| {
+                scratch.stroke_polyline_gradient(&Operator::Source, start_color, end_color, points,
+                                                 line_width)
+            });
+            return;
+        }
+        if segments.is_empty() {
+            return;
+        }
+
+        let compose = fetch_operator(operator);
+        let start_color = self.blend_color(start_color);
+        let end_color = self.blend_color(end_color);
+        self.sweep_shapes(segments,
+                          |segment, row, coverage, distance| {
+                              segment.accumulate_distance(row, coverage, distance)
+                          },
+                          |pixels, coverage, distance| {
+                              let spans = coverage.iter().zip(distance.iter());
+                              for (pixel, (&coverage, &distance)) in pixels.iter_mut().zip(spans) {
+                                  let color = start_color.interpolate(&end_color,
+                                                                      distance / total_length);
+                                  composite_with_coverage(compose, &color, pixel, coverage);
+                              }
+                          });
+    }
+
+    // Composites `color` with `operator` everywhere `shapes` cover.
+    fn composite_shapes<S: RowCoverage>(&mut self, operator: &Operator, color: &Rgba,
+                                        shapes: Vec<S>) {
+        if !operator.is_bounded() {
+            self.composite_through_scratch(operator, None, |scratch| {
+                scratch.composite_shapes(&Operator::Source, color, shapes)
+            });
+            return;
+        }
+        let color = self.blend_color(color);
+        self.sweep_shapes(shapes,
+                          |shape, row, coverage, _| shape.accumulate(row, coverage),
+                          |pixels, coverage, _| {
+                              composite_span_with_coverage(operator, &color, pixels, coverage)
+                          });
+    }
+
+    // Sweeps `shapes` down the surface a row at a time.
+    //
+    // `accumulate` gathers a shape's coverage of a row, plus an optional per-pixel parameter, and
+    // returns the span of columns it touched.  `composite` is then called once for every row with
+    // the pixels in its spans and their combined coverage and parameters, and the rows swept are
+    // kept to the surface's format.
+    fn sweep_shapes<S, A, C>(&mut self, shapes: Vec<S>, mut accumulate: A, mut composite: C)
+        where S: RowCoverage,
+              A: FnMut(&S, usize, &mut [f32], &mut [f32]) -> (usize, usize),
+              C: FnMut(&mut [Rgba], &[f32], &[f32]) {
+        let surface_height = self.height as f32;
+        let mut pending = shapes.into_iter()
+            .filter(|shape| shape.bottom() > 0. && shape.top() < surface_height)
+            .collect::<Vec<S>>();
+        if pending.is_empty() {
+            return;
+        }
+
+        // Sorted bottom-to-top so the next shape to start can be popped off the end
+        pending.sort_by(|a, b| b.top().partial_cmp(&a.top()).unwrap());
+        let first_row = pending[pending.len() - 1].top().max(0.).floor() as usize;
+        let mut coverage = vec![0f32; self.width];
+        let mut parameter = vec![0f32; self.width];
+        let mut active: Vec<S> = Vec::new();
+        let mut end_row = first_row;
+
+        for row in first_row..self.height {
+            let (row_top, row_bottom) = (row as f32, row as f32 + 1.);
+            while !pending.is_empty() && pending[pending.len() - 1].top() < row_bottom {
+                active.push(pending.pop().unwrap());
+            }
+            active.retain(|shape| shape.bottom() > row_top);
+            if active.is_empty() {
+                if pending.is_empty() {
+                    break;
+                }
+                continue;
+            }
+
+            // Gather this row's coverage spans
+            let (mut span_start, mut span_end) = (self.width, 0);
+            for shape in active.iter() {
+                let (start, end) = accumulate(shape, row, &mut coverage, &mut parameter);
+                if start < end {
+                    span_start = span_start.min(start);
+                    span_end = span_end.max(end);
+                }
+            }
+            if span_start >= span_end {
+                continue;
+            }
+
+            // Composite the spans, then clear them for the next row
+            let row_start = row * self.width;
+            let pixels = &mut self.base[row_start + span_start..row_start + span_end];
+            composite(pixels, &coverage[span_start..span_end], &parameter[span_start..span_end]);
+            coverage[span_start..span_end].fill(0.);
+            parameter[span_start..span_end].fill(0.);
+            end_row = row + 1;
+        }
+        self.keep_format(first_row..end_row);
+    }
+
+    /// Returns the surface's `(width, height)` in pixels.
+    pub fn dimensions(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    /// Returns the pixel at (`x`, `y`), or an error if it lies outside the surface.
+    ///
+    /// # Usage
+    /// let pixel = surface.get_pixel(10, 20)?;
+    pub fn get_pixel(&self, x: usize, y: usize) -> Result<Rgba, OutOfBounds> {
+        let pixel = self.format.convert(&self.base[self.checked_position(x, y)?]);
+        Ok(if self.linear_blending { srgb::from_linear(&pixel) } else { pixel })
+    }
+
+    /// Overwrites the pixel at (`x`, `y`) with `color`, or returns an error if it lies outside the
+    /// surface.  No compositing operator is applied.
+    ///
+    /// # Usage
+    /// surface.set_pixel(10, 20, Rgba::new(1., 0., 0., 1.))?;
+    pub fn set_pixel(&mut self, x: usize, y: usize, color: Rgba) -> Result<(), OutOfBounds> {
+        let position = self.checked_position(x, y)?;
+        self.base[position] = self.format.convert(&self.blend_color(&color));
+        Ok(())
+    }
+
+    // Returns the index of (`x`, `y`) into `base`, checking both coordinates.  Unlike
+    // `calculate_position`, an x past the right edge is an error rather than the next row.
+    fn checked_position(&self, x: usize, y: usize) -> Result<usize, OutOfBounds> {
+        if x < self.width && y < self.height {
+            Ok(ImageSurface::calculate_position(self.width, x, y))
+        } else {
+            Err(OutOfBounds { x, y, width: self.width, height: self.height })
+        }
+    }
+
+    /// Returns the pixel at (`x`, `y`) as the surface holds it, like `iter()`, or None if it
+    /// lies outside the surface.
+    pub fn get(&self, x: usize, y: usize) -> Option<&Rgba> {
+        let position = ImageSurface::calculate_position(self.width, x, y);
+        self.base.get(position)
+    }
+
+    /// Returns the pixel at (`x`, `y`) mutably, or None if it lies outside the surface.  As with
+    /// `iter_mut()`, what is written should be a color the format can hold.
+    pub fn get_mut(&mut self, x: usize, y: usize) -> Option<&mut Rgba> {
+        let position = ImageSurface::calculate_position(self.width, x, y);
+        self.base.get_mut(position)
+    }
+
+    /// Returns the pixel at `idx`, counting row by row from the top left, as `get()` does.
+    pub fn get_with_index(&self, idx: usize) -> Option<&Rgba> {
+        self.base.get(idx)
+    }
+
+    /// Returns the pixel at `idx` mutably, as `get_mut()` does.
+    pub fn get_mut_with_index(&mut self, idx: usize) -> Option<&mut Rgba> {
+        self.base.get_mut(idx)
+    }
+
+    fn calculate_position(width: usize, x: usize, y: usize) -> usize {
+        y.wrapping_mul(width).wrapping_add(x)
+    }
+}
+
+/// ## Surface
+///
+/// A destination a Context draws on.  Analogous to the backend of a cairo_surface_t: the context
+/// keeps the drawing state and reduces each drawing operation to one of the entry points below,
+/// and the surface decides how to draw it, by compositing pixels as ImageSurface does or by
+/// writing the operation out in some format.
+///
+/// Only extents(), paint(), mask(), fill() and stroke() have to be implemented.  The batched
+/// helpers become fills and strokes of paths unless a surface has something faster, and
+/// flush(), finish(), show_page() and copy_page() do nothing unless it has something to do.
+pub trait Surface {
+    /// Returns the area drawing can reach, in device space, or None if the surface is
+    /// unbounded.
+    fn extents(&self) -> Option<RectangleInt>;
+
+    /// Composites the source everywhere inside the clip.  Analogous to cairo_paint().
+    fn paint(&mut self, composite: &Composite);
+
+    /// Composites the source through the alpha of `mask`, with `matrix` taking the mask's user
+    /// space to device space.  Analogous to cairo_mask().
+    fn mask(&mut self, composite: &Composite, mask: &Pattern, matrix: &Matrix);
+
+    /// Composites the source inside `path`, which is in device space, under `fill_rule`, with
+    /// curves flattened to within `tolerance`.  Analogous to cairo_fill().
+    fn fill(&mut self, composite: &Composite, path: &path::Path, fill_rule: FillRule,
+            tolerance: f32);
+
+    /// Composites the source inside the stroke of `path`, with curves flattened to within
+    /// `tolerance` in device space.  Analogous to cairo_stroke().
+    ///
+    /// The path, style and dashes are in user space, which `matrix` takes to device space, so a
+    /// scaled or skewed matrix stretches the stroke.
+    fn stroke(&mut self, composite: &Composite, path: &path::Path, matrix: &Matrix,
+              style: &StrokeStyle, dash: Option<&DashPattern>, tolerance: f32);
+
+    /// Composites `color` with `operator` inside every rectangle in `rectangles`, unclipped.
+    /// See `ImageSurface::fill_rectangles`.
+    fn fill_rectangles(&mut self, operator: &Operator, color: &Rgba, rectangles: &[Rectangle]) {
+        let source = Pattern::Solid(SolidPattern::new(*color));
+        self.fill(&Composite::unclipped(*operator, &source),
+                  &path::Path::from_rectangles(rectangles), FillRule::Winding, DEFAULT_TOLERANCE);
+    }
+
+    /// Composites `color` with `operator` inside every ellipse in `ellipses`, unclipped.  See
+    /// `ImageSurface::fill_ellipses`.
+    fn fill_ellipses(&mut self, operator: &Operator, color: &Rgba, ellipses: &[Ellipse]) {
+        let source = Pattern::Solid(SolidPattern::new(*color));
+        self.fill(&Composite::unclipped(*operator, &source),
+                  &path::Path::from_ellipses(ellipses, DEFAULT_TOLERANCE), FillRule::Winding,
+                  DEFAULT_TOLERANCE);
+    }
+
+    /// Strokes every segment in `segments` at `line_width` with `color` and `operator`, with
+    /// butt caps and unclipped.  See `ImageSurface::stroke_segments`.
+    fn stroke_segments(&mut self, operator: &Operator, color: &Rgba, segments: &[LineSegment],
+                       line_width: f32) {
+        let source = Pattern::Solid(SolidPattern::new(*color));
+        let style = StrokeStyle { cap: LineCap::Butt, ..StrokeStyle::new(line_width) };
+        self.stroke(&Composite::unclipped(*operator, &source),
+                    &path::Path::from_segments(segments), &Matrix::identity(), &style, None,
+                    DEFAULT_TOLERANCE);
+    }
+
+    /// Strokes the polyline through `points` at `line_width` with `operator`, fading from
+    /// `start_color` to `end_color`, with butt caps and unclipped.  See
+    /// `ImageSurface::stroke_polyline_gradient`.  Unless a surface can fade the color, the
+    /// polyline is drawn in `start_color`.
+    fn stroke_polyline_gradient(&mut self, operator: &Operator, start_color: &Rgba,
+                                _end_color: &Rgba, points: &[Point], line_width: f32) {
+        let source = Pattern::Solid(SolidPattern::new(*start_color));
+        let style = StrokeStyle { cap: LineCap::Butt, ..StrokeStyle::new(line_width) };
+        self.stroke(&Composite::unclipped(*operator, &source),
+                    &path::Path::from_polyline(points), &Matrix::identity(), &style, None,
+                    DEFAULT_TOLERANCE);
+    }
+
+    /// Composites the source through the coverage in `mask`, clipped.  Text drawn from cached
+    /// glyphs arrives this way.  Unless a surface can composite the coverage directly, it is
+    /// made into an A8 image and drawn with mask().
+    fn mask_coverage(&mut self, composite: &Composite, mask: &CoverageMask) {
+        let mut image = ImageSurface::create_for_format(Format::A8, mask.width, mask.height);
+        for (pixel, &coverage) in image.iter_mut().zip(&mask.coverage) {
+            *pixel = Rgba::new(0., 0., 0., coverage as f32 / 255.);
+        }
+        let mut pattern = SurfacePattern::new(image);
+        pattern.set_filter(Filter::Nearest);
+        self.mask(composite, &Pattern::Surface(pattern),
+                  &Matrix::translation(mask.x as f32, mask.y as f32));
+    }
+
+    /// Composites the source through a separate coverage for each color channel in `mask`,
+    /// clipped.  Text antialiased for LCD panels arrives this way.  Unless a surface can
+    /// composite the channels apart, they are averaged and drawn with mask_coverage().
+    fn mask_subpixel(&mut self, composite: &Composite, mask: &SubpixelMask) {
+        self.mask_coverage(composite, &mask.to_gray());
+    }
+
+    /// Finishes any drawing the surface has put off.  Analogous to cairo_surface_flush().
+    fn flush(&mut self) {}
+
+    /// Writes out whatever the surface has left to write, after which it isn't drawn on again.
+    /// Analogous to cairo_surface_finish().
+    fn finish(&mut self) {}
+
+    /// Finishes the current page and clears the surface for the next.  Analogous to
+    /// cairo_surface_show_page().
+    fn show_page(&mut self) {}
+
+    /// Finishes the current page and starts the next from a copy of it.  Analogous to
+    /// cairo_surface_copy_page().
+    fn copy_page(&mut self) {}
+}
+
+/// ## Composite
+///
+/// How a drawing operation composites its source, as a Context hands it to a `Surface`.
+#[derive(Clone, Copy)]
+pub struct Composite<'a> {
+    /// The operator the source is composited with.
+    pub operator: Operator,
+    /// The pattern composited.
+    pub source: &'a Pattern,
+    /// The matrix in place when the source was set, which takes the source's user space to
+    /// device space.
+    pub source_matrix: Matrix,
+    /// Whether only coverage is drawn, as opaque black, whatever the source.  See
+    /// `Context::set_coverage_only`.
+    pub coverage_only: bool,
+    /// How the edges of shapes are antialiased.
+    pub antialias: Antialias,
+    /// The rasterizer shapes are drawn with, by surfaces that rasterize.
+    pub rasterizer: Rasterizer,
+    /// How exactly shapes are tessellated, by surfaces that tessellate.
+    pub precision: Precision,
+    /// How many threads rasterizing may use.
+    pub threads: usize,
+    /// The area drawing is clipped to, if it is clipped.
+    pub clip: Option<&'a Clip>,
+}
+
+impl<'a> Composite<'a> {
+    // Returns a composite of `source` with `operator`, unclipped and otherwise as a new Context
+    // would draw it
+    fn unclipped(operator: Operator, source: &'a Pattern) -> Composite<'a> {
+        Composite {
+            operator,
+            source,
+            source_matrix: Matrix::identity(),
+            coverage_only: false,
+            antialias: Antialias::Default,
+            rasterizer: Rasterizer::Trapezoids,
+            precision: Precision::Fast,
+            threads: 1,
+            clip: None,
+        }
+    }
+
+    /// Returns the one color every pixel gets from the source, if the source is a solid color
+    /// or only coverage is drawn, so that solid fills can take faster paths.
+    pub fn solid_color(&self) -> Option<Rgba> {
+        match *self.source {
+            _ if self.coverage_only => Some(Rgba::new(0., 0., 0., 1.)),
+            Pattern::Solid(ref solid) => Some(solid.color),
+            _ => None,
+        }
+    }
+
+    /// Returns a function giving the color the source composites at the center of the pixel at
+    /// (x, y) in device space.
+    pub fn sampler(&self) -> impl Fn(usize, usize) -> Rgba + Sync + 'a {
+        let (source, coverage_only) = (self.source, self.coverage_only);
+        let to_user = self.source_matrix.invert();
+        let footprint = to_user.map(|to_user| source.footprint(&to_user));
+        move |x, y| {
+            if coverage_only {
+                return Rgba::new(0., 0., 0., 1.);
+            }
+            if let Pattern::Solid(ref solid) = *source {
+                return solid.color;
+            }
+            let center = Point::new(x as f32 + 0.5, y as f32 + 0.5);
+            match (to_user, footprint) {
+                (Some(to_user), Some(footprint)) => {
+                    source.filtered_color_at(to_user.transform_point(center), footprint)
+                },
+                // User space collapsed when the source was set, so there is nothing to sample
+                _ => Rgba::new(0., 0., 0., 0.),
+            }
+        }
+    }
+
+    // Returns the clip's coverage of the surface's pixels, if drawing is clipped
+    fn clip_mask(&self) -> Option<&'a ImageSurface> {
+        self.clip.and_then(|clip| clip.mask())
+    }
+}
+
+/// ## CoverageMask
+///
+/// A block of 8-bit coverage placed in device space, like an A8 image: 0 covers nothing and
+/// 255 covers a pixel completely.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CoverageMask {
+    /// The device x coordinate of the mask's left column.
+    pub x: i32,
+    /// The device y coordinate of the mask's top row.
+    pub y: i32,
+    /// The number of columns.
+    pub width: usize,
+    /// The number of rows.
+    pub height: usize,
+    /// The coverage of each pixel, row by row.
+    pub coverage: Vec<u8>,
+}
+
+impl CoverageMask {
+    /// Returns a mask at (x, y) that covers nothing.
+    pub fn new(x: i32, y: i32, width: usize, height: usize) -> CoverageMask {
+        CoverageMask { x, y, width, height, coverage: vec![0; width * height] }
+    }
+
+    /// Returns the coverage of the pixel at (x, y) in device space, which is 0 outside the mask.
+    pub fn get(&self, x: i32, y: i32) -> u8 {
+        let (column, row) = (x - self.x, y - self.y);
+        if column < 0 || row < 0 || column as usize >= self.width || row as usize >= self.height {
+            return 0;
+        }
+        self.coverage[row as usize * self.width + column as usize]
+    }
+
+    /// Adds the coverage of `other`, moved by (dx, dy), where it overlaps this mask, saturating
+    /// at full coverage.
+    pub fn add(&mut self, other: &CoverageMask, dx: i32, dy: i32) {
+        for row in 0..other.height {
+            let y = other.y + dy + row as i32;
+            if y < self.y || y >= self.y + self.height as i32 {
+                continue;
+            }
+            for column in 0..other.width {
+                let x = other.x + dx + column as i32;
+                if x < self.x || x >= self.x + self.width as i32 {
+                    continue;
+                }
+                let index = (y - self.y) as usize * self.width + (x - self.x) as usize;
+                let coverage = other.coverage[row * other.width + column];
+                self.coverage[index] = self.coverage[index].saturating_add(coverage);
+            }
+        }
+    }
+}
+
+/// ## SubpixelMask
+///
+/// A block of 8-bit coverage for each of the red, green and blue channels, placed in device
+/// space.  Text antialiased with `Antialias::Subpixel` arrives this way, each channel covering
+/// the part of the pixel its LCD subpixel lights.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SubpixelMask {
+    /// The device x coordinate of the mask's left column.
+    pub x: i32,
+    /// The device y coordinate of the mask's top row.
+    pub y: i32,
+    /// The number of columns.
+    pub width: usize,
+    /// The number of rows.
+    pub height: usize,
+    /// The red, green and blue coverage of each pixel, row by row.
+    pub coverage: Vec<[u8; 3]>,
+}
+
+impl SubpixelMask {
+    /// Returns a mask at (x, y) that covers nothing.
+    pub fn new(x: i32, y: i32, width: usize, height: usize) -> SubpixelMask {
+        SubpixelMask { x, y, width, height, coverage: vec![[0; 3]; width * height] }
+    }
+
+    /// Returns the coverage of each channel of the pixel at (x, y) in device space, which is 0
+    /// outside the mask.
+    pub fn get(&self, x: i32, y: i32) -> [u8; 3] {
+        let (column, row) = (x - self.x, y - self.y);
+        if column < 0 || row < 0 || column as usize >= self.width || row as usize >= self.height {
+            return [0; 3];
+        }
+        self.coverage[row as usize * self.width + column as usize]
+    }
+
+    /// Returns the mask with each pixel covered by the average of its channels, for surfaces
+    /// that can't composite the channels apart.
+    pub fn to_gray(&self) -> CoverageMask {
+        let coverage = self.coverage.iter().map(|&[red, green, blue]| {
+            ((red as u16 + green as u16 + blue as u16 + 1) / 3) as u8
+        }).collect();
+        CoverageMask { x: self.x, y: self.y, width: self.width, height: self.height, coverage }
+    }
+}
+
+/// ImageSurface draws by compositing its pixels, with the rasterizer the Composite asks for.
+impl Surface for ImageSurface {
+    fn extents(&self) -> Option<RectangleInt> {
+        Some(RectangleInt::new(0, 0, self.width as i32, self.height as i32))
+    }
+
+    fn paint(&mut self, composite: &Composite) {
+        if let Pattern::Surface(ref pattern) = *composite.source {
+            // Bounded operators leave the destination alone where the source is clear, so an
+            // unextended surface only needs the pixels it reaches visiting
+            if pattern.get_extend() == Extend::None && composite.operator.is_bounded() &&
+               !composite.coverage_only {
+                if let Some(to_user) = composite.source_matrix.invert() {
+                    let to_source = to_user.multiply(&pattern.get_matrix());
+                    self.composite_surface(pattern.surface(), &to_source, pattern.get_filter(),
+                                           &composite.operator, composite.clip_mask());
+                }
+                return;
+            }
+        }
+        match (self.blend_solid_color(composite), composite.clip) {
+            (Some(color), None) => {
+                composite_span(&composite.operator, &color, &mut self.base);
+                self.keep_format(0..self.height);
+            },
+            _ => self.composite_masked(composite, |_| 1.),
+        }
+    }
+
+    fn mask(&mut self, composite: &Composite, mask: &Pattern, matrix: &Matrix) {
+        let to_user = matrix.invert();
+        let footprint = to_user.map(|to_user| mask.footprint(&to_user));
+        self.composite_masked(composite, |center| match (to_user, footprint) {
+            (Some(to_user), Some(footprint)) => {
+                mask.filtered_color_at(to_user.transform_point(center), footprint).alpha
+            },
+            _ => 0.,
+        });
+    }
+
+    fn fill(&mut self, composite: &Composite, path: &path::Path, fill_rule: FillRule,
+            tolerance: f32) {
+        if !composite.operator.is_bounded() {
+            let shape = Composite { operator: Operator::Source, clip: None, ..*composite };
+            self.composite_through_scratch(&composite.operator, composite.clip_mask(),
+                                           |scratch| scratch.fill(&shape, path, fill_rule,
+                                                                  tolerance));
+            return;
+        }
+        if let Some(rectangle) = aligned_rectangle(path) {
+            self.composite_rectangle(composite, &rectangle);
+            return;
+        }
+        let edges = path.edges(tolerance);
+        if !edges.is_empty() {
+            self.composite_edges(composite, edges, fill_rule);
+        }
+    }
+
+    fn stroke(&mut self, composite: &Composite, path: &path::Path, matrix: &Matrix,
+              style: &StrokeStyle, dash: Option<&DashPattern>, tolerance: f32) {
+        if !composite.operator.is_bounded() {
+            let shape = Composite { operator: Operator::Source, clip: None, ..*composite };
+            self.composite_through_scratch(&composite.operator, composite.clip_mask(),
+                                           |scratch| scratch.stroke(&shape, path, matrix, style,
+                                                                    dash, tolerance));
+            return;
+        }
+        // The outline is built in user space, where flattening has to be finer wherever the
+        // matrix enlarges things, and brought to device space to be filled
+        let tolerance = tolerance / matrix.max_scale();
+        let edges: Vec<Edge> = stroke_edges_with_dash(path, style, dash, tolerance)
+            .iter()
+            .map(|edge| Edge::from_points(matrix.transform_point(edge.line.point1),
+                                          matrix.transform_point(edge.line.point2)))
+            .collect();
+        if !edges.is_empty() {
+            self.composite_edges(composite, edges, FillRule::Winding);
+        }
+    }
+
+    fn fill_rectangles(&mut self, operator: &Operator, color: &Rgba, rectangles: &[Rectangle]) {
+        ImageSurface::fill_rectangles(self, operator, color, rectangles);
+    }
+
+    fn fill_ellipses(&mut self, operator: &Operator, color: &Rgba, ellipses: &[Ellipse]) {
+        ImageSurface::fill_ellipses(self, operator, color, ellipses);
+    }
+
+    fn stroke_segments(&mut self, operator: &Operator, color: &Rgba, segments: &[LineSegment],
+                       line_width: f32) {
+        ImageSurface::stroke_segments(self, operator, color, segments, line_width);
+    }
+
+    fn stroke_polyline_gradient(&mut self, operator: &Operator, start_color: &Rgba,
+                                end_color: &Rgba, points: &[Point], line_width: f32) {
+        ImageSurface::stroke_polyline_gradient(self, operator, start_color, end_color, points,
+                                               line_width);
+    }
+
+    fn mask_coverage(&mut self, composite: &Composite, mask: &CoverageMask) {
+        if !composite.operator.is_bounded() {
+            self.composite_masked(composite, |center| {
+                mask.get(center.x.floor() as i32, center.y.floor() as i32) as f32 / 255.
+            });
+            return;
+        }
+        let operator = fetch_operator(&composite.operator);
+        let source = self.blend_sampler(composite);
+        let clip = composite.clip_mask();
+        // Only the rows and columns of the mask that land on the surface are visited
+        let columns = mask.x.max(0)..(mask.x + mask.width as i32).min(self.width as i32);
+        let rows = mask.y.max(0)..(mask.y + mask.height as i32).min(self.height as i32);
+        for y in rows.clone() {
+            for x in columns.clone() {
+                let (x, y) = (x as usize, y as usize);
+                let mut coverage = mask.get(x as i32, y as i32) as f32 / 255.;
+                if let Some(clip) = clip {
+                    coverage *= clip.get(x, y).map_or(0., |clip_coverage| clip_coverage.alpha);
+                }
+                if coverage > 0. {
+                    let pixel = &mut self.base[y * self.width + x];
+                    composite_with_coverage(operator, &source(x, y), pixel, coverage);
+                }
+            }
+        }
+        self.keep_format(rows.start.max(0) as usize..rows.end.max(0) as usize);
+    }
+
+    fn mask_subpixel(&mut self, composite: &Composite, mask: &SubpixelMask) {
+        if !composite.operator.is_bounded() {
+            self.mask_coverage(composite, &mask.to_gray());
+            return;
+        }
+        let operator = fetch_operator(&composite.operator);
+        let source = self.blend_sampler(composite);
+        let clip = composite.clip_mask();
+        let columns = mask.x.max(0)..(mask.x + mask.width as i32).min(self.width as i32);
+        let rows = mask.y.max(0)..(mask.y + mask.height as i32).min(self.height as i32);
+        for y in rows.clone() {
+            for x in columns.clone() {
+                let (x, y) = (x as usize, y as usize);
+                let clip_coverage = match clip {
+                    Some(clip) => clip.get(x, y).map_or(0., |clip_coverage| clip_coverage.alpha),
+                    None => 1.,
+                };
+                let [red, green, blue] = mask.get(x as i32, y as i32);
+                if clip_coverage <= 0. || red as u16 + green as u16 + blue as u16 == 0 {
+                    continue;
+                }
+                // Each channel is composited as far as its own coverage, as pixman's component
+                // alpha does, and the alpha as far as their average
+                let (source, pixel) = (source(x, y), &mut self.base[y * self.width + x]);
+                let original = *pixel;
+                let channel = |coverage: u8| {
+                    let mut channel = original;
+                    composite_with_coverage(operator, &source, &mut channel,
+                                            coverage as f32 / 255. * clip_coverage);
+                    channel
+                };
+                let average = (red as f32 + green as f32 + blue as f32) / 3.;
+                let mut alpha = original;
+                composite_with_coverage(operator, &source, &mut alpha,
+                                        average / 255. * clip_coverage);
+                *pixel = Rgba {
+                    red: channel(red).red,
+                    green: channel(green).green,
+                    blue: channel(blue).blue,
+                    alpha: alpha.alpha,
+                };
+            }
+        }
+        self.keep_format(rows.start.max(0) as usize..rows.end.max(0) as usize);
+    }
+
+    fn show_page(&mut self) {
+        ImageSurface::show_page(self);
+    }
+
+    fn copy_page(&mut self) {
+        ImageSurface::copy_page(self);
+    }
+}
+
+impl ImageSurface {
+    // Composites the source wherever the area inside `edges` under `fill_rule` covers the
+    // surface and the clip lets through, with the rasterizer `composite` asks for.
+    fn composite_edges(&mut self, composite: &Composite, edges: Vec<Edge>,
+                       fill_rule: FillRule) {
+        let clip = composite.clip_mask();
+        match composite.rasterizer {
+            Rasterizer::Trapezoids => {
+                let trapezoids = bo_trap::sweep_with_precision(edges, fill_rule,
+                                                                composite.precision);
+                self.composite_trapezoids(composite, &trapezoids);
+            },
+            Rasterizer::Spans => match self.blend_solid_color(composite) {
+                Some(color) => rasterize_edges_with_color(&edges, fill_rule, color,
+                                                          &composite.operator,
+                                                          composite.antialias, clip, self),
+                None => {
+                    let source = self.blend_sampler(composite);
+                    rasterize_edges_with_source(&edges, fill_rule, source, &composite.operator,
+                                                composite.antialias, clip, self)
+                },
+            },
+        }
+    }
+
+    // Composites the source onto every pixel of `rectangle` the clip lets through, a row at a
+    // time.  The rectangle is whole pixels, so no pixel is partly covered and nothing needs
+    // tessellating or sampling.
+    fn composite_rectangle(&mut self, composite: &Composite, rectangle: &RectangleInt) {
+        let columns = rectangle.x.max(0) as usize..
+                      (rectangle.x + rectangle.width).clamp(0, self.width as i32) as usize;
+        let rows = rectangle.y.max(0) as usize..
+                   (rectangle.y + rectangle.height).clamp(0, self.height as i32) as usize;
+        if columns.is_empty() {
+            return;
+        }
+        let clip = composite.clip_mask();
+        if let (Some(color), None) = (self.blend_solid_color(composite), clip) {
+            for y in rows.clone() {
+                let row = y * self.width;
+                composite_span(&composite.operator, &color,
+                               &mut self.base[row + columns.start..row + columns.end]);
+            }
+            self.keep_format(rows);
+            return;
+        }
+        let operator = fetch_operator(&composite.operator);
+        let source = self.blend_sampler(composite);
+        for y in rows.clone() {
+            for x in columns.clone() {
+                let coverage = match clip {
+                    Some(clip) => clip.get(x, y).map_or(0., |clip_coverage| clip_coverage.alpha),
+                    None => 1.,
+                };
+                let pixel = &mut self.base[y * self.width + x];
+                composite_with_coverage(operator, &source(x, y), pixel, coverage);
+            }
+        }
+        self.keep_format(rows);
+    }
+
+    // Composites `source` with `operator` onto the pixels it reaches, as far as the clip lets
+    // it.  `to_source` takes device space to the source's pixel space, and each pixel's center is
+    // mapped through it and sampled with `filter`.  Pixels whose centers land outside the source,
+    // by more than the half pixel a filter blends its edges over, are left alone, so only the
+    // source's bounds in device space are visited.
+    fn composite_surface(&mut self, source: &ImageSurface, to_source: &Matrix, filter: Filter,
+                         operator: &Operator, clip: Option<&ImageSurface>) {
+        let to_device = match to_source.invert() {
+            Some(to_device) => to_device,
+            None => return,
+        };
+        let (width, height) = (source.width as f32, source.height as f32);
+        let corners = [Point::new(0., 0.), Point::new(width, 0.), Point::new(0., height),
+                       Point::new(width, height)];
+        let (mut left, mut top) = (f32::INFINITY, f32::INFINITY);
+        let (mut right, mut bottom) = (f32::NEG_INFINITY, f32::NEG_INFINITY);
+        for corner in corners.iter().map(|&corner| to_device.transform_point(corner)) {
+            left = left.min(corner.x);
+            top = top.min(corner.y);
+            right = right.max(corner.x);
+            bottom = bottom.max(corner.y);
+        }
+        // Grown by a pixel for the filter's edges, and clipped to the surface
+        let (left, top) = ((left - 1.).floor().max(0.), (top - 1.).floor().max(0.));
+        let right = (right + 1.).ceil().min(self.width as f32);
+        let bottom = (bottom + 1.).ceil().min(self.height as f32);
+        if !(left < right && top < bottom) {
+            return;
+        }
+
+        let compose = fetch_operator(operator);
+        let footprint = pattern::footprint(to_source);
+        // How far outside the source a pixel center can map and still have the filter reach it
+        let margin = match filter {
+            Filter::Nearest => Point::new(0., 0.),
+            Filter::Bilinear => Point::new(0.5, 0.5),
+            Filter::Good | Filter::Best => {
+                Point::new(footprint.x.max(1.) / 2., footprint.y.max(1.) / 2.)
+            },
+        };
+        for y in top as usize..bottom as usize {
+            for x in left as usize..right as usize {
+                let point = to_source.transform_point(Point::new(x as f32 + 0.5, y as f32 + 0.5));
+                if point.x < -margin.x || point.y < -margin.y || point.x >= width + margin.x ||
+                   point.y >= height + margin.y {
+                    continue;
+                }
+                let coverage = match clip {
+                    Some(clip) => clip.get(x, y).map_or(0., |clip_coverage| clip_coverage.alpha),
+                    None => 1.,
+                };
+                let color = self.blend_color(&pattern::sample(source, point, filter,
+                                                              Extend::None, footprint));
+                let pixel = &mut self.base[y * self.width + x];
+                composite_with_coverage(compose, &color, pixel, coverage);
+            }
+        }
+        self.keep_format(top as usize..bottom as usize);
+    }
+
+    // Composites the source wherever `trapezoids` cover the surface and the clip lets through,
+    // on as many threads as `composite` allows.
+    fn composite_trapezoids(&mut self, composite: &Composite, trapezoids: &[Trapezoid]) {
+        let source = self.blend_sampler(composite);
+        rasterize_trapezoids_in_tiles(trapezoids, source, &composite.operator,
+                                      composite.antialias, composite.clip_mask(), self,
+                                      composite.threads);
+    }
+
+    // Composites the source onto every pixel with the operator, as far as the coverage `mask`
+    // gives the pixel's center, in device space, and the clip let it.
+    fn composite_masked<F: Fn(Point) -> f32>(&mut self, composite: &Composite, mask: F) {
+        let bounded = composite.operator.is_bounded();
+        let operator = fetch_operator(&composite.operator);
+        let source = self.blend_sampler(composite);
+        let clip = composite.clip_mask();
+        let width = self.width;
+        for (position, pixel) in self.base.iter_mut().enumerate() {
+            let (x, y) = (position % width, position / width);
+            let coverage = mask(Point::new(x as f32 + 0.5, y as f32 + 0.5));
+            let clip_coverage = match clip {
+                Some(clip) => clip.get(x, y).map_or(0., |clip_coverage| clip_coverage.alpha),
+                None => 1.,
+            };
+            if bounded && coverage > 0. {
+                composite_with_coverage(operator, &source(x, y), pixel, coverage * clip_coverage);
+            } else if !bounded && clip_coverage > 0. {
+                // Where the mask is clear the operator still meets a clear source
+                let color = if coverage > 0. { source(x, y) } else { Rgba::new(0., 0., 0., 0.) };
+                composite_unbounded(operator, &color, pixel, coverage, clip_coverage);
+            }
+        }
+        self.keep_format(0..self.height);
+    }
+
+    // Composites with an operator that isn't bounded, which changes pixels a shape doesn't
+    // cover as well as the ones it does.
+    //
+    // `draw` draws the shape with the Source operator onto a clear scratch surface, which leaves
+    // every pixel holding the source scaled by the shape's coverage, or clear where the shape
+    // isn't.  Each pixel the clip lets through is then composited with its scratch pixel.
+    fn composite_through_scratch<D>(&mut self, operator: &Operator, clip: Option<&ImageSurface>,
+                                    draw: D)
+        where D: FnOnce(&mut ImageSurface) {
+        let mut scratch = ImageSurface::create_for_format(Format::ARGB32, self.width, self.height);
+        scratch.linear_blending = self.linear_blending;
+        draw(&mut scratch);
+
+        let operator = fetch_operator(operator);
+        let width = self.width;
+        let pixels = self.base.iter_mut().zip(scratch.base.iter());
+        for (position, (pixel, source)) in pixels.enumerate() {
+            let clip_coverage = match clip {
+                Some(clip) => clip.get(position % width, position / width)
+                                  .map_or(0., |clip_coverage| clip_coverage.alpha),
+                None => 1.,
+            };
+            composite_with_coverage(operator, source, pixel, clip_coverage);
+        }
+        self.keep_format(0..self.height);
+    }
+}
+
+// Returns the rectangle `path` outlines if it is a single rectangle whose sides lie on pixel
+// boundaries, which fills whole pixels whatever the fill rule and antialiasing.
+fn aligned_rectangle(path: &path::Path) -> Option<RectangleInt> {
+    let mut corners = Vec::with_capacity(5);
+    for (index, element) in path.iter().enumerate() {
+        match (index, *element) {
+            (0, path::PathElement::MoveTo(point)) => corners.push(point),
+            (1..=4, path::PathElement::LineTo(point)) => corners.push(point),
+            (4..=5, path::PathElement::ClosePath) => {},
+            _ => return None,
+        }
+    }
+    if corners.len() == 5 && corners[4] == corners[0] {
+        corners.pop();
+    }
+    // Whole numbers small enough that the rectangle's size fits an i32
+    let whole = |value: f32| value.fract() == 0. && value.abs() < (1 << 30) as f32;
+    if corners.len() != 4 || !corners.iter().all(|corner| whole(corner.x) && whole(corner.y)) {
+        return None;
+    }
+    // The sides alternate between horizontal and vertical, starting with either
+    let (a, b, c, d) = (corners[0], corners[1], corners[2], corners[3]);
+    let horizontal_first = a.y == b.y && b.x == c.x && c.y == d.y && d.x == a.x;
+    let vertical_first = a.x == b.x && b.y == c.y && c.x == d.x && d.y == a.y;
+    if !horizontal_first && !vertical_first {
+        return None;
+    }
+    let (left, right) = (a.x.min(c.x) as i32, a.x.max(c.x) as i32);
+    let (top, bottom) = (a.y.min(c.y) as i32, a.y.max(c.y) as i32);
+    if left == right || top == bottom {
+        return None;
+    }
+    Some(RectangleInt::new(left, top, right - left, bottom - top))
+}
+
+// Returns the rows of a surface `height` rows tall that anything lying between `top` and
+// `bottom`, in device space, reaches into.
+pub(crate) fn rows_between(top: f32, bottom: f32, height: usize) -> Range<usize> {
+    let row = |y: f32| y.max(0.).min(height as f32) as usize;
+    let (top, bottom) = (row(top.floor()), row(bottom.ceil()));
+    top..bottom.max(top)
+}
+
+/// Composites all of `source` onto `destination` with `operator`, with the source's top left
+/// pixel at (`x`, `y`) of the destination.
+///
+/// Only the pixels where the two surfaces overlap are composited.  The rest of the source is
+/// clipped away and the rest of the destination is left as it is, whatever the operator.  Each
+/// source pixel lands on exactly one destination pixel, so drawing a sprite this way skips the
+/// sampling and rasterizing that painting it through a surface pattern would do.
+///
+/// # Usage
+/// surfaces::composite(&sprite, &mut frame, &Operator::Over, 40, -8);
+pub fn composite(source: &ImageSurface, destination: &mut ImageSurface, operator: &Operator,
+                 x: i32, y: i32) {
+    // The overlap, in destination pixels
+    let (x, y) = (x as i64, y as i64);
+    let (left, top) = (x.max(0), y.max(0));
+    let right = (x + source.width as i64).min(destination.width as i64);
+    let bottom = (y + source.height as i64).min(destination.height as i64);
+    if left >= right || top >= bottom {
+        return;
+    }
+
+    let operator = fetch_operator(operator);
+    let format = source.format;
+    let to_destination: fn(&Rgba) -> Rgba = match (source.linear_blending,
+                                                   destination.linear_blending) {
+        (true, false) => srgb::from_linear,
+        (false, true) => srgb::to_linear,
+        _ => |pixel| *pixel,
+    };
+    let width = (right - left) as usize;
+    for row in top..bottom {
+        let source_start = (row - y) as usize * source.width + (left - x) as usize;
+        let destination_start = row as usize * destination.width + left as usize;
+        let pixels = source.base[source_start..source_start + width].iter();
+        let destination = &mut destination.base[destination_start..destination_start + width];
+        for (pixel, destination) in pixels.zip(destination.iter_mut()) {
+            operator(&to_destination(&format.convert(pixel)), destination);
+        }
+    }
+}
+
+/// Composites `source` onto `destination` with `operator`, transformed by `matrix`, which takes
+/// the source's pixel space to the destination's.
+///
+/// Every destination pixel the transformed source reaches has its center mapped back into the
+/// source and sampled with `filter`, so a scaled or rotated image is drawn without gaps, and
+/// with smooth edges unless the filter is Filter::Nearest.  As with `composite`, the rest of the
+/// destination is left as it is whatever the operator, and a matrix that can't be inverted draws
+/// nothing.
+///
+/// # Usage
+/// let matrix = Matrix::rotation(0.3).multiply(&Matrix::translation(40., 10.));
+/// surfaces::composite_transformed(&sprite, &mut frame, &Operator::Over, &matrix,
+///                                 Filter::Bilinear);
+pub fn composite_transformed(source: &ImageSurface, destination: &mut ImageSurface,
+                             operator: &Operator, matrix: &Matrix, filter: Filter) {
+    if let Some(to_source) = matrix.invert() {
+        destination.composite_surface(source, &to_source, filter, operator, None);
+    }
+}
+
+// Printing every pixel would bury the rest, so only the shape of the surface is shown
+impl fmt::Debug for ImageSurface {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ImageSurface")
+            .field("width", &self.width)
+            .field("height", &self.height)
+            .field("format", &self.format)
+            .field("color_space", &self.color_space)
+            .field("linear_blending", &self.linear_blending)
+            .finish()
+    }
+}
+
+/// Surfaces are equal when they hold the same image: the same size, format and color space,
+/// and the same pixels as their format stores them.
+impl PartialEq for ImageSurface {
+    fn eq(&self, other: &ImageSurface) -> bool {
+        self.width == other.width && self.height == other.height && self.format == other.format
+            && self.color_space == other.color_space && self.pixels().eq(other.pixels())
+    }
+}
+
+/// An ImageSurface packed into bytes, returned by `ImageSurface::pack()` or created packed by
+/// `PackedImageSurface::create_for_format()`.
+///
+/// The pixels are stored premultiplied in the surface's format, exactly as `ImageSurface::data()`
+/// lays them out, and converted to and from Rgba as they are read and written.  Runs of pixels
+/// can be composited onto without unpacking the surface, which for ARGB32 and RGB24 surfaces is
+/// done in integer arithmetic with the Over and Source operators.  It is a `Surface` as well, so
+/// a Context can draw on it.
+#[derive(Clone, PartialEq)]
+pub struct PackedImageSurface {
+    data: Vec<u8>,
+    pub width: usize,
+    pub height: usize,
+    format: Format,
+    stride: usize,
+    color_space: ColorSpace,
+    linear_blending: bool,
+}
+
+impl PackedImageSurface {
+    /// Returns a `width` by `height` surface whose pixels are packed in `format`, cleared to zero
+    /// as `ImageSurface::create_for_format` clears them.  The surface takes the packed memory
+    /// only, four bytes a pixel for ARGB32, and a Context can draw on it directly.
+    ///
+    /// # Usage
+    /// let mut surface = PackedImageSurface::create_for_format(Format::ARGB32, 100, 100);
+    /// let mut context = Context::create(&mut surface);
+    ///
+    /// # Panics
+    /// If either dimension is zero, or `format` is not supported (see `Format::is_supported`), or
+    /// the pixels can't be allocated.  Use `try_create_for_format` to handle these instead.
+    pub fn create_for_format(format: Format, width: usize, height: usize)
+                             -> PackedImageSurface {
+        match PackedImageSurface::try_create_for_format(format, width, height) {
+            Ok(surface) => surface,
+            Err(error) => panic!("error: PackedImageSurface can't be created: {}", error),
+        }
+    }
+
+    /// Returns a `width` by `height` surface whose pixels are packed in `format`, as
+    /// `create_for_format` does, or the error `ImageSurface::try_create_for_format` would give
+    /// rather than a panic if it can't be created.
+    ///
+    /// # Usage
+    /// let surface = PackedImageSurface::try_create_for_format(Format::ARGB32, width, height)?;
+    pub fn try_create_for_format(format: Format, width: usize, height: usize)
+                                 -> Result<PackedImageSurface, SurfaceError> {
+        let (_, stride) = ImageSurface::layout(format, width, height)?;
+        let mut data = Vec::new();
+        if data.try_reserve_exact(stride * height).is_err() {
+            return Err(SurfaceError::new(Status::NoMemory, "pixels can't be allocated"));
+        }
+        data.resize(stride * height, 0);
+        Ok(PackedImageSurface {
+            data,
+            width,
+            height,
+            format,
+            stride,
+            color_space: ColorSpace::Srgb,
+            linear_blending: false,
+        })
+    }
+
+    /// Unpacks the surface into an ImageSurface that can be drawn on.
+    pub fn unpack(self) -> ImageSurface {
+        let mut surface = ImageSurface::create_for_format(self.format, self.width, self.height);
+        surface.stride = self.stride;
+        surface.data = self.data;
+        surface.color_space = self.color_space;
+        surface.linear_blending = self.linear_blending;
+        surface.unpack_data();
+        surface
+    }
+
+    /// Returns the format the pixels are packed in.
+    pub fn get_format(&self) -> Format {
+        self.format
+    }
+
+    /// Returns the number of bytes from the start of one row of `data()` to the start of the next.
+    pub fn get_stride(&self) -> usize {
+        self.stride
+    }
+
+    /// Returns the color space the pixels are tagged with.
+    pub fn get_color_space(&self) -> ColorSpace {
+        self.color_space
+    }
+
+    /// Returns the packed bytes, laid out as described in `ImageSurface::data()`.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Returns the packed bytes for writing.
+    pub fn data_mut(&mut self) -> &mut [u8] {
+        &mut self.data
+    }
+
+    /// Returns the pixel at (`x`, `y`), or an error if it lies outside the surface.
+    pub fn get_pixel(&self, x: usize, y: usize) -> Result<Rgba, OutOfBounds> {
+        let row = self.row(x, y)?;
+        Ok(ImageSurface::unpack_pixel(self.format, &self.data[row..], x))
+    }
+
+    /// Overwrites the pixel at (`x`, `y`) with `color`, or returns an error if it lies outside the
+    /// surface.  No compositing operator is applied.
+    pub fn set_pixel(&mut self, x: usize, y: usize, color: Rgba) -> Result<(), OutOfBounds> {
+        let row = self.row(x, y)?;
+        ImageSurface::pack_pixel(self.format, &mut self.data[row..], x, &color);
+        Ok(())
+    }
+
+    /// Composites `color` with `operator` onto the `length` pixels from (`x`, `y`) rightwards,
+    /// stopping at the right edge of the surface.  Returns an error if (`x`, `y`) lies outside
+    /// the surface.
+    ///
+    /// Over and Source are done on the packed words of ARGB32 and RGB24 surfaces.  Every other
+    /// case unpacks each pixel, composites it with the operator and packs it again.  Surfaces
+    /// packed with linear blending on always take that path, decoding and encoding the bytes
+    /// through lookup tables, since integer arithmetic on the sRGB bytes would blend them as
+    /// they are.
+    ///
+    /// # Usage
+    /// packed.composite_span(&Operator::Over, &Rgba::new(1., 0., 0., 0.5), 0, 10, 100)?;
+    pub fn composite_span(&mut self, operator: &Operator, color: &Rgba, x: usize, y: usize,
+                          length: usize) -> Result<(), OutOfBounds> {
+        let row = self.row(x, y)?;
+        let (format, end) = (self.format, (x + length).min(self.width));
+        let row = &mut self.data[row..];
+        let packed = matches!(format, Format::ARGB32 | Format::RGB24)
+                     && matches!(*operator, Operator::Over | Operator::Source);
+        if self.linear_blending {
+            let (operator, color) = (fetch_operator(operator), srgb::to_linear(color));
+            for x in x..end {
+                let mut pixel = ImageSurface::unpack_linear_pixel(format, row, x);
+                operator(&color, &mut pixel);
+                ImageSurface::pack_linear_pixel(format, row, x, &pixel);
+            }
+        } else if packed {
+            let mut source = [0; 4];
+            ImageSurface::pack_pixel(format, &mut source, 0, color);
+            let source = u32::from_ne_bytes(source);
+            for pixel in row[x * 4..end * 4].chunks_mut(4) {
+                let word = match *operator {
+                    Operator::Over => over_argb32(source, ImageSurface::word(pixel, 0)),
+                    _ => source,
+                };
+                pixel.copy_from_slice(&word.to_ne_bytes());
+            }
+        } else {
+            let operator = fetch_operator(operator);
+            for x in x..end {
+                let mut pixel = ImageSurface::unpack_pixel(format, row, x);
+                operator(color, &mut pixel);
+                ImageSurface::pack_pixel(format, row, x, &pixel);
+            }
+        }
+        Ok(())
+    }
+
+    // Returns the offset into `data` of row `y`, checking that (`x`, `y`) is on the surface.
+    fn row(&self, x: usize, y: usize) -> Result<usize, OutOfBounds> {
+        if x < self.width && y < self.height {
+            Ok(y * self.stride)
+        } else {
+            Err(OutOfBounds { x, y, width: self.width, height: self.height })
+        }
+    }
+
+    // Unpacks `rows` into a surface of their own and hands it to `draw` with the matrix that
+    // moves device space up to match, so the band's top row is row zero, then packs it back.
+    // Only the rows a drawing operation can reach are unpacked.
+    fn draw_rows<F>(&mut self, rows: Range<usize>, draw: F)
+        where F: FnOnce(&mut ImageSurface, &Matrix)
+    {
+        if rows.is_empty() {
+            return;
+        }
+        let bytes = rows.start * self.stride..rows.end * self.stride;
+        let mut band = ImageSurface::create_for_format(self.format, self.width, rows.len());
+        band.stride = self.stride;
+        band.data = self.data[bytes.clone()].to_vec();
+        band.color_space = self.color_space;
+        band.linear_blending = self.linear_blending;
+        band.unpack_data();
+        draw(&mut band, &Matrix::translation(0., -(rows.start as f32)));
+        band.pack_data();
+        self.data[bytes].copy_from_slice(&band.data);
+    }
+
+    // Returns the rows of the surface that `extents`, in device space, reaches into, widened by
+    // `margin` on either side.
+    fn rows_reached(&self, extents: &Rectangle, margin: f32) -> Range<usize> {
+        rows_between(extents.y - margin, extents.y + extents.height + margin, self.height)
+    }
+}
+
+/// PackedImageSurface draws by unpacking the rows an operation can reach, drawing on them as an
+/// ImageSurface would and packing them again, so only a band of the surface is ever held as
+/// Rgba.  Channels are rounded to the nearest 1/255 after every operation.
+impl Surface for PackedImageSurface {
+    fn extents(&self) -> Option<RectangleInt> {
+        Some(RectangleInt::new(0, 0, self.width as i32, self.height as i32))
+    }
+
+    fn paint(&mut self, composite: &Composite) {
+        let rows = 0..self.height;
+        self.draw_rows(rows, |band, _| band.paint(composite));
+    }
+
+    fn mask(&mut self, composite: &Composite, mask: &Pattern, matrix: &Matrix) {
+        let rows = 0..self.height;
+        self.draw_rows(rows, |band, _| band.mask(composite, mask, matrix));
+    }
+
+    fn fill(&mut self, composite: &Composite, path: &path::Path, fill_rule: FillRule,
+            tolerance: f32) {
+        // Unbounded operators change the rows outside the path as well
+        let rows = match path.extents() {
+            _ if !composite.operator.is_bounded() => 0..self.height,
+            Some(extents) => self.rows_reached(&extents, 1.),
+            None => return,
+        };
+        let top = rows.start;
+        self.draw_rows(rows, |band, up| {
+            let clip = composite.clip.map(|clip| clip.rows(top, band.height));
+            let composite = Composite { source_matrix: composite.source_matrix.multiply(up),
+                                        clip: clip.as_ref(), ..*composite };
+            band.fill(&composite, &path.transform(up), fill_rule, tolerance);
+        });
+    }
+
+    fn stroke(&mut self, composite: &Composite, path: &path::Path, matrix: &Matrix,
+              style: &StrokeStyle, dash: Option<&DashPattern>, tolerance: f32) {
+        // How far the stroke reaches past its path, at most: a miter's tip, or a square cap's
+        // corner
+        let join = match style.join {
+            LineJoin::Miter => style.miter_limit.max(SQRT_2),
+            _ => SQRT_2,
+        };
+        let reach = style.width / 2. * join * matrix.max_scale();
+        let rows = match path.transform(matrix).extents() {
+            _ if !composite.operator.is_bounded() => 0..self.height,
+            Some(extents) => self.rows_reached(&extents, reach + 1.),
+            None => return,
+        };
+        let top = rows.start;
+        self.draw_rows(rows, |band, up| {
+            let clip = composite.clip.map(|clip| clip.rows(top, band.height));
+            let composite = Composite { source_matrix: composite.source_matrix.multiply(up),
+                                        clip: clip.as_ref(), ..*composite };
+            band.stroke(&composite, path, &matrix.multiply(up), style, dash, tolerance);
+        });
+    }
+
+    fn mask_coverage(&mut self, composite: &Composite, mask: &CoverageMask) {
+        let rows = if composite.operator.is_bounded() {
+            let extents = Rectangle::new(mask.x as f32, mask.y as f32, mask.width as f32,
+                                         mask.height as f32);
+            self.rows_reached(&extents, 0.)
+        } else {
+            0..self.height
+        };
+        let top = rows.start;
+        self.draw_rows(rows, |band, up| {
+            let clip = composite.clip.map(|clip| clip.rows(top, band.height));
+            let composite = Composite { source_matrix: composite.source_matrix.multiply(up),
+                                        clip: clip.as_ref(), ..*composite };
+            let mask = CoverageMask { y: mask.y - top as i32, ..mask.clone() };
+            band.mask_coverage(&composite, &mask);
+        });
+    }
+}
+
+// Composites the packed premultiplied ARGB `source` over `destination`.  Each channel becomes
+// source + destination * (255 - source alpha) / 255, rounded, and saturates at 255.  Two
+// channels are worked on at once in the 0x00ff00ff lanes of a word.
+fn over_argb32(source: u32, destination: u32) -> u32 {
+    let remaining = 255 - (source >> 24);
+    let over = |source: u32, destination: u32| {
+        let scaled = destination * remaining + 0x0080_0080;
+        let scaled = ((scaled + ((scaled >> 8) & 0x00ff_00ff)) >> 8) & 0x00ff_00ff;
+        let sum = source + scaled;
+        (sum | (0x0100_0100 - ((sum >> 8) & 0x0001_0001))) & 0x00ff_00ff
+    };
+    over(source & 0x00ff_00ff, destination & 0x00ff_00ff)
+        | over((source >> 8) & 0x00ff_00ff, (destination >> 8) & 0x00ff_00ff) << 8
+}
+
+/// Write access to an ImageSurface's bytes, returned by `ImageSurface::data_mut()`.
+///
+/// Dereferences to the byte buffer.  The surface's pixels are updated from it when the guard is
+/// dropped.
+pub struct ImageData<'a> {
+    surface: &'a mut ImageSurface,
+}
+
+impl<'a> Deref for ImageData<'a> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.surface.data
+    }
+}
+
+impl<'a> DerefMut for ImageData<'a> {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        &mut self.surface.data
+    }
+}
+
+impl<'a> Drop for ImageData<'a> {
+    fn drop(&mut self) {
+        self.surface.unpack_data();
+    }
+}
+
+/// A rectangle of an ImageSurface's pixels, returned by `ImageSurface::sub_surface()`.
+///
+/// Its coordinates start at the rectangle's top left corner.  Filters and compositors can work
+/// on one region, or hand tiles to different threads, without offsetting every index.
+#[derive(Debug, Clone, Copy)]
+pub struct SubSurface<'a> {
+    // From the rectangle's top left pixel to its bottom right one
+    pixels: &'a [Rgba],
+    // Pixels from one row of the surface to the next
+    stride: usize,
+    width: usize,
+    height: usize,
+}
+
+impl<'a> SubSurface<'a> {
+    /// Returns the view's `(width, height)` in pixels.
+    pub fn dimensions(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    /// Returns the pixel at (`x`, `y`) in the view, or None if it lies outside.
+    pub fn get(&self, x: usize, y: usize) -> Option<&'a Rgba> {
+        if x < self.width && y < self.height {
+            Some(&self.pixels[y * self.stride + x])
+        } else {
+            None
+        }
+    }
+
+    /// Returns the view's rows from the top, each a slice of its width.
+    pub fn rows(&self) -> impl Iterator<Item = &'a [Rgba]> {
+        let (pixels, stride, width) = (self.pixels, self.stride, self.width);
+        (0..self.height).map(move |row| &pixels[row * stride..row * stride + width])
+    }
+
+    /// Returns the view's pixels, row by row from the top left.
+    pub fn iter(&self) -> impl Iterator<Item = &'a Rgba> {
+        self.rows().flat_map(|row| row.iter())
+    }
+}
+
+/// A mutable rectangle of an ImageSurface's pixels, returned by
+/// `ImageSurface::sub_surface_mut()`.
+///
+/// Like SubSurface, its coordinates start at the rectangle's top left corner.
+#[derive(Debug)]
+pub struct SubSurfaceMut<'a> {
+    pixels: &'a mut [Rgba],
+    stride: usize,
+    width: usize,
+    height: usize,
+}
+
+impl<'a> SubSurfaceMut<'a> {
+    /// Returns the view's `(width, height)` in pixels.
+    pub fn dimensions(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    /// Returns the pixel at (`x`, `y`) in the view, or None if it lies outside.
+    pub fn get(&self, x: usize, y: usize) -> Option<&Rgba> {
+        if x < self.width && y < self.height {
+            Some(&self.pixels[y * self.stride + x])
+        } else {
+            None
+        }
+    }
+
+    /// Returns the pixel at (`x`, `y`) in the view mutably, or None if it lies outside.
+    pub fn get_mut(&mut self, x: usize, y: usize) -> Option<&mut Rgba> {
+        if x < self.width && y < self.height {
+            Some(&mut self.pixels[y * self.stride + x])
+        } else {
+            None
+        }
+    }
+
+    /// Returns a read-only view of the same pixels.
+    pub fn as_sub_surface(&self) -> SubSurface<'_> {
+        SubSurface { pixels: self.pixels, stride: self.stride, width: self.width,
+                     height: self.height }
+    }
+
+    /// Returns the view's rows from the top, each a mutable slice of its width.
+    pub fn rows_mut(&mut self) -> impl Iterator<Item = &mut [Rgba]> {
+        let width = self.width;
+        // The last row of the view ends at its last pixel rather than a whole stride later
+        self.pixels.chunks_mut(self.stride).take(self.height).map(move |row| &mut row[..width])
+    }
+
+    /// Returns the view's pixels mutably, row by row from the top left.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut Rgba> {
+        self.rows_mut().flat_map(|row| row.iter_mut())
+    }
+}
+
+/// A cloneable, thread-safe handle to an ImageSurface.
+///
+/// ImageSurface itself is `Send` and `Sync`, but drawing needs `&mut` access.  A
+/// SharedImageSurface lets several threads hold the same surface (for example a render thread and
+/// an encode/present thread) and hands out exclusive access through `write()`.  Any number of
+/// readers can hold `read()` guards at the same time, but never alongside a writer.
+///
+/// # Usage
+/// let mut guard = shared.write();
+/// let mut context = Context::create(&mut *guard);
+/// context.paint();
+///
+/// # Panics
+/// `read()` and `write()` panic if another thread panicked while holding a write guard, since the
+/// surface may have been left half-drawn.
+#[derive(Clone)]
+pub struct SharedImageSurface {
+    inner: Arc<RwLock<ImageSurface>>,
+}
+
+impl SharedImageSurface {
+    /// Moves `surface` into a new shared handle.
+    pub fn new(surface: ImageSurface) -> SharedImageSurface {
+        SharedImageSurface {
+            inner: Arc::new(RwLock::new(surface)),
+        }
+    }
+
+    /// Blocks until no writer holds the surface, then returns shared read access to it.
+    pub fn read(&self) -> RwLockReadGuard<'_, ImageSurface> {
+        self.inner.read().expect("error: a thread panicked while drawing to this surface")
+    }
+
+    /// Blocks until no other guard is held, then returns exclusive access to the surface.
+    pub fn write(&self) -> RwLockWriteGuard<'_, ImageSurface> {
+        self.inner.write().expect("error: a thread panicked while drawing to this surface")
+    }
+
+    /// Returns the number of handles that share this surface.
+    pub fn handle_count(&self) -> usize {
+        Arc::strong_count(&self.inner)
+    }
+
+    /// Returns the surface if this is the last handle to it, otherwise gives the handle back.
+    pub fn try_unwrap(self) -> Result<ImageSurface, SharedImageSurface> {
+        match Arc::try_unwrap(self.inner) {
+            Ok(lock) => Ok(lock.into_inner()
+                .expect("error: a thread panicked while drawing to this surface")),
+            Err(inner) => Err(SharedImageSurface { inner }),
+        }
+    }
+}
+
+// Returns a complete PNG chunk: length, type, data and CRC.
+fn png_chunk(chunk_type: &[u8; 4], data: &[u8]) -> Vec<u8> {
+    let mut chunk = Vec::with_capacity(data.len() + 12);
+    let length = data.len() as u32;
+    chunk.extend_from_slice(&[(length >> 24) as u8, (length >> 16) as u8,
+                              (length >> 8) as u8, length as u8]);
+    chunk.extend_from_slice(chunk_type);
+    chunk.extend_from_slice(data);
+    // The CRC covers the chunk type and data, but not the length
+    let crc = crc32(&chunk[4..]);
+    chunk.extend_from_slice(&[(crc >> 24) as u8, (crc >> 16) as u8, (crc >> 8) as u8, crc as u8]);
+    chunk
+}
+
+// The CRC-32 used by PNG (ISO 3309, polynomial 0xedb88320), computed bitwise.
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = 0xffff_ffffu32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            if crc & 1 == 1 {
+                crc = (crc >> 1) ^ 0xedb8_8320;
+            } else {
+                crc >>= 1;
+            }
+        }
+    }
+    !crc
+}
+
+impl IntoIterator for ImageSurface {
+    type Item = Rgba;
+    type IntoIter = IntoIter<Rgba>;
+
+    fn into_iter(mut self) -> Self::IntoIter {
+        let format = self.format;
+        for pixel in self.base.iter_mut() {
+            *pixel = format.convert(pixel);
+        }
+        self.base.into_iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use types::{Filter, Rgba};
+    use surfaces::{BitDepth, ImageSurface, PackedImageSurface, SharedImageSurface, ChannelOrder,
+                   ColorSpace, Format, InvalidData, OutOfBounds, Surface, SurfaceError,
+                   aligned_rectangle, composite, composite_transformed, crc32, over_argb32};
+    use matrix::Matrix;
+    use status::Status;
+    use context::Context;
+    use common_geometry::{Ellipse, LineSegment, Point, Rectangle};
+    use std::f32::consts::PI;
+    use std::thread;
+    use operators::{Operator, fetch_operator};
+    use surfaces::image::GenericImage;
+
+    use std::fs;
+    use std::io::Read;
+    use std::path::Path;
+    extern crate image;
+
+
+    #[test]
+    fn test_image_surface_create() {
+        // Test that ImageSurface's IntoIterator is functioning correctly by comparing every pixel
+        // in the surface to the default (which is transparent).
+        let transparent_pixel = Rgba::new(0., 0., 0., 0.);
+        let surface = ImageSurface::create_for_format(Format::ARGB32, 100, 100);
+        for pixel in surface {
+            assert_eq!(pixel, transparent_pixel);
+        }
+    }
+
+    #[test]
+    fn test_pack_takes_a_quarter_of_the_memory() {
+        // Setup
+        let mut surface = ImageSurface::create_for_format(Format::ARGB32, 100, 100);
+        surface.set_pixel(7, 9, Rgba::new(0.2, 0.4, 0.6, 0.8)).unwrap();
+        let expected = surface.convert(Format::ARGB32).data().to_vec();
+
+        // Call
+        let packed = surface.pack();
+
+        // Test
+        assert_eq!(packed.data().len(), 100 * 100 * 4);
+        assert_eq!(packed.data(), &expected[..]);
+        let mut unpacked = packed.unpack();
+        assert_eq!(unpacked.data(), &expected[..]);
+        assert_eq!(unpacked.get_format(), Format::ARGB32);
+    }
+
+    #[test]
+    fn test_packed_pixel_accessors() {
+        for &format in [Format::ARGB32, Format::RGB24, Format::A8, Format::A1].iter() {
+            // Setup
+            let mut packed = ImageSurface::create_for_format(format, 12, 3).pack();
+            let color = Rgba::new(0.2, 0.4, 0.6, 0.8);
+
+            // Call
+            packed.set_pixel(10, 2, color).unwrap();
+            packed.set_pixel(9, 2, color).unwrap();
+            packed.set_pixel(9, 2, Rgba::new(0., 0., 0., 0.)).unwrap();
+
+            // Test
+            let row = &packed.data()[2 * packed.get_stride()..];
+            let expected = ImageSurface::unpack_pixel(format, row, 10);
+            assert_eq!(packed.get_pixel(10, 2), Ok(expected));
+            assert!((expected.alpha - format.convert(&color).alpha).abs() < 1. / 255.);
+            assert_eq!(packed.get_pixel(9, 2), Ok(format.convert(&Rgba::new(0., 0., 0., 0.))));
+            assert!(packed.get_pixel(12, 0).is_err());
+            assert!(packed.set_pixel(0, 3, color).is_err());
+        }
+    }
+
+    #[test]
+    fn test_packed_composite_span_matches_float() {
+        let colors = [Rgba::new(0.9, 0.3, 0.1, 0.7), Rgba::new(0.1, 0.2, 0.3, 1.)];
+        let operators = [Operator::Over, Operator::Source, Operator::Xor];
+        for color in colors.iter() {
+            for operator in operators.iter() {
+                // Setup
+                let mut surface = ImageSurface::create_for_format(Format::ARGB32, 8, 2);
+                for (index, pixel) in surface.iter_mut().enumerate() {
+                    let value = index as f32 / 16.;
+                    *pixel = Rgba::new(value, 1. - value, 0.5, 0.25 + value / 2.);
+                }
+                let mut packed = surface.pack();
+                let mut expected = ImageSurface::create_for_data(packed.data().to_vec(),
+                                                                 Format::ARGB32, 8, 2, 32)
+                    .unwrap();
+                for pixel in expected.iter_mut().skip(8 + 2).take(6) {
+                    fetch_operator(operator)(color, pixel);
+                }
+
+                // Call
+                packed.composite_span(operator, color, 2, 1, 10).unwrap();
+
+                // Test
+                for (packed, expected) in packed.data().iter().zip(expected.data().iter()) {
+                    assert!((*packed as i32 - *expected as i32).abs() <= 1);
+                }
+                assert!(packed.composite_span(operator, color, 8, 0, 1).is_err());
+            }
+        }
+    }
+
+    #[test]
+    fn test_composite_surfaces() {
+        // Setup
+        let blue = Rgba::new(0., 0., 1., 1.);
+        let red = Rgba::new(1., 0., 0., 0.5);
+        let mut destination = ImageSurface::create_for_format(Format::ARGB32, 4, 4);
+        for pixel in destination.iter_mut() {
+            *pixel = blue;
+        }
+        let mut sprite = ImageSurface::create_for_format(Format::ARGB32, 2, 3);
+        for pixel in sprite.iter_mut() {
+            *pixel = red;
+        }
+        sprite.set_pixel(0, 0, Rgba::new(0., 1., 0., 1.)).unwrap();
+
+        // Call
+        composite(&sprite, &mut destination, &Operator::Source, 3, -1);
+        composite(&sprite, &mut destination, &Operator::Over, -1, 3);
+        composite(&sprite, &mut destination, &Operator::Clear, 4, 0);
+        composite(&sprite, &mut destination, &Operator::Clear, -2, -3);
+
+        // Test
+        // Only the overlaps change: column 3 of the top two rows, and pixel (0, 3).  The green
+        // corner of the sprite is clipped away both times.
+        let mut over = blue;
+        fetch_operator(&Operator::Over)(&red, &mut over);
+        for (position, pixel) in destination.iter().enumerate() {
+            let expected = match (position % 4, position / 4) {
+                (3, 0) | (3, 1) => red,
+                (0, 3) => over,
+                _ => blue,
+            };
+            assert_eq!(*pixel, expected);
+        }
+    }
+
+    #[test]
+    fn test_composite_transformed() {
+        // Setup
+        let blue = Rgba::new(0., 0., 1., 1.);
+        let colors = [Rgba::new(1., 0., 0., 1.), Rgba::new(0., 1., 0., 1.),
+                      Rgba::new(0., 0., 0., 1.), Rgba::new(1., 1., 1., 1.)];
+        let mut destination = ImageSurface::create_for_format(Format::ARGB32, 6, 6);
+        for pixel in destination.iter_mut() {
+            *pixel = blue;
+        }
+        let mut sprite = ImageSurface::create_for_format(Format::ARGB32, 2, 2);
+        for (pixel, color) in sprite.iter_mut().zip(colors.iter()) {
+            *pixel = *color;
+        }
+        let matrix = Matrix::scaling(2., 2.).multiply(&Matrix::translation(1., 1.));
+
+        // Call
+        composite_transformed(&sprite, &mut destination, &Operator::Source, &matrix,
+                              Filter::Nearest);
+
+        // Test
+        // Each sprite pixel becomes a 2x2 block, one pixel in from the corner, and the border
+        // around the blocks keeps its color even though the operator is Source
+        for (position, pixel) in destination.iter().enumerate() {
+            let (x, y) = (position % 6, position / 6);
+            let expected = if x == 0 || y == 0 || x == 5 || y == 5 {
+                blue
+            } else {
+                colors[(y - 1) / 2 * 2 + (x - 1) / 2]
+            };
+            assert_eq!(*pixel, expected);
+        }
+    }
+
+    #[test]
+    fn test_fill_rectangles_unbounded() {
+        // Setup
+        let mut surface = ImageSurface::create_for_format(Format::ARGB32, 3, 1);
+        for pixel in surface.iter_mut() {
+            *pixel = Rgba::new(0., 0., 1., 1.);
+        }
+
+        // Call
+        surface.fill_rectangles(&Operator::DestIn, &Rgba::new(1., 0., 0., 0.5),
+                                &[Rectangle::new(1., 0., 1., 1.)]);
+
+        // Test
+        // DestIn keeps the destination as far as the source is there, so nothing outside it
+        assert_eq!(*surface.get(0, 0).unwrap(), Rgba::new(0., 0., 0., 0.));
+        assert_eq!(*surface.get(1, 0).unwrap(), Rgba::new(0., 0., 1., 0.5));
+        assert_eq!(*surface.get(2, 0).unwrap(), Rgba::new(0., 0., 0., 0.));
+    }
+
+    #[test]
+    fn test_linear_blending() {
+        // Setup
+        let mut gamma = ImageSurface::create_for_format(Format::ARGB32, 1, 1);
+        gamma.set_pixel(0, 0, Rgba::new(1., 1., 1., 1.)).unwrap();
+        let mut linear = gamma.clone();
+        linear.set_linear_blending(true);
+
+        // Call
+        for surface in [&mut gamma, &mut linear] {
+            let mut context = Context::create(surface);
+            context.set_source_rgba(0., 0., 0., 0.5);
+            context.paint();
+        }
+
+        // Test
+        // Blended as they are, half black over white is 0.5, which shows as a quarter of the
+        // light.  Blended in linear light it is half the light, which encodes to 188.
+        assert_eq!(gamma.to_bytes(ChannelOrder::Rgba), vec![128, 128, 128, 255]);
+        assert_eq!(linear.to_bytes(ChannelOrder::Rgba), vec![188, 188, 188, 255]);
+        assert!((linear.get(0, 0).unwrap().red - 0.5).abs() < 1e-6);
+        assert!((linear.get_pixel(0, 0).unwrap().red - 0.7354).abs() < 1e-3);
+        assert_eq!(linear.data()[..4], [188, 188, 188, 255]);
+    }
+
+    #[test]
+    fn test_set_linear_blending_keeps_the_image() {
+        // Setup
+        let mut surface = ImageSurface::create_for_format(Format::ARGB32, 2, 1);
+        surface.set_pixel(0, 0, Rgba::new(0.2, 0.4, 0.6, 0.8)).unwrap();
+        surface.set_pixel(1, 0, Rgba::new(1., 0.5, 0., 1.)).unwrap();
+        let bytes = surface.to_bytes(ChannelOrder::Rgba);
+
+        // Call
+        surface.set_linear_blending(true);
+
+        // Test
+        assert!(surface.get_linear_blending());
+        assert!((surface.get(1, 0).unwrap().green - 0.214).abs() < 1e-3);
+        let pixel = surface.get_pixel(0, 0).unwrap();
+        assert!((pixel.green - 0.32).abs() < 1e-5);
+        for (linear, gamma) in surface.to_bytes(ChannelOrder::Rgba).iter().zip(bytes.iter()) {
+            assert!((*linear as i32 - *gamma as i32).abs() <= 1);
+        }
+
+        // Packing keeps the bytes in sRGB, and unpacking brings back linear light
+        let unpacked = surface.clone().pack().unpack();
+        assert!(unpacked.get_linear_blending());
+        assert!((unpacked.get(1, 0).unwrap().green - 0.214).abs() < 1e-2);
+        surface.set_linear_blending(false);
+        assert!((surface.get(1, 0).unwrap().green - 0.5).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_packed_composite_span_blends_linear() {
+        // Setup
+        let mut surface = ImageSurface::create_for_format(Format::ARGB32, 4, 1);
+        for pixel in surface.iter_mut() {
+            *pixel = Rgba::new(1., 1., 1., 1.);
+        }
+        surface.set_linear_blending(true);
+        let mut packed = surface.pack();
+
+        // Call
+        packed.composite_span(&Operator::Over, &Rgba::new(0., 0., 0., 0.5), 1, 0, 2).unwrap();
+
+        // Test
+        let data = packed.clone().unpack().to_bytes(ChannelOrder::Rgba);
+        assert_eq!(data[..4], [255, 255, 255, 255]);
+        assert_eq!(data[4..12], [188, 188, 188, 255, 188, 188, 188, 255]);
+        assert_eq!(data[12..], [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn test_packed_create_for_format() {
+        // Call
+        let packed = PackedImageSurface::create_for_format(Format::ARGB32, 10, 3);
+        let error = PackedImageSurface::try_create_for_format(Format::ARGB32, 0, 3);
+
+        // Test
+        assert_eq!(packed.data(), &[0; 120][..]);
+        assert!(packed == ImageSurface::create_for_format(Format::ARGB32, 10, 3).pack());
+        assert_eq!(error.err().map(|error| error.status), Some(Status::InvalidSize));
+    }
+
+    #[test]
+    fn test_context_draws_on_packed_surface() {
+        // Setup
+        let draw = |surface: &mut dyn Surface| {
+            let mut context = Context::create(surface);
+            context.set_source_rgba(0.2, 0.4, 0.6, 0.8);
+            context.paint();
+            context.arc(20., 20., 15., 0., 2. * PI);
+            context.clip();
+            context.set_source_rgba(1., 0., 0., 0.7);
+            context.move_to(3.5, 10.25);
+            context.line_to(37., 16.5);
+            context.line_to(12., 33.75);
+            context.fill();
+            context.reset_clip();
+            context.set_source_rgba(0., 0., 1., 1.);
+            context.set_line_width(3.);
+            context.move_to(5., 36.);
+            context.line_to(35., 6.);
+            context.stroke();
+            // Unbounded, so the rows above and below the path are cleared too
+            context.set_operator(Operator::In);
+            context.move_to(0., 4.);
+            context.line_to(40., 4.);
+            context.line_to(40., 30.5);
+            context.close_path();
+            context.fill();
+        };
+        let mut expected = ImageSurface::create_for_format(Format::ARGB32, 40, 40);
+        let mut packed = PackedImageSurface::create_for_format(Format::ARGB32, 40, 40);
+
+        // Call
+        draw(&mut expected);
+        draw(&mut packed);
+
+        // Test
+        // Packing rounds after every operation rather than once at the end
+        let expected = expected.pack();
+        assert!(expected.data().iter().any(|&byte| byte != 0));
+        for (&byte, &expected) in packed.data().iter().zip(expected.data()) {
+            assert!((byte as i32 - expected as i32).abs() <= 1, "{} != {}", byte, expected);
+        }
+        assert_eq!(&packed.data()[..4 * 40 * 4], &[0; 4 * 40 * 4][..]);
+    }
+
+    #[test]
+    fn test_over_argb32_saturates() {
+        // Opaque white over anything stays white, and channels over their alpha don't carry
+        assert_eq!(over_argb32(0xffff_ffff, 0x1234_5678), 0xffff_ffff);
+        assert_eq!(over_argb32(0x80ff_ffff, 0xffff_ffff), 0xffff_ffff);
+        assert_eq!(over_argb32(0x0000_0000, 0x1234_5678), 0x1234_5678);
+    }
+
+    #[test]
+    fn test_image_surface_pixel_accessors() {
+        // Setup
+        let mut surface = ImageSurface::create_for_format(Format::ARGB32, 4, 3);
+        let red = Rgba::new(1., 0., 0., 1.);
+
+        // Call
+        let result = surface.set_pixel(3, 2, red);
+
+        // Test
+        assert_eq!(result, Ok(()));
+        assert_eq!(surface.dimensions(), (4, 3));
+        assert_eq!(surface.get_pixel(3, 2), Ok(red));
+        assert_eq!(surface.get_pixel(0, 0), Ok(Rgba::new(0., 0., 0., 0.)));
+    }
+
+    #[test]
+    fn test_image_surface_pixel_accessors_out_of_bounds() {
+        let mut surface = ImageSurface::create_for_format(Format::ARGB32, 4, 3);
+        let error = OutOfBounds { x: 4, y: 0, width: 4, height: 3 };
+
+        // x past the right edge is not wrapped onto the next row
+        assert_eq!(surface.get_pixel(4, 0), Err(error));
+        assert_eq!(surface.set_pixel(4, 0, Rgba::new(1., 1., 1., 1.)), Err(error));
+        assert_eq!(surface.get_pixel(1, 0), Ok(Rgba::new(0., 0., 0., 0.)));
+        assert!(surface.get_pixel(0, 3).is_err());
+        assert_eq!(error.to_string(), "pixel (4, 0) is outside the 4x3 surface");
+    }
+
+    // Returns a 4x3 surface whose pixel at (x, y) has red x / 10 and green y / 10
+    fn numbered_surface() -> ImageSurface {
+        let mut surface = ImageSurface::create_for_format(Format::ARGB32, 4, 3);
+        for (y, row) in surface.rows_mut().enumerate() {
+            for (x, pixel) in row.iter_mut().enumerate() {
+                *pixel = Rgba::new(x as f32 / 10., y as f32 / 10., 0., 1.);
+            }
+        }
+        surface
+    }
+
+    #[test]
+    fn test_from_vec() {
+        // Setup
+        let surface = numbered_surface();
+        let white = Rgba::new(1., 1., 1., 1.);
+
+        // Call
+        let pixels = surface.clone().into_iter().map(|pixel| pixel.interpolate(&white, 0.5));
+        let faded = ImageSurface::from_vec(pixels.collect(), 4, 3).unwrap();
+
+        // Test
+        assert_eq!(faded.dimensions(), (4, 3));
+        assert_eq!(faded.get_format(), Format::ARGB32);
+        assert_eq!(faded.get_pixel(2, 1), Ok(Rgba::new(0.6, 0.55, 0.5, 1.)));
+        assert_eq!(ImageSurface::from_vec(surface.iter().cloned().collect(), 4, 3), Ok(surface));
+        let error = SurfaceError { status: Status::InvalidSize,
+                                   reason: "pixel count is not width * height" };
+        assert_eq!(ImageSurface::from_vec(vec![white; 11], 4, 3), Err(error.clone()));
+        assert_eq!(ImageSurface::from_vec(vec![white; 13], 4, 3), Err(error));
+        assert!(ImageSurface::from_vec(Vec::new(), 0, 3).is_err());
+    }
+
+    #[test]
+    fn test_from_pixels() {
+        let white = Rgba::new(1., 1., 1., 1.);
+        let surface = ImageSurface::from_pixels((0..6).map(|_| white), 3, 2).unwrap();
+        assert!(surface.iter().all(|pixel| *pixel == white));
+        assert!(ImageSurface::from_pixels((0..5).map(|_| white), 3, 2).is_err());
+        // An endless iterator is cut short rather than collected forever
+        assert!(ImageSurface::from_pixels(::std::iter::repeat(white), 3, 2).is_err());
+    }
+
+    #[test]
+    fn test_rows() {
+        let surface = numbered_surface();
+        assert_eq!(surface.rows().len(), 3);
+        for (y, row) in surface.rows().enumerate() {
+            assert_eq!(row.len(), 4);
+            assert_eq!(row[2], Rgba::new(0.2, y as f32 / 10., 0., 1.));
+        }
+        assert_eq!(*surface.get(3, 1).unwrap(), Rgba::new(0.3, 0.1, 0., 1.));
+    }
+
+    #[test]
+    fn test_sub_surface() {
+        // Setup
+        let surface = numbered_surface();
+
+        // Call
+        let tile = surface.sub_surface(1, 1, 2, 2).unwrap();
+
+        // Test
+        assert_eq!(tile.dimensions(), (2, 2));
+        assert_eq!(*tile.get(0, 0).unwrap(), Rgba::new(0.1, 0.1, 0., 1.));
+        assert_eq!(*tile.get(1, 1).unwrap(), Rgba::new(0.2, 0.2, 0., 1.));
+        assert_eq!(tile.get(2, 0), None);
+        let rows = tile.rows().collect::<Vec<_>>();
+        assert_eq!(rows, vec![surface.rows().nth(1).unwrap()[1..3].to_vec(),
+                              surface.rows().nth(2).unwrap()[1..3].to_vec()]);
+        assert_eq!(tile.iter().count(), 4);
+        // The bottom right corner fits exactly
+        assert!(surface.sub_surface(2, 1, 2, 2).is_ok());
+        assert_eq!(surface.sub_surface(4, 0, 0, 3).unwrap().dimensions(), (0, 0));
+        assert_eq!(surface.sub_surface(3, 1, 2, 2).unwrap_err(),
+                   OutOfBounds { x: 4, y: 2, width: 4, height: 3 });
+        assert!(surface.sub_surface(0, usize::MAX, 1, 2).is_err());
+    }
+
+    #[test]
+    fn test_sub_surface_mut() {
+        // Setup
+        let mut surface = numbered_surface();
+        let white = Rgba::new(1., 1., 1., 1.);
+
+        // Call
+        {
+            let mut tile = surface.sub_surface_mut(2, 0, 2, 3).unwrap();
+            assert_eq!(*tile.as_sub_surface().get(0, 2).unwrap(), Rgba::new(0.2, 0.2, 0., 1.));
+            for pixel in tile.iter_mut() {
+                *pixel = white;
+            }
+            *tile.get_mut(0, 0).unwrap() = Rgba::new(0., 0., 0., 0.);
+        }
+
+        // Test
+        // Only the right two columns change
+        for (y, row) in surface.rows().enumerate() {
+            for (x, pixel) in row.iter().enumerate() {
+                let expected = match (x, y) {
+                    (2, 0) => Rgba::new(0., 0., 0., 0.),
+                    (2, _) | (3, _) => white,
+                    _ => Rgba::new(x as f32 / 10., y as f32 / 10., 0., 1.),
+                };
+                assert_eq!(*pixel, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_create_for_format_clears_like_cairo() {
+        let surface = ImageSurface::create_for_format(Format::RGB24, 2, 2);
+        assert_eq!(surface.get_format(), Format::RGB24);
+        assert_eq!(surface.get_pixel(1, 1), Ok(Rgba::new(0., 0., 0., 1.)));
+
+        let surface = ImageSurface::create_for_format(Format::A8, 2, 2);
+        assert_eq!(surface.get_pixel(1, 1), Ok(Rgba::new(0., 0., 0., 0.)));
+        assert_eq!(ImageSurface::create_for_format(Format::ARGB32, 2, 2).get_format(),
+                   Format::ARGB32);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_create_for_unsupported_format() {
+        ImageSurface::create_for_format(Format::RGB30, 2, 2);
+    }
+
+    #[test]
+    fn test_format_keeps_only_stored_channels() {
+        // Setup
+        let mut surface = ImageSurface::create_for_format(Format::A8, 2, 1);
+        let color = Rgba::new(1., 0.5, 0.25, 0.5);
+
+        // Call
+        surface.set_pixel(0, 0, color).unwrap();
+
+        // Test
+        assert_eq!(surface.get_pixel(0, 0), Ok(Rgba::new(0., 0., 0., 0.5)));
+        assert_eq!(surface.to_bytes(ChannelOrder::Rgba)[..4], [0, 0, 0, 128]);
+        assert_eq!(surface.to_alpha_bytes(), vec![128, 0]);
+        assert_eq!(Format::RGB24.convert(&color).alpha, 1.);
+        assert_eq!(Format::A1.convert(&color), Rgba::new(0., 0., 0., 1.));
+        assert_eq!(Format::A1.convert(&Rgba::new(1., 1., 1., 0.4)), Rgba::new(0., 0., 0., 0.));
+    }
+
+    #[test]
+    fn test_accessors_agree_with_get_pixel() {
+        // Drawing stores pixels as the format keeps them, so every accessor sees what get_pixel()
+        // does
+        for &format in [Format::RGB24, Format::A8, Format::A1].iter() {
+            // Setup
+            let mut surface = ImageSurface::create_for_format(format, 4, 2);
+            let color = Rgba::new(1., 0.5, 0.25, 0.75);
+
+            // Call
+            surface.fill_rectangles(&Operator::Over, &color, &[Rectangle::new(0., 0., 2.5, 2.)]);
+            surface.set_pixel(3, 1, Rgba::new(0., 1., 0., 0.25)).unwrap();
+
+            // Test
+            for (index, pixel) in surface.iter().enumerate() {
+                let (x, y) = (index % 4, index / 4);
+                let expected = surface.get_pixel(x, y).unwrap();
+                assert_eq!(*pixel, expected, "{:?} ({}, {})", format, x, y);
+                assert_eq!(*surface.get(x, y).unwrap(), expected);
+                assert_eq!(surface.rows().nth(y).unwrap()[x], expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_drawing_keeps_format_of_rows_reached() {
+        // Only the rows drawing reaches are brought back to the format, so a pixel written
+        // elsewhere without converting it is left as it was
+        // Setup
+        let mut surface = ImageSurface::create_for_format(Format::A8, 4, 10);
+        let written = Rgba::new(0.5, 0., 0., 0.5);
+        *surface.iter_mut().next().unwrap() = written;
+        let red = Rgba::new(1., 0., 0., 1.);
+
+        // Call
+        surface.fill_rectangles(&Operator::Over, &red, &[Rectangle::new(0., 5.5, 4., 1.)]);
+        {
+            let mut context = Context::create(&mut surface);
+            context.set_source_rgba(1., 0., 0., 1.);
+            context.move_to(0.5, 8.25);
+            context.line_to(3.5, 8.25);
+            context.line_to(2., 9.75);
+            context.fill();
+        }
+
+        // Test
+        assert_eq!(surface.get(0, 0), Some(&written));
+        for y in [5, 6, 8, 9].iter() {
+            let pixel = surface.get(2, *y).unwrap();
+            assert!(pixel.alpha > 0. && pixel.red == 0., "{:?} at row {}", pixel, y);
+        }
+    }
+
+    #[test]
+    fn test_convert_between_formats() {
+        // Setup
+        let mut surface = ImageSurface::create_for_format(Format::ARGB32, 2, 1);
+        surface.set_pixel(0, 0, Rgba::new(1., 0., 0., 0.75)).unwrap();
+        surface.set_color_space(ColorSpace::DisplayP3);
+
+        // Call
+        let mask = surface.convert(Format::A1);
+        let back = mask.convert(Format::ARGB32);
+
+        // Test
+        assert_eq!(mask.get_format(), Format::A1);
+        assert_eq!(mask.get_color_space(), ColorSpace::DisplayP3);
+        assert_eq!(mask.get_pixel(0, 0), Ok(Rgba::new(0., 0., 0., 1.)));
+        assert_eq!(mask.get_pixel(1, 0), Ok(Rgba::new(0., 0., 0., 0.)));
+        assert_eq!(back.get_pixel(0, 0), Ok(Rgba::new(0., 0., 0., 1.)));
+    }
+
+    #[test]
+    fn test_stride_for_width() {
+        assert_eq!(Format::ARGB32.stride_for_width(3), Some(12));
+        assert_eq!(Format::A8.stride_for_width(5), Some(8));
+        assert_eq!(Format::A1.stride_for_width(33), Some(8));
+        assert_eq!(Format::RGB16_565.stride_for_width(3), Some(8));
+        assert_eq!(Format::Invalid.stride_for_width(3), None);
+    }
+
+    #[test]
+    fn test_data_layout() {
+        // Setup
+        let mut surface = ImageSurface::create_for_format(Format::ARGB32, 2, 2);
+        surface.set_pixel(1, 0, Rgba::new(1., 0., 0., 1.)).unwrap();
+        surface.set_pixel(0, 1, Rgba::new(0., 0., 1., 0.2)).unwrap();
+
+        // Call
+        let stride = surface.get_stride();
+        let data = surface.data().to_vec();
+
+        // Test
+        assert_eq!(stride, 8);
+        assert_eq!(data.len(), 16);
+        let word = |offset: usize| {
+            u32::from_ne_bytes([data[offset], data[offset + 1], data[offset + 2],
+                                data[offset + 3]])
+        };
+        assert_eq!(word(0), 0);
+        assert_eq!(word(4), 0xffff0000);
+        assert_eq!(word(stride), 0x33000033);
+    }
+
+    #[test]
+    fn test_data_mut_updates_pixels() {
+        // Setup
+        let mut surface = ImageSurface::create_for_format(Format::A1, 9, 2);
+
+        // Call
+        {
+            let mut data = surface.data_mut();
+            let (index, mask) = ImageSurface::a1_bit(8);
+            data[index] |= mask;
+        }
+
+        // Test
+        assert_eq!(surface.get_pixel(8, 0), Ok(Rgba::new(0., 0., 0., 1.)));
+        assert_eq!(surface.get_pixel(7, 0), Ok(Rgba::new(0., 0., 0., 0.)));
+        assert_eq!(surface.get_pixel(8, 1), Ok(Rgba::new(0., 0., 0., 0.)));
+    }
+
+    #[test]
+    fn test_create_for_data() {
+        // A 1x2 RGB24 image with a row of padding, the second row green
+        let mut data = vec![0; 16];
+        data[8..12].copy_from_slice(&0x0000ff00u32.to_ne_bytes());
+
+        let mut surface = ImageSurface::create_for_data(data.clone(), Format::RGB24, 1, 2, 8)
+            .unwrap();
+
+        assert_eq!(surface.get_stride(), 8);
+        assert_eq!(surface.get_pixel(0, 0), Ok(Rgba::new(0., 0., 0., 1.)));
+        assert_eq!(surface.get_pixel(0, 1), Ok(Rgba::new(0., 1., 0., 1.)));
+        // Packing again sets the unused byte, and leaves the padding alone
+        data[3] = 255;
+        data[11] = 255;
+        assert_eq!(surface.data(), &data[..]);
+    }
+
+    #[test]
+    fn test_create_for_data_rejects_bad_buffers() {
+        let result = ImageSurface::create_for_data(vec![0; 16], Format::ARGB32, 2, 2, 6);
+        assert_eq!(result.err(), Some(InvalidData {
+            reason: "stride is too small or not a multiple of four"
+        }));
+        let result = ImageSurface::create_for_data(vec![0; 15], Format::ARGB32, 2, 2, 8);
+        assert!(result.is_err());
+        let result = ImageSurface::create_for_data(vec![0; 16], Format::RGB30, 2, 2, 8);
+        assert!(result.is_err());
+        // A row too wide to count in bytes is an error rather than an overflow
+        let result = ImageSurface::create_for_data(vec![0; 16], Format::ARGB32, usize::MAX, 1, 8);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_image_surface_into_iter() {
+        // Test that the explicit into_iter() call functions correctly.
+        let transparent_pixel = Rgba::new(0., 0., 0., 0.);
+        let surface = ImageSurface::create_for_format(Format::ARGB32, 100, 100);
+        for pixel in surface.into_iter() {
+            assert_eq!(pixel, transparent_pixel);
+        }
+    }
+
+    // TODO: test into_iter().map()
+
+    #[test]
+    fn test_image_surface_iter() {
+        // Passes if ImageSurface::iter() functions properly
+        let surface = ImageSurface::create_for_format(Format::ARGB32, 100, 100);
+
+        // Leave pixel.red to default (0.0), change all other channels to 1.0
+        let result = surface.iter()
+            .map(|&pixel| {
+                Rgba {
+                    red: pixel.red,
+                    green: 1.,
+                    blue: 1.,
+                    alpha: 1.,
+                }
+            })
+            .collect::<Vec<Rgba>>();
+
+        let expected = Rgba {
+            red: 0.,
+            green: 1.,
+            blue: 1.,
+            alpha: 1.,
+        };
+
+        for pixel in result.into_iter() {
+            // Red is 0. because it is the default, the others got set to 1.
+            assert_eq!(pixel, expected);
+        }
+    }
+
+    #[test]
+    fn test_image_surface_iter_mut() {
+        // Passes if ImageSurface::iter_mut() functions properly
+        let mut surface = ImageSurface::create_for_format(Format::ARGB32, 100, 100);
+        let expected = Rgba::new(1., 0., 0., 1.);
+
+        for pixel in surface.iter_mut() {
+            pixel.alpha = expected.alpha;
+            pixel.red = expected.red;
+        }
+
+        for pixel in surface {
+            assert_eq!(pixel, expected);
+        }
+    }
+
+    #[test]
+    fn test_image_surface_with_operator() {
+        // Demonstrates usage with an operator
+        //
+        // Our goal here is to take a surface and paint it red.  We use the the surface's iter_mut
+        // function because operators modify the image's pixels in-place.
+
+        // Create our source Rgba, destination, and choose an operator
+        let source_rgba = Rgba::new(1., 0., 0., 1.);
+        let mut destination = ImageSurface::create_for_format(Format::ARGB32, 100, 100);
+        let op = Operator::Over;
+
+        // Using fetch_operator and the Operator enum.
+        let operator = fetch_operator(&op);
+        for pixel in destination.iter_mut() {
+            operator(&source_rgba, pixel);
+        }
+
+        // Check that the resulting pixels in destination are red RGBA(1, 0, 0, 1)
+        let expected = Rgba::new(1., 0., 0., 1.);
+        for pixel in destination {
+            assert_eq!(pixel, expected);
+        }
+    }
+
+    #[test]
+    fn test_into_bytes() {
+        // verifies that into bytes returns the correct number of bytes and all bytes are correct
+
+        // Setup
+        let surface = ImageSurface::create_for_format(Format::ARGB32, 100, 100);
+
+        // Call
+        let result = surface.into_bytes();
+
+        // Test
+        assert_eq!(40000, result.len(), "Error: Byte count incorrect");
+        for byte in result {
+            assert_eq!(0, byte, "Error: Byte value incorrect");
+        }
+    }
+
+    #[test]
+    fn test_to_file_output_correct_dimensions() {
+        // Writes image surface to file then verifies image in file has correct dimensions.
+
+        //setup
+        let surface = ImageSurface::create_for_format(Format::ARGB32, 100, 100);
+        let path = Path::new("test2.png");
+        let expected_width = surface.width as u32;
+        let expected_height = surface.height as u32;
+
+        //call
+        surface.to_file(path).unwrap();
+        let img = image::open(path).unwrap();
+        let (result_width, result_height) = img.dimensions();
+
+        //test
+        assert_eq!(result_width, expected_width, "Error: width was not as expected");
+        assert_eq!(result_height, expected_height, "Error: width was not as expected");
+
+        // Cleanup
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_int_to_file_integrity_per_pixel() {
+        // Writes image surface to file then verifies file content is as expected
+
+        // Setup
+        let surface = ImageSurface::create_for_format(Format::ARGB32, 100, 100);
+        let transparent_pixel = Rgba::new(0.,0.,0.,0.);
+        let path = Path::new("test3.png");
+
+        // Call
+        surface.to_file(path).unwrap();
+        let img = image::open(path).unwrap().to_rgba();
+
+        // Test
+        for pixel in img.pixels() {
+            let (r,g,b,a) = (pixel.data[0], pixel.data[1], pixel.data[2], pixel.data[3]);
+            let result = Rgba::new(r as f32, g as f32, b as f32, a as f32);
+            assert_eq!(result,transparent_pixel, "Error: Image integrity failed");
+        }
+        // Cleanup
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_into_words_be() {
+        // Verifies the 16-bit buffer has 8 bytes per pixel, high byte first
+
+        // Setup
+        let mut surface = ImageSurface::create_for_format(Format::ARGB32, 2, 1);
+        *surface.get_mut(0, 0).unwrap() = Rgba::new(1., 0., 0.25, 1.);
+
+        // Call
+        let result = surface.into_words_be();
+
+        // Test
+        assert_eq!(16, result.len(), "Error: Byte count incorrect");
+        assert_eq!(&result[0..8], &[0xff, 0xff, 0x00, 0x00, 0x40, 0x00, 0xff, 0xff]);
+        assert_eq!(&result[8..16], &[0; 8]);
+    }
+
+    #[test]
+    fn test_to_png16_header() {
+        // Writes a 16-bit PNG and verifies the IHDR chunk declares RGBA with 16 bits per channel
+
+        // Setup
+        let surface = ImageSurface::create_for_format(Format::ARGB32, 30, 20);
+        let path = Path::new("deep_color.png");
+
+        // Call
+        surface.to_file_with_depth(path, BitDepth::Sixteen).unwrap();
+        let bytes = read_bytes(path);
+
+        // Test
+        assert_eq!(&bytes[12..16], b"IHDR");
+        assert_eq!(&bytes[16..24], &[0, 0, 0, 30, 0, 0, 0, 20]);
+        assert_eq!(bytes[24], 16, "Error: bit depth is not 16");
+        assert_eq!(bytes[25], 6, "Error: color type is not RGBA");
+        let jpeg = surface.to_file_with_depth(Path::new("deep_color.jpg"), BitDepth::Sixteen);
+        assert_eq!(jpeg.unwrap_err().status, Status::WriteError);
+
+        // Cleanup
+        fs::remove_file(path).unwrap();
+    }
+
+    // Returns the bytes of the file at `path`.
+    fn read_bytes(path: &Path) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        fs::File::open(path).unwrap().read_to_end(&mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn test_crc32() {
+        // Known CRC of the IEND chunk type, which has no data
+        assert_eq!(crc32(b"IEND"), 0xae42_6082);
+    }
+
+    #[test]
+    fn test_default_color_space_is_srgb() {
+        let surface = ImageSurface::create_for_format(Format::ARGB32, 10, 10);
+        assert_eq!(surface.get_color_space(), ColorSpace::Srgb);
+    }
+
+    #[test]
+    fn test_to_png_writes_srgb_chunk() {
+        // Verifies an sRGB chunk immediately follows IHDR, and the file still decodes
+
+        // Setup
+        let surface = ImageSurface::create_for_format(Format::ARGB32, 10, 10);
+        let path = Path::new("srgb_tagged.png");
+
+        // Call
+        surface.to_file(path).unwrap();
+        let bytes = read_bytes(path);
+
+        // Test
+        assert_eq!(&bytes[33..37], &[0, 0, 0, 1]);
+        assert_eq!(&bytes[37..41], b"sRGB");
+        assert_eq!(bytes[41], 0);
+        assert!(image::open(path).is_ok(), "Error: tagged PNG no longer decodes");
+
+        // Cleanup
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_to_png_writes_cicp_chunk() {
+        // Verifies Display P3 and linear surfaces are tagged with cICP chunks
+
+        let cases = [
+            (ColorSpace::DisplayP3, [12, 13, 0, 1], "p3_tagged.png"),
+            (ColorSpace::LinearSrgb, [1, 8, 0, 1], "linear_tagged.png"),
+        ];
+        for &(color_space, expected, name) in cases.iter() {
+            // Setup
+            let mut surface = ImageSurface::create_for_format(Format::ARGB32, 10, 10);
+            surface.set_color_space(color_space);
+            let path = Path::new(name);
+
+            // Call
+            surface.to_file(path).unwrap();
+            let bytes = read_bytes(path);
+
+            // Test
+            assert_eq!(&bytes[33..37], &[0, 0, 0, 4]);
+            assert_eq!(&bytes[37..41], b"cICP");
+            assert_eq!(&bytes[41..45], &expected);
+            assert!(image::open(path).is_ok(), "Error: tagged PNG no longer decodes");
+
+            // Cleanup
+            fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_int_to_jpg_file_created() {
+        // Writes image surface to file and verifies file was created
+
+        // Setup
+        let surface = ImageSurface::create_for_format(Format::ARGB32, 100, 100);
+        let path = Path::new("test3.jpg");
+
+        // Call
+        surface.to_file(path).unwrap();
+
+        // Test
+        assert!(Path::new(path).exists(), "Error: JPG file was not created");
+
+        // Cleanup
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
     fn test_int_to_file_uppercase_extension() {
         // Writes image surface to file and verifies file was created
 
         // Setup
-        let surface = ImageSurface::create(100, 100);
+        let surface = ImageSurface::create_for_format(Format::ARGB32, 100, 100);
         let path = Path::new("test3.JPG");
 
         // Call
-        surface.to_file(path);
+        surface.to_file(path).unwrap();
 
         // Test
         assert!(Path::new(path).exists(), "Error: JPG file was not created");
 
         // Cleanup
-        let _ = fs::remove_file(path).unwrap();
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_int_to_jpeg_extension() {
+        // Writes image surface to file and verifies file was created
+
+        // Setup
+        let surface = ImageSurface::create_for_format(Format::ARGB32, 100, 100);
+        let path = Path::new("test4.jpeg");
+
+        // Call
+        surface.to_file(path).unwrap();
+
+        // Test
+        assert!(Path::new(path).exists(), "Error: JPEG file was not created");
+
+        // Cleanup
+        fs::remove_file(path).unwrap();
     }
 
     #[test]
@@ -411,17 +3685,17 @@ mod tests {
         // Tests private to_png() function
 
         // Setup
-        let surface = ImageSurface::create(100, 100);
+        let surface = ImageSurface::create_for_format(Format::ARGB32, 100, 100);
         let path = Path::new("pngfile.png");
 
         // Call
-        surface.to_file(path);
+        surface.to_file(path).unwrap();
 
         // Test
         assert!(Path::new(path).exists(), "Error: PNG file was not created");
 
         // Cleanup
-        let _ = fs::remove_file(path).unwrap();
+        fs::remove_file(path).unwrap();
     }
 
     #[test]
@@ -429,17 +3703,17 @@ mod tests {
         // Tests private to_jpg() function
 
         // Setup
-        let surface = ImageSurface::create(100, 100);
+        let surface = ImageSurface::create_for_format(Format::ARGB32, 100, 100);
         let path = Path::new("jaypeg.jpg");
 
         // Call
-        surface.to_file(path);
+        surface.to_file(path).unwrap();
 
         // Test
         assert!(Path::new(path).exists(), "Error: JPG file was not created");
 
         // Cleanup
-        let _ = fs::remove_file(path).unwrap();
+        fs::remove_file(path).unwrap();
     }
 
     #[test]
@@ -448,7 +3722,7 @@ mod tests {
         // Verifies we cannot create a image with a 0 height value
 
         // Call and panic
-        let _ = ImageSurface::create(100, 0);
+        let _ = ImageSurface::create_for_format(Format::ARGB32, 100, 0);
     }
 
     #[test]
@@ -457,28 +3731,660 @@ mod tests {
         // Verifies we cannot create a image with a 0 width value
 
         // Call and panic
-        let _ = ImageSurface::create(0, 100);
+        let _ = ImageSurface::create_for_format(Format::ARGB32, 0, 100);
     }
 
     #[test]
-    #[should_panic]
     fn test_invalid_file_extension() {
         // Verifies we cannot create a image with a invalid type
 
         // Setup
-        let surface = ImageSurface::create(100, 100);
+        let surface = ImageSurface::create_for_format(Format::ARGB32, 100, 100);
         let path = Path::new("test_extension.uyk");
 
-        // Call and panic
-        let _ = surface.to_file(path);
+        // Call
+        let result = surface.to_file(path);
 
-        // Cleanup
-        let _ = fs::remove_file(path).unwrap();
+        // Test
+        assert_eq!(result.unwrap_err().status, Status::WriteError);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_to_file_reports_write_errors() {
+        let surface = ImageSurface::create_for_format(Format::ARGB32, 2, 2);
+        let error = surface.to_file(Path::new("no-such-directory/image.png")).unwrap_err();
+        assert_eq!(error, SurfaceError { status: Status::WriteError,
+                                         reason: "file can't be created" });
+        assert_eq!(error.to_string(),
+                   "error while writing to output stream: file can't be created");
+    }
+
+    #[test]
+    fn test_try_create_for_format() {
+        let surface = ImageSurface::try_create_for_format(Format::A8, 3, 2).unwrap();
+        assert_eq!(surface, ImageSurface::create_for_format(Format::A8, 3, 2));
+        let status = |format, width, height| {
+            ImageSurface::try_create_for_format(format, width, height).unwrap_err().status
+        };
+        assert_eq!(status(Format::ARGB32, 0, 2), Status::InvalidSize);
+        assert_eq!(status(Format::RGB16_565, 3, 2), Status::InvalidFormat);
+        assert_eq!(status(Format::ARGB32, usize::MAX, 2), Status::InvalidSize);
+        assert_eq!(status(Format::ARGB32, usize::MAX / 64, 1), Status::NoMemory);
+    }
+
+    #[cfg(feature = "jpeg")]
+    #[test]
+    fn test_create_from_jpeg() {
+        // Encodes a small two-color JPEG in memory, then verifies the decoded surface has the
+        // right dimensions and roughly the right colors (JPEG is lossy).
+        use surfaces::image::jpeg::JPEGEncoder;
+        use surfaces::image::ColorType;
+
+        // Setup: left half red, right half blue, 16x8 pixels
+        let (width, height) = (16, 8);
+        let mut raw = Vec::new();
+        for _ in 0..height {
+            for x in 0..width {
+                if x < width / 2 {
+                    raw.extend_from_slice(&[255, 0, 0]);
+                } else {
+                    raw.extend_from_slice(&[0, 0, 255]);
+                }
+            }
+        }
+        let mut jpeg = Vec::new();
+        JPEGEncoder::new_with_quality(&mut jpeg, 100)
+            .encode(&raw, width, height, ColorType::RGB(8))
+            .unwrap();
+
+        // Call
+        let surface = ImageSurface::create_from_jpeg(jpeg.as_slice()).unwrap();
+
+        // Test
+        assert_eq!(surface.width, width as usize);
+        assert_eq!(surface.height, height as usize);
+        let left = surface.get(1, 4).unwrap();
+        let right = surface.get(14, 4).unwrap();
+        assert_eq!(left.alpha, 1.);
+        assert!(left.red > 0.9 && left.blue < 0.1);
+        assert!(right.blue > 0.9 && right.red < 0.1);
+    }
+
+    #[cfg(feature = "jpeg")]
+    #[test]
+    fn test_create_from_jpeg_invalid_data() {
+        // Verifies garbage input is reported as an error instead of panicking
+        let garbage = vec![0u8, 1, 2, 3, 4, 5];
+        assert!(ImageSurface::create_from_jpeg(garbage.as_slice()).is_err());
+    }
+
+    #[test]
+    fn test_aligned_rectangle() {
+        let rectangle = ::path::Path::from_rectangles(&[Rectangle::new(1., 2., 3., 4.)]);
+        assert_eq!(aligned_rectangle(&rectangle),
+                   Some(::common_geometry::RectangleInt::new(1, 2, 3, 4)));
+        // Wound the other way, and closed by returning to the start
+        let mut reversed = ::path::Path::new();
+        reversed.move_to(4., 6.).line_to(4., 2.).line_to(1., 2.).line_to(1., 6.)
+                .line_to(4., 6.);
+        assert_eq!(aligned_rectangle(&reversed), aligned_rectangle(&rectangle));
+
+        let unaligned = ::path::Path::from_rectangles(&[Rectangle::new(1.5, 2., 3., 4.)]);
+        assert_eq!(aligned_rectangle(&unaligned), None);
+        let two = ::path::Path::from_rectangles(&[Rectangle::new(1., 2., 3., 4.),
+                                                  Rectangle::new(6., 2., 3., 4.)]);
+        assert_eq!(aligned_rectangle(&two), None);
+        let mut skewed = ::path::Path::new();
+        skewed.move_to(0., 0.).line_to(2., 0.).line_to(3., 2.).line_to(1., 2.).close_path();
+        assert_eq!(aligned_rectangle(&skewed), None);
+        let empty = ::path::Path::from_rectangles(&[Rectangle::new(1., 2., 0., 4.)]);
+        assert_eq!(aligned_rectangle(&empty), None);
+    }
+
+    #[test]
+    fn test_fill_aligned_rectangle_matches_the_sweep() {
+        // Fills a 2x3 rectangle, through the fast path for aligned rectangles or, with an extra
+        // corner in the middle of its top side, through the sweep
+        let fill = |fast: bool, clipped: bool, alpha: f32| {
+            let mut surface = ImageSurface::create_for_format(Format::ARGB32, 8, 8);
+            {
+                let mut context = Context::create(&mut surface);
+                context.set_source_rgba(0., 0., 1., 1.);
+                context.paint();
+                if clipped {
+                    context.move_to(0., 0.);
+                    context.line_to(8., 0.);
+                    context.line_to(0., 8.);
+                    context.clip();
+                }
+                context.set_source_rgba(1., 0., 0., alpha);
+                context.translate(2., 1.);
+                context.move_to(0., 0.);
+                if !fast {
+                    context.line_to(1., 0.);
+                }
+                context.line_to(2., 0.);
+                context.line_to(2., 3.);
+                context.line_to(0., 3.);
+                context.close_path();
+                context.fill();
+            }
+            surface
+        };
+
+        for &(clipped, alpha) in [(false, 1.), (false, 0.5), (true, 1.)].iter() {
+            assert_eq!(fill(true, clipped, alpha), fill(false, clipped, alpha));
+        }
+        let filled = fill(true, false, 1.);
+        assert_eq!(*filled.get(3, 3).unwrap(), Rgba::new(1., 0., 0., 1.));
+        assert_eq!(*filled.get(4, 3).unwrap(), Rgba::new(0., 0., 1., 1.));
+    }
+
+    #[test]
+    fn test_fill_rectangles_pixel_aligned() {
+        // Pixel-aligned rectangles fill exactly the pixels they cover, and nothing else
+
+        // Setup
+        let mut surface = ImageSurface::create_for_format(Format::ARGB32, 10, 10);
+        let color = Rgba::new(1., 0., 0., 1.);
+        let rectangles = [Rectangle::new(1., 1., 2., 3.), Rectangle::new(6., 5., 3., 2.)];
+
+        // Call
+        surface.fill_rectangles(&Operator::Over, &color, &rectangles);
+
+        // Test
+        for y in 0..10 {
+            for x in 0..10 {
+                let inside = ((1..3).contains(&x) && (1..4).contains(&y)) ||
+                             ((6..9).contains(&x) && (5..7).contains(&y));
+                let pixel = surface.get(x, y).unwrap();
+                if inside {
+                    assert_eq!(*pixel, color, "Error: ({}, {}) should be filled", x, y);
+                } else {
+                    assert_eq!(pixel.alpha, 0., "Error: ({}, {}) should be empty", x, y);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_fill_rectangles_partial_coverage() {
+        // A rectangle that only covers half of a column antialiases that column
+
+        // Setup
+        let mut surface = ImageSurface::create_for_format(Format::ARGB32, 4, 4);
+        let color = Rgba::new(0., 0., 1., 1.);
+
+        // Call
+        surface.fill_rectangles(&Operator::Over, &color, &[Rectangle::new(0., 0., 1.5, 4.)]);
+
+        // Test
+        assert_eq!(surface.get(0, 2).unwrap().alpha, 1.);
+        assert_eq!(surface.get(1, 2).unwrap().alpha, 0.5);
+        assert_eq!(surface.get(2, 2).unwrap().alpha, 0.);
+    }
+
+    #[test]
+    fn test_fill_rectangles_overlap_is_union() {
+        // Overlapping semi-transparent rectangles composite once, not twice
+
+        // Setup
+        let mut surface = ImageSurface::create_for_format(Format::ARGB32, 4, 4);
+        let color = Rgba::new(0., 1., 0., 0.5);
+        let rectangles = [Rectangle::new(0., 0., 3., 3.), Rectangle::new(1., 1., 3., 3.)];
+
+        // Call
+        surface.fill_rectangles(&Operator::Over, &color, &rectangles);
+
+        // Test
+        assert_eq!(*surface.get(2, 2).unwrap(), color);
+        assert_eq!(*surface.get(0, 0).unwrap(), color);
+        assert_eq!(*surface.get(3, 3).unwrap(), color);
+        assert_eq!(surface.get(3, 0).unwrap().alpha, 0.);
+    }
+
+    #[test]
+    fn test_fill_rectangles_clipped_to_surface() {
+        // Rectangles hanging off the surface, or with negative sizes, must not panic
+
+        // Setup
+        let mut surface = ImageSurface::create_for_format(Format::ARGB32, 4, 4);
+        let color = Rgba::new(1., 1., 1., 1.);
+        let rectangles = [
+            Rectangle::new(-10., -10., 12., 12.),
+            Rectangle::new(10., 10., -7., -7.),
+            Rectangle::new(100., 100., 5., 5.),
+        ];
+
+        // Call
+        surface.fill_rectangles(&Operator::Source, &color, &rectangles);
+
+        // Test
+        assert_eq!(*surface.get(0, 0).unwrap(), color);
+        assert_eq!(*surface.get(3, 3).unwrap(), color);
+        assert_eq!(surface.get(2, 0).unwrap().alpha, 0.);
+    }
+
+    #[test]
+    fn test_fill_ellipses() {
+        // A circle is solid in the middle, antialiased on its edge and symmetric
+
+        // Setup
+        let mut surface = ImageSurface::create_for_format(Format::ARGB32, 20, 20);
+        let color = Rgba::new(1., 0., 0., 1.);
+
+        // Call
+        surface.fill_ellipses(&Operator::Over, &color,
+                              &[Ellipse::circle(10., 10., 6.), Ellipse::new(0., 0., 0., 5.)]);
+
+        // Test
+        assert_eq!(*surface.get(10, 10).unwrap(), color);
+        assert_eq!(*surface.get(5, 10).unwrap(), color);
+        assert_eq!(surface.get(0, 0).unwrap().alpha, 0.);
+        assert_eq!(surface.get(17, 17).unwrap().alpha, 0.);
+        let edge = surface.get(4, 10).unwrap().alpha;
+        assert!(edge > 0. && edge < 1.);
+        for y in 0..20 {
+            for x in 0..20 {
+                let mirrored = surface.get(19 - x, y).unwrap().alpha;
+                assert!((surface.get(x, y).unwrap().alpha - mirrored).abs() < 1e-5);
+            }
+        }
+    }
+
+    #[test]
+    fn test_fill_ellipses_off_surface() {
+        // Ellipses hanging off any side are clipped without panicking
+        let mut surface = ImageSurface::create_for_format(Format::ARGB32, 8, 8);
+        let color = Rgba::new(1., 1., 1., 1.);
+        let ellipses = [Ellipse::new(0., 4., 3., 20.), Ellipse::circle(-50., -50., 10.)];
+
+        surface.fill_ellipses(&Operator::Source, &color, &ellipses);
+
+        assert_eq!(*surface.get(0, 0).unwrap(), color);
+        assert_eq!(*surface.get(0, 7).unwrap(), color);
+        assert_eq!(surface.get(5, 4).unwrap().alpha, 0.);
+    }
+
+    #[test]
+    fn test_stroke_segments_axis_aligned() {
+        // A horizontal and a vertical segment of width 2 cover exactly two rows and two columns
+
+        // Setup
+        let mut surface = ImageSurface::create_for_format(Format::ARGB32, 10, 10);
+        let color = Rgba::new(1., 0., 0., 1.);
+        let segments = [LineSegment::new(1., 2., 9., 2.), LineSegment::new(5., 4., 5., 9.)];
+
+        // Call
+        surface.stroke_segments(&Operator::Over, &color, &segments, 2.);
+
+        // Test
+        for y in 0..10 {
+            for x in 0..10 {
+                let inside = ((1..9).contains(&x) && (1..3).contains(&y)) ||
+                             ((4..6).contains(&x) && (4..9).contains(&y));
+                let pixel = surface.get(x, y).unwrap();
+                if inside {
+                    assert_eq!(*pixel, color, "({}, {})", x, y);
+                } else {
+                    assert_eq!(pixel.alpha, 0., "({}, {})", x, y);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_stroke_segments_diagonal_and_overlap() {
+        // Crossing diagonals are antialiased, and where they cross the union is filled once
+
+        // Setup
+        let mut surface = ImageSurface::create_for_format(Format::ARGB32, 10, 10);
+        let color = Rgba::new(0., 0., 0., 0.5);
+        let segments = [LineSegment::new(0., 0., 10., 10.), LineSegment::new(10., 0., 0., 10.)];
+
+        // Call
+        surface.stroke_segments(&Operator::Over, &color, &segments, 1.5);
+
+        // Test
+        assert_eq!(surface.get(5, 5).unwrap().alpha, 0.5);
+        assert_eq!(surface.get(2, 2).unwrap().alpha, 0.5);
+        let edge = surface.get(3, 2).unwrap().alpha;
+        assert!(edge > 0. && edge < 0.5);
+        assert_eq!(surface.get(5, 1).unwrap().alpha, 0.);
+    }
+
+    #[test]
+    fn test_stroke_segments_hairline() {
+        // One unit wide Over strokes take the Wu path, which plots diagonals one pixel per column
+
+        // Setup
+        let mut surface = ImageSurface::create_for_format(Format::ARGB32, 10, 10);
+        let color = Rgba::new(0., 1., 0., 1.);
+        let segments = [LineSegment::new(0.5, 0.5, 9.5, 9.5), LineSegment::new(-5., 3., 20., 3.)];
+
+        // Call
+        surface.stroke_segments(&Operator::Over, &color, &segments, 1.);
+
+        // Test
+        for i in 1..9 {
+            assert_eq!(surface.get(i, i).unwrap().green, 1.);
+        }
+        // Ending on a pixel center covers half of that pixel
+        assert_eq!(surface.get(0, 0).unwrap().alpha, 0.5);
+        assert_eq!(surface.get(5, 4).unwrap().alpha, 0.);
+        assert_eq!(surface.get(7, 2).unwrap().alpha, 0.5);
+        assert_eq!(surface.get(7, 3).unwrap().alpha, 0.5);
+        assert_eq!(surface.get(7, 4).unwrap().alpha, 0.);
+    }
+
+    #[test]
+    fn test_stroke_polyline_gradient() {
+        // The color follows the length of an L-shaped route, not its bounding box
+
+        // Setup
+        let mut surface = ImageSurface::create_for_format(Format::ARGB32, 20, 20);
+        let start = Rgba::new(1., 0., 0., 1.);
+        let end = Rgba::new(0., 0., 1., 1.);
+        let points = [Point::new(0., 2.), Point::new(10., 2.), Point::new(10., 12.)];
+
+        // Call
+        surface.stroke_polyline_gradient(&Operator::Source, &start, &end, &points, 2.);
+
+        // Test
+        // Pixel (4, 1) sits 4.5 units along a 20 unit stroke, pixel (9, 7) 15.5 units
+        let first = surface.get(4, 1).unwrap();
+        assert!((first.red - 0.775).abs() < 1e-5 && (first.blue - 0.225).abs() < 1e-5);
+        let second = surface.get(9, 7).unwrap();
+        assert!((second.red - 0.225).abs() < 1e-5 && (second.blue - 0.775).abs() < 1e-5);
+        assert_eq!(second.alpha, 1.);
+        assert_eq!(surface.get(15, 15).unwrap().alpha, 0.);
+    }
+
+    #[test]
+    fn test_stroke_polyline_gradient_degenerate() {
+        // Fewer than two distinct points draw nothing
+        let mut surface = ImageSurface::create_for_format(Format::ARGB32, 4, 4);
+        let color = Rgba::new(1., 1., 1., 1.);
+
+        surface.stroke_polyline_gradient(&Operator::Over, &color, &color, &[], 2.);
+        surface.stroke_polyline_gradient(&Operator::Over, &color, &color,
+                                         &[Point::new(1., 1.), Point::new(1., 1.)], 2.);
+
+        for pixel in surface.iter() {
+            assert_eq!(pixel.alpha, 0.);
+        }
+    }
+
+    #[test]
+    fn test_stroke_segments_off_surface() {
+        // Segments partly or entirely off the surface, or of zero length, must not panic
+        let mut surface = ImageSurface::create_for_format(Format::ARGB32, 4, 4);
+        let color = Rgba::new(1., 1., 1., 1.);
+        let segments = [
+            LineSegment::new(-20., 1., 20., 1.),
+            LineSegment::new(50., 50., 60., 70.),
+            LineSegment::new(2., 2., 2., 2.),
+        ];
+
+        surface.stroke_segments(&Operator::Source, &color, &segments, 2.);
+
+        assert_eq!(*surface.get(0, 0).unwrap(), color);
+        assert_eq!(*surface.get(3, 1).unwrap(), color);
+        assert_eq!(surface.get(2, 2).unwrap().alpha, 0.);
+    }
+
+    #[test]
+    fn test_to_bytes_channel_order() {
+        // Setup
+        let mut surface = ImageSurface::create_for_format(Format::ARGB32, 2, 1);
+        *surface.get_mut(0, 0).unwrap() = Rgba::new(1., 0.2, 0., 1.);
+        *surface.get_mut(1, 0).unwrap() = Rgba::new(0., 0., 1., 0.5);
+
+        // Call
+        let rgba = surface.to_bytes(ChannelOrder::Rgba);
+        let bgra = surface.to_bytes(ChannelOrder::Bgra);
+        let argb = surface.to_bytes(ChannelOrder::Argb);
+
+        // Test
+        assert_eq!(rgba, vec![255, 51, 0, 255, 0, 0, 255, 128]);
+        assert_eq!(bgra, vec![0, 51, 255, 255, 255, 0, 0, 128]);
+        assert_eq!(argb, vec![255, 255, 51, 0, 128, 0, 0, 255]);
+    }
+
+    #[test]
+    fn test_to_alpha_bytes() {
+        let mut surface = ImageSurface::create_for_format(Format::ARGB32, 3, 1);
+        *surface.get_mut(1, 0).unwrap() = Rgba::new(1., 1., 1., 0.5);
+        *surface.get_mut(2, 0).unwrap() = Rgba::new(0., 0., 0., 1.);
+
+        assert_eq!(surface.to_alpha_bytes(), vec![0, 128, 255]);
+    }
+
+    #[test]
+    fn test_to_u32_channel_order() {
+        let mut surface = ImageSurface::create_for_format(Format::ARGB32, 1, 1);
+        *surface.get_mut(0, 0).unwrap() = Rgba::new(1., 0.2, 0., 1.);
+
+        assert_eq!(surface.to_u32(ChannelOrder::Argb), vec![0xffff3300]);
+        assert_eq!(surface.to_u32(ChannelOrder::Rgba), vec![0xff3300ff]);
+        assert_eq!(surface.to_u32(ChannelOrder::Bgra), vec![0x0033ffff]);
+        // Packed ARGB is stored as BGRA bytes on little-endian machines
+        assert_eq!(surface.to_u32(ChannelOrder::Argb)[0].to_le_bytes(),
+                   [0x00, 0x33, 0xff, 0xff]);
+    }
+
+    #[test]
+    fn test_image_surface_is_send_and_sync() {
+        // Fails to compile if ImageSurface or SharedImageSurface stop being thread-safe
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<ImageSurface>();
+        assert_send_sync::<SharedImageSurface>();
+    }
+
+    #[test]
+    fn test_shared_surface_drawn_on_another_thread() {
+        // A render thread paints the surface while the main thread keeps a handle to it
+
+        // Setup
+        let surface = ImageSurface::create_for_format(Format::ARGB32, 10, 10);
+        let shared = SharedImageSurface::new(surface);
+        let render_handle = shared.clone();
+        assert_eq!(shared.handle_count(), 2);
+
+        // Call
+        thread::spawn(move || {
+            let mut surface = render_handle.write();
+            let mut context = Context::create(&mut *surface);
+            context.set_source_rgba(0., 0., 1., 1.);
+            context.paint();
+        }).join().unwrap();
+
+        // Test
+        let expected = Rgba::new(0., 0., 1., 1.);
+        for pixel in shared.read().iter() {
+            assert_eq!(*pixel, expected);
+        }
+        assert_eq!(shared.handle_count(), 1);
+    }
+
+    #[test]
+    fn test_shared_surface_try_unwrap() {
+        // The surface can only be taken back out once every other handle is gone
+        let surface = ImageSurface::create_for_format(Format::ARGB32, 10, 10);
+        let shared = SharedImageSurface::new(surface);
+        let other = shared.clone();
+
+        let shared = shared.try_unwrap().err().unwrap();
+        drop(other);
+        let surface = shared.try_unwrap().ok().unwrap();
+        assert_eq!(surface.width, 10);
+    }
+
+    #[test]
+    fn test_show_page_and_copy_page() {
+        // Setup
+        let mut surface = ImageSurface::create_for_format(Format::ARGB32, 2, 2);
+        let red = Rgba::new(1., 0., 0., 1.);
+        {
+            let mut context = Context::create(&mut surface);
+            context.set_source_rgba(1., 0., 0., 1.);
+            context.paint();
+
+            // Call
+            context.copy_page();
+            context.show_page();
+            context.show_page();
+        }
+
+        // Test
+        let pages = surface.pages();
+        assert_eq!(pages.len(), 3);
+        assert_eq!(pages[0].get_pixel(1, 1), Ok(red));
+        assert_eq!(pages[1].get_pixel(1, 1), Ok(red));
+        assert_eq!(pages[2].get_pixel(1, 1), Ok(Rgba::new(0., 0., 0., 0.)));
+        assert_eq!(*surface.get(0, 0).unwrap(), Rgba::new(0., 0., 0., 0.));
+        assert_eq!(surface.take_pages().len(), 3);
+        assert!(surface.pages().is_empty());
+    }
+
+    #[test]
+    fn test_to_png_sequence() {
+        // Setup
+        let mut surface = ImageSurface::create_for_format(Format::ARGB32, 4, 4);
+        surface.show_page();
+        surface.show_page();
+
+        // Call
+        surface.to_png_sequence(Path::new("sequence.png")).unwrap();
+
+        // Test
+        for path in &["sequence-1.png", "sequence-2.png"] {
+            assert!(Path::new(path).exists());
+            fs::remove_file(path).unwrap();
+        }
+        assert!(!Path::new("sequence-3.png").exists());
+    }
+
+    // A surface without pixels that writes down what it is asked to draw
+    #[derive(Default)]
+    struct Log(Vec<String>);
+
+    impl super::Surface for Log {
+        fn extents(&self) -> Option<::common_geometry::RectangleInt> {
+            None
+        }
+
+        fn paint(&mut self, composite: &super::Composite) {
+            self.0.push(format!("paint {:?} {}", composite.operator, composite.clip.is_some()));
+        }
+
+        fn mask(&mut self, _: &super::Composite, _: &::pattern::Pattern, _: &::matrix::Matrix) {
+            self.0.push("mask".to_string());
+        }
+
+        fn fill(&mut self, composite: &super::Composite, path: &::path::Path,
+                fill_rule: ::types::FillRule, _: f32) {
+            self.0.push(format!("fill {} {:?} {:?}", path.elements().len(), fill_rule,
+                                composite.solid_color()));
+        }
+
+        fn stroke(&mut self, _: &super::Composite, path: &::path::Path, matrix: &::matrix::Matrix,
+                  style: &::stroke::StrokeStyle, _: Option<&::dash::DashPattern>, _: f32) {
+            self.0.push(format!("stroke {} {} {}", path.elements().len(), matrix.xx,
+                                style.width));
+        }
+    }
+
+    #[test]
+    fn test_context_draws_on_any_surface() {
+        // Setup
+        let mut log = Log::default();
+        {
+            let mut context = Context::create(&mut log);
+            context.set_source_rgba(1., 0., 0., 1.);
+
+            // Call
+            context.move_to(0., 0.);
+            context.line_to(10., 0.);
+            context.line_to(10., 10.);
+            context.line_to(0., 10.);
+            context.clip();
+            context.paint();
+            context.move_to(1., 1.);
+            context.line_to(2., 2.);
+            context.scale(2., 2.);
+            context.stroke();
+            context.fill_rectangles(&[Rectangle::new(0., 0., 1., 1.)]);
+
+            // Test
+            assert!(context.in_clip(2., 2.));
+            assert!(!context.in_clip(6., 2.));
+        }
+        let red = Rgba::new(1., 0., 0., 1.);
+        assert_eq!(log.0, vec!["paint Over true".to_string(),
+                               "stroke 2 2 2".to_string(),
+                               format!("fill 5 Winding {:?}", Some(red))]);
+    }
+
+    // Draws with only the Surface methods an ImageSurface has to provide, leaving the rest to
+    // the trait's defaults
+    struct Plain(ImageSurface);
+
+    impl super::Surface for Plain {
+        fn extents(&self) -> Option<::common_geometry::RectangleInt> {
+            super::Surface::extents(&self.0)
+        }
+
+        fn paint(&mut self, composite: &super::Composite) {
+            super::Surface::paint(&mut self.0, composite);
+        }
+
+        fn mask(&mut self, composite: &super::Composite, mask: &::pattern::Pattern,
+                matrix: &::matrix::Matrix) {
+            super::Surface::mask(&mut self.0, composite, mask, matrix);
+        }
+
+        fn fill(&mut self, composite: &super::Composite, path: &::path::Path,
+                fill_rule: ::types::FillRule, tolerance: f32) {
+            super::Surface::fill(&mut self.0, composite, path, fill_rule, tolerance);
+        }
+
+        fn stroke(&mut self, composite: &super::Composite, path: &::path::Path,
+                  matrix: &::matrix::Matrix, style: &::stroke::StrokeStyle,
+                  dash: Option<&::dash::DashPattern>, tolerance: f32) {
+            super::Surface::stroke(&mut self.0, composite, path, matrix, style, dash, tolerance);
+        }
+    }
+
+    #[test]
+    fn test_mask_coverage() {
+        // Setup
+        let mut mask = super::CoverageMask::new(-2, 3, 6, 4);
+        for (index, coverage) in mask.coverage.iter_mut().enumerate() {
+            *coverage = (index * 10) as u8;
+        }
+        let source = ::pattern::Pattern::rgba(0., 0., 1., 1.);
+        let composite = super::Composite::unclipped(Operator::Over, &source);
+        let mut direct = ImageSurface::create_for_format(Format::ARGB32, 8, 8);
+        let mut plain = Plain(ImageSurface::create_for_format(Format::ARGB32, 8, 8));
+
+        // Call
+        super::Surface::mask_coverage(&mut direct, &composite, &mask);
+        super::Surface::mask_coverage(&mut plain, &composite, &mask);
+
+        // Test
+        assert_eq!(direct.get(0, 3).unwrap().alpha, 20. / 255.);
+        assert_eq!(direct.get(3, 6).unwrap().alpha, 230. / 255.);
+        assert_eq!(direct.get(4, 6).unwrap().alpha, 0.);
+        assert_eq!(direct.get(0, 7).unwrap().alpha, 0.);
+        // The default, which goes through mask(), composites the same
+        assert_eq!(direct, plain.0);
     }
 
     #[test]
     fn image_surface_index() {
-        let destination = ImageSurface::create(100, 100);
+        let destination = ImageSurface::create_for_format(Format::ARGB32, 100, 100);
         let transparent_pixel = Rgba::new(0., 0., 0., 0.);
         assert_eq!(*destination.get(0, 0).unwrap(), transparent_pixel);
     }
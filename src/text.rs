@@ -0,0 +1,534 @@
+/*
+ * Cairus - a reimplementation of the cairo graphics library in Rust
+ *
+ * Copyright © 2017 CairusOrg
+ *
+ * This library is free software; you can redistribute it and/or
+ * modify it either under the terms of the GNU Lesser General Public
+ * License version 2.1 as published by the Free Software Foundation
+ * (the "LGPL") or, at your option, under the terms of the Mozilla
+ * Public License Version 2.0 (the "MPL"). If you do not alter this
+ * notice, a recipient may use your version of this file under either
+ * the MPL or the LGPL.
+ *
+ * You should have received a copy of the LGPL along with this library
+ * in the file LICENSE-LGPL-2_1; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Suite 500, Boston, MA 02110-1335, USA
+ * You should have received a copy of the MPL along with this library
+ * in the file LICENSE-MPL-2_0
+ *
+ * The contents of this file are subject to the Mozilla Public License
+ * Version 2.0 (the "License"); you may not use this file except in
+ * compliance with the License. You may obtain a copy of the License at
+ * http://www.mozilla.org/MPL/
+ *
+ * This software is distributed on an "AS IS" basis, WITHOUT WARRANTY
+ * OF ANY KIND, either express or implied. See the LGPL or the MPL for
+ * the specific language governing rights and limitations.
+ *
+ * The Original Code is the cairus graphics library.
+ *
+ * Contributor(s):
+ *	CairusOrg
+ *
+ */
+
+//! Cairo's "toy" text API: strings drawn in a simple font that is built in.
+//!
+//! The builtin font is a stroke font, in the manner of plotter fonts.  Each glyph is a few
+//! polylines on a grid four units wide, where the capitals stand six units tall on the baseline
+//! and descenders reach two units below it, with eight units to the em.  A glyph's outline is its
+//! polylines stroked with round caps and joins, so text fills like any other path.  Every glyph
+//! advances five units, which makes the font monospaced.
+//!
+//! Any family name selects the builtin font.  Bold strokes the polylines wider, and italic and
+//! oblique both slant the upright glyphs.  Characters outside printable ASCII are drawn as a box.
+//!
+//! A face can instead be made from a TrueType font loaded with the `font` module, in which case
+//! its glyphs, advances and extents all come from the font.
+
+use std::sync::Arc;
+use common_geometry::Point;
+use font::Font;
+use matrix::Matrix;
+use path::Path;
+use stroke::{StrokeStyle, stroke_outline};
+use types::{FontSlant, FontWeight, LineCap, LineJoin};
+
+// The size of a grid unit, in ems
+const UNIT: f32 = 1. / 8.;
+
+// How far, in grid units, each glyph moves the next along
+const ADVANCE: f32 = 5.;
+
+// The grid row of the baseline
+const BASELINE: f32 = 6.;
+
+// How far the slanted styles lean, in units across per unit up
+const SLANT: f32 = 0.25;
+
+/// ## FontFace
+///
+/// A font family, slant and weight to draw text with, or a loaded font.  Analogous to
+/// cairo_font_face_t.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FontFace {
+    family: String,
+    slant: FontSlant,
+    weight: FontWeight,
+    font: Option<Arc<Font>>,
+}
+
+impl FontFace {
+    /// Returns the face of `family` with `slant` and `weight`.  Analogous to
+    /// cairo_toy_font_face_create().
+    pub fn new(family: &str, slant: FontSlant, weight: FontWeight) -> FontFace {
+        FontFace {
+            family: family.to_string(),
+            slant,
+            weight,
+            font: None,
+        }
+    }
+
+    /// Returns a face that draws text with `font`.  The face has no family name, and is upright
+    /// at the normal weight.
+    ///
+    /// # Usage
+    /// let face = FontFace::from_font(Font::from_file(Path::new("DejaVuSans.ttf"))?);
+    pub fn from_font(font: Font) -> FontFace {
+        FontFace {
+            family: String::new(),
+            slant: FontSlant::Normal,
+            weight: FontWeight::Normal,
+            font: Some(Arc::new(font)),
+        }
+    }
+
+    /// Returns the loaded font the face draws with, or None for the builtin font.
+    pub fn get_font(&self) -> Option<&Font> {
+        self.font.as_deref()
+    }
+
+    // Returns the shared handle to the loaded font, which the glyph cache keys on
+    pub(crate) fn font_handle(&self) -> Option<&Arc<Font>> {
+        self.font.as_ref()
+    }
+
+    /// Returns the glyphs that set `text`, each placed after the one before by its advance.
+    ///
+    /// Analogous to cairo_scaled_font_text_to_glyphs(), at one em.  The builtin font's glyph
+    /// indices are the characters' code points.
+    pub fn text_to_glyphs(&self, text: &str) -> Vec<Glyph> {
+        let mut glyphs = Vec::new();
+        let mut pen = 0.;
+        for character in text.chars() {
+            let (index, advance) = match self.font {
+                Some(ref font) => {
+                    let index = font.glyph_index(character);
+                    (index as u32, font.glyph_advance(index))
+                },
+                None => (character as u32, ADVANCE * UNIT),
+            };
+            glyphs.push(Glyph { index, x: pen, y: 0. });
+            pen += advance;
+        }
+        glyphs
+    }
+
+    /// Returns the outline of the glyph at `index` one em high, with its origin at the origin,
+    /// to fill with `FillRule::Winding`.  See text_path() for `tolerance`.
+    pub fn glyph_path(&self, index: u32, tolerance: f32) -> Path {
+        match self.font {
+            Some(ref font) if index <= u16::MAX as u32 => font.glyph_path(index as u16),
+            Some(_) => Path::new(),
+            None => {
+                let character = char::from_u32(index).unwrap_or(char::REPLACEMENT_CHARACTER);
+                self.outline(&self.glyph_polylines(character, 0.), tolerance)
+            },
+        }
+    }
+
+    /// Returns the family name the face was selected with.
+    pub fn get_family(&self) -> &str {
+        &self.family
+    }
+
+    /// Returns the slant of the face.
+    pub fn get_slant(&self) -> FontSlant {
+        self.slant
+    }
+
+    /// Returns the weight of the face.
+    pub fn get_weight(&self) -> FontWeight {
+        self.weight
+    }
+
+    /// Returns the outline of `text` set one em high, with the origin on the baseline at the
+    /// start of the text and y pointing down, to fill with `FillRule::Winding`.  The builtin
+    /// font's round caps and joins are flattened to within `tolerance` ems, while a loaded font's
+    /// curves are kept.
+    pub fn text_path(&self, text: &str, tolerance: f32) -> Path {
+        if let Some(ref font) = self.font {
+            return font.text_path(text);
+        }
+        self.outline(&self.polylines(text), tolerance)
+    }
+
+    // Returns the outline of the builtin font's `polylines`, stroked at the face's weight
+    fn outline(&self, polylines: &[Vec<Point>], tolerance: f32) -> Path {
+        let mut skeleton = Path::new();
+        for polyline in polylines {
+            skeleton.move_to(polyline[0].x, polyline[0].y);
+            for point in &polyline[1..] {
+                skeleton.line_to(point.x, point.y);
+            }
+        }
+        let style = StrokeStyle {
+            join: LineJoin::Round,
+            cap: LineCap::Round,
+            ..StrokeStyle::new(self.stroke_width())
+        };
+        stroke_outline(&skeleton, &style, None, tolerance)
+    }
+
+    /// Returns the outline of `text` as text_path() does, transformed by `matrix`.  Curves are
+    /// flattened to within `tolerance` after the transformation, so `matrix` can take ems
+    /// straight to device space.  A matrix that collapses everything gives an empty outline.
+    pub fn text_path_with_matrix(&self, text: &str, matrix: &Matrix, tolerance: f32) -> Path {
+        let scale = matrix.max_scale();
+        if scale == 0. || !scale.is_finite() {
+            return Path::new();
+        }
+        self.text_path(text, tolerance / scale).transform(matrix)
+    }
+
+    /// Returns the extents of `text` set one em high, measured from the origin on the baseline
+    /// at its start.  Analogous to cairo_text_extents().
+    pub fn text_extents(&self, text: &str) -> TextExtents {
+        if let Some(ref font) = self.font {
+            return font_text_extents(font, text);
+        }
+        let advance = text.chars().count() as f32 * ADVANCE * UNIT;
+        let polylines = self.polylines(text);
+        let mut points = polylines.iter().flat_map(|polyline| polyline.iter());
+        let first = match points.next() {
+            Some(first) => *first,
+            None => return TextExtents { x_advance: advance, ..TextExtents::default() },
+        };
+        let (mut left, mut top, mut right, mut bottom) = (first.x, first.y, first.x, first.y);
+        for point in points {
+            left = left.min(point.x);
+            top = top.min(point.y);
+            right = right.max(point.x);
+            bottom = bottom.max(point.y);
+        }
+        // The round caps and joins reach half the stroke width past the polylines
+        let half_width = self.stroke_width() / 2.;
+        TextExtents {
+            x_bearing: left - half_width,
+            y_bearing: top - half_width,
+            width: right - left + 2. * half_width,
+            height: bottom - top + 2. * half_width,
+            x_advance: advance,
+            y_advance: 0.,
+        }
+    }
+
+    // Returns the width the glyphs' polylines are stroked with, in ems
+    fn stroke_width(&self) -> f32 {
+        match self.weight {
+            FontWeight::Normal => 0.6 * UNIT,
+            FontWeight::Bold => UNIT,
+        }
+    }
+
+    // Returns the polylines of every glyph of `text` one em high, placed along the baseline and
+    // slanted as the face asks
+    fn polylines(&self, text: &str) -> Vec<Vec<Point>> {
+        text.chars().enumerate().flat_map(|(index, character)| {
+            self.glyph_polylines(character, index as f32 * ADVANCE)
+        }).collect()
+    }
+
+    // Returns the polylines of the builtin glyph for `character` one em high, with its origin
+    // `left` grid units along the baseline
+    fn glyph_polylines(&self, character: char, left: f32) -> Vec<Vec<Point>> {
+        let slant = match self.slant {
+            FontSlant::Normal => 0.,
+            FontSlant::Italic | FontSlant::Oblique => SLANT,
+        };
+        // Half a unit either side of the grid spaces the glyphs
+        let left = left + 0.5;
+        glyph(character).split(';').filter(|polyline| !polyline.is_empty()).map(|polyline| {
+            polyline.split_whitespace().map(|point| {
+                let (x, y) = grid_point(point);
+                Point::new((left + x + slant * (BASELINE - y)) * UNIT, (y - BASELINE) * UNIT)
+            }).collect()
+        }).collect()
+    }
+}
+
+impl Default for FontFace {
+    /// Returns the face cairo starts with: the default family, upright, at the normal weight.
+    fn default() -> FontFace {
+        FontFace::new("sans-serif", FontSlant::Normal, FontWeight::Normal)
+    }
+}
+
+/// ## Glyph
+///
+/// A glyph of a face, and where its origin sits.  Analogous to cairo_glyph_t.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Glyph {
+    /// The glyph's index in the face.
+    pub index: u32,
+    /// How far right of the start of the text the glyph's origin is.
+    pub x: f32,
+    /// How far below the start of the text the glyph's origin is.
+    pub y: f32,
+}
+
+/// ## TextExtents
+///
+/// The measurements of a string of text.  Analogous to cairo_text_extents_t.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TextExtents {
+    /// How far right of the origin the ink starts.
+    pub x_bearing: f32,
+    /// How far below the origin the ink starts, which is negative for ink above the baseline.
+    pub y_bearing: f32,
+    /// The width of the ink.
+    pub width: f32,
+    /// The height of the ink.
+    pub height: f32,
+    /// How far right the text moves the current point.
+    pub x_advance: f32,
+    /// How far down the text moves the current point.
+    pub y_advance: f32,
+}
+
+// Returns the extents of `text` set in `font` one em high, from the bounding boxes of its glyphs
+fn font_text_extents(font: &Font, text: &str) -> TextExtents {
+    let mut pen = 0.;
+    let mut ink: Option<(f32, f32, f32, f32)> = None;
+    for character in text.chars() {
+        let glyph = font.glyph_index(character);
+        if let Some(bounds) = font.glyph_bounds(glyph) {
+            let (left, top) = (pen + bounds.x, bounds.y);
+            let (right, bottom) = (left + bounds.width, top + bounds.height);
+            ink = Some(match ink {
+                Some((l, t, r, b)) => (l.min(left), t.min(top), r.max(right), b.max(bottom)),
+                None => (left, top, right, bottom),
+            });
+        }
+        pen += font.glyph_advance(glyph);
+    }
+    let (left, top, right, bottom) = ink.unwrap_or((0., 0., 0., 0.));
+    TextExtents {
+        x_bearing: left,
+        y_bearing: top,
+        width: right - left,
+        height: bottom - top,
+        x_advance: pen,
+        y_advance: 0.,
+    }
+}
+
+// Returns the grid coordinates of `point`, an "x,y" pair from a builtin glyph
+fn grid_point(point: &str) -> (f32, f32) {
+    let mut coordinates = point.split(',').map(|value| value.parse::<f32>().unwrap_or(0.));
+    (coordinates.next().unwrap_or(0.), coordinates.next().unwrap_or(0.))
+}
+
+// Returns the polylines of the builtin glyph for `character`: points are "x,y" pairs on the
+// grid, separated by spaces, and polylines are separated by semicolons.  A polyline that stays
+// at one point is a dot.
+fn glyph(character: char) -> &'static str {
+    match character {
+        ' ' => "",
+        '!' => "2,0 2,4;2,6 2,6",
+        '"' => "1,0 1,1.5;3,0 3,1.5",
+        '#' => "1,1 1,5;3,1 3,5;0,2 4,2;0,4 4,4",
+        '$' => "4,1.5 3,1 1,1 0,2 1,3 3,3 4,4 3,5 1,5 0,4.5;2,0 2,6",
+        '%' => "4,0 0,6;0.5,0.5 1.2,0.5 1.2,1.2 0.5,1.2 0.5,0.5;\
+                2.8,4.8 3.5,4.8 3.5,5.5 2.8,5.5 2.8,4.8",
+        '&' => "4,6 1,2 1,1 2,0 3,1 3,2 0,4 0,5 1,6 2,6 4,4",
+        '\'' => "2,0 2,1.5",
+        '(' => "3,0 2,1 1.7,3 2,5 3,6",
+        ')' => "1,0 2,1 2.3,3 2,5 1,6",
+        '*' => "2,1 2,5;0.3,2 3.7,4;3.7,2 0.3,4",
+        '+' => "2,1.5 2,5.5;0,3.5 4,3.5",
+        ',' => "2,6 1.5,7",
+        '-' => "0.5,3.5 3.5,3.5",
+        '.' => "2,6 2,6",
+        '/' => "4,0 0,6",
+        '0' => "1,0 3,0 4,1 4,5 3,6 1,6 0,5 0,1 1,0;4,1 0,5",
+        '1' => "1,1 2,0 2,6;1,6 3,6",
+        '2' => "0,1 1,0 3,0 4,1 4,2 0,6 4,6",
+        '3' => "0,1 1,0 3,0 4,1 4,2 3,3 1,3;3,3 4,4 4,5 3,6 1,6 0,5",
+        '4' => "3,6 3,0 0,4 4,4",
+        '5' => "4,0 0,0 0,3 3,3 4,4 4,5 3,6 1,6 0,5",
+        '6' => "4,1 3,0 1,0 0,1 0,5 1,6 3,6 4,5 4,4 3,3 0,3",
+        '7' => "0,0 4,0 1,6",
+        '8' => "1,3 0,2 0,1 1,0 3,0 4,1 4,2 3,3 1,3 0,4 0,5 1,6 3,6 4,5 4,4 3,3",
+        '9' => "0,5 1,6 3,6 4,5 4,1 3,0 1,0 0,1 0,2 1,3 4,3",
+        ':' => "2,2.5 2,2.5;2,6 2,6",
+        ';' => "2,2.5 2,2.5;2,6 1.5,7",
+        '<' => "4,1.5 0,3.5 4,5.5",
+        '=' => "0,2.5 4,2.5;0,4.5 4,4.5",
+        '>' => "0,1.5 4,3.5 0,5.5",
+        '?' => "0,1 1,0 3,0 4,1 4,2 2,3.5 2,4;2,6 2,6",
+        '@' => "3,4 3,2 1.5,2 1,3 1.5,4 4,4 4,1 3,0 1,0 0,1 0,5 1,6 4,6",
+        'A' => "0,6 2,0 4,6;0.7,4 3.3,4",
+        'B' => "0,6 0,0 3,0 4,1 4,2 3,3 0,3;3,3 4,4 4,5 3,6 0,6",
+        'C' => "4,1 3,0 1,0 0,1 0,5 1,6 3,6 4,5",
+        'D' => "0,0 0,6 2.5,6 4,4.5 4,1.5 2.5,0 0,0",
+        'E' => "4,0 0,0 0,6 4,6;0,3 3,3",
+        'F' => "4,0 0,0 0,6;0,3 3,3",
+        'G' => "4,1 3,0 1,0 0,1 0,5 1,6 3,6 4,5 4,3 2,3",
+        'H' => "0,0 0,6;4,0 4,6;0,3 4,3",
+        'I' => "1,0 3,0;2,0 2,6;1,6 3,6",
+        'J' => "4,0 4,5 3,6 1,6 0,5",
+        'K' => "0,0 0,6;4,0 0,4;1.5,2.5 4,6",
+        'L' => "0,0 0,6 4,6",
+        'M' => "0,6 0,0 2,3 4,0 4,6",
+        'N' => "0,6 0,0 4,6 4,0",
+        'O' => "1,0 3,0 4,1 4,5 3,6 1,6 0,5 0,1 1,0",
+        'P' => "0,6 0,0 3,0 4,1 4,2 3,3 0,3",
+        'Q' => "1,0 3,0 4,1 4,5 3,6 1,6 0,5 0,1 1,0;2.5,4.5 4,6",
+        'R' => "0,6 0,0 3,0 4,1 4,2 3,3 0,3;2,3 4,6",
+        'S' => "4,1 3,0 1,0 0,1 0,2 1,3 3,3 4,4 4,5 3,6 1,6 0,5",
+        'T' => "0,0 4,0;2,0 2,6",
+        'U' => "0,0 0,5 1,6 3,6 4,5 4,0",
+        'V' => "0,0 2,6 4,0",
+        'W' => "0,0 1,6 2,3 3,6 4,0",
+        'X' => "0,0 4,6;4,0 0,6",
+        'Y' => "0,0 2,3 4,0;2,3 2,6",
+        'Z' => "0,0 4,0 0,6 4,6",
+        '[' => "3,0 1,0 1,6 3,6",
+        '\\' => "0,0 4,6",
+        ']' => "1,0 3,0 3,6 1,6",
+        '^' => "0.5,2 2,0 3.5,2",
+        '_' => "0,7 4,7",
+        '`' => "1.5,0 2.5,1",
+        'a' => "1,2 3,2 4,3 4,6;4,4 1,4 0,5 1,6 3,6 4,5",
+        'b' => "0,0 0,6;0,3 1,2 3,2 4,3 4,5 3,6 1,6 0,5",
+        'c' => "4,3 3,2 1,2 0,3 0,5 1,6 3,6 4,5",
+        'd' => "4,0 4,6;4,3 3,2 1,2 0,3 0,5 1,6 3,6 4,5",
+        'e' => "0,4 4,4 4,3 3,2 1,2 0,3 0,5 1,6 3,6 4,5.5",
+        'f' => "4,0.5 3,0 2,0 1,1 1,6;0,2 3,2",
+        'g' => "4,2 4,7 3,8 1,8 0,7.5;4,3 3,2 1,2 0,3 0,5 1,6 3,6 4,5",
+        'h' => "0,0 0,6;0,3 1,2 3,2 4,3 4,6",
+        'i' => "2,2 2,6;2,0.5 2,0.5",
+        'j' => "3,2 3,7 2,8 1,8 0,7;3,0.5 3,0.5",
+        'k' => "0,0 0,6;4,2 0,5;1.33,4 4,6",
+        'l' => "1.5,0 1.5,5 2.5,6 3,6",
+        'm' => "0,6 0,2;0,3 1,2 2,3 2,6;2,3 3,2 4,3 4,6",
+        'n' => "0,2 0,6;0,3 1,2 3,2 4,3 4,6",
+        'o' => "1,2 3,2 4,3 4,5 3,6 1,6 0,5 0,3 1,2",
+        'p' => "0,2 0,8;0,3 1,2 3,2 4,3 4,5 3,6 1,6 0,5",
+        'q' => "4,2 4,8;4,3 3,2 1,2 0,3 0,5 1,6 3,6 4,5",
+        'r' => "0,2 0,6;0,4 2,2 4,2",
+        's' => "4,2.5 3.5,2 0.5,2 0,2.5 0,3.5 0.5,4 3.5,4 4,4.5 4,5.5 3.5,6 0.5,6 0,5.5",
+        't' => "1,0 1,5 2,6 3,6;0,2 3,2",
+        'u' => "0,2 0,5 1,6 3,6 4,5;4,2 4,6",
+        'v' => "0,2 2,6 4,2",
+        'w' => "0,2 1,6 2,4 3,6 4,2",
+        'x' => "0,2 4,6;4,2 0,6",
+        'y' => "0,2 2,6;4,2 1,8",
+        'z' => "0,2 4,2 0,6 4,6",
+        '{' => "3,0 2,0.5 2,2.5 1,3 2,3.5 2,5.5 3,6",
+        '|' => "2,0 2,7",
+        '}' => "1,0 2,0.5 2,2.5 3,3 2,3.5 2,5.5 1,6",
+        '~' => "0,3.5 1,3 3,4 4,3.5",
+        _ => "0,0 4,0 4,6 0,6 0,0",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FontFace, TextExtents, glyph};
+    use path::PathElement;
+    use types::{FontSlant, FontWeight};
+
+    #[test]
+    fn text_extents() {
+        // Setup
+        let face = FontFace::default();
+
+        // Call
+        let extents = face.text_extents("Hi");
+
+        // Test
+        let half_width = 0.6 / 8. / 2.;
+        assert_eq!(extents.x_advance, 1.25);
+        assert_eq!(extents.y_advance, 0.);
+        assert!((extents.x_bearing - (0.5 / 8. - half_width)).abs() < 1e-6);
+        assert!((extents.y_bearing - (-0.75 - half_width)).abs() < 1e-6);
+        assert!((extents.height - (0.75 + 2. * half_width)).abs() < 1e-6);
+        assert!((extents.width - (7. / 8. + 2. * half_width)).abs() < 1e-6);
+    }
+
+    #[test]
+    fn text_extents_of_blank_text() {
+        let face = FontFace::default();
+        assert_eq!(face.text_extents(""), TextExtents::default());
+        let blank = TextExtents { x_advance: 1.25, ..TextExtents::default() };
+        assert_eq!(face.text_extents("  "), blank);
+    }
+
+    #[test]
+    fn font_face_styles() {
+        // Setup
+        let regular = FontFace::default();
+        let bold = FontFace::new("serif", FontSlant::Normal, FontWeight::Bold);
+        let oblique = FontFace::new("serif", FontSlant::Oblique, FontWeight::Normal);
+
+        // Test
+        assert_eq!(bold.get_family(), "serif");
+        assert!(bold.text_extents("l").width > regular.text_extents("l").width);
+        // The top of the l leans right, past where the upright one reaches
+        let (upright, leaning) = (regular.text_extents("l"), oblique.text_extents("l"));
+        assert!(leaning.x_bearing + leaning.width > upright.x_bearing + upright.width);
+        assert_eq!(leaning.x_advance, upright.x_advance);
+    }
+
+    #[test]
+    fn text_path_is_closed_outlines() {
+        // Setup
+        let face = FontFace::default();
+
+        // Call
+        let path = face.text_path("a.", 0.01);
+
+        // Test
+        assert!(!path.is_empty());
+        let elements = path.elements();
+        for (index, element) in elements.iter().enumerate() {
+            if let PathElement::MoveTo(_) = *element {
+                assert!(index == 0 || elements[index - 1] == PathElement::ClosePath);
+            }
+        }
+        assert_eq!(elements.last(), Some(&PathElement::ClosePath));
+        assert!(face.text_path(" ", 0.01).is_empty());
+    }
+
+    #[test]
+    fn every_printable_glyph_parses() {
+        for code in 32u8..127 {
+            for polyline in glyph(code as char).split(';').filter(|p| !p.is_empty()) {
+                let points: Vec<&str> = polyline.split_whitespace().collect();
+                assert!(points.len() >= 2, "{:?}", code as char);
+                for point in points {
+                    let coordinates: Vec<f32> = point.split(',').map(|v| v.parse().unwrap())
+                                                     .collect();
+                    assert_eq!(coordinates.len(), 2, "{:?}", code as char);
+                    assert!(coordinates[0] >= 0. && coordinates[0] <= 4.);
+                    assert!(coordinates[1] >= 0. && coordinates[1] <= 8.);
+                }
+            }
+        }
+        assert_eq!(glyph('é'), glyph('\u{1}'));
+    }
+}
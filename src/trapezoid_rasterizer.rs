@@ -76,7 +76,16 @@
 //! +--------------------------+                   X--X -X--X---X---X---X--X--X
 
 //! Cairus iterates through each X in the Subpixel grid above, and checks if that X point is
-//! inside the trapezoid.  If it is, the opacity of the original pixel will increase.
+//! inside the trapezoid.  If it is, the opacity of the original pixel will increase.  The X's
+//! sit at the centers of the grid's cells rather than on its lines, so no sample lies on the
+//! pixel's border, where trapezoids on both sides of it would count it.
+//!
+//! Only pixels that a trapezoid's edges pass through are sampled.  Pixels lying wholly inside
+//! the trapezoid are fully covered without sampling.
+//!
+//! Before it is sampled, each trapezoid's corners are snapped to 1/256 of a pixel, as cairo's
+//! 24.8 fixed-point coordinates are, so the result doesn't depend on rounding error in where
+//! the tessellator put them.
 //!
 //!  See the `fn Pixel::sample_points()` function for the implementation.
 //!
@@ -114,22 +123,35 @@
 //!
 //!  See `fn ray_from_point_crosses_line` for ray intersection algorithm, and
 //!  `fn Trapezoid::contains_point` for how it is used to determine if a point is in a trapezoid.
-
-use surfaces::ImageSurface;
-use common_geometry::{Point, LineSegment};
+//!
+//!  With `Antialias::None` only the center of each pixel is sampled, so every pixel is either
+//!  fully covered or not covered at all.  Every other Antialias setting uses the subpixel grid;
+//!  `Antialias::Subpixel` renders like `Antialias::Gray` here.  Text is drawn for LCD panels
+//!  by the `subpixel` module instead.
+
+use surfaces::{Format, ImageSurface, rows_between};
+use common_geometry::{Edge, Point, LineSegment, Rectangle};
+use bo_trap;
+use error::Error;
+use fixed::snap_point;
+use operators::{Operator, composite_with_coverage, fetch_operator};
+use spans::Span;
 use std::f32;
-use std::collections::HashMap;
-use types::{Pixel, IntoPixels};
+use std::ops::Range;
+use std::thread;
+use types::{Antialias, FillRule, Pixel, IntoPixels, Rgba};
 
 /// ## Trapezoid
 ///
 /// Defines a trapezoid as four points.
 ///
+/// Fills are tessellated into trapezoids with horizontal bases, see `Path::trapezoids`, which
+/// can be read back with top(), bottom(), left() and right(), or rasterized a row of pixels at a
+/// time with spans().  Either base may have no length, which makes a triangle.
+///
 /// ### Panics
 /// `fn Trapezoid::from_bases` will panic if the LineSegments are not parallel.
-///
-/// TODO: Implement `fn points()` or `fn a()`, `fn b()` , etc...
-/// TODO: Test/verify degenerate Trapezoid (a triangle) is still valid
+#[derive(Debug, Clone, PartialEq)]
 pub struct Trapezoid {
     pub lines: Vec<LineSegment>
 }
@@ -139,17 +161,41 @@ impl Trapezoid {
     // Returns a new Trapezoid defined by points.
     pub fn from_points(a: Point, b: Point, c: Point, d: Point) -> Trapezoid {
         let bases = bases_from_points(a, b, c, d);
-        Trapezoid::from_bases(bases[0].0, bases[0].1)
+        Trapezoid::from_parallel_bases(bases[0].0, bases[0].1)
     }
 
-    // Returns a new Trapezoid from two bases
+    /// Returns a new Trapezoid from two bases.
+    ///
+    /// # Panics
+    /// If the bases aren't parallel.
+    #[deprecated(note = "panics on bases that aren't parallel; use try_from_bases")]
     pub fn from_bases(base1: LineSegment, base2: LineSegment) -> Trapezoid {
+        match Trapezoid::try_from_bases(base1, base2) {
+            Ok(trapezoid) => trapezoid,
+            Err(_) => {
+                panic!("Trapezoid::from_bases() can only be called on LineSegments with equal \
+                        slope!")
+            },
+        }
+    }
+
+    /// Returns a new Trapezoid from two bases, or Error::InvalidPathData if they aren't parallel
+    /// and so can't be the bases of one.
+    ///
+    /// # Usage
+    /// let trapezoid = Trapezoid::try_from_bases(top, bottom)?;
+    pub fn try_from_bases(base1: LineSegment, base2: LineSegment) -> Result<Trapezoid, Error> {
         if base1.length() != 0. &&
            base2.length() != 0. &&
            base1.slope() != base2.slope() {
-            panic!("Trapezoid::from_bases() can only be called on LineSegments with equal slope!");
+            return Err(Error::InvalidPathData("the bases of a trapezoid are not parallel"));
         }
+        Ok(Trapezoid::from_parallel_bases(base1, base2))
+    }
 
+    // Returns the Trapezoid between two bases that are known to be parallel, as horizontal ones
+    // are.
+    pub(crate) fn from_parallel_bases(base1: LineSegment, base2: LineSegment) -> Trapezoid {
         Trapezoid {
             lines: lines_from_bases(base1, base2)
         }
@@ -159,6 +205,88 @@ impl Trapezoid {
         &self.lines
     }
 
+    /// Returns the y of the trapezoid's top, its smallest y.
+    pub fn top(&self) -> f32 {
+        self.points().map(|point| point.y).fold(f32::INFINITY, f32::min)
+    }
+
+    /// Returns the y of the trapezoid's bottom, its largest y.
+    pub fn bottom(&self) -> f32 {
+        self.points().map(|point| point.y).fold(f32::NEG_INFINITY, f32::max)
+    }
+
+    /// Returns the trapezoid's left side, from its top to its bottom.
+    ///
+    /// For a trapezoid with horizontal bases, as fills are tessellated into, this is the line
+    /// from the left end of the top base to the left end of the bottom base.  For any other,
+    /// it runs between the leftmost points at the top and at the bottom.
+    pub fn left(&self) -> LineSegment {
+        let (top, bottom) = (self.top(), self.bottom());
+        let (upper, lower) = (self.span_at(top).unwrap_or((f32::NAN, f32::NAN)),
+                              self.span_at(bottom).unwrap_or((f32::NAN, f32::NAN)));
+        LineSegment::new(upper.0, top, lower.0, bottom)
+    }
+
+    /// Returns the trapezoid's right side, from its top to its bottom.  See left().
+    pub fn right(&self) -> LineSegment {
+        let (top, bottom) = (self.top(), self.bottom());
+        let (upper, lower) = (self.span_at(top).unwrap_or((f32::NAN, f32::NAN)),
+                              self.span_at(bottom).unwrap_or((f32::NAN, f32::NAN)));
+        LineSegment::new(upper.1, top, lower.1, bottom)
+    }
+
+    /// Returns an iterator over the rows of pixels the trapezoid covers, from the top down, each
+    /// with the spans of its pixels that are covered alike.
+    ///
+    /// The coverage is sampled exactly as the trapezoid is filled, with `antialias`, so adding up
+    /// the spans of a fill's trapezoids gives the coverage fill() composites through.  Pixels
+    /// above or left of the origin are left out, as surfaces don't have them.  Suits renderers
+    /// of their own, such as ones uploading coverage to a GPU.
+    ///
+    /// # Usage
+    /// for (y, spans) in trapezoid.spans(Antialias::Default) {
+    ///     renderer.render_row(y, &spans);
+    /// }
+    pub fn spans(&self, antialias: Antialias) -> TrapezoidSpans {
+        let trapezoid = self.snapped();
+        let (top, bottom) = (trapezoid.top(), trapezoid.bottom());
+        let right = trapezoid.points().map(|point| point.x).fold(f32::NEG_INFINITY, f32::max);
+        let (y, end) = if top < bottom {
+            (top.floor().max(0.) as usize, bottom.ceil().max(0.) as usize)
+        } else {
+            (0, 0)
+        };
+        let width = right.ceil().max(0.) as usize;
+        TrapezoidSpans { trapezoid, antialias, width, y, end }
+    }
+
+    // Returns the trapezoid's corners, each once for every line it ends
+    fn points(&self) -> impl Iterator<Item = Point> + '_ {
+        self.lines.iter().flat_map(|line| vec![line.point1, line.point2])
+    }
+
+    // Returns how much of `pixel` the trapezoid covers, from 0 to 255 samples
+    fn samples_covered(&self, pixel: &Pixel, antialias: Antialias) -> u32 {
+        if !pixel.is_edge() {
+            255
+        } else if antialias == Antialias::None {
+            if self.contains_point(&pixel.center()) { 255 } else { 0 }
+        } else {
+            pixel.sample_points().iter().filter(|point| self.contains_point(point)).count() as u32
+        }
+    }
+
+    /// Returns a copy of this Trapezoid with its corners snapped to the fixed-point grid.
+    ///
+    /// Trapezoids that share a side still share it exactly afterwards, so none of the samples
+    /// along it are lost or counted twice through rounding error.
+    pub fn snapped(&self) -> Trapezoid {
+        let lines = self.lines.iter().map(|line| {
+            LineSegment::from_points(snap_point(line.point1), snap_point(line.point2))
+        }).collect();
+        Trapezoid { lines }
+    }
+
     /// Returns true if this Trapezoid contains `point`, otherwise returns false
     pub fn contains_point(&self, point: &Point) -> bool {
         let mut crossing_count = 0;
@@ -175,55 +303,194 @@ impl Trapezoid {
 
 impl IntoPixels for Trapezoid {
     /// Converts this trapezoid into a Vec of Pixels
+    ///
+    /// Every pixel the trapezoid overlaps is returned once.  Pixels lying wholly inside it are
+    /// interior pixels; the rest are edge pixels, to be sampled.
     fn into_pixels(&self) -> Vec<Pixel> {
-        let mut outline_pixels = Vec::new();
-        for line in self.lines() {
-            for pixel in line.into_pixels() {
-                outline_pixels.push(pixel);
+        let mut pixels = Vec::new();
+        let points: Vec<Point> = self.lines().iter()
+            .flat_map(|line| vec![line.point1, line.point2])
+            .collect();
+        let top = points.iter().map(|point| point.y).fold(f32::INFINITY, f32::min);
+        let bottom = points.iter().map(|point| point.y).fold(f32::NEG_INFINITY, f32::max);
+        if top.is_nan() || bottom.is_nan() || top >= bottom {
+            return pixels;
+        }
+
+        for y in (top.floor() as i32)..(bottom.ceil() as i32) {
+            // The part of this row of pixels the trapezoid spans
+            let band_top = top.max(y as f32);
+            let band_bottom = bottom.min(y as f32 + 1.);
+            let (upper, lower) = match (self.span_at(band_top), self.span_at(band_bottom)) {
+                (Some(upper), Some(lower)) => (upper, lower),
+                _ => continue,
+            };
+
+            // Outside the band's spans only vertices can reach further
+            let mut left = upper.0.min(lower.0);
+            let mut right = upper.1.max(lower.1);
+            for point in points.iter().filter(|point| point.y > band_top && point.y < band_bottom) {
+                left = left.min(point.x);
+                right = right.max(point.x);
+            }
+
+            // The trapezoid is convex, so it covers a column of the row from top to bottom
+            // exactly when it covers both ends
+            let full_row = band_top == y as f32 && band_bottom == y as f32 + 1.;
+            let (inner_left, inner_right) = (upper.0.max(lower.0), upper.1.min(lower.1));
+            for x in (left.floor() as i32)..(right.ceil() as i32) {
+                let interior = full_row && x as f32 >= inner_left && x as f32 + 1. <= inner_right;
+                pixels.push(Pixel{x, y, is_edge: !interior});
             }
         }
 
-        // Order by y-value, for scanline from bottom
-        outline_pixels.sort_by(|&ref a, &ref b| a.y.cmp(&b.y));
-        let mut minmap = HashMap::new();
-        let mut maxmap = HashMap::new();
-        for pixel in outline_pixels.iter() {
-            minmap.insert(pixel.y, pixel.x);
-            maxmap.insert(pixel.y, pixel.x);
+        pixels
+    }
+}
+
+impl Trapezoid {
+    // Returns the parts of the trapezoid inside `bounds`, or nothing if it lies wholly outside.
+    //
+    // A trapezoid with horizontal bases, as the tessellator produces, is cut to the bounds: its
+    // height is cut down first, then it is split where its sides cross the left and right of
+    // the bounds, so that each piece is bounded by one side or the other throughout.  Any other
+    // trapezoid is only culled.
+    fn clipped(&self, bounds: &Rectangle) -> Vec<Trapezoid> {
+        let bounds = bounds.normalized();
+        let (min_x, max_x) = (bounds.x, bounds.x + bounds.width);
+        let (min_y, max_y) = (bounds.y, bounds.y + bounds.height);
+        let points: Vec<Point> = self.lines().iter()
+            .flat_map(|line| vec![line.point1, line.point2])
+            .collect();
+        let top = points.iter().map(|point| point.y).fold(f32::INFINITY, f32::min);
+        let bottom = points.iter().map(|point| point.y).fold(f32::NEG_INFINITY, f32::max);
+        let left = points.iter().map(|point| point.x).fold(f32::INFINITY, f32::min);
+        let right = points.iter().map(|point| point.x).fold(f32::NEG_INFINITY, f32::max);
+        if !(top < max_y && bottom > min_y && left < max_x && right > min_x) {
+            return Vec::new();
+        }
+        let inside = top >= min_y && bottom <= max_y && left >= min_x && right <= max_x;
+        if inside || points.iter().any(|point| point.y != top && point.y != bottom) {
+            return vec![Trapezoid { lines: self.lines.clone() }];
         }
 
-        for pixel in outline_pixels.iter() {
-            if pixel.x < *minmap.get_mut(&pixel.y).unwrap() {
-                minmap.insert(pixel.y, pixel.x);
+        let (top, bottom) = (top.max(min_y), bottom.min(max_y));
+        let (upper, lower) = match (self.span_at(top), self.span_at(bottom)) {
+            (Some(upper), Some(lower)) => (upper, lower),
+            _ => return Vec::new(),
+        };
+        let side_at = |upper: f32, lower: f32, y: f32| {
+            upper + (lower - upper) * (y - top) / (bottom - top)
+        };
+
+        let mut cuts = vec![top, bottom];
+        for &(upper_x, lower_x) in &[(upper.0, lower.0), (upper.1, lower.1)] {
+            for &x in &[min_x, max_x] {
+                if (upper_x - x) * (lower_x - x) < 0. {
+                    cuts.push(top + (bottom - top) * (x - upper_x) / (lower_x - upper_x));
+                }
             }
+        }
+        cuts.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        cuts.dedup();
+
+        let span = |y: f32| {
+            (side_at(upper.0, lower.0, y).max(min_x), side_at(upper.1, lower.1, y).min(max_x))
+        };
+        cuts.windows(2).filter_map(|band| {
+            let (band_top, band_bottom) = (band[0], band[1]);
+            let ((top_left, top_right), (bottom_left, bottom_right)) =
+                (span(band_top), span(band_bottom));
+            // A band beyond the left or right of the bounds has nothing left in it
+            if top_left >= top_right && bottom_left >= bottom_right {
+                return None;
+            }
+            Some(Trapezoid::from_parallel_bases(
+                LineSegment::new(top_left, band_top, top_right.max(top_left), band_top),
+                LineSegment::new(bottom_left, band_bottom, bottom_right.max(bottom_left),
+                                 band_bottom)))
+        }).collect()
+    }
 
-            if pixel.x > *maxmap.get_mut(&pixel.y).unwrap() {
-                maxmap.insert(pixel.y, pixel.x);
+    // Returns the leftmost and rightmost x of the trapezoid along the horizontal line at `y`, or
+    // None if the line misses it.
+    fn span_at(&self, y: f32) -> Option<(f32, f32)> {
+        let mut span: Option<(f32, f32)> = None;
+        for line in self.lines() {
+            let (top, bottom) = (line.point1.y.min(line.point2.y), line.point1.y.max(line.point2.y));
+            if y < top || y > bottom {
+                continue;
             }
+            let xs = if top == bottom {
+                (line.point1.x.min(line.point2.x), line.point1.x.max(line.point2.x))
+            } else {
+                let t = (y - line.point1.y) / (line.point2.y - line.point1.y);
+                let x = line.point1.x + t * (line.point2.x - line.point1.x);
+                (x, x)
+            };
+            span = Some(match span {
+                Some((left, right)) => (left.min(xs.0), right.max(xs.1)),
+                None => xs,
+            });
         }
+        span
+    }
+}
 
-        let mut pixels = Vec::new();
-        let min_y = outline_pixels[0].y;
-        let max_y = outline_pixels[outline_pixels.len() - 1].y + 1;
-        for (outer_idx, y) in (min_y..max_y).enumerate() {
-            if outer_idx == 0 {
-                continue;
+
+/// ## TrapezoidSpans
+///
+/// An iterator over the rows of pixels a trapezoid covers and their spans.  See
+/// `Trapezoid::spans`.
+#[derive(Debug)]
+pub struct TrapezoidSpans {
+    // Snapped, as the trapezoid is before it is filled
+    trapezoid: Trapezoid,
+    antialias: Antialias,
+    // The width of the pixels the trapezoid reaches into from the left of the surface
+    width: usize,
+    // The next row, and the row after the last
+    y: usize,
+    end: usize,
+}
+
+impl Iterator for TrapezoidSpans {
+    type Item = (usize, Vec<Span>);
+
+    fn next(&mut self) -> Option<(usize, Vec<Span>)> {
+        while self.y < self.end {
+            let y = self.y;
+            self.y += 1;
+
+            let mut coverage = vec![0; self.width];
+            let bounds = Rectangle::new(0., y as f32, self.width as f32, 1.);
+            for piece in self.trapezoid.clipped(&bounds) {
+                for pixel in piece.into_pixels() {
+                    if pixel.y != y as i32 || pixel.x < 0 || pixel.x as usize >= self.width {
+                        continue;
+                    }
+                    coverage[pixel.x as usize] += piece.samples_covered(&pixel, self.antialias);
+                }
             }
-            for (inner_idx, x) in (minmap[&y]..(maxmap[&y] + 1)).enumerate() {
-                if inner_idx == 0 {
-                    continue;
+
+            let mut spans: Vec<Span> = Vec::new();
+            for (x, &samples) in coverage.iter().enumerate().filter(|&(_, &samples)| samples > 0) {
+                let samples = samples.min(255) as f32 / 255.;
+                match spans.last_mut() {
+                    Some(span) if span.x + span.length == x && span.coverage == samples => {
+                        span.length += 1;
+                    },
+                    _ => spans.push(Span { x, length: 1, coverage: samples }),
                 }
-                let pixel = Pixel{x: x, y: y, is_edge: false};
-                pixels.push(pixel);
+            }
+            if !spans.is_empty() {
+                return Some((y, spans));
             }
         }
-
-        pixels.append(&mut outline_pixels);
-        pixels
+        None
     }
 }
 
-
 // Defines a collection for holding a Trapezoid's bases.
 //
 // A Trapezoid's base line segments are always parallel.
@@ -251,7 +518,7 @@ impl TrapezoidBasePair {
 ///
 /// A Trapezoid's base line segments are the parallel lines that form the Trapezoid.
 fn bases_from_points(a: Point, b: Point, c: Point, d: Point) -> Vec<TrapezoidBasePair> {
-    let mut points = vec![a, b, c, d];
+    let mut points = [a, b, c, d];
     points.sort_by(|&a, &b| { a.x.partial_cmp(&b.x).unwrap() });
 
     let mut possible_lines = Vec::new();
@@ -318,29 +585,71 @@ fn ray_from_point_crosses_line(point: &Point, line: &LineSegment) -> bool {
 /// Returns an ImageSurface mask from a Vec of Trapezoids.
 ///
 /// The Rgba values will only have alpha values, as it is expected that this mask will only be
-/// used with the `operator_in` operator.
-pub fn mask_from_trapezoids(trapezoids: &Vec<Trapezoid>, width: usize, height: usize) -> ImageSurface {
-    let mut mask = ImageSurface::create(width, height);
+/// used with the `operator_in` operator.  Where trapezoids overlap their coverage adds up,
+/// saturating at full coverage.
+pub fn mask_from_trapezoids(trapezoids: &[Trapezoid], width: usize, height: usize) -> ImageSurface {
+    mask_from_trapezoids_with_antialias(trapezoids, Antialias::Default, width, height)
+}
+
+/// Returns the parts of `trapezoids` inside `bounds`.
+///
+/// Trapezoids wholly outside are dropped, and those partly outside are cut down, so that
+/// geometry far off a surface costs nothing to rasterize.
+pub fn clip_trapezoids(trapezoids: &[Trapezoid], bounds: &Rectangle) -> Vec<Trapezoid> {
+    trapezoids.iter().flat_map(|trapezoid| trapezoid.clipped(bounds)).collect()
+}
+
+// Returns the smallest rectangle of whole pixels holding every pixel of `mask` with any
+// coverage, or None if no pixel has any.
+fn coverage_extents(mask: &ImageSurface) -> Option<Rectangle> {
+    let mut extents: Option<(usize, usize, usize, usize)> = None;
+    for (position, coverage) in mask.iter().enumerate() {
+        if coverage.alpha > 0. {
+            let (x, y) = (position % mask.width, position / mask.width);
+            extents = Some(match extents {
+                Some((left, top, right, bottom)) => {
+                    (left.min(x), top.min(y), right.max(x + 1), bottom.max(y + 1))
+                },
+                None => (x, y, x + 1, y + 1),
+            });
+        }
+    }
+    extents.map(|(left, top, right, bottom)| {
+        Rectangle::new(left as f32, top as f32, (right - left) as f32, (bottom - top) as f32)
+    })
+}
+
+/// Returns an ImageSurface mask from a Vec of Trapezoids, sampling their edges as `antialias`
+/// selects.
+///
+/// The trapezoids are clipped to the mask first, as `clip_trapezoids` does.
+///
+/// See the module documentation for how each Antialias setting samples pixels.
+pub fn mask_from_trapezoids_with_antialias(trapezoids: &[Trapezoid], antialias: Antialias,
+                                           width: usize, height: usize) -> ImageSurface {
+    mask_for_rows(trapezoids, antialias, width, 0, height)
+}
 
-    for trapezoid in trapezoids {
+// Returns the mask of `rows` rows of a surface `width` pixels wide, from row `top` down.
+//
+// The trapezoids are snapped before they are clipped to the rows, so that a trapezoid split
+// between neighbouring rows covers the same samples as it would whole.
+fn mask_for_rows(trapezoids: &[Trapezoid], antialias: Antialias, width: usize, top: usize,
+                 rows: usize) -> ImageSurface {
+    let mut mask = ImageSurface::create_for_format(Format::ARGB32, width, rows);
+    let bounds = Rectangle::new(0., top as f32, width as f32, rows as f32);
+    let clipped = trapezoids.iter().flat_map(|trapezoid| trapezoid.snapped().clipped(&bounds));
+
+    for trapezoid in clipped {
         for pixel in trapezoid.into_pixels() {
-            let mut successes = 0;
-            if pixel.is_edge() {
-                for sample_point in pixel.sample_points() {
-                    if trapezoid.contains_point(&sample_point) {
-                        successes += 1;
-                    }
-                }
-            } else {
-                successes = 255;
+            let successes = trapezoid.samples_covered(&pixel, antialias);
+            if pixel.x < 0 || pixel.y < top as i32 {
+                continue;
             }
-            let (x, y) = (pixel.x as usize, pixel.y as usize);
-            match mask.get_mut(x, y) {
-                Some(mut rgba) => {
-                    rgba.alpha += successes as f32 / 255.;
-                    rgba.alpha.max(1.);
-                },
-                None => {},
+            let (x, y) = (pixel.x as usize, pixel.y as usize - top);
+            if let Some(rgba) = mask.get_mut(x, y) {
+                rgba.alpha += successes as f32 / 255.;
+                rgba.alpha = rgba.alpha.min(1.);
             }
          }
      }
@@ -348,6 +657,162 @@ pub fn mask_from_trapezoids(trapezoids: &Vec<Trapezoid>, width: usize, height: u
      mask
 }
 
+/// Returns an ImageSurface mask covering the area inside `edges` under `fill_rule`.
+///
+/// The edges are split into trapezoids with `bo_trap::sweep_with_fill_rule`, which are then
+/// rasterized as in `mask_from_trapezoids`.
+pub fn mask_from_edges(edges: Vec<Edge>, fill_rule: FillRule, width: usize, height: usize)
+                       -> ImageSurface {
+    mask_from_trapezoids(&bo_trap::sweep_with_fill_rule(edges, fill_rule), width, height)
+}
+
+/// Composites `source` onto `surface` with `operator` wherever `trapezoids` cover it.
+///
+/// This is the last step of filling a path: the trapezoids from `bo_trap` are rasterized into a
+/// coverage mask as in `mask_from_trapezoids`, and each covered pixel is composited by the
+/// fraction of it that is covered.  Pixels the trapezoids don't touch are left alone, even for
+/// operators such as Source and In that would otherwise change them.
+///
+/// # Arguments
+/// * `trapezoids` - The area to fill.
+/// * `source` - The color to composite.
+/// * `operator` - The compositing operator.
+/// * `surface` - The destination surface.
+///
+/// # Usage
+/// rasterize_trapezoids(&trapezoids, &Rgba::new(1., 0., 0., 1.), &Operator::Over, &mut surface);
+pub fn rasterize_trapezoids(trapezoids: &[Trapezoid], source: &Rgba, operator: &Operator,
+                            surface: &mut ImageSurface) {
+    rasterize_trapezoids_with_antialias(trapezoids, source, operator, Antialias::Default, surface);
+}
+
+/// Composites `source` onto `surface` with `operator` wherever `trapezoids` cover it, sampling
+/// their edges as `antialias` selects.
+pub fn rasterize_trapezoids_with_antialias(trapezoids: &[Trapezoid], source: &Rgba,
+                                           operator: &Operator, antialias: Antialias,
+                                           surface: &mut ImageSurface) {
+    rasterize_trapezoids_with_clip(trapezoids, source, operator, antialias, None, surface);
+}
+
+/// Composites `source` onto `surface` with `operator` wherever `trapezoids` cover it, sampling
+/// their edges as `antialias` selects, and only as far as `clip` lets through.
+///
+/// `clip` is a mask the size of `surface`, such as one from `mask_from_trapezoids`, whose alpha
+/// scales the coverage of each pixel.  None leaves the surface unclipped.
+pub fn rasterize_trapezoids_with_clip(trapezoids: &[Trapezoid], source: &Rgba,
+                                      operator: &Operator, antialias: Antialias,
+                                      clip: Option<&ImageSurface>, surface: &mut ImageSurface) {
+    rasterize_trapezoids_with_source(trapezoids, |_, _| *source, operator, antialias, clip,
+                                     surface);
+}
+
+/// Composites onto `surface` with `operator` wherever `trapezoids` cover it, as in
+/// `rasterize_trapezoids_with_clip`, taking the source color of each pixel from `source`.
+///
+/// `source` is given the pixel's column and row, and is only asked about pixels that are at
+/// least partly covered, so an expensive source such as a surface pattern is sampled no more
+/// than it has to be.
+pub fn rasterize_trapezoids_with_source<F>(trapezoids: &[Trapezoid], source: F,
+                                           operator: &Operator, antialias: Antialias,
+                                           clip: Option<&ImageSurface>,
+                                           surface: &mut ImageSurface)
+    where F: Fn(usize, usize) -> Rgba {
+    // Nothing outside the clip's extents can be drawn, so there's no need to rasterize it
+    let clipped;
+    let trapezoids = match clip.map(coverage_extents) {
+        Some(Some(extents)) => {
+            clipped = clip_trapezoids(trapezoids, &extents);
+            &clipped[..]
+        },
+        Some(None) => return,
+        None => trapezoids,
+    };
+    let mut mask = mask_from_trapezoids_with_antialias(trapezoids, antialias, surface.width,
+                                                       surface.height);
+    if let Some(clip) = clip {
+        for (coverage, clip_coverage) in mask.iter_mut().zip(clip.iter()) {
+            coverage.alpha *= clip_coverage.alpha;
+        }
+    }
+    let operator = fetch_operator(operator);
+    let width = surface.width;
+    for (position, (pixel, coverage)) in surface.iter_mut().zip(mask.iter()).enumerate() {
+        if coverage.alpha > 0. {
+            let color = source(position % width, position / width);
+            composite_with_coverage(operator, &color, pixel, coverage.alpha);
+        }
+    }
+    surface.keep_format(trapezoid_rows(trapezoids, surface.height));
+}
+
+// The number of rows of pixels in each tile of rasterize_trapezoids_in_tiles
+const TILE_ROWS: usize = 32;
+
+/// Composites onto `surface` as `rasterize_trapezoids_with_source` does, splitting the work
+/// between `threads` threads.
+///
+/// The surface is cut into tiles of whole rows, which are dealt out to the threads in turn so
+/// that each gets a share of every part of the surface.  Each thread clips the trapezoids to
+/// its tiles and rasterizes and composites them independently, so `source` has to be safe to
+/// call from several threads at once.  No more threads are used than there are tiles, and one
+/// thread or fewer renders on the calling thread.
+pub fn rasterize_trapezoids_in_tiles<F>(trapezoids: &[Trapezoid], source: F,
+                                        operator: &Operator, antialias: Antialias,
+                                        clip: Option<&ImageSurface>,
+                                        surface: &mut ImageSurface, threads: usize)
+    where F: Fn(usize, usize) -> Rgba + Sync {
+    let width = surface.width;
+    let threads = threads.min(surface.height.div_ceil(TILE_ROWS));
+    if threads <= 1 || width == 0 {
+        rasterize_trapezoids_with_source(trapezoids, source, operator, antialias, clip, surface);
+        return;
+    }
+
+    let operator = fetch_operator(operator);
+    let source = &source;
+    let mut pixels: Vec<&mut Rgba> = surface.iter_mut().collect();
+    let mut shares: Vec<Vec<(usize, &mut [&mut Rgba])>> = (0..threads).map(|_| Vec::new())
+                                                                     .collect();
+    for (index, tile) in pixels.chunks_mut(TILE_ROWS * width).enumerate() {
+        shares[index % threads].push((index * TILE_ROWS, tile));
+    }
+    thread::scope(|scope| {
+        for share in shares {
+            scope.spawn(move || {
+                for (top, tile) in share {
+                    render_tile(trapezoids, source, operator, antialias, clip, width, top, tile);
+                }
+            });
+        }
+    });
+    surface.keep_format(trapezoid_rows(trapezoids, surface.height));
+}
+
+// Returns the rows of a surface `height` rows tall that `trapezoids` reach into.
+fn trapezoid_rows(trapezoids: &[Trapezoid], height: usize) -> Range<usize> {
+    let top = trapezoids.iter().map(Trapezoid::top).fold(f32::INFINITY, f32::min);
+    let bottom = trapezoids.iter().map(Trapezoid::bottom).fold(f32::NEG_INFINITY, f32::max);
+    rows_between(top, bottom, height)
+}
+
+// Rasterizes and composites the part of `trapezoids` in `tile`, the rows of a surface `width`
+// pixels wide from row `top` down.
+#[allow(clippy::too_many_arguments)]
+fn render_tile<F>(trapezoids: &[Trapezoid], source: &F, operator: fn(&Rgba, &mut Rgba),
+                  antialias: Antialias, clip: Option<&ImageSurface>, width: usize, top: usize,
+                  tile: &mut [&mut Rgba])
+    where F: Fn(usize, usize) -> Rgba {
+    let mask = mask_for_rows(trapezoids, antialias, width, top, tile.len() / width);
+    for (position, (pixel, coverage)) in tile.iter_mut().zip(mask.iter()).enumerate() {
+        let (x, y) = (position % width, top + position / width);
+        let clip_coverage = clip.map_or(1., |clip| clip.get(x, y).map_or(0., |clip| clip.alpha));
+        let coverage = coverage.alpha * clip_coverage;
+        if coverage > 0. {
+            composite_with_coverage(operator, &source(x, y), pixel, coverage);
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::{
@@ -355,9 +820,21 @@ mod tests {
         TrapezoidBasePair,
         ray_from_point_crosses_line,
         mask_from_trapezoids,
+        mask_from_trapezoids_with_antialias,
+        mask_from_edges,
+        rasterize_trapezoids,
+        rasterize_trapezoids_with_clip,
+        rasterize_trapezoids_with_source,
         bases_from_points,
+        clip_trapezoids,
+        coverage_extents,
+        rasterize_trapezoids_in_tiles,
     };
-    use common_geometry::{Point, LineSegment};
+    use common_geometry::{Edge, Point, LineSegment, Rectangle};
+    use error::Error;
+    use operators::Operator;
+    use surfaces::{Format, ImageSurface};
+    use types::{Antialias, FillRule, Rgba};
 
     // Test that you can construct a trapezoid from points
     #[test]
@@ -406,7 +883,7 @@ mod tests {
         let pair = TrapezoidBasePair(base1, base2);
         let bases = bases_from_points(a, b, c, d);
         assert!(bases[0] == pair);
-        assert!(bases.len() >= 1);
+        assert!(!bases.is_empty());
     }
 
     // Test that the ray_from_point_crosses_line function performs the 'crossings_test'
@@ -559,6 +1036,20 @@ mod tests {
         }
     }
 
+    // Tests that the coverage of overlapping trapezoids adds up, but never past full coverage
+    #[test]
+    fn mask_of_overlapping_trapezoids_saturates() {
+        let a = Point{x: 0., y: 0.};
+        let b = Point{x: 10., y: 0.};
+        let c = Point{x: 10., y: 10.};
+        let d = Point{x: 0., y: 10.};
+        let trap = Trapezoid::from_points(a, b, c, d);
+        let mask = mask_from_trapezoids(&[trap.clone(), trap], 10, 10);
+
+        assert_eq!(mask.get(5, 5).unwrap().alpha, 1.);
+        assert!(mask.iter().all(|rgba| rgba.alpha <= 1.));
+    }
+
     /// Check that when two trapezoids share a line, that line gets rasterized when
     /// `fn mask_from_trapezoids` is called.
     #[test]
@@ -594,7 +1085,7 @@ mod tests {
         let d = Point{x: 3., y: 3.};
         let base2 = LineSegment{point1: c, point2: d};
 
-        let trapezoid = Trapezoid::from_bases(base1, base2);
+        let trapezoid = Trapezoid::from_parallel_bases(base1, base2);
 
         let internal_point = Point{x: 3., y: 2.};
         let external_point = Point{x: 2., y: 2.5};
@@ -604,6 +1095,7 @@ mod tests {
 
     #[test]
     #[should_panic]
+    #[allow(deprecated)]
     fn trap_from_bases_panics_on_non_parallel() {
         let a = Point{x: 0., y: 0.};
         let b = Point{x: 1., y: 1.};
@@ -615,4 +1107,297 @@ mod tests {
 
         let _ = Trapezoid::from_bases(base1, base2);
     }
+
+    #[test]
+    fn trap_try_from_bases() {
+        let base1 = LineSegment::new(0., 0., 1., 1.);
+        let error = Trapezoid::try_from_bases(base1, LineSegment::new(0., 0., 1., 2.));
+        assert_eq!(error,
+                   Err(Error::InvalidPathData("the bases of a trapezoid are not parallel")));
+        let trapezoid = Trapezoid::try_from_bases(base1, LineSegment::new(0., 2., 1., 3.));
+        let expected = Trapezoid::from_parallel_bases(base1, LineSegment::new(0., 2., 1., 3.));
+        assert_eq!(trapezoid, Ok(expected));
+    }
+
+    // Test that the fill rule decides whether a doubly wound area is filled
+    #[test]
+    fn mask_from_edges_fill_rule() {
+        // Setup
+        let edge = |x1, y1, x2, y2, direction| Edge {
+            line: LineSegment::new(x1, y1, x2, y2),
+            top: f32::min(y1, y2),
+            bottom: f32::max(y1, y2),
+            direction,
+        };
+        let mut edges = Vec::new();
+        for &(min, max) in &[(0., 8.), (2., 6.)] {
+            edges.push(edge(max, min, max, max, 1));
+            edges.push(edge(min, max, min, min, -1));
+        }
+
+        // Call
+        let winding = mask_from_edges(edges.clone(), FillRule::Winding, 10, 10);
+        let even_odd = mask_from_edges(edges, FillRule::EvenOdd, 10, 10);
+
+        // Test
+        assert_eq!(winding.get(4, 4).unwrap().alpha, 1.);
+        assert_eq!(even_odd.get(4, 4).unwrap().alpha, 0.);
+        assert_eq!(even_odd.get(1, 4).unwrap().alpha, 1.);
+    }
+
+    #[test]
+    fn trapezoid_accessors() {
+        // Setup
+        let trapezoid = Trapezoid::from_parallel_bases(LineSegment::new(1., 0., 3., 0.),
+                                                       LineSegment::new(5., 4., 0., 4.));
+        let triangle = Trapezoid::from_parallel_bases(LineSegment::new(2., 1., 2., 1.),
+                                                      LineSegment::new(0., 3., 4., 3.));
+
+        // Test
+        assert_eq!((trapezoid.top(), trapezoid.bottom()), (0., 4.));
+        assert_eq!(trapezoid.left(), LineSegment::new(1., 0., 0., 4.));
+        assert_eq!(trapezoid.right(), LineSegment::new(3., 0., 5., 4.));
+        assert_eq!((triangle.top(), triangle.bottom()), (1., 3.));
+        assert_eq!(triangle.left(), LineSegment::new(2., 1., 0., 3.));
+        assert_eq!(triangle.right(), LineSegment::new(2., 1., 4., 3.));
+    }
+
+    #[test]
+    fn trapezoid_spans_match_the_mask() {
+        // Setup
+        let trapezoids = vec![
+            Trapezoid::from_parallel_bases(LineSegment::new(1.3, 0.5, 3.6, 0.5),
+                                           LineSegment::new(0.2, 4.25, 7.9, 4.25)),
+            Trapezoid::from_parallel_bases(LineSegment::new(0.2, 4.25, 7.9, 4.25),
+                                           LineSegment::new(-2., 6., 6., 6.)),
+            Trapezoid::from_parallel_bases(LineSegment::new(5., 7., 5., 7.),
+                                           LineSegment::new(4., 9.5, 9., 9.5)),
+        ];
+
+        for &antialias in [Antialias::Default, Antialias::None].iter() {
+            // Call
+            let mut coverage = vec![0.; 100];
+            for trapezoid in trapezoids.iter() {
+                for (y, spans) in trapezoid.spans(antialias) {
+                    // Spans are in order, don't touch unless their coverage differs, and cover
+                    // something
+                    for pair in spans.windows(2) {
+                        assert!(pair[0].x + pair[0].length <= pair[1].x);
+                        assert!(pair[0].x + pair[0].length < pair[1].x ||
+                                pair[0].coverage != pair[1].coverage);
+                    }
+                    for span in spans {
+                        assert!(span.coverage > 0.);
+                        for x in span.x..span.x + span.length {
+                            coverage[y * 10 + x] += span.coverage;
+                        }
+                    }
+                }
+            }
+
+            // Test
+            let mask = mask_from_trapezoids_with_antialias(&trapezoids, antialias, 10, 10);
+            for (pixel, &expected) in mask.iter().zip(coverage.iter()) {
+                assert!((pixel.alpha - expected).abs() < 1e-6, "{} {}", pixel.alpha, expected);
+            }
+        }
+    }
+
+    // Test that a trapezoid is composited onto a surface, and pixels outside it are untouched
+    #[test]
+    fn rasterize_trapezoids_composites_covered_pixels() {
+        // Setup
+        let mut surface = ImageSurface::create_for_format(Format::ARGB32, 10, 10);
+        let blue = Rgba::new(0., 0., 1., 1.);
+        for pixel in surface.iter_mut() {
+            *pixel = blue;
+        }
+        let a = Point{x: 2., y: 2.};
+        let b = Point{x: 8., y: 2.};
+        let c = Point{x: 8., y: 6.};
+        let d = Point{x: 2., y: 6.};
+        let trapezoids = vec![Trapezoid::from_points(a, b, c, d)];
+        let red = Rgba::new(1., 0., 0., 0.5);
+
+        // Call
+        rasterize_trapezoids(&trapezoids, &red, &Operator::Source, &mut surface);
+
+        // Test
+        assert_eq!(*surface.get(5, 4).unwrap(), red);
+        assert_eq!(*surface.get(1, 4).unwrap(), blue);
+        assert_eq!(*surface.get(5, 8).unwrap(), blue);
+    }
+
+    // Test that a clip mask scales coverage, and nothing is drawn where it is clear
+    #[test]
+    fn rasterize_trapezoids_with_clip_masks_coverage() {
+        // Setup
+        let mut surface = ImageSurface::create_for_format(Format::ARGB32, 10, 10);
+        let mut clip = ImageSurface::create_for_format(Format::ARGB32, 10, 10);
+        for x in 0..5 {
+            for y in 0..10 {
+                clip.get_mut(x, y).unwrap().alpha = if x < 3 { 1. } else { 0.5 };
+            }
+        }
+        let a = Point{x: 2., y: 2.};
+        let b = Point{x: 8., y: 2.};
+        let c = Point{x: 8., y: 6.};
+        let d = Point{x: 2., y: 6.};
+        let trapezoids = vec![Trapezoid::from_points(a, b, c, d)];
+        let red = Rgba::new(1., 0., 0., 1.);
+
+        // Call
+        rasterize_trapezoids_with_clip(&trapezoids, &red, &Operator::Over, Antialias::Default,
+                                       Some(&clip), &mut surface);
+
+        // Test
+        assert_eq!(*surface.get(2, 4).unwrap(), red);
+        assert_eq!(surface.get(4, 4).unwrap().alpha, 0.5);
+        assert_eq!(surface.get(6, 4).unwrap().alpha, 0.);
+    }
+
+    // Test that the source is asked for the color of each covered pixel
+    #[test]
+    fn rasterize_trapezoids_with_source_per_pixel() {
+        // Setup
+        let mut surface = ImageSurface::create_for_format(Format::ARGB32, 10, 10);
+        let trapezoids = vec![Trapezoid::from_points(Point{x: 2., y: 2.}, Point{x: 8., y: 2.},
+                                                     Point{x: 8., y: 6.}, Point{x: 2., y: 6.})];
+        let gradient = |x: usize, _: usize| Rgba::new(x as f32 / 10., 0., 0., 1.);
+
+        // Call
+        rasterize_trapezoids_with_source(&trapezoids, gradient, &Operator::Source,
+                                         Antialias::Default, None, &mut surface);
+
+        // Test
+        assert_eq!(*surface.get(2, 3).unwrap(), Rgba::new(0.2, 0., 0., 1.));
+        assert_eq!(*surface.get(7, 5).unwrap(), Rgba::new(0.7, 0., 0., 1.));
+        assert_eq!(surface.get(8, 5).unwrap().alpha, 0.);
+    }
+
+    // Test that without antialiasing a trapezoid's slanted edges cover pixels fully or not at all
+    #[test]
+    fn mask_without_antialias_is_bilevel() {
+        // Setup
+        let a = Point{x: 0., y: 0.};
+        let b = Point{x: 10., y: 0.};
+        let c = Point{x: 5., y: 9.};
+        let d = Point{x: 7., y: 9.};
+        let trapezoids = vec![Trapezoid::from_points(a, b, c, d)];
+
+        // Call
+        let aliased = mask_from_trapezoids_with_antialias(&trapezoids, Antialias::None, 10, 10);
+        let gray = mask_from_trapezoids_with_antialias(&trapezoids, Antialias::Gray, 10, 10);
+
+        // Test
+        assert!(aliased.iter().all(|pixel| pixel.alpha == 0. || pixel.alpha == 1.));
+        assert!(gray.iter().any(|pixel| pixel.alpha > 0. && pixel.alpha < 1.));
+        assert_eq!(aliased.get(5, 5).unwrap().alpha, 1.);
+        assert_eq!(aliased.get(1, 8).unwrap().alpha, 0.);
+    }
+
+    fn trapezoids_area(trapezoids: &[Trapezoid]) -> f32 {
+        trapezoids.iter().map(|trapezoid| {
+            let (top, bottom) = (trapezoid.lines[0], trapezoid.lines[2]);
+            let width = |base: LineSegment| (base.point2.x - base.point1.x).abs();
+            (width(top) + width(bottom)) / 2. * (bottom.point1.y - top.point1.y).abs()
+        }).sum()
+    }
+
+    // Test that clipping cuts trapezoids down to the bounds and drops those outside them
+    #[test]
+    fn clip_trapezoids_to_bounds() {
+        // Setup
+        let top = LineSegment::new(-4., -2., 4., -2.);
+        let bottom = LineSegment::new(-8., 6., 8., 6.);
+        let inside = Trapezoid::from_parallel_bases(LineSegment::new(1., 1., 2., 1.),
+                                                    LineSegment::new(1., 2., 3., 2.));
+        let outside = Trapezoid::from_parallel_bases(LineSegment::new(20., 1., 22., 1.),
+                                                     LineSegment::new(20., 2., 21., 2.));
+        let trapezoids = vec![Trapezoid::from_parallel_bases(top, bottom), inside, outside];
+        let bounds = Rectangle::new(0., 0., 6., 4.);
+
+        // Call
+        let clipped = clip_trapezoids(&trapezoids, &bounds);
+
+        // Test
+        // Between y = 0 and 4 the big trapezoid's left side is beyond x = 0, and its right side
+        // runs from x = 5 to 7, crossing x = 6 at y = 2
+        let points: Vec<Point> = clipped.iter()
+            .flat_map(|trapezoid| trapezoid.lines.iter().map(|line| line.point1))
+            .collect();
+        assert!(points.iter().all(|point| {
+            point.x >= 0. && point.x <= 6. && point.y >= 0. && point.y <= 4.
+        }));
+        assert_eq!(clipped.len(), 3);
+        assert_eq!(trapezoids_area(&clipped), 11. + 12. + 1.5);
+        assert!(clip_trapezoids(&trapezoids, &Rectangle::new(30., 0., 5., 5.)).is_empty());
+    }
+
+    // Test that a trapezoid far larger than the surface rasterizes like one that fits it
+    #[test]
+    fn mask_clips_huge_trapezoids() {
+        // Setup
+        let huge = vec![Trapezoid::from_parallel_bases(LineSegment::new(-1e7, -1e7, 1e7, -1e7),
+                                                       LineSegment::new(-1e7, 1e7, 1e7, 1e7))];
+        let fitting = vec![Trapezoid::from_parallel_bases(LineSegment::new(0., 0., 4., 0.),
+                                                          LineSegment::new(0., 4., 4., 4.))];
+
+        // Call
+        let mask = mask_from_trapezoids(&huge, 4, 4);
+
+        // Test
+        assert!(mask.iter().zip(mask_from_trapezoids(&fitting, 4, 4).iter())
+                    .all(|(huge, fitting)| huge == fitting));
+        assert!(mask.iter().all(|pixel| pixel.alpha == 1.));
+    }
+
+    // Test that the extents of a clip mask cover exactly its covered pixels
+    #[test]
+    fn coverage_extents_of_mask() {
+        // Setup
+        let mut mask = ImageSurface::create_for_format(Format::ARGB32, 6, 6);
+        mask.get_mut(1, 4).unwrap().alpha = 0.5;
+        mask.get_mut(3, 2).unwrap().alpha = 1.;
+
+        // Call
+        let extents = coverage_extents(&mask);
+
+        // Test
+        assert_eq!(extents, Some(Rectangle::new(1., 2., 3., 3.)));
+        assert_eq!(coverage_extents(&ImageSurface::create_for_format(Format::ARGB32, 6, 6)), None);
+    }
+
+    // Test that rendering in tiles on several threads draws what one thread does
+    #[test]
+    fn rasterize_in_tiles_matches_single_thread() {
+        // Setup
+        let points = [Point::new(3.5, 2.), Point::new(90., 40.), Point::new(20., 95.),
+                      Point::new(50., 10.)];
+        let edges: Vec<Edge> = (0..4).map(|index| {
+            Edge::from_points(points[index], points[(index + 1) % 4])
+        }).collect();
+        let trapezoids = ::bo_trap::sweep_with_fill_rule(edges, FillRule::Winding);
+        let mut clip = ImageSurface::create_for_format(Format::ARGB32, 100, 100);
+        for (position, pixel) in clip.iter_mut().enumerate() {
+            pixel.alpha = if position % 100 < 60 { 1. } else { 0.5 };
+        }
+        let source = |x: usize, y: usize| Rgba::new(x as f32 / 100., y as f32 / 100., 0., 1.);
+        let render = |threads| {
+            let mut surface = ImageSurface::create_for_format(Format::ARGB32, 100, 100);
+            rasterize_trapezoids_in_tiles(&trapezoids, source, &Operator::Over, Antialias::Default,
+                                          Some(&clip), &mut surface, threads);
+            surface
+        };
+
+        // Call
+        let single = render(1);
+        let tiled = render(3);
+
+        // Test
+        assert!(single.iter().any(|pixel| pixel.alpha > 0.));
+        for (single, tiled) in single.iter().zip(tiled.iter()) {
+            assert_eq!(single, tiled);
+        }
+    }
 }
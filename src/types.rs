@@ -34,17 +34,281 @@
 
 //! Defines Cairus types
 //!
-//! Currently the only types here are for representing color.
+//! Currently the types here are for representing color, and the small enums that select drawing
+//! options (fill rule, line cap and join, antialiasing).
 
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+use color_names::NAMED_COLORS;
 use common_geometry::Point;
 
+// Implements Display, FromStr and `all()` for a fieldless enum, naming each variant the way cairo
+// scripts do.  Parsing ignores case and treats '_' like '-', so "EVEN_ODD" reads as "even-odd".
+//
+// Usage:
+//      impl_names!(LineCap, "line cap", [Butt => "butt", Round => "round", Square => "square"]);
+macro_rules! impl_names {
+    ($kind:ident, $description:expr, [$($variant:ident => $name:expr),+ $(,)*]) => {
+        impl $kind {
+            /// Returns every variant, in declaration order.
+            pub fn all() -> &'static [$kind] {
+                &[$($kind::$variant),+]
+            }
+
+            /// Returns the name used by `Display` and accepted by `FromStr`.
+            pub fn name(&self) -> &'static str {
+                match *self {
+                    $($kind::$variant => $name),+
+                }
+            }
+        }
+
+        impl ::std::fmt::Display for $kind {
+            fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+                f.write_str(self.name())
+            }
+        }
+
+        impl ::std::str::FromStr for $kind {
+            type Err = $crate::types::ParseNameError;
+
+            fn from_str(s: &str) -> Result<$kind, $crate::types::ParseNameError> {
+                let normalized = s.trim().to_lowercase().replace('_', "-");
+                $(
+                    if normalized == $name {
+                        return Ok($kind::$variant);
+                    }
+                )+
+                Err($crate::types::ParseNameError::new($description, s))
+            }
+        }
+    }
+}
+
+/// The error returned when a string doesn't name any variant of one of the option enums.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseNameError {
+    kind: &'static str,
+    value: String,
+}
+
+impl ParseNameError {
+    pub(crate) fn new(kind: &'static str, value: &str) -> ParseNameError {
+        ParseNameError {
+            kind,
+            value: value.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for ParseNameError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "unknown {} \"{}\"", self.kind, self.value)
+    }
+}
+
+impl Error for ParseNameError {}
+
+/// Selects how the inside of a self-intersecting path is decided.  Analogous to
+/// cairo_fill_rule_t.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FillRule {
+    /// A point is inside if the path winds around it a non-zero number of times.
+    Winding,
+    /// A point is inside if a ray from it crosses the path an odd number of times.
+    EvenOdd,
+}
+
+impl_names!(FillRule, "fill rule", [Winding => "winding", EvenOdd => "even-odd"]);
+
+/// Selects how the ends of stroked lines are drawn.  Analogous to cairo_line_cap_t.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LineCap {
+    /// The line stops exactly at its end point.
+    Butt,
+    /// A semicircle centered on the end point.
+    Round,
+    /// A square centered on the end point, extending half the line width past it.
+    Square,
+}
+
+impl_names!(LineCap, "line cap", [Butt => "butt", Round => "round", Square => "square"]);
+
+/// Selects how the corners between stroked segments are drawn.  Analogous to
+/// cairo_line_join_t.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LineJoin {
+    /// A sharp corner, cut off by the miter limit.
+    Miter,
+    /// A circular arc centered on the joint.
+    Round,
+    /// The corner is cut off halfway through the line width.
+    Bevel,
+}
+
+impl_names!(LineJoin, "line join", [Miter => "miter", Round => "round", Bevel => "bevel"]);
+
+/// Selects the kind of antialiasing used when rendering.  Analogous to cairo_antialias_t.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Antialias {
+    /// Whatever the backend does by default.
+    Default,
+    /// Bilevel coverage, no antialiasing.
+    None,
+    /// Single-color antialiasing.
+    Gray,
+    /// Antialiasing that uses the subpixel order of LCD panels.
+    Subpixel,
+    /// Prefer speed over quality.
+    Fast,
+    /// Balance speed and quality.
+    Good,
+    /// Prefer quality over speed.
+    Best,
+}
+
+impl_names!(Antialias, "antialias", [
+    Default => "default",
+    None => "none",
+    Gray => "gray",
+    Subpixel => "subpixel",
+    Fast => "fast",
+    Good => "good",
+    Best => "best",
+]);
+
+/// Selects the order of the red, green and blue subpixels across an LCD panel's pixels, which
+/// `Antialias::Subpixel` renders text for.  Analogous to cairo_subpixel_order_t.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SubpixelOrder {
+    /// Whatever the backend does by default, which is `Rgb`.
+    Default,
+    /// Red on the left, then green, then blue.
+    Rgb,
+    /// Blue on the left, then green, then red.
+    Bgr,
+}
+
+impl_names!(SubpixelOrder, "subpixel order", [Default => "default", Rgb => "rgb", Bgr => "bgr"]);
+
+/// Selects how fills are turned into pixel coverage.  cairo chooses for itself; here it can be
+/// chosen for each fill.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Rasterizer {
+    /// The fill is tessellated into trapezoids, which are sampled.  The default.
+    Trapezoids,
+    /// The fill's edges are scan converted into spans of coverage, row by row, without
+    /// tessellating them.  Faster for paths with many crossings.
+    Spans,
+}
+
+impl_names!(Rasterizer, "rasterizer", [Trapezoids => "trapezoids", Spans => "spans"]);
+
+/// Selects how a surface pattern is sampled between its pixels.  Analogous to cairo_filter_t.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Filter {
+    /// The color of the nearest pixel, which keeps scaled images blocky.
+    Nearest,
+    /// Linear interpolation between the four nearest pixels.
+    Bilinear,
+    /// A reasonable filter for the scale: bilinear, or a box over each device pixel's footprint
+    /// when the image is reduced.  The default, as in cairo.
+    Good,
+    /// The highest quality filter available, the same as Good for now.
+    Best,
+}
+
+impl_names!(Filter, "filter", [
+    Nearest => "nearest",
+    Bilinear => "bilinear",
+    Good => "good",
+    Best => "best",
+]);
+
+/// Selects what a surface pattern shows outside the surface.  Analogous to cairo_extend_t.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Extend {
+    /// Nothing: outside the surface is transparent.  The default, as in cairo.
+    None,
+    /// The surface is tiled.
+    Repeat,
+    /// The surface is tiled, with every other tile mirrored so the tiles meet seamlessly.
+    Reflect,
+    /// The pixels along the surface's edges are stretched outwards.
+    Pad,
+}
+
+impl_names!(Extend, "extend", [
+    None => "none",
+    Repeat => "repeat",
+    Reflect => "reflect",
+    Pad => "pad",
+]);
+
+/// Selects the slant of a font.  Analogous to cairo_font_slant_t.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FontSlant {
+    /// Upright.  The default, as in cairo.
+    Normal,
+    /// A cursive slanted style.
+    Italic,
+    /// The upright style, slanted.
+    Oblique,
+}
+
+impl_names!(FontSlant, "font slant", [
+    Normal => "normal",
+    Italic => "italic",
+    Oblique => "oblique",
+]);
+
+/// Selects the weight of a font.  Analogous to cairo_font_weight_t.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FontWeight {
+    /// The regular weight.  The default, as in cairo.
+    Normal,
+    /// A heavier weight.
+    Bold,
+}
+
+impl_names!(FontWeight, "font weight", [Normal => "normal", Bold => "bold"]);
+
+/// The error returned by `Rgba::try_into_bytes` for a color that doesn't fit in bytes, and when
+/// parsing a color fails.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InvalidColor {
+    /// What is wrong with the color.
+    pub reason: &'static str,
+}
+
+impl fmt::Display for InvalidColor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid color: {}", self.reason)
+    }
+}
+
+impl Error for InvalidColor {}
+
 /// Represents color with red, green, blue, and alpha channels.
+///
+/// This is the one color type in Cairus: patterns, surfaces and the operators all work on it,
+/// and it is exported as `cairus::Rgba`.  The color channels are premultiplied by alpha, which
+/// `Rgba::new` and the other constructors take care of, so only code writing the fields directly
+/// has to premultiply them itself.
+///
+/// The layout is that of `[f32; 4]` in red, green, blue, alpha order, so runs of pixels can be
+/// loaded into vector registers as they are.
 #[derive(Debug, Clone, Copy)]
+#[repr(C)]
 pub struct Rgba {
+    /// The red channel, premultiplied by alpha.
     pub red: f32,
+    /// The green channel, premultiplied by alpha.
     pub green: f32,
+    /// The blue channel, premultiplied by alpha.
     pub blue: f32,
-    // The opacity channel
+    /// The opacity channel, from 0 for transparent to 1 for opaque.
     pub alpha: f32,
 }
 
@@ -62,7 +326,7 @@ impl Rgba {
             red: red * alpha,
             green: green * alpha,
             blue: blue * alpha,
-            alpha: alpha
+            alpha
         }
     }
 
@@ -71,13 +335,206 @@ impl Rgba {
     /// Each channel gets converted from a float to a byte (which can represent numbers up to 255).
     /// They are divided by the alpha value to 'factor out' colors being pre-multiplied (see method
     /// Rgba::new() on pre-multiplied alpha).
-    pub fn into_bytes(&self) -> Vec<u8> {
+    ///
+    /// Channels are rounded to the nearest byte and saturate at 0 and 255, so an out of range
+    /// color still converts to the nearest one that fits.  A color with no alpha, or a NaN
+    /// alpha, converts to all zeroes.  Use `try_into_bytes` to catch such colors instead.
+    ///
+    /// The color is taken by value, as `into_` conversions take theirs.  Rgba is Copy, so a
+    /// borrowed color, such as a pixel of a surface, converts all the same.
+    pub fn into_bytes(self) -> Vec<u8> {
+        if self.alpha.is_nan() || self.alpha <= 0. {
+            return vec![0, 0, 0, 0];
+        }
+
+        // NaN colors clamp to NaN, which casts to 0
+        let byte = |channel: f32| (channel.clamp(0., 1.) * 255.).round() as u8;
         vec![
-             (self.red * 255. / self.alpha) as u8,  (self.green * 255. / self.alpha) as u8,
-             (self.blue * 255. / self.alpha) as u8, (self.alpha * 255.) as u8
+             byte(self.red / self.alpha), byte(self.green / self.alpha),
+             byte(self.blue / self.alpha), byte(self.alpha)
             ]
     }
 
+    /// Returns the bytes `into_bytes` would, or an error if the color has a channel that is NaN
+    /// or outside [0, 1], or a color channel larger than its alpha, which un-premultiplies to
+    /// more than 1.
+    ///
+    /// # Usage
+    /// let bytes = color.try_into_bytes()?;
+    pub fn try_into_bytes(self) -> Result<Vec<u8>, InvalidColor> {
+        let channels = [self.red, self.green, self.blue, self.alpha];
+        if channels.iter().any(|channel| channel.is_nan()) {
+            return Err(InvalidColor { reason: "a channel is NaN" });
+        }
+        if channels.iter().any(|&channel| !(0. ..=1.).contains(&channel)) {
+            return Err(InvalidColor { reason: "a channel is outside [0, 1]" });
+        }
+        if channels[..3].iter().any(|&channel| channel > self.alpha) {
+            return Err(InvalidColor { reason: "a color channel is larger than alpha" });
+        }
+        Ok(self.into_bytes())
+    }
+
+    /// Returns the Rgba that `into_bytes` would turn into `bytes`: red, green, blue and alpha,
+    /// un-premultiplied and scaled to 0-255.
+    ///
+    /// # Usage
+    /// let color = Rgba::from_bytes(&[255, 0, 0, 128]);
+    ///
+    /// # Panics
+    /// If `bytes` holds fewer than four bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Rgba {
+        let channel = |byte: u8| byte as f32 / 255.;
+        Rgba::new(channel(bytes[0]), channel(bytes[1]), channel(bytes[2]), channel(bytes[3]))
+    }
+
+    /// Returns the Rgba for a color given as hue, saturation and lightness, as in CSS's hsl().
+    ///
+    /// `hue` is in degrees and wraps around, so -120 and 240 are the same blue.  `saturation`,
+    /// `lightness` and `alpha` are clamped to [0, 1].
+    ///
+    /// # Usage
+    /// let orange = Rgba::from_hsl(30., 1., 0.5, 1.);
+    pub fn from_hsl(hue: f32, saturation: f32, lightness: f32, alpha: f32) -> Rgba {
+        let (saturation, lightness) = (saturation.clamp(0., 1.), lightness.clamp(0., 1.));
+        let chroma = (1. - (2. * lightness - 1.).abs()) * saturation;
+        Rgba::from_hue(hue, chroma, lightness - chroma / 2., alpha)
+    }
+
+    /// Returns the Rgba for a color given as hue, saturation and value, the model of most color
+    /// pickers.
+    ///
+    /// `hue` is in degrees and wraps around.  `saturation`, `value` and `alpha` are clamped to
+    /// [0, 1].
+    ///
+    /// # Usage
+    /// let dark_green = Rgba::from_hsv(120., 1., 0.5, 1.);
+    pub fn from_hsv(hue: f32, saturation: f32, value: f32, alpha: f32) -> Rgba {
+        let (saturation, value) = (saturation.clamp(0., 1.), value.clamp(0., 1.));
+        let chroma = value * saturation;
+        Rgba::from_hue(hue, chroma, value - chroma, alpha)
+    }
+
+    // Returns the color of `hue` in degrees with `chroma` between its largest and smallest
+    // channels, lifted by `lightest` on every channel, which HSL and HSV share.
+    fn from_hue(hue: f32, chroma: f32, lightest: f32, alpha: f32) -> Rgba {
+        let sector = hue.rem_euclid(360.) / 60.;
+        let middle = chroma * (1. - (sector % 2. - 1.).abs());
+        let (red, green, blue) = match sector as u32 {
+            0 => (chroma, middle, 0.),
+            1 => (middle, chroma, 0.),
+            2 => (0., chroma, middle),
+            3 => (0., middle, chroma),
+            4 => (middle, 0., chroma),
+            _ => (chroma, 0., middle),
+        };
+        Rgba::new(red + lightest, green + lightest, blue + lightest, alpha.clamp(0., 1.))
+    }
+
+    /// Returns the Rgba for a hexadecimal color as CSS writes them: "#rgb", "#rgba", "#rrggbb"
+    /// or "#rrggbbaa", with the leading '#' optional.  Colors without alpha are opaque.
+    ///
+    /// # Usage
+    /// let translucent_red = Rgba::from_hex("#ff000080")?;
+    pub fn from_hex(hex: &str) -> Result<Rgba, InvalidColor> {
+        let digits = hex.strip_prefix('#').unwrap_or(hex);
+        if !digits.bytes().all(|digit| digit.is_ascii_hexdigit()) {
+            return Err(InvalidColor { reason: "a hexadecimal color has a non-hexadecimal digit" });
+        }
+        // One digit a channel, repeated to make a byte, or two
+        let length = match digits.len() {
+            3 | 4 => 1,
+            6 | 8 => 2,
+            _ => return Err(InvalidColor { reason: "wrong number of hexadecimal digits" }),
+        };
+        let mut bytes = [255; 4];
+        for (index, byte) in bytes.iter_mut().enumerate().take(digits.len() / length) {
+            // The digits are all ASCII, so slicing them by bytes is safe
+            let value = u8::from_str_radix(&digits[index * length..(index + 1) * length], 16);
+            *byte = value.unwrap_or(0) * if length == 1 { 17 } else { 1 };
+        }
+        Ok(Rgba::from_bytes(&bytes))
+    }
+
+    /// Returns the Rgba for one of the CSS named colors, such as "cornflowerblue", or
+    /// "transparent" for transparent black.  Names are matched ignoring case.
+    ///
+    /// # Usage
+    /// context.set_source(Pattern::Solid(SolidPattern::new(Rgba::from_name("tomato")?)));
+    pub fn from_name(name: &str) -> Result<Rgba, InvalidColor> {
+        let name = name.to_ascii_lowercase();
+        if name == "transparent" {
+            return Ok(Rgba::new(0., 0., 0., 0.));
+        }
+        match NAMED_COLORS.binary_search_by(|&(named, _)| named.cmp(name.as_str())) {
+            Ok(index) => {
+                let color = NAMED_COLORS[index].1;
+                Ok(Rgba::from_bytes(&[(color >> 16) as u8, (color >> 8) as u8, color as u8, 255]))
+            },
+            Err(_) => Err(InvalidColor { reason: "not a CSS color name" }),
+        }
+    }
+
+    /// Returns the pixels in `bytes`, four un-premultiplied bytes per pixel in the order alpha,
+    /// red, green, blue, as premultiplied Rgba's.  Bytes past the last whole pixel are ignored.
+    ///
+    /// This is the layout of a non-premultiplied ARGB buffer, as image decoders and other
+    /// libraries hand them over.
+    ///
+    /// # Usage
+    /// let pixels = Rgba::premultiply_argb(&buffer);
+    pub fn premultiply_argb(bytes: &[u8]) -> Vec<Rgba> {
+        bytes.chunks_exact(4)
+            .map(|argb| Rgba::from_bytes(&[argb[1], argb[2], argb[3], argb[0]]))
+            .collect()
+    }
+
+    /// Returns `pixels` as four un-premultiplied bytes each, in the order alpha, red, green,
+    /// blue.  The reverse of `premultiply_argb`.
+    ///
+    /// # Usage
+    /// let buffer = Rgba::unpremultiply_argb(surface.iter().as_slice());
+    pub fn unpremultiply_argb(pixels: &[Rgba]) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(pixels.len() * 4);
+        for pixel in pixels {
+            let rgba = pixel.into_bytes();
+            bytes.extend_from_slice(&[rgba[3], rgba[0], rgba[1], rgba[2]]);
+        }
+        bytes
+    }
+
+    /// Returns a vector of 16-bit words representing the Rgba values.
+    ///
+    /// This is the deep-color counterpart of `into_bytes`: each channel is scaled to the range
+    /// 0-65535 instead of 0-255, and colors are un-premultiplied, rounded and saturated the same
+    /// way.  A color with no alpha, or a NaN alpha, converts to all zeroes.
+    pub fn into_words(self) -> Vec<u16> {
+        if self.alpha.is_nan() || self.alpha <= 0. {
+            return vec![0, 0, 0, 0];
+        }
+
+        // NaN colors clamp to NaN, which casts to 0
+        let word = |channel: f32| (channel.clamp(0., 1.) * 65535.).round() as u16;
+        vec![
+             word(self.red / self.alpha), word(self.green / self.alpha),
+             word(self.blue / self.alpha), word(self.alpha)
+            ]
+    }
+
+    /// Returns the color `t` of the way from this Rgba to `other`, with `t` clamped to [0, 1].
+    ///
+    /// Interpolation happens on the pre-multiplied channels, as in cairo's gradients, so fading
+    /// to a transparent color doesn't pick up a dark fringe.
+    pub fn interpolate(&self, other: &Rgba, t: f32) -> Rgba {
+        let t = t.clamp(0., 1.);
+        Rgba {
+            red: self.red + (other.red - self.red) * t,
+            green: self.green + (other.green - self.green) * t,
+            blue: self.blue + (other.blue - self.blue) * t,
+            alpha: self.alpha + (other.alpha - self.alpha) * t,
+        }
+    }
+
     /// Modifies all RGBA values to be between 1.0 and 0.0.
     /// Any value greater than 1.0 resets to 1.0, any value lower than 0.0 resets to 0.0.  This is
     /// not a feature of color theory, but of Cairo (it also corrects bad Rgba values without
@@ -92,14 +549,24 @@ impl Rgba {
             self.alpha = 0.;
         } else {
             // Bound every channel between 0 and 1
-            self.red = self.red.min(1.).max(0.);
-            self.green = self.green.min(1.).max(0.);
-            self.blue = self.blue.min(1.).max(0.);
-            self.alpha = self.alpha.min(1.).max(0.);
+            self.red = self.red.clamp(0., 1.);
+            self.green = self.green.clamp(0., 1.);
+            self.blue = self.blue.clamp(0., 1.);
+            self.alpha = self.alpha.clamp(0., 1.);
         }
     }
 }
 
+/// Parses a color the way CSS writes it: a hexadecimal color starting with '#', as for
+/// `Rgba::from_hex`, or a name, as for `Rgba::from_name`.
+impl FromStr for Rgba {
+    type Err = InvalidColor;
+
+    fn from_str(s: &str) -> Result<Rgba, InvalidColor> {
+        if s.starts_with('#') { Rgba::from_hex(s) } else { Rgba::from_name(s) }
+    }
+}
+
 impl PartialEq for Rgba {
     fn eq(&self, other: &Rgba) -> bool {
         self.red == other.red && self.green == other.green &&
@@ -118,14 +585,16 @@ pub struct Pixel {
 impl Pixel {
     /// Returns a Vec of Points whose coordinates are the points to be sampled for anti-aliasing.
     pub fn sample_points(&self) -> Vec<Point> {
+        // Each point is the center of a cell of a 17x15 grid, so no point lies on the pixel's
+        // border, where it would be counted by the shapes on both sides of it.
         let mut points = Vec::new();
-        let x_increment = 1. / 16.;
-        let y_increment = 1. / 14.;
+        let x_increment = 1. / 17.;
+        let y_increment = 1. / 15.;
         for subgrid_x in 0..17 {
-            let x = self.x as f32 + (subgrid_x as f32 * x_increment);
+            let x = self.x as f32 + ((subgrid_x as f32 + 0.5) * x_increment);
             for subgrid_y in 0..15 {
-                let y =  self.y as f32 + (subgrid_y as f32 * y_increment);
-                let point = Point{x: x, y: y};
+                let y =  self.y as f32 + ((subgrid_y as f32 + 0.5) * y_increment);
+                let point = Point{x, y};
                 points.push(point);
             }
         }
@@ -133,8 +602,14 @@ impl Pixel {
         points
     }
 
+    /// Returns the Point at the center of this pixel, the one point sampled without
+    /// anti-aliasing.
+    pub fn center(&self) -> Point {
+        Point{x: self.x as f32 + 0.5, y: self.y as f32 + 0.5}
+    }
+
     pub fn new(x: i32, y: i32) -> Pixel {
-        Pixel {x: x, y: y, is_edge: true}
+        Pixel {x, y, is_edge: true}
     }
 
     pub fn is_edge(&self) -> bool {
@@ -143,12 +618,61 @@ impl Pixel {
 }
 
 pub trait IntoPixels {
+    #[allow(clippy::wrong_self_convention)]
     fn into_pixels(&self) -> Vec<Pixel>;
 }
 
 #[cfg(test)]
 mod tests {
-    use super::Rgba;
+    use color_names::NAMED_COLORS;
+    use super::{Antialias, Extend, FillRule, Filter, FontSlant, FontWeight, LineCap, LineJoin,
+                ParseNameError, Rgba, SubpixelOrder};
+
+    #[test]
+    fn test_enum_names_round_trip() {
+        for rule in FillRule::all() {
+            assert_eq!(rule.to_string().parse::<FillRule>(), Ok(*rule));
+        }
+        for cap in LineCap::all() {
+            assert_eq!(cap.to_string().parse::<LineCap>(), Ok(*cap));
+        }
+        for join in LineJoin::all() {
+            assert_eq!(join.to_string().parse::<LineJoin>(), Ok(*join));
+        }
+        for antialias in Antialias::all() {
+            assert_eq!(antialias.to_string().parse::<Antialias>(), Ok(*antialias));
+        }
+        assert_eq!(Antialias::all().len(), 7);
+        for order in SubpixelOrder::all() {
+            assert_eq!(order.to_string().parse::<SubpixelOrder>(), Ok(*order));
+        }
+        for filter in Filter::all() {
+            assert_eq!(filter.to_string().parse::<Filter>(), Ok(*filter));
+        }
+        for extend in Extend::all() {
+            assert_eq!(extend.to_string().parse::<Extend>(), Ok(*extend));
+        }
+        for slant in FontSlant::all() {
+            assert_eq!(slant.to_string().parse::<FontSlant>(), Ok(*slant));
+        }
+        for weight in FontWeight::all() {
+            assert_eq!(weight.to_string().parse::<FontWeight>(), Ok(*weight));
+        }
+    }
+
+    #[test]
+    fn test_enum_names_parsing_is_lenient() {
+        assert_eq!("EVEN_ODD".parse::<FillRule>(), Ok(FillRule::EvenOdd));
+        assert_eq!(" Round ".parse::<LineJoin>(), Ok(LineJoin::Round));
+        assert_eq!(FillRule::EvenOdd.to_string(), "even-odd");
+    }
+
+    #[test]
+    fn test_enum_names_unknown() {
+        let error = "pointy".parse::<LineCap>().unwrap_err();
+        assert_eq!(error, ParseNameError::new("line cap", "pointy"));
+        assert_eq!(error.to_string(), "unknown line cap \"pointy\"");
+    }
 
     #[test]
     fn test_rgba_into_bytes_all_ones() {
@@ -157,6 +681,14 @@ mod tests {
         assert_eq!(color.into_bytes(), expected);
     }
 
+    #[test]
+    fn test_rgba_into_bytes_borrowed() {
+        let colors = [Rgba::new(1., 0., 0., 1.), Rgba::new(0., 0., 1., 0.5)];
+        let bytes: Vec<Vec<u8>> = colors.iter().map(|color| color.into_bytes()).collect();
+        assert_eq!(bytes, vec![vec![255, 0, 0, 255], vec![0, 0, 255, 128]]);
+        assert_eq!(colors[0], Rgba::new(1., 0., 0., 1.));
+    }
+
     #[test]
     fn test_rgba_into_bytes_all_zeroes() {
         let color = Rgba::new(0., 0., 0., 0.);
@@ -167,10 +699,153 @@ mod tests {
     #[test]
     fn test_rgba_into_bytes_all_half() {
         let color = Rgba::new(0.5, 0.5, 0.5, 0.5);
-        let expected = vec![127, 127, 127, 127];
+        let expected = vec![128, 128, 128, 128];
         assert_eq!(color.into_bytes(), expected);
     }
 
+    #[test]
+    fn test_rgba_into_bytes_saturates() {
+        // No alpha, even with color, is all zeroes rather than a division by zero
+        let clear = Rgba { red: 0.5, green: 0., blue: 0., alpha: 0. };
+        assert_eq!(clear.into_bytes(), vec![0, 0, 0, 0]);
+        let nan = Rgba { red: 0.5, green: 0., blue: 0., alpha: f32::NAN };
+        assert_eq!(nan.into_bytes(), vec![0, 0, 0, 0]);
+
+        // Channels past 1 saturate rather than wrapping around
+        let bright = Rgba { red: 1.5, green: -1., blue: 0.25, alpha: 2. };
+        assert_eq!(bright.into_bytes(), vec![191, 0, 32, 255]);
+        let over = Rgba { red: 0.75, green: 0., blue: 0., alpha: 0.5 };
+        assert_eq!(over.into_bytes(), vec![255, 0, 0, 128]);
+    }
+
+    #[test]
+    fn test_rgba_try_into_bytes() {
+        assert_eq!(Rgba::new(1., 0.2, 0., 1.).try_into_bytes(), Ok(vec![255, 51, 0, 255]));
+        assert_eq!(Rgba::new(0., 0., 0., 0.).try_into_bytes(), Ok(vec![0, 0, 0, 0]));
+
+        let nan = Rgba { red: f32::NAN, green: 0., blue: 0., alpha: 1. };
+        assert_eq!(nan.try_into_bytes().unwrap_err().reason, "a channel is NaN");
+        let bright = Rgba { red: 1., green: 0., blue: 0., alpha: 1.5 };
+        assert_eq!(bright.try_into_bytes().unwrap_err().reason, "a channel is outside [0, 1]");
+        let over = Rgba { red: 0.75, green: 0., blue: 0., alpha: 0.5 };
+        assert_eq!(over.try_into_bytes().unwrap_err().reason,
+                   "a color channel is larger than alpha");
+    }
+
+    #[test]
+    fn test_rgba_from_bytes() {
+        let color = Rgba::from_bytes(&[255, 51, 0, 255]);
+        assert_eq!(color, Rgba::new(1., 0.2, 0., 1.));
+        assert_eq!(color.into_bytes(), vec![255, 51, 0, 255]);
+        assert_eq!(Rgba::from_bytes(&[255, 255, 255, 0]), Rgba::new(0., 0., 0., 0.));
+    }
+
+    #[test]
+    fn test_rgba_from_hsl() {
+        assert_eq!(Rgba::from_hsl(0., 1., 0.5, 1.), Rgba::new(1., 0., 0., 1.));
+        assert_eq!(Rgba::from_hsl(120., 1., 0.25, 1.), Rgba::new(0., 0.5, 0., 1.));
+        assert_eq!(Rgba::from_hsl(-120., 1., 0.5, 0.5), Rgba::new(0., 0., 1., 0.5));
+        assert_eq!(Rgba::from_hsl(30., 1., 0.5, 1.), Rgba::new(1., 0.5, 0., 1.));
+        // Without saturation the hue doesn't matter
+        assert_eq!(Rgba::from_hsl(200., 0., 0.75, 1.), Rgba::new(0.75, 0.75, 0.75, 1.));
+        assert_eq!(Rgba::from_hsl(300., 2., 1., 1.), Rgba::new(1., 1., 1., 1.));
+    }
+
+    #[test]
+    fn test_rgba_from_hsv() {
+        assert_eq!(Rgba::from_hsv(120., 1., 0.5, 1.), Rgba::new(0., 0.5, 0., 1.));
+        assert_eq!(Rgba::from_hsv(300., 1., 1., 1.), Rgba::new(1., 0., 1., 1.));
+        assert_eq!(Rgba::from_hsv(540., 0.5, 1., 1.), Rgba::new(0.5, 1., 1., 1.));
+        assert_eq!(Rgba::from_hsv(60., 1., 0., 1.), Rgba::new(0., 0., 0., 1.));
+    }
+
+    #[test]
+    fn test_rgba_from_hex() {
+        assert_eq!(Rgba::from_hex("#ff8000"), Ok(Rgba::from_bytes(&[255, 128, 0, 255])));
+        assert_eq!(Rgba::from_hex("FF800080"), Ok(Rgba::from_bytes(&[255, 128, 0, 128])));
+        assert_eq!(Rgba::from_hex("#f80"), Ok(Rgba::from_bytes(&[255, 136, 0, 255])));
+        assert_eq!(Rgba::from_hex("#f808"), Ok(Rgba::from_bytes(&[255, 136, 0, 136])));
+        assert!(Rgba::from_hex("#ff800").is_err());
+        assert!(Rgba::from_hex("#ff800g").is_err());
+        assert!(Rgba::from_hex("#+f8").is_err());
+        assert!(Rgba::from_hex("").is_err());
+    }
+
+    #[test]
+    fn test_rgba_from_name() {
+        assert_eq!(Rgba::from_name("red"), Ok(Rgba::new(1., 0., 0., 1.)));
+        assert_eq!(Rgba::from_name("CornflowerBlue"), Ok(Rgba::from_bytes(&[100, 149, 237, 255])));
+        assert_eq!(Rgba::from_name("transparent"), Ok(Rgba::new(0., 0., 0., 0.)));
+        assert_eq!(Rgba::from_name("grey"), Rgba::from_name("gray"));
+        assert!(Rgba::from_name("blurple").is_err());
+        // Every name is found, which needs the table to be sorted
+        for &(name, _) in NAMED_COLORS {
+            assert!(Rgba::from_name(name).is_ok(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_rgba_from_str() {
+        assert_eq!("#00f".parse::<Rgba>(), Ok(Rgba::new(0., 0., 1., 1.)));
+        assert_eq!("white".parse::<Rgba>(), Ok(Rgba::new(1., 1., 1., 1.)));
+        assert!("00f".parse::<Rgba>().is_err());
+    }
+
+    #[test]
+    fn test_rgba_argb_slices() {
+        // Setup
+        let bytes = [255, 255, 0, 0, 51, 0, 0, 255, 0, 1, 2];
+
+        // Call
+        let pixels = Rgba::premultiply_argb(&bytes);
+
+        // Test
+        // The trailing partial pixel is left out
+        assert_eq!(pixels, vec![Rgba::new(1., 0., 0., 1.), Rgba::new(0., 0., 1., 0.2)]);
+        assert_eq!(pixels[1].blue, 0.2);
+        assert_eq!(Rgba::unpremultiply_argb(&pixels), bytes[..8].to_vec());
+    }
+
+    #[test]
+    fn test_rgba_interpolate() {
+        let from = Rgba::new(1., 0., 0., 1.);
+        let to = Rgba::new(0., 0., 1., 0.5);
+        assert_eq!(from.interpolate(&to, 0.), from);
+        assert_eq!(from.interpolate(&to, 1.), to);
+        assert_eq!(from.interpolate(&to, 0.5), Rgba{red: 0.5, green: 0., blue: 0.25, alpha: 0.75});
+        assert_eq!(from.interpolate(&to, 7.), to);
+    }
+
+    #[test]
+    fn test_rgba_into_words_all_ones() {
+        let color = Rgba::new(1., 1., 1., 1.);
+        let expected = vec![65535, 65535, 65535, 65535];
+        assert_eq!(color.into_words(), expected);
+    }
+
+    #[test]
+    fn test_rgba_into_words_all_zeroes() {
+        let color = Rgba::new(0., 0., 0., 0.);
+        let expected = vec![0, 0, 0, 0];
+        assert_eq!(color.into_words(), expected);
+    }
+
+    #[test]
+    fn test_rgba_into_words_keeps_precision() {
+        // 0.25 can't be represented exactly in 8 bits, but it can in 16 bits.
+        let color = Rgba::new(0.25, 0.5, 0.75, 1.);
+        let expected = vec![16384, 32768, 49151, 65535];
+        assert_eq!(color.into_words(), expected);
+    }
+
+    #[test]
+    fn test_rgba_into_words_saturates() {
+        let nan = Rgba { red: 0.5, green: 0., blue: 0., alpha: f32::NAN };
+        assert_eq!(nan.into_words(), vec![0, 0, 0, 0]);
+        let bright = Rgba { red: 1.5, green: -1., blue: 0.25, alpha: 2. };
+        assert_eq!(bright.into_words(), vec![49151, 0, 8192, 65535]);
+    }
+
     #[test]
     fn test_rgba_corrects_large_values() {
         let mut color = Rgba::new(3., 3., 3., 3.);
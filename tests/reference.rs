@@ -0,0 +1,394 @@
+/*
+ * Cairus - a reimplementation of the cairo graphics library in Rust
+ *
+ * Copyright © 2017 CairusOrg
+ *
+ * This library is free software; you can redistribute it and/or
+ * modify it either under the terms of the GNU Lesser General Public
+ * License version 2.1 as published by the Free Software Foundation
+ * (the "LGPL") or, at your option, under the terms of the Mozilla
+ * Public License Version 2.0 (the "MPL"). If you do not alter this
+ * notice, a recipient may use your version of this file under either
+ * the MPL or the LGPL.
+ *
+ * You should have received a copy of the LGPL along with this library
+ * in the file LICENSE-LGPL-2_1; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Suite 500, Boston, MA 02110-1335, USA
+ * You should have received a copy of the MPL along with this library
+ * in the file LICENSE-MPL-2_0
+ *
+ * The contents of this file are subject to the Mozilla Public License
+ * Version 2.0 (the "License"); you may not use this file except in
+ * compliance with the License. You may obtain a copy of the License at
+ * http://www.mozilla.org/MPL/
+ *
+ * This software is distributed on an "AS IS" basis, WITHOUT WARRANTY
+ * OF ANY KIND, either express or implied. See the LGPL or the MPL for
+ * the specific language governing rights and limitations.
+ *
+ * The Original Code is the cairus graphics library.
+ *
+ * Contributor(s):
+ *	CairusOrg
+ *
+ */
+
+//! Reference tests: renders a suite of scenes and compares each against the image cairo renders
+//! for it, so that regressions anywhere from the tessellator to the operators show up as changed
+//! pixels.
+//!
+//! The references are in tests/reference, rendered by tests/reference/generate.c, which draws
+//! the same scenes with cairo.  A scene changed here has to be changed there too, and its
+//! reference regenerated as that file describes.
+//!
+//! Each scene is written out as a PNG and read back, then compared pixel by pixel with its
+//! reference in premultiplied ARGB.  A scene passes if no channel of any pixel differs by more
+//! than the scene's tolerance, which leaves room for edges antialiased a little differently from
+//! cairo's.  Full circles are filled with their exact coverage, where cairo samples 15 rows per
+//! pixel, so the scenes with one allow for that at the tops and bottoms of the circles.  For a
+//! scene that fails, its output and an image of the differences are left next to each other in
+//! the target directory, and the failure says where they are.
+
+extern crate cairus;
+extern crate image;
+
+use std::f32::consts::PI;
+use std::fs;
+use std::path::{Path, PathBuf};
+use cairus::context::Context;
+use cairus::operators::Operator;
+use cairus::{FillRule, Format, ImageSurface, LineCap, LineJoin, Pattern};
+
+/// A scene, drawn on a `width` by `height` ARGB32 surface by `draw`.
+struct Scene {
+    name: &'static str,
+    width: usize,
+    height: usize,
+    /// The largest difference allowed in any channel of any pixel, out of 255.
+    tolerance: u8,
+    draw: fn(&mut Context),
+}
+
+const SCENES: &[Scene] = &[
+    Scene { name: "fill_rectangles", width: 64, height: 64, tolerance: 8, draw: fill_rectangles },
+    Scene { name: "fill_rule", width: 64, height: 34, tolerance: 16, draw: fill_rule },
+    Scene { name: "stroke_joins", width: 64, height: 64, tolerance: 16, draw: stroke_joins },
+    Scene { name: "arcs_and_curves", width: 64, height: 64, tolerance: 20, draw: arcs_and_curves },
+    Scene { name: "dashes", width: 64, height: 64, tolerance: 4, draw: dashes },
+    Scene { name: "transforms", width: 64, height: 64, tolerance: 12, draw: transforms },
+    Scene { name: "clip", width: 64, height: 64, tolerance: 6, draw: clip },
+    Scene { name: "operators", width: 64, height: 48, tolerance: 2, draw: operators },
+    Scene { name: "group", width: 64, height: 64, tolerance: 10, draw: group },
+];
+
+fn background(context: &mut Context) {
+    context.set_source_rgba(1., 1., 1., 1.);
+    context.paint();
+}
+
+fn rectangle(context: &mut Context, x: f32, y: f32, width: f32, height: f32) {
+    context.move_to(x, y);
+    context.rel_line_to(width, 0.);
+    context.rel_line_to(0., height);
+    context.rel_line_to(-width, 0.);
+    context.close_path();
+}
+
+fn fill_rectangles(context: &mut Context) {
+    background(context);
+    context.set_source_rgba(1., 0., 0., 1.);
+    rectangle(context, 4., 4., 24., 16.);
+    context.fill();
+    context.set_source_rgba(0., 0., 1., 0.5);
+    rectangle(context, 10.5, 12.25, 30., 20.5);
+    context.fill();
+    context.set_source_rgba(0., 0.5, 0., 0.75);
+    rectangle(context, 36.3, 40.7, 22.2, 18.9);
+    context.fill();
+}
+
+fn star(context: &mut Context, xc: f32, yc: f32, radius: f32) {
+    context.move_to(xc, yc - radius);
+    for point in 1..5 {
+        let angle = point as f32 * 4. * PI / 5. - PI / 2.;
+        context.line_to(xc + radius * angle.cos(), yc + radius * angle.sin());
+    }
+    context.close_path();
+}
+
+fn fill_rule(context: &mut Context) {
+    background(context);
+    context.set_source_rgba(0., 0.6, 0., 1.);
+    star(context, 16., 17., 14.);
+    context.fill();
+    context.set_fill_rule(FillRule::EvenOdd);
+    star(context, 48., 17., 14.);
+    context.fill();
+}
+
+fn stroke_joins(context: &mut Context) {
+    background(context);
+    context.set_source_rgba(0., 0., 0., 1.);
+    context.set_line_width(6.);
+    for row in 0..3 {
+        let y = 10. + row as f32 * 20.;
+        context.set_line_join(LineJoin::all()[row]);
+        context.set_line_cap(LineCap::all()[row]);
+        context.move_to(8., y + 8.);
+        context.line_to(24., y - 4.);
+        context.line_to(40., y + 8.);
+        context.line_to(56., y);
+        context.stroke();
+    }
+}
+
+fn arcs_and_curves(context: &mut Context) {
+    background(context);
+    context.set_source_rgba(0.8, 0.2, 0., 1.);
+    context.arc(20., 20., 14., 0., 2. * PI);
+    context.fill();
+    context.set_source_rgba(0., 0., 0.8, 0.8);
+    context.move_to(34., 30.);
+    context.arc_negative(34., 30., 20., 0., -PI / 2.);
+    context.close_path();
+    context.fill();
+    context.set_source_rgba(0., 0., 0., 1.);
+    context.set_line_width(3.);
+    context.move_to(4., 60.);
+    context.curve_to(20., 20., 44., 70., 60., 36.);
+    context.stroke();
+}
+
+fn dashes(context: &mut Context) {
+    background(context);
+    context.set_source_rgba(0., 0., 0., 1.);
+    context.set_line_width(2.);
+    context.set_dash(&[6., 3.], 2.).unwrap();
+    rectangle(context, 8., 8., 48., 48.);
+    context.stroke();
+    context.set_line_width(4.);
+    context.set_line_cap(LineCap::Round);
+    context.move_to(16., 32.);
+    context.line_to(48., 32.);
+    context.stroke();
+}
+
+fn transforms(context: &mut Context) {
+    background(context);
+    context.translate(32., 32.);
+    context.rotate(PI / 6.);
+    context.scale(2., 1.);
+    context.set_source_rgba(0.5, 0., 0.5, 0.8);
+    rectangle(context, -10., -8., 20., 16.);
+    context.fill();
+    context.identity_matrix();
+    context.set_source_rgba(0., 0., 0., 1.);
+    context.set_line_width(1.);
+    context.translate(8., 56.);
+    context.scale(3., 0.5);
+    context.move_to(0., 0.);
+    context.line_to(16., -16.);
+    context.stroke();
+}
+
+fn clip(context: &mut Context) {
+    background(context);
+    context.arc(32., 32., 24., 0., 2. * PI);
+    context.clip();
+    context.set_source_rgba(0., 0.4, 0.8, 1.);
+    for stripe in 0..8 {
+        rectangle(context, stripe as f32 * 8., 0., 4., 64.);
+    }
+    context.fill();
+    context.reset_clip();
+    context.set_source_rgba(1., 0., 0., 1.);
+    rectangle(context, 0., 28., 64., 8.);
+    context.fill();
+}
+
+fn operators(context: &mut Context) {
+    let operators = [
+        Operator::Over, Operator::In, Operator::Out, Operator::Atop, Operator::DestOver,
+        Operator::DestIn, Operator::DestOut, Operator::Xor, Operator::Add, Operator::Multiply,
+        Operator::Screen, Operator::Difference,
+    ];
+    for (cell, &operator) in operators.iter().enumerate() {
+        let (x, y) = ((cell % 4) as f32 * 16., (cell / 4) as f32 * 16.);
+        rectangle(context, x, y, 16., 16.);
+        context.clip();
+        context.set_operator(Operator::Over);
+        context.set_source_rgba(0.9, 0.1, 0.1, 0.7);
+        rectangle(context, x + 2., y + 2., 8., 8.);
+        context.fill();
+        context.set_operator(operator);
+        context.set_source_rgba(0.1, 0.2, 0.9, 0.6);
+        rectangle(context, x + 6., y + 6., 8., 8.);
+        context.fill();
+        context.reset_clip();
+    }
+}
+
+fn group(context: &mut Context) {
+    background(context);
+    context.push_group();
+    context.set_source_rgba(0., 0.5, 0., 1.);
+    rectangle(context, 8., 8., 32., 32.);
+    context.fill();
+    context.set_source_rgba(0., 0., 0.5, 1.);
+    context.arc(40., 40., 16., 0., 2. * PI);
+    context.fill();
+    context.pop_group_to_source();
+    context.mask(&Pattern::rgba(0., 0., 0., 0.5));
+}
+
+/// An image as premultiplied ARGB, one array per pixel, row by row from the top left.
+struct Argb {
+    width: usize,
+    height: usize,
+    pixels: Vec<[u8; 4]>,
+}
+
+impl Argb {
+    /// Reads the PNG at `path`, which holds unpremultiplied RGBA as cairo writes it.
+    fn read(path: &Path) -> Result<Argb, String> {
+        let image = image::open(path)
+            .map_err(|error| format!("{} can't be read: {}", path.display(), error))?
+            .to_rgba();
+        let (width, height) = image.dimensions();
+        let premultiply = |channel: u8, alpha: u8| {
+            ((channel as u32 * alpha as u32 + 127) / 255) as u8
+        };
+        let pixels = image.into_raw().chunks(4)
+            .map(|rgba| [rgba[3], premultiply(rgba[0], rgba[3]), premultiply(rgba[1], rgba[3]),
+                         premultiply(rgba[2], rgba[3])])
+            .collect();
+        Ok(Argb { width: width as usize, height: height as usize, pixels })
+    }
+}
+
+/// How an output differs from its reference.
+#[derive(Debug, PartialEq)]
+struct Comparison {
+    /// The number of pixels with a channel that differs by more than the tolerance.
+    failing: usize,
+    /// The largest difference in each channel, in ARGB order.
+    max_difference: [u8; 4],
+    /// The first failing pixel, as its position and its ARGB in the output and the reference.
+    first_failure: Option<(usize, usize, [u8; 4], [u8; 4])>,
+    /// The difference in each channel of each pixel, in ARGB order.
+    differences: Vec<[u8; 4]>,
+}
+
+/// Compares `output` with `reference`, which must have the same dimensions.
+fn compare(output: &Argb, reference: &Argb, tolerance: u8) -> Comparison {
+    let mut comparison = Comparison {
+        failing: 0,
+        max_difference: [0; 4],
+        first_failure: None,
+        differences: Vec::with_capacity(output.pixels.len()),
+    };
+    for (index, (actual, expected)) in output.pixels.iter().zip(&reference.pixels).enumerate() {
+        let mut difference = [0; 4];
+        for channel in 0..4 {
+            difference[channel] = actual[channel].abs_diff(expected[channel]);
+            comparison.max_difference[channel] =
+                comparison.max_difference[channel].max(difference[channel]);
+        }
+        if difference.iter().any(|&channel| channel > tolerance) {
+            comparison.failing += 1;
+            if comparison.first_failure.is_none() {
+                let (x, y) = (index % output.width, index / output.width);
+                comparison.first_failure = Some((x, y, *actual, *expected));
+            }
+        }
+        comparison.differences.push(difference);
+    }
+    comparison
+}
+
+/// Writes the differences of `comparison` to `path` as an opaque PNG, with the differences in
+/// red, green and blue in those channels and the difference in alpha added to all three, so
+/// identical pixels are black.
+fn write_differences(comparison: &Comparison, width: usize, height: usize, path: &Path) {
+    let mut buffer = Vec::with_capacity(comparison.differences.len() * 4);
+    for difference in &comparison.differences {
+        let [alpha, red, green, blue] = *difference;
+        buffer.extend_from_slice(&[red.saturating_add(alpha), green.saturating_add(alpha),
+                                   blue.saturating_add(alpha), 255]);
+    }
+    image::save_buffer(path, &buffer, width as u32, height as u32, image::RGBA(8))
+        .expect("differences can't be written");
+}
+
+/// Renders `scene`, compares it with its reference, and describes how it fails, if it does.
+fn check(scene: &Scene, output_directory: &Path) -> Result<(), String> {
+    let mut surface = ImageSurface::create_for_format(Format::ARGB32, scene.width, scene.height);
+    {
+        let mut context = Context::create(&mut surface);
+        (scene.draw)(&mut context);
+    }
+    let output_path = output_directory.join(format!("{}.png", scene.name));
+    surface.to_file(&output_path)
+        .map_err(|error| format!("{} can't be written: {}", output_path.display(), error))?;
+
+    let reference_path = reference_directory().join(format!("{}.png", scene.name));
+    let (output, reference) = (Argb::read(&output_path)?, Argb::read(&reference_path)?);
+    if (output.width, output.height) != (reference.width, reference.height) {
+        return Err(format!("is {}x{}, but its reference is {}x{}", output.width, output.height,
+                           reference.width, reference.height));
+    }
+    let comparison = compare(&output, &reference, scene.tolerance);
+    let (x, y, actual, expected) = match comparison.first_failure {
+        Some(failure) => failure,
+        None => return Ok(()),
+    };
+    let difference_path = output_directory.join(format!("{}-diff.png", scene.name));
+    write_differences(&comparison, output.width, output.height, &difference_path);
+    Err(format!("{} pixels differ by more than {}, by up to ARGB {:?}; the first is ({}, {}), \
+                 ARGB {:?} where the reference has {:?}.  See {} and {}",
+                comparison.failing, scene.tolerance, comparison.max_difference, x, y, actual,
+                expected, output_path.display(), difference_path.display()))
+}
+
+fn reference_directory() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("reference")
+}
+
+#[test]
+fn test_scenes_match_references() {
+    let output_directory = Path::new(env!("CARGO_TARGET_TMPDIR")).join("reference");
+    fs::create_dir_all(&output_directory).unwrap();
+
+    let failures: Vec<String> = SCENES.iter()
+        .filter_map(|scene| check(scene, &output_directory).err()
+            .map(|failure| format!("{}: {}", scene.name, failure)))
+        .collect();
+
+    assert!(failures.is_empty(), "scenes differ from their references:\n{}",
+            failures.join("\n"));
+}
+
+#[test]
+fn test_every_scene_has_a_reference() {
+    for scene in SCENES {
+        let path = reference_directory().join(format!("{}.png", scene.name));
+        assert!(path.exists(), "{} has no reference", scene.name);
+    }
+}
+
+#[test]
+fn test_compare() {
+    // Setup
+    let reference = Argb { width: 2, height: 1, pixels: vec![[255, 10, 20, 30], [0, 0, 0, 0]] };
+    let output = Argb { width: 2, height: 1, pixels: vec![[255, 12, 20, 30], [9, 0, 5, 0]] };
+
+    // Call
+    let loose = compare(&output, &reference, 9);
+    let strict = compare(&output, &reference, 2);
+
+    // Test
+    assert_eq!(loose.failing, 0);
+    assert_eq!(loose.max_difference, [9, 2, 5, 0]);
+    assert_eq!(strict.failing, 1);
+    assert_eq!(strict.first_failure, Some((1, 0, [9, 0, 5, 0], [0, 0, 0, 0])));
+}
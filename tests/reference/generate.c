@@ -0,0 +1,284 @@
+/*
+ * Cairus - a reimplementation of the cairo graphics library in Rust
+ *
+ * Copyright © 2017 CairusOrg
+ *
+ * This library is free software; you can redistribute it and/or
+ * modify it either under the terms of the GNU Lesser General Public
+ * License version 2.1 as published by the Free Software Foundation
+ * (the "LGPL") or, at your option, under the terms of the Mozilla
+ * Public License Version 2.0 (the "MPL"). If you do not alter this
+ * notice, a recipient may use your version of this file under either
+ * the MPL or the LGPL.
+ *
+ * You should have received a copy of the LGPL along with this library
+ * in the file LICENSE-LGPL-2_1; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Suite 500, Boston, MA 02110-1335, USA
+ * You should have received a copy of the MPL along with this library
+ * in the file LICENSE-MPL-2_0
+ *
+ * The contents of this file are subject to the Mozilla Public License
+ * Version 2.0 (the "License"); you may not use this file except in
+ * compliance with the License. You may obtain a copy of the License at
+ * http://www.mozilla.org/MPL/
+ *
+ * This software is distributed on an "AS IS" basis, WITHOUT WARRANTY
+ * OF ANY KIND, either express or implied. See the LGPL or the MPL for
+ * the specific language governing rights and limitations.
+ *
+ * The Original Code is the cairus graphics library.
+ *
+ * Contributor(s):
+ *	CairusOrg
+ *
+ */
+
+/*
+ * Renders the scenes of tests/reference.rs with cairo, as the reference images they are
+ * compared against.  Each scene here must draw exactly what its namesake there draws.
+ *
+ * To regenerate the references after changing a scene:
+ *
+ *     cc tests/reference/generate.c $(pkg-config --cflags --libs cairo) -lm -o generate
+ *     ./generate tests/reference
+ *
+ * Only the subset of cairo in include/cairo.h is used, so without cairo's headers the program
+ * can also be built with `-Iinclude` and linked against libcairo.so.2 directly.
+ */
+
+#include <cairo.h>
+#include <math.h>
+#include <stdio.h>
+
+static void
+background (cairo_t *cr)
+{
+    cairo_set_source_rgb (cr, 1, 1, 1);
+    cairo_paint (cr);
+}
+
+static void
+fill_rectangles (cairo_t *cr)
+{
+    background (cr);
+    cairo_set_source_rgb (cr, 1, 0, 0);
+    cairo_rectangle (cr, 4, 4, 24, 16);
+    cairo_fill (cr);
+    cairo_set_source_rgba (cr, 0, 0, 1, 0.5);
+    cairo_rectangle (cr, 10.5, 12.25, 30, 20.5);
+    cairo_fill (cr);
+    cairo_set_source_rgba (cr, 0, 0.5, 0, 0.75);
+    cairo_rectangle (cr, 36.3, 40.7, 22.2, 18.9);
+    cairo_fill (cr);
+}
+
+static void
+star (cairo_t *cr, double xc, double yc, double radius)
+{
+    int point;
+
+    cairo_move_to (cr, xc, yc - radius);
+    for (point = 1; point < 5; point++) {
+        double angle = point * 4 * M_PI / 5 - M_PI / 2;
+        cairo_line_to (cr, xc + radius * cos (angle), yc + radius * sin (angle));
+    }
+    cairo_close_path (cr);
+}
+
+static void
+fill_rule (cairo_t *cr)
+{
+    background (cr);
+    cairo_set_source_rgb (cr, 0, 0.6, 0);
+    star (cr, 16, 17, 14);
+    cairo_fill (cr);
+    cairo_set_fill_rule (cr, CAIRO_FILL_RULE_EVEN_ODD);
+    star (cr, 48, 17, 14);
+    cairo_fill (cr);
+}
+
+static void
+stroke_joins (cairo_t *cr)
+{
+    int row;
+
+    background (cr);
+    cairo_set_source_rgb (cr, 0, 0, 0);
+    cairo_set_line_width (cr, 6);
+    for (row = 0; row < 3; row++) {
+        double y = 10 + row * 20;
+        cairo_set_line_join (cr, (cairo_line_join_t) row);
+        cairo_set_line_cap (cr, (cairo_line_cap_t) row);
+        cairo_move_to (cr, 8, y + 8);
+        cairo_line_to (cr, 24, y - 4);
+        cairo_line_to (cr, 40, y + 8);
+        cairo_line_to (cr, 56, y);
+        cairo_stroke (cr);
+    }
+}
+
+static void
+arcs_and_curves (cairo_t *cr)
+{
+    background (cr);
+    cairo_set_source_rgb (cr, 0.8, 0.2, 0);
+    cairo_arc (cr, 20, 20, 14, 0, 2 * M_PI);
+    cairo_fill (cr);
+    cairo_set_source_rgba (cr, 0, 0, 0.8, 0.8);
+    cairo_move_to (cr, 34, 30);
+    cairo_arc_negative (cr, 34, 30, 20, 0, -M_PI / 2);
+    cairo_close_path (cr);
+    cairo_fill (cr);
+    cairo_set_source_rgb (cr, 0, 0, 0);
+    cairo_set_line_width (cr, 3);
+    cairo_move_to (cr, 4, 60);
+    cairo_curve_to (cr, 20, 20, 44, 70, 60, 36);
+    cairo_stroke (cr);
+}
+
+static void
+dashes (cairo_t *cr)
+{
+    double dash[] = { 6, 3 };
+
+    background (cr);
+    cairo_set_source_rgb (cr, 0, 0, 0);
+    cairo_set_line_width (cr, 2);
+    cairo_set_dash (cr, dash, 2, 2);
+    cairo_rectangle (cr, 8, 8, 48, 48);
+    cairo_stroke (cr);
+    cairo_set_line_width (cr, 4);
+    cairo_set_line_cap (cr, CAIRO_LINE_CAP_ROUND);
+    cairo_move_to (cr, 16, 32);
+    cairo_line_to (cr, 48, 32);
+    cairo_stroke (cr);
+}
+
+static void
+transforms (cairo_t *cr)
+{
+    background (cr);
+    cairo_translate (cr, 32, 32);
+    cairo_rotate (cr, M_PI / 6);
+    cairo_scale (cr, 2, 1);
+    cairo_set_source_rgba (cr, 0.5, 0, 0.5, 0.8);
+    cairo_rectangle (cr, -10, -8, 20, 16);
+    cairo_fill (cr);
+    cairo_identity_matrix (cr);
+    cairo_set_source_rgb (cr, 0, 0, 0);
+    cairo_set_line_width (cr, 1);
+    cairo_translate (cr, 8, 56);
+    cairo_scale (cr, 3, 0.5);
+    cairo_move_to (cr, 0, 0);
+    cairo_line_to (cr, 16, -16);
+    cairo_stroke (cr);
+}
+
+static void
+clip (cairo_t *cr)
+{
+    int stripe;
+
+    background (cr);
+    cairo_arc (cr, 32, 32, 24, 0, 2 * M_PI);
+    cairo_clip (cr);
+    cairo_set_source_rgb (cr, 0, 0.4, 0.8);
+    for (stripe = 0; stripe < 8; stripe++) {
+        cairo_rectangle (cr, stripe * 8, 0, 4, 64);
+    }
+    cairo_fill (cr);
+    cairo_reset_clip (cr);
+    cairo_set_source_rgb (cr, 1, 0, 0);
+    cairo_rectangle (cr, 0, 28, 64, 8);
+    cairo_fill (cr);
+}
+
+static void
+operators (cairo_t *cr)
+{
+    static const cairo_operator_t ops[] = {
+        CAIRO_OPERATOR_OVER, CAIRO_OPERATOR_IN, CAIRO_OPERATOR_OUT, CAIRO_OPERATOR_ATOP,
+        CAIRO_OPERATOR_DEST_OVER, CAIRO_OPERATOR_DEST_IN, CAIRO_OPERATOR_DEST_OUT,
+        CAIRO_OPERATOR_XOR, CAIRO_OPERATOR_ADD, CAIRO_OPERATOR_MULTIPLY,
+        CAIRO_OPERATOR_SCREEN, CAIRO_OPERATOR_DIFFERENCE,
+    };
+    int cell;
+
+    for (cell = 0; cell < 12; cell++) {
+        double x = (cell % 4) * 16, y = (cell / 4) * 16;
+        cairo_rectangle (cr, x, y, 16, 16);
+        cairo_clip (cr);
+        cairo_set_operator (cr, CAIRO_OPERATOR_OVER);
+        cairo_set_source_rgba (cr, 0.9, 0.1, 0.1, 0.7);
+        cairo_rectangle (cr, x + 2, y + 2, 8, 8);
+        cairo_fill (cr);
+        cairo_set_operator (cr, ops[cell]);
+        cairo_set_source_rgba (cr, 0.1, 0.2, 0.9, 0.6);
+        cairo_rectangle (cr, x + 6, y + 6, 8, 8);
+        cairo_fill (cr);
+        cairo_reset_clip (cr);
+    }
+}
+
+static void
+group (cairo_t *cr)
+{
+    background (cr);
+    cairo_push_group (cr);
+    cairo_set_source_rgb (cr, 0, 0.5, 0);
+    cairo_rectangle (cr, 8, 8, 32, 32);
+    cairo_fill (cr);
+    cairo_set_source_rgb (cr, 0, 0, 0.5);
+    cairo_arc (cr, 40, 40, 16, 0, 2 * M_PI);
+    cairo_fill (cr);
+    cairo_pop_group_to_source (cr);
+    cairo_paint_with_alpha (cr, 0.5);
+}
+
+static const struct {
+    const char *name;
+    int width, height;
+    void (*draw) (cairo_t *cr);
+} scenes[] = {
+    { "fill_rectangles", 64, 64, fill_rectangles },
+    { "fill_rule", 64, 34, fill_rule },
+    { "stroke_joins", 64, 64, stroke_joins },
+    { "arcs_and_curves", 64, 64, arcs_and_curves },
+    { "dashes", 64, 64, dashes },
+    { "transforms", 64, 64, transforms },
+    { "clip", 64, 64, clip },
+    { "operators", 64, 48, operators },
+    { "group", 64, 64, group },
+};
+
+int
+main (int argc, char **argv)
+{
+    unsigned int scene;
+
+    if (argc != 2) {
+        fprintf (stderr, "usage: %s directory\n", argv[0]);
+        return 1;
+    }
+    for (scene = 0; scene < sizeof (scenes) / sizeof (scenes[0]); scene++) {
+        char filename[4096];
+        cairo_surface_t *surface = cairo_image_surface_create (CAIRO_FORMAT_ARGB32,
+                                                               scenes[scene].width,
+                                                               scenes[scene].height);
+        cairo_t *cr = cairo_create (surface);
+        cairo_status_t status;
+
+        scenes[scene].draw (cr);
+        status = cairo_status (cr);
+        cairo_destroy (cr);
+        snprintf (filename, sizeof (filename), "%s/%s.png", argv[1], scenes[scene].name);
+        if (status == CAIRO_STATUS_SUCCESS)
+            status = cairo_surface_write_to_png (surface, filename);
+        cairo_surface_destroy (surface);
+        if (status != CAIRO_STATUS_SUCCESS) {
+            fprintf (stderr, "%s: %s\n", scenes[scene].name, cairo_status_to_string (status));
+            return 1;
+        }
+    }
+    return 0;
+}